@@ -0,0 +1,187 @@
+//! tonic gRPC service exposing ND2 metadata and frames from a file server.
+//!
+//! [`Nd2Service`] serves files below a root directory. [`Nd2File`] is not
+//! `Send`, so every call opens its file on a blocking worker thread; frame
+//! streams keep that one reader open for the whole range.
+
+use std::path::{Component, Path, PathBuf};
+
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::{Request, Response, Status};
+
+use nd2::{FileError, InputError, Nd2Error, Nd2File};
+
+/// Generated protobuf types and service traits for `nd2.v1`.
+pub mod proto {
+    tonic::include_proto!("nd2.v1");
+}
+
+pub use proto::nd2_service_server::Nd2ServiceServer;
+use proto::{Frame, FrameRangeRequest, FrameRequest, MetadataRequest, MetadataResponse};
+
+/// Frames buffered per stream before the reader waits on the client.
+const STREAM_BUFFER: usize = 4;
+
+/// Map crate errors onto the closest gRPC status code.
+fn to_status(err: Nd2Error) -> Status {
+    let message = err.to_string();
+    match err {
+        Nd2Error::Input {
+            source: InputError::OutOfRange { .. },
+        } => Status::out_of_range(message),
+        Nd2Error::Input { .. } => Status::invalid_argument(message),
+        Nd2Error::File {
+            source: FileError::Io(e),
+        } if e.kind() == std::io::ErrorKind::NotFound => Status::not_found(message),
+        Nd2Error::Unsupported { .. } => Status::unimplemented(message),
+        _ => Status::failed_precondition(message),
+    }
+}
+
+/// Read-only ND2 service rooted at a directory.
+#[derive(Debug, Clone)]
+pub struct Nd2Service {
+    root: PathBuf,
+}
+
+impl Nd2Service {
+    pub fn new<P: AsRef<Path>>(root: P) -> Self {
+        Self {
+            root: root.as_ref().to_path_buf(),
+        }
+    }
+
+    /// Wrap the service for `tonic::transport::Server::add_service`.
+    pub fn into_server(self) -> Nd2ServiceServer<Self> {
+        Nd2ServiceServer::new(self)
+    }
+
+    /// Resolve a client path below the root, rejecting absolute paths and
+    /// `..` so clients cannot escape it.
+    fn resolve(&self, path: &str) -> Result<PathBuf, Status> {
+        let relative = Path::new(path);
+        if path.is_empty()
+            || relative
+                .components()
+                .any(|c| !matches!(c, Component::Normal(_) | Component::CurDir))
+        {
+            return Err(Status::invalid_argument(format!(
+                "path must be relative to the server root: {path:?}"
+            )));
+        }
+        Ok(self.root.join(relative))
+    }
+}
+
+/// Run `f` on a freshly opened file on the blocking thread pool.
+async fn with_file<T, F>(path: PathBuf, f: F) -> Result<T, Status>
+where
+    T: Send + 'static,
+    F: FnOnce(&mut Nd2File) -> nd2::Result<T> + Send + 'static,
+{
+    tokio::task::spawn_blocking(move || {
+        let mut nd2 = Nd2File::open(&path).map_err(to_status)?;
+        f(&mut nd2).map_err(to_status)
+    })
+    .await
+    .map_err(|e| Status::internal(format!("reader task failed: {e}")))?
+}
+
+fn read_frame(nd2: &mut Nd2File, index: usize) -> nd2::Result<Frame> {
+    let (height, width) = nd2.shape()?;
+    let (pixels, meta) = nd2.read_frame_with_meta(index)?;
+    let channels = pixels.len() / (height * width).max(1);
+    Ok(Frame {
+        index: index as u64,
+        shape: vec![channels as u64, height as u64, width as u64],
+        data: pixels.iter().flat_map(|p| p.to_le_bytes()).collect(),
+        timestamp_ms: meta.timestamp_ms,
+        coords: meta
+            .coords
+            .into_iter()
+            .map(|(axis, i)| (axis, i as u64))
+            .collect(),
+    })
+}
+
+#[tonic::async_trait]
+impl proto::nd2_service_server::Nd2Service for Nd2Service {
+    async fn get_metadata(
+        &self,
+        request: Request<MetadataRequest>,
+    ) -> Result<Response<MetadataResponse>, Status> {
+        let path = self.resolve(&request.get_ref().path)?;
+        let response = with_file(path, |nd2| {
+            let summary = nd2.summary()?;
+            let frame_count = nd2.n_frames()? as u64;
+            let summary_json = serde_json::to_string(&summary).unwrap_or_default();
+            Ok(MetadataResponse {
+                version_major: summary.version_major,
+                version_minor: summary.version_minor,
+                sizes: summary
+                    .sizes
+                    .iter()
+                    .map(|(axis, len)| (axis.clone(), *len as u64))
+                    .collect(),
+                channel_names: summary
+                    .channels
+                    .iter()
+                    .map(|c| c.name.clone().unwrap_or_default())
+                    .collect(),
+                frame_count,
+                summary_json,
+            })
+        })
+        .await?;
+        Ok(Response::new(response))
+    }
+
+    async fn get_frame(&self, request: Request<FrameRequest>) -> Result<Response<Frame>, Status> {
+        let FrameRequest { path, index } = request.into_inner();
+        let path = self.resolve(&path)?;
+        let frame = with_file(path, move |nd2| read_frame(nd2, index as usize)).await?;
+        Ok(Response::new(frame))
+    }
+
+    type StreamFramesStream = ReceiverStream<Result<Frame, Status>>;
+
+    async fn stream_frames(
+        &self,
+        request: Request<FrameRangeRequest>,
+    ) -> Result<Response<Self::StreamFramesStream>, Status> {
+        let FrameRangeRequest { path, start, end } = request.into_inner();
+        let path = self.resolve(&path)?;
+        let (tx, rx) = mpsc::channel(STREAM_BUFFER);
+        tokio::task::spawn_blocking(move || {
+            let mut nd2 = match Nd2File::open(&path) {
+                Ok(nd2) => nd2,
+                Err(e) => {
+                    let _ = tx.blocking_send(Err(to_status(e)));
+                    return;
+                }
+            };
+            let n_frames = match nd2.n_frames() {
+                Ok(n) => n,
+                Err(e) => {
+                    let _ = tx.blocking_send(Err(to_status(e)));
+                    return;
+                }
+            };
+            let end = if end == 0 {
+                n_frames
+            } else {
+                (end as usize).min(n_frames)
+            };
+            for index in start as usize..end {
+                let frame = read_frame(&mut nd2, index).map_err(to_status);
+                let failed = frame.is_err();
+                // Stop when the client has gone away or after the first error.
+                if tx.blocking_send(frame).is_err() || failed {
+                    return;
+                }
+            }
+        });
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
+}
@@ -0,0 +1,24 @@
+//! `nd2-grpc-server <root> [addr]`: serve the ND2 files below `root`.
+
+use std::net::SocketAddr;
+
+use nd2_grpc::Nd2Service;
+
+const DEFAULT_ADDR: &str = "127.0.0.1:50051";
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mut args = std::env::args().skip(1);
+    let Some(root) = args.next() else {
+        eprintln!("usage: nd2-grpc-server <root> [addr]");
+        std::process::exit(2);
+    };
+    let addr: SocketAddr = args.next().as_deref().unwrap_or(DEFAULT_ADDR).parse()?;
+
+    eprintln!("serving {root} on {addr}");
+    tonic::transport::Server::builder()
+        .add_service(Nd2Service::new(root).into_server())
+        .serve(addr)
+        .await?;
+    Ok(())
+}
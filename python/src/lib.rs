@@ -0,0 +1,231 @@
+//! PyO3 bindings exposing [`nd2::Nd2File`] to Python as `nd2_rs.ND2File`.
+
+use std::path::PathBuf;
+
+use numpy::{IntoPyArray, PyArray2, PyArray3, PyArrayDyn, PyArrayMethods};
+use pyo3::exceptions::{PyIOError, PyIndexError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+
+use nd2::{FileError, InputError, NapariColormap, Nd2Error, Nd2File, StackOrder};
+
+/// Map crate errors onto the closest built-in Python exception.
+fn to_py_err(err: Nd2Error) -> PyErr {
+    let message = err.to_string();
+    match err {
+        Nd2Error::Input {
+            source: InputError::OutOfRange { .. },
+        } => PyIndexError::new_err(message),
+        Nd2Error::File {
+            source: FileError::Io(_),
+        } => PyIOError::new_err(message),
+        _ => PyValueError::new_err(message),
+    }
+}
+
+/// Reader for a Nikon ND2 file.
+///
+/// Pixel reads return `numpy.uint16` arrays; metadata is returned as plain
+/// Python dicts and lists.
+#[pyclass(name = "ND2File", module = "nd2_rs", unsendable)]
+struct PyNd2File {
+    inner: Option<Nd2File>,
+    path: PathBuf,
+}
+
+impl PyNd2File {
+    fn file(&mut self) -> PyResult<&mut Nd2File> {
+        self.inner
+            .as_mut()
+            .ok_or_else(|| PyValueError::new_err("I/O operation on closed ND2File"))
+    }
+}
+
+#[pymethods]
+impl PyNd2File {
+    #[new]
+    fn new(path: PathBuf) -> PyResult<Self> {
+        let inner = Nd2File::open(&path).map_err(to_py_err)?;
+        Ok(Self {
+            inner: Some(inner),
+            path,
+        })
+    }
+
+    /// Path the file was opened from.
+    #[getter]
+    fn path(&self) -> PathBuf {
+        self.path.clone()
+    }
+
+    /// File format version as `(major, minor)`.
+    #[getter]
+    fn version(&mut self) -> PyResult<(u32, u32)> {
+        Ok(self.file()?.version())
+    }
+
+    /// Axis lengths keyed by axis name (`P`, `T`, `C`, `Z`, `Y`, `X`).
+    #[getter]
+    fn sizes<'py>(&mut self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let summary = self.file()?.summary().map_err(to_py_err)?;
+        let sizes = PyDict::new_bound(py);
+        for (axis, len) in summary.sizes {
+            sizes.set_item(axis, len)?;
+        }
+        Ok(sizes)
+    }
+
+    /// Whether the file has been closed.
+    #[getter]
+    fn closed(&self) -> bool {
+        self.inner.is_none()
+    }
+
+    /// Dataset summary as a dict.
+    fn metadata<'py>(&mut self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let summary = self.file()?.summary().map_err(to_py_err)?;
+        let meta = PyDict::new_bound(py);
+        meta.set_item("schema_version", summary.schema_version)?;
+        meta.set_item("version", (summary.version_major, summary.version_minor))?;
+        let sizes = PyDict::new_bound(py);
+        for (axis, len) in &summary.sizes {
+            sizes.set_item(axis, len)?;
+        }
+        meta.set_item("sizes", sizes)?;
+        meta.set_item("logical_frame_count", summary.logical_frame_count)?;
+        meta.set_item("pixel_type", summary.pixel_type)?;
+        let channels = summary
+            .channels
+            .iter()
+            .map(|channel| {
+                let entry = PyDict::new_bound(py);
+                entry.set_item("index", channel.index)?;
+                entry.set_item("name", channel.name.clone())?;
+                entry.set_item("color", channel.color.clone())?;
+                entry.set_item("pixel_type", channel.pixel_type.clone())?;
+                Ok(entry)
+            })
+            .collect::<PyResult<Vec<_>>>()?;
+        meta.set_item("channels", channels)?;
+        Ok(meta)
+    }
+
+    /// napari `add_image` kwargs for each channel of `position`, for use as
+    /// `viewer.add_image(f.read_stack(position)[:, c], **layers[c])`.
+    #[pyo3(signature = (position = 0))]
+    fn napari_layers<'py>(
+        &mut self,
+        py: Python<'py>,
+        position: usize,
+    ) -> PyResult<Vec<Bound<'py, PyDict>>> {
+        let layers = self.file()?.napari_layers(position).map_err(to_py_err)?;
+        layers
+            .into_iter()
+            .map(|layer| {
+                let kwargs = PyDict::new_bound(py);
+                kwargs.set_item("name", layer.name)?;
+                kwargs.set_item("scale", layer.scale.to_vec())?;
+                kwargs.set_item("translate", layer.translate.to_vec())?;
+                match layer.colormap {
+                    NapariColormap::Named(name) => kwargs.set_item("colormap", name)?,
+                    NapariColormap::Custom(name, colors) => {
+                        kwargs.set_item("colormap", (name, colors.to_vec()))?
+                    }
+                }
+                kwargs.set_item("contrast_limits", layer.contrast_limits.to_vec())?;
+                kwargs.set_item("blending", layer.blending)?;
+                Ok(kwargs)
+            })
+            .collect()
+    }
+
+    /// Read one frame by sequence index as a `(C, Y, X)` array.
+    fn read_frame<'py>(
+        &mut self,
+        py: Python<'py>,
+        index: usize,
+    ) -> PyResult<Bound<'py, PyArray3<u16>>> {
+        let nd2 = self.file()?;
+        let (height, width) = nd2.shape().map_err(to_py_err)?;
+        let pixels = nd2.read_frame(index).map_err(to_py_err)?;
+        let plane = (height * width).max(1);
+        let channels = pixels.len() / plane;
+        pixels
+            .into_pyarray_bound(py)
+            .reshape([channels, height, width])
+    }
+
+    /// Read the `(Y, X)` plane at position `p`, time `t`, channel `c` and
+    /// Z index `z`.
+    fn read_frame_2d<'py>(
+        &mut self,
+        py: Python<'py>,
+        p: usize,
+        t: usize,
+        c: usize,
+        z: usize,
+    ) -> PyResult<Bound<'py, PyArray2<u16>>> {
+        let nd2 = self.file()?;
+        let (height, width) = nd2.shape().map_err(to_py_err)?;
+        let pixels = nd2.read_frame_2d(p, t, c, z).map_err(to_py_err)?;
+        pixels.into_pyarray_bound(py).reshape([height, width])
+    }
+
+    /// Read every plane of one position as a `(T, C, Z, Y, X)` array.
+    #[pyo3(signature = (position = 0))]
+    fn read_stack<'py>(
+        &mut self,
+        py: Python<'py>,
+        position: usize,
+    ) -> PyResult<Bound<'py, PyArrayDyn<u16>>> {
+        let nd2 = self.file()?;
+        let summary = nd2.summary().map_err(to_py_err)?;
+        let shape: Vec<usize> = ["T", "C", "Z", "Y", "X"]
+            .iter()
+            .map(|axis| summary.sizes.get(*axis).copied().unwrap_or(1))
+            .collect();
+        let pixels = nd2
+            .read_stack(position, StackOrder::Tczyx)
+            .map_err(to_py_err)?;
+        pixels.into_pyarray_bound(py).reshape(shape)
+    }
+
+    /// Release the underlying file handle.
+    fn close(&mut self) {
+        self.inner = None;
+    }
+
+    fn __len__(&mut self) -> PyResult<usize> {
+        self.file()?.n_frames().map_err(to_py_err)
+    }
+
+    fn __enter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __exit__(
+        &mut self,
+        _exc_type: Option<PyObject>,
+        _exc_value: Option<PyObject>,
+        _traceback: Option<PyObject>,
+    ) -> bool {
+        self.close();
+        false
+    }
+
+    fn __repr__(&self) -> String {
+        let state = if self.inner.is_some() {
+            ""
+        } else {
+            " (closed)"
+        };
+        format!("<ND2File {}{}>", self.path.display(), state)
+    }
+}
+
+#[pymodule]
+fn nd2_rs(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyNd2File>()?;
+    m.add("__version__", env!("CARGO_PKG_VERSION"))?;
+    Ok(())
+}
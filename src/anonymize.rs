@@ -0,0 +1,167 @@
+//! Anonymized copies of files, for public deposition.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+
+use crate::chunk::ChunkWriter;
+use crate::constants::ND2_FILE_SIGNATURE;
+use crate::error::{Nd2Error, Result};
+use crate::io::same_file;
+use crate::meta_parse::TextInfoEdit;
+use crate::options::AnonymizePolicy;
+use crate::parse::{ClxEdit, ClxLiteParser, ClxValue, ClxValueRef};
+use crate::reader::{is_clx_chunk, Nd2File};
+
+/// Copy the file at `src` to `dst` without the personal details `policy`
+/// names: text info fields, file paths and GUIDs in the metadata, and
+/// optionally the absolute acquisition time.
+///
+/// Metadata chunks stored as CLX Lite, including the events and ROIs
+/// under `CustomData|`, are rewritten; frames, numeric `CustomData|` arrays
+/// and binary layers are copied byte for byte. A metadata chunk that cannot
+/// be rewritten, such as one with entry types newer than this crate, fails
+/// the copy rather than being left as it was. Chunks no longer listed in the
+/// chunkmap, such as text info left behind by an earlier edit, are not
+/// copied. `dst` is a complete file that opens like `src`.
+pub fn anonymize_to<P: AsRef<Path>, Q: AsRef<Path>>(
+    src: P,
+    dst: Q,
+    policy: &AnonymizePolicy,
+) -> Result<()> {
+    if same_file(src.as_ref(), dst.as_ref()) {
+        return Err(Nd2Error::input_argument(
+            "dst",
+            "anonymized copy would overwrite its source",
+        ));
+    }
+    let mut nd2 = Nd2File::open(src.as_ref())?;
+    if nd2.is_legacy() {
+        let (major, minor) = nd2.version();
+        return Err(Nd2Error::unsupported_version(major, minor));
+    }
+    let parser = ClxLiteParser::new(false).limits(nd2.options().limits);
+    let text_info_name = nd2.text_info_chunk_name();
+    let mut source = File::open(src.as_ref())?;
+    let mut writer = ChunkWriter::new(BufWriter::new(File::create(dst.as_ref())?));
+    writer.copy_chunk(&mut source, ND2_FILE_SIGNATURE, 0)?;
+
+    let mut guids = HashMap::new();
+    for (name, offset, _) in nd2.chunk_entries() {
+        if !is_clx_chunk(&name) {
+            writer.copy_chunk(&mut source, &name, offset)?;
+            continue;
+        }
+        let data = nd2.read_chunk(&name)?;
+        let mut anonymizer = Anonymizer {
+            policy,
+            text_info: (name == text_info_name).then(|| TextInfoEdit::new(&policy.text_info)),
+            guids: &mut guids,
+            changed: false,
+        };
+        let rewritten = parser.rewrite(&data, &mut anonymizer)?;
+        if anonymizer.changed {
+            writer.write_chunk(&name, &rewritten)?;
+        } else {
+            writer.copy_chunk(&mut source, &name, offset)?;
+        }
+    }
+    writer
+        .finish()?
+        .into_inner()
+        .map_err(|err| err.into_error())?;
+    Ok(())
+}
+
+/// Scrubs the entries of one metadata chunk.
+struct Anonymizer<'a> {
+    policy: &'a AnonymizePolicy,
+    /// Text info changes, in the text info chunk.
+    text_info: Option<TextInfoEdit<'a>>,
+    /// Placeholder of each GUID replaced so far in the file, by lowercase
+    /// GUID.
+    guids: &'a mut HashMap<String, String>,
+    changed: bool,
+}
+
+impl Anonymizer<'_> {
+    fn scrub(&mut self, value: &str) -> Option<String> {
+        if self.policy.blank_paths && looks_like_path(value) {
+            return Some(String::new());
+        }
+        if !self.policy.replace_guids {
+            return None;
+        }
+        let (guid, braced) = match value.strip_prefix('{').and_then(|v| v.strip_suffix('}')) {
+            Some(guid) => (guid, true),
+            None => (value, false),
+        };
+        if !is_guid(guid) {
+            return None;
+        }
+        let n = self.guids.len() + 1;
+        let placeholder = self
+            .guids
+            .entry(guid.to_ascii_lowercase())
+            .or_insert_with(|| format!("00000000-0000-0000-0000-{:012x}", n));
+        Some(if braced {
+            format!("{{{}}}", placeholder)
+        } else {
+            placeholder.clone()
+        })
+    }
+}
+
+impl ClxEdit for Anonymizer<'_> {
+    fn replace(&mut self, path: &[String], value: &ClxValueRef<'_>) -> Result<Option<ClxValue>> {
+        if let Some(text_info) = &mut self.text_info {
+            if let Some(new) = text_info.replace(path, value)? {
+                self.changed = true;
+                return Ok(Some(new));
+            }
+        }
+        let shift = self.policy.time_shift_days;
+        let new = match *value {
+            ClxValueRef::String(s) => self.scrub(&s.to_string()).map(ClxValue::String),
+            ClxValueRef::Float(days)
+                if shift != 0.0 && path.last().is_some_and(|name| name == "dTimeAbsolute") =>
+            {
+                Some(ClxValue::Float(days + shift))
+            }
+            _ => None,
+        };
+        self.changed |= new.is_some();
+        Ok(new)
+    }
+
+    fn append(&mut self, path: &[String]) -> Vec<(String, ClxValue)> {
+        let entries = match &mut self.text_info {
+            Some(text_info) => text_info.append(path),
+            None => Vec::new(),
+        };
+        self.changed |= !entries.is_empty();
+        entries
+    }
+}
+
+/// A Windows path with a drive letter, a UNC path or an absolute Unix path
+/// with at least two components.
+fn looks_like_path(value: &str) -> bool {
+    let bytes = value.as_bytes();
+    let drive = bytes.len() > 2
+        && bytes[0].is_ascii_alphabetic()
+        && bytes[1] == b':'
+        && matches!(bytes[2], b'\\' | b'/');
+    let unix = value.starts_with('/') && value[1..].contains('/');
+    drive || unix || value.starts_with("\\\\")
+}
+
+/// `xxxxxxxx-xxxx-xxxx-xxxx-xxxxxxxxxxxx` in hex digits.
+fn is_guid(value: &str) -> bool {
+    value.len() == 36
+        && value.bytes().enumerate().all(|(i, b)| match i {
+            8 | 13 | 18 | 23 => b == b'-',
+            _ => b.is_ascii_hexdigit(),
+        })
+}
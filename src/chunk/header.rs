@@ -13,15 +13,28 @@ pub struct ChunkHeader {
 }
 
 impl ChunkHeader {
+    /// Size of the encoded header in bytes.
+    pub const SIZE: usize = 16;
+
     /// Read chunk header from a reader
     pub fn read<R: Read>(reader: &mut R) -> Result<Self> {
-        let magic = reader.read_u32::<LittleEndian>().map_err(|e| {
+        let mut bytes = [0u8; Self::SIZE];
+        reader.read_exact(&mut bytes).map_err(|e| {
+            Nd2Error::file_invalid_format(format!("Failed to read chunk header: {e}"))
+        })?;
+        Self::parse(&bytes)
+    }
+
+    /// Parse a chunk header from the start of `bytes`.
+    pub fn parse(bytes: &[u8]) -> Result<Self> {
+        let mut cursor = bytes;
+        let magic = cursor.read_u32::<LittleEndian>().map_err(|e| {
             Nd2Error::file_invalid_format(format!("Failed to read chunk magic: {e}"))
         })?;
-        let name_length = reader.read_u32::<LittleEndian>().map_err(|e| {
+        let name_length = cursor.read_u32::<LittleEndian>().map_err(|e| {
             Nd2Error::file_invalid_format(format!("Failed to read chunk name length: {e}"))
         })?;
-        let data_length = reader.read_u64::<LittleEndian>().map_err(|e| {
+        let data_length = cursor.read_u64::<LittleEndian>().map_err(|e| {
             Nd2Error::file_invalid_format(format!("Failed to read chunk data length: {e}"))
         })?;
 
@@ -32,6 +45,15 @@ impl ChunkHeader {
         })
     }
 
+    /// Encode the header as stored.
+    pub fn encode(&self) -> [u8; Self::SIZE] {
+        let mut bytes = [0u8; Self::SIZE];
+        bytes[..4].copy_from_slice(&self.magic.to_le_bytes());
+        bytes[4..8].copy_from_slice(&self.name_length.to_le_bytes());
+        bytes[8..].copy_from_slice(&self.data_length.to_le_bytes());
+        bytes
+    }
+
     /// Validate the magic number
     pub fn validate_magic(&self) -> Result<()> {
         if self.magic != ND2_CHUNK_MAGIC {
@@ -39,4 +61,12 @@ impl ChunkHeader {
         }
         Ok(())
     }
+
+    /// End offset of a chunk with this header starting at `offset`.
+    pub fn end(&self, offset: u64) -> Option<u64> {
+        offset
+            .checked_add(Self::SIZE as u64)?
+            .checked_add(self.name_length as u64)?
+            .checked_add(self.data_length)
+    }
 }
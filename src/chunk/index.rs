@@ -0,0 +1,571 @@
+use std::cell::OnceCell;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::ops::Range;
+
+use crate::checksum::Crc32c;
+use crate::chunk::recover::recover_chunks;
+use crate::chunk::{
+    chunkmap_entries, for_each_chunkmap_entry, read_chunk_into, read_chunkmap_section,
+    read_chunkmap_section_at, ChunkMap,
+};
+use crate::constants::ND2_FILEMAP_SIGNATURE;
+use crate::error::{Nd2Error, Result};
+use crate::types::{Diagnostic, DiagnosticKind};
+
+const IMAGE_CHUNK_PREFIX: &[u8] = b"ImageDataSeq|";
+
+/// Chunkmap sections read at most, the newest included.
+const MAX_CHUNKMAP_SECTIONS: usize = 16;
+
+/// Frame sequence indices below this are checked for duplicates with a
+/// bitmap (2 MiB at most), others with a set.
+const MAX_DENSE_BITS: usize = 1 << 24;
+
+/// The reader's view of the chunkmap.
+///
+/// Only metadata chunks go into a name map when the file is opened. Frame
+/// chunks (`ImageDataSeq|N!`) are counted but left in the raw section, and
+/// their offsets are collected into an array indexed by sequence number the
+/// first time a frame is looked up. On long time-lapses that skips hundreds
+/// of thousands of name allocations for callers that only read metadata.
+///
+/// Files NIS Elements appended to can hold several chunkmap sections, each
+/// newer one listing the previous one as a chunk named
+/// `ND2 FILEMAP SIGNATURE NAME 0001!`. All of them are read. When a name
+/// appears more than once, in one section or across sections, the entry
+/// listed last wins (newer sections after older ones) and a
+/// [`DiagnosticKind::DuplicateChunk`] warning is raised.
+///
+/// Frame entries with no data are placeholders left by aborted
+/// acquisitions. They take part in the duplicate handling like any other
+/// entry; a frame whose winning entry is a placeholder is left out, as if
+/// it had no entry, and reported as a [`DiagnosticKind::PlaceholderChunk`]
+/// warning.
+pub(crate) struct ChunkIndex {
+    chunks: ChunkMap,
+    /// Raw chunkmap sections with the range of their entry data, oldest
+    /// first.
+    sections: Vec<(Vec<u8>, Range<usize>)>,
+    file_size: u64,
+    /// Longest chunk read, see [`Limits::max_chunk_bytes`](crate::Limits).
+    max_chunk_bytes: u64,
+    n_images: usize,
+    images: OnceCell<ImageOffsets>,
+}
+
+struct ImageOffsets {
+    /// (offset, size) by sequence index, for indices below the frame count.
+    dense: Vec<Option<(u64, u64)>>,
+    /// Entries with out-of-range indices, so a bogus `ImageDataSeq|N!` name
+    /// cannot force a huge allocation.
+    sparse: HashMap<usize, (u64, u64)>,
+}
+
+impl ChunkIndex {
+    /// Read and index the chunkmap at the end of the file, and the older
+    /// sections it links to.
+    ///
+    /// Every entry is validated here, so out-of-bounds frame chunks and
+    /// duplicate names are reported in `diagnostics` up front. Sections and
+    /// chunks longer than `max_chunk_bytes` are not read.
+    pub(crate) fn read<R: Read + Seek>(
+        reader: &mut R,
+        max_chunk_bytes: u64,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) -> Result<Self> {
+        let (section, offset, file_size) = read_chunkmap_section(reader, max_chunk_bytes)?;
+        let entries = chunkmap_entries(&section, offset, file_size)?;
+        let mut sections = vec![(section, entries)];
+        let mut offsets = vec![offset];
+        while let Some(previous) = previous_section_offset(&sections[sections.len() - 1], file_size)
+        {
+            if offsets.contains(&previous) {
+                break;
+            }
+            if offsets.len() == MAX_CHUNKMAP_SECTIONS {
+                diagnostics.push(Diagnostic::new(
+                    DiagnosticKind::ChunkmapSectionIgnored,
+                    format!(
+                        "More than {} chunkmap sections; older ones ignored",
+                        MAX_CHUNKMAP_SECTIONS
+                    ),
+                ));
+                break;
+            }
+            let older = read_chunkmap_section_at(reader, previous, file_size, max_chunk_bytes)
+                .and_then(|section| {
+                    let entries = chunkmap_entries(&section, previous, file_size)?;
+                    Ok((section, entries))
+                });
+            match older {
+                Ok(older) => {
+                    sections.push(older);
+                    offsets.push(previous);
+                }
+                Err(err) => {
+                    diagnostics.push(Diagnostic::new(
+                        DiagnosticKind::ChunkmapSectionIgnored,
+                        format!(
+                            "Older chunkmap section at offset {} ignored: {}",
+                            previous, err
+                        ),
+                    ));
+                    break;
+                }
+            }
+        }
+        sections.reverse();
+        Self::index(sections, file_size, max_chunk_bytes, diagnostics)
+    }
+
+    /// Index chunkmap `sections`, oldest first.
+    fn index(
+        sections: Vec<(Vec<u8>, Range<usize>)>,
+        file_size: u64,
+        max_chunk_bytes: u64,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) -> Result<Self> {
+        let mut chunks: ChunkMap = BTreeMap::new();
+        // Frame entries are only counted here; a bitmap of the sequence
+        // indices seen finds duplicates without allocating their names.
+        let mut seen = Vec::<u64>::new();
+        let mut seen_sparse = HashSet::new();
+        let mut n_images = 0;
+        let mut duplicates = Vec::new();
+        // Frames whose latest entry so far is a placeholder.
+        let mut placeholders = BTreeSet::new();
+        for (section, entries) in &sections {
+            let mut warnings = Vec::new();
+            for_each_chunkmap_entry(
+                &section[entries.clone()],
+                file_size,
+                &mut warnings,
+                |name, value| {
+                    if name == ND2_FILEMAP_SIGNATURE {
+                        return;
+                    }
+                    if let Some(index) = image_seq_index(name) {
+                        if is_placeholder(name, value) {
+                            placeholders.insert(index);
+                        } else if !placeholders.is_empty() {
+                            placeholders.remove(&index);
+                        }
+                    }
+                    let duplicate = match image_seq_index(name) {
+                        Some(index) if index < MAX_DENSE_BITS => {
+                            let (word, bit) = (index / 64, 1u64 << (index % 64));
+                            if seen.len() <= word {
+                                seen.resize(word + 1, 0);
+                            }
+                            let duplicate = seen[word] & bit != 0;
+                            seen[word] |= bit;
+                            duplicate
+                        }
+                        Some(index) => !seen_sparse.insert(index),
+                        None => chunks.insert(name.to_vec(), value).is_some(),
+                    };
+                    if duplicate {
+                        duplicates.push(name.to_vec());
+                    } else if image_seq_index(name).is_some() {
+                        n_images += 1;
+                    }
+                },
+            )?;
+            diagnostics.append(&mut warnings);
+        }
+        n_images -= placeholders.len();
+        for name in duplicates {
+            diagnostics.push(Diagnostic::new(
+                DiagnosticKind::DuplicateChunk,
+                format!(
+                    "Chunk '{}' is listed more than once; using the last entry",
+                    String::from_utf8_lossy(&name)
+                ),
+            ));
+        }
+        if let (Some(first), Some(last)) = (placeholders.first(), placeholders.last()) {
+            diagnostics.push(Diagnostic::new(
+                DiagnosticKind::PlaceholderChunk,
+                format!(
+                    "{} frame chunks without data, between sequence indices {} and {}, \
+                     treated as missing",
+                    placeholders.len(),
+                    first,
+                    last
+                ),
+            ));
+        }
+        Ok(Self {
+            chunks,
+            sections,
+            file_size,
+            max_chunk_bytes,
+            n_images,
+            images: OnceCell::new(),
+        })
+    }
+
+    /// Rebuild the index by scanning the source for chunk headers, for a
+    /// file whose chunkmap cannot be read (see
+    /// [`Nd2Options::allow_recovery`](crate::Nd2Options::allow_recovery)).
+    /// Later chunks win over earlier ones with the same name.
+    pub(crate) fn recover<R: Read + Seek>(
+        reader: &mut R,
+        file: Option<&File>,
+        threads: usize,
+        max_chunk_bytes: u64,
+    ) -> Result<Self> {
+        let recovered = recover_chunks(reader, file, threads)?;
+        let file_size = reader.seek(SeekFrom::End(0))?;
+        // Laid out as chunkmap entry data, so frame chunks are indexed
+        // lazily as for a chunkmap read from the file.
+        let mut section = Vec::new();
+        for (name, offset, size) in recovered {
+            section.extend_from_slice(&name);
+            section.extend_from_slice(&offset.to_le_bytes());
+            section.extend_from_slice(&size.to_le_bytes());
+        }
+        let entries = 0..section.len();
+        // Chunks rewritten in place are expected here, not worth a warning.
+        Self::index(
+            vec![(section, entries)],
+            file_size,
+            max_chunk_bytes,
+            &mut Vec::new(),
+        )
+    }
+
+    /// (offset, size) of a chunk by name.
+    pub(crate) fn get(&self, name: &[u8]) -> Option<(u64, u64)> {
+        match image_seq_index(name) {
+            Some(index) => self.image(index),
+            None => self.chunks.get(name).copied(),
+        }
+    }
+
+    /// The stored name of the chunk a caller refers to as `name`: `name`
+    /// itself when listed, otherwise the one chunk whose name is not UTF-8
+    /// and displays as `name` once invalid bytes are replaced (as listed by
+    /// [`Nd2File::chunk_names`](crate::Nd2File::chunk_names)). `None` when
+    /// no chunk, or more than one, matches.
+    pub(crate) fn resolve(&self, name: &[u8]) -> Option<Vec<u8>> {
+        if self.contains(name) {
+            return Some(name.to_vec());
+        }
+        let mut matches = self.chunks.keys().filter(|stored| {
+            std::str::from_utf8(stored).is_err()
+                && String::from_utf8_lossy(stored).as_bytes() == name
+        });
+        match (matches.next(), matches.next()) {
+            (Some(stored), None) => Some(stored.clone()),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn contains(&self, name: &[u8]) -> bool {
+        self.get(name).is_some()
+    }
+
+    /// (offset, size) of the `ImageDataSeq|{index}!` chunk.
+    pub(crate) fn image(&self, index: usize) -> Option<(u64, u64)> {
+        let images = self.images.get_or_init(|| self.index_images());
+        match images.dense.get(index) {
+            Some(slot) => *slot,
+            None => images.sparse.get(&index).copied(),
+        }
+    }
+
+    /// Frame chunks that end inside the file: how many there are, and one
+    /// past the highest sequence index among them.
+    pub(crate) fn frames_present(&self) -> (usize, usize) {
+        let images = self.images.get_or_init(|| self.index_images());
+        let fits = |&(offset, size): &(u64, u64)| {
+            offset
+                .checked_add(size)
+                .is_some_and(|end| end <= self.file_size)
+        };
+        let dense = images
+            .dense
+            .iter()
+            .enumerate()
+            .filter_map(|(index, slot)| slot.filter(fits).map(|_| index));
+        let sparse = images
+            .sparse
+            .iter()
+            .filter(|(_, location)| fits(location))
+            .map(|(&index, _)| index);
+        dense.chain(sparse).fold((0, 0), |(count, end), index| {
+            (count + 1, end.max(index.saturating_add(1)))
+        })
+    }
+
+    /// Names of all indexed chunks, in natural order: digit runs compare by
+    /// value, so `ImageDataSeq|10!` follows `ImageDataSeq|9!`.
+    pub(crate) fn names(&self) -> Vec<Vec<u8>> {
+        let images = self.images.get_or_init(|| self.index_images());
+        let image_indices = images
+            .dense
+            .iter()
+            .enumerate()
+            .filter_map(|(index, slot)| slot.map(|_| index))
+            .chain(images.sparse.keys().copied());
+        let mut names: Vec<Vec<u8>> = self
+            .chunks
+            .keys()
+            .cloned()
+            .chain(image_indices.map(|index| format!("ImageDataSeq|{}!", index).into_bytes()))
+            .collect();
+        names.sort_unstable_by(|a, b| natural_cmp(a, b));
+        names
+    }
+
+    /// Frame chunks by sequence index, in sequence order.
+    pub(crate) fn frame_chunks(&self) -> Vec<(usize, (u64, u64))> {
+        let images = self.images.get_or_init(|| self.index_images());
+        let mut chunks: Vec<_> = images
+            .dense
+            .iter()
+            .enumerate()
+            .filter_map(|(index, slot)| slot.map(|location| (index, location)))
+            .chain(
+                images
+                    .sparse
+                    .iter()
+                    .map(|(&index, &location)| (index, location)),
+            )
+            .collect();
+        chunks.sort_unstable_by_key(|&(index, _)| index);
+        chunks
+    }
+
+    /// Every indexed chunk as (name, offset, size), in file order.
+    pub(crate) fn entries(&self) -> Vec<(Vec<u8>, u64, u64)> {
+        let mut entries: Vec<_> = self
+            .chunks
+            .iter()
+            .map(|(name, &(offset, size))| (name.clone(), offset, size))
+            .chain(
+                self.frame_chunks()
+                    .into_iter()
+                    .map(|(index, (offset, size))| {
+                        (
+                            format!("ImageDataSeq|{}!", index).into_bytes(),
+                            offset,
+                            size,
+                        )
+                    }),
+            )
+            .collect();
+        entries.sort_by_key(|&(_, offset, _)| offset);
+        entries
+    }
+
+    /// CRC-32C of the chunkmap sections indexed, oldest first.
+    pub(crate) fn sections_crc32c(&self) -> u32 {
+        let mut crc = Crc32c::new();
+        for (section, _) in &self.sections {
+            crc.update(section);
+        }
+        crc.finish()
+    }
+
+    /// Size of the indexed file in bytes.
+    pub(crate) fn file_size(&self) -> u64 {
+        self.file_size
+    }
+
+    /// Number of chunkmap entries.
+    pub(crate) fn len(&self) -> usize {
+        self.chunks.len() + self.n_images
+    }
+
+    /// Offset just past the last frame chunk, `0` without frames.
+    #[cfg(feature = "mmap")]
+    pub(crate) fn frames_end(&self) -> u64 {
+        let images = self.images.get_or_init(|| self.index_images());
+        images
+            .dense
+            .iter()
+            .flatten()
+            .chain(images.sparse.values())
+            .map(|&(offset, size)| offset.saturating_add(size))
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Part of the frame cache key: a hash of the file size and the
+    /// chunkmap, which records the offset and size of every chunk and so
+    /// changes whenever the file is rewritten. Files written with the same
+    /// layout share it, so it is combined with the file's identity.
+    #[cfg(feature = "frame-cache")]
+    pub(crate) fn fingerprint(&self) -> u64 {
+        use crate::frame_cache::{fnv1a, FNV_OFFSET};
+        self.sections.iter().fold(
+            fnv1a(FNV_OFFSET, &self.file_size.to_le_bytes()),
+            |hash, (section, _)| fnv1a(hash, section),
+        )
+    }
+
+    /// Read a chunk's data by name.
+    pub(crate) fn read_chunk<R: Read + Seek>(
+        &self,
+        reader: &mut R,
+        name: &[u8],
+    ) -> Result<Vec<u8>> {
+        let mut data = Vec::new();
+        self.read_chunk_into(reader, name, &mut data)?;
+        Ok(data)
+    }
+
+    /// Read a chunk's data by name into `data`, reusing its allocation.
+    pub(crate) fn read_chunk_into<R: Read + Seek>(
+        &self,
+        reader: &mut R,
+        name: &[u8],
+        data: &mut Vec<u8>,
+    ) -> Result<()> {
+        let (offset, map_size) = self
+            .get(name)
+            .ok_or_else(|| Nd2Error::file_chunk_not_found(String::from_utf8_lossy(name)))?;
+        read_chunk_into(reader, name, offset, map_size, self.max_chunk_bytes, data)
+    }
+
+    fn index_images(&self) -> ImageOffsets {
+        let mut images = ImageOffsets {
+            dense: vec![None; self.n_images],
+            sparse: HashMap::new(),
+        };
+        // Already validated (and diagnosed) when the index was built. Later
+        // entries overwrite earlier ones, as for metadata chunks.
+        for (section, entries) in &self.sections {
+            let _ = for_each_chunkmap_entry(
+                &section[entries.clone()],
+                self.file_size,
+                &mut Vec::new(),
+                |name, value| {
+                    if let Some(index) = image_seq_index(name) {
+                        // A placeholder listed last leaves the frame missing.
+                        let value = (!is_placeholder(name, value)).then_some(value);
+                        match (images.dense.get_mut(index), value) {
+                            (Some(slot), value) => *slot = value,
+                            (None, Some(value)) => {
+                                images.sparse.insert(index, value);
+                            }
+                            (None, None) => {
+                                images.sparse.remove(&index);
+                            }
+                        }
+                    }
+                },
+            );
+        }
+        images
+    }
+}
+
+/// Offset of the older chunkmap section a section links to, if any.
+fn previous_section_offset(
+    (section, entries): &(Vec<u8>, Range<usize>),
+    file_size: u64,
+) -> Option<u64> {
+    let mut previous = None;
+    let _ = for_each_chunkmap_entry(
+        &section[entries.clone()],
+        file_size,
+        &mut Vec::new(),
+        |name, (offset, _size)| {
+            if name == ND2_FILEMAP_SIGNATURE {
+                previous = Some(offset);
+            }
+        },
+    );
+    previous
+}
+
+/// Whether the entry `name` at `(offset, size)` is a frame chunk with no
+/// data, as written ahead of frames that an aborted acquisition never
+/// filled in.
+fn is_placeholder(name: &[u8], (_, size): (u64, u64)) -> bool {
+    size == 0 && image_seq_index(name).is_some()
+}
+
+/// Compare names byte by byte, except that runs of ASCII digits compare by
+/// their numeric value.
+fn natural_cmp(mut a: &[u8], mut b: &[u8]) -> Ordering {
+    fn trim(digits: &[u8]) -> &[u8] {
+        let zeros = digits.iter().take_while(|&&c| c == b'0').count();
+        &digits[zeros..]
+    }
+    let digit_run = |s: &[u8]| s.iter().take_while(|c| c.is_ascii_digit()).count();
+    loop {
+        let (a_run, b_run) = (digit_run(a), digit_run(b));
+        if a_run > 0 && b_run > 0 {
+            let (a_digits, b_digits) = (&a[..a_run], &b[..b_run]);
+            let (a_value, b_value) = (trim(a_digits), trim(b_digits));
+            // Without leading zeros, a longer run is a larger number; ties
+            // go to the run with fewer leading zeros.
+            let order = a_value
+                .len()
+                .cmp(&b_value.len())
+                .then_with(|| a_value.cmp(b_value))
+                .then_with(|| a_run.cmp(&b_run));
+            if order != Ordering::Equal {
+                return order;
+            }
+            (a, b) = (&a[a_run..], &b[b_run..]);
+            continue;
+        }
+        match (a.split_first(), b.split_first()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some((x, a_rest)), Some((y, b_rest))) => {
+                if x != y {
+                    return x.cmp(y);
+                }
+                (a, b) = (a_rest, b_rest);
+            }
+        }
+    }
+}
+
+/// Sequence index of a canonical `ImageDataSeq|N!` name.
+pub(crate) fn image_seq_index(name: &[u8]) -> Option<usize> {
+    seq_digits(name.strip_prefix(IMAGE_CHUNK_PREFIX)?.strip_suffix(b"!")?)
+}
+
+/// Name prefixes of the chunks stored once per frame, as `<prefix>N!`.
+const FRAME_CHUNK_PREFIXES: [&[u8]; 4] = [
+    IMAGE_CHUNK_PREFIX,
+    b"ImageMetadataSeqLV|",
+    b"ImageMetadataSeq|",
+    b"CustomDataSeq|",
+];
+
+/// Split the canonical name of a per-frame chunk (`ImageDataSeq|N!`,
+/// `ImageMetadataSeqLV|N!`, `CustomDataSeq|<tag>|N!`, ...) into the part
+/// before its sequence index and the index.
+pub(crate) fn frame_chunk_index(name: &[u8]) -> Option<(&[u8], usize)> {
+    if !FRAME_CHUNK_PREFIXES
+        .iter()
+        .any(|prefix| name.starts_with(prefix))
+    {
+        return None;
+    }
+    let (prefix, rest) = name.split_at(name.iter().rposition(|&b| b == b'|')? + 1);
+    seq_digits(rest.strip_suffix(b"!")?).map(|seq| (prefix, seq))
+}
+
+fn seq_digits(digits: &[u8]) -> Option<usize> {
+    // Names with leading zeros don't round-trip through `{prefix}{N}!`.
+    if digits.is_empty()
+        || !digits.iter().all(u8::is_ascii_digit)
+        || (digits.len() > 1 && digits[0] == b'0')
+    {
+        return None;
+    }
+    std::str::from_utf8(digits).ok()?.parse().ok()
+}
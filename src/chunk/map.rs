@@ -1,43 +1,114 @@
 use byteorder::{LittleEndian, ReadBytesExt};
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 use std::io::{Read, Seek, SeekFrom};
+use std::ops::Range;
 
 use crate::chunk::ChunkHeader;
 use crate::constants::{ND2_CHUNKMAP_SIGNATURE, ND2_FILEMAP_SIGNATURE};
 use crate::error::{Nd2Error, Result};
+use crate::types::{Diagnostic, DiagnosticKind};
 
-/// ChunkMap: mapping of chunk names to (offset, size) pairs
-pub type ChunkMap = HashMap<Vec<u8>, (u64, u64)>;
+/// ChunkMap: mapping of chunk names to (offset, size) pairs, ordered by
+/// name so listings are the same from run to run
+pub type ChunkMap = BTreeMap<Vec<u8>, (u64, u64)>;
 
-/// Read the chunkmap from the end of the file
-pub fn read_chunkmap<R: Read + Seek>(reader: &mut R) -> Result<ChunkMap> {
+/// Length of the file trailer: 32-byte signature + 8-byte chunkmap offset.
+pub const CHUNKMAP_TRAILER_LEN: usize = 40;
+
+/// Read the raw chunkmap section with a single `read_exact`, returning it
+/// with its file offset and the file size. Sections longer than `max_len`
+/// bytes are an error.
+pub(crate) fn read_chunkmap_section<R: Read + Seek>(
+    reader: &mut R,
+    max_len: u64,
+) -> Result<(Vec<u8>, u64, u64)> {
     let file_size = reader.seek(SeekFrom::End(0))?;
 
-    // Read last 40 bytes: 32-byte signature + 8-byte offset
-    reader.seek(SeekFrom::End(-40)).map_err(|e| {
-        Nd2Error::file_invalid_format(format!(
-            "Failed to seek to chunkmap signature (file may be too small): {e}"
-        ))
+    reader
+        .seek(SeekFrom::End(-(CHUNKMAP_TRAILER_LEN as i64)))
+        .map_err(|e| {
+            Nd2Error::file_invalid_format(format!(
+                "Failed to seek to chunkmap signature (file may be too small): {e}"
+            ))
+        })?;
+    let mut trailer = [0u8; CHUNKMAP_TRAILER_LEN];
+    reader.read_exact(&mut trailer).map_err(|e| {
+        Nd2Error::file_invalid_format(format!("Failed to read chunkmap signature: {e}"))
     })?;
+    let chunkmap_offset = parse_chunkmap_trailer(&trailer)?;
+    let section = read_chunkmap_section_at(reader, chunkmap_offset, file_size, max_len)?;
+    Ok((section, chunkmap_offset, file_size))
+}
 
-    let mut signature = [0u8; 32];
-    reader.read_exact(&mut signature).map_err(|e| {
-        Nd2Error::file_invalid_format(format!("Failed to read chunkmap signature: {e}"))
+/// Read the raw chunkmap section at `chunkmap_offset` with a single
+/// `read_exact`, if at most `max_len` bytes long.
+pub(crate) fn read_chunkmap_section_at<R: Read + Seek>(
+    reader: &mut R,
+    chunkmap_offset: u64,
+    file_size: u64,
+    max_len: u64,
+) -> Result<Vec<u8>> {
+    reader.seek(SeekFrom::Start(chunkmap_offset))?;
+    let mut head = [0u8; ChunkHeader::SIZE];
+    reader.read_exact(&mut head).map_err(|e| {
+        Nd2Error::file_invalid_format(format!("Failed to read chunkmap header: {e}"))
     })?;
+    let header = ChunkHeader::parse(&head)?;
+    let section_len = chunkmap_section_len(&header, chunkmap_offset, file_size)?;
+    check_chunk_len(ND2_FILEMAP_SIGNATURE, section_len as u64, max_len)?;
+
+    let mut section = vec![0u8; section_len];
+    section[..ChunkHeader::SIZE].copy_from_slice(&head);
+    reader
+        .read_exact(&mut section[ChunkHeader::SIZE..])
+        .map_err(|e| {
+            Nd2Error::file_invalid_format(format!(
+                "Failed to read {} bytes of chunkmap data: {e}",
+                header.data_length
+            ))
+        })?;
 
-    if &signature != ND2_CHUNKMAP_SIGNATURE {
+    Ok(section)
+}
+
+/// Parse a whole in-memory ND2 file's chunkmap.
+pub fn parse_chunkmap(file: &[u8], diagnostics: &mut Vec<Diagnostic>) -> Result<ChunkMap> {
+    let file_size = file.len() as u64;
+    let trailer_start = file
+        .len()
+        .checked_sub(CHUNKMAP_TRAILER_LEN)
+        .ok_or_else(|| {
+            Nd2Error::file_invalid_format(
+                "Failed to read chunkmap signature (file may be too small)".to_string(),
+            )
+        })?;
+    let chunkmap_offset = parse_chunkmap_trailer(&file[trailer_start..])?;
+    let start = usize::try_from(chunkmap_offset)
+        .ok()
+        .filter(|&start| start <= file.len())
+        .ok_or_else(|| Nd2Error::file_chunkmap("Chunkmap offset exceeds file size"))?;
+    parse_chunkmap_section(&file[start..], chunkmap_offset, file_size, diagnostics)
+}
+
+/// Parse the 40-byte file trailer, returning the chunkmap section offset.
+pub fn parse_chunkmap_trailer(trailer: &[u8]) -> Result<u64> {
+    if trailer.len() < CHUNKMAP_TRAILER_LEN {
+        return Err(Nd2Error::file_invalid_format(format!(
+            "Chunkmap trailer is {} bytes, expected {}",
+            trailer.len(),
+            CHUNKMAP_TRAILER_LEN
+        )));
+    }
+    if &trailer[..32] != ND2_CHUNKMAP_SIGNATURE {
         return Err(Nd2Error::file_invalid_format(
             "Invalid chunkmap signature (expected ND2_CHUNKMAP_SIGNATURE)",
         ));
     }
+    Ok((&trailer[32..40]).read_u64::<LittleEndian>()?)
+}
 
-    let chunkmap_offset = reader.read_u64::<LittleEndian>()?;
-
-    // Seek to chunkmap section
-    reader.seek(SeekFrom::Start(chunkmap_offset))?;
-
-    // Read chunkmap header
-    let header = ChunkHeader::read(reader)?;
+/// Validate a chunkmap section header and return the section's total length.
+fn chunkmap_section_len(header: &ChunkHeader, offset: u64, file_size: u64) -> Result<usize> {
     header.validate_magic()?;
 
     // Read and validate chunkmap name (supports optional zero padding)
@@ -47,11 +118,55 @@ pub fn read_chunkmap<R: Read + Seek>(reader: &mut R) -> Result<ChunkMap> {
             header.name_length
         )));
     }
-    let mut name = vec![0u8; header.name_length as usize];
-    reader
-        .read_exact(&mut name)
-        .map_err(|e| Nd2Error::file_invalid_format(format!("Failed to read chunkmap name: {e}")))?;
+    let section_end = header.end(offset);
+    if !matches!(section_end, Some(end) if end <= file_size) {
+        return Err(Nd2Error::file_chunkmap(format!(
+            "Chunkmap section at offset {} (name {} bytes, data {} bytes) exceeds file size {}",
+            offset, header.name_length, header.data_length, file_size
+        )));
+    }
+    section_end
+        .and_then(|end| usize::try_from(end - offset).ok())
+        .ok_or_else(|| Nd2Error::file_chunkmap("Chunkmap section too large"))
+}
 
+/// Parse a chunkmap section: its 16-byte header, name and entry data.
+///
+/// `section` holds the bytes starting at `offset` in a file of `file_size`
+/// bytes and may extend past the section end.
+pub fn parse_chunkmap_section(
+    section: &[u8],
+    offset: u64,
+    file_size: u64,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> Result<ChunkMap> {
+    let entries = &section[chunkmap_entries(section, offset, file_size)?];
+    let mut chunkmap = BTreeMap::new();
+    for_each_chunkmap_entry(entries, file_size, diagnostics, |name, value| {
+        chunkmap.insert(name.to_vec(), value);
+    })?;
+    Ok(chunkmap)
+}
+
+/// Validate a chunkmap section's header and name, returning the range of
+/// its entry data within `section`.
+pub(crate) fn chunkmap_entries(
+    section: &[u8],
+    offset: u64,
+    file_size: u64,
+) -> Result<Range<usize>> {
+    let header = ChunkHeader::parse(section)?;
+    let section_len = chunkmap_section_len(&header, offset, file_size)?;
+    if section.len() < section_len {
+        return Err(Nd2Error::file_chunkmap(format!(
+            "Chunkmap section needs {} bytes, got {}",
+            section_len,
+            section.len()
+        )));
+    }
+
+    let name_end = ChunkHeader::SIZE + header.name_length as usize;
+    let name = &section[ChunkHeader::SIZE..name_end];
     let is_expected_name = name.starts_with(ND2_FILEMAP_SIGNATURE)
         && name[ND2_FILEMAP_SIGNATURE.len()..]
             .iter()
@@ -61,192 +176,261 @@ pub fn read_chunkmap<R: Read + Seek>(reader: &mut R) -> Result<ChunkMap> {
         return Err(Nd2Error::file_chunkmap("Invalid chunkmap section name"));
     }
 
-    // Read chunkmap entries
-    // Read all chunkmap data into a buffer to avoid EOF issues with BufReader
-    let chunkmap_len: usize = header
-        .data_length
-        .try_into()
-        .map_err(|_| Nd2Error::file_chunkmap("Chunkmap section too large"))?;
-    let mut chunkmap_data = vec![0u8; chunkmap_len];
-    reader.read_exact(&mut chunkmap_data).map_err(|e| {
-        Nd2Error::file_invalid_format(format!(
-            "Failed to read {} bytes of chunkmap data: {e}",
-            header.data_length
-        ))
-    })?;
+    Ok(name_end..section_len)
+}
 
-    // Parse entries from the buffer.
-    let mut chunkmap = HashMap::new();
+/// Walk chunkmap entry data (the section payload after its name), calling
+/// `visit` with each entry's name and (offset, size).
+///
+/// Each entry is a `!`-terminated name followed by a little-endian
+/// (offset, size) pair; names are sliced straight out of `chunkmap_data`.
+pub(crate) fn for_each_chunkmap_entry(
+    chunkmap_data: &[u8],
+    file_size: u64,
+    diagnostics: &mut Vec<Diagnostic>,
+    mut visit: impl FnMut(&[u8], (u64, u64)),
+) -> Result<()> {
     let mut pos = 0usize;
 
-    let read_offset_size = |chunkmap_data: &[u8], value_pos: usize| -> Option<(u64, u64)> {
-        if value_pos + 16 > chunkmap_data.len() {
-            return None;
-        }
-
-        let offset = u64::from_le_bytes([
-            chunkmap_data[value_pos],
-            chunkmap_data[value_pos + 1],
-            chunkmap_data[value_pos + 2],
-            chunkmap_data[value_pos + 3],
-            chunkmap_data[value_pos + 4],
-            chunkmap_data[value_pos + 5],
-            chunkmap_data[value_pos + 6],
-            chunkmap_data[value_pos + 7],
-        ]);
-        let size = u64::from_le_bytes([
-            chunkmap_data[value_pos + 8],
-            chunkmap_data[value_pos + 9],
-            chunkmap_data[value_pos + 10],
-            chunkmap_data[value_pos + 11],
-            chunkmap_data[value_pos + 12],
-            chunkmap_data[value_pos + 13],
-            chunkmap_data[value_pos + 14],
-            chunkmap_data[value_pos + 15],
-        ]);
-
+    let read_offset_size = |value_pos: usize| -> Option<(u64, u64)> {
+        let values = chunkmap_data.get(value_pos..value_pos.checked_add(16)?)?;
+        let offset = u64::from_le_bytes(values[..8].try_into().ok()?);
+        let size = u64::from_le_bytes(values[8..].try_into().ok()?);
         Some((offset, size))
     };
 
     while pos < chunkmap_data.len() {
-        // Read until we hit '!' for chunk name
-        let mut chunk_name = Vec::new();
-        while pos < chunkmap_data.len() {
-            let byte = chunkmap_data[pos];
-            pos += 1;
-            chunk_name.push(byte);
-
-            if chunk_name.ends_with(b"!") {
-                break;
-            }
-
-            // Check for terminator signature
-            if chunk_name.len() >= 32
-                && &chunk_name[chunk_name.len() - 32..] == ND2_CHUNKMAP_SIGNATURE
-            {
-                // We've hit the terminator, done reading
-                return Ok(chunkmap);
-            }
-        }
-
-        // If we didn't get a complete chunk name, break out
-        if !chunk_name.ends_with(b"!") {
+        // Chunk names run up to and including '!'.
+        let Some(name_len) = chunkmap_data[pos..].iter().position(|&b| b == b'!') else {
             break;
-        }
+        };
+        let chunk_name = &chunkmap_data[pos..pos + name_len + 1];
+        pos += name_len + 1;
 
         // End marker is a special terminator chunk entry.
         if chunk_name == ND2_CHUNKMAP_SIGNATURE {
             break;
         }
 
-        let mut value_pos = pos;
-        let mut found_entry = false;
-        let mut best_score = -1i32;
-
         // Prefer the candidate with a valid file-bound check when available.
         // Older ND2 files (and some edge cases) may encode this field using offset+1 alignment.
+        let mut best: Option<(i32, usize, (u64, u64))> = None;
         for candidate in 0..=1 {
-            let value = match read_offset_size(&chunkmap_data, pos + candidate) {
-                Some(v) => v,
-                None => continue,
+            let Some((offset, size)) = read_offset_size(pos + candidate) else {
+                continue;
             };
-
-            let (offset, size) = value;
-            let mut score = 0;
-            if offset <= file_size {
-                score = 1;
-                if let Some(end) = offset.checked_add(size) {
-                    if end <= file_size {
-                        score = 2;
-                    }
-                }
-            }
-
-            if score > best_score {
-                best_score = score;
-                chunkmap.insert(chunk_name.clone(), value);
-                value_pos = pos + candidate;
-                found_entry = true;
+            let score = match offset.checked_add(size) {
+                _ if offset > file_size => 0,
+                Some(end) if end <= file_size => 2,
+                _ => 1,
+            };
+            if best.map_or(true, |(best_score, _, _)| score > best_score) {
+                best = Some((score, pos + candidate, (offset, size)));
                 if score == 2 {
-                    // Best possible score, keep scanning for a better candidate is unnecessary.
+                    // Nothing can beat an entry that fits in the file.
                     break;
                 }
             }
         }
 
-        if !found_entry {
+        let Some((score, value_pos, (offset, size))) = best else {
             return Err(Nd2Error::file_chunkmap(
                 "Invalid chunkmap entry offset/size values",
             ));
+        };
+
+        if score < 2 {
+            diagnostics.push(Diagnostic::new(
+                DiagnosticKind::SuspiciousChunkSize,
+                format!(
+                    "Chunk '{}' at offset {} with size {} extends past end of file ({} bytes)",
+                    String::from_utf8_lossy(chunk_name),
+                    offset,
+                    size,
+                    file_size
+                ),
+            ));
         }
+        visit(chunk_name, (offset, size));
 
         pos = value_pos + 16;
     }
 
-    Ok(chunkmap)
+    Ok(())
 }
 
-/// Read a chunk's data given the chunkmap
-pub fn read_chunk<R: Read + Seek>(
+/// Read a chunk's data from its chunkmap `offset` and `map_size` into
+/// `data`, reusing its allocation.
+pub(crate) fn read_chunk_into<R: Read + Seek>(
     reader: &mut R,
-    chunkmap: &ChunkMap,
     name: &[u8],
-) -> Result<Vec<u8>> {
+    offset: u64,
+    map_size: u64,
+    max_len: u64,
+    data: &mut Vec<u8>,
+) -> Result<()> {
+    let (data_offset, size) = read_chunk_span(reader, name, offset, map_size, max_len)?;
+    read_chunk_data(reader, name, data_offset, size, data)
+}
+
+/// Read and validate the header of the chunk at `offset`, returning where
+/// its data starts and how long it is. Data longer than `max_len` bytes is
+/// an error.
+pub(crate) fn read_chunk_span<R: Read + Seek>(
+    reader: &mut R,
+    name: &[u8],
+    offset: u64,
+    map_size: u64,
+    max_len: u64,
+) -> Result<(u64, usize)> {
     let file_size = reader.seek(SeekFrom::End(0))?;
+    check_chunk_offset(name, offset, file_size)?;
+    reader.seek(SeekFrom::Start(offset))?;
+    let header = ChunkHeader::read(reader)?;
+    chunk_data_span(&header, name, offset, map_size, file_size, max_len)
+}
+
+/// Read `size` bytes of chunk data at `data_offset` (as returned by
+/// [`read_chunk_span`]) into `data`, reusing its allocation.
+pub(crate) fn read_chunk_data<R: Read + Seek>(
+    reader: &mut R,
+    name: &[u8],
+    data_offset: u64,
+    size: usize,
+    data: &mut Vec<u8>,
+) -> Result<()> {
+    reader.seek(SeekFrom::Start(data_offset))?;
+    data.clear();
+    data.resize(size, 0);
+    reader.read_exact(data).map_err(|e| {
+        Nd2Error::file_invalid_format(format!(
+            "Failed to read chunk data for '{}': {}",
+            String::from_utf8_lossy(name),
+            e
+        ))
+    })
+}
 
-    let (offset, map_size) = chunkmap
+/// Borrow a chunk's data from a whole in-memory ND2 file.
+pub fn chunk_data<'a>(file: &'a [u8], chunkmap: &ChunkMap, name: &[u8]) -> Result<&'a [u8]> {
+    let (offset, map_size) = lookup(chunkmap, name)?;
+    chunk_data_at(file, name, offset, map_size)
+}
+
+/// Borrow the data of chunk `name`, listed at `offset` with `map_size`
+/// bytes, from a whole in-memory ND2 file.
+pub(crate) fn chunk_data_at<'a>(
+    file: &'a [u8],
+    name: &[u8],
+    offset: u64,
+    map_size: u64,
+) -> Result<&'a [u8]> {
+    check_chunk_offset(name, offset, file.len() as u64)?;
+    let chunk = usize::try_from(offset)
+        .ok()
+        .and_then(|start| file.get(start..))
+        .ok_or_else(|| invalid_bounds(name, offset, map_size))?;
+    let header = ChunkHeader::parse(chunk)?;
+    let size = chunk_data_len(&header, offset, map_size, file.len() as u64, name)?;
+    let start = ChunkHeader::SIZE + header.name_length as usize;
+    Ok(&chunk[start..start + size])
+}
+
+fn lookup(chunkmap: &ChunkMap, name: &[u8]) -> Result<(u64, u64)> {
+    chunkmap
         .get(name)
-        .ok_or_else(|| Nd2Error::file_chunk_not_found(String::from_utf8_lossy(name)))?;
+        .copied()
+        .ok_or_else(|| Nd2Error::file_chunk_not_found(String::from_utf8_lossy(name)))
+}
 
-    // Seek to chunk data (skip header + name)
-    reader.seek(SeekFrom::Start(*offset))?;
+fn invalid_bounds(name: &[u8], offset: u64, map_size: u64) -> Nd2Error {
+    Nd2Error::file_invalid_format(format!(
+        "Invalid chunk bounds for '{}': offset {} size {}",
+        String::from_utf8_lossy(name),
+        offset,
+        map_size
+    ))
+}
 
-    let header = ChunkHeader::read(reader)?;
-    header.validate_magic()?;
+/// Fail unless a chunk header fits between the chunkmap entry's `offset`
+/// and the end of the file, so a corrupt entry is reported as such rather
+/// than as an I/O error once its header is read.
+pub(crate) fn check_chunk_offset(name: &[u8], offset: u64, file_size: u64) -> Result<()> {
+    let fits = offset
+        .checked_add(ChunkHeader::SIZE as u64)
+        .is_some_and(|header_end| header_end <= file_size);
+    if !fits {
+        return Err(Nd2Error::file_invalid_format(format!(
+            "Chunkmap entry for '{}' points past EOF at offset {} (file is {} bytes)",
+            String::from_utf8_lossy(name),
+            offset,
+            file_size
+        )));
+    }
+    Ok(())
+}
 
-    // Skip chunk name
-    reader.seek(SeekFrom::Current(header.name_length as i64))?;
+/// Validate a chunk header read at `offset` and return the offset and
+/// length of its data, which may be at most `max_len` bytes.
+pub(crate) fn chunk_data_span(
+    header: &ChunkHeader,
+    name: &[u8],
+    offset: u64,
+    map_size: u64,
+    file_size: u64,
+    max_len: u64,
+) -> Result<(u64, usize)> {
+    let size = chunk_data_len(header, offset, map_size, file_size, name)?;
+    check_chunk_len(name, size as u64, max_len)?;
+    // The whole chunk was checked to end inside the file.
+    let data_offset = offset + ChunkHeader::SIZE as u64 + header.name_length as u64;
+    Ok((data_offset, size))
+}
 
-    let size = header.data_length;
-    let chunk_end = (*offset)
-        .checked_add(16)
-        .and_then(|v| v.checked_add(header.name_length as u64))
-        .and_then(|v| v.checked_add(size))
-        .ok_or_else(|| {
-            Nd2Error::file_invalid_format(format!(
-                "Invalid chunk bounds for '{}': offset {} size {}",
-                String::from_utf8_lossy(name),
-                offset,
-                map_size
-            ))
-        })?;
+/// Fail when the `len` bytes of chunk `name` go past `max_len`
+/// ([`Limits::max_chunk_bytes`](crate::Limits::max_chunk_bytes)).
+pub(crate) fn check_chunk_len(name: &[u8], len: u64, max_len: u64) -> Result<()> {
+    if len > max_len {
+        return Err(Nd2Error::file_limit_exceeded(format!(
+            "Chunk '{}' is {} bytes, more than the {} byte limit",
+            String::from_utf8_lossy(name),
+            len,
+            max_len
+        )));
+    }
+    Ok(())
+}
 
+/// Validate a chunk header read at `offset` and return its data length.
+fn chunk_data_len(
+    header: &ChunkHeader,
+    offset: u64,
+    map_size: u64,
+    file_size: u64,
+    name: &[u8],
+) -> Result<usize> {
+    header.validate_magic()?;
+
+    let chunk_end = header
+        .end(offset)
+        .ok_or_else(|| invalid_bounds(name, offset, map_size))?;
     if chunk_end > file_size {
         return Err(Nd2Error::file_invalid_format(format!(
-            "Invalid chunk bounds for '{}': offset {} size {}",
+            "Chunk '{}' at offset {} runs past EOF: its {} data bytes end at {} (file is {} \
+             bytes)",
             String::from_utf8_lossy(name),
             offset,
-            map_size
+            header.data_length,
+            chunk_end,
+            file_size
         )));
     }
 
-    let size: usize = size.try_into().map_err(|_| {
+    header.data_length.try_into().map_err(|_| {
         Nd2Error::file_invalid_format(format!(
             "Chunk size {} for '{}' too large for this platform",
-            size,
+            header.data_length,
             String::from_utf8_lossy(name)
         ))
-    })?;
-
-    // Read chunk data
-    let mut data = vec![0u8; size];
-    reader.read_exact(&mut data).map_err(|e| {
-        Nd2Error::file_invalid_format(format!(
-            "Failed to read chunk data for '{}': {}",
-            String::from_utf8_lossy(name),
-            e
-        ))
-    })?;
-
-    Ok(data)
+    })
 }
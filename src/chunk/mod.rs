@@ -1,5 +1,10 @@
 pub mod header;
+mod index;
 pub mod map;
+mod recover;
+mod write;
 
 pub use header::*;
+pub(crate) use index::{frame_chunk_index, image_seq_index, ChunkIndex};
 pub use map::*;
+pub(crate) use write::{encode_chunk, encode_chunkmap, ChunkWriter};
@@ -0,0 +1,154 @@
+//! Chunkmap recovery: rebuilding the chunk list of a file whose chunkmap is
+//! missing or corrupt by scanning it for chunk headers.
+
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::ops::Range;
+
+use crate::chunk::ChunkHeader;
+use crate::constants::{
+    ND2_CHUNKMAP_SIGNATURE, ND2_CHUNK_MAGIC, ND2_FILEMAP_SIGNATURE, ND2_FILE_SIGNATURE,
+};
+use crate::error::Result;
+use crate::io::PositionalReader;
+
+/// Bytes scanned per block.
+const SCAN_BLOCK: u64 = 8 * 1024 * 1024;
+
+/// Longest name region read from a candidate chunk header; chunk names are
+/// short, and the region is padded to the chunk alignment at most.
+const MAX_NAME_LEN: u32 = 64 * 1024;
+
+/// A recovered chunk: name (up to and including `!`), offset, data length.
+pub(crate) type RecoveredChunk = (Vec<u8>, u64, u64);
+
+/// Scan the whole source for chunk headers and return the chunks they
+/// start, in file order.
+///
+/// The file is read in blocks, spread over `threads` threads when `file`
+/// allows positional reads. Every offset holding [`ND2_CHUNK_MAGIC`] is a
+/// candidate; blocks overlap by three bytes so a magic number straddling
+/// two blocks is found by the block it starts in. Candidates are then
+/// checked in file order: a chunk is kept when its header is followed by a
+/// `!`-terminated name and its data ends inside the file, and candidates
+/// inside a kept chunk (magic bytes in pixel data) are skipped. The file
+/// signature and chunkmap sections are left out.
+pub(crate) fn recover_chunks<R: Read + Seek>(
+    reader: &mut R,
+    file: Option<&File>,
+    threads: usize,
+) -> Result<Vec<RecoveredChunk>> {
+    let file_size = reader.seek(SeekFrom::End(0))?;
+    let n_blocks = (file_size + SCAN_BLOCK - 1) / SCAN_BLOCK;
+    let threads = (threads as u64).clamp(1, n_blocks.max(1)) as usize;
+    let mut candidates = match file {
+        Some(file) if threads > 1 => std::thread::scope(|scope| {
+            let workers: Vec<_> = (0..threads)
+                .map(|worker| {
+                    scope.spawn(move || {
+                        let blocks = (worker as u64..n_blocks).step_by(threads);
+                        scan_blocks(&mut PositionalReader::new(file), blocks, file_size)
+                    })
+                })
+                .collect();
+            workers
+                .into_iter()
+                .map(|worker| {
+                    worker
+                        .join()
+                        .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+                })
+                .collect::<Result<Vec<_>>>()
+                .map(|found| found.concat())
+        })?,
+        _ => scan_blocks(reader, 0..n_blocks, file_size)?,
+    };
+    candidates.sort_unstable();
+
+    let mut chunks = Vec::new();
+    let mut kept_end = 0u64;
+    for offset in candidates {
+        if offset < kept_end {
+            continue;
+        }
+        let Some((name, header)) = read_candidate(reader, offset, file_size)? else {
+            continue;
+        };
+        let Some(end) = header.end(offset).filter(|&end| end <= file_size) else {
+            continue;
+        };
+        kept_end = end;
+        if name == ND2_FILE_SIGNATURE
+            || name == ND2_FILEMAP_SIGNATURE
+            || name == ND2_CHUNKMAP_SIGNATURE
+        {
+            continue;
+        }
+        chunks.push((name, offset, header.data_length));
+    }
+    Ok(chunks)
+}
+
+/// Offsets of [`ND2_CHUNK_MAGIC`] starting inside the given blocks.
+fn scan_blocks<R: Read + Seek>(
+    reader: &mut R,
+    blocks: impl Iterator<Item = u64>,
+    file_size: u64,
+) -> Result<Vec<u64>> {
+    let magic = ND2_CHUNK_MAGIC.to_le_bytes();
+    let mut found = Vec::new();
+    let mut buf = Vec::new();
+    for block in blocks {
+        let Range { start, end } = block * SCAN_BLOCK..((block + 1) * SCAN_BLOCK).min(file_size);
+        // Read on into the next block, for a magic number starting at the
+        // last bytes of this one.
+        let read_end = end.saturating_add(magic.len() as u64 - 1).min(file_size);
+        buf.clear();
+        buf.resize((read_end - start) as usize, 0);
+        reader.seek(SeekFrom::Start(start))?;
+        reader.read_exact(&mut buf)?;
+        found.extend(
+            buf.windows(magic.len())
+                .enumerate()
+                .filter(|(_, window)| *window == magic)
+                .map(|(i, _)| start + i as u64),
+        );
+    }
+    Ok(found)
+}
+
+/// The header and name of a chunk candidate at `offset`, `None` when they
+/// don't look like a chunk's.
+fn read_candidate<R: Read + Seek>(
+    reader: &mut R,
+    offset: u64,
+    file_size: u64,
+) -> Result<Option<(Vec<u8>, ChunkHeader)>> {
+    if offset.saturating_add(ChunkHeader::SIZE as u64) > file_size {
+        return Ok(None);
+    }
+    reader.seek(SeekFrom::Start(offset))?;
+    let header = ChunkHeader::read(reader)?;
+    if header.name_length == 0 || header.name_length > MAX_NAME_LEN {
+        return Ok(None);
+    }
+    let name_end = offset
+        .saturating_add(ChunkHeader::SIZE as u64)
+        .saturating_add(header.name_length as u64);
+    if name_end > file_size {
+        return Ok(None);
+    }
+    let mut name = vec![0u8; header.name_length as usize];
+    reader.read_exact(&mut name)?;
+    // Names end at `!`, followed by padding up to the data.
+    let Some(len) = name.iter().position(|&b| b == b'!') else {
+        return Ok(None);
+    };
+    name.truncate(len + 1);
+    // Names are printable; bytes past ASCII are kept, for names written in
+    // other encodings.
+    if name.iter().any(|b| b.is_ascii_control()) {
+        return Ok(None);
+    }
+    Ok(Some((name, header)))
+}
@@ -0,0 +1,113 @@
+//! Encoding chunks and chunkmap sections, for writing files.
+
+use std::io::{Read, Seek, SeekFrom, Write};
+
+use crate::chunk::ChunkHeader;
+use crate::constants::{
+    ND2_CHUNKMAP_SIGNATURE, ND2_CHUNK_MAGIC, ND2_FILEMAP_SIGNATURE, ND2_FILE_SIGNATURE,
+};
+use crate::error::{Nd2Error, Result};
+
+/// A chunk named `name` holding `data`, header included.
+pub(crate) fn encode_chunk(name: &[u8], data: &[u8]) -> Vec<u8> {
+    let header = ChunkHeader {
+        magic: ND2_CHUNK_MAGIC,
+        name_length: name.len() as u32,
+        data_length: data.len() as u64,
+    };
+    let mut chunk = Vec::with_capacity(ChunkHeader::SIZE + name.len() + data.len());
+    chunk.extend_from_slice(&header.encode());
+    chunk.extend_from_slice(name);
+    chunk.extend_from_slice(data);
+    chunk
+}
+
+/// A chunkmap section to be written at `offset`, listing `entries` as
+/// (name, offset, size). It ends with the trailer pointing back at it, so
+/// it has to be the last thing in the file.
+pub(crate) fn encode_chunkmap(entries: &[(Vec<u8>, u64, u64)], offset: u64) -> Vec<u8> {
+    let mut map = Vec::new();
+    for (name, chunk_offset, size) in entries {
+        map.extend_from_slice(name);
+        map.extend_from_slice(&chunk_offset.to_le_bytes());
+        map.extend_from_slice(&size.to_le_bytes());
+    }
+    map.extend_from_slice(ND2_CHUNKMAP_SIGNATURE);
+    map.extend_from_slice(&offset.to_le_bytes());
+    encode_chunk(ND2_FILEMAP_SIGNATURE, &map)
+}
+
+/// Writes a file chunk by chunk, listing each in the chunkmap written by
+/// [`ChunkWriter::finish`].
+pub(crate) struct ChunkWriter<W: Write> {
+    out: W,
+    position: u64,
+    entries: Vec<(Vec<u8>, u64, u64)>,
+}
+
+impl<W: Write> ChunkWriter<W> {
+    pub(crate) fn new(out: W) -> Self {
+        Self {
+            out,
+            position: 0,
+            entries: Vec::new(),
+        }
+    }
+
+    /// Write a chunk named `name` holding `data`.
+    pub(crate) fn write_chunk(&mut self, name: &[u8], data: &[u8]) -> Result<()> {
+        let chunk = encode_chunk(name, data);
+        self.out.write_all(&chunk)?;
+        self.list(name, data.len() as u64);
+        self.position += chunk.len() as u64;
+        Ok(())
+    }
+
+    /// Copy the chunk at `offset` in `reader` as it is, header, name
+    /// padding and all, listing it as `name`. The file signature chunk is
+    /// copied without being listed.
+    pub(crate) fn copy_chunk<R: Read + Seek>(
+        &mut self,
+        reader: &mut R,
+        name: &[u8],
+        offset: u64,
+    ) -> Result<()> {
+        reader.seek(SeekFrom::Start(offset))?;
+        let header = ChunkHeader::read(reader)?;
+        header.validate_magic()?;
+        let len = header
+            .end(0)
+            .ok_or_else(|| Nd2Error::file_invalid_format("Chunk length overflow".to_string()))?;
+        reader.seek(SeekFrom::Start(offset))?;
+        let copied = std::io::copy(&mut reader.take(len), &mut self.out)?;
+        if copied != len {
+            return Err(Nd2Error::file_invalid_format(format!(
+                "Chunk '{}' at offset {} runs past EOF",
+                String::from_utf8_lossy(name),
+                offset
+            )));
+        }
+        if name != ND2_FILE_SIGNATURE {
+            self.list(name, header.data_length);
+        }
+        self.position += len;
+        Ok(())
+    }
+
+    /// Bytes written so far.
+    pub(crate) fn position(&self) -> u64 {
+        self.position
+    }
+
+    fn list(&mut self, name: &[u8], size: u64) {
+        self.entries.push((name.to_vec(), self.position, size));
+    }
+
+    /// Write the chunkmap listing every chunk written, ending the file.
+    pub(crate) fn finish(mut self) -> Result<W> {
+        let map = encode_chunkmap(&self.entries, self.position);
+        self.out.write_all(&map)?;
+        self.out.flush()?;
+        Ok(self.out)
+    }
+}
@@ -0,0 +1,85 @@
+//! `polars` views of per-frame acquisition metadata.
+
+use polars::prelude::{DataFrame, NamedFrom, PolarsError, Series};
+
+use crate::error::{Nd2Error, Result};
+use crate::reader::Nd2File;
+use crate::table::{frame_rows, FrameRow, FRAME_AXES};
+use crate::types::RecordedValue;
+
+impl Nd2File {
+    /// One row per frame: `index`, a `u32` column for each loop axis the
+    /// frames are indexed by (`P`, `T`, `C`, `Z`), `time_ms` from
+    /// [`Nd2File::frame_times`] and `x_um`, `y_um`, `z_um` from
+    /// [`Nd2File::frame_positions`] (null when unknown or not recorded).
+    /// The same rows as the frame sidecar of
+    /// [`CompanionExporter`](crate::CompanionExporter).
+    pub fn frames_dataframe(&mut self) -> Result<DataFrame> {
+        let rows = frame_rows(self)?;
+        let mut columns = vec![Series::new(
+            "index",
+            rows.iter().map(|row| row.index as u32).collect::<Vec<_>>(),
+        )];
+        for axis in FRAME_AXES {
+            if rows.iter().any(|row| row.coord(axis).is_some()) {
+                let values: Vec<Option<u32>> = rows
+                    .iter()
+                    .map(|row| row.coord(axis).map(|i| i as u32))
+                    .collect();
+                columns.push(Series::new(axis, values));
+            }
+        }
+        let column = |f: fn(&FrameRow) -> Option<f64>| rows.iter().map(f).collect::<Vec<_>>();
+        columns.push(Series::new("time_ms", column(|row| row.time_ms)));
+        columns.push(Series::new("x_um", column(|row| row.x_um)));
+        columns.push(Series::new("y_um", column(|row| row.y_um)));
+        columns.push(Series::new("z_um", column(|row| row.z_um)));
+        DataFrame::new(columns).map_err(to_error)
+    }
+
+    /// The columns of [`Nd2File::recorded_data`] as a frame: `Frame`, the
+    /// sequence index, then `Time [s]` and the logged tags by name, with
+    /// their NIS column names. Integer tags are `i64` columns, text tags
+    /// string columns and the others `f64` columns.
+    pub fn recorded_data_dataframe(&mut self) -> Result<DataFrame> {
+        let mut data: Vec<_> = self.recorded_data()?.into_iter().collect();
+        data.sort_by(|(a, _), (b, _)| (a != TIME_COLUMN, a).cmp(&(b != TIME_COLUMN, b)));
+        let n_frames = data.first().map_or(0, |(_, values)| values.len());
+        let mut columns = vec![Series::new(
+            "Frame",
+            (0..n_frames as u32).collect::<Vec<_>>(),
+        )];
+        columns.extend(
+            data.iter()
+                .map(|(name, values)| recorded_series(name, values)),
+        );
+        DataFrame::new(columns).map_err(to_error)
+    }
+}
+
+/// The column of [`Nd2File::recorded_data`] every file has.
+const TIME_COLUMN: &str = "Time [s]";
+
+/// `values` as a column named `name`, typed by the values it holds.
+fn recorded_series(name: &str, values: &[RecordedValue]) -> Series {
+    if values.iter().all(|v| matches!(v, RecordedValue::Int(_))) {
+        let ints: Vec<Option<i64>> = values
+            .iter()
+            .map(|v| match *v {
+                RecordedValue::Int(i) => Some(i),
+                _ => None,
+            })
+            .collect();
+        Series::new(name, ints)
+    } else if values.iter().any(|v| matches!(v, RecordedValue::Text(_))) {
+        let texts: Vec<Option<&str>> = values.iter().map(RecordedValue::as_str).collect();
+        Series::new(name, texts)
+    } else {
+        let floats: Vec<Option<f64>> = values.iter().map(RecordedValue::as_f64).collect();
+        Series::new(name, floats)
+    }
+}
+
+fn to_error(err: PolarsError) -> Nd2Error {
+    std::io::Error::new(std::io::ErrorKind::Other, err.to_string()).into()
+}
@@ -0,0 +1,563 @@
+//! Frame decoding, separated from chunk I/O so compressed frames can be
+//! decoded off the reader's thread.
+
+use std::cell::RefCell;
+use std::io::Read;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{mpsc, Arc, Mutex};
+
+use flate2::read::ZlibDecoder;
+
+use crate::error::{Nd2Error, Result};
+use crate::options::Limits;
+use crate::pixel::{decode_components, Pixel};
+use crate::types::{Attributes, CompressionType, PixelDataType};
+
+/// Most bytes deflate can expand one compressed byte to.
+const MAX_DEFLATE_RATIO: usize = 1032;
+
+/// A decoded (C, Y, X) frame and its chunk timestamp.
+pub(crate) type DecodedFrame<T> = (Vec<T>, Option<f64>);
+
+/// Frame chunk bytes as read from the file, before decoding.
+pub(crate) enum FramePayload {
+    /// Whole chunk payload: the 8-byte timestamp then a zlib stream.
+    Compressed(Vec<u8>),
+    /// Raw pixel rows, with the timestamp when the chunk header was intact.
+    Raw {
+        timestamp_ms: Option<f64>,
+        bytes: Vec<u8>,
+    },
+}
+
+/// Per-file frame layout derived from the image attributes.
+#[derive(Debug, Clone)]
+pub(crate) struct FrameGeometry {
+    pub(crate) sequence_count: usize,
+    /// Frame chunks hold a compressed stream after the timestamp rather
+    /// than pixel rows: zlib, or JPEG 2000 when `lossy`.
+    pub(crate) compressed: bool,
+    pub(crate) lossy: bool,
+    /// Bytes of raw pixel rows in an uncompressed frame.
+    pub(crate) expected_raw: usize,
+    /// Longest compressed frame chunk read, see [`Limits::max_chunk_bytes`].
+    pub(crate) max_chunk_bytes: u64,
+    pixel_data_type: PixelDataType,
+    height: usize,
+    width: usize,
+    n_c: usize,
+    n_comp: usize,
+    bytes_per_pixel: usize,
+    raw_row_pixels: usize,
+    frame_size: usize,
+    frame_area: usize,
+    n_c_n_comp: usize,
+}
+
+impl FrameGeometry {
+    /// Layout of the frames described by `attrs`, failing when a frame
+    /// would take more bytes than `limits` allow: as the pixel rows of an
+    /// uncompressed frame, or inflated from a compressed one.
+    pub(crate) fn new(attrs: &Attributes, limits: &Limits) -> Result<Self> {
+        let h = attrs.height_px as usize;
+        let w = attrs.width_px.unwrap_or(0) as usize;
+        let (n_c, n_comp) = match attrs.channel_count {
+            Some(ch) if ch > 0 => (ch as usize, (attrs.component_count / ch) as usize),
+            _ => (attrs.component_count as usize, 1),
+        };
+        let bytes_per_pixel = (attrs.bits_per_component_in_memory / 8) as usize;
+        if bytes_per_pixel == 0 {
+            return Err(Nd2Error::file_invalid_format(
+                "Invalid bits_per_component_in_memory".to_string(),
+            ));
+        }
+        let raw_row_bytes = attrs.width_bytes.map(|w| w as usize).unwrap_or_else(|| {
+            w.saturating_mul(n_c)
+                .saturating_mul(n_comp)
+                .saturating_mul(bytes_per_pixel)
+        });
+        if raw_row_bytes == 0 {
+            return Err(Nd2Error::file_invalid_format(
+                "Invalid frame row stride".to_string(),
+            ));
+        }
+        if raw_row_bytes % bytes_per_pixel != 0 {
+            return Err(Nd2Error::file_invalid_format(format!(
+                "Frame row stride {} is not divisible by bytes per pixel {}",
+                raw_row_bytes, bytes_per_pixel
+            )));
+        }
+        let raw_row_pixels = raw_row_bytes / bytes_per_pixel;
+
+        let frame_size = h
+            .checked_mul(w)
+            .and_then(|v| v.checked_mul(n_c))
+            .and_then(|v| v.checked_mul(n_comp))
+            .ok_or_else(|| {
+                Nd2Error::file_invalid_format("Frame dimensions overflow".to_string())
+            })?;
+        let expected_raw = h
+            .checked_mul(raw_row_bytes)
+            .ok_or_else(|| Nd2Error::file_invalid_format("Frame byte size overflow".to_string()))?;
+        let frame_area = h
+            .checked_mul(w)
+            .ok_or_else(|| Nd2Error::file_invalid_format("Frame area overflow".to_string()))?;
+        let n_c_n_comp = n_c.checked_mul(n_comp).ok_or_else(|| {
+            Nd2Error::file_invalid_format("Frame channel/component overflow".to_string())
+        })?;
+        if raw_row_pixels < n_c_n_comp.saturating_mul(w) {
+            return Err(Nd2Error::file_invalid_format(format!(
+                "Frame row stride {} pixels is smaller than required width {}",
+                raw_row_pixels,
+                n_c_n_comp.saturating_mul(w)
+            )));
+        }
+
+        let lossy = matches!(attrs.compression_type, Some(CompressionType::Lossy));
+        let compressed = lossy || matches!(attrs.compression_type, Some(CompressionType::Lossless));
+        let (limit, what) = if compressed {
+            (limits.max_decompressed_bytes, "inflated")
+        } else {
+            (limits.max_chunk_bytes, "raw")
+        };
+        if expected_raw as u64 > limit {
+            return Err(Nd2Error::file_limit_exceeded(format!(
+                "Frames of {} {} bytes exceed the {} byte limit",
+                expected_raw, what, limit
+            )));
+        }
+
+        Ok(Self {
+            sequence_count: attrs.sequence_count as usize,
+            compressed,
+            lossy,
+            expected_raw,
+            max_chunk_bytes: limits.max_chunk_bytes,
+            pixel_data_type: attrs.pixel_data_type,
+            height: h,
+            width: w,
+            n_c,
+            n_comp,
+            bytes_per_pixel,
+            raw_row_pixels,
+            frame_size,
+            frame_area,
+            n_c_n_comp,
+        })
+    }
+
+    /// Decompress (if needed) and decode frame `index` to (C, Y, X) order,
+    /// returning it with the chunk timestamp.
+    pub(crate) fn decode<T: Pixel>(
+        &self,
+        index: usize,
+        payload: &FramePayload,
+    ) -> Result<DecodedFrame<T>> {
+        SCRATCH.with(|scratch| self.decode_with(index, payload, &mut scratch.borrow_mut()))
+    }
+
+    /// [`FrameGeometry::decode`] with the caller's scratch buffers.
+    fn decode_with<T: Pixel>(
+        &self,
+        index: usize,
+        payload: &FramePayload,
+        scratch: &mut Scratch,
+    ) -> Result<DecodedFrame<T>> {
+        let Scratch { inflated, planar } = scratch;
+        let (timestamp_ms, pixel_bytes) = match payload {
+            FramePayload::Compressed(data) => {
+                let timestamp = self.inflate_into(index, data, inflated)?;
+                (Some(timestamp), inflated.as_slice())
+            }
+            FramePayload::Raw {
+                timestamp_ms,
+                bytes,
+            } => (*timestamp_ms, bytes.as_slice()),
+        };
+        Ok((self.to_planar(index, pixel_bytes, planar)?, timestamp_ms))
+    }
+
+    /// Inflate a compressed chunk payload into `inflated` (pixel rows in
+    /// stored order), returning the chunk timestamp.
+    fn inflate_into(&self, index: usize, data: &[u8], inflated: &mut Vec<u8>) -> Result<f64> {
+        if data.len() < 8 {
+            return Err(Nd2Error::file_invalid_format(format!(
+                "Frame {} compressed chunk too short ({} bytes)",
+                index,
+                data.len()
+            )));
+        }
+        let timestamp = f64::from_le_bytes([
+            data[0], data[1], data[2], data[3], data[4], data[5], data[6], data[7],
+        ]);
+        if self.lossy {
+            self.jpeg2000_into(index, &data[8..], inflated)?;
+            return Ok(timestamp);
+        }
+        inflated.clear();
+        // Attributes alone don't bound the allocation: reserve no more than
+        // the stream can inflate to, and stop reading at the frame size.
+        inflated.reserve(
+            self.expected_raw
+                .min((data.len() - 8).saturating_mul(MAX_DEFLATE_RATIO)),
+        );
+        ZlibDecoder::new(&data[8..])
+            .take(self.expected_raw as u64)
+            .read_to_end(inflated)?;
+        Ok(timestamp)
+    }
+
+    /// Decode a JPEG 2000 codestream with one component per stored
+    /// component into pixel rows in stored order.
+    #[cfg(feature = "jpeg2000")]
+    fn jpeg2000_into(&self, index: usize, codestream: &[u8], rows: &mut Vec<u8>) -> Result<()> {
+        if self.pixel_data_type == PixelDataType::Float {
+            return Err(Nd2Error::unsupported_compression(
+                "lossy (JPEG 2000) frames of float pixels",
+            ));
+        }
+        let image = jpeg2k::Image::from_bytes(codestream).map_err(|err| {
+            Nd2Error::file_invalid_format(format!(
+                "Frame {}: JPEG 2000 codestream could not be decoded: {}",
+                index, err
+            ))
+        })?;
+        let components = image.components();
+        if components.len() != self.n_c_n_comp
+            || components.iter().any(|component| {
+                component.width() as usize != self.width
+                    || component.height() as usize != self.height
+            })
+        {
+            return Err(Nd2Error::file_invalid_format(format!(
+                "Frame {}: JPEG 2000 image does not have {} components of {}x{}",
+                index, self.n_c_n_comp, self.width, self.height
+            )));
+        }
+        let bytes_per_pixel = self.bytes_per_pixel;
+        let max = (1u64 << (8 * bytes_per_pixel)) - 1;
+        let row_bytes = self.raw_row_pixels * bytes_per_pixel;
+        rows.clear();
+        rows.resize(self.expected_raw, 0);
+        for (k, component) in components.iter().enumerate() {
+            for (i, &value) in component.data().iter().take(self.frame_area).enumerate() {
+                let (y, x) = (i / self.width, i % self.width);
+                let at = y * row_bytes + (x * self.n_c_n_comp + k) * bytes_per_pixel;
+                let value = (value.max(0) as u64).min(max).to_le_bytes();
+                rows[at..at + bytes_per_pixel].copy_from_slice(&value[..bytes_per_pixel]);
+            }
+        }
+        Ok(())
+    }
+
+    #[cfg(not(feature = "jpeg2000"))]
+    fn jpeg2000_into(&self, _index: usize, _codestream: &[u8], _rows: &mut Vec<u8>) -> Result<()> {
+        Err(Nd2Error::unsupported_compression(
+            "lossy (JPEG 2000) frames need the `jpeg2000` feature",
+        ))
+    }
+
+    /// Inflate a compressed chunk payload, keeping the pixel bytes in
+    /// stored order, as kept by the frame cache.
+    pub(crate) fn inflate(&self, index: usize, data: &[u8]) -> Result<FramePayload> {
+        let mut bytes = Vec::new();
+        let timestamp = self.inflate_into(index, data, &mut bytes)?;
+        Ok(FramePayload::Raw {
+            timestamp_ms: Some(timestamp),
+            bytes,
+        })
+    }
+
+    /// Bytes of pixel data a frame needs, without row padding.
+    pub(crate) fn pixel_bytes(&self) -> usize {
+        self.frame_size * self.bytes_per_pixel
+    }
+
+    /// Row stride of frames holding `n_channels` of these frames' channels,
+    /// without row padding.
+    pub(crate) fn channel_row_bytes(&self, n_channels: usize) -> usize {
+        self.width * n_channels * self.n_comp * self.bytes_per_pixel
+    }
+
+    /// Keep `channels` (in that order) of frame `index`'s interleaved,
+    /// row-strided pixel bytes, in rows of
+    /// [`FrameGeometry::channel_row_bytes`].
+    pub(crate) fn select_channels(
+        &self,
+        index: usize,
+        pixel_bytes: &[u8],
+        channels: &[usize],
+    ) -> Result<Vec<u8>> {
+        if pixel_bytes.len() < self.expected_raw {
+            return Err(Nd2Error::file_invalid_format(format!(
+                "Frame {}: expected {} bytes of pixel rows, got {}",
+                index,
+                self.expected_raw,
+                pixel_bytes.len()
+            )));
+        }
+        let channel_bytes = self.n_comp * self.bytes_per_pixel;
+        let pixel_stride = self.n_c_n_comp * self.bytes_per_pixel;
+        let row_bytes = self.raw_row_pixels * self.bytes_per_pixel;
+        let mut out = Vec::with_capacity(self.height * self.channel_row_bytes(channels.len()));
+        for row in pixel_bytes[..self.expected_raw].chunks_exact(row_bytes) {
+            for pixel in row.chunks_exact(pixel_stride).take(self.width) {
+                for &c in channels {
+                    out.extend_from_slice(&pixel[c * channel_bytes..(c + 1) * channel_bytes]);
+                }
+            }
+        }
+        Ok(out)
+    }
+
+    /// Reorder interleaved, row-strided pixel bytes into (C, Y, X) and
+    /// decode them, using `planar` as scratch space.
+    fn to_planar<T: Pixel>(
+        &self,
+        index: usize,
+        pixel_bytes: &[u8],
+        planar: &mut Vec<u8>,
+    ) -> Result<Vec<T>> {
+        let bytes_per_pixel = self.bytes_per_pixel;
+        let frame_size = self.frame_size;
+
+        if pixel_bytes.len() % bytes_per_pixel != 0 {
+            return Err(Nd2Error::file_invalid_format(format!(
+                "Frame {}: pixel data length {} is not divisible by {}",
+                index,
+                pixel_bytes.len(),
+                bytes_per_pixel
+            )));
+        }
+
+        if pixel_bytes.len() / bytes_per_pixel < frame_size {
+            return Err(Nd2Error::file_invalid_format(format!(
+                "Frame {}: expected {} pixels ({} bytes), got {} bytes",
+                index,
+                frame_size,
+                frame_size.saturating_mul(bytes_per_pixel),
+                pixel_bytes.len()
+            )));
+        }
+        let frame_bytes = frame_size * bytes_per_pixel;
+
+        // Single-component frames without row padding are already planar.
+        if self.n_c_n_comp == 1 && self.raw_row_pixels == self.width {
+            return decode_components(
+                &pixel_bytes[..frame_bytes],
+                bytes_per_pixel,
+                self.pixel_data_type,
+            );
+        }
+
+        let (h, w) = (self.height, self.width);
+        let (n_c, n_comp, n_c_n_comp) = (self.n_c, self.n_comp, self.n_c_n_comp);
+        let frame_area = self.frame_area;
+        let row_pixels = self.raw_row_pixels;
+        planar.clear();
+        planar.resize(frame_bytes, 0);
+
+        for y in 0..h {
+            let y_offset = y.checked_mul(row_pixels).ok_or_else(|| {
+                Nd2Error::file_invalid_format("Frame offset overflow".to_string())
+            })?;
+            let y_plane_offset = y.checked_mul(w).ok_or_else(|| {
+                Nd2Error::file_invalid_format("Frame plane offset overflow".to_string())
+            })?;
+            for x in 0..w {
+                let x_offset = x.checked_mul(n_c_n_comp).ok_or_else(|| {
+                    Nd2Error::file_invalid_format("Frame offset overflow".to_string())
+                })?;
+                for c in 0..n_c {
+                    let c_offset = c.checked_mul(n_comp).ok_or_else(|| {
+                        Nd2Error::file_invalid_format("Frame offset overflow".to_string())
+                    })?;
+                    for comp in 0..n_comp {
+                        let src_idx = y_offset
+                            .checked_add(x_offset)
+                            .and_then(|v| v.checked_add(c_offset))
+                            .and_then(|v| v.checked_add(comp))
+                            .and_then(|v| v.checked_mul(bytes_per_pixel))
+                            .ok_or_else(|| {
+                                Nd2Error::file_invalid_format("Frame offset overflow".to_string())
+                            })?;
+                        let dst_x = y_plane_offset.checked_add(x).ok_or_else(|| {
+                            Nd2Error::file_invalid_format("Frame offset overflow".to_string())
+                        })?;
+                        let c_plane = c_offset.checked_add(comp).ok_or_else(|| {
+                            Nd2Error::file_invalid_format("Frame offset overflow".to_string())
+                        })?;
+                        let dst_idx = c_plane
+                            .checked_mul(frame_area)
+                            .and_then(|v| v.checked_add(dst_x))
+                            .and_then(|v| v.checked_mul(bytes_per_pixel))
+                            .ok_or_else(|| {
+                                Nd2Error::file_invalid_format("Frame offset overflow".to_string())
+                            })?;
+                        let value = pixel_bytes
+                            .get(src_idx..src_idx + bytes_per_pixel)
+                            .ok_or_else(|| {
+                                Nd2Error::file_invalid_format(format!(
+                                    "Frame {}: pixel data too short for row stride",
+                                    index
+                                ))
+                            })?;
+                        let slot = planar
+                            .get_mut(dst_idx..dst_idx + bytes_per_pixel)
+                            .ok_or_else(|| Nd2Error::internal_overflow("frame plane index"))?;
+                        slot.copy_from_slice(value);
+                    }
+                }
+            }
+        }
+
+        decode_components(planar, bytes_per_pixel, self.pixel_data_type)
+    }
+}
+
+/// Work sent to a [`DecodePool`] worker, run with the worker's scratch
+/// buffers.
+type Job<'env> = Box<dyn FnOnce(&mut Scratch) + Send + 'env>;
+
+/// Decode worker threads that last for a whole bulk read, so threads are
+/// started once per read rather than once per batch. Each worker takes a
+/// [`Scratch`] from the caller and hands it back when the read ends, so the
+/// buffers also carry over to the next bulk read.
+pub(crate) struct DecodePool<'env> {
+    /// Queue of the workers; `None` with fewer than two threads, in which
+    /// case jobs run on the calling thread.
+    jobs: Option<mpsc::Sender<Job<'env>>>,
+}
+
+impl<'env> DecodePool<'env> {
+    /// Run `body` with a pool of `threads` scoped workers, which are joined
+    /// when it returns. Workers take their buffers from `scratch` and put
+    /// them back.
+    pub(crate) fn scope<R>(
+        threads: usize,
+        scratch: &mut Vec<Scratch>,
+        body: impl FnOnce(&DecodePool<'env>) -> R,
+    ) -> R {
+        if threads < 2 {
+            return body(&DecodePool { jobs: None });
+        }
+        std::thread::scope(|scope| {
+            let (jobs, queue) = mpsc::channel::<Job<'env>>();
+            let queue = Arc::new(Mutex::new(queue));
+            let workers: Vec<_> = (0..threads)
+                .map(|_| {
+                    let queue = Arc::clone(&queue);
+                    let mut buffers = scratch.pop().unwrap_or_default();
+                    scope.spawn(move || loop {
+                        // The lock is released once a job is taken, before
+                        // it runs.
+                        let job = match queue.lock() {
+                            Ok(queue) => queue.recv(),
+                            Err(_) => return buffers,
+                        };
+                        match job {
+                            Ok(job) => job(&mut buffers),
+                            Err(_) => return buffers,
+                        }
+                    })
+                })
+                .collect();
+            let pool = DecodePool { jobs: Some(jobs) };
+            let out = body(&pool);
+            // Closing the queue lets the workers finish.
+            drop(pool);
+            for worker in workers {
+                match worker.join() {
+                    Ok(buffers) => scratch.push(buffers),
+                    Err(panic) => panic::resume_unwind(panic),
+                }
+            }
+            out
+        })
+    }
+
+    /// Decode `payloads` (frame `indices`, in order) on the workers, handing
+    /// the payloads back for reuse.
+    ///
+    /// Each job first calls `fetch` with the payload's position in
+    /// `indices`, so chunks can also be read off the calling thread.
+    pub(crate) fn decode<T, F>(
+        &self,
+        geometry: &'env FrameGeometry,
+        indices: &[usize],
+        payloads: Vec<FramePayload>,
+        fetch: F,
+    ) -> (Vec<Result<DecodedFrame<T>>>, Vec<FramePayload>)
+    where
+        T: Pixel,
+        F: Fn(usize, &mut FramePayload) -> Result<()> + Send + Sync + 'env,
+    {
+        let fetch = Arc::new(fetch);
+        let (done, results) = mpsc::channel();
+        for (i, (&index, mut payload)) in indices.iter().zip(payloads).enumerate() {
+            let (fetch, done) = (Arc::clone(&fetch), done.clone());
+            let job: Job<'env> = Box::new(move |scratch| {
+                let frame = panic::catch_unwind(AssertUnwindSafe(|| {
+                    fetch(i, &mut payload)
+                        .and_then(|()| geometry.decode_with::<T>(index, &payload, scratch))
+                }));
+                // The receiver only goes away while the caller unwinds.
+                let _ = done.send((i, frame, payload));
+            });
+            // Workers only stop once the pool is dropped, so a send cannot
+            // fail; run the job here if it somehow does.
+            match &self.jobs {
+                Some(jobs) => {
+                    if let Err(mpsc::SendError(job)) = jobs.send(job) {
+                        SCRATCH.with(|scratch| job(&mut scratch.borrow_mut()));
+                    }
+                }
+                None => SCRATCH.with(|scratch| job(&mut scratch.borrow_mut())),
+            }
+        }
+        drop(done);
+        let mut slots: Vec<_> = indices.iter().map(|_| None).collect();
+        for (i, frame, payload) in results {
+            match frame {
+                Ok(frame) => slots[i] = Some((frame, payload)),
+                Err(panic) => panic::resume_unwind(panic),
+            }
+        }
+        slots
+            .into_iter()
+            .map(|slot| {
+                slot.unwrap_or_else(|| {
+                    (
+                        Err(Nd2Error::internal_invariant(
+                            "decode job did not report back",
+                        )),
+                        FramePayload::Compressed(Vec::new()),
+                    )
+                })
+            })
+            .unzip()
+    }
+}
+
+impl FramePayload {
+    /// The payload's buffer, to be refilled by the next read.
+    pub(crate) fn into_buffer(self) -> Vec<u8> {
+        match self {
+            FramePayload::Compressed(bytes) | FramePayload::Raw { bytes, .. } => bytes,
+        }
+    }
+}
+
+/// Buffers for inflated and reordered frame bytes, kept between frames so
+/// reads don't allocate them for every frame: one per thread for single
+/// reads, and one per [`DecodePool`] worker, owned by the reader between
+/// bulk reads.
+#[derive(Default)]
+pub(crate) struct Scratch {
+    inflated: Vec<u8>,
+    planar: Vec<u8>,
+}
+
+thread_local! {
+    static SCRATCH: RefCell<Scratch> = RefCell::new(Scratch::default());
+}
@@ -0,0 +1,103 @@
+//! Editing the metadata of existing files in place.
+
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use crate::chunk::{encode_chunk, encode_chunkmap, ChunkHeader};
+use crate::error::{Nd2Error, Result};
+use crate::meta_parse::{TextInfoEdit, TEXT_INFO_LEVEL};
+use crate::parse::{encode_entry, ClxLiteParser};
+use crate::reader::Nd2File;
+use crate::types::{EditMode, TextInfo};
+
+/// Set the text info fields of the file at `path` that are `Some` in
+/// `changes` (description, author, sample ID, ...), leaving the other
+/// fields, the other metadata and the frames as they are.
+///
+/// The rewritten text info chunk goes over the old one when it is no
+/// larger. Otherwise it is appended to the file with a new chunkmap that
+/// lists it instead of the old chunk; a failed append is cut off again, so
+/// the file keeps its previous chunkmap. No frame is moved either way.
+pub fn edit_text_info<P: AsRef<Path>>(path: P, changes: &TextInfo) -> Result<EditMode> {
+    let path = path.as_ref();
+    let mut nd2 = Nd2File::open(path)?;
+    if nd2.is_legacy() {
+        let (major, minor) = nd2.version();
+        return Err(Nd2Error::unsupported_version(major, minor));
+    }
+    let name = nd2.text_info_chunk_name();
+    let location = nd2.chunk_location(name);
+    let mut edit = TextInfoEdit::new(changes);
+    let data = match location {
+        Some(_) => {
+            let old = nd2.read_chunk(name)?;
+            let data = ClxLiteParser::new(false)
+                .limits(nd2.options().limits)
+                .rewrite(&old, &mut edit)?;
+            if !edit.is_complete() {
+                return Err(Nd2Error::file_metadata(format!(
+                    "Text info chunk has no {} level to add fields to",
+                    TEXT_INFO_LEVEL
+                )));
+            }
+            data
+        }
+        None => {
+            let mut data = Vec::new();
+            encode_entry(TEXT_INFO_LEVEL, &edit.level(), &mut data)?;
+            data
+        }
+    };
+    let mut entries = nd2.chunk_entries();
+    drop(nd2);
+
+    let mut file = OpenOptions::new().read(true).write(true).open(path)?;
+    if let Some((offset, _)) = location {
+        if overwrite_chunk(&mut file, offset, &data)? {
+            return Ok(EditMode::InPlace);
+        }
+    }
+
+    let end = file.seek(SeekFrom::End(0))?;
+    let chunk = encode_chunk(name, &data);
+    let map_offset = end + chunk.len() as u64;
+    let entry = (name.to_vec(), end, data.len() as u64);
+    match entries.iter_mut().find(|(stored, _, _)| stored == name) {
+        Some(stored) => *stored = entry,
+        None => entries.push(entry),
+    }
+    let map = encode_chunkmap(&entries, map_offset);
+    if let Err(err) = file.write_all(&chunk).and_then(|()| file.write_all(&map)) {
+        let _ = file.set_len(end);
+        return Err(err.into());
+    }
+    file.sync_all()?;
+    Ok(EditMode::Appended)
+}
+
+/// Replace the data of the chunk at `offset` with `data` when it fits in
+/// the chunk's current data, returning whether it did. The bytes left over
+/// are zeroed.
+fn overwrite_chunk(file: &mut File, offset: u64, data: &[u8]) -> Result<bool> {
+    file.seek(SeekFrom::Start(offset))?;
+    let header = ChunkHeader::read(file)?;
+    header.validate_magic()?;
+    if data.len() as u64 > header.data_length {
+        return Ok(false);
+    }
+    let data_offset = offset + ChunkHeader::SIZE as u64 + header.name_length as u64;
+    file.seek(SeekFrom::Start(data_offset))?;
+    file.write_all(data)?;
+    let left = header.data_length - data.len() as u64;
+    std::io::copy(&mut std::io::repeat(0).take(left), file)?;
+    // The header shrinks last, once the data it points at is complete.
+    let header = ChunkHeader {
+        data_length: data.len() as u64,
+        ..header
+    };
+    file.seek(SeekFrom::Start(offset))?;
+    file.write_all(&header.encode())?;
+    file.sync_all()?;
+    Ok(true)
+}
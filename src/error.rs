@@ -49,6 +49,9 @@ pub enum FileError {
 
     #[error("Metadata parse error: {context}")]
     MetadataParse { context: String },
+
+    #[error("Resource limit exceeded: {context}")]
+    LimitExceeded { context: String },
 }
 
 #[derive(Error, Debug)]
@@ -86,6 +89,9 @@ pub enum UnsupportedError {
 
     #[error("Unsupported CLX data type: {type_code}")]
     ClxType { type_code: u8 },
+
+    #[error("Unsupported frame compression: {detail}")]
+    Compression { detail: String },
 }
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
@@ -146,6 +152,22 @@ impl Nd2Error {
         }
     }
 
+    pub fn file_clx_parse(context: impl Into<String>) -> Self {
+        Self::File {
+            source: FileError::ClxParse {
+                context: context.into(),
+            },
+        }
+    }
+
+    pub fn file_limit_exceeded(context: impl Into<String>) -> Self {
+        Self::File {
+            source: FileError::LimitExceeded {
+                context: context.into(),
+            },
+        }
+    }
+
     pub fn file_invalid_magic(expected: u32, actual: u32) -> Self {
         Self::File {
             source: FileError::InvalidMagic { expected, actual },
@@ -185,6 +207,15 @@ impl Nd2Error {
         }
     }
 
+    pub fn input_incompatible(expected: impl Into<String>, provided: impl Into<String>) -> Self {
+        Self::Input {
+            source: InputError::IncompatibleParams {
+                expected: expected.into(),
+                provided: provided.into(),
+            },
+        }
+    }
+
     pub fn internal_overflow(operation: impl Into<String>) -> Self {
         Self::Internal {
             source: InternalError::Overflow {
@@ -193,6 +224,14 @@ impl Nd2Error {
         }
     }
 
+    pub fn internal_invariant(detail: impl Into<String>) -> Self {
+        Self::Internal {
+            source: InternalError::InvariantViolation {
+                detail: detail.into(),
+            },
+        }
+    }
+
     pub fn unsupported_version(major: u32, minor: u32) -> Self {
         Self::Unsupported {
             source: UnsupportedError::Version { major, minor },
@@ -204,6 +243,14 @@ impl Nd2Error {
             source: UnsupportedError::ClxType { type_code },
         }
     }
+
+    pub fn unsupported_compression(detail: impl Into<String>) -> Self {
+        Self::Unsupported {
+            source: UnsupportedError::Compression {
+                detail: detail.into(),
+            },
+        }
+    }
 }
 
 impl From<std::io::Error> for Nd2Error {
@@ -0,0 +1,168 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[cfg(feature = "json")]
+use serde::Serialize;
+
+use super::ome_xml;
+use crate::error::{Nd2Error, Result};
+use crate::reader::Nd2File;
+use crate::table::{event_rows, frame_rows, position_rows, Cell, TableRow};
+
+/// Format of the sidecars of a [`CompanionExporter`].
+///
+/// Non-exhaustive, since formats behind Cargo features come and go with them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SidecarFormat {
+    /// Comma-separated values with a header row; the first column is the
+    /// `schema_version`.
+    Csv,
+    /// A JSON object with the `schema_version` and the rows as an array of
+    /// objects. Requires the `json` feature.
+    #[cfg(feature = "json")]
+    Json,
+}
+
+impl SidecarFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            Self::Csv => "csv",
+            #[cfg(feature = "json")]
+            Self::Json => "json",
+        }
+    }
+}
+
+/// Writes a Bio-Formats style `.companion.ome` file: the OME-XML of
+/// [`Nd2File::ome_xml`] with every `Image` annotated with the name of the
+/// ND2 file it describes, for tools that read OME metadata but not ND2.
+///
+/// With [`CompanionExporter::sidecars`], tables are also written next to
+/// it, where `<stem>` is the companion path without its `.companion.ome`
+/// suffix:
+///
+/// - `<stem>.positions.<ext>`: the planned XY points.
+/// - `<stem>.frames.<ext>`: one row per frame, with its loop coordinates,
+///   [`Nd2File::frame_times`] and the recorded
+///   [`Nd2File::frame_positions`].
+/// - `<stem>.events.<ext>`: the experiment [`Nd2File::events`].
+///
+/// Sidecars carry [`CompanionExporter::SIDECAR_SCHEMA_VERSION`]; columns are added without
+/// a bump, while renaming, removing or changing the meaning of one bumps it.
+#[derive(Debug, Clone)]
+pub struct CompanionExporter {
+    path: PathBuf,
+    source: Option<String>,
+    sidecars: Option<SidecarFormat>,
+}
+
+impl CompanionExporter {
+    /// Current schema version of the sidecars.
+    pub const SIDECAR_SCHEMA_VERSION: u32 = 1;
+
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+            source: None,
+            sidecars: None,
+        }
+    }
+
+    /// File name of the ND2 file, as referenced from the companion.
+    /// Defaults to the companion's stem with `.nd2` appended.
+    pub fn source(mut self, name: impl Into<String>) -> Self {
+        self.source = Some(name.into());
+        self
+    }
+
+    /// Also write position, frame and event sidecars in `format`.
+    pub fn sidecars(mut self, format: SidecarFormat) -> Self {
+        self.sidecars = Some(format);
+        self
+    }
+
+    /// The companion path without its `.companion.ome` (or other) suffix.
+    fn stem(&self) -> Result<String> {
+        let name = self
+            .path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .ok_or_else(|| Nd2Error::input_argument("path", "companion path has no file name"))?;
+        let stem = name
+            .strip_suffix(".companion.ome")
+            .or_else(|| name.strip_suffix(".ome.xml"))
+            .or_else(|| name.rsplit_once('.').map(|(stem, _)| stem))
+            .unwrap_or(name);
+        Ok(stem.to_string())
+    }
+
+    /// Path of the sidecar named `kind`.
+    pub fn sidecar_path(&self, kind: &str, format: SidecarFormat) -> Result<PathBuf> {
+        Ok(self
+            .path
+            .with_file_name(format!("{}.{}.{}", self.stem()?, kind, format.extension())))
+    }
+
+    pub fn export(&self, nd2: &mut Nd2File) -> Result<()> {
+        let source = match &self.source {
+            Some(name) => name.clone(),
+            None => format!("{}.nd2", self.stem()?),
+        };
+        fs::write(&self.path, ome_xml::build_companion(nd2, &source)?)?;
+        if let Some(format) = self.sidecars {
+            let positions = render(&position_rows(nd2)?, format)?;
+            fs::write(self.sidecar_path("positions", format)?, positions)?;
+            let frames = render(&frame_rows(nd2)?, format)?;
+            fs::write(self.sidecar_path("frames", format)?, frames)?;
+            let events = render(&event_rows(nd2)?, format)?;
+            fs::write(self.sidecar_path("events", format)?, events)?;
+        }
+        Ok(())
+    }
+}
+
+/// A sidecar file: the schema version and the rows.
+#[cfg(feature = "json")]
+#[derive(Serialize)]
+struct Sidecar<'a, R> {
+    schema_version: u32,
+    rows: &'a [R],
+}
+
+/// `rows` as a sidecar in `format`.
+fn render<R: TableRow>(rows: &[R], format: SidecarFormat) -> Result<String> {
+    let schema_version = CompanionExporter::SIDECAR_SCHEMA_VERSION;
+    match format {
+        SidecarFormat::Csv => {
+            let mut out = format!("schema_version,{}\n", R::COLUMNS.join(","));
+            for row in rows {
+                out.push_str(&schema_version.to_string());
+                for cell in row.cells() {
+                    out.push(',');
+                    match cell {
+                        Cell::Number(v) => out.push_str(&v.to_string()),
+                        Cell::Text(s) if s.contains([',', '"', '\n', '\r']) => {
+                            out.push_str(&format!("\"{}\"", s.replace('"', "\"\"")));
+                        }
+                        Cell::Text(s) => out.push_str(&s),
+                        Cell::Missing => {}
+                    }
+                }
+                out.push('\n');
+            }
+            Ok(out)
+        }
+        #[cfg(feature = "json")]
+        SidecarFormat::Json => {
+            let sidecar = Sidecar {
+                schema_version,
+                rows,
+            };
+            let mut out = serde_json::to_string_pretty(&sidecar)
+                .map_err(|err| std::io::Error::new(std::io::ErrorKind::Other, err.to_string()))?;
+            out.push('\n');
+            Ok(out)
+        }
+    }
+}
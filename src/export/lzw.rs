@@ -0,0 +1,78 @@
+//! TIFF flavour of LZW: codes of 9 to 12 bits packed MSB first, with the
+//! code width growing one code early.
+
+use std::collections::HashMap;
+
+const CLEAR: u16 = 256;
+const END: u16 = 257;
+const FIRST: u16 = 258;
+/// The encoder starts over once the table reaches this size, before the
+/// decoder would need 13-bit codes.
+const TABLE_LIMIT: u16 = 4094;
+
+#[derive(Default)]
+struct BitWriter {
+    out: Vec<u8>,
+    acc: u32,
+    bits: u32,
+}
+
+impl BitWriter {
+    fn put(&mut self, code: u16, width: u32) {
+        self.acc = (self.acc << width) | code as u32;
+        self.bits += width;
+        while self.bits >= 8 {
+            self.bits -= 8;
+            self.out.push((self.acc >> self.bits) as u8);
+        }
+        self.acc &= (1 << self.bits) - 1;
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.bits > 0 {
+            self.out.push((self.acc << (8 - self.bits)) as u8);
+        }
+        self.out
+    }
+}
+
+pub(crate) fn encode(data: &[u8]) -> Vec<u8> {
+    let mut out = BitWriter::default();
+    let mut table: HashMap<(u16, u8), u16> = HashMap::new();
+    let mut next = FIRST;
+    let mut width = 9;
+    out.put(CLEAR, width);
+
+    let mut bytes = data.iter();
+    let Some(&first) = bytes.next() else {
+        out.put(END, width);
+        return out.finish();
+    };
+    let mut prefix = first as u16;
+    for &byte in bytes {
+        if let Some(&code) = table.get(&(prefix, byte)) {
+            prefix = code;
+            continue;
+        }
+        out.put(prefix, width);
+        table.insert((prefix, byte), next);
+        next += 1;
+        if next == TABLE_LIMIT {
+            out.put(CLEAR, width);
+            table.clear();
+            next = FIRST;
+            width = 9;
+        } else if next == 1 << width {
+            width += 1;
+        }
+        prefix = byte as u16;
+    }
+    out.put(prefix, width);
+    // The decoder adds an entry for the last code too, which may widen the
+    // end code.
+    if next > FIRST && next + 1 == 1 << width {
+        width += 1;
+    }
+    out.put(END, width);
+    out.finish()
+}
@@ -0,0 +1,164 @@
+use std::fmt::Write as _;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+
+use super::{AxisScales, PlaneLayout};
+use crate::error::{Nd2Error, Result};
+use crate::reader::Nd2File;
+use crate::types::ExpLoop;
+
+/// Writes one Z-stack as an ITK MetaImage, with voxel spacing in µm and the
+/// stage position as origin.
+///
+/// A `.mha` path stores header and pixels in one file; any other extension
+/// (normally `.mhd`) writes the header plus a sibling `.raw` data file.
+/// The image uses ITK's default LPS orientation with an identity direction.
+#[derive(Debug, Clone)]
+pub struct MetaImageExporter {
+    path: PathBuf,
+    position: usize,
+    timepoint: usize,
+    channel: usize,
+    compression: Option<u32>,
+}
+
+impl MetaImageExporter {
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+            position: 0,
+            timepoint: 0,
+            channel: 0,
+            compression: None,
+        }
+    }
+
+    /// Position (P index) to export. Defaults to 0.
+    pub fn position(mut self, position: usize) -> Self {
+        self.position = position;
+        self
+    }
+
+    /// Time point (T index) to export. Defaults to 0.
+    pub fn timepoint(mut self, timepoint: usize) -> Self {
+        self.timepoint = timepoint;
+        self
+    }
+
+    /// Channel (C index) to export. Defaults to 0.
+    pub fn channel(mut self, channel: usize) -> Self {
+        self.channel = channel;
+        self
+    }
+
+    /// Compress pixel data with zlib at the given level (0-9). Uncompressed by default.
+    pub fn compression(mut self, level: u32) -> Self {
+        self.compression = Some(level.min(9));
+        self
+    }
+
+    pub fn export(&self, nd2: &mut Nd2File) -> Result<()> {
+        let layout = PlaneLayout::read(nd2, self.position)?;
+        layout.channels(Some(self.channel))?;
+        if self.timepoint >= layout.n_time {
+            return Err(Nd2Error::input_out_of_range(
+                "time index",
+                self.timepoint,
+                layout.n_time,
+            ));
+        }
+
+        let planes: Vec<[usize; 4]> = (0..layout.n_z)
+            .map(|z| [self.position, self.timepoint, self.channel, z])
+            .collect();
+        let mut raw = Vec::with_capacity(layout.n_z * layout.height * layout.width * 2);
+        for plane in nd2.read_planes(&planes)? {
+            raw.extend(plane.iter().flat_map(|p| p.to_le_bytes()));
+        }
+        let data = match self.compression {
+            Some(level) => {
+                let mut encoder = ZlibEncoder::new(Vec::new(), Compression::new(level));
+                encoder.write_all(&raw)?;
+                encoder.finish()?
+            }
+            None => raw,
+        };
+
+        let inline = self
+            .path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("mha"));
+        let data_path = self.path.with_extension("raw");
+        let data_file = if inline {
+            "LOCAL".to_string()
+        } else {
+            data_path
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default()
+        };
+        let mut header = self
+            .header(nd2, &layout, data.len(), &data_file)?
+            .into_bytes();
+        if inline {
+            header.extend_from_slice(&data);
+        } else {
+            fs::write(&data_path, &data)?;
+        }
+        fs::write(&self.path, header)?;
+        Ok(())
+    }
+
+    fn header(
+        &self,
+        nd2: &mut Nd2File,
+        layout: &PlaneLayout,
+        data_size: usize,
+        data_file: &str,
+    ) -> Result<String> {
+        let scales = AxisScales::read(nd2)?;
+        let (sx, sy) = scales.xy.unwrap_or((1.0, 1.0));
+        let sz = scales.z.unwrap_or(1.0);
+        let origin = nd2
+            .experiment()?
+            .iter()
+            .find_map(|loop_| match loop_ {
+                ExpLoop::XYPosLoop(xy) => xy.parameters.points.get(self.position).cloned(),
+                _ => None,
+            })
+            .map_or((0.0, 0.0, 0.0), |point| {
+                let s = point.stage_position_um;
+                (s.x, s.y, s.z)
+            });
+
+        let mut header = String::new();
+        header.push_str("ObjectType = Image\nNDims = 3\n");
+        header.push_str("BinaryData = True\nBinaryDataByteOrderMSB = False\n");
+        match self.compression {
+            Some(_) => {
+                let _ = writeln!(
+                    header,
+                    "CompressedData = True\nCompressedDataSize = {data_size}"
+                );
+            }
+            None => header.push_str("CompressedData = False\n"),
+        }
+        header.push_str("TransformMatrix = 1 0 0 0 1 0 0 0 1\n");
+        let _ = writeln!(header, "Offset = {} {} {}", origin.0, origin.1, origin.2);
+        header.push_str("CenterOfRotation = 0 0 0\nAnatomicalOrientation = RAI\n");
+        let _ = writeln!(header, "ElementSpacing = {sx} {sy} {sz}");
+        let _ = writeln!(
+            header,
+            "DimSize = {} {} {}",
+            layout.width, layout.height, layout.n_z
+        );
+        header.push_str("ElementType = MET_USHORT\n");
+        // ElementDataFile must be the last header field.
+        let _ = writeln!(header, "ElementDataFile = {data_file}");
+        Ok(header)
+    }
+}
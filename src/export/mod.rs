@@ -0,0 +1,203 @@
+//! Library-level exporters that convert an [`Nd2File`] into other formats.
+//!
+//! Each exporter is configured with builder-style methods and then run with
+//! `export(&mut nd2)`.
+
+pub mod companion;
+pub(crate) mod lzw;
+pub mod metaimage;
+pub mod multipoint;
+pub mod n5;
+pub(crate) mod napari;
+pub mod nifti;
+pub mod ome_tiff;
+pub(crate) mod ome_xml;
+pub mod ome_zarr;
+pub mod png;
+#[cfg(feature = "rerun")]
+pub mod rerun;
+pub mod tiff;
+pub mod tone;
+#[cfg(feature = "ffmpeg")]
+pub mod video;
+pub mod zarr;
+
+#[cfg(feature = "rerun")]
+pub use self::rerun::*;
+pub use companion::*;
+pub use metaimage::*;
+pub use multipoint::*;
+pub use n5::*;
+pub use nifti::*;
+pub use ome_tiff::*;
+pub use ome_xml::OME_SCHEMA_VERSION;
+pub use ome_zarr::*;
+pub use png::*;
+pub use tiff::*;
+pub use tone::*;
+#[cfg(feature = "ffmpeg")]
+pub use video::*;
+pub use zarr::*;
+
+use crate::error::{Nd2Error, Result};
+use crate::reader::Nd2File;
+use crate::types::ExpLoop;
+
+/// Plane grid of one position: T × C × Z planes of Y × X pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct PlaneLayout {
+    pub n_time: usize,
+    pub n_chan: usize,
+    pub n_z: usize,
+    pub height: usize,
+    pub width: usize,
+}
+
+impl PlaneLayout {
+    pub fn read(nd2: &mut Nd2File, position: usize) -> Result<Self> {
+        let sizes = nd2.sizes()?;
+        let get = |axis: &str| sizes.get(axis).copied().unwrap_or(1);
+        let n_pos = get("P");
+        if position >= n_pos {
+            return Err(Nd2Error::input_out_of_range(
+                "position index",
+                position,
+                n_pos,
+            ));
+        }
+        Ok(Self {
+            n_time: get("T"),
+            n_chan: get("C"),
+            n_z: get("Z"),
+            height: get("Y"),
+            width: get("X"),
+        })
+    }
+
+    /// Channels to export: all of them, or the single requested one.
+    pub fn channels(&self, channel: Option<usize>) -> Result<Vec<usize>> {
+        match channel {
+            Some(c) if c >= self.n_chan => Err(Nd2Error::input_out_of_range(
+                "channel index",
+                c,
+                self.n_chan,
+            )),
+            Some(c) => Ok(vec![c]),
+            None => Ok((0..self.n_chan).collect()),
+        }
+    }
+}
+
+/// Physical axis scales: T in ms, Z and (X, Y) in µm, when known.
+pub(crate) struct AxisScales {
+    pub t: Option<f64>,
+    pub z: Option<f64>,
+    pub xy: Option<(f64, f64)>,
+}
+
+impl AxisScales {
+    pub fn read(nd2: &mut Nd2File) -> Result<Self> {
+        let positive = |v: f64| (v.is_finite() && v > 0.0).then_some(v);
+        let mut scales = Self {
+            t: None,
+            z: None,
+            xy: None,
+        };
+        for loop_ in nd2.experiment()? {
+            match loop_ {
+                ExpLoop::TimeLoop(t) => scales.t = positive(t.parameters.period_ms),
+                ExpLoop::NETimeLoop(n) => {
+                    scales.t = n
+                        .parameters
+                        .periods
+                        .first()
+                        .and_then(|p| positive(p.period_ms))
+                }
+                ExpLoop::ZStackLoop(z) => scales.z = positive(z.parameters.step_um.abs()),
+                _ => {}
+            }
+        }
+        if let Some(scaling) = nd2.summary()?.scaling {
+            if let (Some(x), Some(y)) = (scaling.x.and_then(positive), scaling.y.and_then(positive))
+            {
+                scales.xy = Some((x, y));
+            }
+        }
+        Ok(scales)
+    }
+}
+
+/// Block-mean downsample an X-fastest volume of `dims` by `factor`. Edge
+/// voxels average whatever part of their window lies inside the volume.
+pub(crate) fn downsample(
+    volume: &[u16],
+    dims: [usize; 3],
+    factor: [usize; 3],
+) -> (Vec<u16>, [usize; 3]) {
+    if factor == [1, 1, 1] {
+        return (volume.to_vec(), dims);
+    }
+    let out_dims = [
+        ceil_div(dims[0], factor[0]),
+        ceil_div(dims[1], factor[1]),
+        ceil_div(dims[2], factor[2]),
+    ];
+    let mut out = Vec::with_capacity(out_dims.iter().product());
+    for oz in 0..out_dims[2] {
+        for oy in 0..out_dims[1] {
+            for ox in 0..out_dims[0] {
+                let (mut sum, mut count) = (0u64, 0u64);
+                for z in oz * factor[2]..((oz + 1) * factor[2]).min(dims[2]) {
+                    for y in oy * factor[1]..((oy + 1) * factor[1]).min(dims[1]) {
+                        let row = (z * dims[1] + y) * dims[0];
+                        for x in ox * factor[0]..((ox + 1) * factor[0]).min(dims[0]) {
+                            sum += volume[row + x] as u64;
+                            count += 1;
+                        }
+                    }
+                }
+                out.push(((sum + count / 2) / count) as u16);
+            }
+        }
+    }
+    (out, out_dims)
+}
+
+pub(crate) fn ceil_div(value: usize, divisor: usize) -> usize {
+    (value + divisor - 1) / divisor
+}
+
+/// Quote and escape `value` as a JSON string literal.
+pub(crate) fn json_string(value: &str) -> String {
+    let mut out = String::with_capacity(value.len() + 2);
+    out.push('"');
+    for ch in value.chars() {
+        match ch {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Escape `value` for use in XML text and attribute values.
+pub(crate) fn xml_escape(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    for ch in value.chars() {
+        match ch {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
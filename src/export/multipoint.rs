@@ -0,0 +1,78 @@
+use std::fmt::Write as _;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::xml_escape;
+use crate::error::{Nd2Error, Result};
+use crate::reader::Nd2File;
+
+/// Writes the file's XY positions as a NIS Elements multipoint list (the
+/// XML produced by "Export" in the ND Acquisition XY tab), so the same
+/// fields can be re-imported for a follow-up acquisition.
+///
+/// The list is UTF-16LE with a byte-order mark, like the files NIS writes.
+#[derive(Debug, Clone)]
+pub struct MultipointExporter {
+    path: PathBuf,
+}
+
+impl MultipointExporter {
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+        }
+    }
+
+    pub fn export(&self, nd2: &mut Nd2File) -> Result<()> {
+        let xml = multipoint_xml(nd2)?;
+        let mut bytes = vec![0xFF, 0xFE];
+        bytes.extend(xml.encode_utf16().flat_map(|unit| unit.to_le_bytes()));
+        fs::write(&self.path, bytes)?;
+        Ok(())
+    }
+}
+
+/// Build the multipoint list XML for the XY positions of `nd2`.
+fn multipoint_xml(nd2: &mut Nd2File) -> Result<String> {
+    let xy = nd2.xy_positions()?.ok_or_else(|| {
+        Nd2Error::input_argument("multipoint", "file has no XY position loop to export")
+    })?;
+    let pfs_enabled = xy.points.iter().any(|point| point.pfs_offset.is_some());
+
+    let mut xml = String::new();
+    xml.push_str("<?xml version=\"1.0\" encoding=\"UTF-16\"?>\n");
+    xml.push_str("<variant version=\"1.0\">\n");
+    xml.push_str("<no_name runtype=\"CLxListVariant\">\n");
+    let _ = writeln!(
+        xml,
+        "<bIncludeZ runtype=\"bool\" value=\"{}\"/>",
+        xy.is_setting_z
+    );
+    let _ = writeln!(
+        xml,
+        "<bPFSEnabled runtype=\"bool\" value=\"{pfs_enabled}\"/>"
+    );
+    for (i, point) in xy.points.iter().enumerate() {
+        let name = point.name.clone().unwrap_or_else(|| format!("#{}", i + 1));
+        let stage = point.stage_position_um;
+        let _ = writeln!(
+            xml,
+            "<Point{i:05} runtype=\"NDSetupMultipointListItem\">\n\
+             <bChecked runtype=\"bool\" value=\"true\"/>\n\
+             <strName runtype=\"CLxStringW\" value=\"{}\"/>\n\
+             <dXPosition runtype=\"double\" value=\"{}\"/>\n\
+             <dYPosition runtype=\"double\" value=\"{}\"/>\n\
+             <dZPosition runtype=\"double\" value=\"{}\"/>\n\
+             <dPFSOffset runtype=\"double\" value=\"{}\"/>\n\
+             <baUserData runtype=\"CLxByteArray\" value=\"\"/>\n\
+             </Point{i:05}>",
+            xml_escape(&name),
+            stage.x,
+            stage.y,
+            stage.z,
+            point.pfs_offset.unwrap_or(-1.0),
+        );
+    }
+    xml.push_str("</no_name>\n</variant>\n");
+    Ok(xml)
+}
@@ -0,0 +1,345 @@
+use std::fmt::Write as _;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use flate2::write::GzEncoder;
+use flate2::Compression;
+
+use super::{ceil_div, downsample, xml_escape, AxisScales, PlaneLayout};
+use crate::error::{Nd2Error, Result};
+use crate::reader::Nd2File;
+use crate::types::{ExpLoop, Position};
+
+/// Default N5 block size (X, Y, Z).
+pub const DEFAULT_N5_BLOCK_SIZE: [usize; 3] = [64, 64, 64];
+
+/// Writes every position as a BigDataViewer N5 container plus the companion
+/// SpimData XML, so tiled acquisitions open directly in BigStitcher.
+///
+/// Each (position, channel) pair becomes one view setup, placed with its
+/// stage position as tile location. The XML is written next to the
+/// container with an `.xml` extension (`scan.n5` → `scan.xml`).
+#[derive(Debug, Clone)]
+pub struct N5Exporter {
+    path: PathBuf,
+    channel: Option<usize>,
+    block_size: [usize; 3],
+    downsampling: Vec<[usize; 3]>,
+    compression: Option<u32>,
+}
+
+impl N5Exporter {
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+            channel: None,
+            block_size: DEFAULT_N5_BLOCK_SIZE,
+            downsampling: Vec::new(),
+            compression: None,
+        }
+    }
+
+    /// Export only one channel instead of all channels.
+    pub fn channel(mut self, channel: usize) -> Self {
+        self.channel = Some(channel);
+        self
+    }
+
+    /// Block size in pixels (X, Y, Z). Defaults to [`DEFAULT_N5_BLOCK_SIZE`].
+    pub fn block_size(mut self, block_size: [usize; 3]) -> Self {
+        self.block_size = block_size;
+        self
+    }
+
+    /// Additional resolution levels, as (X, Y, Z) downsampling factors
+    /// relative to full resolution, e.g. `[[2, 2, 1], [4, 4, 2]]`.
+    /// Each level is the mean of the covered full-resolution voxels.
+    pub fn downsampling(mut self, factors: &[[usize; 3]]) -> Self {
+        self.downsampling = factors.to_vec();
+        self
+    }
+
+    /// Compress blocks with gzip at the given level (0-9). Raw by default.
+    pub fn compression(mut self, level: u32) -> Self {
+        self.compression = Some(level.min(9));
+        self
+    }
+
+    /// Path of the companion BDV XML.
+    pub fn xml_path(&self) -> PathBuf {
+        self.path.with_extension("xml")
+    }
+
+    pub fn export(&self, nd2: &mut Nd2File) -> Result<()> {
+        if self.block_size.contains(&0) {
+            return Err(Nd2Error::input_argument(
+                "block_size",
+                "block dimensions must be non-zero",
+            ));
+        }
+        if self.downsampling.iter().flatten().any(|&f| f == 0) {
+            return Err(Nd2Error::input_argument(
+                "downsampling",
+                "downsampling factors must be non-zero",
+            ));
+        }
+        let n_pos = nd2.sizes()?.get("P").copied().unwrap_or(1);
+        let layout = PlaneLayout::read(nd2, 0)?;
+        let channels = layout.channels(self.channel)?;
+        let mut factors = vec![[1, 1, 1]];
+        factors.extend(self.downsampling.iter().copied());
+
+        fs::create_dir_all(&self.path)?;
+        fs::write(self.path.join("attributes.json"), "{\"n5\": \"2.0.0\"}\n")?;
+
+        let full = [layout.width, layout.height, layout.n_z];
+        for p in 0..n_pos {
+            for (ci, &c) in channels.iter().enumerate() {
+                let setup = self.path.join(format!("setup{}", p * channels.len() + ci));
+                fs::create_dir_all(&setup)?;
+                fs::write(
+                    setup.join("attributes.json"),
+                    format!(
+                        "{{\"downsamplingFactors\": {}, \"dataType\": \"uint16\"}}\n",
+                        json_nested(&factors)
+                    ),
+                )?;
+                for t in 0..layout.n_time {
+                    let planes: Vec<[usize; 4]> = (0..layout.n_z).map(|z| [p, t, c, z]).collect();
+                    let volume = nd2.read_planes(&planes)?.concat();
+                    let timepoint = setup.join(format!("timepoint{t}"));
+                    for (level, factor) in factors.iter().enumerate() {
+                        let (data, dims) = downsample(&volume, full, *factor);
+                        self.write_dataset(&timepoint.join(format!("s{level}")), &data, dims)?;
+                    }
+                }
+            }
+        }
+
+        fs::write(
+            self.xml_path(),
+            self.bdv_xml(nd2, n_pos, &channels, &layout)?,
+        )?;
+        Ok(())
+    }
+
+    fn write_dataset(&self, dir: &Path, data: &[u16], dims: [usize; 3]) -> Result<()> {
+        fs::create_dir_all(dir)?;
+        let compression = match self.compression {
+            Some(level) => {
+                format!("{{\"type\": \"gzip\", \"level\": {level}, \"useZlib\": false}}")
+            }
+            None => "{\"type\": \"raw\"}".to_string(),
+        };
+        fs::write(
+            dir.join("attributes.json"),
+            format!(
+                "{{\"dataType\": \"uint16\", \"compression\": {compression}, \
+                 \"blockSize\": {}, \"dimensions\": {}}}\n",
+                json_list(&self.block_size),
+                json_list(&dims)
+            ),
+        )?;
+
+        let [bx, by, bz] = self.block_size;
+        for gz in 0..ceil_div(dims[2], bz) {
+            for gy in 0..ceil_div(dims[1], by) {
+                for gx in 0..ceil_div(dims[0], bx) {
+                    let origin = [gx * bx, gy * by, gz * bz];
+                    let size = [
+                        bx.min(dims[0] - origin[0]),
+                        by.min(dims[1] - origin[1]),
+                        bz.min(dims[2] - origin[2]),
+                    ];
+                    let block = self.encode_block(data, dims, origin, size)?;
+                    let block_dir = dir.join(gx.to_string()).join(gy.to_string());
+                    fs::create_dir_all(&block_dir)?;
+                    fs::write(block_dir.join(gz.to_string()), block)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Serialize one block: N5 header (mode 0, 3 dims, block size) followed
+    /// by big-endian samples in X-fastest order, compressed as configured.
+    fn encode_block(
+        &self,
+        data: &[u16],
+        dims: [usize; 3],
+        origin: [usize; 3],
+        size: [usize; 3],
+    ) -> Result<Vec<u8>> {
+        let mut raw = Vec::with_capacity(size.iter().product::<usize>() * 2);
+        for z in origin[2]..origin[2] + size[2] {
+            for y in origin[1]..origin[1] + size[1] {
+                let row = (z * dims[1] + y) * dims[0];
+                for &v in &data[row + origin[0]..row + origin[0] + size[0]] {
+                    raw.extend_from_slice(&v.to_be_bytes());
+                }
+            }
+        }
+
+        let mut out = Vec::with_capacity(16 + raw.len());
+        out.extend_from_slice(&0u16.to_be_bytes());
+        out.extend_from_slice(&3u16.to_be_bytes());
+        for s in size {
+            out.extend_from_slice(&(s as u32).to_be_bytes());
+        }
+        match self.compression {
+            Some(level) => {
+                let mut encoder = GzEncoder::new(out, Compression::new(level));
+                encoder.write_all(&raw)?;
+                Ok(encoder.finish()?)
+            }
+            None => {
+                out.extend_from_slice(&raw);
+                Ok(out)
+            }
+        }
+    }
+
+    fn bdv_xml(
+        &self,
+        nd2: &mut Nd2File,
+        n_pos: usize,
+        channels: &[usize],
+        layout: &PlaneLayout,
+    ) -> Result<String> {
+        let scales = AxisScales::read(nd2)?;
+        let (vx, vy) = scales.xy.unwrap_or((1.0, 1.0));
+        let vz = scales.z.unwrap_or(1.0);
+        let points: Vec<Position> = nd2
+            .experiment()?
+            .iter()
+            .find_map(|loop_| match loop_ {
+                ExpLoop::XYPosLoop(xy) => Some(xy.parameters.points.clone()),
+                _ => None,
+            })
+            .unwrap_or_default();
+        let channel_names: Vec<Option<String>> = nd2
+            .summary()?
+            .channels
+            .into_iter()
+            .map(|channel| channel.name)
+            .collect();
+        let n5_name = self
+            .path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let location = |p: usize| {
+            points
+                .get(p)
+                .map(|point| point.stage_position_um)
+                .map_or((0.0, 0.0, 0.0), |s| (s.x, s.y, s.z))
+        };
+
+        let mut xml = String::new();
+        xml.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        xml.push_str("<SpimData version=\"0.2\">\n");
+        xml.push_str("  <BasePath type=\"relative\">.</BasePath>\n");
+        xml.push_str("  <SequenceDescription>\n");
+        let _ = writeln!(
+            xml,
+            "    <ImageLoader format=\"bdv.n5\" version=\"1.0\">\n      \
+             <n5 type=\"relative\">{}</n5>\n    </ImageLoader>",
+            xml_escape(&n5_name)
+        );
+        xml.push_str("    <ViewSetups>\n");
+        for p in 0..n_pos {
+            for (ci, &c) in channels.iter().enumerate() {
+                let id = p * channels.len() + ci;
+                let _ = writeln!(
+                    xml,
+                    "      <ViewSetup>\n        <id>{id}</id>\n        <name>{id}</name>\n        \
+                     <size>{} {} {}</size>\n        \
+                     <voxelSize>\n          <unit>µm</unit>\n          <size>{vx} {vy} {vz}</size>\n        </voxelSize>\n        \
+                     <attributes>\n          <illumination>0</illumination>\n          \
+                     <channel>{c}</channel>\n          <tile>{p}</tile>\n          \
+                     <angle>0</angle>\n        </attributes>\n      </ViewSetup>",
+                    layout.width, layout.height, layout.n_z
+                );
+            }
+        }
+        xml.push_str(
+            "      <Attributes name=\"illumination\">\n        \
+             <Illumination>\n          <id>0</id>\n          <name>0</name>\n        \
+             </Illumination>\n      </Attributes>\n",
+        );
+        xml.push_str("      <Attributes name=\"channel\">\n");
+        for &c in channels {
+            let name = channel_names
+                .get(c)
+                .cloned()
+                .flatten()
+                .unwrap_or_else(|| c.to_string());
+            let _ = writeln!(
+                xml,
+                "        <Channel>\n          <id>{c}</id>\n          <name>{}</name>\n        </Channel>",
+                xml_escape(&name)
+            );
+        }
+        xml.push_str("      </Attributes>\n");
+        xml.push_str("      <Attributes name=\"tile\">\n");
+        for p in 0..n_pos {
+            let name = points
+                .get(p)
+                .and_then(|point| point.name.clone())
+                .unwrap_or_else(|| p.to_string());
+            let (x, y, z) = location(p);
+            let _ = writeln!(
+                xml,
+                "        <Tile>\n          <id>{p}</id>\n          <name>{}</name>\n          \
+                 <location>{x} {y} {z}</location>\n        </Tile>",
+                xml_escape(&name)
+            );
+        }
+        xml.push_str("      </Attributes>\n");
+        xml.push_str(
+            "      <Attributes name=\"angle\">\n        \
+             <Angle>\n          <id>0</id>\n          <name>0</name>\n        \
+             </Angle>\n      </Attributes>\n",
+        );
+        xml.push_str("    </ViewSetups>\n");
+        let _ = writeln!(
+            xml,
+            "    <Timepoints type=\"range\">\n      <first>0</first>\n      \
+             <last>{}</last>\n    </Timepoints>",
+            layout.n_time.saturating_sub(1)
+        );
+        xml.push_str("  </SequenceDescription>\n");
+
+        // Voxel calibration and stage translation in µm, one affine per view.
+        xml.push_str("  <ViewRegistrations>\n");
+        for t in 0..layout.n_time {
+            for p in 0..n_pos {
+                let (x, y, z) = location(p);
+                for ci in 0..channels.len() {
+                    let _ = writeln!(
+                        xml,
+                        "    <ViewRegistration timepoint=\"{t}\" setup=\"{}\">\n      \
+                         <ViewTransform type=\"affine\">\n        <Name>calibration</Name>\n        \
+                         <affine>{vx} 0.0 0.0 {x} 0.0 {vy} 0.0 {y} 0.0 0.0 {vz} {z}</affine>\n      \
+                         </ViewTransform>\n    </ViewRegistration>",
+                        p * channels.len() + ci
+                    );
+                }
+            }
+        }
+        xml.push_str("  </ViewRegistrations>\n");
+        xml.push_str("</SpimData>\n");
+        Ok(xml)
+    }
+}
+
+fn json_list(values: &[usize]) -> String {
+    let items: Vec<String> = values.iter().map(|v| v.to_string()).collect();
+    format!("[{}]", items.join(", "))
+}
+
+fn json_nested(values: &[[usize; 3]]) -> String {
+    let items: Vec<String> = values.iter().map(|v| json_list(v)).collect();
+    format!("[{}]", items.join(", "))
+}
@@ -0,0 +1,64 @@
+use super::{AxisScales, PlaneLayout};
+use crate::error::Result;
+use crate::reader::Nd2File;
+use crate::types::{ExpLoop, NapariColormap, NapariLayer};
+
+/// napari's own defaults when splitting channels with `channel_axis`.
+const TWO_CHANNEL_COLORMAPS: [&str; 2] = ["magenta", "green"];
+const MULTI_CHANNEL_COLORMAPS: [&str; 6] = ["cyan", "yellow", "magenta", "red", "green", "blue"];
+
+/// Layer kwargs for every channel of `position`.
+pub(crate) fn layers(nd2: &mut Nd2File, position: usize) -> Result<Vec<NapariLayer>> {
+    let layout = PlaneLayout::read(nd2, position)?;
+    let scales = AxisScales::read(nd2)?;
+    let (sx, sy) = scales.xy.unwrap_or((1.0, 1.0));
+    let scale = [scales.t.unwrap_or(1.0), scales.z.unwrap_or(1.0), sy, sx];
+    let stage = nd2.experiment()?.iter().find_map(|loop_| match loop_ {
+        ExpLoop::XYPosLoop(xy) => xy
+            .parameters
+            .points
+            .get(position)
+            .map(|point| point.stage_position_um),
+        _ => None,
+    });
+    let translate = stage.map_or([0.0; 4], |s| [0.0, s.z, s.y, s.x]);
+
+    let attrs = nd2.attributes()?;
+    let bits = match attrs.bits_per_component_significant {
+        0 => attrs.bits_per_component_in_memory,
+        bits => bits,
+    };
+    let contrast_limits = [0.0, 2f64.powi(bits.min(64) as i32) - 1.0];
+
+    let summary = nd2.summary()?;
+    let n_chan = layout.n_chan;
+    Ok((0..n_chan)
+        .map(|c| {
+            let channel = summary.channels.get(c);
+            let name = channel
+                .and_then(|channel| channel.name.clone())
+                .unwrap_or_else(|| format!("Channel {c}"));
+            let colormap = match channel.and_then(|channel| channel.color.clone()) {
+                Some(color) => NapariColormap::Custom(name.clone(), ["#000000".into(), color]),
+                None if n_chan == 1 => NapariColormap::Named("gray".into()),
+                None if n_chan == 2 => NapariColormap::Named(TWO_CHANNEL_COLORMAPS[c].into()),
+                None => NapariColormap::Named(
+                    MULTI_CHANNEL_COLORMAPS[c % MULTI_CHANNEL_COLORMAPS.len()].into(),
+                ),
+            };
+            NapariLayer {
+                name,
+                scale,
+                translate,
+                colormap,
+                contrast_limits,
+                blending: if n_chan > 1 {
+                    "additive"
+                } else {
+                    "translucent"
+                }
+                .into(),
+            }
+        })
+        .collect())
+}
@@ -0,0 +1,158 @@
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use flate2::write::GzEncoder;
+use flate2::Compression;
+
+use super::{AxisScales, PlaneLayout};
+use crate::error::{Nd2Error, Result};
+use crate::reader::Nd2File;
+use crate::types::ExpLoop;
+
+/// Size of the NIfTI-1 header; pixel data starts after a 4-byte extension
+/// flag at [`NIFTI_VOX_OFFSET`].
+const NIFTI_HEADER_SIZE: usize = 348;
+const NIFTI_VOX_OFFSET: usize = 352;
+const DT_UINT16: i16 = 512;
+const NIFTI_UNITS_MICRON: u8 = 3;
+const NIFTI_XFORM_SCANNER_ANAT: i16 = 1;
+
+/// Writes one Z-stack as a single-file NIfTI-1 volume (`.nii`), with voxel
+/// size in µm and the stage position as the qform/sform origin.
+///
+/// Paths ending in `.gz` are gzip-compressed (`.nii.gz`).
+#[derive(Debug, Clone)]
+pub struct NiftiExporter {
+    path: PathBuf,
+    position: usize,
+    timepoint: usize,
+    channel: usize,
+}
+
+impl NiftiExporter {
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+            position: 0,
+            timepoint: 0,
+            channel: 0,
+        }
+    }
+
+    /// Position (P index) to export. Defaults to 0.
+    pub fn position(mut self, position: usize) -> Self {
+        self.position = position;
+        self
+    }
+
+    /// Time point (T index) to export. Defaults to 0.
+    pub fn timepoint(mut self, timepoint: usize) -> Self {
+        self.timepoint = timepoint;
+        self
+    }
+
+    /// Channel (C index) to export. Defaults to 0.
+    pub fn channel(mut self, channel: usize) -> Self {
+        self.channel = channel;
+        self
+    }
+
+    pub fn export(&self, nd2: &mut Nd2File) -> Result<()> {
+        let layout = PlaneLayout::read(nd2, self.position)?;
+        layout.channels(Some(self.channel))?;
+        if self.timepoint >= layout.n_time {
+            return Err(Nd2Error::input_out_of_range(
+                "time index",
+                self.timepoint,
+                layout.n_time,
+            ));
+        }
+        let dims = [layout.width, layout.height, layout.n_z];
+        if let Some(&len) = dims.iter().find(|&&len| len > i16::MAX as usize) {
+            return Err(Nd2Error::input_argument(
+                "nifti",
+                format!("dimension {len} exceeds the NIfTI-1 limit of {}", i16::MAX),
+            ));
+        }
+
+        let mut out = self.header(nd2, dims)?;
+        let planes: Vec<[usize; 4]> = (0..layout.n_z)
+            .map(|z| [self.position, self.timepoint, self.channel, z])
+            .collect();
+        for plane in nd2.read_planes(&planes)? {
+            out.extend(plane.iter().flat_map(|p| p.to_le_bytes()));
+        }
+
+        let gzip = self
+            .path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("gz"));
+        if gzip {
+            let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(&out)?;
+            out = encoder.finish()?;
+        }
+        fs::write(&self.path, out)?;
+        Ok(())
+    }
+
+    /// Little-endian NIfTI-1 header plus the empty extension flag.
+    fn header(&self, nd2: &mut Nd2File, dims: [usize; 3]) -> Result<Vec<u8>> {
+        let scales = AxisScales::read(nd2)?;
+        let (sx, sy) = scales.xy.unwrap_or((1.0, 1.0));
+        let spacing = [sx as f32, sy as f32, scales.z.unwrap_or(1.0) as f32];
+        let origin = nd2
+            .experiment()?
+            .iter()
+            .find_map(|loop_| match loop_ {
+                ExpLoop::XYPosLoop(xy) => xy.parameters.points.get(self.position).cloned(),
+                _ => None,
+            })
+            .map_or([0.0; 3], |point| {
+                let s = point.stage_position_um;
+                [s.x as f32, s.y as f32, s.z as f32]
+            });
+
+        let mut h = vec![0u8; NIFTI_VOX_OFFSET];
+        let put_i16 =
+            |h: &mut [u8], at: usize, v: i16| h[at..at + 2].copy_from_slice(&v.to_le_bytes());
+        let put_f32 =
+            |h: &mut [u8], at: usize, v: f32| h[at..at + 4].copy_from_slice(&v.to_le_bytes());
+
+        h[0..4].copy_from_slice(&(NIFTI_HEADER_SIZE as i32).to_le_bytes());
+        h[38] = b'r';
+        let dim = [3, dims[0], dims[1], dims[2], 1, 1, 1, 1];
+        for (i, &d) in dim.iter().enumerate() {
+            put_i16(&mut h, 40 + 2 * i, d as i16);
+        }
+        put_i16(&mut h, 70, DT_UINT16);
+        put_i16(&mut h, 72, 16);
+        // pixdim[0] is the qform handedness factor.
+        put_f32(&mut h, 76, 1.0);
+        for (i, &s) in spacing.iter().enumerate() {
+            put_f32(&mut h, 80 + 4 * i, s);
+        }
+        put_f32(&mut h, 108, NIFTI_VOX_OFFSET as f32);
+        put_f32(&mut h, 112, 1.0);
+        h[123] = NIFTI_UNITS_MICRON;
+        let descrip = format!(
+            "nd2-rs P{} T{} C{}",
+            self.position, self.timepoint, self.channel
+        );
+        h[148..148 + descrip.len()].copy_from_slice(descrip.as_bytes());
+
+        // Identity rotation (quaternion b = c = d = 0) offset to the stage position.
+        put_i16(&mut h, 252, NIFTI_XFORM_SCANNER_ANAT);
+        put_i16(&mut h, 254, NIFTI_XFORM_SCANNER_ANAT);
+        for (i, &o) in origin.iter().enumerate() {
+            put_f32(&mut h, 268 + 4 * i, o);
+        }
+        for row in 0..3 {
+            put_f32(&mut h, 280 + 16 * row + 4 * row, spacing[row]);
+            put_f32(&mut h, 280 + 16 * row + 12, origin[row]);
+        }
+        h[344..348].copy_from_slice(b"n+1\0");
+        Ok(h)
+    }
+}
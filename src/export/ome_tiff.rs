@@ -0,0 +1,132 @@
+use std::fs::File;
+use std::io::{BufWriter, Seek, Write};
+use std::path::{Path, PathBuf};
+
+use super::tiff::{TiffPage, TiffWriter};
+use super::{downsample, ome_xml, PlaneLayout, TiffCompression, TiffLayout};
+use crate::error::{Nd2Error, Result};
+use crate::reader::Nd2File;
+
+/// Writes every position as one `Image` of a 16-bit OME-TIFF, with the
+/// OME-XML from [`Nd2File::ome_xml`] (channels, physical sizes, per-plane
+/// DeltaT and stage position) in the first page's ImageDescription.
+///
+/// Pages follow position by position in XYCZT order (C fastest, then Z,
+/// then T). A BigTIFF is written when the uncompressed planes would not fit
+/// in 4 GiB, unless [`OmeTiffExporter::big_tiff`] says otherwise.
+///
+/// With [`OmeTiffExporter::pyramid`], every page also carries
+/// reduced-resolution versions of itself as SubIFDs, for whole-slide
+/// viewers. Each level is computed from the one before while the page is
+/// written, so memory holds one time point's planes and two levels at most.
+#[derive(Debug, Clone)]
+pub struct OmeTiffExporter {
+    path: PathBuf,
+    layout: TiffLayout,
+    compression: TiffCompression,
+    big_tiff: Option<bool>,
+    pyramid: usize,
+}
+
+impl OmeTiffExporter {
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+            layout: TiffLayout::default(),
+            compression: TiffCompression::None,
+            big_tiff: None,
+            pyramid: 0,
+        }
+    }
+
+    /// Strip or tile layout of each page. Defaults to strips of 64 rows.
+    pub fn layout(mut self, layout: TiffLayout) -> Self {
+        self.layout = layout;
+        self
+    }
+
+    /// Compression of each strip or tile. Uncompressed by default.
+    pub fn compression(mut self, compression: TiffCompression) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    /// Always (`true`) or never (`false`) write a BigTIFF.
+    pub fn big_tiff(mut self, big_tiff: bool) -> Self {
+        self.big_tiff = Some(big_tiff);
+        self
+    }
+
+    /// Add `levels` reduced-resolution versions of every page, each half the
+    /// width and height of the one before (2×2 mean). Needs a tiled layout.
+    pub fn pyramid(mut self, levels: usize) -> Self {
+        self.pyramid = levels;
+        self
+    }
+
+    pub fn export(&self, nd2: &mut Nd2File) -> Result<()> {
+        if self.pyramid > 0 && matches!(self.layout, TiffLayout::Strips { .. }) {
+            return Err(Nd2Error::input_argument(
+                "pyramid",
+                "resolution levels need a tiled layout",
+            ));
+        }
+        let xml = ome_xml::build_for_tiff(nd2)?;
+        let n_pos = nd2.sizes()?.get("P").copied().unwrap_or(1);
+        let layout = PlaneLayout::read(nd2, 0)?;
+        let channels = layout.channels(None)?;
+        let mut plane_bytes = (layout.width * layout.height * 2) as u64;
+        if self.pyramid > 0 {
+            // The levels add up to less than a third of the full page.
+            plane_bytes += plane_bytes / 3;
+        }
+        let pages = (n_pos * layout.n_time * layout.n_z * channels.len()) as u64;
+        // Leave room for the IFDs and the OME-XML.
+        let estimate = pages * (plane_bytes + 1024) + xml.len() as u64;
+        let big = self.big_tiff.unwrap_or(estimate > u64::from(u32::MAX));
+
+        let file = BufWriter::new(File::create(&self.path)?);
+        let mut writer = TiffWriter::new(file, big)?;
+        let mut page = TiffPage::new(layout.width, layout.height);
+        page.layout = self.layout;
+        page.compression = self.compression;
+        for p in 0..n_pos {
+            for t in 0..layout.n_time {
+                let planes: Vec<[usize; 4]> = (0..layout.n_z)
+                    .flat_map(|z| channels.iter().map(move |&c| [p, t, c, z]))
+                    .collect();
+                for plane in nd2.read_planes(&planes)? {
+                    page.description = (writer.pages == 0).then(|| xml.clone());
+                    let sub_ifds = self.write_levels(&mut writer, &page, &plane)?;
+                    writer.write_page(&page, &plane, &sub_ifds)?;
+                }
+            }
+        }
+        writer.finish()?;
+        Ok(())
+    }
+
+    /// Write the reduced-resolution levels of one page and return their IFD
+    /// offsets.
+    fn write_levels<W: Write + Seek>(
+        &self,
+        writer: &mut TiffWriter<W>,
+        page: &TiffPage,
+        plane: &[u16],
+    ) -> Result<Vec<u64>> {
+        let mut offsets = Vec::with_capacity(self.pyramid);
+        let mut level = TiffPage::new(page.width, page.height);
+        level.layout = page.layout;
+        level.compression = page.compression;
+        let mut previous: Option<Vec<u16>> = None;
+        for _ in 0..self.pyramid {
+            let source = previous.as_deref().unwrap_or(plane);
+            let (pixels, dims) = downsample(source, [level.width, level.height, 1], [2, 2, 1]);
+            level.width = dims[0];
+            level.height = dims[1];
+            offsets.push(writer.write_reduced(&level, &pixels)?);
+            previous = Some(pixels);
+        }
+        Ok(offsets)
+    }
+}
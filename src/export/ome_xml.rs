@@ -0,0 +1,187 @@
+use std::fmt::Write as _;
+
+use super::{xml_escape, PlaneLayout};
+use crate::error::{Nd2Error, Result};
+use crate::reader::Nd2File;
+use crate::types::{ExpLoop, PixelDataType};
+
+/// OME schema release the generated XML targets.
+pub const OME_SCHEMA_VERSION: &str = "2016-06";
+
+const OME_NAMESPACE: &str = "http://www.openmicroscopy.org/Schemas/OME/2016-06";
+
+/// Build an OME-XML document describing every position of `nd2` as one
+/// `Image`, with channel, physical size and per-plane DeltaT/Position
+/// metadata. Pixels are referenced as `MetadataOnly`.
+pub(crate) fn build(nd2: &mut Nd2File) -> Result<String> {
+    build_document(nd2, PixelData::MetadataOnly)
+}
+
+/// [`build`] for an OME-TIFF holding every plane as a 16-bit page, one
+/// position after the other in XYCZT order.
+pub(crate) fn build_for_tiff(nd2: &mut Nd2File) -> Result<String> {
+    build_document(nd2, PixelData::Tiff)
+}
+
+/// [`build`] for a companion file next to the ND2 file named `source`,
+/// which every `Image` references through a map annotation.
+pub(crate) fn build_companion(nd2: &mut Nd2File, source: &str) -> Result<String> {
+    build_document(nd2, PixelData::Companion(source))
+}
+
+/// Where the document says the pixels are.
+#[derive(Clone, Copy)]
+enum PixelData<'a> {
+    MetadataOnly,
+    Tiff,
+    Companion(&'a str),
+}
+
+fn build_document(nd2: &mut Nd2File, pixels: PixelData<'_>) -> Result<String> {
+    let tiff = matches!(pixels, PixelData::Tiff);
+    let attrs = nd2.attributes()?.clone();
+    let summary = nd2.summary()?;
+    let n_pos = summary.sizes.get("P").copied().unwrap_or(1);
+    let layout = PlaneLayout::read(nd2, 0)?;
+    let n_chan = layout.n_chan.max(1);
+    let samples = (attrs.component_count as usize / n_chan).max(1);
+    let pixel_type = match (attrs.pixel_data_type, attrs.bits_per_component_in_memory) {
+        _ if tiff => "uint16",
+        (PixelDataType::Unsigned, 8) => "uint8",
+        (PixelDataType::Unsigned, 16) => "uint16",
+        (PixelDataType::Unsigned, 32) => "uint32",
+        (PixelDataType::Float, 32) => "float",
+        (PixelDataType::Float, 64) => "double",
+        (data_type, bits) => {
+            return Err(Nd2Error::input_argument(
+                "ome-xml",
+                format!("no OME pixel type for {data_type:?} with {bits} bits"),
+            ))
+        }
+    };
+
+    let mut time_increment = None;
+    let mut z_step = None;
+    let mut points = Vec::new();
+    for loop_ in nd2.experiment()? {
+        match loop_ {
+            ExpLoop::TimeLoop(t) => time_increment = Some(t.parameters.period_ms),
+            ExpLoop::ZStackLoop(z) => z_step = Some(z.parameters.step_um.abs()),
+            ExpLoop::XYPosLoop(xy) => points = xy.parameters.points.clone(),
+            _ => {}
+        }
+    }
+    let positive = |v: Option<f64>| v.filter(|v| v.is_finite() && *v > 0.0);
+    let scaling = summary.scaling.as_ref();
+
+    let mut xml = String::new();
+    xml.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    let _ = writeln!(
+        xml,
+        "<OME xmlns=\"{ns}\" xmlns:xsi=\"http://www.w3.org/2001/XMLSchema-instance\" \
+         xsi:schemaLocation=\"{ns} {ns}/ome.xsd\" Creator=\"nd2-rs {version}\">",
+        ns = OME_NAMESPACE,
+        version = env!("CARGO_PKG_VERSION"),
+    );
+    for p in 0..n_pos {
+        let point = points.get(p);
+        let name = point
+            .and_then(|point| point.name.clone())
+            .unwrap_or_else(|| format!("Position {p}"));
+        let _ = writeln!(
+            xml,
+            "  <Image ID=\"Image:{p}\" Name=\"{}\">",
+            xml_escape(&name)
+        );
+        let _ = write!(
+            xml,
+            "    <Pixels ID=\"Pixels:{p}\" DimensionOrder=\"XYCZT\" Type=\"{pixel_type}\" \
+             BigEndian=\"false\" Interleaved=\"false\" \
+             SizeX=\"{}\" SizeY=\"{}\" SizeZ=\"{}\" SizeC=\"{}\" SizeT=\"{}\"",
+            layout.width,
+            layout.height,
+            layout.n_z,
+            n_chan * samples,
+            layout.n_time,
+        );
+        if attrs.bits_per_component_significant > 0 {
+            let _ = write!(
+                xml,
+                " SignificantBits=\"{}\"",
+                attrs.bits_per_component_significant
+            );
+        }
+        if let Some(x) = positive(scaling.and_then(|s| s.x)) {
+            let _ = write!(xml, " PhysicalSizeX=\"{x}\" PhysicalSizeXUnit=\"µm\"");
+        }
+        if let Some(y) = positive(scaling.and_then(|s| s.y)) {
+            let _ = write!(xml, " PhysicalSizeY=\"{y}\" PhysicalSizeYUnit=\"µm\"");
+        }
+        if let Some(z) = positive(z_step.or(scaling.and_then(|s| s.z))) {
+            let _ = write!(xml, " PhysicalSizeZ=\"{z}\" PhysicalSizeZUnit=\"µm\"");
+        }
+        if let Some(dt) = positive(time_increment) {
+            let _ = write!(xml, " TimeIncrement=\"{dt}\" TimeIncrementUnit=\"ms\"");
+        }
+        xml.push_str(">\n");
+
+        for (c, channel) in summary.channels.iter().enumerate().take(n_chan) {
+            let _ = write!(
+                xml,
+                "      <Channel ID=\"Channel:{p}:{c}\" SamplesPerPixel=\"{samples}\""
+            );
+            if let Some(name) = &channel.name {
+                let _ = write!(xml, " Name=\"{}\"", xml_escape(name));
+            }
+            xml.push_str("/>\n");
+        }
+        if tiff {
+            let planes = layout.n_time * layout.n_z * n_chan;
+            let _ = writeln!(
+                xml,
+                "      <TiffData IFD=\"{}\" PlaneCount=\"{planes}\"/>",
+                p * planes
+            );
+        } else {
+            xml.push_str("      <MetadataOnly/>\n");
+        }
+
+        for t in 0..layout.n_time {
+            for z in 0..layout.n_z {
+                for c in 0..n_chan {
+                    let seq = nd2.seq_index_from_coords(p, t, c, z)?;
+                    let _ = write!(xml, "      <Plane TheZ=\"{z}\" TheT=\"{t}\" TheC=\"{c}\"");
+                    if let Some(dt) = nd2.frame_timestamp(seq)?.filter(|v| v.is_finite()) {
+                        let _ = write!(xml, " DeltaT=\"{dt}\" DeltaTUnit=\"ms\"");
+                    }
+                    if let Some(stage) = point.map(|point| point.stage_position_um) {
+                        let _ = write!(
+                            xml,
+                            " PositionX=\"{}\" PositionXUnit=\"µm\" \
+                             PositionY=\"{}\" PositionYUnit=\"µm\" \
+                             PositionZ=\"{}\" PositionZUnit=\"µm\"",
+                            stage.x, stage.y, stage.z
+                        );
+                    }
+                    xml.push_str("/>\n");
+                }
+            }
+        }
+        xml.push_str("    </Pixels>\n");
+        if let PixelData::Companion(_) = pixels {
+            xml.push_str("    <AnnotationRef ID=\"Annotation:SourceFile\"/>\n");
+        }
+        xml.push_str("  </Image>\n");
+    }
+    if let PixelData::Companion(source) = pixels {
+        let _ = write!(
+            xml,
+            "  <StructuredAnnotations>\n    <MapAnnotation ID=\"Annotation:SourceFile\">\n      \
+             <Value>\n        <M K=\"SourceFile\">{}</M>\n      </Value>\n    </MapAnnotation>\n  \
+             </StructuredAnnotations>\n",
+            xml_escape(source)
+        );
+    }
+    xml.push_str("</OME>\n");
+    Ok(xml)
+}
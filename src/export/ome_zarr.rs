@@ -0,0 +1,258 @@
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use flate2::write::GzEncoder;
+use flate2::Compression;
+
+use super::{json_string, AxisScales, PlaneLayout};
+use crate::checksum::crc32c;
+use crate::error::{Nd2Error, Result};
+use crate::reader::Nd2File;
+
+/// Writes one position as an OME-NGFF 0.5 image (Zarr v3) with axes
+/// `t, c, z, y, x` and physical scales taken from the file metadata.
+///
+/// Chunks hold one Y × X plane. With [`OmeZarrExporter::sharded`], the
+/// planes of each (T, C) Z-stack are packed into a single shard object.
+#[derive(Debug, Clone)]
+pub struct OmeZarrExporter {
+    path: PathBuf,
+    position: usize,
+    channel: Option<usize>,
+    compression: Option<u32>,
+    sharded: bool,
+}
+
+impl OmeZarrExporter {
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+            position: 0,
+            channel: None,
+            compression: None,
+            sharded: false,
+        }
+    }
+
+    /// Position (P index) to export. Defaults to 0.
+    pub fn position(mut self, position: usize) -> Self {
+        self.position = position;
+        self
+    }
+
+    /// Export only one channel instead of all channels.
+    pub fn channel(mut self, channel: usize) -> Self {
+        self.channel = Some(channel);
+        self
+    }
+
+    /// Compress chunks with gzip at the given level (0-9). Uncompressed by default.
+    pub fn compression(mut self, level: u32) -> Self {
+        self.compression = Some(level.min(9));
+        self
+    }
+
+    /// Store each Z-stack as one shard instead of one object per plane.
+    pub fn sharded(mut self, sharded: bool) -> Self {
+        self.sharded = sharded;
+        self
+    }
+
+    pub fn export(&self, nd2: &mut Nd2File) -> Result<()> {
+        let layout = PlaneLayout::read(nd2, self.position)?;
+        let channels = layout.channels(self.channel)?;
+        let scales = AxisScales::read(nd2)?;
+        let array_dir = self.path.join("0");
+        fs::create_dir_all(&array_dir)?;
+
+        fs::write(
+            self.path.join("zarr.json"),
+            self.group_metadata(&scales, nd2.version()),
+        )?;
+        let shape = [
+            layout.n_time,
+            channels.len(),
+            layout.n_z,
+            layout.height,
+            layout.width,
+        ];
+        fs::write(array_dir.join("zarr.json"), self.array_metadata(&shape))?;
+
+        for t in 0..layout.n_time {
+            let planes: Vec<[usize; 4]> = channels
+                .iter()
+                .flat_map(|&c| (0..layout.n_z).map(move |z| [self.position, t, c, z]))
+                .collect();
+            let mut planes = nd2.read_planes(&planes)?.into_iter();
+            for ci in 0..channels.len() {
+                let mut shard = Vec::new();
+                let mut index = Vec::with_capacity(layout.n_z * 16);
+                for z in 0..layout.n_z {
+                    let plane = planes.next().ok_or_else(|| {
+                        Nd2Error::internal_invariant(
+                            "read_planes returned fewer planes than requested",
+                        )
+                    })?;
+                    let data = self.encode_chunk(&plane)?;
+                    if self.sharded {
+                        index.extend_from_slice(&(shard.len() as u64).to_le_bytes());
+                        index.extend_from_slice(&(data.len() as u64).to_le_bytes());
+                        shard.extend_from_slice(&data);
+                    } else {
+                        let dir = array_dir
+                            .join("c")
+                            .join(t.to_string())
+                            .join(ci.to_string())
+                            .join(z.to_string())
+                            .join("0");
+                        fs::create_dir_all(&dir)?;
+                        fs::write(dir.join("0"), data)?;
+                    }
+                }
+                if self.sharded {
+                    shard.extend_from_slice(&index);
+                    shard.extend_from_slice(&crc32c(&index).to_le_bytes());
+                    let dir = array_dir
+                        .join("c")
+                        .join(t.to_string())
+                        .join(ci.to_string())
+                        .join("0")
+                        .join("0");
+                    fs::create_dir_all(&dir)?;
+                    fs::write(dir.join("0"), shard)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn encode_chunk(&self, plane: &[u16]) -> Result<Vec<u8>> {
+        let raw: Vec<u8> = plane.iter().flat_map(|p| p.to_le_bytes()).collect();
+        Ok(match self.compression {
+            Some(level) => {
+                let mut encoder = GzEncoder::new(Vec::new(), Compression::new(level));
+                encoder.write_all(&raw)?;
+                encoder.finish()?
+            }
+            None => raw,
+        })
+    }
+
+    fn chunk_codecs(&self) -> String {
+        let bytes = "{\"name\": \"bytes\", \"configuration\": {\"endian\": \"little\"}}";
+        match self.compression {
+            Some(level) => format!(
+                "[{bytes}, {{\"name\": \"gzip\", \"configuration\": {{\"level\": {level}}}}}]"
+            ),
+            None => format!("[{bytes}]"),
+        }
+    }
+
+    fn array_metadata(&self, shape: &[usize; 5]) -> String {
+        let plane_chunk = [1, 1, 1, shape[3], shape[4]];
+        let (chunk_shape, codecs) = if self.sharded {
+            let shard = [1, 1, shape[2], shape[3], shape[4]];
+            let codecs = format!(
+                concat!(
+                    "[{{\"name\": \"sharding_indexed\", \"configuration\": {{",
+                    "\"chunk_shape\": {}, \"codecs\": {}, ",
+                    "\"index_codecs\": [{{\"name\": \"bytes\", \"configuration\": {{\"endian\": \"little\"}}}}, ",
+                    "{{\"name\": \"crc32c\"}}], \"index_location\": \"end\"}}}}]"
+                ),
+                json_list(&plane_chunk),
+                self.chunk_codecs()
+            );
+            (shard, codecs)
+        } else {
+            (plane_chunk, self.chunk_codecs())
+        };
+        format!(
+            concat!(
+                "{{\n",
+                "  \"zarr_format\": 3,\n",
+                "  \"node_type\": \"array\",\n",
+                "  \"shape\": {},\n",
+                "  \"data_type\": \"uint16\",\n",
+                "  \"chunk_grid\": {{\"name\": \"regular\", \"configuration\": {{\"chunk_shape\": {}}}}},\n",
+                "  \"chunk_key_encoding\": {{\"name\": \"default\", \"configuration\": {{\"separator\": \"/\"}}}},\n",
+                "  \"fill_value\": 0,\n",
+                "  \"codecs\": {},\n",
+                "  \"dimension_names\": [\"t\", \"c\", \"z\", \"y\", \"x\"]\n",
+                "}}\n"
+            ),
+            json_list(shape),
+            json_list(&chunk_shape),
+            codecs
+        )
+    }
+
+    fn group_metadata(&self, scales: &AxisScales, version: (u32, u32)) -> String {
+        let space = |unit: bool| {
+            if unit {
+                ", \"unit\": \"micrometer\""
+            } else {
+                ""
+            }
+        };
+        let axes = format!(
+            concat!(
+                "[{{\"name\": \"t\", \"type\": \"time\"{}}}, ",
+                "{{\"name\": \"c\", \"type\": \"channel\"}}, ",
+                "{{\"name\": \"z\", \"type\": \"space\"{}}}, ",
+                "{{\"name\": \"y\", \"type\": \"space\"{}}}, ",
+                "{{\"name\": \"x\", \"type\": \"space\"{}}}]"
+            ),
+            if scales.t.is_some() {
+                ", \"unit\": \"millisecond\""
+            } else {
+                ""
+            },
+            space(scales.z.is_some()),
+            space(scales.xy.is_some()),
+            space(scales.xy.is_some()),
+        );
+        let (x, y) = scales.xy.unwrap_or((1.0, 1.0));
+        let scale = [scales.t.unwrap_or(1.0), 1.0, scales.z.unwrap_or(1.0), y, x];
+        let scale: Vec<String> = scale.iter().map(|v| json_number(*v)).collect();
+        let name = json_string(&format!(
+            "ND2 v{}.{} position {}",
+            version.0, version.1, self.position
+        ));
+        format!(
+            concat!(
+                "{{\n",
+                "  \"zarr_format\": 3,\n",
+                "  \"node_type\": \"group\",\n",
+                "  \"attributes\": {{\n",
+                "    \"ome\": {{\n",
+                "      \"version\": \"0.5\",\n",
+                "      \"multiscales\": [{{\n",
+                "        \"name\": {},\n",
+                "        \"axes\": {},\n",
+                "        \"datasets\": [{{\"path\": \"0\", \"coordinateTransformations\": ",
+                "[{{\"type\": \"scale\", \"scale\": [{}]}}]}}]\n",
+                "      }}]\n",
+                "    }}\n",
+                "  }}\n",
+                "}}\n"
+            ),
+            name,
+            axes,
+            scale.join(", ")
+        )
+    }
+}
+
+fn json_list(values: &[usize]) -> String {
+    let items: Vec<String> = values.iter().map(|v| v.to_string()).collect();
+    format!("[{}]", items.join(", "))
+}
+
+fn json_number(value: f64) -> String {
+    if value.fract() == 0.0 && value.abs() < 1e15 {
+        format!("{value:.1}")
+    } else {
+        value.to_string()
+    }
+}
@@ -0,0 +1,213 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+
+use super::{PlaneLayout, ToneLut, ToneMapping, ToneRange};
+use crate::error::{Nd2Error, Result};
+use crate::reader::Nd2File;
+
+/// Writes every plane of one position as a 16-bit grayscale PNG named
+/// `t{T}_c{C}_z{Z}.png` inside an output directory, or as 8-bit previews
+/// with [`PngExporter::tone_mapping`].
+#[derive(Debug, Clone)]
+pub struct PngExporter {
+    dir: PathBuf,
+    position: usize,
+    channel: Option<usize>,
+    compression: u32,
+    tone_mapping: Option<ToneMapping>,
+}
+
+impl PngExporter {
+    pub fn new<P: AsRef<Path>>(dir: P) -> Self {
+        Self {
+            dir: dir.as_ref().to_path_buf(),
+            position: 0,
+            channel: None,
+            compression: 6,
+            tone_mapping: None,
+        }
+    }
+
+    /// Position (P index) to export. Defaults to 0.
+    pub fn position(mut self, position: usize) -> Self {
+        self.position = position;
+        self
+    }
+
+    /// Export only one channel instead of all channels.
+    pub fn channel(mut self, channel: usize) -> Self {
+        self.channel = Some(channel);
+        self
+    }
+
+    /// Zlib compression level (0-9). Defaults to 6.
+    pub fn compression(mut self, level: u32) -> Self {
+        self.compression = level.min(9);
+        self
+    }
+
+    /// Write 8-bit grayscale previews mapped with `tone_mapping` instead of
+    /// 16-bit PNGs. [`ToneRange::Auto`] limits are computed per plane.
+    pub fn tone_mapping(mut self, tone_mapping: ToneMapping) -> Self {
+        self.tone_mapping = Some(tone_mapping);
+        self
+    }
+
+    pub fn export(&self, nd2: &mut Nd2File) -> Result<()> {
+        let layout = PlaneLayout::read(nd2, self.position)?;
+        let channels = layout.channels(self.channel)?;
+        std::fs::create_dir_all(&self.dir)?;
+        let fixed_lut = match self.tone_mapping {
+            Some(
+                tone @ ToneMapping {
+                    range: ToneRange::Fixed { min, max },
+                    ..
+                },
+            ) => Some(tone.lut((min, max))),
+            _ => None,
+        };
+        let mut preview = Vec::new();
+        for t in 0..layout.n_time {
+            let planes: Vec<[usize; 4]> = (0..layout.n_z)
+                .flat_map(|z| channels.iter().map(move |&c| [self.position, t, c, z]))
+                .collect();
+            for (&[_, _, c, z], plane) in planes.iter().zip(nd2.read_planes(&planes)?) {
+                let path = self.dir.join(format!("t{t}_c{c}_z{z}.png"));
+                let file = BufWriter::new(File::create(path)?);
+                let Some(tone) = self.tone_mapping else {
+                    write_png_gray16(file, layout.width, layout.height, &plane, self.compression)?;
+                    continue;
+                };
+                match &fixed_lut {
+                    Some(lut) => lut.apply_into(&plane, &mut preview),
+                    None => plane_lut(&tone, &plane).apply_into(&plane, &mut preview),
+                }
+                write_png_gray8(
+                    file,
+                    layout.width,
+                    layout.height,
+                    &preview,
+                    self.compression,
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Lookup table for a plane with limits from its own histogram.
+fn plane_lut(tone: &ToneMapping, plane: &[u16]) -> ToneLut {
+    let mut histogram = vec![0u64; 1 << 16];
+    for &v in plane {
+        histogram[v as usize] += 1;
+    }
+    tone.lut(tone.limits(&histogram))
+}
+
+/// Encode a 16-bit grayscale PNG.
+pub(crate) fn write_png_gray16<W: Write>(
+    out: W,
+    width: usize,
+    height: usize,
+    pixels: &[u16],
+    level: u32,
+) -> Result<()> {
+    write_png_gray(out, width, height, pixels, 16, level, |row, pixels| {
+        for &px in pixels {
+            row.extend_from_slice(&px.to_be_bytes());
+        }
+    })
+}
+
+/// Encode an 8-bit grayscale PNG.
+pub(crate) fn write_png_gray8<W: Write>(
+    out: W,
+    width: usize,
+    height: usize,
+    pixels: &[u8],
+    level: u32,
+) -> Result<()> {
+    write_png_gray(out, width, height, pixels, 8, level, |row, pixels| {
+        row.extend_from_slice(pixels)
+    })
+}
+
+/// Encode a grayscale PNG of `bit_depth`, appending each row's samples
+/// with `encode_row`.
+fn write_png_gray<W: Write, P>(
+    mut out: W,
+    width: usize,
+    height: usize,
+    pixels: &[P],
+    bit_depth: u8,
+    level: u32,
+    encode_row: impl Fn(&mut Vec<u8>, &[P]),
+) -> Result<()> {
+    if pixels.len() != width * height {
+        return Err(Nd2Error::input_argument(
+            "png",
+            format!(
+                "plane has {} pixels, expected {}x{}",
+                pixels.len(),
+                width,
+                height
+            ),
+        ));
+    }
+    let width32 =
+        u32::try_from(width).map_err(|_| Nd2Error::input_argument("png", "width exceeds u32"))?;
+    let height32 =
+        u32::try_from(height).map_err(|_| Nd2Error::input_argument("png", "height exceeds u32"))?;
+
+    out.write_all(b"\x89PNG\r\n\x1a\n")?;
+
+    let mut ihdr = Vec::with_capacity(13);
+    ihdr.extend_from_slice(&width32.to_be_bytes());
+    ihdr.extend_from_slice(&height32.to_be_bytes());
+    // color type 0 (grayscale), deflate, adaptive filtering, no interlace
+    ihdr.extend_from_slice(&[bit_depth, 0, 0, 0, 0]);
+    write_png_chunk(&mut out, b"IHDR", &ihdr)?;
+
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::new(level));
+    let mut row = Vec::with_capacity(1 + width * usize::from(bit_depth / 8));
+    for y in 0..height {
+        row.clear();
+        row.push(0); // filter type: none
+        encode_row(&mut row, &pixels[y * width..(y + 1) * width]);
+        encoder.write_all(&row)?;
+    }
+    let idat = encoder.finish()?;
+    write_png_chunk(&mut out, b"IDAT", &idat)?;
+    write_png_chunk(&mut out, b"IEND", &[])?;
+    out.flush()?;
+    Ok(())
+}
+
+fn write_png_chunk<W: Write>(out: &mut W, kind: &[u8; 4], data: &[u8]) -> Result<()> {
+    let len = u32::try_from(data.len())
+        .map_err(|_| Nd2Error::input_argument("png", "chunk exceeds u32 length"))?;
+    out.write_all(&len.to_be_bytes())?;
+    out.write_all(kind)?;
+    out.write_all(data)?;
+    let crc = crc32(crc32(0xFFFF_FFFF, kind), data) ^ 0xFFFF_FFFF;
+    out.write_all(&crc.to_be_bytes())?;
+    Ok(())
+}
+
+fn crc32(mut crc: u32, data: &[u8]) -> u32 {
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                0xEDB8_8320 ^ (crc >> 1)
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    crc
+}
@@ -0,0 +1,105 @@
+use rerun::{ColorModel, Image, Points3D, RecordingStream, Scalar};
+
+use crate::error::{Nd2Error, Result};
+use crate::frame::Frame;
+use crate::reader::Nd2File;
+use crate::types::ExpLoop;
+
+/// Logs frames, stage positions and timestamps to a Rerun recording.
+///
+/// Each frame is logged on the `frame` (sequence index) timeline and, when
+/// its chunk has a timestamp, the `acquisition` timeline in seconds:
+///
+/// - `p{P}/z{Z}/c{C}`: one grayscale image per plane
+/// - `stage/current`: the stage position of the frame's XY point
+/// - `acquisition/timestamp_ms`: the frame timestamp as a scalar
+///
+/// All XY points are logged once, statically, under `stage/positions`.
+#[derive(Debug, Clone, Default)]
+pub struct RerunLogger {
+    channel: Option<usize>,
+}
+
+impl RerunLogger {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Log only one channel instead of all channels.
+    pub fn channel(mut self, channel: usize) -> Self {
+        self.channel = Some(channel);
+        self
+    }
+
+    pub fn log(&self, nd2: &mut Nd2File, rec: &RecordingStream) -> Result<()> {
+        let (height, width) = nd2.shape()?;
+        let points: Vec<_> = nd2
+            .experiment()?
+            .iter()
+            .find_map(|loop_| match loop_ {
+                ExpLoop::XYPosLoop(xy) => Some(xy.parameters.points.clone()),
+                _ => None,
+            })
+            .unwrap_or_default();
+        if !points.is_empty() {
+            let labels: Vec<String> = points
+                .iter()
+                .enumerate()
+                .map(|(p, point)| point.name.clone().unwrap_or_else(|| format!("P{p}")))
+                .collect();
+            let coords = points.iter().map(|point| {
+                let s = point.stage_position_um;
+                (s.x as f32, s.y as f32, s.z as f32)
+            });
+            rec.log_static(
+                "stage/positions",
+                &Points3D::new(coords).with_labels(labels),
+            )
+            .map_err(to_error)?;
+        }
+
+        let plane_len = (height * width).max(1);
+        for index in nd2.frames()?.iter().map(Frame::index) {
+            let (pixels, meta) = match nd2.read_frame_with_meta(index) {
+                Ok(frame) => frame,
+                Err(err) => {
+                    nd2.skip_bad_frame(index, err)?;
+                    continue;
+                }
+            };
+            rec.set_time_sequence("frame", index as i64);
+            if let Some(ms) = meta.timestamp_ms {
+                rec.set_time_seconds("acquisition", ms / 1000.0);
+                rec.log("acquisition/timestamp_ms", &Scalar::new(ms))
+                    .map_err(to_error)?;
+            }
+            if let Some(s) = meta.stage_position_um {
+                rec.log(
+                    "stage/current",
+                    &Points3D::new([(s.x as f32, s.y as f32, s.z as f32)]),
+                )
+                .map_err(to_error)?;
+            }
+
+            let coord = |axis: &str| meta.coords.get(axis).copied();
+            let (p, z) = (coord("P").unwrap_or(0), coord("Z").unwrap_or(0));
+            // Channels are either a loop axis (one per frame) or stored in-pixel.
+            for (i, plane) in pixels.chunks_exact(plane_len).enumerate() {
+                let c = coord("C").unwrap_or(i);
+                if self.channel.is_some_and(|only| only != c) {
+                    continue;
+                }
+                rec.log(
+                    format!("p{p}/z{z}/c{c}"),
+                    &Image::from_elements(plane, [width as u32, height as u32], ColorModel::L),
+                )
+                .map_err(to_error)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn to_error(err: rerun::RecordingStreamError) -> Nd2Error {
+    std::io::Error::new(std::io::ErrorKind::Other, err.to_string()).into()
+}
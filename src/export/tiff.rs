@@ -0,0 +1,470 @@
+use std::fs::File;
+use std::io::{BufWriter, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use flate2::write::ZlibEncoder;
+
+use super::lzw;
+use super::PlaneLayout;
+use crate::error::{Nd2Error, Result};
+use crate::reader::Nd2File;
+
+/// Writes one position as a multi-page 16-bit TIFF in ImageJ hyperstack order
+/// (C fastest, then Z, then T).
+#[derive(Debug, Clone)]
+pub struct TiffExporter {
+    path: PathBuf,
+    position: usize,
+    channel: Option<usize>,
+}
+
+impl TiffExporter {
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+            position: 0,
+            channel: None,
+        }
+    }
+
+    /// Position (P index) to export. Defaults to 0.
+    pub fn position(mut self, position: usize) -> Self {
+        self.position = position;
+        self
+    }
+
+    /// Export only one channel instead of all channels.
+    pub fn channel(mut self, channel: usize) -> Self {
+        self.channel = Some(channel);
+        self
+    }
+
+    pub fn export(&self, nd2: &mut Nd2File) -> Result<()> {
+        let layout = PlaneLayout::read(nd2, self.position)?;
+        let channels = layout.channels(self.channel)?;
+        let description = format!(
+            "ImageJ=1.11a\nimages={}\nchannels={}\nslices={}\nframes={}\nhyperstack=true\n",
+            layout.n_time * layout.n_z * channels.len(),
+            channels.len(),
+            layout.n_z,
+            layout.n_time
+        );
+
+        let mut writer = TiffWriter::new(BufWriter::new(File::create(&self.path)?), false)?;
+        let mut page = TiffPage::new(layout.width, layout.height);
+        page.layout = TiffLayout::Strips {
+            rows: layout.height,
+        };
+        for t in 0..layout.n_time {
+            let planes: Vec<[usize; 4]> = (0..layout.n_z)
+                .flat_map(|z| channels.iter().map(move |&c| [self.position, t, c, z]))
+                .collect();
+            for plane in nd2.read_planes(&planes)? {
+                page.description = (writer.pages == 0).then(|| description.clone());
+                writer.write_page(&page, &plane, &[])?;
+            }
+        }
+        writer.finish()?;
+        Ok(())
+    }
+}
+
+/// How the pixels of a TIFF page are split up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TiffLayout {
+    /// Strips of `rows` full rows each (the last may be shorter).
+    Strips { rows: usize },
+    /// Tiles of `width` × `height` pixels, both multiples of 16; tiles
+    /// along the right and bottom edges are padded with zeros.
+    Tiles { width: usize, height: usize },
+}
+
+impl Default for TiffLayout {
+    /// Strips of 64 rows.
+    fn default() -> Self {
+        Self::Strips { rows: 64 }
+    }
+}
+
+impl TiffLayout {
+    fn validate(&self) -> Result<()> {
+        match *self {
+            Self::Strips { rows: 0 } => Err(Nd2Error::input_argument(
+                "layout",
+                "strips need at least one row",
+            )),
+            Self::Tiles { width, height }
+                if width == 0 || height == 0 || width % 16 != 0 || height % 16 != 0 =>
+            {
+                Err(Nd2Error::input_argument(
+                    "layout",
+                    format!(
+                        "tile size {}x{} is not a positive multiple of 16",
+                        width, height
+                    ),
+                ))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Compression of the strips or tiles of a TIFF page.
+///
+/// Non-exhaustive, since [`TiffCompression::Zstd`] only exists with the
+/// `tiff-zstd` feature.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub enum TiffCompression {
+    /// Stored as they are.
+    #[default]
+    None,
+    /// LZW, readable by every TIFF reader.
+    Lzw,
+    /// zlib (Adobe Deflate) at the given level, 0-9.
+    Deflate(u32),
+    /// Zstandard at the given level, as written by libtiff 4.0.10 and
+    /// later (compression code 50000).
+    #[cfg(feature = "tiff-zstd")]
+    Zstd(i32),
+}
+
+impl TiffCompression {
+    fn code(self) -> u16 {
+        match self {
+            Self::None => 1,
+            Self::Lzw => 5,
+            Self::Deflate(_) => 8,
+            #[cfg(feature = "tiff-zstd")]
+            Self::Zstd(_) => 50000,
+        }
+    }
+
+    fn compress(self, data: Vec<u8>) -> Result<Vec<u8>> {
+        match self {
+            Self::None => Ok(data),
+            Self::Lzw => Ok(lzw::encode(&data)),
+            Self::Deflate(level) => {
+                let mut encoder =
+                    ZlibEncoder::new(Vec::new(), flate2::Compression::new(level.min(9)));
+                encoder.write_all(&data)?;
+                Ok(encoder.finish()?)
+            }
+            #[cfg(feature = "tiff-zstd")]
+            Self::Zstd(level) => Ok(zstd::encode_all(&data[..], level)?),
+        }
+    }
+}
+
+/// One 16-bit grayscale page for [`TiffWriter::write_page`].
+#[derive(Debug, Clone)]
+pub(crate) struct TiffPage {
+    pub width: usize,
+    pub height: usize,
+    pub layout: TiffLayout,
+    pub compression: TiffCompression,
+    pub description: Option<String>,
+}
+
+impl TiffPage {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            layout: TiffLayout::default(),
+            compression: TiffCompression::None,
+            description: None,
+        }
+    }
+
+    /// The page's strips or tiles, uncompressed.
+    fn segments(&self, pixels: &[u16]) -> Vec<Vec<u8>> {
+        let row = |y: usize| &pixels[y * self.width..(y + 1) * self.width];
+        match self.layout {
+            TiffLayout::Strips { rows } => (0..self.height)
+                .step_by(rows)
+                .map(|top| {
+                    (top..(top + rows).min(self.height))
+                        .flat_map(|y| row(y).iter().flat_map(|p| p.to_le_bytes()))
+                        .collect()
+                })
+                .collect(),
+            TiffLayout::Tiles { width, height } => {
+                let mut tiles = Vec::new();
+                for top in (0..self.height).step_by(height) {
+                    for left in (0..self.width).step_by(width) {
+                        let mut tile = Vec::with_capacity(width * height * 2);
+                        for y in top..top + height {
+                            for x in left..left + width {
+                                let value = if y < self.height && x < self.width {
+                                    row(y)[x]
+                                } else {
+                                    0
+                                };
+                                tile.extend_from_slice(&value.to_le_bytes());
+                            }
+                        }
+                        tiles.push(tile);
+                    }
+                }
+                tiles
+            }
+        }
+    }
+}
+
+/// Little-endian TIFF or BigTIFF writer for 16-bit grayscale pages.
+pub(crate) struct TiffWriter<W: Write + Seek> {
+    inner: W,
+    pos: u64,
+    big: bool,
+    /// Where the offset of the next page's IFD goes.
+    next_ifd_ptr: u64,
+    pub pages: usize,
+}
+
+const TYPE_ASCII: u16 = 2;
+const TYPE_SHORT: u16 = 3;
+const TYPE_LONG: u16 = 4;
+const TYPE_LONG8: u16 = 16;
+
+/// The values of one IFD entry.
+enum TagValue {
+    Ascii(Vec<u8>),
+    Short(Vec<u16>),
+    Long(Vec<u32>),
+    Long8(Vec<u64>),
+}
+
+impl TagValue {
+    fn kind(&self) -> u16 {
+        match self {
+            Self::Ascii(_) => TYPE_ASCII,
+            Self::Short(_) => TYPE_SHORT,
+            Self::Long(_) => TYPE_LONG,
+            Self::Long8(_) => TYPE_LONG8,
+        }
+    }
+
+    fn count(&self) -> usize {
+        match self {
+            Self::Ascii(v) => v.len(),
+            Self::Short(v) => v.len(),
+            Self::Long(v) => v.len(),
+            Self::Long8(v) => v.len(),
+        }
+    }
+
+    fn bytes(&self) -> Vec<u8> {
+        match self {
+            Self::Ascii(v) => v.clone(),
+            Self::Short(v) => v.iter().flat_map(|v| v.to_le_bytes()).collect(),
+            Self::Long(v) => v.iter().flat_map(|v| v.to_le_bytes()).collect(),
+            Self::Long8(v) => v.iter().flat_map(|v| v.to_le_bytes()).collect(),
+        }
+    }
+}
+
+impl<W: Write + Seek> TiffWriter<W> {
+    /// Start a TIFF, or a BigTIFF when `big`, whose offsets are 64-bit
+    /// instead of being limited to 4 GiB.
+    pub fn new(mut inner: W, big: bool) -> Result<Self> {
+        inner.write_all(b"II")?;
+        if big {
+            inner.write_all(&43u16.to_le_bytes())?;
+            inner.write_all(&8u16.to_le_bytes())?;
+            inner.write_all(&0u16.to_le_bytes())?;
+            inner.write_all(&0u64.to_le_bytes())?;
+        } else {
+            inner.write_all(&42u16.to_le_bytes())?;
+            inner.write_all(&0u32.to_le_bytes())?;
+        }
+        Ok(Self {
+            inner,
+            pos: if big { 16 } else { 8 },
+            big,
+            next_ifd_ptr: if big { 8 } else { 4 },
+            pages: 0,
+        })
+    }
+
+    fn offset32(value: u64) -> Result<u32> {
+        u32::try_from(value).map_err(|_| {
+            Nd2Error::input_argument("tiff", "output exceeds 4 GiB classic TIFF limit")
+        })
+    }
+
+    /// Offsets or byte counts, as LONG in a TIFF and LONG8 in a BigTIFF.
+    fn offsets(&self, values: Vec<u64>) -> Result<TagValue> {
+        if self.big {
+            return Ok(TagValue::Long8(values));
+        }
+        values
+            .into_iter()
+            .map(Self::offset32)
+            .collect::<Result<_>>()
+            .map(TagValue::Long)
+    }
+
+    fn write_bytes(&mut self, bytes: &[u8]) -> Result<u64> {
+        let at = self.pos;
+        self.inner.write_all(bytes)?;
+        self.pos += bytes.len() as u64;
+        Ok(at)
+    }
+
+    /// Pad to a word boundary, where IFDs and their values start.
+    fn align(&mut self) -> Result<()> {
+        if self.pos % 2 == 1 {
+            self.write_bytes(&[0])?;
+        }
+        Ok(())
+    }
+
+    /// Write a full-resolution page, with `sub_ifds` holding the offsets of
+    /// its reduced-resolution versions, if any.
+    pub fn write_page(&mut self, page: &TiffPage, pixels: &[u16], sub_ifds: &[u64]) -> Result<()> {
+        let entries = self.write_image(page, pixels, sub_ifds, false)?;
+        self.write_ifd(&entries, true)?;
+        Ok(())
+    }
+
+    /// Write a reduced-resolution page outside the page chain, returning the
+    /// offset of its IFD for the SubIFDs of the page it belongs to.
+    pub fn write_reduced(&mut self, page: &TiffPage, pixels: &[u16]) -> Result<u64> {
+        let entries = self.write_image(page, pixels, &[], true)?;
+        self.write_ifd(&entries, false)
+    }
+
+    /// Write the strips or tiles of a page and return its IFD entries.
+    fn write_image(
+        &mut self,
+        page: &TiffPage,
+        pixels: &[u16],
+        sub_ifds: &[u64],
+        reduced: bool,
+    ) -> Result<Vec<(u16, TagValue)>> {
+        if pixels.len() != page.width * page.height {
+            return Err(Nd2Error::input_argument(
+                "tiff",
+                format!(
+                    "plane has {} pixels, expected {}x{}",
+                    pixels.len(),
+                    page.width,
+                    page.height
+                ),
+            ));
+        }
+        page.layout.validate()?;
+        let mut offsets = Vec::new();
+        let mut counts = Vec::new();
+        for segment in page.segments(pixels) {
+            let data = page.compression.compress(segment)?;
+            offsets.push(self.write_bytes(&data)?);
+            counts.push(data.len() as u64);
+        }
+
+        let width = Self::offset32(page.width as u64)?;
+        let height = Self::offset32(page.height as u64)?;
+        let mut entries = Vec::new();
+        if reduced {
+            entries.push((254, TagValue::Long(vec![1])));
+        }
+        entries.extend([
+            (256, TagValue::Long(vec![width])),
+            (257, TagValue::Long(vec![height])),
+            (258, TagValue::Short(vec![16])),
+            (259, TagValue::Short(vec![page.compression.code()])),
+            (262, TagValue::Short(vec![1])),
+        ]);
+        if let Some(text) = &page.description {
+            let mut bytes = text.as_bytes().to_vec();
+            bytes.push(0);
+            entries.push((270, TagValue::Ascii(bytes)));
+        }
+        entries.push((277, TagValue::Short(vec![1])));
+        match page.layout {
+            TiffLayout::Strips { rows } => {
+                let rows = Self::offset32(rows.min(page.height) as u64)?;
+                entries.push((273, self.offsets(offsets)?));
+                entries.push((278, TagValue::Long(vec![rows])));
+                entries.push((279, self.offsets(counts)?));
+            }
+            TiffLayout::Tiles { width, height } => {
+                entries.push((322, TagValue::Long(vec![Self::offset32(width as u64)?])));
+                entries.push((323, TagValue::Long(vec![Self::offset32(height as u64)?])));
+                entries.push((324, self.offsets(offsets)?));
+                entries.push((325, self.offsets(counts)?));
+            }
+        }
+        entries.push((284, TagValue::Short(vec![1])));
+        if !sub_ifds.is_empty() {
+            entries.push((330, self.offsets(sub_ifds.to_vec())?));
+        }
+        entries.push((339, TagValue::Short(vec![1])));
+        Ok(entries)
+    }
+
+    /// Write an IFD holding `entries` (in ascending tag order), with the
+    /// values that do not fit in an entry before it, and return its offset.
+    /// When `link`, it becomes the next page of the chain.
+    fn write_ifd(&mut self, entries: &[(u16, TagValue)], link: bool) -> Result<u64> {
+        let inline = if self.big { 8 } else { 4 };
+        let mut fields = Vec::with_capacity(entries.len());
+        for (tag, value) in entries {
+            let mut bytes = value.bytes();
+            if bytes.len() > inline {
+                self.align()?;
+                let at = self.write_bytes(&bytes)?;
+                bytes = if self.big {
+                    at.to_le_bytes().to_vec()
+                } else {
+                    Self::offset32(at)?.to_le_bytes().to_vec()
+                };
+            }
+            bytes.resize(inline, 0);
+            fields.push((*tag, value.kind(), value.count() as u64, bytes));
+        }
+
+        let mut ifd = Vec::new();
+        if self.big {
+            ifd.extend_from_slice(&(fields.len() as u64).to_le_bytes());
+        } else {
+            ifd.extend_from_slice(&(fields.len() as u16).to_le_bytes());
+        }
+        for (tag, kind, count, value) in fields {
+            ifd.extend_from_slice(&tag.to_le_bytes());
+            ifd.extend_from_slice(&kind.to_le_bytes());
+            if self.big {
+                ifd.extend_from_slice(&count.to_le_bytes());
+            } else {
+                ifd.extend_from_slice(&Self::offset32(count)?.to_le_bytes());
+            }
+            ifd.extend_from_slice(&value);
+        }
+        ifd.resize(ifd.len() + inline, 0);
+        self.align()?;
+        let ifd_offset = self.write_bytes(&ifd)?;
+        if !link {
+            return Ok(ifd_offset);
+        }
+
+        // Link the previous IFD (or the header) to this one.
+        self.inner.seek(SeekFrom::Start(self.next_ifd_ptr))?;
+        if self.big {
+            self.inner.write_all(&ifd_offset.to_le_bytes())?;
+        } else {
+            self.inner
+                .write_all(&Self::offset32(ifd_offset)?.to_le_bytes())?;
+        }
+        self.inner.seek(SeekFrom::Start(self.pos))?;
+        self.next_ifd_ptr = self.pos - inline as u64;
+        self.pages += 1;
+        Ok(ifd_offset)
+    }
+
+    pub fn finish(mut self) -> Result<W> {
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+}
@@ -0,0 +1,124 @@
+use std::fmt;
+
+/// Intensity window mapped onto the 8-bit output range.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ToneRange {
+    /// Fixed display limits, like a NIS LUT.
+    Fixed { min: u16, max: u16 },
+    /// Limits from the whole selection, saturating `saturated` (0-1) of the
+    /// pixels at each end.
+    Auto { saturated: f64 },
+}
+
+/// 16-bit to 8-bit tone mapping: a linear window followed by a gamma curve.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ToneMapping {
+    pub range: ToneRange,
+    pub gamma: f64,
+}
+
+impl Default for ToneMapping {
+    fn default() -> Self {
+        Self {
+            range: ToneRange::Auto { saturated: 0.001 },
+            gamma: 1.0,
+        }
+    }
+}
+
+impl ToneMapping {
+    /// Display limits for a histogram of 16-bit values.
+    pub fn limits(&self, histogram: &[u64]) -> (u16, u16) {
+        match self.range {
+            ToneRange::Fixed { min, max } => (min, max),
+            ToneRange::Auto { saturated } => {
+                let total: u64 = histogram.iter().sum();
+                let clip = (total as f64 * saturated.clamp(0.0, 0.5)) as u64;
+                let mut seen = 0;
+                let low = histogram
+                    .iter()
+                    .position(|&n| {
+                        seen += n;
+                        seen > clip
+                    })
+                    .unwrap_or(0);
+                seen = 0;
+                let high = histogram
+                    .iter()
+                    .rposition(|&n| {
+                        seen += n;
+                        seen > clip
+                    })
+                    .unwrap_or(u16::MAX as usize);
+                (low as u16, high.max(low) as u16)
+            }
+        }
+    }
+
+    /// Map a plane to 8 bits within `limits`.
+    ///
+    /// Builds a [`ToneLut`] for the call; use [`ToneMapping::lut`] to map
+    /// many planes with the same limits.
+    pub fn apply(&self, plane: &[u16], limits: (u16, u16)) -> Vec<u8> {
+        self.lut(limits).apply(plane)
+    }
+
+    /// Lookup table mapping every 16-bit value within `limits`.
+    pub fn lut(&self, limits: (u16, u16)) -> ToneLut {
+        let (min, max) = limits;
+        let span = max.saturating_sub(min).max(1) as f64;
+        let gamma = if self.gamma > 0.0 {
+            1.0 / self.gamma
+        } else {
+            1.0
+        };
+        let mut table = vec![255u8; 1 << 16];
+        table[..=min as usize].fill(0);
+        for v in min.saturating_add(1)..max {
+            let x = (v - min) as f64 / span;
+            table[v as usize] = (x.powf(gamma) * 255.0).round() as u8;
+        }
+        ToneLut {
+            table: table
+                .into_boxed_slice()
+                .try_into()
+                .expect("table has one entry per u16 value"),
+        }
+    }
+}
+
+/// Precomputed 16-bit to 8-bit mapping for one display range, built by
+/// [`ToneMapping::lut`].
+///
+/// Mapping a pixel is a single table load, so converting a plane costs far
+/// less than evaluating the window and gamma curve per pixel.
+#[derive(Clone)]
+pub struct ToneLut {
+    table: Box<[u8; 1 << 16]>,
+}
+
+impl ToneLut {
+    /// Map one 16-bit value.
+    pub fn map(&self, value: u16) -> u8 {
+        self.table[value as usize]
+    }
+
+    /// Map a plane to 8 bits.
+    pub fn apply(&self, plane: &[u16]) -> Vec<u8> {
+        let mut out = Vec::new();
+        self.apply_into(plane, &mut out);
+        out
+    }
+
+    /// Map a plane to 8 bits into `out`, reusing its allocation.
+    pub fn apply_into(&self, plane: &[u16], out: &mut Vec<u8>) {
+        out.clear();
+        out.extend(plane.iter().map(|&v| self.table[v as usize]));
+    }
+}
+
+impl fmt::Debug for ToneLut {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ToneLut").finish_non_exhaustive()
+    }
+}
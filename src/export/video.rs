@@ -0,0 +1,222 @@
+use std::ffi::OsString;
+use std::io::Write;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use super::{PlaneLayout, ToneMapping, ToneRange};
+use crate::error::{Nd2Error, Result};
+use crate::reader::Nd2File;
+
+/// Output codec for [`VideoExporter`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum VideoCodec {
+    /// H.264 (`libx264`), 4:2:0. Plays almost everywhere.
+    #[default]
+    H264,
+    /// HEVC (`libx265`), 4:2:0.
+    Hevc,
+    /// Apple ProRes 422 HQ (`prores_ks`), for editing.
+    ProRes,
+}
+
+impl VideoCodec {
+    fn ffmpeg_args(self, crf: u32) -> Vec<String> {
+        let args: &[&str] = match self {
+            VideoCodec::H264 => &["-c:v", "libx264", "-pix_fmt", "yuv420p"],
+            VideoCodec::Hevc => &["-c:v", "libx265", "-pix_fmt", "yuv420p"],
+            VideoCodec::ProRes => &[
+                "-c:v",
+                "prores_ks",
+                "-profile:v",
+                "3",
+                "-pix_fmt",
+                "yuv422p10le",
+            ],
+        };
+        let mut args: Vec<String> = args.iter().map(|s| s.to_string()).collect();
+        if self != VideoCodec::ProRes {
+            args.extend(["-crf".to_string(), crf.to_string()]);
+        }
+        args
+    }
+}
+
+/// Encodes a time series of one (position, channel, Z) plane as video by
+/// piping tone-mapped 8-bit frames to an `ffmpeg` executable.
+#[derive(Debug, Clone)]
+pub struct VideoExporter {
+    path: PathBuf,
+    position: usize,
+    channel: usize,
+    z: usize,
+    timepoints: Option<Range<usize>>,
+    fps: f64,
+    codec: VideoCodec,
+    quality: u32,
+    tone_mapping: ToneMapping,
+    ffmpeg: OsString,
+}
+
+impl VideoExporter {
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+            position: 0,
+            channel: 0,
+            z: 0,
+            timepoints: None,
+            fps: 10.0,
+            codec: VideoCodec::default(),
+            quality: 18,
+            tone_mapping: ToneMapping::default(),
+            ffmpeg: "ffmpeg".into(),
+        }
+    }
+
+    /// Position (P index) to encode. Defaults to 0.
+    pub fn position(mut self, position: usize) -> Self {
+        self.position = position;
+        self
+    }
+
+    /// Channel (C index) to encode. Defaults to 0.
+    pub fn channel(mut self, channel: usize) -> Self {
+        self.channel = channel;
+        self
+    }
+
+    /// Z index to encode. Defaults to 0.
+    pub fn z(mut self, z: usize) -> Self {
+        self.z = z;
+        self
+    }
+
+    /// Time points to encode. Defaults to all of them.
+    pub fn timepoints(mut self, range: Range<usize>) -> Self {
+        self.timepoints = Some(range);
+        self
+    }
+
+    /// Output frame rate. Defaults to 10.
+    pub fn fps(mut self, fps: f64) -> Self {
+        self.fps = fps;
+        self
+    }
+
+    pub fn codec(mut self, codec: VideoCodec) -> Self {
+        self.codec = codec;
+        self
+    }
+
+    /// CRF for H.264/HEVC (lower is better). Defaults to 18; ignored by ProRes.
+    pub fn quality(mut self, crf: u32) -> Self {
+        self.quality = crf.min(51);
+        self
+    }
+
+    pub fn tone_mapping(mut self, tone_mapping: ToneMapping) -> Self {
+        self.tone_mapping = tone_mapping;
+        self
+    }
+
+    /// `ffmpeg` executable to run. Defaults to `ffmpeg` on `PATH`.
+    pub fn ffmpeg<S: Into<OsString>>(mut self, program: S) -> Self {
+        self.ffmpeg = program.into();
+        self
+    }
+
+    pub fn export(&self, nd2: &mut Nd2File) -> Result<()> {
+        let layout = PlaneLayout::read(nd2, self.position)?;
+        layout.channels(Some(self.channel))?;
+        if self.z >= layout.n_z {
+            return Err(Nd2Error::input_out_of_range("z index", self.z, layout.n_z));
+        }
+        let timepoints = self.timepoints.clone().unwrap_or(0..layout.n_time);
+        if timepoints.end > layout.n_time {
+            return Err(Nd2Error::input_out_of_range(
+                "time index",
+                timepoints.end - 1,
+                layout.n_time,
+            ));
+        }
+        if timepoints.is_empty() {
+            return Err(Nd2Error::input_argument("timepoints", "selection is empty"));
+        }
+        if !(self.fps.is_finite() && self.fps > 0.0) {
+            return Err(Nd2Error::input_argument(
+                "fps",
+                format!("must be positive, got {}", self.fps),
+            ));
+        }
+
+        let limits = match self.tone_mapping.range {
+            ToneRange::Fixed { min, max } => (min, max),
+            ToneRange::Auto { .. } => {
+                let mut histogram = vec![0u64; 1 << 16];
+                for t in timepoints.clone() {
+                    let plane = [self.position, t, self.channel, self.z];
+                    // Skipped frames are left out rather than counted as zeros.
+                    for v in nd2
+                        .read_planes_or_skip(&[plane])?
+                        .into_iter()
+                        .flatten()
+                        .flatten()
+                    {
+                        histogram[v as usize] += 1;
+                    }
+                }
+                self.tone_mapping.limits(&histogram)
+            }
+        };
+
+        let mut child = Command::new(&self.ffmpeg)
+            .args([
+                "-y",
+                "-loglevel",
+                "error",
+                "-f",
+                "rawvideo",
+                "-pix_fmt",
+                "gray",
+            ])
+            .args(["-s", &format!("{}x{}", layout.width, layout.height)])
+            .args(["-r", &self.fps.to_string(), "-i", "-"])
+            // 4:2:0 and 4:2:2 need even dimensions.
+            .args(["-vf", "pad=ceil(iw/2)*2:ceil(ih/2)*2"])
+            .args(self.codec.ffmpeg_args(self.quality))
+            .arg(&self.path)
+            .stdin(Stdio::piped())
+            .spawn()?;
+        let mut stdin = child.stdin.take().expect("stdin is piped");
+        let lut = self.tone_mapping.lut(limits);
+        let mut frame = Vec::new();
+        let mut written = Ok(());
+        for t in timepoints {
+            written = nd2
+                .read_planes(&[[self.position, t, self.channel, self.z]])
+                .and_then(|mut planes| {
+                    lut.apply_into(&planes.swap_remove(0), &mut frame);
+                    Ok(stdin.write_all(&frame)?)
+                });
+            if written.is_err() {
+                break;
+            }
+        }
+        drop(stdin);
+        if let Err(e) = written {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(e);
+        }
+        let status = child.wait()?;
+        if !status.success() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::Other,
+                format!("ffmpeg exited with {status}"),
+            )
+            .into());
+        }
+        Ok(())
+    }
+}
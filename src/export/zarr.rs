@@ -0,0 +1,123 @@
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+
+use super::PlaneLayout;
+use crate::error::{Nd2Error, Result};
+use crate::reader::Nd2File;
+
+/// Writes one position as a Zarr v2 array of shape (T, C, Z, Y, X) with one
+/// chunk per plane.
+#[derive(Debug, Clone)]
+pub struct ZarrExporter {
+    path: PathBuf,
+    position: usize,
+    channel: Option<usize>,
+    compression: Option<u32>,
+}
+
+impl ZarrExporter {
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+            position: 0,
+            channel: None,
+            compression: None,
+        }
+    }
+
+    /// Position (P index) to export. Defaults to 0.
+    pub fn position(mut self, position: usize) -> Self {
+        self.position = position;
+        self
+    }
+
+    /// Export only one channel instead of all channels.
+    pub fn channel(mut self, channel: usize) -> Self {
+        self.channel = Some(channel);
+        self
+    }
+
+    /// Compress chunks with zlib at the given level (0-9). Uncompressed by default.
+    pub fn compression(mut self, level: u32) -> Self {
+        self.compression = Some(level.min(9));
+        self
+    }
+
+    pub fn export(&self, nd2: &mut Nd2File) -> Result<()> {
+        let layout = PlaneLayout::read(nd2, self.position)?;
+        let channels = layout.channels(self.channel)?;
+        fs::create_dir_all(&self.path)?;
+
+        let compressor = match self.compression {
+            Some(level) => format!("{{\"id\": \"zlib\", \"level\": {level}}}"),
+            None => "null".to_string(),
+        };
+        let zarray = format!(
+            concat!(
+                "{{\n",
+                "  \"zarr_format\": 2,\n",
+                "  \"shape\": [{}, {}, {}, {}, {}],\n",
+                "  \"chunks\": [1, 1, 1, {}, {}],\n",
+                "  \"dtype\": \"<u2\",\n",
+                "  \"compressor\": {},\n",
+                "  \"fill_value\": 0,\n",
+                "  \"order\": \"C\",\n",
+                "  \"filters\": null,\n",
+                "  \"dimension_separator\": \"/\"\n",
+                "}}\n"
+            ),
+            layout.n_time,
+            channels.len(),
+            layout.n_z,
+            layout.height,
+            layout.width,
+            layout.height,
+            layout.width,
+            compressor
+        );
+        fs::write(self.path.join(".zarray"), zarray)?;
+        fs::write(
+            self.path.join(".zattrs"),
+            "{\n  \"_ARRAY_DIMENSIONS\": [\"T\", \"C\", \"Z\", \"Y\", \"X\"]\n}\n",
+        )?;
+
+        for t in 0..layout.n_time {
+            let planes: Vec<[usize; 4]> = channels
+                .iter()
+                .flat_map(|&c| (0..layout.n_z).map(move |z| [self.position, t, c, z]))
+                .collect();
+            let mut planes = nd2.read_planes(&planes)?.into_iter();
+            for ci in 0..channels.len() {
+                for z in 0..layout.n_z {
+                    let plane = planes.next().ok_or_else(|| {
+                        Nd2Error::internal_invariant(
+                            "read_planes returned fewer planes than requested",
+                        )
+                    })?;
+                    let raw: Vec<u8> = plane.iter().flat_map(|p| p.to_le_bytes()).collect();
+                    let data = match self.compression {
+                        Some(level) => {
+                            let mut encoder = ZlibEncoder::new(Vec::new(), Compression::new(level));
+                            encoder.write_all(&raw)?;
+                            encoder.finish()?
+                        }
+                        None => raw,
+                    };
+                    let dir = self
+                        .path
+                        .join(t.to_string())
+                        .join(ci.to_string())
+                        .join(z.to_string())
+                        .join("0");
+                    fs::create_dir_all(&dir)?;
+                    fs::write(dir.join("0"), data)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,206 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Nd2Error, Result};
+use crate::reader::Nd2File;
+use crate::types::{Channel, StagePosition};
+
+/// Handle to a single frame (one `ImageDataSeq` chunk).
+///
+/// Carries the frame's loop coordinates and chunk location; pixels are only
+/// read and decoded when [`Frame::pixels`] is called.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Frame {
+    pub(crate) index: usize,
+    pub(crate) coords: BTreeMap<String, usize>,
+    pub(crate) offset: u64,
+    pub(crate) size: u64,
+}
+
+impl Frame {
+    /// Sequence index of this frame, as accepted by [`Nd2File::read_frame`].
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Loop coordinates of this frame (axis name -> index).
+    pub fn coords(&self) -> &BTreeMap<String, usize> {
+        &self.coords
+    }
+
+    /// Index along one axis (e.g. `"T"`), if the frame is indexed by it.
+    pub fn coord(&self, axis: &str) -> Option<usize> {
+        self.coords.get(axis).copied()
+    }
+
+    /// File offset of the frame chunk.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Size of the frame chunk as recorded in the chunk map.
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Read and decode this frame's pixels as (C, Y, X) u16 data.
+    pub fn pixels(&self, nd2: &mut Nd2File) -> Result<Vec<u16>> {
+        nd2.read_frame(self.index)
+    }
+
+    /// Read and decode this frame's pixels as one Y×X plane per channel.
+    pub fn split_channels(&self, nd2: &mut Nd2File) -> Result<Vec<Vec<u16>>> {
+        let pixels = nd2.read_frame(self.index)?;
+        nd2.split_planes(pixels)
+    }
+
+    #[cfg(feature = "ndarray")]
+    /// Read and decode this frame's pixels as a (C, Y, X) array.
+    pub fn into_array(self, nd2: &mut Nd2File) -> Result<ndarray::Array3<u16>> {
+        nd2.read_frame_array(self.index)
+    }
+}
+
+/// Position of one Y×X plane along the P, T, C and Z axes, as accepted by
+/// [`Nd2File::read_frame_at`] and [`Nd2File::seq_index_for`].
+///
+/// Axes a file has no loop for have length 1, so their index is 0.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct FrameCoord {
+    pub p: usize,
+    pub t: usize,
+    pub c: usize,
+    pub z: usize,
+}
+
+impl FrameCoord {
+    pub fn new(p: usize, t: usize, c: usize, z: usize) -> Self {
+        Self { p, t, c, z }
+    }
+}
+
+/// Loop coordinates and chunk location of every frame.
+///
+/// Built once from the attributes and experiment loops by
+/// [`Nd2File::frame_index`] and kept alongside them, so per-frame lookups
+/// don't re-derive the loop layout. Serializable, for callers that persist a
+/// file's frame table.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FrameIndex {
+    /// Axes frames are indexed by, outermost first; the last varies fastest.
+    pub axes: Vec<String>,
+    /// Length of each axis in `axes`.
+    pub shape: Vec<usize>,
+    /// Coordinates of each frame along `axes`, by sequence index.
+    pub coords: Vec<Vec<usize>>,
+    /// (offset, size) of each frame chunk, `None` when the chunkmap has no
+    /// entry for it or the chunk runs past the end of the file.
+    pub chunks: Vec<Option<(u64, u64)>>,
+}
+
+impl FrameIndex {
+    /// Number of frames.
+    pub fn len(&self) -> usize {
+        self.coords.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.coords.is_empty()
+    }
+
+    /// Sequence indices of frames without a chunk.
+    pub fn missing(&self) -> Vec<usize> {
+        (0..self.chunks.len())
+            .filter(|&index| self.chunks[index].is_none())
+            .collect()
+    }
+
+    /// Handle for frame `index`.
+    pub fn frame(&self, index: usize) -> Result<Frame> {
+        let coords = self
+            .coords
+            .get(index)
+            .ok_or_else(|| Nd2Error::input_out_of_range("sequence index", index, self.len()))?;
+        let (offset, size) =
+            self.chunks.get(index).copied().flatten().ok_or_else(|| {
+                Nd2Error::file_chunk_not_found(format!("ImageDataSeq|{}!", index))
+            })?;
+        Ok(Frame {
+            index,
+            coords: self
+                .axes
+                .iter()
+                .cloned()
+                .zip(coords.iter().copied())
+                .collect(),
+            offset,
+            size,
+        })
+    }
+
+    /// Sequence index of the frame at `coords` (one per axis in `axes`).
+    pub fn seq_index(&self, coords: &[usize]) -> Result<usize> {
+        if coords.len() != self.shape.len() {
+            return Err(Nd2Error::file_invalid_format(
+                "Coord/axis length mismatch".to_string(),
+            ));
+        }
+
+        for (idx, (&coord, &shape)) in coords.iter().zip(self.shape.iter()).enumerate() {
+            if shape == 0 {
+                return Err(Nd2Error::file_invalid_format(format!(
+                    "Invalid axis length: {} has size 0",
+                    self.axes[idx]
+                )));
+            }
+            if coord >= shape {
+                return Err(Nd2Error::input_out_of_range(
+                    format!("axis {}", self.axes[idx]),
+                    coord,
+                    shape,
+                ));
+            }
+        }
+
+        let mut seq = 0usize;
+        let mut stride = 1;
+        for i in (0..coords.len()).rev() {
+            let next = coords[i]
+                .checked_mul(stride)
+                .ok_or_else(|| Nd2Error::internal_overflow("sequence index multiply"))?;
+            seq = seq
+                .checked_add(next)
+                .ok_or_else(|| Nd2Error::internal_overflow("sequence index add"))?;
+            stride = stride
+                .checked_mul(self.shape[i])
+                .ok_or_else(|| Nd2Error::internal_overflow("sequence stride multiply"))?;
+        }
+        Ok(seq)
+    }
+}
+
+/// Per-frame metadata returned by [`Nd2File::frame_metadata`] and
+/// [`Nd2File::read_frame_with_meta`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FrameMetadata {
+    /// Sequence index of the frame.
+    pub index: usize,
+    /// Loop coordinates of the frame (axis name -> index).
+    pub coords: BTreeMap<String, usize>,
+    /// Acquisition time in milliseconds stored in the frame chunk, if the
+    /// chunk header could be located.
+    pub timestamp_ms: Option<f64>,
+    /// Stage position when the frame was acquired: the `CustomData|X!`,
+    /// `Y!` and `Z!` values recorded for it, else the position of its XY
+    /// point when the file has an XY loop.
+    pub stage_position_um: Option<StagePosition>,
+    /// Camera exposure in milliseconds (`CustomData|Camera_ExposureTime1!`).
+    pub exposure_ms: Option<f64>,
+    /// Perfect Focus offset (`CustomData|PFS_OFFSET!`).
+    pub pfs_offset: Option<f64>,
+    /// Channels as recorded for this frame: its own picture metadata chunk
+    /// when it has one, else that of the first frame. `None` when the file
+    /// has no picture metadata.
+    pub channels: Option<Vec<Channel>>,
+}
@@ -0,0 +1,147 @@
+//! On-disk cache of inflated frames (`frame-cache` feature).
+
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::OnceLock;
+
+use crate::decode::{FrameGeometry, FramePayload};
+use crate::error::Result;
+
+/// zstd level for cache entries: fast to write, still well below the
+/// inflated size for typical microscopy frames.
+const LEVEL: i32 = 3;
+
+/// Tells apart the temporary files of concurrent stores in this process.
+static PARTIAL_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// Inflated frames of one file, stored as `<seq>.zst` under a directory
+/// named after the file's fingerprint, so any number of files can share
+/// one cache root.
+///
+/// An entry holds the chunk timestamp (8 bytes, NaN when unknown) and the
+/// inflated pixel bytes in stored (interleaved) order, so the same entry
+/// serves every pixel type the frame can be read as.
+#[derive(Clone)]
+pub(crate) struct FrameCache {
+    dir: PathBuf,
+}
+
+impl FrameCache {
+    pub(crate) fn new(root: &Path, fingerprint: u64) -> Self {
+        Self {
+            dir: root.join(format!("{:016x}", fingerprint)),
+        }
+    }
+
+    fn entry_path(&self, index: usize) -> PathBuf {
+        self.dir.join(format!("{}.zst", index))
+    }
+
+    /// Frame `index` as cached, `None` when it is not (or the entry cannot
+    /// be read, which is then treated as a miss).
+    pub(crate) fn load(&self, index: usize) -> Option<FramePayload> {
+        let compressed = fs::read(self.entry_path(index)).ok()?;
+        let mut bytes = zstd::decode_all(&compressed[..]).ok()?;
+        let timestamp: [u8; 8] = bytes.get(..8)?.try_into().ok()?;
+        bytes.drain(..8);
+        let timestamp_ms = Some(f64::from_le_bytes(timestamp)).filter(|t| !t.is_nan());
+        Some(FramePayload::Raw {
+            timestamp_ms,
+            bytes,
+        })
+    }
+
+    /// Store frame `index`. Entries are written to a temporary file and
+    /// renamed into place, so concurrent readers never see a partial one.
+    pub(crate) fn store(
+        &self,
+        index: usize,
+        timestamp_ms: Option<f64>,
+        bytes: &[u8],
+    ) -> io::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        let timestamp = timestamp_ms.unwrap_or(f64::NAN).to_le_bytes();
+        let compressed = zstd::encode_all((&timestamp[..]).chain(bytes), LEVEL)?;
+        let path = self.entry_path(index);
+        let partial = self.dir.join(format!(
+            "{}.zst.{}-{}.tmp",
+            index,
+            std::process::id(),
+            PARTIAL_ID.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::write(&partial, compressed)?;
+        fs::rename(&partial, &path).map_err(|err| {
+            let _ = fs::remove_file(&partial);
+            err
+        })
+    }
+
+    /// Inflate a compressed `payload` of frame `index` in place and store
+    /// the result. A failed store is kept in `store_error` (the first one
+    /// only) rather than failing the read.
+    pub(crate) fn inflate_and_store(
+        &self,
+        geometry: &FrameGeometry,
+        index: usize,
+        payload: &mut FramePayload,
+        store_error: &OnceLock<io::Error>,
+    ) -> Result<()> {
+        let FramePayload::Compressed(data) = payload else {
+            return Ok(());
+        };
+        let inflated = geometry.inflate(index, data)?;
+        if let FramePayload::Raw {
+            timestamp_ms,
+            bytes,
+        } = &inflated
+        {
+            if let Err(err) = self.store(index, *timestamp_ms, bytes) {
+                let _ = store_error.set(err);
+            }
+        }
+        *payload = inflated;
+        Ok(())
+    }
+}
+
+/// Identifies a file on disk for cache keys: a hash of its canonical path
+/// (when opened by path), its device and inode (on Unix), its size and its
+/// modification time. `None` when neither a path nor an inode tells the
+/// file apart from others, or its metadata cannot be read.
+pub(crate) fn file_identity(path: Option<&Path>, file: &fs::File) -> Option<u64> {
+    let meta = file.metadata().ok()?;
+    let path = path.and_then(|path| fs::canonicalize(path).ok());
+    if path.is_none() && !cfg!(unix) {
+        return None;
+    }
+    let mut hash = FNV_OFFSET;
+    if let Some(path) = path {
+        hash = fnv1a(hash, path.to_string_lossy().as_bytes());
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        hash = fnv1a(hash, &meta.dev().to_le_bytes());
+        hash = fnv1a(hash, &meta.ino().to_le_bytes());
+    }
+    let modified = meta
+        .modified()
+        .ok()?
+        .duration_since(std::time::UNIX_EPOCH)
+        .ok()?;
+    hash = fnv1a(hash, &meta.len().to_le_bytes());
+    hash = fnv1a(hash, &modified.as_secs().to_le_bytes());
+    Some(fnv1a(hash, &modified.subsec_nanos().to_le_bytes()))
+}
+
+/// 64-bit FNV-1a, a stable hash for cache directory names.
+pub(crate) fn fnv1a(hash: u64, bytes: &[u8]) -> u64 {
+    bytes.iter().fold(hash, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+/// FNV-1a offset basis.
+pub(crate) const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
@@ -0,0 +1,348 @@
+//! Frame reads shared between threads, and the chunk I/O behind every
+//! frame read.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::sync::{Arc, RwLock};
+
+use crate::constants::ND2_CHUNK_MAGIC;
+use crate::decode::{FrameGeometry, FramePayload};
+use crate::error::{Nd2Error, Result};
+use crate::io::PositionalReader;
+use crate::pixel::{stored_type_name, Pixel};
+use crate::types::PixelDataType;
+
+/// Reads frames of one file from any number of threads at once.
+///
+/// Obtained from [`Nd2File::frame_reader`](crate::Nd2File::frame_reader).
+/// Unlike [`Nd2File::read_frame`](crate::Nd2File::read_frame), reads take
+/// `&self`: each one is a positional read of its own chunk, with no shared
+/// cursor or buffer, so a single `FrameReader` can be shared by reference
+/// (or cloned cheaply) across worker threads without a lock. Chunk headers
+/// are checked until their layout is confirmed, after which frames are
+/// located from the chunkmap alone, as by `Nd2File` reads.
+///
+/// Frames are read from the file as it was indexed when the reader was
+/// created; the frame cache of [`Nd2Options::frame_cache`] is not used.
+///
+/// [`Nd2Options::frame_cache`]: crate::Nd2Options
+#[derive(Clone)]
+pub struct FrameReader {
+    file: Arc<File>,
+    geometry: FrameGeometry,
+    bits_per_component_in_memory: u32,
+    pixel_data_type: PixelDataType,
+    /// (offset, size) of each frame chunk by sequence index.
+    chunks: Arc<[Option<(u64, u64)>]>,
+    spans: Arc<RwLock<FrameSpans>>,
+    /// File size when the reader was created, bounding derived spans.
+    file_size: u64,
+}
+
+impl FrameReader {
+    pub(crate) fn new(
+        file: Arc<File>,
+        geometry: FrameGeometry,
+        bits_per_component_in_memory: u32,
+        pixel_data_type: PixelDataType,
+        chunks: Arc<[Option<(u64, u64)>]>,
+        spans: FrameSpans,
+        file_size: u64,
+    ) -> Self {
+        Self {
+            file,
+            geometry,
+            bits_per_component_in_memory,
+            pixel_data_type,
+            chunks,
+            spans: Arc::new(RwLock::new(spans)),
+            file_size,
+        }
+    }
+
+    /// Number of frames, as [`Nd2File::n_frames`](crate::Nd2File::n_frames)
+    /// returned when the reader was created.
+    pub fn n_frames(&self) -> usize {
+        self.chunks.len()
+    }
+
+    /// Read one frame by sequence index as (C, Y, X) u16 data.
+    pub fn read_frame(&self, index: usize) -> Result<Vec<u16>> {
+        self.read_frame_as(index)
+    }
+
+    /// Read one frame by sequence index as (C, Y, X) components of type `T`.
+    pub fn read_frame_as<T: Pixel>(&self, index: usize) -> Result<Vec<T>> {
+        if !T::accepts(self.bits_per_component_in_memory, self.pixel_data_type) {
+            return Err(Nd2Error::input_incompatible(
+                stored_type_name(self.bits_per_component_in_memory, self.pixel_data_type),
+                T::NAME,
+            ));
+        }
+        let geometry = &self.geometry;
+        let mut reader = PositionalReader::new(&*self.file);
+        let payload = self
+            .span(&mut reader, index)
+            .and_then(|span| read_frame_span(&mut reader, index, span, geometry, Vec::new()))
+            .map_err(|err| frame_read_error(err, index, self.n_frames()))?;
+        geometry
+            .decode(index, &payload)
+            .map(|(pixels, _timestamp)| pixels)
+    }
+
+    fn span<R: Read + Seek>(&self, reader: &mut R, index: usize) -> Result<FrameSpan> {
+        let location = *self.chunks.get(index).ok_or_else(|| {
+            Nd2Error::input_out_of_range("sequence index", index, self.chunks.len())
+        })?;
+        let derived = match self.spans.read() {
+            Ok(spans) => spans.derive(index, location, &self.geometry, self.file_size),
+            Err(_) => None,
+        };
+        if let Some(span) = derived {
+            return Ok(span);
+        }
+        let span = locate_frame(reader, index, location, &self.geometry)?;
+        if let Ok(mut spans) = self.spans.write() {
+            spans.confirm(index, location, span);
+        }
+        Ok(span)
+    }
+}
+
+impl fmt::Debug for FrameReader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FrameReader")
+            .field("n_frames", &self.n_frames())
+            .field("compressed", &self.geometry.compressed)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Where a frame chunk's bytes are, once its header has been checked.
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum FrameSpan {
+    /// The whole chunk payload: timestamp, then the zlib stream.
+    Compressed { data_offset: u64, len: usize },
+    /// The timestamp, when the chunk header was intact, and the raw pixel
+    /// rows.
+    Raw {
+        timestamp_offset: Option<u64>,
+        pixel_offset: u64,
+    },
+}
+
+/// Header layout of frame chunks, confirmed by reading chunk headers, so
+/// later frames are located from their chunkmap entry alone.
+///
+/// NIS Elements writes every frame chunk with the same header, whose length
+/// only depends on the length of the chunk name. Once one chunk with a name
+/// of a given length has been checked, the bytes of the others follow from
+/// their offset and size in the chunkmap without reading their headers; a
+/// sequential pass checks a handful of headers rather than one per frame.
+/// Holds one entry per name length, so it stays small however many frames
+/// are read.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct FrameSpans {
+    /// Bytes from the start of a chunk to its data, by chunk name length.
+    header_lengths: BTreeMap<usize, u64>,
+}
+
+impl FrameSpans {
+    /// Span of frame `index` from its chunkmap entry at `location`, when a
+    /// chunk header with a name as long as its own was confirmed and the
+    /// bytes the entry claims lie inside a file of `file_size` bytes.
+    pub(crate) fn derive(
+        &self,
+        index: usize,
+        location: Option<(u64, u64)>,
+        geometry: &FrameGeometry,
+        file_size: u64,
+    ) -> Option<FrameSpan> {
+        let (offset, size) = location?;
+        let data_offset = offset.checked_add(*self.header_lengths.get(&frame_name_len(index))?)?;
+        if data_offset.checked_add(size)? > file_size {
+            return None;
+        }
+        if geometry.compressed {
+            if size > geometry.max_chunk_bytes {
+                return None;
+            }
+            return Some(FrameSpan::Compressed {
+                data_offset,
+                len: usize::try_from(size).ok()?,
+            });
+        }
+        if size < 8 + geometry.expected_raw as u64 {
+            return None;
+        }
+        Some(FrameSpan::Raw {
+            timestamp_offset: Some(data_offset),
+            pixel_offset: data_offset + 8,
+        })
+    }
+
+    /// Record the header layout of frame `index`, whose chunk at `location`
+    /// was checked and found at `span`. Chunks whose data length disagrees
+    /// with their chunkmap entry are not trusted as a model for others.
+    pub(crate) fn confirm(&mut self, index: usize, location: Option<(u64, u64)>, span: FrameSpan) {
+        let Some((offset, size)) = location else {
+            return;
+        };
+        let data_offset = match span {
+            FrameSpan::Compressed { data_offset, len } if len as u64 == size => data_offset,
+            FrameSpan::Raw {
+                timestamp_offset: Some(data_offset),
+                ..
+            } => data_offset,
+            _ => return,
+        };
+        if let Some(header_length) = data_offset.checked_sub(offset) {
+            self.header_lengths
+                .insert(frame_name_len(index), header_length);
+        }
+    }
+}
+
+/// Length of `ImageDataSeq|{index}!`.
+fn frame_name_len(index: usize) -> usize {
+    "ImageDataSeq|!".len() + index.checked_ilog10().map_or(1, |log| log as usize + 1)
+}
+
+/// Locate frame `index`'s bytes from its chunk at `location`, checking the
+/// chunk header against the chunkmap and the file size.
+pub(crate) fn locate_frame<R: Read + Seek>(
+    reader: &mut R,
+    index: usize,
+    location: Option<(u64, u64)>,
+    geometry: &FrameGeometry,
+) -> Result<FrameSpan> {
+    let chunk_name = format!("ImageDataSeq|{}!", index);
+    let (offset, size) = location.ok_or_else(|| Nd2Error::file_chunk_not_found(&chunk_name))?;
+    if geometry.compressed {
+        let (data_offset, len) = crate::chunk::read_chunk_span(
+            reader,
+            chunk_name.as_bytes(),
+            offset,
+            size,
+            geometry.max_chunk_bytes,
+        )?;
+        return Ok(FrameSpan::Compressed { data_offset, len });
+    }
+
+    let file_size = reader.seek(SeekFrom::End(0))?;
+    crate::chunk::check_chunk_offset(chunk_name.as_bytes(), offset, file_size)?;
+    let timestamp_offset = image_chunk_payload_offset(reader, offset)?;
+    let pixel_offset = match timestamp_offset {
+        Some(payload_offset) => payload_offset.checked_add(8).ok_or_else(|| {
+            Nd2Error::file_invalid_format("Frame payload offset overflow".to_string())
+        })?,
+        None => offset.checked_add(4096).ok_or_else(|| {
+            Nd2Error::file_invalid_format("Frame fallback offset overflow".to_string())
+        })?,
+    };
+    let pixel_end = pixel_offset
+        .checked_add(geometry.expected_raw as u64)
+        .ok_or_else(|| Nd2Error::file_invalid_format("Frame bounds overflow".to_string()))?;
+    if pixel_end > file_size {
+        return Err(Nd2Error::file_invalid_format(format!(
+            "Frame chunk '{}' exceeds file bounds",
+            chunk_name
+        )));
+    }
+    Ok(FrameSpan::Raw {
+        timestamp_offset,
+        pixel_offset,
+    })
+}
+
+/// Read frame `index`'s bytes at `span` into `bytes`, without decoding them.
+pub(crate) fn read_frame_span<R: Read + Seek>(
+    reader: &mut R,
+    index: usize,
+    span: FrameSpan,
+    geometry: &FrameGeometry,
+    mut bytes: Vec<u8>,
+) -> Result<FramePayload> {
+    match span {
+        FrameSpan::Compressed { data_offset, len } => {
+            let chunk_name = format!("ImageDataSeq|{}!", index);
+            crate::chunk::read_chunk_data(
+                reader,
+                chunk_name.as_bytes(),
+                data_offset,
+                len,
+                &mut bytes,
+            )?;
+            Ok(FramePayload::Compressed(bytes))
+        }
+        FrameSpan::Raw {
+            timestamp_offset,
+            pixel_offset,
+        } => {
+            let timestamp_ms = match timestamp_offset {
+                Some(timestamp_offset) => {
+                    reader.seek(SeekFrom::Start(timestamp_offset))?;
+                    let mut timestamp = [0u8; 8];
+                    reader.read_exact(&mut timestamp)?;
+                    Some(f64::from_le_bytes(timestamp))
+                }
+                None => None,
+            };
+            reader.seek(SeekFrom::Start(pixel_offset))?;
+            bytes.clear();
+            bytes.resize(geometry.expected_raw, 0);
+            reader.read_exact(&mut bytes)?;
+            Ok(FramePayload::Raw {
+                timestamp_ms,
+                bytes,
+            })
+        }
+    }
+}
+
+/// Offset of the payload of the image chunk at `offset`, `None` when no
+/// intact chunk header is there. A chunk header with no data (a placeholder
+/// never filled in) is an error, rather than reading the bytes after it as
+/// the frame.
+pub(crate) fn image_chunk_payload_offset<R: Read + Seek>(
+    reader: &mut R,
+    offset: u64,
+) -> Result<Option<u64>> {
+    reader.seek(SeekFrom::Start(offset))?;
+
+    let header = match crate::chunk::ChunkHeader::read(reader) {
+        Ok(header) => header,
+        Err(_) => return Ok(None),
+    };
+
+    if header.magic != ND2_CHUNK_MAGIC {
+        return Ok(None);
+    }
+    if header.data_length == 0 {
+        return Err(Nd2Error::file_invalid_format(format!(
+            "Frame chunk at offset {} is an empty placeholder",
+            offset
+        )));
+    }
+
+    let payload_offset = offset
+        .checked_add(16)
+        .and_then(|v| v.checked_add(header.name_length as u64))
+        .ok_or_else(|| {
+            Nd2Error::file_invalid_format("Frame payload offset overflow".to_string())
+        })?;
+
+    Ok(Some(payload_offset))
+}
+
+/// Report a frame without a chunkmap entry as an out-of-range index.
+pub(crate) fn frame_read_error(err: Nd2Error, index: usize, sequence_count: usize) -> Nd2Error {
+    match err {
+        Nd2Error::File {
+            source: crate::error::FileError::ChunkNotFound { .. },
+        } => Nd2Error::input_out_of_range("sequence index", index, sequence_count),
+        err => err,
+    }
+}
@@ -1,6 +1,191 @@
-use std::io::{Read, Seek};
+use std::borrow::Borrow;
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::Path;
+
+use crate::options::ShareMode;
+
+/// A memory map of a whole file, shared by the reader reading through it
+/// and the slices [`Nd2File`](crate::Nd2File) lends from it.
+#[cfg(feature = "mmap")]
+#[derive(Clone)]
+pub(crate) struct SharedMap(pub(crate) std::sync::Arc<memmap2::Mmap>);
+
+#[cfg(feature = "mmap")]
+impl AsRef<[u8]> for SharedMap {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
 
 /// Type-erased readable/seekable source for ND2 parsing.
 pub trait ReadSeek: Read + Seek {}
 
 impl<T: Read + Seek> ReadSeek for T {}
+
+/// Open `path` read-only with the requested share mode.
+pub(crate) fn open_shared(path: &Path, mode: ShareMode) -> std::io::Result<File> {
+    let mut options = std::fs::OpenOptions::new();
+    options.read(true);
+    #[cfg(windows)]
+    {
+        use std::os::windows::fs::OpenOptionsExt;
+        const FILE_SHARE_READ: u32 = 0x1;
+        const FILE_SHARE_WRITE: u32 = 0x2;
+        const FILE_SHARE_DELETE: u32 = 0x4;
+        options.share_mode(match mode {
+            ShareMode::Shared => FILE_SHARE_READ | FILE_SHARE_WRITE | FILE_SHARE_DELETE,
+            ShareMode::DenyWrite => FILE_SHARE_READ,
+        });
+    }
+    #[cfg(not(windows))]
+    let _ = mode;
+    options.open(path)
+}
+
+/// Whether `a` and `b` name the same existing file.
+pub(crate) fn same_file(a: &Path, b: &Path) -> bool {
+    std::fs::canonicalize(a).is_ok_and(|a| std::fs::canonicalize(b).is_ok_and(|b| a == b))
+}
+
+/// Whether this platform has positional reads (`pread` / `ReadFile` at an
+/// offset) for [`PositionalReader`].
+pub(crate) const POSITIONAL_READS: bool = cfg!(any(unix, windows));
+
+/// [`Read`] + [`Seek`] over positional file reads.
+///
+/// Seeking only moves an in-memory cursor, so jumping between frames costs
+/// nothing and never discards buffered data, and several readers can share
+/// one `&File` across threads.
+#[derive(Debug)]
+pub(crate) struct PositionalReader<F> {
+    file: F,
+    pos: u64,
+}
+
+impl<F: Borrow<File>> PositionalReader<F> {
+    pub(crate) fn new(file: F) -> Self {
+        Self { file, pos: 0 }
+    }
+}
+
+impl<F: Borrow<File>> Read for PositionalReader<F> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = read_at(self.file.borrow(), buf, self.pos)?;
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+impl<F: Borrow<File>> Seek for PositionalReader<F> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let (base, offset) = match pos {
+            SeekFrom::Start(pos) => {
+                self.pos = pos;
+                return Ok(pos);
+            }
+            // Files still being acquired grow, so ask for the current length.
+            SeekFrom::End(offset) => (self.file.borrow().metadata()?.len(), offset),
+            SeekFrom::Current(offset) => (self.pos, offset),
+        };
+        self.pos = base.checked_add_signed(offset).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )
+        })?;
+        Ok(self.pos)
+    }
+}
+
+/// [`PositionalReader`] whose reads from `start` to the end of the map are
+/// served from a memory map of that region of the file.
+///
+/// Used to map only the metadata at the end of an ND2 file when the whole
+/// file cannot be mapped, while frame data is still read from the file.
+#[cfg(feature = "mmap")]
+pub(crate) struct TailMappedReader<F> {
+    inner: PositionalReader<F>,
+    start: u64,
+    map: memmap2::Mmap,
+}
+
+#[cfg(feature = "mmap")]
+impl<F: Borrow<File>> TailMappedReader<F> {
+    /// Map `file` from `start` to its current end.
+    ///
+    /// # Safety
+    ///
+    /// The file must not be truncated or written to while the returned
+    /// reader lives. The mapped tail holds the chunkmap, which a writer
+    /// still appending frames rewrites on every save.
+    pub(crate) unsafe fn new(file: F, start: u64) -> io::Result<Self> {
+        let len = file.borrow().metadata()?.len().saturating_sub(start);
+        let len = usize::try_from(len).map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::Other,
+                "mapped region does not fit in the address space",
+            )
+        })?;
+        // SAFETY: the caller guarantees that the file is not modified or
+        // truncated while the map, owned by the returned reader, lives.
+        let map = unsafe {
+            memmap2::MmapOptions::new()
+                .offset(start)
+                .len(len)
+                .map(file.borrow())?
+        };
+        Ok(Self {
+            inner: PositionalReader::new(file),
+            start,
+            map,
+        })
+    }
+}
+
+#[cfg(feature = "mmap")]
+impl<F: Borrow<File>> Read for TailMappedReader<F> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mapped = self
+            .inner
+            .pos
+            .checked_sub(self.start)
+            .and_then(|offset| usize::try_from(offset).ok())
+            .and_then(|offset| self.map.get(offset..))
+            .filter(|rest| !rest.is_empty());
+        match mapped {
+            Some(rest) => {
+                let n = rest.len().min(buf.len());
+                buf[..n].copy_from_slice(&rest[..n]);
+                self.inner.pos += n as u64;
+                Ok(n)
+            }
+            None => self.inner.read(buf),
+        }
+    }
+}
+
+#[cfg(feature = "mmap")]
+impl<F: Borrow<File>> Seek for TailMappedReader<F> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.inner.seek(pos)
+    }
+}
+
+#[cfg(unix)]
+fn read_at(file: &File, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+    std::os::unix::fs::FileExt::read_at(file, buf, offset)
+}
+
+#[cfg(windows)]
+fn read_at(file: &File, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+    std::os::windows::fs::FileExt::seek_read(file, buf, offset)
+}
+
+#[cfg(not(any(unix, windows)))]
+fn read_at(_file: &File, _buf: &mut [u8], _offset: u64) -> io::Result<usize> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "positional reads are not available on this platform",
+    ))
+}
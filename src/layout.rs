@@ -0,0 +1,23 @@
+/// Memory layout of a single frame, for [`Nd2File::read_frame_ordered`].
+///
+/// [`Nd2File::read_frame_ordered`]: crate::Nd2File::read_frame_ordered
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum FrameOrder {
+    /// Planar: one Y × X plane per channel (the layout of [`crate::Nd2File::read_frame`]).
+    #[default]
+    Cyx,
+    /// Interleaved: channels are the fastest-varying axis.
+    Yxc,
+}
+
+/// Memory layout of all planes of one position, for [`Nd2File::read_stack`].
+///
+/// [`Nd2File::read_stack`]: crate::Nd2File::read_stack
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum StackOrder {
+    /// T, C, Z, Y, X (the order used by the Zarr exporter).
+    #[default]
+    Tczyx,
+    /// T, Z, C, Y, X (ImageJ hyperstack order).
+    Tzcyx,
+}
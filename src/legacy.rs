@@ -0,0 +1,491 @@
+//! Legacy (version 1.x) files: a JPEG2000 (JP2) box structure with one
+//! codestream (`jp2c`) per frame and metadata in XML boxes.
+//!
+//! Such a file is read through a [`LegacyView`], which shows it as a
+//! version 2 chunk layout: image attributes from the first codestream's
+//! `SIZ` marker, metadata levels converted from the XML, and a lossy frame
+//! chunk per codestream whose payload is read from the file in place.
+
+use std::io::{self, Read, Seek, SeekFrom};
+
+use crate::chunk::{encode_chunk, encode_chunkmap, ChunkHeader};
+use crate::constants::{ND2_CHUNK_MAGIC, ND2_FILE_SIGNATURE};
+use crate::error::{Nd2Error, Result};
+use crate::meta_parse::TEXT_INFO_LEVEL;
+use crate::parse::{encode_entry, ClxObject, ClxValue};
+
+const BOX_CODESTREAM: &[u8; 4] = b"jp2c";
+const BOX_XML: &[u8; 4] = b"xml ";
+
+/// Start of codestream (`SOC`) and image and tile size (`SIZ`) markers.
+const MARKER_SOC: [u8; 2] = [0xFF, 0x4F];
+const MARKER_SIZ: [u8; 2] = [0xFF, 0x51];
+
+/// Frame geometry of a codestream, from its `SIZ` marker.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct CodestreamSize {
+    width: u32,
+    height: u32,
+    components: u32,
+    bits: u32,
+}
+
+/// Boxes of a legacy file that it is read from.
+#[derive(Debug)]
+pub(crate) struct LegacyLayout {
+    size: CodestreamSize,
+    /// Offset and length of each codestream, in frame order.
+    codestreams: Vec<(u64, u64)>,
+    xml: Vec<String>,
+}
+
+impl LegacyLayout {
+    /// Walk the top-level boxes of `reader`. `None` when it holds no
+    /// codestream, so there is nothing to read as a legacy ND2 file.
+    pub(crate) fn scan<R: Read + Seek>(reader: &mut R, max_box_bytes: u64) -> Result<Option<Self>> {
+        let file_size = reader.seek(SeekFrom::End(0))?;
+        let mut codestreams = Vec::new();
+        let mut xml = Vec::new();
+        let mut offset = 0u64;
+        while file_size - offset >= 8 {
+            reader.seek(SeekFrom::Start(offset))?;
+            let mut header = [0u8; 8];
+            reader.read_exact(&mut header)?;
+            let box_type = [header[4], header[5], header[6], header[7]];
+            let (header_len, box_len) =
+                match u32::from_be_bytes([header[0], header[1], header[2], header[3]]) {
+                    // Zero: the box runs to the end of the file.
+                    0 => (8, file_size - offset),
+                    // One: a 64-bit length follows the type.
+                    1 => {
+                        let mut length = [0u8; 8];
+                        reader.read_exact(&mut length)?;
+                        (16, u64::from_be_bytes(length))
+                    }
+                    length => (8, u64::from(length)),
+                };
+            if box_len < header_len || box_len > file_size - offset {
+                return Err(Nd2Error::file_invalid_format(format!(
+                    "JP2 box '{}' at offset {} has invalid length {}",
+                    String::from_utf8_lossy(&box_type),
+                    offset,
+                    box_len
+                )));
+            }
+            let payload = (offset + header_len, box_len - header_len);
+            if &box_type == BOX_CODESTREAM {
+                codestreams.push(payload);
+            } else if &box_type == BOX_XML {
+                if payload.1 > max_box_bytes {
+                    return Err(Nd2Error::file_invalid_format(format!(
+                        "XML box at offset {} is {} bytes, over the {} byte limit",
+                        offset, payload.1, max_box_bytes
+                    )));
+                }
+                let mut text = Vec::new();
+                reader.take(payload.1).read_to_end(&mut text)?;
+                xml.push(String::from_utf8_lossy(&text).into_owned());
+            }
+            offset += box_len;
+        }
+        let Some(&(first, first_len)) = codestreams.first() else {
+            return Ok(None);
+        };
+        let mut head = Vec::new();
+        reader.seek(SeekFrom::Start(first))?;
+        reader.take(first_len.min(256)).read_to_end(&mut head)?;
+        let size = codestream_size(&head).ok_or_else(|| {
+            Nd2Error::file_invalid_format(format!(
+                "Codestream at offset {} has no valid SIZ marker",
+                first
+            ))
+        })?;
+        Ok(Some(Self {
+            size,
+            codestreams,
+            xml,
+        }))
+    }
+
+    /// Number of frames, one per codestream.
+    pub(crate) fn frame_count(&self) -> usize {
+        self.codestreams.len()
+    }
+}
+
+/// Geometry from the `SIZ` marker, which directly follows `SOC`.
+fn codestream_size(head: &[u8]) -> Option<CodestreamSize> {
+    if head.get(..2)? != MARKER_SOC || head.get(2..4)? != MARKER_SIZ {
+        return None;
+    }
+    let u32_at = |at: usize| Some(u32::from_be_bytes(head.get(at..at + 4)?.try_into().ok()?));
+    // Marker segment: Lsiz, Rsiz, Xsiz, Ysiz, XOsiz, YOsiz, four tile
+    // fields, Csiz, then Ssiz, XRsiz and YRsiz of each component.
+    let width = u32_at(8)?.checked_sub(u32_at(16)?)?;
+    let height = u32_at(12)?.checked_sub(u32_at(20)?)?;
+    let components = u32::from(u16::from_be_bytes(head.get(40..42)?.try_into().ok()?));
+    let bits = u32::from(head.get(42)? & 0x7F) + 1;
+    (width > 0 && height > 0 && components > 0).then_some(CodestreamSize {
+        width,
+        height,
+        components,
+        bits,
+    })
+}
+
+/// Metadata levels found in the XML boxes, re-encoded as chunk data.
+#[derive(Default)]
+struct LegacyMetadata {
+    experiment: Option<Vec<u8>>,
+    text_info: Option<Vec<u8>>,
+    picture: Option<Vec<u8>>,
+}
+
+impl LegacyMetadata {
+    /// Sort each XML box by what it describes. Boxes that are not variant
+    /// XML, or describe nothing read from modern files, are ignored.
+    fn from_xml(xml: &[String]) -> Result<Self> {
+        let mut metadata = Self::default();
+        for text in xml {
+            let Some(ClxValue::Object(level)) = variant_xml(text) else {
+                continue;
+            };
+            let level = unwrap_level(level);
+            let (slot, name) = if ["SLxExperiment", "uLoopPars", "eType"]
+                .iter()
+                .any(|key| level.contains_key(*key))
+            {
+                (&mut metadata.experiment, "SLxExperiment")
+            } else if ["SLxPictureMetadata", "sPicturePlanes", "dCalibration"]
+                .iter()
+                .any(|key| level.contains_key(*key))
+            {
+                (&mut metadata.picture, "SLxPictureMetadata")
+            } else if level
+                .keys()
+                .any(|key| key.starts_with("TextInfoItem_") || &**key == TEXT_INFO_LEVEL)
+            {
+                (&mut metadata.text_info, TEXT_INFO_LEVEL)
+            } else {
+                continue;
+            };
+            if slot.is_none() {
+                let level = match level.get(name) {
+                    Some(inner @ ClxValue::Object(_)) => inner.clone(),
+                    _ => ClxValue::Object(level),
+                };
+                let mut data = Vec::new();
+                encode_entry(name, &level, &mut data)?;
+                *slot = Some(data);
+            }
+        }
+        Ok(metadata)
+    }
+}
+
+/// The level inside wrappers holding nothing else.
+fn unwrap_level(mut level: ClxObject) -> ClxObject {
+    while level.len() == 1 {
+        match level.values().next() {
+            Some(ClxValue::Object(inner))
+                if !matches!(
+                    level.keys().next().map(|key| &**key),
+                    Some("SLxExperiment" | "SLxPictureMetadata" | "SLxImageTextInfo")
+                ) =>
+            {
+                level = inner.clone();
+            }
+            _ => break,
+        }
+    }
+    level
+}
+
+/// A file shown as the version 2 chunk layout of its [`LegacyLayout`]:
+/// pieces of generated chunk data and ranges of the file, end to end.
+pub(crate) struct LegacyView<R> {
+    source: R,
+    /// Start in the view and content of each piece, in order.
+    pieces: Vec<(u64, Piece)>,
+    len: u64,
+    position: u64,
+}
+
+enum Piece {
+    Bytes(Vec<u8>),
+    /// Offset and length in the file.
+    Source(u64, u64),
+}
+
+impl Piece {
+    fn len(&self) -> u64 {
+        match self {
+            Piece::Bytes(bytes) => bytes.len() as u64,
+            Piece::Source(_, len) => *len,
+        }
+    }
+}
+
+impl<R: Read + Seek> LegacyView<R> {
+    pub(crate) fn new(source: R, layout: &LegacyLayout) -> Result<Self> {
+        let mut view = Self {
+            source,
+            pieces: Vec::new(),
+            len: 0,
+            position: 0,
+        };
+        let mut entries = Vec::new();
+        let mut signature = b"Ver1.0".to_vec();
+        signature.resize(64, 0);
+        view.push(Piece::Bytes(encode_chunk(ND2_FILE_SIGNATURE, &signature)));
+
+        let metadata = LegacyMetadata::from_xml(&layout.xml)?;
+        let frames = layout.frame_count() as u64;
+        let experiment = match metadata.experiment {
+            Some(experiment) => Some(experiment),
+            None if frames > 1 => {
+                let mut data = Vec::new();
+                encode_entry(
+                    "SLxExperiment",
+                    &object([
+                        ("eType", ClxValue::UInt(1)),
+                        ("uLoopPars", object([("uiCount", ClxValue::UInt(frames))])),
+                    ]),
+                    &mut data,
+                )?;
+                Some(data)
+            }
+            None => None,
+        };
+        let chunks = [
+            (&b"ImageAttributes!"[..], Some(attributes(layout)?)),
+            (b"ImageMetadata!", experiment),
+            (b"ImageTextInfo!", metadata.text_info),
+            (b"ImageMetadataSeq|0!", metadata.picture),
+        ];
+        for (name, data) in chunks {
+            if let Some(data) = data {
+                entries.push((name.to_vec(), view.len, data.len() as u64));
+                view.push(Piece::Bytes(encode_chunk(name, &data)));
+            }
+        }
+
+        // Frame chunks: header, name and an unknown (zero) timestamp, then
+        // the codestream in place.
+        for (index, &(offset, len)) in layout.codestreams.iter().enumerate() {
+            let name = format!("ImageDataSeq|{}!", index).into_bytes();
+            let header = ChunkHeader {
+                magic: ND2_CHUNK_MAGIC,
+                name_length: name.len() as u32,
+                data_length: 8 + len,
+            };
+            entries.push((name.clone(), view.len, 8 + len));
+            let mut head = header.encode().to_vec();
+            head.extend_from_slice(&name);
+            head.extend_from_slice(&0f64.to_le_bytes());
+            view.push(Piece::Bytes(head));
+            view.push(Piece::Source(offset, len));
+        }
+        let map = encode_chunkmap(&entries, view.len);
+        view.push(Piece::Bytes(map));
+        Ok(view)
+    }
+
+    fn push(&mut self, piece: Piece) {
+        let len = piece.len();
+        self.pieces.push((self.len, piece));
+        self.len += len;
+    }
+}
+
+/// Image attributes of the codestreams: interleaved components, each a
+/// channel, kept in 8, 16 or 32 bits.
+fn attributes(layout: &LegacyLayout) -> Result<Vec<u8>> {
+    let size = layout.size;
+    let in_memory: u32 = match size.bits {
+        0..=8 => 8,
+        9..=16 => 16,
+        _ => 32,
+    };
+    let width_bytes = u64::from(size.width) * u64::from(size.components) * u64::from(in_memory / 8);
+    let width_bytes = u32::try_from(width_bytes)
+        .map_err(|_| Nd2Error::internal_overflow("legacy frame row size"))?;
+    let mut data = Vec::new();
+    encode_entry(
+        "SLxImageAttributes",
+        &object([
+            ("uiWidth", ClxValue::UInt(size.width.into())),
+            ("uiWidthBytes", ClxValue::UInt(width_bytes.into())),
+            ("uiHeight", ClxValue::UInt(size.height.into())),
+            ("uiComp", ClxValue::UInt(size.components.into())),
+            ("uiChannelCount", ClxValue::UInt(size.components.into())),
+            ("uiBpcInMemory", ClxValue::UInt(in_memory.into())),
+            ("uiBpcSignificant", ClxValue::UInt(size.bits.into())),
+            (
+                "uiSequenceCount",
+                ClxValue::UInt(layout.frame_count() as u64),
+            ),
+            ("eCompression", ClxValue::String("lossy".to_string())),
+        ]),
+        &mut data,
+    )?;
+    Ok(data)
+}
+
+fn object<const N: usize>(entries: [(&str, ClxValue); N]) -> ClxValue {
+    ClxValue::Object(
+        entries
+            .into_iter()
+            .map(|(name, value)| (name.into(), value))
+            .collect(),
+    )
+}
+
+impl<R: Read + Seek> Read for LegacyView<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let at = self
+            .pieces
+            .partition_point(|(start, _)| *start <= self.position);
+        let Some((start, piece)) = at.checked_sub(1).map(|i| &self.pieces[i]) else {
+            return Ok(0);
+        };
+        let within = self.position - start;
+        let left = piece.len().saturating_sub(within);
+        let want = (buf.len() as u64).min(left) as usize;
+        let read = match piece {
+            Piece::Bytes(bytes) => {
+                buf[..want].copy_from_slice(&bytes[within as usize..within as usize + want]);
+                want
+            }
+            Piece::Source(offset, _) => {
+                self.source.seek(SeekFrom::Start(offset + within))?;
+                self.source.read(&mut buf[..want])?
+            }
+        };
+        self.position += read as u64;
+        Ok(read)
+    }
+}
+
+impl<R: Read + Seek> Seek for LegacyView<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let position = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(delta) => self.len.checked_add_signed(delta),
+            SeekFrom::Current(delta) => self.position.checked_add_signed(delta),
+        };
+        self.position = position.ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "seek before start of file")
+        })?;
+        Ok(self.position)
+    }
+}
+
+/// Parse the variant XML NIS-Elements writes (`<variant>` holding elements
+/// with `runtype` and `value` attributes) into a [`ClxValue`]: elements
+/// with children become levels, the others values of their `runtype`.
+/// `None` when `text` is not well-formed enough to read.
+fn variant_xml(text: &str) -> Option<ClxValue> {
+    let mut stack: Vec<(String, ClxObject)> = vec![(String::new(), ClxObject::new())];
+    let mut rest = text;
+    while let Some(open) = rest.find('<') {
+        rest = &rest[open + 1..];
+        if let Some(skipped) = rest.strip_prefix("!--") {
+            rest = &skipped[skipped.find("-->")? + 3..];
+            continue;
+        }
+        let close = rest.find('>')?;
+        let tag = &rest[..close];
+        rest = &rest[close + 1..];
+        if tag.starts_with('?') || tag.starts_with('!') {
+            continue;
+        }
+        if let Some(name) = tag.strip_prefix('/') {
+            let (open_name, level) = stack.pop()?;
+            if open_name != name.trim() || stack.is_empty() {
+                return None;
+            }
+            let parent = &mut stack.last_mut()?.1;
+            parent.insert(open_name.as_str().into(), ClxValue::Object(level));
+            continue;
+        }
+        let (tag, empty) = match tag.strip_suffix('/') {
+            Some(tag) => (tag, true),
+            None => (tag, false),
+        };
+        let (name, attributes) = tag.split_once(char::is_whitespace).unwrap_or((tag, ""));
+        let runtype = xml_attribute(attributes, "runtype");
+        let value = match xml_attribute(attributes, "value") {
+            Some(value) => value,
+            None if empty => String::new(),
+            None => match rest.find('<') {
+                // A value written as element text.
+                Some(end) if rest[end..].starts_with("</") && !rest[..end].trim().is_empty() => {
+                    xml_unescape(rest[..end].trim())
+                }
+                _ => {
+                    stack.push((name.to_string(), ClxObject::new()));
+                    continue;
+                }
+            },
+        };
+        if !empty {
+            let end = rest.find("</")?;
+            rest = &rest[end + rest[end..].find('>')? + 1..];
+        }
+        let value = typed_value(runtype.as_deref(), value);
+        stack.last_mut()?.1.insert(name.into(), value);
+    }
+    let (_, root) = stack.pop()?;
+    stack.is_empty().then_some(ClxValue::Object(root))
+}
+
+/// The value of a variant element of type `runtype` written as `value`.
+fn typed_value(runtype: Option<&str>, value: String) -> ClxValue {
+    let runtype = runtype.unwrap_or_default().to_ascii_lowercase();
+    let parsed = if runtype == "bool" {
+        match value.as_str() {
+            "true" | "1" => Some(ClxValue::Bool(true)),
+            "false" | "0" => Some(ClxValue::Bool(false)),
+            _ => None,
+        }
+    } else if runtype.contains("uint") || runtype.contains("dword") {
+        value.parse().ok().map(ClxValue::UInt)
+    } else if runtype.contains("int") {
+        value.parse().ok().map(ClxValue::Int)
+    } else if runtype.contains("double") || runtype.contains("float") {
+        value.parse().ok().map(ClxValue::Float)
+    } else {
+        None
+    };
+    parsed.unwrap_or(ClxValue::String(value))
+}
+
+/// The unescaped value of `name="..."` in an element's `attributes`.
+fn xml_attribute(attributes: &str, name: &str) -> Option<String> {
+    let mut rest = attributes;
+    while let Some(at) = rest.find(name) {
+        let before = rest[..at].chars().next_back();
+        let after = rest[at + name.len()..].trim_start();
+        rest = &rest[at + name.len()..];
+        if before.map_or(true, char::is_whitespace) {
+            if let Some(after) = after.strip_prefix('=') {
+                let after = after.trim_start();
+                let quote = after.chars().next()?;
+                if quote == '"' || quote == '\'' {
+                    let end = after[1..].find(quote)?;
+                    return Some(xml_unescape(&after[1..1 + end]));
+                }
+            }
+        }
+    }
+    None
+}
+
+fn xml_unescape(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
@@ -18,17 +18,76 @@
 //! ```
 
 mod error;
+mod export;
 mod io;
+mod layout;
+mod options;
 mod types;
 
+mod anonymize;
+mod checksum;
 mod chunk;
 mod constants;
+#[cfg(feature = "polars")]
+mod dataframe;
+mod decode;
+mod edit;
+mod frame;
+#[cfg(feature = "frame-cache")]
+mod frame_cache;
+mod frame_reader;
+mod legacy;
+mod manifest;
 #[path = "metadata/mod.rs"]
 mod meta_parse;
 mod parse;
+mod patch;
+mod pixel;
 mod reader;
+mod repair;
+pub mod sansio;
+mod subset;
+mod table;
+mod transcode;
+#[cfg(all(feature = "io-uring", target_os = "linux"))]
+mod uring;
+mod validate;
 
-pub use error::{Nd2Error, Result};
+pub use anonymize::anonymize_to;
+pub use edit::edit_text_info;
+pub use error::{
+    ErrorSource, FileError, InputError, InternalError, Nd2Error, Result, UnsupportedError,
+};
+#[cfg(feature = "rerun")]
+pub use export::RerunLogger;
+pub use export::{
+    CompanionExporter, MetaImageExporter, MultipointExporter, N5Exporter, NiftiExporter,
+    OmeTiffExporter, OmeZarrExporter, PngExporter, SidecarFormat, TiffCompression, TiffExporter,
+    TiffLayout, ToneLut, ToneMapping, ToneRange, ZarrExporter, DEFAULT_N5_BLOCK_SIZE,
+    OME_SCHEMA_VERSION,
+};
+#[cfg(feature = "ffmpeg")]
+pub use export::{VideoCodec, VideoExporter};
+pub use frame::{Frame, FrameCoord, FrameIndex, FrameMetadata};
+pub use frame_reader::FrameReader;
 pub use io::ReadSeek;
+pub use layout::{FrameOrder, StackOrder};
+pub use options::{AnonymizePolicy, Limits, Nd2Options, ReadStrategy, ShareMode, SubsetSelection};
+pub use parse::{ClxObject, ClxValue};
+pub use patch::apply_metadata_patch;
+pub use pixel::{Pixel, PixelBuffer};
 pub use reader::Nd2File;
-pub use types::{DatasetSummary, SummaryChannel, SummaryScaling};
+pub use repair::repair_to;
+pub use subset::subset_to;
+pub use transcode::transcode_to;
+pub use types::{
+    Affine2, AnimParam, Attributes, AxisInterpretation, BinaryLayer, BoxShape, Channel,
+    ChannelMeta, ChunkEntry, ChunkListing, Color, CompressionType, Contents, CustomLoop,
+    DatasetSummary, Diagnostic, DiagnosticKind, EditMode, EventKind, ExpLoop, ExperimentEvent,
+    ExtrudedShape, FrameCounts, LoopIndices, Manifest, ManifestReport, Metadata, MetadataPatch,
+    Microscope, NETimeLoop, NETimeLoopParams, NapariColormap, NapariLayer, Nd2Snapshot, Period,
+    PeriodDiff, PixelDataType, Position, RecordedValue, Roi, RoiInfo, RoiInterpType, RoiShapeType,
+    StagePosition, SummaryChannel, SummaryScaling, TextInfo, TimeLoop, TimeLoopParams,
+    ValidationLevel, ValidationReport, Volume, VoxelSize, XYPosLoop, XYPosLoopParams, ZStackLoop,
+    ZStackLoopParams,
+};
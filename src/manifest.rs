@@ -0,0 +1,55 @@
+//! Sidecar integrity manifests for archived files.
+
+use crate::error::Result;
+use crate::reader::Nd2File;
+use crate::types::{Manifest, ManifestReport};
+
+impl Nd2File {
+    /// Record the file's size and digests: of the whole file, its chunkmap
+    /// and every frame chunk. Reads the whole file, and every frame chunk a
+    /// second time; save it next to the file with
+    /// [`Manifest::save`] at [`Manifest::sidecar_path`].
+    pub fn manifest(&mut self) -> Result<Manifest> {
+        let (file_size, file_crc32c) = self.file_crc32c()?;
+        Ok(Manifest {
+            file_size,
+            file_crc32c,
+            chunkmap_crc32c: self.chunkmap_crc32c(),
+            frames: self.frame_crc32cs()?,
+        })
+    }
+
+    /// Check the file against a `manifest` made earlier.
+    ///
+    /// An intact file costs one read of the whole file. Only when it no
+    /// longer matches are the chunkmap and frame chunks compared, to report
+    /// which frames changed.
+    pub fn verify_against(&mut self, manifest: &Manifest) -> Result<ManifestReport> {
+        let mut report = ManifestReport {
+            file_matches: true,
+            chunkmap_matches: true,
+            changed_frames: Vec::new(),
+            missing_frames: Vec::new(),
+            extra_frames: Vec::new(),
+        };
+        if self.file_crc32c()? == (manifest.file_size, manifest.file_crc32c) {
+            return Ok(report);
+        }
+        report.file_matches = false;
+        report.chunkmap_matches = self.chunkmap_crc32c() == manifest.chunkmap_crc32c;
+        let frames = self.frame_crc32cs()?;
+        for (&index, &recorded) in &manifest.frames {
+            match frames.get(&index) {
+                None => report.missing_frames.push(index),
+                Some(&digest) if digest != recorded => report.changed_frames.push(index),
+                Some(_) => {}
+            }
+        }
+        report.extra_frames = frames
+            .keys()
+            .filter(|index| !manifest.frames.contains_key(index))
+            .copied()
+            .collect();
+        Ok(report)
+    }
+}
@@ -16,8 +16,8 @@ pub fn parse_attributes(clx: ClxValue) -> Result<Attributes> {
     let get_u32 = |key: &str| -> Result<u32> {
         // Try the key as-is first, then try with type suffixes
         obj.get(key)
-            .or_else(|| obj.get(&format!("{}_u32", key)))
-            .or_else(|| obj.get(&format!("{}_i32", key)))
+            .or_else(|| obj.get(format!("{}_u32", key).as_str()))
+            .or_else(|| obj.get(format!("{}_i32", key).as_str()))
             .and_then(|v| v.as_u64().or_else(|| v.as_i64().map(|i| i as u64)))
             .map(|v| v as u32)
             .ok_or_else(|| Nd2Error::file_metadata(format!("Missing or invalid {}", key)))
@@ -25,8 +25,8 @@ pub fn parse_attributes(clx: ClxValue) -> Result<Attributes> {
 
     let get_opt_u32 = |key: &str| -> Option<u32> {
         obj.get(key)
-            .or_else(|| obj.get(&format!("{}_u32", key)))
-            .or_else(|| obj.get(&format!("{}_i32", key)))
+            .or_else(|| obj.get(format!("{}_u32", key).as_str()))
+            .or_else(|| obj.get(format!("{}_i32", key).as_str()))
             .and_then(|v| v.as_u64().or_else(|| v.as_i64().map(|i| i as u64)))
             .map(|v| v as u32)
     };
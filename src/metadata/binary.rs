@@ -0,0 +1,109 @@
+//! Binary layers (`CustomDataVar|BinaryMetadata_v1!`) and their
+//! run-length encoded masks.
+
+use std::io::Read;
+
+use flate2::read::ZlibDecoder;
+
+use super::experiment::value_as_u32;
+use crate::error::{Nd2Error, Result};
+use crate::parse::{ClxObject, ClxValue};
+use crate::types::{BinaryLayer, Color};
+
+const BINARY_LEVEL: &str = "BinaryMetadata_v1";
+
+/// The binary layers in `clx`, in stored order. Entries without a file
+/// tag have no masks and are skipped.
+pub fn parse_binary_layers(clx: &ClxValue) -> Vec<BinaryLayer> {
+    let Some(root) = clx.as_object() else {
+        return Vec::new();
+    };
+    let level = root
+        .get(BINARY_LEVEL)
+        .and_then(|v| v.as_object())
+        .unwrap_or(root);
+    level
+        .values()
+        .filter_map(|value| parse_layer(value.as_object()?))
+        .collect()
+}
+
+fn parse_layer(layer: &ClxObject) -> Option<BinaryLayer> {
+    let string = |key: &str| {
+        layer
+            .get(key)
+            .and_then(|v| v.as_str())
+            .unwrap_or_default()
+            .to_string()
+    };
+    let number = |key: &str| layer.get(key).and_then(value_as_u32).unwrap_or(0);
+    let file_tag = string("FileTag");
+    if file_tag.is_empty() {
+        return None;
+    }
+    Some(BinaryLayer {
+        id: number("BinLayerID"),
+        name: string("Name"),
+        comp_name: string("CompName"),
+        comp_order: number("CompOrder"),
+        color: Color::from_abgr_u32(number("Color")),
+        color_mode: number("ColorMode"),
+        state: number("State"),
+        file_tag,
+    })
+}
+
+/// Decode a mask chunk's data into `height` × `width` pixels, row-major.
+/// After a 4-byte prefix, the data is a zlib stream of little-endian
+/// `u32`s: the mask width and height, then run lengths alternating
+/// between unset and set pixels, starting with unset. Pixels past the last
+/// run are unset.
+pub(crate) fn decode_binary_mask(data: &[u8], height: usize, width: usize) -> Result<Vec<bool>> {
+    let stream = data.get(4..).ok_or_else(|| {
+        Nd2Error::file_invalid_format("Binary mask chunk shorter than its prefix".to_string())
+    })?;
+    let area = height.saturating_mul(width);
+    // Every run but the first covers at least one pixel.
+    let max_len = 8 + 4 * (area as u64 + 1);
+    let mut inflated = Vec::new();
+    ZlibDecoder::new(stream)
+        .take(max_len + 1)
+        .read_to_end(&mut inflated)
+        .map_err(|e| Nd2Error::file_invalid_format(format!("Binary mask: {}", e)))?;
+    if inflated.len() as u64 > max_len || inflated.len() < 8 || inflated.len() % 4 != 0 {
+        return Err(Nd2Error::file_invalid_format(format!(
+            "Binary mask inflates to {} bytes, not a {}x{} run-length mask",
+            inflated.len(),
+            width,
+            height
+        )));
+    }
+    let mut words = inflated
+        .chunks_exact(4)
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as usize);
+    let (mask_width, mask_height) = (words.next(), words.next());
+    if (mask_width, mask_height) != (Some(width), Some(height)) {
+        return Err(Nd2Error::file_invalid_format(format!(
+            "Binary mask is {}x{}, frames are {}x{}",
+            mask_width.unwrap_or(0),
+            mask_height.unwrap_or(0),
+            width,
+            height
+        )));
+    }
+    let mut mask = vec![false; area];
+    let mut at = 0usize;
+    for (i, run) in words.enumerate() {
+        let end = at
+            .checked_add(run)
+            .filter(|&end| end <= area)
+            .ok_or_else(|| {
+                Nd2Error::file_invalid_format("Binary mask runs past the frame".to_string())
+            })?;
+        if i % 2 == 1 {
+            mask[at..end].fill(true);
+        }
+        at = end;
+    }
+    Ok(mask)
+}
@@ -0,0 +1,45 @@
+//! Experiment events (`CustomData|ExperimentEventsV1_0!`).
+
+use std::collections::BTreeMap;
+
+use super::roi::{f64_or, field, string, u32_or};
+use crate::parse::{ClxObject, ClxValue};
+use crate::types::{EventKind, ExperimentEvent};
+
+const EVENTS_LEVEL: &str = "ExperimentEventsV1_0";
+
+/// The events in `clx`, by time. Frame positions are left unset; entries
+/// without a time are skipped.
+pub fn parse_events(clx: &ClxValue) -> Vec<ExperimentEvent> {
+    let Some(root) = clx.as_object() else {
+        return Vec::new();
+    };
+    let level = root
+        .get(EVENTS_LEVEL)
+        .and_then(|v| v.as_object())
+        .unwrap_or(root);
+    let events = field(level, "Events")
+        .and_then(|v| v.as_object())
+        .unwrap_or(level);
+    let mut events: Vec<ExperimentEvent> = events
+        .values()
+        .filter_map(|value| value.as_object())
+        .enumerate()
+        .filter_map(|(position, event)| parse_event(event, position as u32))
+        .collect();
+    events.sort_by(|a, b| a.time_ms.total_cmp(&b.time_ms));
+    events
+}
+
+fn parse_event(event: &ClxObject, position: u32) -> Option<ExperimentEvent> {
+    field(event, "Time")?;
+    let kind = field(event, "Meaning").or_else(|| field(event, "Type"));
+    Some(ExperimentEvent {
+        id: u32_or(event, "Id", position),
+        time_ms: f64_or(event, "Time", 0.0),
+        kind: EventKind::from_u32(kind.and_then(super::experiment::value_as_u32).unwrap_or(0)),
+        description: string(event, "Description"),
+        seq_index: None,
+        loop_indices: BTreeMap::new(),
+    })
+}
@@ -1,23 +1,24 @@
-use crate::error::Result;
-use crate::parse::ClxValue;
+use crate::error::{Nd2Error, Result};
+use crate::options::Limits;
+use crate::parse::{ClxLiteParser, ClxObject, ClxValue, ClxValueRef, ClxVisitor, Utf16Str};
 use crate::types::{
-    CustomLoop, ExpLoop, NETimeLoop, NETimeLoopParams, Period, Position, StagePosition, TimeLoop,
-    TimeLoopParams, XYPosLoop, XYPosLoopParams, ZStackLoop, ZStackLoopParams,
+    CustomLoop, Diagnostic, DiagnosticKind, ExpLoop, NETimeLoop, NETimeLoopParams, Period,
+    Position, StagePosition, TimeLoop, TimeLoopParams, XYPosLoop, XYPosLoopParams, ZStackLoop,
+    ZStackLoopParams,
 };
 
-pub fn parse_experiment(clx: ClxValue) -> Result<Vec<ExpLoop>> {
-    parse_experiment_inner(unwrap_single_item(clx), 0, Vec::new())
+pub fn parse_experiment(clx: &ClxValue, diagnostics: &mut Vec<Diagnostic>) -> Result<Vec<ExpLoop>> {
+    parse_experiment_inner(unwrap_single_item(clx), 0, Vec::new(), diagnostics)
 }
 
-fn unwrap_single_item(mut v: ClxValue) -> ClxValue {
+fn unwrap_single_item(mut v: &ClxValue) -> &ClxValue {
     loop {
-        let next = match &v {
-            ClxValue::Array(arr) if arr.len() == 1 => Some(arr[0].clone()),
+        let next = match v {
+            ClxValue::Array(arr) if arr.len() == 1 => arr.first(),
             ClxValue::Object(map) if map.len() == 1 => map
                 .get("")
                 .or_else(|| map.get("i0000000000"))
-                .or_else(|| map.get("SLxExperiment"))
-                .cloned(),
+                .or_else(|| map.get("SLxExperiment")),
             _ => None,
         };
         if let Some(n) = next {
@@ -30,9 +31,10 @@ fn unwrap_single_item(mut v: ClxValue) -> ClxValue {
 }
 
 fn parse_experiment_inner(
-    clx: ClxValue,
+    clx: &ClxValue,
     _level: u32,
     mut dest: Vec<ExpLoop>,
+    diagnostics: &mut Vec<Diagnostic>,
 ) -> Result<Vec<ExpLoop>> {
     let clx = unwrap_single_item(clx);
     let Some(obj) = clx.as_object() else {
@@ -40,7 +42,7 @@ fn parse_experiment_inner(
         return Ok(dest);
     };
 
-    if let Some(exp_loop) = parse_single_loop(obj)? {
+    if let Some(exp_loop) = parse_single_loop(obj, diagnostics)? {
         if exp_loop.count() > 0 {
             dest.push(exp_loop);
         }
@@ -65,45 +67,48 @@ fn parse_experiment_inner(
         };
 
         for item in items {
-            let inner = unwrap_single_item(item.clone());
-            dest = parse_experiment_inner(inner, _level + 1, dest)?;
+            let inner = unwrap_single_item(item);
+            dest = parse_experiment_inner(inner, _level + 1, dest, diagnostics)?;
         }
     }
 
     Ok(dest)
 }
 
-fn value_as_u32(v: &ClxValue) -> Option<u32> {
+pub(crate) fn value_as_u32(v: &ClxValue) -> Option<u32> {
     v.as_u64()
         .map(|u| u as u32)
         .or_else(|| v.as_i64().and_then(|i| (i >= 0).then_some(i as u32)))
 }
 
-fn value_as_f64(v: &ClxValue) -> Option<f64> {
+pub(crate) fn value_as_f64(v: &ClxValue) -> Option<f64> {
     v.as_f64()
         .or_else(|| v.as_u64().map(|u| u as f64))
         .or_else(|| v.as_i64().map(|i| i as f64))
 }
 
-fn value_as_bool(v: &ClxValue) -> Option<bool> {
+pub(crate) fn value_as_bool(v: &ClxValue) -> Option<bool> {
     v.as_bool()
         .or_else(|| v.as_u64().map(|u| u != 0))
         .or_else(|| v.as_i64().map(|i| i != 0))
 }
 
-fn map_get_u32(map: &std::collections::HashMap<String, ClxValue>, key: &str) -> Option<u32> {
+fn map_get_u32(map: &ClxObject, key: &str) -> Option<u32> {
     map.get(key).and_then(value_as_u32)
 }
 
-fn map_get_f64(map: &std::collections::HashMap<String, ClxValue>, key: &str) -> Option<f64> {
+fn map_get_f64(map: &ClxObject, key: &str) -> Option<f64> {
     map.get(key).and_then(value_as_f64)
 }
 
-fn map_get_bool(map: &std::collections::HashMap<String, ClxValue>, key: &str) -> Option<bool> {
+fn map_get_bool(map: &ClxObject, key: &str) -> Option<bool> {
     map.get(key).and_then(value_as_bool)
 }
 
-fn parse_single_loop(obj: &std::collections::HashMap<String, ClxValue>) -> Result<Option<ExpLoop>> {
+fn parse_single_loop(
+    obj: &ClxObject,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> Result<Option<ExpLoop>> {
     let loop_type = map_get_u32(obj, "uiLoopType").or_else(|| map_get_u32(obj, "eType"));
     let nesting_level = map_get_u32(obj, "uiNestingLevel").unwrap_or(0);
 
@@ -227,7 +232,7 @@ fn parse_single_loop(obj: &std::collections::HashMap<String, ClxValue>) -> Resul
                     if !valid.is_empty() && (i >= valid.len() || !valid[i]) {
                         continue;
                     }
-                    let pos_item = unwrap_single_item(pos_item.clone());
+                    let pos_item = unwrap_single_item(pos_item);
                     if let Some(pos_obj) = pos_item.as_object() {
                         let x = ref_x + map_get_f64(pos_obj, "dPosX").unwrap_or(0.0);
                         let y = ref_y + map_get_f64(pos_obj, "dPosY").unwrap_or(0.0);
@@ -335,6 +340,337 @@ fn parse_single_loop(obj: &std::collections::HashMap<String, ClxValue>) -> Resul
             count: loop_count,
             nesting_level,
         }))),
-        _ => Ok(None),
+        Some(other) => {
+            diagnostics.push(Diagnostic::new(
+                DiagnosticKind::UnknownLoopType,
+                format!("Skipped experiment loop with unknown type {other}"),
+            ));
+            Ok(None)
+        }
+        None => Ok(None),
+    }
+}
+
+/// Parameters of the outermost XY position loop in experiment chunk `data`,
+/// as [`parse_experiment`] would find them.
+///
+/// The data is walked with a [`ClxVisitor`] instead of being parsed into a
+/// [`ClxValue`] tree: each point is reduced to its coordinates as it is
+/// read, so position lists with many thousands of entries are never held as
+/// a whole tree.
+pub fn parse_xy_positions(data: &[u8], limits: Limits) -> Result<Option<XYPosLoopParams>> {
+    let mut walk = XyWalk {
+        stack: vec![XyFrame {
+            loop_level: true,
+            ..XyFrame::default()
+        }],
+        entered: 0,
+        found: None,
+    };
+    ClxLiteParser::new(false)
+        .lenient(true)
+        .limits(limits)
+        .visit(data, &mut walk)?;
+    if let Some(root) = walk.stack.pop() {
+        walk.finish_loop(&root);
+    }
+    Ok(walk.found.map(|(_, params)| params))
+}
+
+/// Scalar entries the XY position loop is built from.
+const XY_NUMBERS: [&str; 12] = [
+    "uiLoopType",
+    "eType",
+    "uiCount",
+    "bUseZ",
+    "bIsSettingZ",
+    "bRelativeXY",
+    "dReferenceX",
+    "dReferenceY",
+    "dPosX",
+    "dPosY",
+    "dPosZ",
+    "dPFSOffset",
+];
+
+/// Point name entries, by precedence.
+const XY_NAMES: [&str; 3] = ["dPosName", "pPosName", "wszName"];
+
+/// Names of the single-entry objects [`unwrap_single_item`] looks through.
+const WRAPPERS: [&str; 3] = ["", "i0000000000", "SLxExperiment"];
+
+/// One open object of the walk, keeping only what an XY loop needs.
+#[derive(Default)]
+struct XyFrame {
+    name: String,
+    /// Pre-order position, so the outermost loop wins.
+    order: usize,
+    /// Whether [`parse_experiment`] would look for a loop here.
+    loop_level: bool,
+    entries: usize,
+    numbers: [Option<f64>; XY_NUMBERS.len()],
+    names: [Option<String>; XY_NAMES.len()],
+    /// `uLoopPars`.
+    params: Option<Box<XyFrame>>,
+    /// The last single-entry wrapper object inside this one.
+    inner: Option<Box<XyFrame>>,
+    /// `pItemValid`.
+    valid: Option<Vec<bool>>,
+    /// `Points` and `pPeriod` lists.
+    lists: Vec<(String, Vec<Option<XyPoint>>)>,
+    /// Elements by entry name, while this is one of those lists or
+    /// `pItemValid`.
+    elements: Vec<(String, XyElement)>,
+}
+
+enum XyElement {
+    Value(Option<bool>),
+    Object(XyPoint),
+}
+
+/// A point's stored fields, before the loop's reference and Z setting are
+/// applied.
+struct XyPoint {
+    x: Option<f64>,
+    y: Option<f64>,
+    z: Option<f64>,
+    pfs_offset: Option<f64>,
+    name: Option<String>,
+}
+
+impl XyFrame {
+    fn number(&self, key: &str) -> Option<f64> {
+        let i = XY_NUMBERS.iter().position(|&k| k == key)?;
+        self.numbers[i]
+    }
+
+    fn flag(&self, key: &str) -> Option<bool> {
+        self.number(key).map(|v| v != 0.0)
+    }
+
+    fn list(&self, key: &str) -> Option<&[Option<XyPoint>]> {
+        self.lists
+            .iter()
+            .find(|(name, _)| name == key)
+            .map(|(_, points)| points.as_slice())
+    }
+
+    fn is_list(&self) -> bool {
+        matches!(self.name.as_str(), "Points" | "pPeriod" | "pItemValid")
+    }
+
+    fn unwrapped(&self) -> &XyFrame {
+        match &self.inner {
+            Some(inner) if self.entries == 1 => inner.unwrapped(),
+            _ => self,
+        }
+    }
+
+    fn point(&self) -> XyPoint {
+        let frame = self.unwrapped();
+        XyPoint {
+            x: frame.number("dPosX"),
+            y: frame.number("dPosY"),
+            z: frame.number("dPosZ"),
+            pfs_offset: frame.number("dPFSOffset"),
+            name: frame.names.iter().flatten().next().cloned(),
+        }
+    }
+
+    /// List elements in the order [`parse_single_loop`] visits them: object
+    /// keys sorted, array elements as stored.
+    fn sorted_elements(&mut self) -> Vec<XyElement> {
+        let mut elements = std::mem::take(&mut self.elements);
+        elements.sort_by(|(a, _), (b, _)| a.cmp(b));
+        elements.into_iter().map(|(_, element)| element).collect()
+    }
+}
+
+/// [`ClxVisitor`] collecting XY position loops.
+struct XyWalk {
+    stack: Vec<XyFrame>,
+    entered: usize,
+    found: Option<(usize, XYPosLoopParams)>,
+}
+
+impl XyWalk {
+    fn top(&mut self) -> Result<&mut XyFrame> {
+        self.stack
+            .last_mut()
+            .ok_or_else(|| Nd2Error::internal_invariant("CLX walk left the root object"))
+    }
+
+    /// Record `frame` if it is an XY position loop outside those found so far.
+    fn finish_loop(&mut self, frame: &XyFrame) {
+        let loop_type = frame.number("uiLoopType").or_else(|| frame.number("eType"));
+        if loop_type != Some(2.0) {
+            return;
+        }
+        if matches!(&self.found, Some((order, _)) if *order < frame.order) {
+            return;
+        }
+        let params = match &frame.params {
+            Some(pars) => match &pars.inner {
+                Some(inner) if pars.entries == 1 && inner.name == "i0000000000" => inner,
+                _ => pars,
+            },
+            None => frame,
+        };
+        let is_setting_z = params
+            .flag("bUseZ")
+            .or_else(|| params.flag("bIsSettingZ"))
+            .or_else(|| frame.flag("bIsSettingZ"))
+            .unwrap_or(false);
+        let (ref_x, ref_y) = if params.flag("bRelativeXY").unwrap_or(false) {
+            (
+                params.number("dReferenceX").unwrap_or(0.0),
+                params.number("dReferenceY").unwrap_or(0.0),
+            )
+        } else {
+            (0.0, 0.0)
+        };
+        let valid = frame.valid.as_deref().unwrap_or_default();
+        let items = params
+            .list("Points")
+            .or_else(|| params.list("pPeriod"))
+            .or_else(|| frame.list("pPeriod"))
+            .unwrap_or_default();
+
+        let mut points = Vec::new();
+        for (i, item) in items.iter().enumerate() {
+            if !valid.is_empty() && (i >= valid.len() || !valid[i]) {
+                continue;
+            }
+            let Some(point) = item else {
+                continue;
+            };
+            points.push(Position {
+                stage_position_um: StagePosition {
+                    x: ref_x + point.x.unwrap_or(0.0),
+                    y: ref_y + point.y.unwrap_or(0.0),
+                    z: if is_setting_z {
+                        point.z.unwrap_or(0.0)
+                    } else {
+                        0.0
+                    },
+                },
+                pfs_offset: point.pfs_offset.filter(|v| *v >= 0.0),
+                name: point.name.clone(),
+            });
+        }
+        let loop_count = params
+            .number("uiCount")
+            .or_else(|| frame.number("uiCount"))
+            .unwrap_or(0.0);
+        // Loops without a count are dropped by `parse_experiment`.
+        if points.is_empty() && loop_count < 1.0 {
+            return;
+        }
+        self.found = Some((
+            frame.order,
+            XYPosLoopParams {
+                is_setting_z,
+                points,
+            },
+        ));
+    }
+}
+
+impl ClxVisitor for XyWalk {
+    fn enter(&mut self, name: Utf16Str<'_>) -> Result<bool> {
+        self.entered += 1;
+        let order = self.entered;
+        let parent = self.top()?;
+        parent.entries += 1;
+        let name = name.to_string();
+        let loop_level = parent.loop_level
+            && (WRAPPERS.contains(&name.as_str())
+                || name == "ppNextLevelEx"
+                || parent.name == "ppNextLevelEx");
+        self.stack.push(XyFrame {
+            name,
+            order,
+            loop_level,
+            ..XyFrame::default()
+        });
+        Ok(true)
+    }
+
+    fn leave(&mut self) -> Result<()> {
+        let mut frame = self
+            .stack
+            .pop()
+            .ok_or_else(|| Nd2Error::internal_invariant("CLX walk left the root object"))?;
+        if frame.loop_level {
+            self.finish_loop(&frame);
+        }
+        let parent = self.top()?;
+        if parent.is_list() {
+            let element = XyElement::Object(frame.point());
+            parent.elements.push((frame.name, element));
+            return Ok(());
+        }
+        match frame.name.as_str() {
+            "uLoopPars" => parent.params = Some(Box::new(frame)),
+            "pItemValid" => {
+                let valid = frame
+                    .sorted_elements()
+                    .into_iter()
+                    .map(|element| match element {
+                        XyElement::Value(flag) => flag.unwrap_or(true),
+                        XyElement::Object(_) => true,
+                    })
+                    .collect();
+                parent.valid = Some(valid);
+            }
+            "Points" | "pPeriod" => {
+                let points = frame
+                    .sorted_elements()
+                    .into_iter()
+                    .map(|element| match element {
+                        XyElement::Object(point) => Some(point),
+                        XyElement::Value(_) => None,
+                    })
+                    .collect();
+                parent.lists.push((frame.name, points));
+            }
+            name if WRAPPERS.contains(&name) && !frame.loop_level => {
+                parent.inner = Some(Box::new(frame));
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn value(&mut self, name: Utf16Str<'_>, value: ClxValueRef<'_>) -> Result<()> {
+        let frame = self.top()?;
+        frame.entries += 1;
+        let number = match value {
+            ClxValueRef::Bool(b) => Some(f64::from(u8::from(b))),
+            ClxValueRef::Int(i) => Some(i as f64),
+            ClxValueRef::UInt(u) => Some(u as f64),
+            ClxValueRef::Float(f) => Some(f),
+            _ => None,
+        };
+        if frame.is_list() {
+            let flag = match value {
+                ClxValueRef::ByteArray(_) | ClxValueRef::String(_) => None,
+                _ => number.map(|v| v != 0.0),
+            };
+            frame
+                .elements
+                .push((name.to_string(), XyElement::Value(flag)));
+            return Ok(());
+        }
+        if let Some(i) = XY_NUMBERS.iter().position(|key| name.eq_str(key)) {
+            frame.numbers[i] = number;
+        } else if let Some(i) = XY_NAMES.iter().position(|key| name.eq_str(key)) {
+            frame.names[i] = value.as_str().map(|s| s.to_string());
+        } else if name.eq_str("pItemValid") {
+            if let ClxValueRef::ByteArray(bytes) = value {
+                frame.valid = Some(bytes.iter().map(|b| *b != 0).collect());
+            }
+        }
+        Ok(())
     }
 }
@@ -1,5 +1,17 @@
 pub mod attributes;
+pub mod binary;
+pub mod event;
 pub mod experiment;
+pub mod picture;
+pub mod recorded;
+pub mod roi;
+pub mod text_info;
 
 pub use attributes::*;
+pub use binary::*;
+pub use event::*;
 pub use experiment::*;
+pub use picture::*;
+pub use recorded::*;
+pub use roi::*;
+pub use text_info::*;
@@ -0,0 +1,231 @@
+//! Picture metadata (`ImageMetadataSeqLV|0!`): channels, objective and
+//! calibration.
+
+use super::experiment::{value_as_bool, value_as_f64, value_as_u32};
+use crate::parse::{ClxObject, ClxValue};
+use crate::types::{
+    Affine2, Attributes, AxisInterpretation, Channel, ChannelMeta, Color, Contents, ExpLoop,
+    LoopIndices, Metadata, Microscope, Volume,
+};
+
+const PICTURE_LEVEL: &str = "SLxPictureMetadata";
+
+/// Build [`Metadata`] from the picture metadata `clx` (`None` when the file
+/// has none), with frame layout from `attributes` and the Z step and loop
+/// order from `experiment`.
+pub fn parse_metadata(
+    clx: Option<&ClxValue>,
+    attributes: &Attributes,
+    experiment: &[ExpLoop],
+) -> Metadata {
+    let planes = clx
+        .and_then(|clx| clx.as_object())
+        .map(
+            |root| match root.get(PICTURE_LEVEL).and_then(|v| v.as_object()) {
+                Some(picture) => picture,
+                None => root,
+            },
+        )
+        .and_then(|picture| Some((picture, plane_list(picture)?)));
+    let channel_count = match &planes {
+        Some((_, planes)) if !planes.is_empty() => planes.len() as u32,
+        _ => attributes.channel_count.unwrap_or(1),
+    };
+    let contents = Some(Contents {
+        channel_count,
+        frame_count: attributes.sequence_count,
+    });
+    let Some((picture, planes)) = planes else {
+        return Metadata {
+            contents,
+            channels: None,
+        };
+    };
+
+    let loops = (!experiment.is_empty()).then(|| loop_indices(experiment));
+    let volume = volume(picture, attributes, experiment);
+    let samples = picture.get("sSampleSetting").and_then(|v| v.as_object());
+    let channels = planes
+        .into_iter()
+        .enumerate()
+        .map(|(index, plane)| {
+            let sample = value_u32(plane, "uiSampleIndex")
+                .and_then(|i| samples?.get(format!("a{}", i).as_str()))
+                .or_else(|| samples?.get(format!("a{}", index).as_str()))
+                .and_then(|v| v.as_object());
+            Channel {
+                channel: ChannelMeta {
+                    name: plane
+                        .get("sDescription")
+                        .and_then(|v| v.as_str())
+                        .unwrap_or_default()
+                        .to_string(),
+                    index: index as u32,
+                    color: Color::from_abgr_u32(value_u32(plane, "uiColor").unwrap_or(0xFFFF_FFFF)),
+                    emission_lambda_nm: spectrum_peak(plane.get("pEmissionSpectrum")),
+                    excitation_lambda_nm: spectrum_peak(plane.get("pExcitationSpectrum")),
+                },
+                loops: loops.clone(),
+                microscope: microscope(picture, sample),
+                volume: volume.clone(),
+            }
+        })
+        .collect();
+    Metadata {
+        contents,
+        channels: Some(channels),
+    }
+}
+
+/// The planes of `sPicturePlanes` (`sPlaneNew`, or `sPlane` in older
+/// files) in channel order.
+fn plane_list(picture: &ClxObject) -> Option<Vec<&ClxObject>> {
+    let level = picture.get("sPicturePlanes")?.as_object()?;
+    let planes = level
+        .get("sPlaneNew")
+        .or_else(|| level.get("sPlane"))?
+        .as_object()?;
+    let mut planes: Vec<(usize, &ClxObject)> = planes
+        .iter()
+        .filter_map(|(key, value)| {
+            let index = key.strip_prefix('a')?.parse().ok()?;
+            Some((index, value.as_object()?))
+        })
+        .collect();
+    planes.sort_by_key(|(index, _)| *index);
+    Some(planes.into_iter().map(|(_, plane)| plane).collect())
+}
+
+fn value_u32(object: &ClxObject, key: &str) -> Option<u32> {
+    object.get(key).and_then(value_as_u32)
+}
+
+/// A positive, finite number stored under `key`.
+fn positive(object: &ClxObject, key: &str) -> Option<f64> {
+    object
+        .get(key)
+        .and_then(value_as_f64)
+        .filter(|v| v.is_finite() && *v > 0.0)
+}
+
+/// Wavelength of the highest point of a spectrum (`pPoint` items with
+/// `dWavelength` and `dTValue`).
+fn spectrum_peak(spectrum: Option<&ClxValue>) -> Option<f64> {
+    let points = spectrum?.as_object()?.get("pPoint")?.as_object()?;
+    points
+        .values()
+        .filter_map(|point| {
+            let point = point.as_object()?;
+            Some((
+                point.get("dTValue").and_then(value_as_f64).unwrap_or(0.0),
+                positive(point, "dWavelength")?,
+            ))
+        })
+        .max_by(|a, b| a.0.total_cmp(&b.0))
+        .map(|(_, wavelength)| wavelength)
+}
+
+/// Objective and optics of a channel: its sample setting's
+/// `pObjectiveSetting` first, then the picture's `sObjective`, then the
+/// picture metadata itself.
+fn microscope(picture: &ClxObject, sample: Option<&ClxObject>) -> Microscope {
+    let sources: Vec<&ClxObject> = [
+        sample
+            .and_then(|sample| sample.get("pObjectiveSetting"))
+            .and_then(|v| v.as_object()),
+        sample,
+        picture.get("sObjective").and_then(|v| v.as_object()),
+        Some(picture),
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+    let number = |keys: &[&str]| {
+        sources
+            .iter()
+            .find_map(|source| keys.iter().find_map(|key| positive(source, key)))
+    };
+    Microscope {
+        objective_magnification: number(&["dObjectiveMag"]),
+        objective_name: sources.iter().find_map(|source| {
+            source
+                .get("wsObjectiveName")
+                .and_then(|v| v.as_str())
+                .filter(|name| !name.is_empty())
+                .map(str::to_string)
+        }),
+        objective_numerical_aperture: number(&["dObjectiveNA"]),
+        zoom_magnification: number(&["dZoom"]),
+        immersion_refractive_index: number(&["dRefractIndex1", "dRefractIndex"]),
+        projective_magnification: number(&["dProjectiveMag"]),
+        pinhole_diameter_um: number(&["dPinholeDiameter"]),
+    }
+}
+
+/// Voxel geometry shared by all channels: XY calibration from
+/// `dCalibration`, Z from the Z-stack step.
+fn volume(picture: &ClxObject, attributes: &Attributes, experiment: &[ExpLoop]) -> Volume {
+    let xy = positive(picture, "dCalibration");
+    let calibrated = picture
+        .get("bCalibrated")
+        .and_then(value_as_bool)
+        .unwrap_or(xy.is_some());
+    let (z_step, n_z) = experiment
+        .iter()
+        .find_map(|loop_| match loop_ {
+            ExpLoop::ZStackLoop(z) => Some((z.parameters.step_um.abs(), z.count)),
+            _ => None,
+        })
+        .unwrap_or((0.0, 1));
+    let z = (z_step.is_finite() && z_step > 0.0).then_some(z_step);
+    let width = attributes.width_px.unwrap_or_else(|| {
+        let bytes = (attributes.bits_per_component_in_memory / 8).max(1);
+        attributes.width_bytes.unwrap_or(0) / (bytes * attributes.component_count.max(1))
+    });
+    let matrix = match ["dStgLgCT11", "dStgLgCT12", "dStgLgCT21", "dStgLgCT22"]
+        .map(|key| picture.get(key).and_then(value_as_f64))
+    {
+        [Some(a), Some(b), Some(c), Some(d)] => Affine2::linear([[a, b], [c, d]]),
+        _ => Affine2::IDENTITY,
+    };
+    Volume {
+        axes_calibrated: (calibrated, calibrated, z.is_some()),
+        axes_calibration: (xy.unwrap_or(1.0), xy.unwrap_or(1.0), z.unwrap_or(1.0)),
+        axes_interpretation: (
+            AxisInterpretation::Distance,
+            AxisInterpretation::Distance,
+            AxisInterpretation::Distance,
+        ),
+        bits_per_component_in_memory: attributes.bits_per_component_in_memory,
+        bits_per_component_significant: attributes.bits_per_component_significant,
+        camera_transformation_matrix: matrix,
+        component_count: attributes.component_count,
+        component_data_type: attributes.pixel_data_type,
+        voxel_count: (width, attributes.height_px, n_z),
+        component_maxima: None,
+        component_minima: None,
+        pixel_to_stage_transformation_matrix: None,
+    }
+}
+
+/// Nesting level of each loop type, outermost 0.
+fn loop_indices(experiment: &[ExpLoop]) -> LoopIndices {
+    let mut indices = LoopIndices {
+        ne_time_loop: None,
+        time_loop: None,
+        xy_pos_loop: None,
+        z_stack_loop: None,
+        custom_loop: None,
+    };
+    for (level, loop_) in experiment.iter().enumerate() {
+        let slot = match loop_ {
+            ExpLoop::NETimeLoop(_) => &mut indices.ne_time_loop,
+            ExpLoop::TimeLoop(_) => &mut indices.time_loop,
+            ExpLoop::XYPosLoop(_) => &mut indices.xy_pos_loop,
+            ExpLoop::ZStackLoop(_) => &mut indices.z_stack_loop,
+            ExpLoop::CustomLoop(_) => &mut indices.custom_loop,
+        };
+        slot.get_or_insert(level as u32);
+    }
+    indices
+}
@@ -0,0 +1,99 @@
+//! Recorded data: per-frame measurements described in
+//! `CustomDataVar|CustomDataV2_0!` and stored in `CustomData|{id}!` arrays.
+
+use super::roi::{string, u32_or};
+use crate::parse::ClxValue;
+use crate::types::RecordedValue;
+
+const TAGS_LEVEL: &str = "CustomTagDescription_v1.0";
+
+/// A recorded column: its values are in chunk `CustomData|{id}!`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CustomTag {
+    pub id: String,
+    /// Stored type: 1 is UTF-16 text, 2 a 32-bit integer, 3 a 64-bit float.
+    pub kind: u32,
+    /// Characters per value, for text.
+    pub size: u32,
+    pub description: String,
+    pub unit: String,
+}
+
+impl CustomTag {
+    /// Column name as NIS Elements shows it, `Desc [Unit]`.
+    pub fn column_name(&self) -> String {
+        let name = if self.description.is_empty() {
+            &self.id
+        } else {
+            &self.description
+        };
+        if self.unit.is_empty() {
+            name.clone()
+        } else {
+            format!("{} [{}]", name, self.unit)
+        }
+    }
+
+    /// The first `count` values of `data`, `None` when the type is unknown
+    /// or `data` holds fewer.
+    pub fn decode(&self, data: &[u8], count: usize) -> Option<Vec<RecordedValue>> {
+        let width = match self.kind {
+            1 => (self.size as usize).checked_mul(2)?,
+            2 => 4,
+            3 => 8,
+            _ => return None,
+        };
+        if width == 0 || data.len() / width < count {
+            return None;
+        }
+        let values = data.chunks_exact(width).take(count);
+        Some(match self.kind {
+            1 => values
+                .map(|bytes| {
+                    let units: Vec<u16> = bytes
+                        .chunks_exact(2)
+                        .map(|unit| u16::from_le_bytes([unit[0], unit[1]]))
+                        .take_while(|&unit| unit != 0)
+                        .collect();
+                    RecordedValue::Text(String::from_utf16_lossy(&units))
+                })
+                .collect(),
+            2 => values
+                .map(|bytes| {
+                    let bytes = <[u8; 4]>::try_from(bytes).ok()?;
+                    Some(RecordedValue::Int(i32::from_le_bytes(bytes) as i64))
+                })
+                .collect::<Option<_>>()?,
+            _ => values
+                .map(|bytes| {
+                    let bytes = <[u8; 8]>::try_from(bytes).ok()?;
+                    Some(RecordedValue::Float(f64::from_le_bytes(bytes)))
+                })
+                .collect::<Option<_>>()?,
+        })
+    }
+}
+
+/// The tags described in `clx`, in stored order. Entries without an id
+/// are skipped.
+pub fn parse_custom_tags(clx: &ClxValue) -> Vec<CustomTag> {
+    let Some(root) = clx.as_object() else {
+        return Vec::new();
+    };
+    let Some(tags) = root.get(TAGS_LEVEL).and_then(|v| v.as_object()) else {
+        return Vec::new();
+    };
+    tags.values()
+        .filter_map(|value| value.as_object())
+        .filter_map(|tag| {
+            let id = string(tag, "ID");
+            (!id.is_empty()).then(|| CustomTag {
+                id,
+                kind: u32_or(tag, "Type", 0),
+                size: u32_or(tag, "Size", 0),
+                description: string(tag, "Desc"),
+                unit: string(tag, "Unit"),
+            })
+        })
+        .collect()
+}
@@ -0,0 +1,155 @@
+//! Regions of interest (`CustomData|RoiMetadata_v1!`).
+
+use super::experiment::{value_as_bool, value_as_f64, value_as_u32};
+use crate::parse::{ClxObject, ClxValue};
+use crate::types::{AnimParam, BoxShape, ExtrudedShape, Roi, RoiInfo, RoiInterpType, RoiShapeType};
+
+const ROI_LEVEL: &str = "RoiMetadata_v1";
+
+/// The ROIs in `clx`, by id. Entries without an id are skipped.
+pub fn parse_rois(clx: &ClxValue) -> Vec<Roi> {
+    let Some(root) = clx.as_object() else {
+        return Vec::new();
+    };
+    let level = root
+        .get(ROI_LEVEL)
+        .and_then(|v| v.as_object())
+        .unwrap_or(root);
+    let mut rois: Vec<Roi> = level
+        .values()
+        .filter_map(|value| parse_roi(value.as_object()?))
+        .collect();
+    rois.sort_by_key(|roi| roi.id);
+    rois
+}
+
+fn parse_roi(roi: &ClxObject) -> Option<Roi> {
+    let info = field(roi, "Info").and_then(|v| v.as_object());
+    Some(Roi {
+        id: field(roi, "Id").and_then(value_as_u32)?,
+        info: parse_info(info.unwrap_or(&ClxObject::new())),
+        guid: string(roi, "GUID"),
+        anim_params: indexed(roi, "AnimParams")
+            .into_iter()
+            .map(parse_anim_param)
+            .collect(),
+    })
+}
+
+/// Entry `name` of `object`, also when written with an `m_` prefix, a
+/// type prefix (`m_dTimeMs`) or another first-letter case.
+pub(super) fn field<'a>(object: &'a ClxObject, name: &str) -> Option<&'a ClxValue> {
+    object.get(name).or_else(|| {
+        object.iter().find_map(|(key, value)| {
+            let key = key.strip_prefix("m_").unwrap_or(key);
+            let typed = key
+                .strip_suffix(name)
+                .is_some_and(|prefix| prefix.bytes().all(|b| b.is_ascii_lowercase()));
+            (typed || key.eq_ignore_ascii_case(name)).then_some(value)
+        })
+    })
+}
+
+pub(super) fn u32_or(object: &ClxObject, name: &str, default: u32) -> u32 {
+    field(object, name)
+        .and_then(value_as_u32)
+        .unwrap_or(default)
+}
+
+pub(super) fn f64_or(object: &ClxObject, name: &str, default: f64) -> f64 {
+    field(object, name)
+        .and_then(value_as_f64)
+        .unwrap_or(default)
+}
+
+fn bool_or(object: &ClxObject, name: &str, default: bool) -> bool {
+    field(object, name)
+        .and_then(value_as_bool)
+        .unwrap_or(default)
+}
+
+pub(super) fn string(object: &ClxObject, name: &str) -> String {
+    field(object, name)
+        .and_then(|v| v.as_str())
+        .unwrap_or_default()
+        .to_string()
+}
+
+/// The items `{name}_0`, `{name}_1`, ... of `object`, `{name}_Size` of
+/// them, or as many as there are when the size is missing.
+fn indexed<'a>(object: &'a ClxObject, name: &str) -> Vec<&'a ClxValue> {
+    let item = |i: usize| field(object, &format!("{}_{}", name, i));
+    match field(object, &format!("{}_Size", name)).and_then(value_as_u32) {
+        Some(size) => (0..size as usize).filter_map(item).collect(),
+        None => (0..).map_while(item).collect(),
+    }
+}
+
+fn parse_info(info: &ClxObject) -> RoiInfo {
+    RoiInfo {
+        shape_type: RoiShapeType::from_u32(u32_or(info, "ShapeType", 2)),
+        interp_type: RoiInterpType::from_u32(u32_or(info, "InterpType", 1)),
+        cookie: u32_or(info, "Cookie", 0),
+        color: u32_or(info, "Color", 255),
+        label: string(info, "Label"),
+        stimulation_group: u32_or(info, "StimulationGroup", 0),
+        scope: u32_or(info, "Scope", 1),
+        app_data: u32_or(info, "AppData", 0),
+        multi_frame: bool_or(info, "MultiFrame", false),
+        locked: bool_or(info, "Locked", false),
+        comp_count: u32_or(info, "CompCount", 2),
+        bpc: u32_or(info, "Bpc", 16),
+        autodetected: bool_or(info, "Autodetected", false),
+        gradient_stimulation: bool_or(info, "GradientStimulation", false),
+        gradient_stimulation_bit_depth: u32_or(info, "GradientStimulationBitDepth", 0),
+        gradient_stimulation_lo: f64_or(info, "GradientStimulationLo", 0.0),
+        gradient_stimulation_hi: f64_or(info, "GradientStimulationHi", 0.0),
+    }
+}
+
+fn parse_anim_param(value: &ClxValue) -> AnimParam {
+    let empty = ClxObject::new();
+    let param = value.as_object().unwrap_or(&empty);
+    let level = |name: &str| field(param, name).and_then(|v| v.as_object());
+    let box_shape = level("BoxShape").map_or_else(BoxShape::default, |shape| BoxShape {
+        size_x: f64_or(shape, "SizeX", 0.0),
+        size_y: f64_or(shape, "SizeY", 0.0),
+        size_z: f64_or(shape, "SizeZ", 0.0),
+    });
+    let extruded_shape =
+        level("ExtrudedShape").map_or_else(ExtrudedShape::default, |shape| ExtrudedShape {
+            size_z: f64_or(shape, "SizeZ", 0.0),
+            base_points: indexed(shape, "BasePoints")
+                .into_iter()
+                .filter_map(point)
+                .collect(),
+        });
+    AnimParam {
+        time_ms: f64_or(param, "TimeMs", 0.0),
+        enabled: bool_or(param, "Enabled", true),
+        center_x: f64_or(param, "CenterX", 0.0),
+        center_y: f64_or(param, "CenterY", 0.0),
+        center_z: f64_or(param, "CenterZ", 0.0),
+        rotation_z: f64_or(param, "RotationZ", 0.0),
+        box_shape,
+        extruded_shape,
+    }
+}
+
+/// An (x, y) point: two unnamed numbers, or `x` and `y` entries.
+fn point(value: &ClxValue) -> Option<(f64, f64)> {
+    match value {
+        ClxValue::Array(items) => match items.as_slice() {
+            [x, y, ..] => Some((value_as_f64(x)?, value_as_f64(y)?)),
+            _ => None,
+        },
+        ClxValue::Object(object) => match object.get("") {
+            Some(items) => point(items),
+            None => Some((
+                field(object, "x").and_then(value_as_f64)?,
+                field(object, "y").and_then(value_as_f64)?,
+            )),
+        },
+        _ => None,
+    }
+}
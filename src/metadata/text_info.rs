@@ -1,32 +1,176 @@
+use std::collections::BTreeSet;
+
 use crate::error::Result;
-use crate::parse::ClxValue;
+use crate::parse::{ClxEdit, ClxValue, ClxValueRef};
 use crate::types::TextInfo;
 
+/// Level holding the text info entries.
+pub(crate) const TEXT_INFO_LEVEL: &str = "SLxImageTextInfo";
+
+/// Names the entries `TextInfoItem_0`, `TextInfoItem_1`, ... go by in some
+/// writers, in [`TextInfo`] field order.
+const ITEM_NAMES: [&str; 15] = [
+    "ImageId",
+    "Type",
+    "Group",
+    "SampleId",
+    "Author",
+    "Description",
+    "Capturing",
+    "Sampling",
+    "Location",
+    "Date",
+    "Conclusion",
+    "Info1",
+    "Info2",
+    "Optics",
+    "AppVersion",
+];
+
+/// The fields of `info`, in `TextInfoItem_N` order.
+fn fields(info: &TextInfo) -> [&Option<String>; 15] {
+    [
+        &info.image_id,
+        &info.info_type,
+        &info.group,
+        &info.sample_id,
+        &info.author,
+        &info.description,
+        &info.capturing,
+        &info.sampling,
+        &info.location,
+        &info.date,
+        &info.conclusion,
+        &info.info1,
+        &info.info2,
+        &info.optics,
+        &info.app_version,
+    ]
+}
+
+fn fields_mut(info: &mut TextInfo) -> [&mut Option<String>; 15] {
+    [
+        &mut info.image_id,
+        &mut info.info_type,
+        &mut info.group,
+        &mut info.sample_id,
+        &mut info.author,
+        &mut info.description,
+        &mut info.capturing,
+        &mut info.sampling,
+        &mut info.location,
+        &mut info.date,
+        &mut info.conclusion,
+        &mut info.info1,
+        &mut info.info2,
+        &mut info.optics,
+        &mut info.app_version,
+    ]
+}
+
+/// Position of the text info entry `name` in [`TextInfo`] field order.
+fn item_index(name: &str) -> Option<usize> {
+    match name.strip_prefix("TextInfoItem_") {
+        Some(index) => index.parse().ok().filter(|&i| i < ITEM_NAMES.len()),
+        None => ITEM_NAMES.iter().position(|&item| item == name),
+    }
+}
+
 pub fn parse_text_info(clx: ClxValue) -> Result<TextInfo> {
-    let obj = match clx.as_object() {
+    let root = match clx.as_object() {
         Some(o) => o,
         None => return Ok(TextInfo::default()),
     };
+    let obj = root
+        .get(TEXT_INFO_LEVEL)
+        .and_then(|v| v.as_object())
+        .unwrap_or(root);
 
-    let get_str = |key: &str| -> Option<String> {
-        obj.get(key).and_then(|v| v.as_str()).map(|s| s.to_string())
-    };
+    let mut info = TextInfo::default();
+    for (name, value) in obj {
+        let (Some(index), Some(value)) = (item_index(name), value.as_str()) else {
+            continue;
+        };
+        // Unset fields are stored as empty strings.
+        if !value.is_empty() {
+            *fields_mut(&mut info)[index] = Some(value.to_string());
+        }
+    }
+    Ok(info)
+}
+
+/// Sets the fields of a [`TextInfo`] that are `Some` when rewriting a text
+/// info chunk, adding entries for those it doesn't hold.
+pub(crate) struct TextInfoEdit<'a> {
+    changes: &'a TextInfo,
+    /// Fields written so far, by position.
+    written: BTreeSet<usize>,
+}
+
+impl<'a> TextInfoEdit<'a> {
+    pub(crate) fn new(changes: &'a TextInfo) -> Self {
+        Self {
+            changes,
+            written: BTreeSet::new(),
+        }
+    }
+
+    /// Whether every change was written.
+    pub(crate) fn is_complete(&self) -> bool {
+        fields(self.changes)
+            .iter()
+            .enumerate()
+            .all(|(index, field)| field.is_none() || self.written.contains(&index))
+    }
+
+    /// Entries for the changes not written yet.
+    fn remaining(&mut self) -> Vec<(String, ClxValue)> {
+        let mut entries = Vec::new();
+        for (index, field) in fields(self.changes).into_iter().enumerate() {
+            if let Some(value) = field {
+                if self.written.insert(index) {
+                    entries.push((
+                        format!("TextInfoItem_{}", index),
+                        ClxValue::String(value.clone()),
+                    ));
+                }
+            }
+        }
+        entries
+    }
+
+    /// The text info level of a file without one, holding the changes.
+    pub(crate) fn level(&mut self) -> ClxValue {
+        let items = self
+            .remaining()
+            .into_iter()
+            .map(|(name, value)| (name.into(), value))
+            .collect();
+        ClxValue::Object(items)
+    }
+}
+
+impl ClxEdit for TextInfoEdit<'_> {
+    fn replace(&mut self, path: &[String], value: &ClxValueRef<'_>) -> Result<Option<ClxValue>> {
+        let index = match path {
+            [level, name] if level == TEXT_INFO_LEVEL => item_index(name),
+            [name] => item_index(name),
+            _ => None,
+        };
+        let Some(index) = index.filter(|_| value.as_str().is_some()) else {
+            return Ok(None);
+        };
+        let Some(new) = fields(self.changes)[index] else {
+            return Ok(None);
+        };
+        self.written.insert(index);
+        Ok(Some(ClxValue::String(new.clone())))
+    }
 
-    Ok(TextInfo {
-        image_id: get_str("ImageId"),
-        info_type: get_str("Type"),
-        group: get_str("Group"),
-        sample_id: get_str("SampleId"),
-        author: get_str("Author"),
-        description: get_str("Description"),
-        capturing: get_str("Capturing"),
-        sampling: get_str("Sampling"),
-        location: get_str("Location"),
-        date: get_str("Date"),
-        conclusion: get_str("Conclusion"),
-        info1: get_str("Info1"),
-        info2: get_str("Info2"),
-        optics: get_str("Optics"),
-        app_version: get_str("AppVersion"),
-    })
+    fn append(&mut self, path: &[String]) -> Vec<(String, ClxValue)> {
+        match path {
+            [level] if level == TEXT_INFO_LEVEL => self.remaining(),
+            _ => Vec::new(),
+        }
+    }
 }
@@ -0,0 +1,395 @@
+use std::ops::Range;
+
+use crate::types::TextInfo;
+
+/// Default capacity of the buffered reader wrapped around the ND2 source.
+pub const DEFAULT_BUFFER_CAPACITY: usize = 8 * 1024;
+
+/// Default size limit of one coalesced read of adjacent frame chunks.
+pub const DEFAULT_COALESCED_READ_BYTES: usize = 16 * 1024 * 1024;
+
+/// What other processes may do with a file while it is open for reading.
+///
+/// Only affects Windows, where files are opened with explicit share flags;
+/// elsewhere advisory sharing does not apply and both modes behave the same.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum ShareMode {
+    /// Allow others to keep reading, writing and renaming the file, so that
+    /// acquisitions still being written by NIS Elements can be opened.
+    #[default]
+    Shared,
+    /// Allow others to read only; opening fails if another process has
+    /// the file open for writing, and writers are locked out while open.
+    DenyWrite,
+}
+
+/// How bytes are fetched from the ND2 source.
+///
+/// Non-exhaustive, since [`ReadStrategy::IoUring`] only exists with the
+/// `io-uring` feature.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[non_exhaustive]
+pub enum ReadStrategy {
+    /// Positional reads for files opened by path or handle, direct reads for
+    /// in-memory sources, and a buffered reader for any other source.
+    #[default]
+    Auto,
+    /// Seek and read through a `BufReader` of
+    /// [`Nd2Options::buffer_capacity`] bytes.
+    Buffered,
+    /// No read buffer: every read goes to the source, positionally for files.
+    /// Frame chunks are read in one call anyway, so random frame access
+    /// avoids refilling (and discarding) a buffer on every seek.
+    Direct,
+    /// Like [`ReadStrategy::Direct`], but bulk frame reads of files opened by
+    /// path or handle submit all chunk reads of a batch to an io_uring at
+    /// once. Falls back to positional reads off Linux or when the kernel
+    /// refuses to set up a ring.
+    #[cfg(feature = "io-uring")]
+    IoUring,
+}
+
+/// Resource limits for parsing files that may be hostile, such as uploads
+/// handled by a service. Set with [`Nd2Options::limits`], or on a
+/// [`ClxLiteParser`](crate::sansio::ClxLiteParser) directly.
+///
+/// Limits tighten the guards every file is read with (CLX data nested at
+/// most 256 levels deep and walking at most 1 GiB); they never loosen them.
+/// Going past one fails with a
+/// [`FileError::LimitExceeded`](crate::FileError::LimitExceeded) error as
+/// soon as the size is known, before anything of that size is allocated.
+/// The default sets no limits of its own.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Limits {
+    pub(crate) max_chunk_bytes: u64,
+    pub(crate) max_decompressed_bytes: u64,
+    pub(crate) max_clx_nodes: u64,
+    pub(crate) max_depth: usize,
+}
+
+impl Limits {
+    /// Create limits that add nothing to the built-in guards.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Largest chunk read from the file: the chunkmap, a metadata chunk, a
+    /// compressed frame chunk or the pixel rows of an uncompressed frame.
+    pub fn max_chunk_bytes(mut self, bytes: u64) -> Self {
+        self.max_chunk_bytes = bytes;
+        self
+    }
+
+    /// Most bytes one compressed frame inflates to, and one CLX parse walks
+    /// in total, inflated sections and re-parsed byte arrays included.
+    pub fn max_decompressed_bytes(mut self, bytes: u64) -> Self {
+        self.max_decompressed_bytes = bytes;
+        self
+    }
+
+    /// Most CLX entries (values, levels and nested byte arrays) one parse
+    /// reads.
+    pub fn max_clx_nodes(mut self, nodes: u64) -> Self {
+        self.max_clx_nodes = nodes;
+        self
+    }
+
+    /// Deepest nesting of CLX levels and nested byte arrays one parse
+    /// follows.
+    pub fn max_depth(mut self, depth: usize) -> Self {
+        self.max_depth = depth;
+        self
+    }
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_chunk_bytes: u64::MAX,
+            max_decompressed_bytes: u64::MAX,
+            max_clx_nodes: u64::MAX,
+            max_depth: usize::MAX,
+        }
+    }
+}
+
+/// What [`anonymize_to`](crate::anonymize_to) removes from the copy of a
+/// file.
+///
+/// By default the author, sample ID, image ID, location and date of the text
+/// info are blanked, metadata strings holding a file path are blanked, and
+/// GUIDs are replaced by placeholders: one per distinct GUID, so entries
+/// referring to each other still do. Timestamps are kept.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnonymizePolicy {
+    pub(crate) text_info: TextInfo,
+    pub(crate) blank_paths: bool,
+    pub(crate) replace_guids: bool,
+    pub(crate) time_shift_days: f64,
+}
+
+impl AnonymizePolicy {
+    /// Create the default policy.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Text info fields to set instead of the default ones, as by
+    /// [`edit_text_info`](crate::edit_text_info): fields left `None` are
+    /// kept, `Some("")` blanks one.
+    pub fn text_info(mut self, changes: TextInfo) -> Self {
+        self.text_info = changes;
+        self
+    }
+
+    /// Blank metadata strings that are file paths (drive letter, UNC or
+    /// absolute Unix paths).
+    pub fn blank_paths(mut self, enabled: bool) -> Self {
+        self.blank_paths = enabled;
+        self
+    }
+
+    /// Replace GUIDs in metadata strings by placeholders.
+    pub fn replace_guids(mut self, enabled: bool) -> Self {
+        self.replace_guids = enabled;
+        self
+    }
+
+    /// Move the absolute acquisition times (`dTimeAbsolute` entries, in
+    /// days) by `days`. Frame times relative to the acquisition start are
+    /// kept as they are.
+    pub fn time_shift_days(mut self, days: f64) -> Self {
+        self.time_shift_days = days;
+        self
+    }
+}
+
+impl Default for AnonymizePolicy {
+    fn default() -> Self {
+        let blank = || Some(String::new());
+        Self {
+            text_info: TextInfo {
+                image_id: blank(),
+                sample_id: blank(),
+                author: blank(),
+                location: blank(),
+                date: blank(),
+                ..TextInfo::default()
+            },
+            blank_paths: true,
+            replace_guids: true,
+            time_shift_days: 0.0,
+        }
+    }
+}
+
+/// Frames [`subset_to`](crate::subset_to) copies into a new file.
+///
+/// Each axis is kept whole unless narrowed here. Positions and channels are
+/// given by index and keep their order in the file; time points are a range
+/// of indices.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SubsetSelection {
+    pub(crate) positions: Option<Vec<usize>>,
+    pub(crate) channels: Option<Vec<usize>>,
+    pub(crate) time: Option<Range<usize>>,
+}
+
+impl SubsetSelection {
+    /// Select everything.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Keep only the XY positions with these indices.
+    pub fn positions(mut self, positions: impl IntoIterator<Item = usize>) -> Self {
+        self.positions = Some(sorted(positions));
+        self
+    }
+
+    /// Keep only the channels with these indices.
+    pub fn channels(mut self, channels: impl IntoIterator<Item = usize>) -> Self {
+        self.channels = Some(sorted(channels));
+        self
+    }
+
+    /// Keep only the time points in `range`.
+    pub fn time(mut self, range: Range<usize>) -> Self {
+        self.time = Some(range);
+        self
+    }
+}
+
+fn sorted(indices: impl IntoIterator<Item = usize>) -> Vec<usize> {
+    let mut indices: Vec<usize> = indices.into_iter().collect();
+    indices.sort_unstable();
+    indices.dedup();
+    indices
+}
+
+/// Options controlling how an ND2 file is opened.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Nd2Options {
+    pub(crate) buffer_capacity: usize,
+    pub(crate) cache_metadata: bool,
+    pub(crate) max_cached_metadata_bytes: Option<u64>,
+    pub(crate) share_mode: ShareMode,
+    pub(crate) decode_threads: Option<usize>,
+    pub(crate) read_strategy: ReadStrategy,
+    pub(crate) coalesced_read_bytes: usize,
+    pub(crate) allow_recovery: bool,
+    pub(crate) allow_unknown_version: bool,
+    pub(crate) skip_bad_frames: bool,
+    pub(crate) limits: Limits,
+    #[cfg(feature = "frame-cache")]
+    pub(crate) frame_cache_dir: Option<std::path::PathBuf>,
+}
+
+impl Nd2Options {
+    /// Create options with the default settings.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Capacity in bytes of the internal read buffer, when reads are
+    /// buffered (see [`ReadStrategy`]).
+    pub fn buffer_capacity(mut self, capacity: usize) -> Self {
+        self.buffer_capacity = capacity;
+        self
+    }
+
+    /// Keep parsed metadata (attributes, experiment loops) in memory between
+    /// calls. Defaults to `true`; when disabled, metadata is re-read from the
+    /// file on every access.
+    pub fn cache_metadata(mut self, enabled: bool) -> Self {
+        self.cache_metadata = enabled;
+        self
+    }
+
+    /// Only cache metadata parsed from chunks of at most `bytes` bytes.
+    /// Larger CLX trees are re-read on each access instead of being held.
+    pub fn max_cached_metadata_bytes(mut self, bytes: u64) -> Self {
+        self.max_cached_metadata_bytes = Some(bytes);
+        self
+    }
+
+    /// Share mode used when opening by path. Defaults to [`ShareMode::Shared`].
+    pub fn share_mode(mut self, mode: ShareMode) -> Self {
+        self.share_mode = mode;
+        self
+    }
+
+    /// How reads reach the source. Defaults to [`ReadStrategy::Auto`].
+    pub fn read_strategy(mut self, strategy: ReadStrategy) -> Self {
+        self.read_strategy = strategy;
+        self
+    }
+
+    /// Largest read used to fetch a run of frame chunks that follow each
+    /// other on disk, in bulk reads and exports of frames in file order.
+    /// Frames are sliced out of the combined buffer. `0` reads every chunk
+    /// on its own. Defaults to 16 MiB. `ReadStrategy::IoUring` batches its
+    /// reads instead.
+    pub fn coalesce_reads(mut self, max_bytes: usize) -> Self {
+        self.coalesced_read_bytes = max_bytes;
+        self
+    }
+
+    /// Keep inflated frames of compressed files in `dir`, zstd-compressed,
+    /// so later reads of the same frames (in this or another process) skip
+    /// zlib decoding. Entries are keyed by the file's identity (canonical
+    /// path, device and inode, size and modification time) together with a
+    /// fingerprint of its chunkmap, and by the frame's sequence index; a
+    /// rewritten file gets new entries. Only files opened with
+    /// [`Nd2File::open_with`](crate::Nd2File::open_with) or
+    /// [`Nd2File::open_file_with`](crate::Nd2File::open_file_with) use the
+    /// cache (the latter only where the handle has an inode, i.e. on Unix):
+    /// readers and in-memory buffers cannot be told apart from another file
+    /// with the same chunk layout, and are read without it. Failing to write an entry is reported in
+    /// [`Nd2File::diagnostics`](crate::Nd2File::diagnostics) and does not
+    /// fail the read. Off by default.
+    #[cfg(feature = "frame-cache")]
+    pub fn frame_cache<P: Into<std::path::PathBuf>>(mut self, dir: P) -> Self {
+        self.frame_cache_dir = Some(dir.into());
+        self
+    }
+
+    /// When the chunkmap at the end of the file is missing or corrupt (as
+    /// after a crashed acquisition), rebuild it by scanning the whole file
+    /// for chunk headers instead of failing to open. The scan reads the
+    /// file once, on [`Nd2Options::decode_threads`] threads for files
+    /// opened by path or handle, and is reported in
+    /// [`Nd2File::diagnostics`](crate::Nd2File::diagnostics). Off by
+    /// default.
+    pub fn allow_recovery(mut self, enabled: bool) -> Self {
+        self.allow_recovery = enabled;
+        self
+    }
+
+    /// Open files of a format version newer than 3.x as if they were 3.0
+    /// instead of failing with an unsupported version error. NIS format
+    /// bumps have so far been additive, so this usually works, but nothing
+    /// is guaranteed: opening such a file records an
+    /// [`UnknownVersion`](crate::DiagnosticKind::UnknownVersion) diagnostic,
+    /// and [`Nd2File::version`](crate::Nd2File::version) still reports the
+    /// version in the file. Off by default.
+    pub fn allow_unknown_version(mut self, enabled: bool) -> Self {
+        self.allow_unknown_version = enabled;
+        self
+    }
+
+    /// Let exports and [`Nd2File::read_stack`](crate::Nd2File::read_stack)
+    /// go on past frames that cannot be read or decoded, instead of failing
+    /// hours into a conversion. Planes of such frames are written as zeros,
+    /// or left out where the output has no slot for them (Rerun recordings)
+    /// and of auto tone-range histograms. Each frame skipped is listed by
+    /// [`Nd2File::bad_frames`](crate::Nd2File::bad_frames) and reported in
+    /// [`Nd2File::diagnostics`](crate::Nd2File::diagnostics) with its error.
+    /// Single-frame reads still fail. Off by default.
+    pub fn skip_bad_frames(mut self, enabled: bool) -> Self {
+        self.skip_bad_frames = enabled;
+        self
+    }
+
+    /// Resource limits enforced while reading the file's chunkmap,
+    /// metadata and frames. Defaults to [`Limits::default`], which adds none
+    /// to the built-in guards.
+    pub fn limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Worker threads used to decompress frames in bulk reads and exports.
+    /// Defaults to the available parallelism; `1` decodes on the calling
+    /// thread.
+    pub fn decode_threads(mut self, threads: usize) -> Self {
+        self.decode_threads = Some(threads.max(1));
+        self
+    }
+
+    pub(crate) fn decode_worker_count(&self) -> usize {
+        self.decode_threads.unwrap_or_else(|| {
+            std::thread::available_parallelism().map_or(1, std::num::NonZeroUsize::get)
+        })
+    }
+}
+
+impl Default for Nd2Options {
+    fn default() -> Self {
+        Self {
+            buffer_capacity: DEFAULT_BUFFER_CAPACITY,
+            cache_metadata: true,
+            max_cached_metadata_bytes: None,
+            share_mode: ShareMode::Shared,
+            decode_threads: None,
+            read_strategy: ReadStrategy::Auto,
+            coalesced_read_bytes: DEFAULT_COALESCED_READ_BYTES,
+            allow_recovery: false,
+            allow_unknown_version: false,
+            skip_bad_frames: false,
+            limits: Limits::default(),
+            #[cfg(feature = "frame-cache")]
+            frame_cache_dir: None,
+        }
+    }
+}
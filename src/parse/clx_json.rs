@@ -0,0 +1,98 @@
+//! JSON views of parsed CLX: a `Serialize` impl and, with the `json`
+//! feature, a conversion to `serde_json::Value`.
+//!
+//! Byte arrays become standard base64 strings (with padding). Non-finite
+//! floats, which JSON has no number for, become `null` in
+//! `ClxValue::to_json`; `Serialize` leaves them to the serializer.
+
+use serde::ser::{SerializeMap, SerializeSeq};
+use serde::{Serialize, Serializer};
+#[cfg(feature = "json")]
+use serde_json::{Map, Number, Value};
+
+use super::ClxValue;
+
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+#[cfg(feature = "json")]
+impl ClxValue {
+    /// The value as JSON, objects keeping their name order.
+    pub fn to_json(&self) -> Value {
+        match self {
+            ClxValue::Bool(b) => Value::Bool(*b),
+            ClxValue::Int(i) => Value::Number(Number::from(*i)),
+            ClxValue::UInt(u) => Value::Number(Number::from(*u)),
+            ClxValue::Float(f) => Number::from_f64(*f).map_or(Value::Null, Value::Number),
+            ClxValue::String(s) => Value::String(s.clone()),
+            ClxValue::ByteArray(bytes) => Value::String(base64(bytes)),
+            ClxValue::Object(map) => Value::Object(
+                map.iter()
+                    .map(|(key, value)| (key.to_string(), value.to_json()))
+                    .collect::<Map<String, Value>>(),
+            ),
+            ClxValue::Array(items) => Value::Array(items.iter().map(ClxValue::to_json).collect()),
+        }
+    }
+}
+
+#[cfg(feature = "json")]
+impl From<ClxValue> for Value {
+    fn from(value: ClxValue) -> Self {
+        value.to_json()
+    }
+}
+
+#[cfg(feature = "json")]
+impl From<&ClxValue> for Value {
+    fn from(value: &ClxValue) -> Self {
+        value.to_json()
+    }
+}
+
+impl Serialize for ClxValue {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            ClxValue::Bool(b) => serializer.serialize_bool(*b),
+            ClxValue::Int(i) => serializer.serialize_i64(*i),
+            ClxValue::UInt(u) => serializer.serialize_u64(*u),
+            ClxValue::Float(f) => serializer.serialize_f64(*f),
+            ClxValue::String(s) => serializer.serialize_str(s),
+            ClxValue::ByteArray(bytes) => serializer.serialize_str(&base64(bytes)),
+            ClxValue::Object(map) => {
+                let mut entries = serializer.serialize_map(Some(map.len()))?;
+                for (key, value) in map {
+                    entries.serialize_entry(&**key, value)?;
+                }
+                entries.end()
+            }
+            ClxValue::Array(items) => {
+                let mut seq = serializer.serialize_seq(Some(items.len()))?;
+                for item in items {
+                    seq.serialize_element(item)?;
+                }
+                seq.end()
+            }
+        }
+    }
+}
+
+fn base64(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity((bytes.len() + 2) / 3 * 4);
+    for group in bytes.chunks(3) {
+        let b = [
+            group[0],
+            *group.get(1).unwrap_or(&0),
+            *group.get(2).unwrap_or(&0),
+        ];
+        let bits = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+        for i in 0..4 {
+            if i <= group.len() {
+                out.push(BASE64_ALPHABET[(bits >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
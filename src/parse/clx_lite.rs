@@ -1,12 +1,32 @@
 use byteorder::{LittleEndian, ReadBytesExt};
 use flate2::read::ZlibDecoder;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::io::{Cursor, Read};
+use std::sync::Arc;
 
+use super::clx_ref::read_utf16_str;
 use crate::constants::clx_types;
 use crate::error::{Nd2Error, Result};
+use crate::options::Limits;
+use crate::types::{Diagnostic, DiagnosticKind};
+
+/// Deepest nesting of levels and nested CLX Lite byte arrays a parse
+/// follows. Real metadata nests a dozen deep at most.
+const MAX_DEPTH: usize = 256;
+
+/// Most bytes one parse walks in total: the data, every section inflated
+/// from it and every byte array re-parsed as nested CLX Lite (so a nested
+/// array counts once per depth it is walked at). Bounds zlib bombs and the
+/// quadratic cost of deeply nested byte arrays.
+const MAX_WALKED_BYTES: u64 = 1 << 30;
+
+/// Entries of a CLX object by name, in name order.
+pub type ClxObject = BTreeMap<Arc<str>, ClxValue>;
 
 /// Parsed JSON-like value from CLX Lite format
+///
+/// Object keys are shared: every occurrence of a name within one parse
+/// points at the same `Arc<str>`.
 #[derive(Debug, Clone, PartialEq)]
 pub enum ClxValue {
     Bool(bool),
@@ -15,12 +35,12 @@ pub enum ClxValue {
     Float(f64),
     String(String),
     ByteArray(Vec<u8>),
-    Object(HashMap<String, ClxValue>),
+    Object(ClxObject),
     Array(Vec<ClxValue>),
 }
 
 impl ClxValue {
-    pub fn as_object(&self) -> Option<&HashMap<String, ClxValue>> {
+    pub fn as_object(&self) -> Option<&ClxObject> {
         if let ClxValue::Object(map) = self {
             Some(map)
         } else {
@@ -70,26 +90,108 @@ impl ClxValue {
 }
 
 /// Parser for CLX Lite binary TLV format
+///
+/// Data nested more than 256 levels deep, or making a parse walk more than
+/// 1 GiB including inflated sections and re-parsed byte arrays, is rejected
+/// with a [`FileError::LimitExceeded`](crate::FileError::LimitExceeded)
+/// error, as is data going past the parser's [`Limits`].
 pub struct ClxLiteParser {
-    strip_prefix: bool,
+    pub(super) strip_prefix: bool,
+    pub(super) lenient: bool,
+    limits: Limits,
 }
 
 impl ClxLiteParser {
     pub fn new(strip_prefix: bool) -> Self {
-        Self { strip_prefix }
+        Self {
+            strip_prefix,
+            lenient: false,
+            limits: Limits::default(),
+        }
+    }
+
+    /// Tighten the nesting depth, bytes walked and entries read per parse
+    /// to `limits` ([`Limits::max_depth`], [`Limits::max_decompressed_bytes`]
+    /// and [`Limits::max_clx_nodes`]).
+    pub fn limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Limits of one parse with this parser.
+    pub(super) fn budget(&self) -> Budget {
+        Budget {
+            walked: 0,
+            nodes: 0,
+            max_walked: self.limits.max_decompressed_bytes.min(MAX_WALKED_BYTES),
+            max_nodes: self.limits.max_clx_nodes,
+            max_depth: self.limits.max_depth.min(MAX_DEPTH),
+        }
+    }
+
+    /// Skip entries of a type this parser doesn't know, and decode invalid
+    /// UTF-16 with replacement characters, instead of failing.
+    ///
+    /// An unknown entry's length is unknown, so parsing goes on after the
+    /// items of the level holding it: the entries after it in that level
+    /// are lost, everything else is kept. An unknown entry outside any level
+    /// is still an error. Off by default.
+    pub fn lenient(mut self, enabled: bool) -> Self {
+        self.lenient = enabled;
+        self
     }
 
     /// Parse the entire buffer into a ClxValue
     pub fn parse(&self, data: &[u8]) -> Result<ClxValue> {
+        self.parse_with_diagnostics(data, &mut Vec::new())
+    }
+
+    /// Like [`ClxLiteParser::parse`], recording what a
+    /// [lenient](ClxLiteParser::lenient) parser recovered from: each
+    /// skipped entry as a [`DiagnosticKind::UnknownClxType`] diagnostic,
+    /// each name or string with invalid UTF-16 as a
+    /// [`DiagnosticKind::InvalidUtf16`] one.
+    pub fn parse_with_diagnostics(
+        &self,
+        data: &[u8],
+        diagnostics: &mut Vec<Diagnostic>,
+    ) -> Result<ClxValue> {
+        let mut state = ParseState {
+            keys: KeyCache::default(),
+            diagnostics,
+            budget: self.budget(),
+        };
+        self.parse_interned(data, 0, &mut state)
+    }
+
+    fn parse_interned(
+        &self,
+        data: &[u8],
+        depth: usize,
+        state: &mut ParseState<'_>,
+    ) -> Result<ClxValue> {
+        state.budget.walk(data.len())?;
         let mut cursor = Cursor::new(data);
-        self.parse_with_count(&mut cursor, 1)
+        self.parse_with_count(&mut cursor, 1, None, depth, state)
     }
 
-    fn parse_with_count(&self, cursor: &mut Cursor<&[u8]>, count: usize) -> Result<ClxValue> {
-        let mut output = HashMap::new();
+    /// Parse `count` entries at nesting `depth`, belonging to a level whose
+    /// items end at `level_end` if known.
+    fn parse_with_count(
+        &self,
+        cursor: &mut Cursor<&[u8]>,
+        count: usize,
+        level_end: Option<u64>,
+        depth: usize,
+        state: &mut ParseState<'_>,
+    ) -> Result<ClxValue> {
+        state.budget.check_depth(depth)?;
+        let mut output = BTreeMap::new();
 
         for _ in 0..count {
-            let (name, data_type) = self.read_chunk_header(cursor)?;
+            state.budget.count_node()?;
+            let entry_start = cursor.position();
+            let (name, data_type) = self.read_chunk_header(cursor, state)?;
 
             if data_type == -1 {
                 break;
@@ -98,11 +200,11 @@ impl ClxLiteParser {
             let value = match data_type as u8 {
                 clx_types::COMPRESS => {
                     // Skip 10 bytes, decompress rest, parse recursively
-                    cursor.set_position(cursor.position() + 10);
+                    cursor.set_position(cursor.position().saturating_add(10));
                     let mut compressed = Vec::new();
                     cursor.read_to_end(&mut compressed)?;
-                    let decompressed = decompress_zlib(&compressed)?;
-                    return self.parse(&decompressed);
+                    let decompressed = state.budget.decompress_zlib(&compressed)?;
+                    return self.parse_interned(&decompressed, depth, state);
                 }
                 clx_types::BOOL => ClxValue::Bool(cursor.read_u8()? != 0),
                 clx_types::INT32 => ClxValue::Int(cursor.read_i32::<LittleEndian>()? as i64),
@@ -111,21 +213,30 @@ impl ClxLiteParser {
                 clx_types::UINT64 => ClxValue::UInt(cursor.read_u64::<LittleEndian>()?),
                 clx_types::DOUBLE => ClxValue::Float(cursor.read_f64::<LittleEndian>()?),
                 clx_types::VOID_POINTER => ClxValue::UInt(cursor.read_u64::<LittleEndian>()?),
-                clx_types::STRING => self.read_utf16_string(cursor)?,
-                clx_types::BYTE_ARRAY => self.read_byte_array(cursor)?,
-                clx_types::LEVEL => self.read_level(cursor)?,
-                other => return Err(Nd2Error::unsupported_clx_type(other)),
+                clx_types::STRING => self.read_utf16_string(cursor, state)?,
+                clx_types::BYTE_ARRAY => self.read_byte_array(cursor, depth, state)?,
+                clx_types::LEVEL => self.read_level(cursor, entry_start, depth, state)?,
+                other => {
+                    self.skip_unsupported(cursor, other, level_end)?;
+                    state.diagnostics.push(Diagnostic::new(
+                        DiagnosticKind::UnknownClxType,
+                        format!(
+                            "Skipped CLX entry '{}' of unknown type {} and the rest of its level",
+                            name, other
+                        ),
+                    ));
+                    break;
+                }
             };
 
             // Handle empty names (list elements in nd2)
             if name.is_empty() {
                 if let Some(ClxValue::Array(arr)) = output.get_mut("") {
                     arr.push(value);
-                } else if output.contains_key("") {
-                    let existing = output.remove("").unwrap();
-                    output.insert(String::new(), ClxValue::Array(vec![existing, value]));
+                } else if let Some(existing) = output.remove("") {
+                    output.insert(name, ClxValue::Array(vec![existing, value]));
                 } else {
-                    output.insert(String::new(), value);
+                    output.insert(name, value);
                 }
             } else {
                 output.insert(name, value);
@@ -135,7 +246,28 @@ impl ClxLiteParser {
         Ok(ClxValue::Object(output))
     }
 
-    fn read_chunk_header(&self, cursor: &mut Cursor<&[u8]>) -> Result<(String, i8)> {
+    /// Move past an entry of unsupported `data_type` to `level_end`, the
+    /// end of the enclosing level's items, when lenient and that is known.
+    pub(super) fn skip_unsupported(
+        &self,
+        cursor: &mut Cursor<&[u8]>,
+        data_type: u8,
+        level_end: Option<u64>,
+    ) -> Result<()> {
+        match level_end {
+            Some(end) if self.lenient && end >= cursor.position() => {
+                cursor.set_position(end);
+                Ok(())
+            }
+            _ => Err(Nd2Error::unsupported_clx_type(data_type)),
+        }
+    }
+
+    fn read_chunk_header(
+        &self,
+        cursor: &mut Cursor<&[u8]>,
+        state: &mut ParseState<'_>,
+    ) -> Result<(Arc<str>, i8)> {
         let data_type = cursor.read_u8()? as i8;
         let name_length = cursor.read_u8()? as usize;
 
@@ -147,79 +279,271 @@ impl ClxLiteParser {
         }
 
         let name = if data_type == clx_types::COMPRESS as i8 {
-            String::new()
+            state.keys.empty()
         } else {
-            let mut name_bytes = vec![0u8; name_length * 2];
-            cursor.read_exact(&mut name_bytes)?;
-            let name = decode_utf16_le(&name_bytes)?;
-            // Strip null terminator
-            let name = name.trim_end_matches('\0');
-            if self.strip_prefix {
-                strip_lowercase_prefix(name)
-            } else {
-                name.to_string()
-            }
+            let start = cursor.position() as usize;
+            let name_bytes = cursor
+                .get_ref()
+                .get(start..)
+                .and_then(|rest| rest.get(..name_length * 2))
+                .ok_or_else(|| {
+                    std::io::Error::new(
+                        std::io::ErrorKind::UnexpectedEof,
+                        "CLX name runs past the end of the buffer",
+                    )
+                })?;
+            cursor.set_position((start + name_bytes.len()) as u64);
+            let diagnostics = &mut *state.diagnostics;
+            state.keys.intern(name_bytes, self.strip_prefix, |bytes| {
+                self.decode_utf16(bytes, diagnostics)
+            })?
         };
 
         Ok((name, data_type))
     }
 
-    fn read_utf16_string(&self, cursor: &mut Cursor<&[u8]>) -> Result<ClxValue> {
-        // Read 2 bytes at a time until we hit \x00\x00
-        let mut bytes = Vec::new();
-        loop {
-            let b1 = cursor.read_u8()?;
-            let b2 = cursor.read_u8()?;
-            bytes.push(b1);
-            bytes.push(b2);
-            if b1 == 0 && b2 == 0 {
-                break;
+    fn read_utf16_string(
+        &self,
+        cursor: &mut Cursor<&[u8]>,
+        state: &mut ParseState<'_>,
+    ) -> Result<ClxValue> {
+        // Find the \x00\x00 terminator in the buffer, then decode up to it
+        let bytes = read_utf16_str(cursor)?.as_bytes();
+        Ok(ClxValue::String(
+            self.decode_utf16(bytes, state.diagnostics)?,
+        ))
+    }
+
+    /// Decode the UTF-16 LE `bytes` of a name or string. A lenient parser
+    /// decodes unpaired surrogates as U+FFFD and records a
+    /// [`DiagnosticKind::InvalidUtf16`] diagnostic instead of failing.
+    fn decode_utf16(&self, bytes: &[u8], diagnostics: &mut Vec<Diagnostic>) -> Result<String> {
+        let units = bytes
+            .chunks_exact(2)
+            .map(|c| u16::from_le_bytes([c[0], c[1]]));
+        let mut decoded = String::with_capacity(bytes.len() / 2);
+        let mut replaced = false;
+        for c in char::decode_utf16(units) {
+            match c {
+                Ok(c) => decoded.push(c),
+                Err(err) if !self.lenient => {
+                    return Err(Nd2Error::file_invalid_format(err.to_string()))
+                }
+                Err(_) => {
+                    decoded.push(char::REPLACEMENT_CHARACTER);
+                    replaced = true;
+                }
             }
         }
-        let s = decode_utf16_le(&bytes)?;
-        Ok(ClxValue::String(s.trim_end_matches('\0').to_string()))
+        if replaced {
+            diagnostics.push(Diagnostic::new(
+                DiagnosticKind::InvalidUtf16,
+                format!(
+                    "Invalid UTF-16 in CLX metadata decoded as '{}'",
+                    decoded.trim_end_matches('\0')
+                ),
+            ));
+        }
+        Ok(decoded)
     }
 
-    fn read_byte_array(&self, cursor: &mut Cursor<&[u8]>) -> Result<ClxValue> {
-        let size = cursor.read_u64::<LittleEndian>()? as usize;
-        let mut bytes = vec![0u8; size];
+    fn read_byte_array(
+        &self,
+        cursor: &mut Cursor<&[u8]>,
+        depth: usize,
+        state: &mut ParseState<'_>,
+    ) -> Result<ClxValue> {
+        let size = cursor.read_u64::<LittleEndian>()?;
+        let remaining = remaining_len(cursor);
+        if size > remaining {
+            return Err(Nd2Error::file_invalid_format(format!(
+                "CLX byte array of {} bytes exceeds remaining {} bytes",
+                size, remaining
+            )));
+        }
+        let mut bytes = vec![0u8; size as usize];
         cursor.read_exact(&mut bytes)?;
 
         // Try to parse as nested CLX Lite if it looks valid
         if looks_like_clx_lite(&bytes) {
-            if let Ok(nested) = self.parse(&bytes) {
-                return Ok(nested);
+            match self.parse_interned(&bytes, depth + 1, state) {
+                Ok(nested) => return Ok(nested),
+                Err(err) if is_limit_error(&err) => return Err(err),
+                Err(_) => {}
             }
         }
 
         Ok(ClxValue::ByteArray(bytes))
     }
 
-    fn read_level(&self, cursor: &mut Cursor<&[u8]>) -> Result<ClxValue> {
+    fn read_level(
+        &self,
+        cursor: &mut Cursor<&[u8]>,
+        entry_start: u64,
+        depth: usize,
+        state: &mut ParseState<'_>,
+    ) -> Result<ClxValue> {
         let item_count = cursor.read_u32::<LittleEndian>()? as usize;
-        let _length = cursor.read_u64::<LittleEndian>()? as usize;
+        let length = cursor.read_u64::<LittleEndian>()?;
+        let level_end = level_end(cursor, entry_start, length);
 
         // Parse the nested data
-        let value = self.parse_with_count(cursor, item_count)?;
+        let value = self.parse_with_count(cursor, item_count, level_end, depth + 1, state)?;
 
         // Skip the item_count * 8 bytes of offset data
-        cursor.set_position(cursor.position() + (item_count as u64 * 8));
+        cursor.set_position(
+            cursor
+                .position()
+                .saturating_add((item_count as u64).saturating_mul(8)),
+        );
 
         // Handle the case where all items have empty names (array-like)
-        if let ClxValue::Object(ref map) = value {
-            if map.len() == 1 && map.contains_key("") {
-                if let Some(arr) = map.get("") {
-                    return Ok(arr.clone());
-                }
+        match value {
+            ClxValue::Object(mut map) if map.len() == 1 && map.contains_key("") => {
+                Ok(map.remove("").expect("checked above"))
             }
+            value => Ok(value),
+        }
+    }
+}
+
+/// End of the items of the level entry starting at `entry_start`, from the
+/// length in its header (which counts from the entry start and leaves out
+/// the offset table), when that lies inside the data.
+pub(super) fn level_end(cursor: &Cursor<&[u8]>, entry_start: u64, length: u64) -> Option<u64> {
+    entry_start
+        .checked_add(length)
+        .filter(|&end| end >= cursor.position() && end <= cursor.get_ref().len() as u64)
+}
+
+/// What one parse has used of its parser's limits.
+pub(super) struct Budget {
+    /// Bytes walked so far: the data, every section inflated from it and
+    /// every byte array re-parsed as nested CLX Lite.
+    walked: u64,
+    /// Entries read so far.
+    nodes: u64,
+    max_walked: u64,
+    max_nodes: u64,
+    max_depth: usize,
+}
+
+impl Budget {
+    /// Fail once nesting goes past the depth limit.
+    pub(super) fn check_depth(&self, depth: usize) -> Result<()> {
+        if depth > self.max_depth {
+            return Err(Nd2Error::file_limit_exceeded(format!(
+                "CLX data nested more than {} levels deep",
+                self.max_depth
+            )));
         }
+        Ok(())
+    }
 
-        Ok(value)
+    /// Add `len` bytes to the bytes walked, failing once they go past the
+    /// limit.
+    pub(super) fn walk(&mut self, len: usize) -> Result<()> {
+        self.walked = self.walked.saturating_add(len as u64);
+        if self.walked > self.max_walked {
+            return Err(self.walk_limit_error());
+        }
+        Ok(())
     }
+
+    /// Count one more entry, failing once entries go past the limit.
+    pub(super) fn count_node(&mut self) -> Result<()> {
+        self.nodes += 1;
+        if self.nodes > self.max_nodes {
+            return Err(Nd2Error::file_limit_exceeded(format!(
+                "CLX data has more than {} entries",
+                self.max_nodes
+            )));
+        }
+        Ok(())
+    }
+
+    /// Decompress zlib data, failing rather than inflating it past what
+    /// the parse may still walk.
+    pub(super) fn decompress_zlib(&self, data: &[u8]) -> Result<Vec<u8>> {
+        let budget = self.max_walked.saturating_sub(self.walked);
+        let mut decompressed = Vec::new();
+        ZlibDecoder::new(data)
+            .take(budget.saturating_add(1))
+            .read_to_end(&mut decompressed)
+            .map_err(|e| Nd2Error::file_invalid_format(e.to_string()))?;
+        if decompressed.len() as u64 > budget {
+            return Err(self.walk_limit_error());
+        }
+        Ok(decompressed)
+    }
+
+    fn walk_limit_error(&self) -> Nd2Error {
+        Nd2Error::file_limit_exceeded(format!("CLX data expands past {} bytes", self.max_walked))
+    }
+}
+
+/// Whether `err` is a limit being hit, which a failed parse of a nested
+/// byte array passes on instead of falling back to its bytes.
+pub(super) fn is_limit_error(err: &Nd2Error) -> bool {
+    matches!(
+        err,
+        Nd2Error::File {
+            source: crate::error::FileError::LimitExceeded { .. }
+        }
+    )
+}
+
+/// State of one owned parse.
+struct ParseState<'d> {
+    keys: KeyCache,
+    diagnostics: &'d mut Vec<Diagnostic>,
+    budget: Budget,
+}
+
+/// Decoded object keys by their raw UTF-16 name, for one parse.
+///
+/// Metadata trees repeat the same few hundred names many thousands of times;
+/// each is decoded once and then shared.
+#[derive(Default)]
+struct KeyCache {
+    keys: HashMap<Vec<u8>, Arc<str>>,
+    empty: Option<Arc<str>>,
+}
+
+impl KeyCache {
+    fn intern(
+        &mut self,
+        name_bytes: &[u8],
+        strip_prefix: bool,
+        decode: impl FnOnce(&[u8]) -> Result<String>,
+    ) -> Result<Arc<str>> {
+        if let Some(key) = self.keys.get(name_bytes) {
+            return Ok(Arc::clone(key));
+        }
+        let name = decode(name_bytes)?;
+        // Strip null terminator
+        let name = name.trim_end_matches('\0');
+        let key: Arc<str> = if strip_prefix {
+            strip_lowercase_prefix(name).into()
+        } else {
+            name.into()
+        };
+        self.keys.insert(name_bytes.to_vec(), Arc::clone(&key));
+        Ok(key)
+    }
+
+    fn empty(&mut self) -> Arc<str> {
+        Arc::clone(self.empty.get_or_insert_with(|| "".into()))
+    }
+}
+
+/// Bytes left between the cursor position and the end of its buffer.
+pub(super) fn remaining_len(cursor: &Cursor<&[u8]>) -> u64 {
+    (cursor.get_ref().len() as u64).saturating_sub(cursor.position())
 }
 
 /// Check if data looks like valid CLX Lite
-fn looks_like_clx_lite(data: &[u8]) -> bool {
+pub(super) fn looks_like_clx_lite(data: &[u8]) -> bool {
     if data.len() < 2 {
         return false;
     }
@@ -258,28 +582,9 @@ fn looks_like_clx_lite(data: &[u8]) -> bool {
     true
 }
 
-/// Decode UTF-16 LE bytes to String
-fn decode_utf16_le(bytes: &[u8]) -> Result<String> {
-    let u16s: Vec<u16> = bytes
-        .chunks_exact(2)
-        .map(|c| u16::from_le_bytes([c[0], c[1]]))
-        .collect();
-    String::from_utf16(&u16s).map_err(|e| Nd2Error::file_invalid_format(e.to_string()))
-}
-
 /// Strip lowercase prefix from identifier (e.g., "uiWidth" -> "Width")
 fn strip_lowercase_prefix(s: &str) -> String {
     s.chars()
         .skip_while(|c| c.is_lowercase() || *c == '_')
         .collect()
 }
-
-/// Decompress zlib data
-fn decompress_zlib(data: &[u8]) -> Result<Vec<u8>> {
-    let mut decoder = ZlibDecoder::new(data);
-    let mut decompressed = Vec::new();
-    decoder
-        .read_to_end(&mut decompressed)
-        .map_err(|e| Nd2Error::file_invalid_format(e.to_string()))?;
-    Ok(decompressed)
-}
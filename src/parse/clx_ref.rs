@@ -0,0 +1,388 @@
+use byteorder::{LittleEndian, ReadBytesExt};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::Cursor;
+
+use super::clx_lite::{
+    is_limit_error, level_end, looks_like_clx_lite, remaining_len, Budget, ClxLiteParser, ClxValue,
+};
+use crate::constants::clx_types;
+use crate::error::{Nd2Error, Result};
+
+/// UTF-16 LE string borrowed from a CLX Lite buffer, without its null
+/// terminator. Decoded only when asked.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Utf16Str<'a>(&'a [u8]);
+
+impl<'a> Utf16Str<'a> {
+    /// The raw UTF-16 LE bytes.
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    fn units(&self) -> impl Iterator<Item = u16> + 'a {
+        self.0
+            .chunks_exact(2)
+            .map(|c| u16::from_le_bytes([c[0], c[1]]))
+    }
+
+    /// Decoded characters; unpaired surrogates become U+FFFD.
+    pub fn chars(&self) -> impl Iterator<Item = char> + 'a {
+        char::decode_utf16(self.units()).map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+    }
+
+    /// Decode to an owned `String`, failing on invalid UTF-16.
+    pub fn decode(&self) -> Result<String> {
+        let mut decoded = String::with_capacity(self.0.len() / 2);
+        for c in char::decode_utf16(self.units()) {
+            decoded.push(c.map_err(|e| Nd2Error::file_invalid_format(e.to_string()))?);
+        }
+        Ok(decoded)
+    }
+
+    /// Compare with `s` without decoding.
+    pub fn eq_str(&self, s: &str) -> bool {
+        self.units().eq(s.encode_utf16())
+    }
+
+    /// Strip the trailing null terminator(s).
+    pub(super) fn trim_nul(bytes: &'a [u8]) -> Self {
+        let mut end = bytes.len() & !1;
+        while end >= 2 && bytes[end - 2..end] == [0, 0] {
+            end -= 2;
+        }
+        Self(&bytes[..end])
+    }
+
+    /// Skip a lowercase type prefix, like [`ClxLiteParser::new`] with
+    /// `strip_prefix` (e.g. "uiWidth" -> "Width").
+    pub(super) fn strip_lowercase_prefix(self) -> Self {
+        let skip = char::decode_utf16(self.units())
+            .take_while(|c| matches!(c, Ok(c) if c.is_lowercase() || *c == '_'))
+            .map(|c| c.map_or(2, |c| c.len_utf16() * 2))
+            .sum::<usize>();
+        Self(&self.0[skip..])
+    }
+}
+
+impl fmt::Display for Utf16Str<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.chars().try_for_each(|c| fmt::Write::write_char(f, c))
+    }
+}
+
+impl fmt::Debug for Utf16Str<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.chars().collect::<String>(), f)
+    }
+}
+
+/// CLX Lite value borrowing its strings and byte arrays from the parsed
+/// buffer, from [`ClxLiteParser::parse_borrowed`].
+///
+/// Objects keep their entries in file order; as with [`ClxValue`], a
+/// repeated name resolves to its last entry.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ClxValueRef<'a> {
+    Bool(bool),
+    Int(i64),
+    UInt(u64),
+    Float(f64),
+    String(Utf16Str<'a>),
+    ByteArray(&'a [u8]),
+    Object(Vec<(Utf16Str<'a>, ClxValueRef<'a>)>),
+    Array(Vec<ClxValueRef<'a>>),
+}
+
+impl<'a> ClxValueRef<'a> {
+    /// Look up an object entry by name.
+    pub fn get(&self, name: &str) -> Option<&ClxValueRef<'a>> {
+        self.as_object()?
+            .iter()
+            .rev()
+            .find(|(key, _)| key.eq_str(name))
+            .map(|(_, value)| value)
+    }
+
+    pub fn as_object(&self) -> Option<&[(Utf16Str<'a>, ClxValueRef<'a>)]> {
+        if let ClxValueRef::Object(entries) = self {
+            Some(entries)
+        } else {
+            None
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[ClxValueRef<'a>]> {
+        if let ClxValueRef::Array(items) = self {
+            Some(items)
+        } else {
+            None
+        }
+    }
+
+    pub fn as_str(&self) -> Option<Utf16Str<'a>> {
+        if let ClxValueRef::String(s) = self {
+            Some(*s)
+        } else {
+            None
+        }
+    }
+
+    pub fn as_bytes(&self) -> Option<&'a [u8]> {
+        if let ClxValueRef::ByteArray(bytes) = self {
+            Some(bytes)
+        } else {
+            None
+        }
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        if let ClxValueRef::Int(i) = self {
+            Some(*i)
+        } else {
+            None
+        }
+    }
+
+    pub fn as_u64(&self) -> Option<u64> {
+        if let ClxValueRef::UInt(u) = self {
+            Some(*u)
+        } else {
+            None
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        if let ClxValueRef::Float(f) = self {
+            Some(*f)
+        } else {
+            None
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        if let ClxValueRef::Bool(b) = self {
+            Some(*b)
+        } else {
+            None
+        }
+    }
+
+    /// Copy into an owned [`ClxValue`].
+    pub fn to_owned_value(&self) -> Result<ClxValue> {
+        Ok(match self {
+            ClxValueRef::Bool(b) => ClxValue::Bool(*b),
+            ClxValueRef::Int(i) => ClxValue::Int(*i),
+            ClxValueRef::UInt(u) => ClxValue::UInt(*u),
+            ClxValueRef::Float(f) => ClxValue::Float(*f),
+            ClxValueRef::String(s) => ClxValue::String(s.decode()?),
+            ClxValueRef::ByteArray(bytes) => ClxValue::ByteArray(bytes.to_vec()),
+            ClxValueRef::Object(entries) => {
+                let mut map = BTreeMap::new();
+                for (key, value) in entries {
+                    map.insert(key.decode()?.into(), value.to_owned_value()?);
+                }
+                ClxValue::Object(map)
+            }
+            ClxValueRef::Array(items) => ClxValue::Array(
+                items
+                    .iter()
+                    .map(ClxValueRef::to_owned_value)
+                    .collect::<Result<_>>()?,
+            ),
+        })
+    }
+}
+
+impl ClxLiteParser {
+    /// Parse without copying: strings and byte arrays borrow from `data`.
+    ///
+    /// Compressed CLX data cannot be borrowed from; a buffer that starts
+    /// with a compressed section is an error (use [`ClxLiteParser::parse`]),
+    /// and compressed nested byte arrays stay [`ClxValueRef::ByteArray`].
+    /// Entries skipped by a [lenient](ClxLiteParser::lenient) parser are not
+    /// reported.
+    pub fn parse_borrowed<'a>(&self, data: &'a [u8]) -> Result<ClxValueRef<'a>> {
+        self.parse_ref(data, 0, &mut self.budget())
+    }
+
+    /// Parse `data` at nesting `depth`, adding its length to the bytes
+    /// walked.
+    fn parse_ref<'a>(
+        &self,
+        data: &'a [u8],
+        depth: usize,
+        budget: &mut Budget,
+    ) -> Result<ClxValueRef<'a>> {
+        budget.walk(data.len())?;
+        let mut cursor = Cursor::new(data);
+        self.parse_ref_with_count(&mut cursor, 1, None, depth, budget)
+    }
+
+    fn parse_ref_with_count<'a>(
+        &self,
+        cursor: &mut Cursor<&'a [u8]>,
+        count: usize,
+        level_end: Option<u64>,
+        depth: usize,
+        budget: &mut Budget,
+    ) -> Result<ClxValueRef<'a>> {
+        budget.check_depth(depth)?;
+        let mut output: Vec<(Utf16Str<'a>, ClxValueRef<'a>)> = Vec::new();
+        // Index in `output` of the entry collecting empty-name list elements.
+        let mut list: Option<usize> = None;
+
+        for _ in 0..count {
+            budget.count_node()?;
+            let entry_start = cursor.position();
+            let data_type = cursor.read_u8()? as i8;
+            let name_length = cursor.read_u8()? as usize;
+            if data_type == clx_types::DEPRECATED as i8 || data_type == clx_types::UNKNOWN as i8 {
+                return Err(Nd2Error::file_invalid_format(format!(
+                    "Unknown data type in metadata header: {}",
+                    data_type
+                )));
+            }
+            if data_type == -1 {
+                break;
+            }
+            if data_type == clx_types::COMPRESS as i8 {
+                return Err(Nd2Error::file_invalid_format(
+                    "Compressed CLX data cannot be parsed in place".to_string(),
+                ));
+            }
+
+            let name = Utf16Str::trim_nul(take(cursor, name_length * 2)?);
+            let name = if self.strip_prefix {
+                name.strip_lowercase_prefix()
+            } else {
+                name
+            };
+
+            let value = match data_type as u8 {
+                clx_types::BOOL => ClxValueRef::Bool(cursor.read_u8()? != 0),
+                clx_types::INT32 => ClxValueRef::Int(cursor.read_i32::<LittleEndian>()? as i64),
+                clx_types::UINT32 => ClxValueRef::UInt(cursor.read_u32::<LittleEndian>()? as u64),
+                clx_types::INT64 => ClxValueRef::Int(cursor.read_i64::<LittleEndian>()?),
+                clx_types::UINT64 => ClxValueRef::UInt(cursor.read_u64::<LittleEndian>()?),
+                clx_types::DOUBLE => ClxValueRef::Float(cursor.read_f64::<LittleEndian>()?),
+                clx_types::VOID_POINTER => ClxValueRef::UInt(cursor.read_u64::<LittleEndian>()?),
+                clx_types::STRING => ClxValueRef::String(read_utf16_str(cursor)?),
+                clx_types::BYTE_ARRAY => self.read_byte_array_ref(cursor, depth, budget)?,
+                clx_types::LEVEL => self.read_level_ref(cursor, entry_start, depth, budget)?,
+                other => {
+                    self.skip_unsupported(cursor, other, level_end)?;
+                    break;
+                }
+            };
+
+            // Handle empty names (list elements in nd2)
+            if name.is_empty() {
+                match list {
+                    Some(i) => match &mut output[i].1 {
+                        ClxValueRef::Array(items) => items.push(value),
+                        first => {
+                            let first = std::mem::replace(first, ClxValueRef::Bool(false));
+                            output[i].1 = ClxValueRef::Array(vec![first, value]);
+                        }
+                    },
+                    None => {
+                        list = Some(output.len());
+                        output.push((name, value));
+                    }
+                }
+            } else {
+                output.push((name, value));
+            }
+        }
+
+        Ok(ClxValueRef::Object(output))
+    }
+
+    fn read_byte_array_ref<'a>(
+        &self,
+        cursor: &mut Cursor<&'a [u8]>,
+        depth: usize,
+        budget: &mut Budget,
+    ) -> Result<ClxValueRef<'a>> {
+        let size = cursor.read_u64::<LittleEndian>()?;
+        let remaining = remaining_len(cursor);
+        if size > remaining {
+            return Err(Nd2Error::file_invalid_format(format!(
+                "CLX byte array of {} bytes exceeds remaining {} bytes",
+                size, remaining
+            )));
+        }
+        let bytes = take(cursor, size as usize)?;
+
+        // Try to parse as nested CLX Lite if it looks valid
+        if looks_like_clx_lite(bytes) {
+            match self.parse_ref(bytes, depth + 1, budget) {
+                Ok(nested) => return Ok(nested),
+                Err(err) if is_limit_error(&err) => return Err(err),
+                Err(_) => {}
+            }
+        }
+
+        Ok(ClxValueRef::ByteArray(bytes))
+    }
+
+    fn read_level_ref<'a>(
+        &self,
+        cursor: &mut Cursor<&'a [u8]>,
+        entry_start: u64,
+        depth: usize,
+        budget: &mut Budget,
+    ) -> Result<ClxValueRef<'a>> {
+        let item_count = cursor.read_u32::<LittleEndian>()? as usize;
+        let length = cursor.read_u64::<LittleEndian>()?;
+        let level_end = level_end(cursor, entry_start, length);
+
+        let value = self.parse_ref_with_count(cursor, item_count, level_end, depth + 1, budget)?;
+
+        // Skip the item_count * 8 bytes of offset data
+        cursor.set_position(
+            cursor
+                .position()
+                .saturating_add((item_count as u64).saturating_mul(8)),
+        );
+
+        // Handle the case where all items have empty names (array-like)
+        match value {
+            ClxValueRef::Object(mut entries) if entries.len() == 1 && entries[0].0.is_empty() => {
+                Ok(entries.pop().expect("one entry").1)
+            }
+            value => Ok(value),
+        }
+    }
+}
+
+/// Borrow the next `len` bytes and advance past them.
+pub(super) fn take<'a>(cursor: &mut Cursor<&'a [u8]>, len: usize) -> Result<&'a [u8]> {
+    let data: &'a [u8] = cursor.get_ref();
+    let start = cursor.position() as usize;
+    let bytes = start
+        .checked_add(len)
+        .and_then(|end| data.get(start..end))
+        .ok_or_else(|| Nd2Error::from(std::io::Error::from(std::io::ErrorKind::UnexpectedEof)))?;
+    cursor.set_position((start + len) as u64);
+    Ok(bytes)
+}
+
+/// Borrow a null-terminated UTF-16 LE string.
+pub(super) fn read_utf16_str<'a>(cursor: &mut Cursor<&'a [u8]>) -> Result<Utf16Str<'a>> {
+    let data: &'a [u8] = cursor.get_ref();
+    let start = cursor.position() as usize;
+    let rest = data.get(start..).unwrap_or_default();
+    let len = rest
+        .chunks_exact(2)
+        .position(|unit| unit == [0, 0])
+        .map(|units| units * 2)
+        .ok_or_else(|| Nd2Error::from(std::io::Error::from(std::io::ErrorKind::UnexpectedEof)))?;
+    cursor.set_position((start + len + 2) as u64);
+    Ok(Utf16Str::trim_nul(&rest[..len]))
+}
@@ -0,0 +1,188 @@
+use byteorder::{LittleEndian, ReadBytesExt};
+use std::io::Cursor;
+
+use super::clx_lite::{is_limit_error, looks_like_clx_lite, remaining_len, Budget, ClxLiteParser};
+use super::clx_ref::{read_utf16_str, take, ClxValueRef, Utf16Str};
+use crate::constants::clx_types;
+use crate::error::{Nd2Error, Result};
+
+/// Receives the entries of CLX Lite data in file order from
+/// [`ClxLiteParser::visit`], so large metadata chunks can be processed
+/// without building a [`ClxValue`](super::ClxValue) tree.
+///
+/// Entries arrive as stored: list elements have empty names, and levels are
+/// not collapsed into arrays as by [`ClxLiteParser::parse`].
+pub trait ClxVisitor {
+    /// A nested object named `name` starts: a `LEVEL` entry, or a byte
+    /// array holding CLX Lite. Return `false` to skip its entries;
+    /// [`ClxVisitor::leave`] is then not called for it.
+    fn enter(&mut self, name: Utf16Str<'_>) -> Result<bool> {
+        let _ = name;
+        Ok(true)
+    }
+
+    /// The object entered last ends.
+    fn leave(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    /// A scalar, string or plain byte array entry.
+    fn value(&mut self, name: Utf16Str<'_>, value: ClxValueRef<'_>) -> Result<()>;
+
+    /// An entry of unknown `data_type` was skipped by a
+    /// [lenient](ClxLiteParser::lenient) parser, with the entries after it
+    /// in the same object.
+    fn skipped(&mut self, name: Utf16Str<'_>, data_type: u8) -> Result<()> {
+        let _ = (name, data_type);
+        Ok(())
+    }
+}
+
+impl ClxLiteParser {
+    /// Walk `data`, handing its entries to `visitor` instead of building a
+    /// tree. Compressed sections are inflated one at a time; strings and
+    /// byte arrays are borrowed from the data being walked.
+    pub fn visit<V: ClxVisitor + ?Sized>(&self, data: &[u8], visitor: &mut V) -> Result<()> {
+        let mut state = VisitState {
+            visitor,
+            budget: self.budget(),
+        };
+        self.visit_data(data, 0, &mut state, true)
+    }
+
+    /// Walk `data` at nesting `depth`.
+    fn visit_data<V: ClxVisitor + ?Sized>(
+        &self,
+        data: &[u8],
+        depth: usize,
+        state: &mut VisitState<'_, V>,
+        report: bool,
+    ) -> Result<()> {
+        state.budget.walk(data.len())?;
+        let mut cursor = Cursor::new(data);
+        self.visit_with_count(&mut cursor, 1, None, depth, state, report)
+    }
+
+    /// Walk `count` entries at nesting `depth`, of a level whose items end
+    /// at `level_end` if known. With `report` unset the entries are only
+    /// checked, as when skipping an object or probing a byte array.
+    fn visit_with_count<V: ClxVisitor + ?Sized>(
+        &self,
+        cursor: &mut Cursor<&[u8]>,
+        count: usize,
+        level_end: Option<u64>,
+        depth: usize,
+        state: &mut VisitState<'_, V>,
+        report: bool,
+    ) -> Result<()> {
+        state.budget.check_depth(depth)?;
+        for _ in 0..count {
+            state.budget.count_node()?;
+            let entry_start = cursor.position();
+            let data_type = cursor.read_u8()? as i8;
+            let name_length = cursor.read_u8()? as usize;
+            if data_type == clx_types::DEPRECATED as i8 || data_type == clx_types::UNKNOWN as i8 {
+                return Err(Nd2Error::file_invalid_format(format!(
+                    "Unknown data type in metadata header: {}",
+                    data_type
+                )));
+            }
+            if data_type == -1 {
+                break;
+            }
+            if data_type == clx_types::COMPRESS as i8 {
+                // Skip 10 bytes, decompress rest, walk it instead
+                cursor.set_position(cursor.position().saturating_add(10));
+                let remaining = remaining_len(cursor) as usize;
+                let decompressed = state.budget.decompress_zlib(take(cursor, remaining)?)?;
+                return self.visit_data(&decompressed, depth, state, report);
+            }
+
+            let name = Utf16Str::trim_nul(take(cursor, name_length * 2)?);
+            let name = if self.strip_prefix {
+                name.strip_lowercase_prefix()
+            } else {
+                name
+            };
+
+            let value = match data_type as u8 {
+                clx_types::BOOL => ClxValueRef::Bool(cursor.read_u8()? != 0),
+                clx_types::INT32 => ClxValueRef::Int(cursor.read_i32::<LittleEndian>()? as i64),
+                clx_types::UINT32 => ClxValueRef::UInt(cursor.read_u32::<LittleEndian>()? as u64),
+                clx_types::INT64 => ClxValueRef::Int(cursor.read_i64::<LittleEndian>()?),
+                clx_types::UINT64 => ClxValueRef::UInt(cursor.read_u64::<LittleEndian>()?),
+                clx_types::DOUBLE => ClxValueRef::Float(cursor.read_f64::<LittleEndian>()?),
+                clx_types::VOID_POINTER => ClxValueRef::UInt(cursor.read_u64::<LittleEndian>()?),
+                clx_types::STRING => ClxValueRef::String(read_utf16_str(cursor)?),
+                clx_types::BYTE_ARRAY => {
+                    let size = cursor.read_u64::<LittleEndian>()?;
+                    let remaining = remaining_len(cursor);
+                    if size > remaining {
+                        return Err(Nd2Error::file_invalid_format(format!(
+                            "CLX byte array of {} bytes exceeds remaining {} bytes",
+                            size, remaining
+                        )));
+                    }
+                    let bytes = take(cursor, size as usize)?;
+                    // Walk nested CLX Lite like a level, once it is known to
+                    // parse (as `parse` falls back to the raw bytes).
+                    let nested = looks_like_clx_lite(bytes)
+                        && match self.visit_data(bytes, depth + 1, state, false) {
+                            Ok(()) => true,
+                            Err(err) if is_limit_error(&err) => return Err(err),
+                            Err(_) => false,
+                        };
+                    if nested {
+                        if report && state.visitor.enter(name)? {
+                            self.visit_data(bytes, depth + 1, state, true)?;
+                            state.visitor.leave()?;
+                        }
+                        continue;
+                    }
+                    ClxValueRef::ByteArray(bytes)
+                }
+                clx_types::LEVEL => {
+                    let item_count = cursor.read_u32::<LittleEndian>()? as usize;
+                    let length = cursor.read_u64::<LittleEndian>()?;
+                    let items_end = super::clx_lite::level_end(cursor, entry_start, length);
+                    let descend = report && state.visitor.enter(name)?;
+                    self.visit_with_count(
+                        cursor,
+                        item_count,
+                        items_end,
+                        depth + 1,
+                        state,
+                        descend,
+                    )?;
+                    // Skip the item_count * 8 bytes of offset data
+                    cursor.set_position(
+                        cursor
+                            .position()
+                            .saturating_add((item_count as u64).saturating_mul(8)),
+                    );
+                    if descend {
+                        state.visitor.leave()?;
+                    }
+                    continue;
+                }
+                other => {
+                    self.skip_unsupported(cursor, other, level_end)?;
+                    if report {
+                        state.visitor.skipped(name, other)?;
+                    }
+                    break;
+                }
+            };
+            if report {
+                state.visitor.value(name, value)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// State of one walk.
+struct VisitState<'v, V: ?Sized> {
+    visitor: &'v mut V,
+    budget: Budget,
+}
@@ -0,0 +1,377 @@
+use byteorder::{LittleEndian, ReadBytesExt};
+use std::io::Cursor;
+
+use super::clx_lite::{level_end, remaining_len, Budget, ClxLiteParser, ClxValue};
+use super::clx_ref::{read_utf16_str, take, ClxValueRef, Utf16Str};
+use crate::constants::clx_types;
+use crate::error::{Nd2Error, Result};
+
+/// Changes made by [`ClxLiteParser::rewrite`].
+pub(crate) trait ClxEdit {
+    /// New value for the scalar, string or byte array entry at `path` (the
+    /// names of the levels holding it, then its own), or `None` to keep it.
+    fn replace(&mut self, path: &[String], value: &ClxValueRef<'_>) -> Result<Option<ClxValue>> {
+        let _ = (path, value);
+        Ok(None)
+    }
+
+    /// Whether to leave out the entry at `path`, the `index`th item of its
+    /// level. Entries inside a removed level are not offered to the edit.
+    fn remove(&mut self, path: &[String], index: usize) -> bool {
+        let _ = (path, index);
+        false
+    }
+
+    /// Entries to add at the end of the level at `path`, after its own.
+    fn append(&mut self, path: &[String]) -> Vec<(String, ClxValue)> {
+        let _ = path;
+        Vec::new()
+    }
+}
+
+impl ClxLiteParser {
+    /// Re-encode `data` with the changes of `edit`.
+    ///
+    /// Entries are written back as stored unless `edit` replaces or removes
+    /// them, and replaced values keep the type of the entry they replace. A
+    /// level whose items change gets its item count and length updated and its
+    /// offset table rewritten; other levels keep their bytes. Compressed
+    /// sections are written back inflated, and byte arrays holding nested
+    /// CLX Lite are kept as they are.
+    pub(crate) fn rewrite(&self, data: &[u8], edit: &mut dyn ClxEdit) -> Result<Vec<u8>> {
+        let mut state = RewriteState {
+            edit,
+            budget: self.budget(),
+            path: Vec::new(),
+            removing: 0,
+        };
+        let mut out = Vec::with_capacity(data.len());
+        self.rewrite_data(data, 0, &mut state, &mut out)?;
+        Ok(out)
+    }
+
+    /// Rewrite `data` at nesting `depth` into `out`, keeping any bytes after
+    /// its entries.
+    fn rewrite_data(
+        &self,
+        data: &[u8],
+        depth: usize,
+        state: &mut RewriteState<'_>,
+        out: &mut Vec<u8>,
+    ) -> Result<()> {
+        state.budget.walk(data.len())?;
+        let mut cursor = Cursor::new(data);
+        self.rewrite_with_count(&mut cursor, 1, None, depth, state, out)?;
+        let end = usize::try_from(cursor.position()).unwrap_or(usize::MAX);
+        out.extend_from_slice(data.get(end..).unwrap_or_default());
+        Ok(())
+    }
+
+    /// Rewrite `count` entries at nesting `depth` into `out`, of a level
+    /// whose items end at `level_end` if known. Returns where each entry
+    /// starts in `out`, and how many entries were removed.
+    fn rewrite_with_count(
+        &self,
+        cursor: &mut Cursor<&[u8]>,
+        count: usize,
+        level_end: Option<u64>,
+        depth: usize,
+        state: &mut RewriteState<'_>,
+        out: &mut Vec<u8>,
+    ) -> Result<(Vec<usize>, usize)> {
+        state.budget.check_depth(depth)?;
+        let data: &[u8] = cursor.get_ref();
+        let mut starts = Vec::new();
+        let mut removed = 0;
+        for index in 0..count {
+            state.budget.count_node()?;
+            let entry_start = cursor.position() as usize;
+            let data_type = cursor.read_u8()?;
+            let name_length = cursor.read_u8()? as usize;
+            if data_type == clx_types::DEPRECATED || data_type == clx_types::UNKNOWN {
+                return Err(Nd2Error::file_invalid_format(format!(
+                    "Unknown data type in metadata header: {}",
+                    data_type as i8
+                )));
+            }
+            if data_type as i8 == -1 {
+                out.extend_from_slice(&data[entry_start..cursor.position() as usize]);
+                break;
+            }
+            starts.push(out.len());
+            if data_type == clx_types::COMPRESS {
+                cursor.set_position(cursor.position().saturating_add(10));
+                let remaining = remaining_len(cursor) as usize;
+                let inflated = state.budget.decompress_zlib(take(cursor, remaining)?)?;
+                self.rewrite_data(&inflated, depth, state, out)?;
+                return Ok((starts, removed));
+            }
+
+            let name = Utf16Str::trim_nul(take(cursor, name_length * 2)?);
+            let header_end = cursor.position() as usize;
+            state.path.push(name.to_string());
+            let remove = state.removing == 0 && state.edit.remove(&state.path, index);
+            state.path.pop();
+            if remove {
+                starts.pop();
+                removed += 1;
+            }
+            let value = match data_type {
+                clx_types::BOOL => ClxValueRef::Bool(cursor.read_u8()? != 0),
+                clx_types::INT32 => ClxValueRef::Int(cursor.read_i32::<LittleEndian>()? as i64),
+                clx_types::UINT32 => ClxValueRef::UInt(cursor.read_u32::<LittleEndian>()? as u64),
+                clx_types::INT64 => ClxValueRef::Int(cursor.read_i64::<LittleEndian>()?),
+                clx_types::UINT64 => ClxValueRef::UInt(cursor.read_u64::<LittleEndian>()?),
+                clx_types::DOUBLE => ClxValueRef::Float(cursor.read_f64::<LittleEndian>()?),
+                clx_types::VOID_POINTER => ClxValueRef::UInt(cursor.read_u64::<LittleEndian>()?),
+                clx_types::STRING => ClxValueRef::String(read_utf16_str(cursor)?),
+                clx_types::BYTE_ARRAY => {
+                    let size = cursor.read_u64::<LittleEndian>()?;
+                    let remaining = remaining_len(cursor);
+                    if size > remaining {
+                        return Err(Nd2Error::file_invalid_format(format!(
+                            "CLX byte array of {} bytes exceeds remaining {} bytes",
+                            size, remaining
+                        )));
+                    }
+                    ClxValueRef::ByteArray(take(cursor, size as usize)?)
+                }
+                clx_types::LEVEL => {
+                    state.path.push(name.to_string());
+                    let level = &data[entry_start..header_end];
+                    let result = if remove {
+                        state.removing += 1;
+                        let result =
+                            self.rewrite_level(cursor, level, depth, state, &mut Vec::new());
+                        state.removing -= 1;
+                        result
+                    } else {
+                        self.rewrite_level(cursor, level, depth, state, out)
+                    };
+                    state.path.pop();
+                    result?;
+                    continue;
+                }
+                other => match level_end {
+                    // Unknown length: the rest of the level is kept as is.
+                    Some(end) if self.lenient && end >= cursor.position() => {
+                        if remove {
+                            removed -= 1;
+                            starts.push(out.len());
+                        }
+                        out.extend_from_slice(&data[entry_start..end as usize]);
+                        cursor.set_position(end);
+                        break;
+                    }
+                    _ => return Err(Nd2Error::unsupported_clx_type(other)),
+                },
+            };
+
+            if remove {
+                continue;
+            }
+            if state.removing > 0 {
+                out.extend_from_slice(&data[entry_start..cursor.position() as usize]);
+                continue;
+            }
+            state.path.push(name.to_string());
+            let replacement = state.edit.replace(&state.path, &value);
+            state.path.pop();
+            match replacement? {
+                Some(value) => {
+                    out.extend_from_slice(&data[entry_start..header_end]);
+                    encode_value_as(data_type, &value, out)?;
+                }
+                None => out.extend_from_slice(&data[entry_start..cursor.position() as usize]),
+            }
+        }
+        Ok((starts, removed))
+    }
+
+    /// Rewrite the level entry whose type, name length and name are
+    /// `header`, with the cursor after them.
+    fn rewrite_level(
+        &self,
+        cursor: &mut Cursor<&[u8]>,
+        header: &[u8],
+        depth: usize,
+        state: &mut RewriteState<'_>,
+        out: &mut Vec<u8>,
+    ) -> Result<()> {
+        let data: &[u8] = cursor.get_ref();
+        let entry_start = cursor.position() as usize - header.len();
+        let item_count = cursor.read_u32::<LittleEndian>()? as usize;
+        let length = cursor.read_u64::<LittleEndian>()?;
+        let items_end = level_end(cursor, entry_start as u64, length);
+        let items_start = cursor.position() as usize;
+
+        let mut body = Vec::new();
+        let (mut starts, removed) =
+            self.rewrite_with_count(cursor, item_count, items_end, depth + 1, state, &mut body)?;
+        let unchanged = body == data[items_start..cursor.position() as usize];
+        // Skip the item_count * 8 bytes of offset data
+        let table_len = (item_count as u64)
+            .saturating_mul(8)
+            .min(remaining_len(cursor));
+        take(cursor, table_len as usize)?;
+
+        let appended = if state.removing == 0 {
+            state.edit.append(&state.path)
+        } else {
+            Vec::new()
+        };
+        if unchanged && appended.is_empty() {
+            out.extend_from_slice(&data[entry_start..cursor.position() as usize]);
+            return Ok(());
+        }
+        for (name, value) in &appended {
+            starts.push(body.len());
+            encode_entry(name, value, &mut body)?;
+        }
+        out.extend_from_slice(header);
+        put_level(
+            out,
+            header.len(),
+            item_count - removed + appended.len(),
+            &starts,
+            &body,
+        )
+    }
+}
+
+/// State of one rewrite.
+struct RewriteState<'e> {
+    edit: &'e mut dyn ClxEdit,
+    budget: Budget,
+    /// Names of the levels being rewritten.
+    path: Vec<String>,
+    /// Removed levels being walked past, whose entries `edit` is not asked
+    /// about.
+    removing: usize,
+}
+
+/// Encode `value` as a CLX Lite entry named `name`. Integers are written as
+/// 64-bit, objects and arrays as levels.
+pub(crate) fn encode_entry(name: &str, value: &ClxValue, out: &mut Vec<u8>) -> Result<()> {
+    let data_type = match value {
+        ClxValue::Bool(_) => clx_types::BOOL,
+        ClxValue::Int(_) => clx_types::INT64,
+        ClxValue::UInt(_) => clx_types::UINT64,
+        ClxValue::Float(_) => clx_types::DOUBLE,
+        ClxValue::String(_) => clx_types::STRING,
+        ClxValue::ByteArray(_) => clx_types::BYTE_ARRAY,
+        ClxValue::Object(_) | ClxValue::Array(_) => clx_types::LEVEL,
+    };
+    let units: Vec<u16> = name.encode_utf16().chain(std::iter::once(0)).collect();
+    let name_length = u8::try_from(units.len()).map_err(|_| {
+        Nd2Error::input_argument("name", format!("CLX entry name '{}' is too long", name))
+    })?;
+    let header_len = 2 + units.len() * 2;
+    out.push(data_type);
+    out.push(name_length);
+    out.extend(units.iter().flat_map(|unit| unit.to_le_bytes()));
+
+    let items: Vec<(&str, &ClxValue)> = match value {
+        ClxValue::Object(entries) => entries.iter().map(|(k, v)| (&**k, v)).collect(),
+        ClxValue::Array(items) => items.iter().map(|v| ("", v)).collect(),
+        value => return encode_value_as(data_type, value, out),
+    };
+    let mut body = Vec::new();
+    let mut starts = Vec::with_capacity(items.len());
+    for (name, value) in items {
+        starts.push(body.len());
+        encode_entry(name, value, &mut body)?;
+    }
+    put_level(out, header_len, starts.len(), &starts, &body)
+}
+
+/// Write the rest of a level entry whose header (type, name length and
+/// name) is `header_len` bytes: the item count, the length, which counts
+/// from the entry start to the end of the items, the items in `body`, then
+/// the offset table of the `count` items starting at `starts` in `body`,
+/// relative to the entry start.
+fn put_level(
+    out: &mut Vec<u8>,
+    header_len: usize,
+    count: usize,
+    starts: &[usize],
+    body: &[u8],
+) -> Result<()> {
+    let count =
+        u32::try_from(count).map_err(|_| Nd2Error::internal_overflow("CLX level item count"))?;
+    let items_start = header_len + 12;
+    out.extend_from_slice(&count.to_le_bytes());
+    out.extend_from_slice(&((items_start + body.len()) as u64).to_le_bytes());
+    out.extend_from_slice(body);
+    for i in 0..count as usize {
+        let offset = starts.get(i).map_or(0, |start| items_start + start);
+        out.extend_from_slice(&(offset as u64).to_le_bytes());
+    }
+    Ok(())
+}
+
+/// Encode `value` as the value of an entry of `data_type`, converting
+/// numbers that fit.
+fn encode_value_as(data_type: u8, value: &ClxValue, out: &mut Vec<u8>) -> Result<()> {
+    let mismatch = || {
+        Nd2Error::input_argument(
+            "value",
+            format!("{:?} does not fit a CLX entry of type {}", value, data_type),
+        )
+    };
+    let integer = match *value {
+        ClxValue::Int(v) => Some(v as i128),
+        ClxValue::UInt(v) => Some(v as i128),
+        _ => None,
+    };
+    match (data_type, value) {
+        (clx_types::BOOL, ClxValue::Bool(v)) => out.push(*v as u8),
+        (clx_types::INT32, _) => {
+            let v = integer
+                .and_then(|v| i32::try_from(v).ok())
+                .ok_or_else(mismatch)?;
+            out.extend_from_slice(&v.to_le_bytes());
+        }
+        (clx_types::UINT32, _) => {
+            let v = integer
+                .and_then(|v| u32::try_from(v).ok())
+                .ok_or_else(mismatch)?;
+            out.extend_from_slice(&v.to_le_bytes());
+        }
+        (clx_types::INT64, _) => {
+            let v = integer
+                .and_then(|v| i64::try_from(v).ok())
+                .ok_or_else(mismatch)?;
+            out.extend_from_slice(&v.to_le_bytes());
+        }
+        (clx_types::UINT64 | clx_types::VOID_POINTER, _) => {
+            let v = integer
+                .and_then(|v| u64::try_from(v).ok())
+                .ok_or_else(mismatch)?;
+            out.extend_from_slice(&v.to_le_bytes());
+        }
+        (clx_types::DOUBLE, _) => {
+            let v = value
+                .as_f64()
+                .or(integer.map(|v| v as f64))
+                .ok_or_else(mismatch)?;
+            out.extend_from_slice(&v.to_le_bytes());
+        }
+        (clx_types::STRING, ClxValue::String(v)) => {
+            if v.contains('\0') {
+                return Err(mismatch());
+            }
+            out.extend(
+                v.encode_utf16()
+                    .chain(std::iter::once(0))
+                    .flat_map(|unit| unit.to_le_bytes()),
+            );
+        }
+        (clx_types::BYTE_ARRAY, ClxValue::ByteArray(v)) => {
+            out.extend_from_slice(&(v.len() as u64).to_le_bytes());
+            out.extend_from_slice(v);
+        }
+        _ => return Err(mismatch()),
+    }
+    Ok(())
+}
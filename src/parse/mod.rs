@@ -1,3 +1,10 @@
+mod clx_json;
 pub mod clx_lite;
+pub mod clx_ref;
+pub mod clx_visit;
+mod clx_write;
 
 pub use clx_lite::*;
+pub use clx_ref::*;
+pub use clx_visit::*;
+pub(crate) use clx_write::{encode_entry, ClxEdit};
@@ -0,0 +1,197 @@
+//! Corrected copies of files with metadata overrides.
+
+use std::collections::BTreeSet;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+
+use crate::chunk::ChunkWriter;
+use crate::constants::ND2_FILE_SIGNATURE;
+use crate::error::{Nd2Error, Result};
+use crate::io::same_file;
+use crate::meta_parse::{TextInfoEdit, TEXT_INFO_LEVEL};
+use crate::parse::{encode_entry, ClxEdit, ClxLiteParser, ClxValue, ClxValueRef};
+use crate::reader::Nd2File;
+use crate::types::{MetadataPatch, TextInfo};
+
+/// Copy the file at `src` to `dst` with the metadata overrides of `patch`,
+/// such as the objective picked by mistake at acquisition time.
+///
+/// Text info fields are set as by [`edit_text_info`](crate::edit_text_info).
+/// Channel names, the pixel calibration and the objective are replaced in
+/// the picture metadata, wherever it stores them; an override the file has
+/// no entry for is an error rather than a silent no-op. Frames and other
+/// chunks are copied byte for byte.
+pub fn apply_metadata_patch<P: AsRef<Path>, Q: AsRef<Path>>(
+    src: P,
+    dst: Q,
+    patch: &MetadataPatch,
+) -> Result<()> {
+    if same_file(src.as_ref(), dst.as_ref()) {
+        return Err(Nd2Error::input_argument(
+            "dst",
+            "patched copy would overwrite its source",
+        ));
+    }
+    let mut nd2 = Nd2File::open(src.as_ref())?;
+    if nd2.is_legacy() {
+        let (major, minor) = nd2.version();
+        return Err(Nd2Error::unsupported_version(major, minor));
+    }
+    let n_channels = nd2.n_channels()?;
+    if let Some((&index, _)) = patch.channel_names.range(n_channels..).next() {
+        return Err(Nd2Error::input_out_of_range(
+            "channel index",
+            index,
+            n_channels,
+        ));
+    }
+    let picture_name = nd2.picture_metadata_chunk_name();
+    if patch.touches_picture() && nd2.chunk_location(picture_name).is_none() {
+        return Err(Nd2Error::file_metadata(
+            "File has no picture metadata to patch".to_string(),
+        ));
+    }
+    let text_info_name = nd2.text_info_chunk_name();
+    let text_info_found = nd2.chunk_location(text_info_name).is_some();
+    let patch_text_info = patch.text_info != TextInfo::default();
+    let parser = ClxLiteParser::new(false).limits(nd2.options().limits);
+    let mut source = File::open(src.as_ref())?;
+    let mut writer = ChunkWriter::new(BufWriter::new(File::create(dst.as_ref())?));
+    writer.copy_chunk(&mut source, ND2_FILE_SIGNATURE, 0)?;
+
+    for (name, offset, _) in nd2.chunk_entries() {
+        if name == text_info_name && patch_text_info {
+            let mut edit = TextInfoEdit::new(&patch.text_info);
+            let data = parser.rewrite(&nd2.read_chunk(&name)?, &mut edit)?;
+            if !edit.is_complete() {
+                return Err(Nd2Error::file_metadata(format!(
+                    "Text info chunk has no {} level to add fields to",
+                    TEXT_INFO_LEVEL
+                )));
+            }
+            writer.write_chunk(&name, &data)?;
+        } else if name == picture_name && patch.touches_picture() {
+            let mut edit = PictureEdit::new(patch);
+            let data = parser.rewrite(&nd2.read_chunk(&name)?, &mut edit)?;
+            edit.check()?;
+            writer.write_chunk(&name, &data)?;
+        } else {
+            writer.copy_chunk(&mut source, &name, offset)?;
+        }
+    }
+    if patch_text_info && !text_info_found {
+        let mut data = Vec::new();
+        encode_entry(
+            TEXT_INFO_LEVEL,
+            &TextInfoEdit::new(&patch.text_info).level(),
+            &mut data,
+        )?;
+        writer.write_chunk(text_info_name, &data)?;
+    }
+    writer
+        .finish()?
+        .into_inner()
+        .map_err(|err| err.into_error())?;
+    Ok(())
+}
+
+/// Replaces channel names, calibration and objective entries of the
+/// picture metadata.
+struct PictureEdit<'a> {
+    patch: &'a MetadataPatch,
+    /// Channels whose name was written.
+    named: BTreeSet<usize>,
+    /// Names of the other entries written.
+    written: BTreeSet<&'static str>,
+}
+
+impl<'a> PictureEdit<'a> {
+    fn new(patch: &'a MetadataPatch) -> Self {
+        Self {
+            patch,
+            named: BTreeSet::new(),
+            written: BTreeSet::new(),
+        }
+    }
+
+    /// Fail on the first override that found no entry.
+    fn check(&self) -> Result<()> {
+        if let Some(index) = self
+            .patch
+            .channel_names
+            .keys()
+            .find(|index| !self.named.contains(index))
+        {
+            return Err(Nd2Error::file_metadata(format!(
+                "Picture metadata has no plane {} to rename",
+                index
+            )));
+        }
+        let wanted = [
+            ("dCalibration", self.patch.calibration_um.is_some()),
+            ("wsObjectiveName", self.patch.objective_name.is_some()),
+            (
+                "dObjectiveMag",
+                self.patch.objective_magnification.is_some(),
+            ),
+            (
+                "dObjectiveNA",
+                self.patch.objective_numerical_aperture.is_some(),
+            ),
+        ];
+        if let Some((name, _)) = wanted
+            .iter()
+            .find(|(name, set)| *set && !self.written.contains(name))
+        {
+            return Err(Nd2Error::file_metadata(format!(
+                "Picture metadata has no {} entry to patch",
+                name
+            )));
+        }
+        Ok(())
+    }
+}
+
+impl ClxEdit for PictureEdit<'_> {
+    fn replace(&mut self, path: &[String], value: &ClxValueRef<'_>) -> Result<Option<ClxValue>> {
+        let names: Vec<&str> = path.iter().map(String::as_str).collect();
+        let patch = self.patch;
+        let (name, new) = match (names.as_slice(), value) {
+            ([.., "sPlaneNew" | "sPlane", plane, "sDescription"], ClxValueRef::String(_)) => {
+                let Some(index) = plane.strip_prefix('a').and_then(|i| i.parse().ok()) else {
+                    return Ok(None);
+                };
+                let Some(channel) = patch.channel_names.get(&index) else {
+                    return Ok(None);
+                };
+                self.named.insert(index);
+                return Ok(Some(ClxValue::String(channel.clone())));
+            }
+            ([.., "dCalibration"], ClxValueRef::Float(_)) => {
+                ("dCalibration", patch.calibration_um.map(ClxValue::Float))
+            }
+            ([.., "bCalibrated"], ClxValueRef::Bool(_)) => (
+                "bCalibrated",
+                patch.calibration_um.map(|_| ClxValue::Bool(true)),
+            ),
+            ([.., "wsObjectiveName"], ClxValueRef::String(_)) => (
+                "wsObjectiveName",
+                patch.objective_name.clone().map(ClxValue::String),
+            ),
+            ([.., "dObjectiveMag"], ClxValueRef::Float(_)) => (
+                "dObjectiveMag",
+                patch.objective_magnification.map(ClxValue::Float),
+            ),
+            ([.., "dObjectiveNA"], ClxValueRef::Float(_)) => (
+                "dObjectiveNA",
+                patch.objective_numerical_aperture.map(ClxValue::Float),
+            ),
+            _ => return Ok(None),
+        };
+        if new.is_some() {
+            self.written.insert(name);
+        }
+        Ok(new)
+    }
+}
@@ -0,0 +1,195 @@
+use crate::error::{Nd2Error, Result};
+use crate::types::PixelDataType;
+
+mod sealed {
+    pub trait Sealed {}
+    impl Sealed for u8 {}
+    impl Sealed for u16 {}
+    impl Sealed for u32 {}
+    impl Sealed for f32 {}
+}
+
+/// Pixel component types that frames can be decoded into.
+///
+/// Implemented for `u8`, `u16`, `u32` and `f32`. A type is accepted for a
+/// file when every stored value converts to it exactly: unsigned data can be
+/// widened (e.g. 8-bit into `u16`, up to 16-bit into `f32`), but never
+/// narrowed.
+pub trait Pixel: sealed::Sealed + Copy + Default + Send + Sync + 'static {
+    /// Type name used in error messages.
+    const NAME: &'static str;
+
+    /// Whether stored components with these attributes convert losslessly.
+    fn accepts(bits_in_memory: u32, data_type: PixelDataType) -> bool;
+
+    #[doc(hidden)]
+    fn from_u8(value: u8) -> Self;
+    #[doc(hidden)]
+    fn from_u16(value: u16) -> Self;
+    #[doc(hidden)]
+    fn from_u32(value: u32) -> Self;
+    #[doc(hidden)]
+    fn from_f32(value: f32) -> Self;
+}
+
+impl Pixel for u8 {
+    const NAME: &'static str = "u8";
+
+    fn accepts(bits_in_memory: u32, data_type: PixelDataType) -> bool {
+        data_type == PixelDataType::Unsigned && bits_in_memory <= 8
+    }
+
+    fn from_u8(value: u8) -> Self {
+        value
+    }
+    fn from_u16(value: u16) -> Self {
+        u8::try_from(value).unwrap_or(u8::MAX)
+    }
+    fn from_u32(value: u32) -> Self {
+        u8::try_from(value).unwrap_or(u8::MAX)
+    }
+    fn from_f32(value: f32) -> Self {
+        value as u8
+    }
+}
+
+impl Pixel for u16 {
+    const NAME: &'static str = "u16";
+
+    fn accepts(bits_in_memory: u32, data_type: PixelDataType) -> bool {
+        data_type == PixelDataType::Unsigned && bits_in_memory <= 16
+    }
+
+    fn from_u8(value: u8) -> Self {
+        value.into()
+    }
+    fn from_u16(value: u16) -> Self {
+        value
+    }
+    fn from_u32(value: u32) -> Self {
+        u16::try_from(value).unwrap_or(u16::MAX)
+    }
+    fn from_f32(value: f32) -> Self {
+        value as u16
+    }
+}
+
+impl Pixel for u32 {
+    const NAME: &'static str = "u32";
+
+    fn accepts(bits_in_memory: u32, data_type: PixelDataType) -> bool {
+        data_type == PixelDataType::Unsigned && bits_in_memory <= 32
+    }
+
+    fn from_u8(value: u8) -> Self {
+        value.into()
+    }
+    fn from_u16(value: u16) -> Self {
+        value.into()
+    }
+    fn from_u32(value: u32) -> Self {
+        value
+    }
+    fn from_f32(value: f32) -> Self {
+        value as u32
+    }
+}
+
+impl Pixel for f32 {
+    const NAME: &'static str = "f32";
+
+    fn accepts(bits_in_memory: u32, data_type: PixelDataType) -> bool {
+        match data_type {
+            PixelDataType::Unsigned => bits_in_memory <= 16,
+            PixelDataType::Float => bits_in_memory == 32,
+        }
+    }
+
+    fn from_u8(value: u8) -> Self {
+        value.into()
+    }
+    fn from_u16(value: u16) -> Self {
+        value.into()
+    }
+    fn from_u32(value: u32) -> Self {
+        value as f32
+    }
+    fn from_f32(value: f32) -> Self {
+        value
+    }
+}
+
+/// Frame components in the file's native type, as returned by
+/// [`Nd2File::read_frame_native`](crate::Nd2File::read_frame_native).
+#[derive(Debug, Clone, PartialEq)]
+pub enum PixelBuffer {
+    U8(Vec<u8>),
+    U16(Vec<u16>),
+    U32(Vec<u32>),
+    F32(Vec<f32>),
+}
+
+impl PixelBuffer {
+    /// Number of components.
+    pub fn len(&self) -> usize {
+        match self {
+            Self::U8(values) => values.len(),
+            Self::U16(values) => values.len(),
+            Self::U32(values) => values.len(),
+            Self::F32(values) => values.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Name of the component type, as in [`Pixel::NAME`].
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Self::U8(_) => u8::NAME,
+            Self::U16(_) => u16::NAME,
+            Self::U32(_) => u32::NAME,
+            Self::F32(_) => f32::NAME,
+        }
+    }
+}
+
+/// Name of the native component type for the given attributes.
+pub(crate) fn stored_type_name(bits_in_memory: u32, data_type: PixelDataType) -> String {
+    match data_type {
+        PixelDataType::Unsigned => format!("u{bits_in_memory}"),
+        PixelDataType::Float => format!("f{bits_in_memory}"),
+    }
+}
+
+/// Decode little-endian stored components into `T`.
+pub(crate) fn decode_components<T: Pixel>(
+    bytes: &[u8],
+    bytes_per_component: usize,
+    data_type: PixelDataType,
+) -> Result<Vec<T>> {
+    let values = match (data_type, bytes_per_component) {
+        (PixelDataType::Unsigned, 1) => bytes.iter().map(|&b| T::from_u8(b)).collect(),
+        (PixelDataType::Unsigned, 2) => bytes
+            .chunks_exact(2)
+            .map(|c| T::from_u16(u16::from_le_bytes([c[0], c[1]])))
+            .collect(),
+        (PixelDataType::Unsigned, 4) => bytes
+            .chunks_exact(4)
+            .map(|c| T::from_u32(u32::from_le_bytes([c[0], c[1], c[2], c[3]])))
+            .collect(),
+        (PixelDataType::Float, 4) => bytes
+            .chunks_exact(4)
+            .map(|c| T::from_f32(f32::from_le_bytes([c[0], c[1], c[2], c[3]])))
+            .collect(),
+        _ => {
+            return Err(Nd2Error::file_invalid_format(format!(
+                "Unsupported pixel layout: {} with {} bytes per component",
+                stored_type_name((bytes_per_component * 8) as u32, data_type),
+                bytes_per_component
+            )))
+        }
+    };
+    Ok(values)
+}
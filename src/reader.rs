@@ -1,35 +1,106 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
 use std::fs::File;
 use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::ops::Range;
 use std::path::Path;
+use std::sync::{Arc, OnceLock};
 
-use flate2::read::ZlibDecoder;
-
-use crate::chunk::{read_chunk, read_chunkmap, ChunkMap};
+use crate::checksum::Crc32c;
+use crate::chunk::{read_chunk_span, ChunkHeader, ChunkIndex};
 use crate::constants::{JP2_MAGIC, ND2_CHUNK_MAGIC, ND2_FILE_SIGNATURE};
+use crate::decode::{DecodePool, FrameGeometry, FramePayload, Scratch};
 use crate::error::{Nd2Error, Result};
-use crate::meta_parse::{parse_attributes, parse_experiment};
-use crate::parse::ClxLiteParser;
-use crate::types::{Attributes, CompressionType, DatasetSummary, ExpLoop, SummaryChannel};
+use crate::frame::{Frame, FrameCoord, FrameIndex, FrameMetadata};
+use crate::frame_reader::{
+    frame_read_error, image_chunk_payload_offset, locate_frame, read_frame_span, FrameReader,
+    FrameSpan, FrameSpans,
+};
+use crate::layout::{FrameOrder, StackOrder};
+use crate::legacy::{LegacyLayout, LegacyView};
+use crate::meta_parse::{
+    decode_binary_mask, parse_attributes, parse_binary_layers, parse_custom_tags, parse_events,
+    parse_experiment, parse_metadata, parse_rois, parse_text_info, parse_xy_positions,
+};
+use crate::parse::{ClxLiteParser, ClxValue};
+use crate::pixel::{stored_type_name, Pixel, PixelBuffer};
+use crate::types::{
+    Attributes, BinaryLayer, ChunkEntry, ChunkListing, CompressionType, DatasetSummary, Diagnostic,
+    DiagnosticKind, ExpLoop, ExperimentEvent, FrameCounts, Metadata, NapariLayer, Nd2Snapshot,
+    PixelDataType, Position, RecordedValue, Roi, StagePosition, SummaryChannel, TextInfo,
+    VoxelSize, XYPosLoopParams,
+};
 
 /// Axis names matching nd2-py AXIS
-const AXIS_T: &str = "T";
-const AXIS_P: &str = "P";
-const AXIS_C: &str = "C";
+pub(crate) const AXIS_T: &str = "T";
+pub(crate) const AXIS_P: &str = "P";
+pub(crate) const AXIS_C: &str = "C";
 const AXIS_Z: &str = "Z";
 const AXIS_Y: &str = "Y";
 const AXIS_X: &str = "X";
 
-use crate::io::ReadSeek;
+/// Chunks NIS Elements stores experiment events in, by preference; the
+/// second is written by its LV (variant) serializer.
+const EVENTS_CHUNKS: [&[u8]; 2] = [
+    b"CustomData|ExperimentEventsV1_0!",
+    b"CustomDataVar|ExperimentEventsV1_0!",
+];
+
+use crate::io::{PositionalReader, ReadSeek, POSITIONAL_READS};
+use crate::options::{Nd2Options, ReadStrategy};
 
 /// Main reader for ND2 files
 pub struct Nd2File {
-    reader: BufReader<Box<dyn ReadSeek>>,
+    reader: Box<dyn ReadSeek>,
+    /// The underlying file when reads are positional, so frame chunks can
+    /// also be read from worker threads.
+    shared_file: Option<Arc<File>>,
+    /// Ring for batched frame reads of `shared_file`, with
+    /// [`ReadStrategy::IoUring`].
+    #[cfg(all(feature = "io-uring", target_os = "linux"))]
+    uring: Option<crate::uring::UringReader>,
     version: (u32, u32),
-    chunkmap: ChunkMap,
+    chunks: ChunkIndex,
+    options: Nd2Options,
+    diagnostics: Vec<Diagnostic>,
+    /// Frames skipped by bulk reads, with [`Nd2Options::skip_bad_frames`].
+    bad_frames: BTreeSet<usize>,
+    /// Frame chunk buffers handed back after decoding, refilled by later
+    /// reads. Holds at most one bulk-read batch.
+    payload_buffers: Vec<Vec<u8>>,
+    /// Scratch buffers of the decode workers, handed back after each bulk
+    /// read for the next one.
+    scratch_buffers: Vec<Scratch>,
+    /// Frame chunk header layout confirmed so far, so later reads go
+    /// straight to the bytes the chunkmap lists.
+    frame_spans: FrameSpans,
+    /// Buffer for coalesced reads of adjacent frame chunks.
+    run_buffer: Vec<u8>,
+    /// Inflated frames on disk, with [`Nd2Options::frame_cache`].
+    #[cfg(feature = "frame-cache")]
+    frame_cache: Option<crate::frame_cache::FrameCache>,
+    /// [`crate::frame_cache::file_identity`] of the opened file, mixed into
+    /// the frame cache key.
+    #[cfg(feature = "frame-cache")]
+    source_identity: Option<u64>,
+    /// The whole file, when opened by [`Nd2File::open_mmap`].
+    #[cfg(feature = "mmap")]
+    map: Option<crate::io::SharedMap>,
     // Cached metadata
     attributes: Option<Attributes>,
     experiment: Option<Vec<ExpLoop>>,
+    metadata: Option<Metadata>,
+    frame_layout: Option<FrameLayout>,
+}
+
+/// The frame index and the dimensions derived with it, built and dropped
+/// together so frame reads look them up instead of re-deriving them from the
+/// attributes and experiment loops.
+struct FrameLayout {
+    index: FrameIndex,
+    /// What [`Nd2File::sizes`] returns: declared sizes, with loop lengths
+    /// taken from `index`.
+    sizes: HashMap<String, usize>,
 }
 
 impl Nd2File {
@@ -38,12 +109,75 @@ impl Nd2File {
     where
         R: Read + Seek + 'static,
     {
-        Self::open_buffered(BufReader::new(Box::new(reader)))
+        Self::open_reader_with(reader, Nd2Options::default())
+    }
+
+    /// Open an ND2 file from any [`Read`] + [`Seek`] source with explicit options.
+    pub fn open_reader_with<R>(reader: R, options: Nd2Options) -> Result<Self>
+    where
+        R: Read + Seek + 'static,
+    {
+        Self::open_source(Box::new(reader), true, None, options)
+    }
+
+    /// Open an ND2 file held entirely in memory (e.g. a browser `Blob` read
+    /// into an `ArrayBuffer`, or a downloaded object).
+    pub fn from_bytes<B>(bytes: B) -> Result<Self>
+    where
+        B: AsRef<[u8]> + 'static,
+    {
+        Self::open_source(
+            Box::new(std::io::Cursor::new(bytes)),
+            false,
+            None,
+            Nd2Options::default(),
+        )
     }
 
     /// Open an ND2 file for reading from a local path.
     pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
-        Self::open_reader(File::open(path)?)
+        Self::open_with(path, Nd2Options::default())
+    }
+
+    /// Open an ND2 file from a local path with explicit options.
+    pub fn open_with<P: AsRef<Path>>(path: P, options: Nd2Options) -> Result<Self> {
+        let file = crate::io::open_shared(path.as_ref(), options.share_mode)?;
+        #[cfg(feature = "frame-cache")]
+        let identity = crate::frame_cache::file_identity(Some(path.as_ref()), &file);
+        let nd2 = Self::open_file_source(file, options)?;
+        #[cfg(feature = "frame-cache")]
+        let nd2 = nd2.with_frame_cache(identity);
+        Ok(nd2)
+    }
+
+    /// Open an ND2 file from an already-open [`File`] handle.
+    ///
+    /// The handle is read from its start regardless of its current position.
+    pub fn open_file(file: File) -> Result<Self> {
+        Self::open_file_with(file, Nd2Options::default())
+    }
+
+    /// Open an ND2 file from an already-open [`File`] handle with explicit
+    /// options.
+    ///
+    /// Unless [`ReadStrategy::Buffered`] is requested, the file is read with
+    /// positional reads and its cursor is left untouched.
+    pub fn open_file_with(file: File, options: Nd2Options) -> Result<Self> {
+        #[cfg(feature = "frame-cache")]
+        let identity = crate::frame_cache::file_identity(None, &file);
+        let nd2 = Self::open_file_source(file, options)?;
+        #[cfg(feature = "frame-cache")]
+        let nd2 = nd2.with_frame_cache(identity);
+        Ok(nd2)
+    }
+
+    fn open_file_source(file: File, options: Nd2Options) -> Result<Self> {
+        if options.read_strategy == ReadStrategy::Buffered || !POSITIONAL_READS {
+            return Self::open_reader_with(file, options);
+        }
+        let file = Arc::new(file);
+        let reader = Box::new(PositionalReader::new(Arc::clone(&file)));
+        Self::open_source(reader, false, Some(file), options)
     }
 
     #[cfg(feature = "smb")]
@@ -51,87 +185,827 @@ impl Nd2File {
     ///
     /// Requires `mdat_smb_rs::register_provider` before calling.
     pub fn open_smb(path: &str) -> Result<Self> {
-        let reader =
-            mdat_smb_rs::open_path(path).map_err(|message| Nd2Error::file_invalid_format(message))?;
+        let reader = mdat_smb_rs::open_path(path)
+            .map_err(|message| Nd2Error::file_invalid_format(message))?;
         Self::open_reader(reader)
     }
 
-    fn open_buffered(mut reader: BufReader<Box<dyn ReadSeek>>) -> Result<Self> {
-        let version = Self::read_version(&mut reader)?;
-        if version.0 < 2 || version.0 > 3 {
+    #[cfg(feature = "mmap")]
+    /// Open an ND2 file through a read-only memory map of the whole file.
+    ///
+    /// When the whole file cannot be mapped (files larger than the address
+    /// space on 32-bit targets, filesystems without mmap support), falls
+    /// back to [`Nd2File::open_mmap_footer`].
+    ///
+    /// Chunks and uncompressed frames can then be borrowed from the map
+    /// without copying, with [`Nd2File::mapped_chunk`] and
+    /// [`Nd2File::mapped_frame`].
+    ///
+    /// # Safety
+    ///
+    /// The file must not be truncated or written to, by this or any other
+    /// process, while the returned reader or anything borrowed from it
+    /// lives. Reading a mapped page that a writer changed or truncated
+    /// away is undefined behavior (or a `SIGBUS`), which no check in this
+    /// crate can prevent. Acquisitions still being written by NIS
+    /// Elements therefore must not be mapped; open them with
+    /// [`Nd2File::open`] and poll with [`Nd2File::refresh`] instead.
+    pub unsafe fn open_mmap<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let file = File::open(path)?;
+        // SAFETY: the caller guarantees that the file is not modified or
+        // truncated while the map, owned by the returned reader, lives.
+        match unsafe { memmap2::Mmap::map(&file) } {
+            Ok(mmap) => {
+                let map = crate::io::SharedMap(Arc::new(mmap));
+                let mut nd2 = Self::open_source(
+                    Box::new(std::io::Cursor::new(map.clone())),
+                    false,
+                    None,
+                    Nd2Options::default(),
+                )?;
+                // Legacy files are read through a view with other offsets.
+                if !nd2.is_legacy() {
+                    nd2.map = Some(map);
+                }
+                Ok(nd2)
+            }
+            // SAFETY: upheld by the caller as above.
+            Err(_) => unsafe { Self::open_mmap_footer_file(file) },
+        }
+    }
+
+    #[cfg(feature = "mmap")]
+    /// Open an ND2 file with only the region after its last frame chunk,
+    /// where the metadata chunks and the chunkmap live, memory-mapped.
+    /// Frame data is read from the file with positional reads.
+    ///
+    /// If even that region cannot be mapped, the file is read as by
+    /// [`Nd2File::open_file_with`] with [`ReadStrategy::Direct`].
+    ///
+    /// # Safety
+    ///
+    /// As for [`Nd2File::open_mmap`]: the file must not be truncated or
+    /// written to while the returned reader lives. The mapped region holds
+    /// the chunkmap, which NIS Elements rewrites while it is still
+    /// acquiring, so files being written must not be opened this way.
+    pub unsafe fn open_mmap_footer<P: AsRef<Path>>(path: P) -> Result<Self> {
+        // SAFETY: upheld by the caller.
+        unsafe { Self::open_mmap_footer_file(File::open(path)?) }
+    }
+
+    #[cfg(feature = "mmap")]
+    /// # Safety
+    ///
+    /// See [`Nd2File::open_mmap_footer`].
+    unsafe fn open_mmap_footer_file(file: File) -> Result<Self> {
+        let options = Nd2Options::default().read_strategy(ReadStrategy::Direct);
+        let mut nd2 = Self::open_file_with(file, options)?;
+        if let Some(file) = nd2.shared_file.clone() {
+            let start = nd2.chunks.frames_end();
+            // SAFETY: upheld by the caller.
+            if let Ok(reader) = unsafe { crate::io::TailMappedReader::new(file, start) } {
+                nd2.reader = Box::new(reader);
+            }
+        }
+        Ok(nd2)
+    }
+
+    /// Open `source`, wrapping it in a `BufReader` when the read strategy
+    /// asks for one (or, for [`ReadStrategy::Auto`], when `buffer_by_default`).
+    fn open_source(
+        source: Box<dyn ReadSeek>,
+        buffer_by_default: bool,
+        mut shared_file: Option<Arc<File>>,
+        options: Nd2Options,
+    ) -> Result<Self> {
+        let buffered = match options.read_strategy {
+            ReadStrategy::Auto => buffer_by_default,
+            ReadStrategy::Buffered => true,
+            ReadStrategy::Direct => false,
+            #[cfg(feature = "io-uring")]
+            ReadStrategy::IoUring => false,
+        };
+        let mut reader: Box<dyn ReadSeek> = if buffered {
+            Box::new(BufReader::with_capacity(options.buffer_capacity, source))
+        } else {
+            source
+        };
+        let mut version = Self::read_version(&mut reader)?;
+        let mut diagnostics = Vec::new();
+        // Converted or re-saved files can start with a JPEG2000 container
+        // and still hold a modern chunk layer; read that one when found.
+        let mut modern_layer = None;
+        if version.0 < 2 {
+            let mut layer_diagnostics = Vec::new();
+            let layer = ChunkIndex::read(
+                &mut reader,
+                options.limits.max_chunk_bytes,
+                &mut layer_diagnostics,
+            )
+            .ok()
+            .and_then(|layer| Some((modern_layer_version(&layer)?, layer)));
+            if let Some((layer_version, layer)) = layer {
+                diagnostics.push(Diagnostic::new(
+                    DiagnosticKind::LegacyContainer,
+                    format!(
+                        "File starts with a legacy JPEG2000 container; read through its \
+                         modern chunk layer as version {}.{}",
+                        layer_version.0, layer_version.1
+                    ),
+                ));
+                diagnostics.append(&mut layer_diagnostics);
+                version = layer_version;
+                modern_layer = Some(layer);
+            }
+        }
+        // Otherwise read the codestreams and XML boxes themselves, through
+        // a view laid out as version 2 chunks.
+        let mut legacy = None;
+        if version.0 < 2 && modern_layer.is_none() {
+            legacy = LegacyLayout::scan(&mut reader, options.limits.max_chunk_bytes)?;
+        }
+        if version.0 > 3 && options.allow_unknown_version {
+            diagnostics.push(Diagnostic::new(
+                DiagnosticKind::UnknownVersion,
+                format!(
+                    "Format version {}.{} is newer than supported; read as 3.0, \
+                     metadata and frames may be incomplete or wrong",
+                    version.0, version.1
+                ),
+            ));
+        } else if (version.0 < 2 && legacy.is_none()) || version.0 > 3 {
             return Err(Nd2Error::unsupported_version(version.0, version.1));
         }
-        let chunkmap = read_chunkmap(&mut reader)?;
+        if let Some(layout) = legacy {
+            diagnostics.push(Diagnostic::new(
+                DiagnosticKind::LegacyContainer,
+                format!(
+                    "Legacy JPEG2000 file; read {} codestreams as lossy frames",
+                    layout.frame_count()
+                ),
+            ));
+            reader = Box::new(LegacyView::new(reader, &layout)?);
+            // Chunk offsets are in the view, not the file.
+            shared_file = None;
+        }
+        let chunks = match modern_layer.map_or_else(
+            || {
+                ChunkIndex::read(
+                    &mut reader,
+                    options.limits.max_chunk_bytes,
+                    &mut diagnostics,
+                )
+            },
+            Ok,
+        ) {
+            Ok(chunks) => chunks,
+            Err(err) if options.allow_recovery => {
+                let threads = options.decode_worker_count();
+                let chunks = ChunkIndex::recover(
+                    &mut reader,
+                    shared_file.as_deref(),
+                    threads,
+                    options.limits.max_chunk_bytes,
+                )?;
+                if chunks.len() == 0 {
+                    return Err(err);
+                }
+                diagnostics.push(Diagnostic::new(
+                    DiagnosticKind::ChunkmapRecovered,
+                    format!(
+                        "Chunkmap unreadable ({}); recovered {} chunks by scanning the file",
+                        err,
+                        chunks.len()
+                    ),
+                ));
+                chunks
+            }
+            Err(err) => return Err(err),
+        };
+        // Without a ring, reads stay positional.
+        #[cfg(all(feature = "io-uring", target_os = "linux"))]
+        let uring = match (&shared_file, options.read_strategy) {
+            (Some(_), ReadStrategy::IoUring) => crate::uring::UringReader::new().ok(),
+            _ => None,
+        };
         Ok(Self {
             reader,
+            shared_file,
+            #[cfg(all(feature = "io-uring", target_os = "linux"))]
+            uring,
             version,
-            chunkmap,
+            chunks,
+            options,
+            diagnostics,
+            bad_frames: BTreeSet::new(),
+            payload_buffers: Vec::new(),
+            scratch_buffers: Vec::new(),
+            frame_spans: FrameSpans::default(),
+            run_buffer: Vec::new(),
+            #[cfg(feature = "frame-cache")]
+            frame_cache: None,
+            #[cfg(feature = "frame-cache")]
+            source_identity: None,
+            #[cfg(feature = "mmap")]
+            map: None,
             attributes: None,
             experiment: None,
+            metadata: None,
+            frame_layout: None,
         })
     }
 
+    /// Options this file was opened with.
+    pub fn options(&self) -> &Nd2Options {
+        &self.options
+    }
+
     /// Get the file format version (major, minor)
     pub fn version(&self) -> (u32, u32) {
         self.version
     }
 
+    /// Names of the chunks in the chunkmap, in natural order (frame chunks
+    /// by sequence index), so listings compare between runs and files.
+    ///
+    /// Bytes that are not UTF-8 are shown as U+FFFD; such names are still
+    /// accepted by [`Nd2File::read_chunk`], and listed as stored by
+    /// [`Nd2File::chunk_names_raw`].
+    pub fn chunk_names(&self) -> Vec<String> {
+        self.chunks
+            .names()
+            .iter()
+            .map(|name| String::from_utf8_lossy(name).into_owned())
+            .collect()
+    }
+
+    /// Name, offset and size of every chunk in the chunkmap, in the order
+    /// of [`Nd2File::chunk_names`], as a versioned record for JSON or CSV.
+    pub fn chunk_listing(&self) -> ChunkListing {
+        let chunks = self
+            .chunks
+            .names()
+            .into_iter()
+            .filter_map(|name| {
+                let (offset, size) = self.chunks.get(&name)?;
+                Some(ChunkEntry {
+                    name: String::from_utf8_lossy(&name).into_owned(),
+                    offset,
+                    size,
+                })
+            })
+            .collect();
+        ChunkListing {
+            schema_version: ChunkListing::SCHEMA_VERSION,
+            chunks,
+        }
+    }
+
+    /// Names of the chunks in the chunkmap as stored, in the order of
+    /// [`Nd2File::chunk_names`].
+    pub fn chunk_names_raw(&self) -> Vec<Vec<u8>> {
+        self.chunks.names()
+    }
+
+    /// Read a chunk's data by name, either as stored (see
+    /// [`Nd2File::chunk_names_raw`]) or as listed by
+    /// [`Nd2File::chunk_names`]. A listed name that several stored names
+    /// display as is ambiguous and must be given as stored.
+    pub fn read_chunk(&mut self, name: impl AsRef<[u8]>) -> Result<Vec<u8>> {
+        let name = name.as_ref();
+        let stored = self
+            .chunks
+            .resolve(name)
+            .ok_or_else(|| Nd2Error::file_chunk_not_found(String::from_utf8_lossy(name)))?;
+        self.chunks.read_chunk(&mut self.reader, &stored)
+    }
+
+    #[cfg(feature = "mmap")]
+    /// Borrow a chunk's data, named as for [`Nd2File::read_chunk`], from
+    /// the memory map of a file opened with [`Nd2File::open_mmap`],
+    /// without copying it.
+    pub fn mapped_chunk(&self, name: impl AsRef<[u8]>) -> Result<&[u8]> {
+        let map = self.mapped()?;
+        let name = name.as_ref();
+        let stored = self
+            .chunks
+            .resolve(name)
+            .ok_or_else(|| Nd2Error::file_chunk_not_found(String::from_utf8_lossy(name)))?;
+        let (offset, map_size) = self
+            .chunks
+            .get(&stored)
+            .ok_or_else(|| Nd2Error::file_chunk_not_found(String::from_utf8_lossy(&stored)))?;
+        crate::chunk::chunk_data_at(map, &stored, offset, map_size)
+    }
+
+    #[cfg(feature = "mmap")]
+    /// Borrow frame `index`'s pixel rows as stored (little-endian,
+    /// components interleaved, rows padded as in the file) from the memory
+    /// map of a file opened with [`Nd2File::open_mmap`], without copying or
+    /// decoding them. Compressed frames have no such rows.
+    pub fn mapped_frame(&mut self, index: usize) -> Result<&[u8]> {
+        self.mapped()?;
+        let geometry = self.geometry()?;
+        if geometry.compressed {
+            return Err(Nd2Error::unsupported_compression(
+                "compressed frames cannot be borrowed from the memory map",
+            ));
+        }
+        let span = self
+            .frame_span(index, &geometry)
+            .map_err(|err| frame_read_error(err, index, geometry.sequence_count))?;
+        let FrameSpan::Raw { pixel_offset, .. } = span else {
+            return Err(Nd2Error::internal_invariant(
+                "uncompressed frame located as compressed",
+            ));
+        };
+        let start = usize::try_from(pixel_offset)
+            .map_err(|_| Nd2Error::internal_overflow("mapped frame offset"))?;
+        // The pixel rows were checked to end inside the file.
+        Ok(&self.mapped()?[start..start + geometry.expected_raw])
+    }
+
+    #[cfg(feature = "mmap")]
+    fn mapped(&self) -> Result<&[u8]> {
+        self.map.as_ref().map(AsRef::as_ref).ok_or_else(|| {
+            Nd2Error::input_argument("file", "not memory-mapped; open it with Nd2File::open_mmap")
+        })
+    }
+
+    /// Drop cached metadata; it is re-read from the file on next access.
+    pub fn clear_caches(&mut self) {
+        self.attributes = None;
+        self.experiment = None;
+        self.metadata = None;
+        self.frame_layout = None;
+        self.frame_spans = FrameSpans::default();
+    }
+
+    /// Re-read the chunkmap of a file that is still being written, so frames
+    /// completed since it was opened (or last refreshed) can be read.
+    ///
+    /// Cached metadata and the frame index are dropped and rebuilt on next
+    /// access, so [`Nd2File::n_frames`] and [`Nd2File::missing_frames`]
+    /// reflect the file as it is now. Returns whether the file changed:
+    /// its size or its number of chunks. When the chunkmap cannot be read
+    /// (for example while the writer is replacing it), the error is
+    /// returned and the file stays as it was, so polling can simply retry.
+    ///
+    /// Sources fixed when opened, such as in-memory buffers and memory
+    /// maps, never change. A [`FrameReader`] keeps the frames it was
+    /// created with; create a new one after refreshing.
+    pub fn refresh(&mut self) -> Result<bool> {
+        let mut diagnostics = Vec::new();
+        let chunks = ChunkIndex::read(
+            &mut self.reader,
+            self.options.limits.max_chunk_bytes,
+            &mut diagnostics,
+        )?;
+        let changed =
+            chunks.file_size() != self.chunks.file_size() || chunks.len() != self.chunks.len();
+        self.chunks = chunks;
+        #[cfg(feature = "frame-cache")]
+        {
+            self.frame_cache = self.frame_cache_for(self.source_identity);
+        }
+        self.clear_caches();
+        self.record_diagnostics(diagnostics);
+        Ok(changed)
+    }
+
+    /// Key the frame cache by the file's `identity` and its chunkmap. Files
+    /// that cannot be identified (read from memory or a generic reader) are
+    /// read without the cache, since two of them could share a chunkmap.
+    #[cfg(feature = "frame-cache")]
+    fn with_frame_cache(mut self, identity: Option<u64>) -> Self {
+        self.source_identity = identity;
+        self.frame_cache = self.frame_cache_for(identity);
+        self
+    }
+
+    #[cfg(feature = "frame-cache")]
+    fn frame_cache_for(&self, identity: Option<u64>) -> Option<crate::frame_cache::FrameCache> {
+        let dir = self.options.frame_cache_dir.as_deref()?;
+        let key = crate::frame_cache::fnv1a(self.chunks.fingerprint(), &identity?.to_le_bytes());
+        Some(crate::frame_cache::FrameCache::new(dir, key))
+    }
+
+    /// Whether metadata parsed from `chunk_name` may stay cached.
+    fn caches_chunk(&self, chunk_name: &[u8]) -> bool {
+        if !self.options.cache_metadata {
+            return false;
+        }
+        match (
+            self.options.max_cached_metadata_bytes,
+            self.chunks.get(chunk_name),
+        ) {
+            (Some(limit), Some((_, size))) => size <= limit,
+            _ => true,
+        }
+    }
+
+    pub(crate) fn attributes_chunk_name(&self) -> &'static [u8] {
+        if self.version.0 >= 3 {
+            b"ImageAttributesLV!"
+        } else {
+            b"ImageAttributes!"
+        }
+    }
+
+    pub(crate) fn experiment_chunk_name(&self) -> &'static [u8] {
+        if self.version.0 >= 3 {
+            b"ImageMetadataLV!"
+        } else {
+            b"ImageMetadata!"
+        }
+    }
+
+    pub(crate) fn text_info_chunk_name(&self) -> &'static [u8] {
+        if self.version.0 >= 3 {
+            b"ImageTextInfoLV!"
+        } else {
+            b"ImageTextInfo!"
+        }
+    }
+
+    pub(crate) fn picture_metadata_chunk_name(&self) -> &'static [u8] {
+        if self.version.0 >= 3 {
+            b"ImageMetadataSeqLV|0!"
+        } else {
+            b"ImageMetadataSeq|0!"
+        }
+    }
+
+    /// Name of the picture metadata chunk of frame `index`.
+    fn picture_metadata_seq_name(&self, index: usize) -> Vec<u8> {
+        let prefix = if self.version.0 >= 3 {
+            "ImageMetadataSeqLV"
+        } else {
+            "ImageMetadataSeq"
+        };
+        format!("{}|{}!", prefix, index).into_bytes()
+    }
+
     /// Get image attributes
-    fn attributes(&mut self) -> Result<&Attributes> {
-        if self.attributes.is_none() {
-            let chunk_name: &[u8] = if self.version.0 >= 3 {
-                b"ImageAttributesLV!"
-            } else {
-                b"ImageAttributes!"
-            };
-            let data = read_chunk(&mut self.reader, &self.chunkmap, chunk_name)?;
-            let parser = ClxLiteParser::new(false);
-            let clx = parser.parse(&data)?;
-            self.attributes = Some(parse_attributes(clx)?);
+    pub(crate) fn attributes(&mut self) -> Result<&Attributes> {
+        let chunk_name = self.attributes_chunk_name();
+        if !self.caches_chunk(chunk_name) {
+            self.attributes = None;
         }
-        Ok(self.attributes.as_ref().unwrap())
+        let attributes = match self.attributes.take() {
+            Some(attributes) => attributes,
+            None => {
+                let data = self.chunks.read_chunk(&mut self.reader, chunk_name)?;
+                let mut diagnostics = Vec::new();
+                let clx = ClxLiteParser::new(false)
+                    .lenient(true)
+                    .limits(self.options.limits)
+                    .parse_with_diagnostics(&data, &mut diagnostics)?;
+                self.record_diagnostics(diagnostics);
+                parse_attributes(clx)?
+            }
+        };
+        Ok(self.attributes.insert(attributes))
     }
 
     /// Get experiment loop definitions
-    fn experiment(&mut self) -> Result<&Vec<ExpLoop>> {
-        if self.experiment.is_none() {
-            let chunk_name: &[u8] = if self.version.0 >= 3 {
-                b"ImageMetadataLV!"
-            } else {
-                b"ImageMetadata!"
-            };
+    pub(crate) fn experiment(&mut self) -> Result<&Vec<ExpLoop>> {
+        if !self.caches_chunk(self.experiment_chunk_name()) {
+            self.experiment = None;
+        }
+        let experiment = match self.experiment.take() {
+            Some(experiment) => experiment,
+            None => self.load_experiment()?,
+        };
+        Ok(self.experiment.insert(experiment))
+    }
 
-            if !self.chunkmap.contains_key(chunk_name) {
-                self.experiment = Some(Vec::new());
-            } else {
-                let data = read_chunk(&mut self.reader, &self.chunkmap, chunk_name)?;
-                let parser = ClxLiteParser::new(false);
-                let clx = parser.parse(&data)?;
-                // v3 wraps in SLxExperiment; unwrap if present and is object
-                let to_parse = if self.version.0 >= 3 {
-                    match clx.as_object().and_then(|o| o.get("SLxExperiment")) {
-                        Some(inner) if inner.as_object().is_some() => inner.clone(),
-                        _ => clx.clone(),
-                    }
-                } else {
-                    clx.clone()
-                };
-                let mut exp = parse_experiment(to_parse).unwrap_or_default();
-                // If unwrapped gave empty, try parsing root directly (some v3 files differ)
-                if exp.is_empty() && self.version.0 >= 3 {
-                    exp = parse_experiment(clx).unwrap_or_default();
+    fn load_experiment(&mut self) -> Result<Vec<ExpLoop>> {
+        let chunk_name = self.experiment_chunk_name();
+
+        if !self.chunks.contains(chunk_name) {
+            return Ok(Vec::new());
+        }
+
+        let data = self.chunks.read_chunk(&mut self.reader, chunk_name)?;
+        let mut diagnostics = Vec::new();
+        let clx = ClxLiteParser::new(false)
+            .lenient(true)
+            .limits(self.options.limits)
+            .parse_with_diagnostics(&data, &mut diagnostics)?;
+        // v3 wraps in SLxExperiment; unwrap if present and is object
+        let to_parse = if self.version.0 >= 3 {
+            match clx.as_object().and_then(|o| o.get("SLxExperiment")) {
+                Some(inner) if inner.as_object().is_some() => inner,
+                _ => &clx,
+            }
+        } else {
+            &clx
+        };
+        let parsed = diagnostics.len();
+        let mut exp = Self::parse_experiment_lenient(to_parse, &mut diagnostics);
+        // If unwrapped gave empty, try parsing root directly (some v3 files differ)
+        if exp.is_empty() && self.version.0 >= 3 {
+            diagnostics.truncate(parsed);
+            exp = Self::parse_experiment_lenient(&clx, &mut diagnostics);
+        }
+        self.record_diagnostics(diagnostics);
+        Ok(exp)
+    }
+
+    /// Add `diagnostics` found while parsing metadata. Uncached metadata is
+    /// parsed repeatedly; each anomaly is reported once.
+    fn record_diagnostics(&mut self, diagnostics: Vec<Diagnostic>) {
+        for diagnostic in diagnostics {
+            if !self.diagnostics.contains(&diagnostic) {
+                self.diagnostics.push(diagnostic);
+            }
+        }
+    }
+
+    /// Record frame `index` as bad and return `Ok` when
+    /// [`Nd2Options::skip_bad_frames`] is set and `err` concerns the frame
+    /// itself (its chunk is missing, damaged or undecodable); otherwise
+    /// return `err`.
+    pub(crate) fn skip_bad_frame(&mut self, index: usize, err: Nd2Error) -> Result<()> {
+        if !self.options.skip_bad_frames || !(err.is_file() || err.is_input()) {
+            return Err(err);
+        }
+        self.bad_frames.insert(index);
+        self.record_diagnostics(vec![Diagnostic::new(
+            DiagnosticKind::BadFrame,
+            format!("Frame {} could not be read and was skipped: {}", index, err),
+        )]);
+        Ok(())
+    }
+
+    /// Per-channel metadata: names, colors, emission and excitation
+    /// wavelengths, objective and voxel calibration, from the picture
+    /// metadata of the first frame. `channels` is `None` when the file has
+    /// none.
+    pub fn metadata(&mut self) -> Result<&Metadata> {
+        if !self.caches_chunk(self.picture_metadata_chunk_name()) {
+            self.metadata = None;
+        }
+        let metadata = match self.metadata.take() {
+            Some(metadata) => metadata,
+            None => self.load_metadata(self.picture_metadata_chunk_name())?,
+        };
+        Ok(self.metadata.insert(metadata))
+    }
+
+    /// Size of a voxel in µm: X and Y from the first channel's axes
+    /// calibration, Z from the Z-stack step. Uncalibrated axes are 1.
+    pub fn voxel_size(&mut self) -> Result<VoxelSize> {
+        let z_step = self
+            .experiment()?
+            .iter()
+            .find_map(|loop_| match loop_ {
+                ExpLoop::ZStackLoop(z) => Some(z.parameters.step_um.abs()),
+                _ => None,
+            })
+            .filter(|step| step.is_finite() && *step > 0.0);
+        let (x_um, y_um, z_um) = self
+            .metadata()?
+            .channels
+            .as_ref()
+            .and_then(|channels| channels.first())
+            .map_or((1.0, 1.0, 1.0), |channel| channel.volume.axes_calibration);
+        Ok(VoxelSize {
+            x_um,
+            y_um,
+            z_um: z_step.unwrap_or(z_um),
+        })
+    }
+
+    fn load_metadata(&mut self, chunk_name: &[u8]) -> Result<Metadata> {
+        let clx = if self.chunks.contains(chunk_name) {
+            let data = self.chunks.read_chunk(&mut self.reader, chunk_name)?;
+            let mut diagnostics = Vec::new();
+            let clx = ClxLiteParser::new(false)
+                .lenient(true)
+                .limits(self.options.limits)
+                .parse_with_diagnostics(&data, &mut diagnostics)?;
+            self.record_diagnostics(diagnostics);
+            Some(clx)
+        } else {
+            None
+        };
+        let attributes = self.attributes()?.clone();
+        let experiment = self.experiment()?.clone();
+        Ok(parse_metadata(clx.as_ref(), &attributes, &experiment))
+    }
+
+    /// Parameters of the outermost XY position loop.
+    ///
+    /// Taken from the cached experiment loops when there are any; otherwise
+    /// the experiment chunk is walked without building its CLX tree, which
+    /// for long position lists is far smaller.
+    pub(crate) fn xy_positions(&mut self) -> Result<Option<XYPosLoopParams>> {
+        if let Some(experiment) = &self.experiment {
+            return Ok(experiment.iter().find_map(|loop_| match loop_ {
+                ExpLoop::XYPosLoop(xy) => Some(xy.parameters.clone()),
+                _ => None,
+            }));
+        }
+        let chunk_name = self.experiment_chunk_name();
+        if !self.chunks.contains(chunk_name) {
+            return Ok(None);
+        }
+        let data = self.chunks.read_chunk(&mut self.reader, chunk_name)?;
+        parse_xy_positions(&data, self.options.limits)
+    }
+
+    fn parse_experiment_lenient(clx: &ClxValue, diagnostics: &mut Vec<Diagnostic>) -> Vec<ExpLoop> {
+        match parse_experiment(clx, diagnostics) {
+            Ok(exp) => exp,
+            Err(err) => {
+                diagnostics.push(Diagnostic::new(
+                    DiagnosticKind::MetadataFallback,
+                    format!("Experiment metadata could not be parsed, assuming no loops: {err}"),
+                ));
+                Vec::new()
+            }
+        }
+    }
+
+    /// Free-text fields of the file (description, author, sample ID, ...),
+    /// all `None` when it has no text info chunk.
+    pub fn text_info(&mut self) -> Result<TextInfo> {
+        let chunk_name = self.text_info_chunk_name();
+        if !self.chunks.contains(chunk_name) {
+            return Ok(TextInfo::default());
+        }
+        let data = self.chunks.read_chunk(&mut self.reader, chunk_name)?;
+        let mut diagnostics = Vec::new();
+        let clx = ClxLiteParser::new(false)
+            .lenient(true)
+            .limits(self.options.limits)
+            .parse_with_diagnostics(&data, &mut diagnostics)?;
+        self.record_diagnostics(diagnostics);
+        parse_text_info(clx)
+    }
+
+    /// Regions of interest drawn in NIS Elements, by id; empty when the
+    /// file has none.
+    pub fn rois(&mut self) -> Result<Vec<Roi>> {
+        let chunk_name = b"CustomData|RoiMetadata_v1!";
+        if !self.chunks.contains(chunk_name) {
+            return Ok(Vec::new());
+        }
+        let data = self.chunks.read_chunk(&mut self.reader, chunk_name)?;
+        let mut diagnostics = Vec::new();
+        let clx = ClxLiteParser::new(false)
+            .lenient(true)
+            .limits(self.options.limits)
+            .parse_with_diagnostics(&data, &mut diagnostics)?;
+        self.record_diagnostics(diagnostics);
+        Ok(parse_rois(&clx))
+    }
+
+    /// Events recorded during acquisition, by time, each placed on the last
+    /// frame acquired at or before it; empty when the file has none.
+    pub fn events(&mut self) -> Result<Vec<ExperimentEvent>> {
+        let Some(chunk_name) = EVENTS_CHUNKS.iter().find(|name| self.chunks.contains(name)) else {
+            return Ok(Vec::new());
+        };
+        let data = self.chunks.read_chunk(&mut self.reader, chunk_name)?;
+        let mut diagnostics = Vec::new();
+        let clx = ClxLiteParser::new(false)
+            .lenient(true)
+            .limits(self.options.limits)
+            .parse_with_diagnostics(&data, &mut diagnostics)?;
+        self.record_diagnostics(diagnostics);
+        let mut events = parse_events(&clx);
+        if events.is_empty() {
+            return Ok(events);
+        }
+        let times = self.frame_times()?;
+        let frame_index = self.frame_index()?;
+        for event in &mut events {
+            let seq_index = times.iter().rposition(|&time| time <= event.time_ms);
+            if let Some(coords) = seq_index.and_then(|index| frame_index.coords.get(index)) {
+                event.loop_indices = frame_index
+                    .axes
+                    .iter()
+                    .cloned()
+                    .zip(coords.iter().copied())
+                    .collect();
+            }
+            event.seq_index = seq_index;
+        }
+        Ok(events)
+    }
+
+    /// Every metadata chunk parsed as a [`ClxValue`] tree (re-exported as
+    /// [`nd2_rs::ClxValue`](crate::ClxValue), with
+    /// [`ClxObject`](crate::ClxObject) for its levels), by chunk name
+    /// without its trailing `!` (e.g. `ImageMetadataLV`,
+    /// `CustomDataVar|AppInfo_V1_0`), for vendor fields the typed accessors
+    /// don't cover.
+    ///
+    /// Holds the `Image*` chunks other than frames, of the per-frame picture
+    /// metadata only the first, every `CustomDataVar|` chunk and the ROI and
+    /// event chunks; the `CustomData|` arrays are raw numbers and left out.
+    /// Chunks that fail to parse are skipped with a
+    /// [`DiagnosticKind::MetadataFallback`] diagnostic.
+    pub fn unstructured_metadata(&mut self) -> Result<HashMap<String, ClxValue>> {
+        let names: Vec<Vec<u8>> = self
+            .chunks
+            .names()
+            .into_iter()
+            .filter(|name| is_metadata_chunk(name))
+            .collect();
+        let mut metadata = HashMap::with_capacity(names.len());
+        let mut diagnostics = Vec::new();
+        for name in names {
+            let data = self.chunks.read_chunk(&mut self.reader, &name)?;
+            let key =
+                String::from_utf8_lossy(name.strip_suffix(b"!").unwrap_or(&name)).into_owned();
+            match ClxLiteParser::new(false)
+                .lenient(true)
+                .limits(self.options.limits)
+                .parse_with_diagnostics(&data, &mut diagnostics)
+            {
+                Ok(clx) => {
+                    metadata.insert(key, clx);
                 }
-                self.experiment = Some(exp);
+                Err(err) => diagnostics.push(Diagnostic::new(
+                    DiagnosticKind::MetadataFallback,
+                    format!("Chunk {key} could not be parsed and was skipped: {err}"),
+                )),
             }
         }
-        Ok(self.experiment.as_ref().unwrap())
+        self.record_diagnostics(diagnostics);
+        Ok(metadata)
+    }
+
+    /// Binary (segmentation) layers, in stored order; empty when the file
+    /// has none.
+    pub fn binary_layers(&mut self) -> Result<Vec<BinaryLayer>> {
+        let chunk_name = b"CustomDataVar|BinaryMetadata_v1!";
+        if !self.chunks.contains(chunk_name) {
+            return Ok(Vec::new());
+        }
+        let data = self.chunks.read_chunk(&mut self.reader, chunk_name)?;
+        let mut diagnostics = Vec::new();
+        let clx = ClxLiteParser::new(false)
+            .lenient(true)
+            .limits(self.options.limits)
+            .parse_with_diagnostics(&data, &mut diagnostics)?;
+        self.record_diagnostics(diagnostics);
+        Ok(parse_binary_layers(&clx))
+    }
+
+    /// Mask of binary layer `layer` (indexing [`Nd2File::binary_layers`])
+    /// on frame `index`, Y × X row-major; `None` when the frame has no mask
+    /// in that layer.
+    pub fn read_binary_mask(&mut self, layer: usize, index: usize) -> Result<Option<Vec<bool>>> {
+        let layers = self.binary_layers()?;
+        let layer = layers
+            .get(layer)
+            .ok_or_else(|| Nd2Error::input_out_of_range("layer", layer, layers.len()))?;
+        let sequence_count = self.attributes()?.sequence_count as usize;
+        if index >= sequence_count {
+            return Err(Nd2Error::input_out_of_range(
+                "sequence index",
+                index,
+                sequence_count,
+            ));
+        }
+        let chunk_name = format!("CustomDataSeq|{}|{}!", layer.file_tag, index);
+        if !self.chunks.contains(chunk_name.as_bytes()) {
+            return Ok(None);
+        }
+        let data = self
+            .chunks
+            .read_chunk(&mut self.reader, chunk_name.as_bytes())?;
+        if data.len() <= 4 {
+            return Ok(None);
+        }
+        let (height, width) = self.shape()?;
+        decode_binary_mask(&data, height, width).map(Some)
+    }
+
+    /// Non-fatal anomalies recorded while parsing so far.
+    ///
+    /// Metadata is parsed lazily, so the list grows as more accessors are used.
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
+    /// Sequence indices of the frames skipped so far by exports and bulk
+    /// reads with [`Nd2Options::skip_bad_frames`], in ascending order. The
+    /// error of each is in [`Nd2File::diagnostics`].
+    pub fn bad_frames(&self) -> Vec<usize> {
+        self.bad_frames.iter().copied().collect()
     }
 
     /// Return a lightweight dataset overview aligned with other reader crates.
     pub fn summary(&mut self) -> Result<DatasetSummary> {
         let sizes = self.sizes()?;
         let attrs = self.attributes()?.clone();
-        let logical_frame_count = self.loop_indices()?.len();
+        let logical_frame_count = self.frame_index()?.len();
 
         let pixel_type = Some(format!(
             "{}{}",
@@ -152,6 +1026,7 @@ impl Nd2File {
             .collect();
 
         Ok(DatasetSummary {
+            schema_version: DatasetSummary::SCHEMA_VERSION,
             version_major: self.version.0,
             version_minor: self.version.1,
             sizes: sizes.into_iter().collect(),
@@ -162,24 +1037,173 @@ impl Nd2File {
         })
     }
 
-    /// Read raw chunk data by name
-    fn read_raw_chunk(&mut self, name: &[u8]) -> Result<Vec<u8>> {
-        read_chunk(&mut self.reader, &self.chunkmap, name)
+    /// OME-XML (schema [`crate::OME_SCHEMA_VERSION`]) describing every
+    /// position as an `Image`, including per-plane DeltaT and stage position.
+    pub fn ome_xml(&mut self) -> Result<String> {
+        crate::export::ome_xml::build(self)
+    }
+
+    /// napari `add_image` kwargs (scale, stage translate, colormap, contrast
+    /// limits) for each channel of `position`.
+    pub fn napari_layers(&mut self, position: usize) -> Result<Vec<NapariLayer>> {
+        crate::export::napari::layers(self, position)
+    }
+
+    /// Owned copy of all parsed metadata and the frame table.
+    pub fn snapshot(&mut self) -> Result<Nd2Snapshot> {
+        let summary = self.summary()?;
+        let frames = self.frames()?;
+        Ok(Nd2Snapshot {
+            schema_version: Nd2Snapshot::SCHEMA_VERSION,
+            version_major: self.version.0,
+            version_minor: self.version.1,
+            attributes: self.attributes()?.clone(),
+            experiment: self.experiment()?.clone(),
+            summary,
+            frames,
+            diagnostics: self.diagnostics.clone(),
+        })
+    }
+
+    /// Whether each channel stores RGB components (3 components per channel).
+    pub fn is_rgb(&mut self) -> Result<bool> {
+        let attrs = self.attributes()?;
+        let n_chan = attrs.channel_count.unwrap_or(attrs.component_count).max(1);
+        Ok(attrs.component_count / n_chan == 3)
+    }
+
+    /// Whether the file uses the legacy JPEG2000 container (version 1.x).
+    /// A container that also holds a modern chunk layer is read as that
+    /// layer's version instead, with a
+    /// [`LegacyContainer`](crate::DiagnosticKind::LegacyContainer)
+    /// diagnostic.
+    ///
+    /// Legacy files are read from their codestreams, one lossy frame each
+    /// (decoded with the `jpeg2000` feature), and from the metadata in
+    /// their XML boxes. They cannot be rewritten.
+    pub fn is_legacy(&self) -> bool {
+        self.version.0 < 2
+    }
+
+    /// Whether image frames are stored compressed (lossless or lossy).
+    pub fn is_compressed(&mut self) -> Result<bool> {
+        Ok(matches!(
+            self.attributes()?.compression_type,
+            Some(CompressionType::Lossless) | Some(CompressionType::Lossy)
+        ))
+    }
+
+    /// Number of frames addressable by [`Nd2File::read_frame`]: the length
+    /// of the frame index, so a recovered file counts the frames actually
+    /// stored. The count declared in the attributes is in
+    /// [`Nd2File::frame_counts`].
+    pub fn n_frames(&mut self) -> Result<usize> {
+        if let Ok(layout) = self.frame_layout() {
+            return Ok(layout.index.len());
+        }
+        // Files whose frame table cannot be built keep the declared count.
+        Ok(self.attributes()?.sequence_count as usize)
+    }
+
+    /// Number of time points (T), 1 when the file has no time loop.
+    pub fn n_timepoints(&mut self) -> Result<usize> {
+        self.axis_len(AXIS_T)
+    }
+
+    /// Number of channels (C).
+    pub fn n_channels(&mut self) -> Result<usize> {
+        self.axis_len(AXIS_C)
+    }
+
+    /// Number of stage positions (P), 1 when the file has no XY loop.
+    pub fn n_positions(&mut self) -> Result<usize> {
+        self.axis_len(AXIS_P)
+    }
+
+    /// Number of Z planes, 1 when the file has no Z stack.
+    pub fn n_z(&mut self) -> Result<usize> {
+        self.axis_len(AXIS_Z)
+    }
+
+    fn axis_len(&mut self, axis: &str) -> Result<usize> {
+        Ok(self.sizes()?.get(axis).copied().unwrap_or(1))
+    }
+
+    /// Frame plane shape as (Y, X) in pixels.
+    pub fn shape(&mut self) -> Result<(usize, usize)> {
+        let sizes = self.sizes()?;
+        let height = sizes.get(AXIS_Y).copied().unwrap_or(0);
+        let width = sizes.get(AXIS_X).copied().unwrap_or(0);
+        Ok((height, width))
     }
 
     /// Dimensions (P,T,C,Z,Y,X) derived from attributes + experiment.
     /// When experiment is empty, infers minimal structure from sequence_count.
-    fn sizes(&mut self) -> Result<HashMap<String, usize>> {
-        let attrs = self.attributes()?.clone();
-        let exp = self.experiment()?.clone();
+    ///
+    /// Loop sizes follow the frame index, so an acquisition that stopped
+    /// early reports the steps it reached rather than those planned.
+    pub(crate) fn sizes(&mut self) -> Result<HashMap<String, usize>> {
+        if let Ok(layout) = self.frame_layout() {
+            return Ok(layout.sizes.clone());
+        }
+        // Files whose frame table cannot be built keep the declared sizes.
+        let (attrs, exp) = self.layout_metadata()?;
+        Ok(Self::sizes_from(attrs, exp))
+    }
+
+    /// Attributes and experiment loops, loaded as needed and borrowed
+    /// together.
+    fn layout_metadata(&mut self) -> Result<(&Attributes, &[ExpLoop])> {
+        self.attributes()?;
+        self.experiment()?;
+        match (&self.attributes, &self.experiment) {
+            (Some(attrs), Some(exp)) => Ok((attrs, exp)),
+            _ => Err(Nd2Error::internal_invariant(
+                "attributes or experiment missing after loading",
+            )),
+        }
+    }
+
+    /// Frame counts stated by the attributes, stored in the chunkmap and
+    /// planned by the experiment loops.
+    ///
+    /// They differ for acquisitions that stopped early and for files whose
+    /// metadata disagrees with their data; [`Nd2File::missing_frames`]
+    /// lists the frames without a chunk.
+    pub fn frame_counts(&mut self) -> Result<FrameCounts> {
+        let (stored, _) = self.chunks.frames_present();
+        let (attrs, exp) = self.layout_metadata()?;
+        Ok(Self::frame_counts_from(attrs, exp, stored))
+    }
+
+    fn frame_counts_from(attrs: &Attributes, exp: &[ExpLoop], stored: usize) -> FrameCounts {
+        let declared = attrs.sequence_count as usize;
+        // Without experiment loops the shape is inferred from the declared
+        // count, so there is no plan to compare against.
+        let planned = if exp.is_empty() {
+            declared
+        } else {
+            let (_, coord_shape) = Self::coord_axis_order(attrs, exp);
+            coord_shape
+                .iter()
+                .try_fold(1usize, |acc, &n| acc.checked_mul(n))
+                .unwrap_or(usize::MAX)
+        };
+        FrameCounts {
+            declared,
+            stored,
+            planned,
+        }
+    }
 
+    fn sizes_from(attrs: &Attributes, exp: &[ExpLoop]) -> HashMap<String, usize> {
         let n_chan = attrs.channel_count.unwrap_or(attrs.component_count);
         let height = attrs.height_px as usize;
         let width = attrs
             .width_px
-            .or(attrs.width_bytes.map(|w| {
+            .or(attrs.width_bytes.and_then(|w| {
                 let bpp = attrs.bits_per_component_in_memory / 8;
-                w / (bpp * attrs.component_count)
+                w.checked_div(bpp.checked_mul(attrs.component_count)?)
             }))
             .unwrap_or(0) as usize;
 
@@ -231,270 +1255,1311 @@ impl Nd2File {
         sizes.insert(AXIS_Y.to_string(), height);
         sizes.insert(AXIS_X.to_string(), width);
 
-        Ok(sizes)
+        sizes
     }
 
-    /// Loop indices for each sequence chunk: seq_index -> axis name -> index.
+    /// Loop coordinates and chunk location of every frame.
+    ///
+    /// Built on first use and cached with the metadata it is derived from
+    /// (see [`Nd2Options::cache_metadata`]); [`Nd2File::clear_caches`]
+    /// drops it.
+    pub fn frame_index(&mut self) -> Result<&FrameIndex> {
+        Ok(&self.frame_layout()?.index)
+    }
+
+    fn frame_layout(&mut self) -> Result<&FrameLayout> {
+        if !self.caches_chunk(self.attributes_chunk_name())
+            || !self.caches_chunk(self.experiment_chunk_name())
+        {
+            self.frame_layout = None;
+        }
+        let layout = match self.frame_layout.take() {
+            Some(layout) => layout,
+            None => self.build_frame_layout()?,
+        };
+        Ok(self.frame_layout.insert(layout))
+    }
+
+    /// Loop coordinates for each sequence chunk, with its chunk location.
     /// Channel is omitted when stored in-pixel instead of as separate chunks.
-    fn loop_indices(&mut self) -> Result<Vec<HashMap<String, usize>>> {
-        let (axis_order, coord_shape) = self.coord_axis_order()?;
-        let total: usize = coord_shape.iter().product();
+    fn build_frame_layout(&mut self) -> Result<FrameLayout> {
+        // Every frame needs at least a 16-byte chunk header, which bounds how
+        // many frames a file of this size can plausibly describe.
+        let file_size = self.reader.seek(SeekFrom::End(0))?;
+        let (stored, stored_end) = self.chunks.frames_present();
+        let (attrs, exp) = self.layout_metadata()?;
+        let (axis_order, mut coord_shape) = Self::coord_axis_order(attrs, exp);
+        let mut sizes = Self::sizes_from(attrs, exp);
+        let counts = Self::frame_counts_from(attrs, exp, stored);
+        let max_frames = usize::try_from(file_size / 16).unwrap_or(usize::MAX);
+        let total = coord_shape
+            .iter()
+            .try_fold(1usize, |acc, &n| acc.checked_mul(n))
+            .filter(|&total| total <= max_frames)
+            .ok_or_else(|| {
+                Nd2Error::file_invalid_format(format!(
+                    "Experiment loop sizes {:?} exceed what a {} byte file can hold",
+                    coord_shape, file_size
+                ))
+            })?;
 
-        let mut out = Vec::with_capacity(total);
+        if !counts.is_consistent() {
+            self.record_diagnostics(vec![Diagnostic::new(
+                DiagnosticKind::FrameCountMismatch,
+                format!(
+                    "Attributes declare {} frames, the chunkmap holds {} and the experiment \
+                     loops plan {}",
+                    counts.declared, counts.stored, counts.planned
+                ),
+            )]);
+        }
+        // An acquisition that stopped early only has chunks for the first
+        // frames; shrink the outermost loop to the steps it reached.
+        let mut total = total;
+        if stored_end > 0 && stored_end < total {
+            if let Some(outer) = coord_shape.iter().position(|&len| len > 1) {
+                let inner: usize = coord_shape[outer + 1..].iter().product();
+                coord_shape[outer] = stored_end / inner + usize::from(stored_end % inner != 0);
+                total = coord_shape[outer] * inner;
+            }
+        }
+
+        let mut coords = Vec::with_capacity(total);
         let n = axis_order.len();
+        for seq in 0..total {
+            let mut idx = seq;
+            let mut frame = vec![0; n];
+            // Unravel seq: innermost acquisition axis varies fastest
+            for i in (0..n).rev() {
+                frame[i] = idx % coord_shape[i];
+                idx /= coord_shape[i];
+            }
+            coords.push(frame);
+        }
+
+        for (axis, &len) in axis_order.iter().zip(&coord_shape) {
+            sizes.insert(axis.to_string(), len);
+        }
+        let index = FrameIndex {
+            axes: axis_order.iter().map(|axis| axis.to_string()).collect(),
+            shape: coord_shape,
+            // Chunks past the end of a truncated file are missing.
+            chunks: (0..total)
+                .map(|seq| {
+                    self.chunks.image(seq).filter(|&(offset, size)| {
+                        offset.checked_add(size).is_some_and(|end| end <= file_size)
+                    })
+                })
+                .collect(),
+            coords,
+        };
+        Ok(FrameLayout { index, sizes })
+    }
+
+    /// Handles for every frame present in the file, in sequence order,
+    /// without decoding any pixels. Frames listed by
+    /// [`Nd2File::missing_frames`] are left out.
+    pub fn frames(&mut self) -> Result<Vec<Frame>> {
+        let frame_index = self.frame_index()?;
+        (0..frame_index.len())
+            .filter(|&index| frame_index.chunks[index].is_some())
+            .map(|index| frame_index.frame(index))
+            .collect()
+    }
+
+    /// Sequence indices of frames without a chunk in the file: not in the
+    /// chunkmap, or cut off by truncation. Files whose acquisition stopped
+    /// early can be opened with [`Nd2Options::allow_recovery`], and their
+    /// remaining frames read as usual.
+    pub fn missing_frames(&mut self) -> Result<Vec<usize>> {
+        Ok(self.frame_index()?.missing())
+    }
+
+    /// Handle for one frame by sequence index, without decoding its pixels.
+    pub fn frame(&mut self, index: usize) -> Result<Frame> {
+        self.frame_index()?.frame(index)
+    }
+
+    /// Read one frame by sequence index. Returns pixels as (C, Y, X) u16 data.
+    ///
+    /// Takes `&mut self` since reads fill the metadata and buffer caches; to
+    /// read frames from several threads at once, use [`Nd2File::frame_reader`].
+    pub fn read_frame(&mut self, index: usize) -> Result<Vec<u16>> {
+        self.read_frame_as::<u16>(index)
+    }
+
+    /// Read one frame by sequence index as (C, Y, X) components of type `T`.
+    ///
+    /// Fails with an input error when the file's pixel type cannot be
+    /// converted to `T` without loss (see [`Pixel`]).
+    pub fn read_frame_as<T: Pixel>(&mut self, index: usize) -> Result<Vec<T>> {
+        Ok(self.read_frame_decoded::<T>(index)?.0)
+    }
+
+    /// Read one channel of a frame by sequence index as a Y×X plane.
+    ///
+    /// `channel` counts the components stored in the frame chunk, as the C
+    /// axis of [`Nd2File::read_frame`].
+    pub fn read_channel(&mut self, index: usize, channel: usize) -> Result<Vec<u16>> {
+        let pixels = self.read_frame(index)?;
+        let mut planes = self.split_planes(pixels)?;
+        if channel >= planes.len() {
+            return Err(Nd2Error::input_out_of_range(
+                "channel index",
+                channel,
+                planes.len(),
+            ));
+        }
+        Ok(planes.swap_remove(channel))
+    }
+
+    /// Split (C, Y, X) frame data into one Y×X plane per channel.
+    pub(crate) fn split_planes(&mut self, pixels: Vec<u16>) -> Result<Vec<Vec<u16>>> {
+        let (height, width) = self.shape()?;
+        let plane = height
+            .checked_mul(width)
+            .filter(|v| *v > 0)
+            .ok_or_else(|| {
+                Nd2Error::file_invalid_format("Invalid frame plane dimensions".to_string())
+            })?;
+        Ok(pixels.chunks_exact(plane).map(<[u16]>::to_vec).collect())
+    }
+
+    /// Read one frame by sequence index as (C, Y, X) components of the
+    /// smallest type that holds the stored ones: `u8`, `u16` or `u32` for
+    /// 8-, 16- and 32-bit unsigned data, `f32` for float data.
+    pub fn read_frame_native(&mut self, index: usize) -> Result<PixelBuffer> {
+        let attributes = self.attributes()?;
+        let bits = attributes.bits_per_component_in_memory;
+        Ok(match attributes.pixel_data_type {
+            PixelDataType::Float => PixelBuffer::F32(self.read_frame_as(index)?),
+            PixelDataType::Unsigned if bits <= 8 => PixelBuffer::U8(self.read_frame_as(index)?),
+            PixelDataType::Unsigned if bits <= 16 => PixelBuffer::U16(self.read_frame_as(index)?),
+            PixelDataType::Unsigned => PixelBuffer::U32(self.read_frame_as(index)?),
+        })
+    }
+
+    /// Read several frames by sequence index, each as (C, Y, X) u16 data.
+    ///
+    /// Compressed frames are decompressed concurrently; see
+    /// [`Nd2Options::decode_threads`].
+    pub fn read_frames(&mut self, indices: &[usize]) -> Result<Vec<Vec<u16>>> {
+        self.read_frames_as::<u16>(indices)
+    }
+
+    /// Read several frames by sequence index as (C, Y, X) components of type `T`.
+    pub fn read_frames_as<T: Pixel>(&mut self, indices: &[usize]) -> Result<Vec<Vec<T>>> {
+        Ok(self
+            .read_frames_decoded::<T>(indices)?
+            .into_iter()
+            .map(|(pixels, _)| pixels)
+            .collect())
+    }
+
+    /// A [`FrameReader`] for reading frames from several threads at once,
+    /// through `&self`.
+    ///
+    /// This is the concurrent read path: `Nd2File` reads keep `&mut self`,
+    /// as they parse metadata lazily, reuse buffers between reads and may go
+    /// through a single-cursor reader. The `FrameReader` holds what frame
+    /// reads need up front (geometry, chunk offsets) and reads only through
+    /// the shared file handle.
+    ///
+    /// Needs a file opened by path or handle with positional reads (the
+    /// default, see [`ReadStrategy`]); other sources have one cursor only.
+    pub fn frame_reader(&mut self) -> Result<FrameReader> {
+        let file = self.shared_file.clone().ok_or_else(|| {
+            Nd2Error::input_argument(
+                "source",
+                "concurrent frame reads need a file opened with positional reads",
+            )
+        })?;
+        let geometry = self.geometry()?;
+        let n_frames = self.n_frames()?;
+        let attrs = self.attributes()?;
+        let (bits, pixel_data_type) = (attrs.bits_per_component_in_memory, attrs.pixel_data_type);
+        let chunks = (0..n_frames)
+            .map(|index| self.chunks.image(index))
+            .collect();
+        Ok(FrameReader::new(
+            file,
+            geometry,
+            bits,
+            pixel_data_type,
+            chunks,
+            self.frame_spans.clone(),
+            self.chunks.file_size(),
+        ))
+    }
+
+    /// Metadata of frame `index` without decoding its pixels: timestamp,
+    /// stage position, exposure and channels.
+    pub fn frame_metadata(&mut self, index: usize) -> Result<FrameMetadata> {
+        let timestamp_ms = self.frame_timestamp(index)?;
+        self.build_frame_metadata(index, timestamp_ms)
+    }
+
+    /// Read one frame with its metadata, as by [`Nd2File::frame_metadata`].
+    pub fn read_frame_with_meta(&mut self, index: usize) -> Result<(Vec<u16>, FrameMetadata)> {
+        let (pixels, timestamp_ms) = self.read_frame_decoded::<u16>(index)?;
+        Ok((pixels, self.build_frame_metadata(index, timestamp_ms)?))
+    }
+
+    /// Acquisition time in milliseconds of every frame, by sequence index.
+    ///
+    /// Read from the `CustomData|AcqTimesCache!` array NIS Elements keeps
+    /// for the whole acquisition; files without one fall back to the
+    /// timestamp at the start of each frame chunk, which is `NaN` for
+    /// frames whose chunk is missing or was never written.
+    pub fn frame_times(&mut self) -> Result<Vec<f64>> {
+        if let Some(times) = self.custom_data_values("AcqTimesCache")? {
+            return Ok(times);
+        }
+        let index = self.frame_index()?;
+        let (stored, missing) = (index.len(), index.missing());
+        let n_frames = self.n_frames()?;
+        let mut times = Vec::with_capacity(n_frames);
+        for index in 0..stored {
+            let time = match missing.binary_search(&index) {
+                Ok(_) => None,
+                Err(_) => self.frame_timestamp(index)?,
+            };
+            times.push(time.unwrap_or(f64::NAN));
+        }
+        times.resize(n_frames.max(stored), f64::NAN);
+        Ok(times)
+    }
+
+    /// Measurements NIS Elements logged for every frame (temperature, CO2,
+    /// stage speed, PFS status, ...), by column name with one value per
+    /// sequence index, like nd2-python's `recorded_data`.
+    ///
+    /// Always has `Time [s]` from [`Nd2File::frame_times`]; other columns
+    /// come from the tags described in `CustomDataVar|CustomDataV2_0!`,
+    /// named `Desc [Unit]`. Tags whose array is missing, short or of an
+    /// unknown type are left out.
+    pub fn recorded_data(&mut self) -> Result<HashMap<String, Vec<RecordedValue>>> {
+        let mut columns = HashMap::new();
+        let times = self.frame_times()?;
+        columns.insert(
+            "Time [s]".to_string(),
+            times
+                .iter()
+                .map(|ms| RecordedValue::Float(ms / 1000.0))
+                .collect(),
+        );
+        let tags_chunk = b"CustomDataVar|CustomDataV2_0!";
+        if !self.chunks.contains(tags_chunk) {
+            return Ok(columns);
+        }
+        let data = self.chunks.read_chunk(&mut self.reader, tags_chunk)?;
+        let mut diagnostics = Vec::new();
+        let clx = ClxLiteParser::new(false)
+            .lenient(true)
+            .limits(self.options.limits)
+            .parse_with_diagnostics(&data, &mut diagnostics)?;
+        self.record_diagnostics(diagnostics);
+        for tag in parse_custom_tags(&clx) {
+            let chunk_name = format!("CustomData|{}!", tag.id);
+            if !self.chunks.contains(chunk_name.as_bytes()) {
+                continue;
+            }
+            let data = self
+                .chunks
+                .read_chunk(&mut self.reader, chunk_name.as_bytes())?;
+            if let Some(values) = tag.decode(&data, times.len()) {
+                columns.insert(tag.column_name(), values);
+            }
+        }
+        Ok(columns)
+    }
+
+    /// Stage position recorded for every frame, by sequence index, from the
+    /// `CustomData|X!`, `Y!` and `Z!` arrays.
+    ///
+    /// Empty when the file does not record all three coordinates; the
+    /// planned positions are in the XY loop of [`Nd2File::snapshot`]. The
+    /// PFS offset and XY point of each frame are in
+    /// [`Nd2File::frame_position_details`].
+    pub fn frame_positions(&mut self) -> Result<Vec<StagePosition>> {
+        let x = self.custom_data_values("X")?;
+        let y = self.custom_data_values("Y")?;
+        let z = self.custom_data_values("Z")?;
+        let (Some(x), Some(y), Some(z)) = (x, y, z) else {
+            return Ok(Vec::new());
+        };
+        Ok(x.into_iter()
+            .zip(y)
+            .zip(z)
+            .map(|((x, y), z)| StagePosition { x, y, z })
+            .collect())
+    }
+
+    /// [`Nd2File::frame_positions`] with the `CustomData|PFS_OFFSET!` value
+    /// of every frame when recorded (else the offset of the frame's XY
+    /// point) and the name of the frame's XY point.
+    pub fn frame_position_details(&mut self) -> Result<Vec<Position>> {
+        let stage_positions = self.frame_positions()?;
+        if stage_positions.is_empty() {
+            return Ok(Vec::new());
+        }
+        let pfs_offsets = self.custom_data_values("PFS_OFFSET")?;
+        let points = self.xy_positions()?.map(|xy| xy.points).unwrap_or_default();
+        let frame_index = self.frame_index()?;
+        let p_axis = frame_index.axes.iter().position(|axis| axis == AXIS_P);
+        Ok(stage_positions
+            .into_iter()
+            .enumerate()
+            .map(|(index, stage_position_um)| {
+                let point = p_axis
+                    .and_then(|axis| frame_index.coords.get(index)?.get(axis))
+                    .and_then(|&p| points.get(p));
+                Position {
+                    stage_position_um,
+                    pfs_offset: match &pfs_offsets {
+                        Some(offsets) => offsets.get(index).copied(),
+                        None => point.and_then(|point| point.pfs_offset),
+                    },
+                    name: point.and_then(|point| point.name.clone()),
+                }
+            })
+            .collect())
+    }
+
+    fn build_frame_metadata(
+        &mut self,
+        index: usize,
+        timestamp_ms: Option<f64>,
+    ) -> Result<FrameMetadata> {
+        let frame = self.frame(index)?;
+        let point = match frame.coord(AXIS_P) {
+            Some(p) => self.experiment()?.iter().find_map(|loop_| match loop_ {
+                ExpLoop::XYPosLoop(xy) => xy
+                    .parameters
+                    .points
+                    .get(p)
+                    .map(|point| point.stage_position_um),
+                _ => None,
+            }),
+            None => None,
+        };
+        let x = self.custom_data_value("X", index)?;
+        let y = self.custom_data_value("Y", index)?;
+        let z = self.custom_data_value("Z", index)?;
+        let stage_position_um = match (x, y, z) {
+            (Some(x), Some(y), Some(z)) => Some(StagePosition { x, y, z }),
+            _ => point,
+        };
+        let exposure_ms = self.custom_data_value("Camera_ExposureTime1", index)?;
+        let pfs_offset = self.custom_data_value("PFS_OFFSET", index)?;
+        let own = self.picture_metadata_seq_name(index);
+        let channels = if index > 0 && self.chunks.contains(&own) {
+            self.load_metadata(&own)?.channels
+        } else {
+            self.metadata()?.channels.clone()
+        };
+        Ok(FrameMetadata {
+            index,
+            coords: frame.coords,
+            timestamp_ms,
+            stage_position_um,
+            exposure_ms,
+            pfs_offset,
+            channels,
+        })
+    }
+
+    /// The `CustomData|{name}!` array, one value per frame. `None` when the
+    /// chunk is missing or does not hold an 8-byte float per frame; values
+    /// past the last frame are dropped.
+    pub(crate) fn custom_data_values(&mut self, name: &str) -> Result<Option<Vec<f64>>> {
+        let chunk_name = format!("CustomData|{}!", name);
+        if !self.chunks.contains(chunk_name.as_bytes()) {
+            return Ok(None);
+        }
+        let sequence_count = self.attributes()?.sequence_count as usize;
+        let data = self
+            .chunks
+            .read_chunk(&mut self.reader, chunk_name.as_bytes())?;
+        if data.len() < custom_data_len(&chunk_name, sequence_count)? {
+            return Ok(None);
+        }
+        data.chunks_exact(8)
+            .take(sequence_count)
+            .map(|bytes| {
+                <[u8; 8]>::try_from(bytes)
+                    .map(f64::from_le_bytes)
+                    .map_err(|_| {
+                        Nd2Error::internal_invariant("chunks_exact(8) yielded a short chunk")
+                    })
+            })
+            .collect::<Result<_>>()
+            .map(Some)
+    }
+
+    /// Value of frame `index` in the `CustomData|{name}!` array, read on its
+    /// own. `None` when the chunk is missing or does not hold an 8-byte
+    /// float per frame.
+    pub(crate) fn custom_data_value(&mut self, name: &str, index: usize) -> Result<Option<f64>> {
+        let chunk_name = format!("CustomData|{}!", name);
+        let Some((offset, map_size)) = self.chunks.get(chunk_name.as_bytes()) else {
+            return Ok(None);
+        };
+        let sequence_count = self.attributes()?.sequence_count as usize;
+        let (data_offset, size) = read_chunk_span(
+            &mut self.reader,
+            chunk_name.as_bytes(),
+            offset,
+            map_size,
+            self.options.limits.max_chunk_bytes,
+        )?;
+        if index >= sequence_count || size < custom_data_len(&chunk_name, sequence_count)? {
+            return Ok(None);
+        }
+        // In bounds: `index * 8` is below `size`.
+        self.reader
+            .seek(SeekFrom::Start(data_offset + index as u64 * 8))?;
+        let mut bytes = [0u8; 8];
+        self.reader.read_exact(&mut bytes)?;
+        Ok(Some(f64::from_le_bytes(bytes)))
+    }
+
+    fn read_frame_decoded<T: Pixel>(&mut self, index: usize) -> Result<(Vec<T>, Option<f64>)> {
+        let geometry = self.frame_geometry::<T>()?;
+        #[cfg(feature = "frame-cache")]
+        if geometry.compressed && self.frame_cache.is_some() {
+            return self
+                .read_frames_cached(&[index], &geometry, 1)
+                .map(|mut frames| frames.remove(0));
+        }
+        let payload = self.read_frame_payload(index, &geometry)?;
+        let frame = geometry.decode(index, &payload);
+        self.payload_buffers.push(payload.into_buffer());
+        frame
+    }
+
+    /// Decode several frames. Compressed frames are read in batches and
+    /// decompressed on [`Nd2Options::decode_threads`] worker threads.
+    fn read_frames_decoded<T: Pixel>(
+        &mut self,
+        indices: &[usize],
+    ) -> Result<Vec<(Vec<T>, Option<f64>)>> {
+        let geometry = self.frame_geometry::<T>()?;
+        let threads = self.options.decode_worker_count();
+        #[cfg(feature = "frame-cache")]
+        if geometry.compressed && self.frame_cache.is_some() {
+            return self.read_frames_cached(indices, &geometry, threads);
+        }
+        #[cfg(all(feature = "io-uring", target_os = "linux"))]
+        if let (Some(file), true) = (&self.shared_file, self.uring.is_some()) {
+            if indices.len() > 1 {
+                let file = Arc::clone(file);
+                return self.read_frames_uring(&file, indices, &geometry, threads);
+            }
+        }
+        if indices.len() > 1 && self.options.coalesced_read_bytes > 0 {
+            let runs = self.coalesced_runs(indices);
+            if runs.len() < indices.len() {
+                return self.read_frames_coalesced(indices, &runs, &geometry, threads);
+            }
+        }
+        if !geometry.compressed || threads < 2 || indices.len() < 2 {
+            return indices
+                .iter()
+                .map(|&index| {
+                    let payload = self.read_frame_payload(index, &geometry)?;
+                    let frame = geometry.decode(index, &payload);
+                    self.payload_buffers.push(payload.into_buffer());
+                    frame
+                })
+                .collect();
+        }
+
+        let mut out = Vec::with_capacity(indices.len());
+        let shared_file = self.shared_file.clone();
+        let (sequence_count, max_chunk_bytes) = (geometry.sequence_count, geometry.max_chunk_bytes);
+        let mut scratch = std::mem::take(&mut self.scratch_buffers);
+        let read = DecodePool::scope(threads, &mut scratch, |pool| {
+            // Bound the compressed bytes held at once to a few frames per
+            // worker.
+            for batch in indices.chunks(threads * 4) {
+                let (frames, payloads) = match &shared_file {
+                    // Positional reads don't share a cursor, so workers read
+                    // their own chunks.
+                    Some(file) => {
+                        let locations: Vec<_> =
+                            batch.iter().map(|&i| self.chunks.image(i)).collect();
+                        let spans: Arc<[OnceLock<(u64, usize)>]> = batch
+                            .iter()
+                            .map(|&i| match self.derived_span(i, &geometry) {
+                                Some(FrameSpan::Compressed { data_offset, len }) => {
+                                    OnceLock::from((data_offset, len))
+                                }
+                                _ => OnceLock::new(),
+                            })
+                            .collect();
+                        let payloads = batch
+                            .iter()
+                            .map(|_| {
+                                FramePayload::Compressed(
+                                    self.payload_buffers.pop().unwrap_or_default(),
+                                )
+                            })
+                            .collect();
+                        let (file, batch_indices, fetched) =
+                            (Arc::clone(file), batch.to_vec(), Arc::clone(&spans));
+                        let decoded = pool.decode(&geometry, batch, payloads, move |i, payload| {
+                            let FramePayload::Compressed(bytes) = payload else {
+                                return Ok(());
+                            };
+                            let index = batch_indices[i];
+                            let name = format!("ImageDataSeq|{}!", index);
+                            let mut reader = PositionalReader::new(&*file);
+                            let (data_offset, len) = match fetched[i].get() {
+                                Some(&span) => span,
+                                None => {
+                                    let (offset, size) = locations[i].ok_or_else(|| {
+                                        Nd2Error::input_out_of_range(
+                                            "sequence index",
+                                            index,
+                                            sequence_count,
+                                        )
+                                    })?;
+                                    let span = read_chunk_span(
+                                        &mut reader,
+                                        name.as_bytes(),
+                                        offset,
+                                        size,
+                                        max_chunk_bytes,
+                                    )?;
+                                    *fetched[i].get_or_init(|| span)
+                                }
+                            };
+                            crate::chunk::read_chunk_data(
+                                &mut reader,
+                                name.as_bytes(),
+                                data_offset,
+                                len,
+                                bytes,
+                            )
+                        });
+                        for (&index, span) in batch.iter().zip(spans.iter()) {
+                            if let Some(&(data_offset, len)) = span.get() {
+                                self.frame_spans.confirm(
+                                    index,
+                                    self.chunks.image(index),
+                                    FrameSpan::Compressed { data_offset, len },
+                                );
+                            }
+                        }
+                        decoded
+                    }
+                    None => {
+                        let payloads = batch
+                            .iter()
+                            .map(|&index| self.read_frame_payload(index, &geometry))
+                            .collect::<Result<Vec<_>>>()?;
+                        pool.decode(&geometry, batch, payloads, |_, _| Ok(()))
+                    }
+                };
+                self.payload_buffers
+                    .extend(payloads.into_iter().map(FramePayload::into_buffer));
+                for frame in frames {
+                    out.push(frame?);
+                }
+            }
+            Ok(out)
+        });
+        self.scratch_buffers = scratch;
+        read
+    }
+
+    /// Decode compressed frames through the frame cache: cached frames are
+    /// loaded inflated, the others are read from the file and inflated (on
+    /// the decode workers) and stored before decoding.
+    #[cfg(feature = "frame-cache")]
+    fn read_frames_cached<T: Pixel>(
+        &mut self,
+        indices: &[usize],
+        geometry: &FrameGeometry,
+        threads: usize,
+    ) -> Result<Vec<(Vec<T>, Option<f64>)>> {
+        let cache = self
+            .frame_cache
+            .clone()
+            .ok_or_else(|| Nd2Error::internal_invariant("cached reads without a frame cache"))?;
+        let store_error = Arc::new(OnceLock::new());
+        let mut out = Vec::with_capacity(indices.len());
+        let mut scratch = std::mem::take(&mut self.scratch_buffers);
+        let read = DecodePool::scope(threads, &mut scratch, |pool| {
+            for batch in indices.chunks(threads * 4) {
+                let payloads = batch
+                    .iter()
+                    .map(|&index| match cache.load(index) {
+                        Some(payload) => Ok(payload),
+                        None => self.read_frame_payload(index, geometry),
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                let (frames, payloads) = if threads > 1 && batch.len() > 1 {
+                    let (cache, store_error, batch_indices) =
+                        (cache.clone(), Arc::clone(&store_error), batch.to_vec());
+                    pool.decode(geometry, batch, payloads, move |i, payload| {
+                        cache.inflate_and_store(geometry, batch_indices[i], payload, &store_error)
+                    })
+                } else {
+                    batch
+                        .iter()
+                        .zip(payloads)
+                        .map(|(&index, mut payload)| {
+                            let frame = cache
+                                .inflate_and_store(geometry, index, &mut payload, &store_error)
+                                .and_then(|()| geometry.decode(index, &payload));
+                            (frame, payload)
+                        })
+                        .unzip()
+                };
+                self.payload_buffers
+                    .extend(payloads.into_iter().map(FramePayload::into_buffer));
+                for frame in frames {
+                    out.push(frame?);
+                }
+            }
+            Ok(out)
+        });
+        self.scratch_buffers = scratch;
+        if let Some(err) = store_error.get() {
+            // One report is enough: a cache that cannot be written usually
+            // fails for every frame.
+            if !self
+                .diagnostics
+                .iter()
+                .any(|d| d.kind == DiagnosticKind::FrameCacheWrite)
+            {
+                self.diagnostics.push(Diagnostic::new(
+                    DiagnosticKind::FrameCacheWrite,
+                    format!("Frame cache write failed: {}", err),
+                ));
+            }
+        }
+        read
+    }
 
-        for seq in 0..total {
-            let mut idx = seq;
-            let mut m = HashMap::new();
-            // Unravel seq: innermost acquisition axis varies fastest
-            for i in (0..n).rev() {
-                let coord = idx % coord_shape[i];
-                idx /= coord_shape[i];
-                m.insert(axis_order[i].to_string(), coord);
+    /// Split `indices` into runs of frames whose chunks follow each other on
+    /// disk, each spanning at most [`Nd2Options::coalesce_reads`] bytes, as
+    /// ranges of positions in `indices`.
+    fn coalesced_runs(&self, indices: &[usize]) -> Vec<Range<usize>> {
+        let max_bytes = self.options.coalesced_read_bytes as u64;
+        let mut runs: Vec<Range<usize>> = Vec::new();
+        // File region of the open run, up to the end of its last chunk's data.
+        let mut region: Option<(u64, u64)> = None;
+        for (i, &index) in indices.iter().enumerate() {
+            let chunk = self
+                .chunks
+                .image(index)
+                .map(|(offset, size)| (offset, offset.saturating_add(size)));
+            match (region, chunk) {
+                (Some((start, end)), Some((offset, chunk_end)))
+                    if offset >= end
+                        && offset - end <= MAX_COALESCE_GAP
+                        && chunk_end - start <= max_bytes =>
+                {
+                    if let Some(run) = runs.last_mut() {
+                        run.end = i + 1;
+                    }
+                    region = Some((start, chunk_end));
+                }
+                _ => {
+                    runs.push(i..i + 1);
+                    region = chunk;
+                }
             }
-            out.push(m);
         }
+        runs
+    }
 
-        Ok(out)
+    /// Decode frames `indices` reading each of `runs` (from
+    /// [`Nd2File::coalesced_runs`]) with a single read.
+    fn read_frames_coalesced<T: Pixel>(
+        &mut self,
+        indices: &[usize],
+        runs: &[Range<usize>],
+        geometry: &FrameGeometry,
+        threads: usize,
+    ) -> Result<Vec<(Vec<T>, Option<f64>)>> {
+        let mut out = Vec::with_capacity(indices.len());
+        let workers = if geometry.compressed { threads } else { 1 };
+        let mut scratch = std::mem::take(&mut self.scratch_buffers);
+        let read = DecodePool::scope(workers, &mut scratch, |pool| {
+            for run in runs {
+                let batch = &indices[run.clone()];
+                let payloads = if batch.len() > 1 {
+                    self.read_run_payloads(batch, geometry)?
+                } else {
+                    vec![self.read_frame_payload(batch[0], geometry)?]
+                };
+                let (frames, payloads) = if workers > 1 && batch.len() > 1 {
+                    pool.decode(geometry, batch, payloads, |_, _| Ok(()))
+                } else {
+                    let frames = batch
+                        .iter()
+                        .zip(&payloads)
+                        .map(|(&index, payload)| geometry.decode(index, payload))
+                        .collect();
+                    (frames, payloads)
+                };
+                self.payload_buffers
+                    .extend(payloads.into_iter().map(FramePayload::into_buffer));
+                for frame in frames {
+                    out.push(frame?);
+                }
+            }
+            Ok(out)
+        });
+        self.scratch_buffers = scratch;
+        read
     }
 
-    /// Read one frame by sequence index. Returns pixels as (C, Y, X) u16 data.
-    pub fn read_frame(&mut self, index: usize) -> Result<Vec<u16>> {
-        let attrs = self.attributes()?.clone();
-        let max_seq = attrs.sequence_count as usize;
-        let chunk_name = format!("ImageDataSeq|{}!", index);
-        let chunk_key = chunk_name.as_bytes();
-
-        let h = attrs.height_px as usize;
-        let w = attrs.width_px.unwrap_or(0) as usize;
-        let (n_c, n_comp) = match attrs.channel_count {
-            Some(ch) if ch > 0 => (ch as usize, (attrs.component_count / ch) as usize),
-            _ => (attrs.component_count as usize, 1),
+    /// Read the adjacent chunks of frames `batch` with one read and slice
+    /// the payloads out of it. Frames whose chunk turns out not to lie
+    /// inside the read region are read on their own.
+    fn read_run_payloads(
+        &mut self,
+        batch: &[usize],
+        geometry: &FrameGeometry,
+    ) -> Result<Vec<FramePayload>> {
+        let locations = batch
+            .iter()
+            .map(|&index| {
+                self.chunks.image(index).ok_or_else(|| {
+                    Nd2Error::input_out_of_range("sequence index", index, geometry.sequence_count)
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let (Some(&(start, _)), Some(&(last, last_size))) = (locations.first(), locations.last())
+        else {
+            return Ok(Vec::new());
         };
-        let bytes_per_pixel = (attrs.bits_per_component_in_memory / 8) as usize;
-        if bytes_per_pixel == 0 {
-            return Err(Nd2Error::file_invalid_format(
-                "Invalid bits_per_component_in_memory".to_string(),
-            ));
+        let file_size = self.reader.seek(SeekFrom::End(0))?;
+        // Chunkmap sizes leave out the chunk header, so read a little past
+        // the last chunk's data.
+        let end = last
+            .saturating_add(last_size)
+            .saturating_add(MAX_COALESCE_GAP)
+            .min(file_size);
+        let len = usize::try_from(end.saturating_sub(start))
+            .map_err(|_| Nd2Error::internal_overflow("coalesced read length"))?;
+
+        let mut run = std::mem::take(&mut self.run_buffer);
+        run.clear();
+        run.resize(len, 0);
+        self.reader.seek(SeekFrom::Start(start))?;
+        self.reader.read_exact(&mut run)?;
+
+        let mut payloads = Vec::with_capacity(batch.len());
+        for (&index, &(offset, size)) in batch.iter().zip(&locations) {
+            let payload = match self.slice_run_payload(&run, start, index, offset, size, geometry) {
+                Some(payload) => payload,
+                None => self.read_frame_payload(index, geometry)?,
+            };
+            payloads.push(payload);
         }
-        let raw_row_bytes = attrs.width_bytes.map(|w| w as usize).unwrap_or_else(|| {
-            w.saturating_mul(n_c)
-                .saturating_mul(n_comp)
-                .saturating_mul(bytes_per_pixel)
+        self.run_buffer = run;
+        Ok(payloads)
+    }
+
+    /// Frame `index`'s payload out of `run`, the file bytes from
+    /// `run_start`, checking its chunk header there on first use. `None`
+    /// when the chunk header is not intact or the payload is not all in
+    /// `run`.
+    fn slice_run_payload(
+        &mut self,
+        run: &[u8],
+        run_start: u64,
+        index: usize,
+        offset: u64,
+        map_size: u64,
+        geometry: &FrameGeometry,
+    ) -> Option<FramePayload> {
+        let slice = |at: u64, len: usize| {
+            let from = usize::try_from(at.checked_sub(run_start)?).ok()?;
+            run.get(from..from.checked_add(len)?)
+        };
+        let span = match self.derived_span(index, geometry) {
+            Some(span) => span,
+            None => {
+                let header = crate::chunk::ChunkHeader::parse(slice(
+                    offset,
+                    crate::chunk::ChunkHeader::SIZE,
+                )?)
+                .ok()?;
+                if geometry.compressed {
+                    let name = format!("ImageDataSeq|{}!", index);
+                    // Bounded by the end of `run` rather than of the file.
+                    let run_end = run_start.checked_add(run.len() as u64)?;
+                    let (data_offset, len) = crate::chunk::chunk_data_span(
+                        &header,
+                        name.as_bytes(),
+                        offset,
+                        map_size,
+                        run_end,
+                        geometry.max_chunk_bytes,
+                    )
+                    .ok()?;
+                    FrameSpan::Compressed { data_offset, len }
+                } else {
+                    header.validate_magic().ok()?;
+                    let payload_offset = offset
+                        .checked_add(crate::chunk::ChunkHeader::SIZE as u64)?
+                        .checked_add(header.name_length as u64)?;
+                    FrameSpan::Raw {
+                        timestamp_offset: Some(payload_offset),
+                        pixel_offset: payload_offset.checked_add(8)?,
+                    }
+                }
+            }
+        };
+        let (timestamp, data) = match span {
+            FrameSpan::Compressed { data_offset, len } => (None, slice(data_offset, len)?),
+            FrameSpan::Raw {
+                timestamp_offset,
+                pixel_offset,
+            } => {
+                let timestamp = match timestamp_offset {
+                    Some(at) => Some(slice(at, 8)?),
+                    None => None,
+                };
+                (timestamp, slice(pixel_offset, geometry.expected_raw)?)
+            }
+        };
+        let mut bytes = self.payload_buffers.pop().unwrap_or_default();
+        bytes.clear();
+        bytes.extend_from_slice(data);
+        let payload = match span {
+            FrameSpan::Compressed { .. } => FramePayload::Compressed(bytes),
+            FrameSpan::Raw { .. } => FramePayload::Raw {
+                timestamp_ms: timestamp
+                    .and_then(|t| t.try_into().ok())
+                    .map(f64::from_le_bytes),
+                bytes,
+            },
+        };
+        self.frame_spans
+            .confirm(index, Some((offset, map_size)), span);
+        Some(payload)
+    }
+
+    /// Decode several frames, reading each batch's chunks through the
+    /// io_uring in one submission.
+    #[cfg(all(feature = "io-uring", target_os = "linux"))]
+    fn read_frames_uring<T: Pixel>(
+        &mut self,
+        file: &File,
+        indices: &[usize],
+        geometry: &FrameGeometry,
+        threads: usize,
+    ) -> Result<Vec<(Vec<T>, Option<f64>)>> {
+        let mut out = Vec::with_capacity(indices.len());
+        let workers = if geometry.compressed { threads } else { 1 };
+        let mut scratch = std::mem::take(&mut self.scratch_buffers);
+        let read = DecodePool::scope(workers, &mut scratch, |pool| {
+            for batch in indices.chunks((threads * 4).max(8)) {
+                let payloads = self.read_frame_payloads_uring(file, batch, geometry)?;
+                let (frames, payloads) = if workers > 1 {
+                    pool.decode(geometry, batch, payloads, |_, _| Ok(()))
+                } else {
+                    let frames = batch
+                        .iter()
+                        .zip(&payloads)
+                        .map(|(&index, payload)| geometry.decode(index, payload))
+                        .collect();
+                    (frames, payloads)
+                };
+                self.payload_buffers
+                    .extend(payloads.into_iter().map(FramePayload::into_buffer));
+                for frame in frames {
+                    out.push(frame?);
+                }
+            }
+            Ok(out)
         });
-        if raw_row_bytes == 0 {
-            return Err(Nd2Error::file_invalid_format(
-                "Invalid frame row stride".to_string(),
-            ));
-        }
-        if raw_row_bytes % bytes_per_pixel != 0 {
-            return Err(Nd2Error::file_invalid_format(format!(
-                "Frame row stride {} is not divisible by bytes per pixel {}",
-                raw_row_bytes, bytes_per_pixel
-            )));
-        }
-        let raw_row_pixels = raw_row_bytes / bytes_per_pixel;
+        self.scratch_buffers = scratch;
+        read
+    }
 
-        let frame_size = h
-            .checked_mul(w)
-            .and_then(|v| v.checked_mul(n_c))
-            .and_then(|v| v.checked_mul(n_comp))
-            .ok_or_else(|| {
-                Nd2Error::file_invalid_format("Frame dimensions overflow".to_string())
-            })?;
-        let expected_raw = h
-            .checked_mul(raw_row_bytes)
-            .ok_or_else(|| Nd2Error::file_invalid_format("Frame byte size overflow".to_string()))?;
-        let frame_area = h
-            .checked_mul(w)
-            .ok_or_else(|| Nd2Error::file_invalid_format("Frame area overflow".to_string()))?;
-        let n_c_n_comp = n_c.checked_mul(n_comp).ok_or_else(|| {
-            Nd2Error::file_invalid_format("Frame channel/component overflow".to_string())
-        })?;
-        if raw_row_pixels < n_c_n_comp.saturating_mul(w) {
-            return Err(Nd2Error::file_invalid_format(format!(
-                "Frame row stride {} pixels is smaller than required width {}",
-                raw_row_pixels,
-                n_c_n_comp.saturating_mul(w)
-            )));
-        }
+    /// Read the chunks of frames `batch` with as few io_uring submissions as
+    /// the chunk layout allows: chunk headers, then all frame data at once.
+    #[cfg(all(feature = "io-uring", target_os = "linux"))]
+    fn read_frame_payloads_uring(
+        &mut self,
+        file: &File,
+        batch: &[usize],
+        geometry: &FrameGeometry,
+    ) -> Result<Vec<FramePayload>> {
+        let mut buffers: Vec<Vec<u8>> = batch
+            .iter()
+            .map(|_| self.payload_buffers.pop().unwrap_or_default())
+            .collect();
 
-        let pixel_bytes = match attrs.compression_type {
-            Some(CompressionType::Lossless) => {
-                let data = match self.read_raw_chunk(chunk_key) {
-                    Ok(data) => data,
-                    Err(err) => {
-                        if matches!(
-                            err,
-                            Nd2Error::File {
-                                source: crate::error::FileError::ChunkNotFound { .. },
-                            }
-                        ) {
-                            return Err(Nd2Error::input_out_of_range(
+        if geometry.compressed {
+            // Only chunks the confirmed header layout doesn't cover need
+            // their headers checked.
+            let mut spans: Vec<_> = batch
+                .iter()
+                .map(|&index| match self.derived_span(index, geometry) {
+                    Some(FrameSpan::Compressed { data_offset, len }) => Some((data_offset, len)),
+                    _ => None,
+                })
+                .collect();
+            let unchecked = batch
+                .iter()
+                .zip(&spans)
+                .filter(|(_, span)| span.is_none())
+                .map(|(&index, _)| {
+                    self.chunks
+                        .image(index)
+                        .map(|location| (index, location))
+                        .ok_or_else(|| {
+                            Nd2Error::input_out_of_range(
                                 "sequence index",
                                 index,
-                                max_seq,
-                            ));
-                        }
-                        return Err(err);
-                    }
-                };
-
-                if data.len() < 8 {
-                    return Err(Nd2Error::file_invalid_format(format!(
-                        "Frame {} compressed chunk too short ({} bytes)",
-                        index,
-                        data.len()
-                    )));
+                                geometry.sequence_count,
+                            )
+                        })
+                })
+                .collect::<Result<Vec<_>>>()?;
+            let uring = self
+                .uring
+                .as_mut()
+                .ok_or_else(|| Nd2Error::internal_invariant("io_uring reads without a ring"))?;
+
+            if !unchecked.is_empty() {
+                let file_size = file.metadata()?.len();
+                for &(index, (offset, _)) in &unchecked {
+                    let name = format!("ImageDataSeq|{}!", index);
+                    crate::chunk::check_chunk_offset(name.as_bytes(), offset, file_size)?;
                 }
-                let mut decoder = ZlibDecoder::new(&data[8..]);
-                let mut decompressed = Vec::new();
-                decoder.read_to_end(&mut decompressed)?;
-                decompressed
-            }
-            _ => match self.read_uncompressed_frame_bytes(chunk_key, expected_raw) {
-                Ok(data) => data,
-                Err(err) => {
-                    if matches!(
-                        err,
-                        Nd2Error::File {
-                            source: crate::error::FileError::ChunkNotFound { .. },
-                        }
-                    ) {
-                        return Err(Nd2Error::input_out_of_range(
-                            "sequence index",
-                            index,
-                            max_seq,
-                        ));
+                let mut headers = vec![[0u8; crate::chunk::ChunkHeader::SIZE]; unchecked.len()];
+                let mut reads: Vec<_> = unchecked
+                    .iter()
+                    .zip(headers.iter_mut())
+                    .map(|(&(_, (offset, _)), header)| (offset, &mut header[..]))
+                    .collect();
+                uring.read_exact_at(file, &mut reads)?;
+                for (&(index, (offset, size)), header) in unchecked.iter().zip(&headers) {
+                    let name = format!("ImageDataSeq|{}!", index);
+                    let header = crate::chunk::ChunkHeader::parse(header)?;
+                    let (data_offset, len) = crate::chunk::chunk_data_span(
+                        &header,
+                        name.as_bytes(),
+                        offset,
+                        size,
+                        file_size,
+                        geometry.max_chunk_bytes,
+                    )?;
+                    self.frame_spans.confirm(
+                        index,
+                        Some((offset, size)),
+                        FrameSpan::Compressed { data_offset, len },
+                    );
+                    for (span, _) in spans.iter_mut().zip(batch).filter(|(_, &i)| i == index) {
+                        *span = Some((data_offset, len));
                     }
-                    return Err(err);
                 }
-            },
-        };
+            }
 
-        if pixel_bytes.len() % 2 != 0 {
-            return Err(Nd2Error::file_invalid_format(format!(
-                "Frame {}: pixel data length {} is not divisible by 2",
-                index,
-                pixel_bytes.len()
-            )));
+            let mut reads = Vec::with_capacity(batch.len());
+            for (span, bytes) in spans.iter().zip(buffers.iter_mut()) {
+                let Some((data_offset, len)) = *span else {
+                    return Err(Nd2Error::internal_invariant(
+                        "compressed frame without a checked chunk span",
+                    ));
+                };
+                bytes.clear();
+                bytes.resize(len, 0);
+                reads.push((data_offset, &mut bytes[..]));
+            }
+            uring.read_exact_at(file, &mut reads)?;
+            return Ok(buffers.into_iter().map(FramePayload::Compressed).collect());
         }
 
-        if pixel_bytes.len() / 2 < frame_size {
-            return Err(Nd2Error::file_invalid_format(format!(
-                "Frame {}: expected {} pixels ({} bytes), got {} bytes",
-                index,
-                frame_size,
-                frame_size * 2,
-                pixel_bytes.len()
-            )));
+        let spans = batch
+            .iter()
+            .map(|&index| match self.frame_span(index, geometry) {
+                Ok(FrameSpan::Raw {
+                    timestamp_offset,
+                    pixel_offset,
+                }) => Ok((timestamp_offset, pixel_offset)),
+                Ok(FrameSpan::Compressed { .. }) => Err(Nd2Error::internal_invariant(
+                    "uncompressed frame with a compressed chunk span",
+                )),
+                Err(err) => Err(frame_read_error(err, index, geometry.sequence_count)),
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let uring = self
+            .uring
+            .as_mut()
+            .ok_or_else(|| Nd2Error::internal_invariant("io_uring reads without a ring"))?;
+        let mut timestamps = vec![[0u8; 8]; batch.len()];
+        let mut reads = Vec::with_capacity(batch.len() * 2);
+        for ((&(payload_offset, pixel_offset), timestamp), bytes) in spans
+            .iter()
+            .zip(timestamps.iter_mut())
+            .zip(buffers.iter_mut())
+        {
+            if let Some(payload_offset) = payload_offset {
+                reads.push((payload_offset, &mut timestamp[..]));
+            }
+            bytes.clear();
+            bytes.resize(geometry.expected_raw, 0);
+            reads.push((pixel_offset, &mut bytes[..]));
         }
+        uring.read_exact_at(file, &mut reads)?;
+        Ok(spans
+            .iter()
+            .zip(timestamps)
+            .zip(buffers)
+            .map(
+                |((&(payload_offset, _), timestamp), bytes)| FramePayload::Raw {
+                    timestamp_ms: payload_offset.map(|_| f64::from_le_bytes(timestamp)),
+                    bytes,
+                },
+            )
+            .collect())
+    }
 
-        let mut pixels: Vec<u16> = vec![0; pixel_bytes.len() / 2];
-        for (i, chunk) in pixel_bytes.chunks_exact(2).enumerate() {
-            pixels[i] = u16::from_le_bytes([chunk[0], chunk[1]]);
+    fn frame_geometry<T: Pixel>(&mut self) -> Result<FrameGeometry> {
+        let attrs = self.attributes()?;
+        if !T::accepts(attrs.bits_per_component_in_memory, attrs.pixel_data_type) {
+            return Err(Nd2Error::input_incompatible(
+                stored_type_name(attrs.bits_per_component_in_memory, attrs.pixel_data_type),
+                T::NAME,
+            ));
         }
+        self.geometry()
+    }
 
-        if pixels.len() < frame_size {
+    /// Frame layout from the attributes, with a frame count the file can
+    /// actually hold, so per-frame tables are never sized from a corrupt
+    /// `uiSequenceCount` alone.
+    pub(crate) fn geometry(&mut self) -> Result<FrameGeometry> {
+        let limits = self.options.limits;
+        let geometry = FrameGeometry::new(self.attributes()?, &limits)?;
+        // Every frame needs at least a 16-byte chunk header.
+        let file_size = self.chunks.file_size();
+        if geometry.sequence_count as u64 > file_size / 16 {
             return Err(Nd2Error::file_invalid_format(format!(
-                "Frame {}: pixel count {} < expected {}",
-                index,
-                pixels.len(),
-                frame_size
+                "Sequence count {} exceeds what a {} byte file can hold",
+                geometry.sequence_count, file_size
             )));
         }
+        Ok(geometry)
+    }
 
-        let mut out = vec![0u16; frame_size];
-        let row_pixels = raw_row_pixels;
+    /// Read a frame chunk's bytes without decoding them.
+    fn read_frame_payload(
+        &mut self,
+        index: usize,
+        geometry: &FrameGeometry,
+    ) -> Result<FramePayload> {
+        let bytes = self.payload_buffers.pop().unwrap_or_default();
+        self.frame_span(index, geometry)
+            .and_then(|span| read_frame_span(&mut self.reader, index, span, geometry, bytes))
+            .map_err(|err| frame_read_error(err, index, geometry.sequence_count))
+    }
 
-        for y in 0..h {
-            let y_offset = y.checked_mul(row_pixels).ok_or_else(|| {
-                Nd2Error::file_invalid_format("Frame offset overflow".to_string())
-            })?;
-            let y_plane_offset = y.checked_mul(w).ok_or_else(|| {
-                Nd2Error::file_invalid_format("Frame plane offset overflow".to_string())
-            })?;
-            for x in 0..w {
-                let x_offset = x.checked_mul(n_c_n_comp).ok_or_else(|| {
-                    Nd2Error::file_invalid_format("Frame offset overflow".to_string())
+    /// Check frame `index`'s chunk for [`Nd2File::validate`]: `Ok(false)`
+    /// when it is too small to hold a frame, an error when its header or
+    /// (with `inflate`) its compressed data is damaged.
+    pub(crate) fn check_frame_chunk(
+        &mut self,
+        index: usize,
+        geometry: &FrameGeometry,
+        inflate: bool,
+    ) -> Result<bool> {
+        match self.frame_span(index, geometry)? {
+            FrameSpan::Compressed { len, .. } if len < 8 => Ok(false),
+            FrameSpan::Compressed { .. } if inflate => {
+                let payload = self.read_frame_payload(index, geometry)?;
+                let FramePayload::Compressed(data) = payload else {
+                    return Err(Nd2Error::internal_invariant(
+                        "compressed frame read as raw pixels",
+                    ));
+                };
+                let inflated = geometry.inflate(index, &data);
+                self.payload_buffers.push(data);
+                Ok(inflated?.into_buffer().len() >= geometry.pixel_bytes())
+            }
+            FrameSpan::Compressed { .. } => Ok(true),
+            FrameSpan::Raw {
+                timestamp_offset: None,
+                ..
+            } => Err(Nd2Error::file_invalid_format(format!(
+                "Frame chunk 'ImageDataSeq|{}!' has no valid chunk header",
+                index
+            ))),
+            FrameSpan::Raw { .. } => {
+                let (offset, _) = self.chunks.image(index).ok_or_else(|| {
+                    Nd2Error::file_chunk_not_found(format!("ImageDataSeq|{}!", index))
                 })?;
-                for c in 0..n_c {
-                    let c_offset = c.checked_mul(n_comp).ok_or_else(|| {
-                        Nd2Error::file_invalid_format("Frame offset overflow".to_string())
+                self.reader.seek(SeekFrom::Start(offset))?;
+                let header = crate::chunk::ChunkHeader::read(&mut self.reader)?;
+                Ok(header.data_length >= 8 + geometry.expected_raw as u64)
+            }
+        }
+    }
+
+    /// Locate frame `index`'s bytes, checking its chunk header against the
+    /// chunkmap and the file size until the header layout is confirmed.
+    fn frame_span(&mut self, index: usize, geometry: &FrameGeometry) -> Result<FrameSpan> {
+        let location = self.chunks.image(index);
+        if let Some(span) = self.derived_span(index, geometry) {
+            return Ok(span);
+        }
+        let span = locate_frame(&mut self.reader, index, location, geometry)?;
+        self.frame_spans.confirm(index, location, span);
+        Ok(span)
+    }
+
+    /// Span of frame `index` from the chunkmap and the confirmed header
+    /// layout, without reading its chunk header.
+    fn derived_span(&self, index: usize, geometry: &FrameGeometry) -> Option<FrameSpan> {
+        self.frame_spans.derive(
+            index,
+            self.chunks.image(index),
+            geometry,
+            self.chunks.file_size(),
+        )
+    }
+
+    /// Read one frame by sequence index with the given memory layout.
+    pub fn read_frame_ordered<T: Pixel>(
+        &mut self,
+        index: usize,
+        order: FrameOrder,
+    ) -> Result<Vec<T>> {
+        let planar = self.read_frame_as::<T>(index)?;
+        match order {
+            FrameOrder::Cyx => Ok(planar),
+            FrameOrder::Yxc => {
+                let (height, width) = self.shape()?;
+                let plane = height
+                    .checked_mul(width)
+                    .filter(|v| *v > 0)
+                    .ok_or_else(|| {
+                        Nd2Error::file_invalid_format("Invalid frame plane dimensions".to_string())
                     })?;
-                    for comp in 0..n_comp {
-                        let src_idx = y_offset
-                            .checked_add(x_offset)
-                            .and_then(|v| v.checked_add(c_offset))
-                            .and_then(|v| v.checked_add(comp))
-                            .ok_or_else(|| {
-                                Nd2Error::file_invalid_format("Frame offset overflow".to_string())
-                            })?;
-                        let dst_x = y_plane_offset.checked_add(x).ok_or_else(|| {
-                            Nd2Error::file_invalid_format("Frame offset overflow".to_string())
-                        })?;
-                        let c_plane = c_offset.checked_add(comp).ok_or_else(|| {
-                            Nd2Error::file_invalid_format("Frame offset overflow".to_string())
-                        })?;
-                        let dst_idx = c_plane
-                            .checked_mul(frame_area)
-                            .and_then(|v| v.checked_add(dst_x))
-                            .ok_or_else(|| {
-                                Nd2Error::file_invalid_format("Frame offset overflow".to_string())
-                            })?;
-                        out[dst_idx] = pixels[src_idx];
+                let n_chan = planar.len() / plane;
+                let mut out = vec![T::default(); planar.len()];
+                for (c, channel) in planar.chunks_exact(plane).enumerate() {
+                    for (i, &value) in channel.iter().enumerate() {
+                        out[i * n_chan + c] = value;
                     }
                 }
+                Ok(out)
             }
         }
+    }
 
+    /// Read every plane of one position as a single buffer in the given order.
+    ///
+    /// The buffer holds T × C × Z planes of Y × X u16 pixels, with axis
+    /// lengths as reported by [`Nd2File::summary`].
+    pub fn read_stack(&mut self, position: usize, order: StackOrder) -> Result<Vec<u16>> {
+        let sizes = self.sizes()?;
+        let get = |axis: &str| sizes.get(axis).copied().unwrap_or(1);
+        let (n_pos, n_time, n_chan, n_z) = (get(AXIS_P), get(AXIS_T), get(AXIS_C), get(AXIS_Z));
+        if position >= n_pos {
+            return Err(Nd2Error::input_out_of_range(
+                "position index",
+                position,
+                n_pos,
+            ));
+        }
+        let plane = get(AXIS_Y)
+            .checked_mul(get(AXIS_X))
+            .ok_or_else(|| Nd2Error::internal_overflow("stack plane size"))?;
+        let total = [n_time, n_chan, n_z]
+            .iter()
+            .try_fold(plane, |acc, &n| acc.checked_mul(n))
+            .ok_or_else(|| Nd2Error::internal_overflow("stack size"))?;
+
+        let mut out = vec![0u16; total];
+        for t in 0..n_time {
+            let planes: Vec<[usize; 4]> = (0..n_chan)
+                .flat_map(|c| (0..n_z).map(move |z| [position, t, c, z]))
+                .collect();
+            for (&[_, _, c, z], pixels) in planes.iter().zip(self.read_planes(&planes)?) {
+                let slot = match order {
+                    StackOrder::Tczyx => (t * n_chan + c) * n_z + z,
+                    StackOrder::Tzcyx => (t * n_z + z) * n_chan + c,
+                };
+                let start = slot * plane;
+                out[start..start + plane].copy_from_slice(&pixels);
+            }
+        }
         Ok(out)
     }
 
-    fn read_uncompressed_frame_bytes(
-        &mut self,
-        chunk_key: &[u8],
-        expected_raw: usize,
-    ) -> Result<Vec<u8>> {
-        let file_size = self.reader.seek(SeekFrom::End(0))?;
-        let offset = self
-            .chunkmap
-            .get(chunk_key)
-            .map(|(offset, _)| *offset)
-            .ok_or_else(|| Nd2Error::file_chunk_not_found(String::from_utf8_lossy(chunk_key)))?;
-
-        let pixel_offset = match self.read_image_chunk_payload_offset(offset)? {
-            Some(payload_offset) => payload_offset.checked_add(8).ok_or_else(|| {
-                Nd2Error::file_invalid_format("Frame payload offset overflow".to_string())
-            })?,
-            None => offset.checked_add(4096).ok_or_else(|| {
-                Nd2Error::file_invalid_format("Frame fallback offset overflow".to_string())
-            })?,
-        };
+    #[cfg(feature = "ndarray")]
+    /// Read one frame by sequence index as a (C, Y, X) array.
+    pub fn read_frame_array(&mut self, index: usize) -> Result<ndarray::Array3<u16>> {
+        let (height, width) = self.shape()?;
+        let pixels = self.read_frame(index)?;
+        let plane = height
+            .checked_mul(width)
+            .filter(|v| *v > 0)
+            .ok_or_else(|| {
+                Nd2Error::file_invalid_format("Invalid frame plane dimensions".to_string())
+            })?;
+        let n_planes = pixels.len() / plane;
+        ndarray::Array3::from_shape_vec((n_planes, height, width), pixels)
+            .map_err(|e| Nd2Error::file_invalid_format(format!("Frame shape mismatch: {e}")))
+    }
 
-        let pixel_end = pixel_offset
-            .checked_add(expected_raw as u64)
-            .ok_or_else(|| Nd2Error::file_invalid_format("Frame bounds overflow".to_string()))?;
-        if pixel_end > file_size {
-            return Err(Nd2Error::file_invalid_format(format!(
-                "Frame chunk '{}' exceeds file bounds",
-                String::from_utf8_lossy(chunk_key)
-            )));
-        }
+    #[cfg(feature = "ndarray")]
+    /// Read the whole file as a (P, T, C, Z, Y, X) array of u16 pixels.
+    pub fn to_array(&mut self) -> Result<ndarray::ArrayD<u16>> {
+        self.to_array_as::<u16>()
+    }
 
-        self.reader.seek(SeekFrom::Start(pixel_offset))?;
-        let mut pixel_bytes = vec![0u8; expected_raw];
-        self.reader.read_exact(&mut pixel_bytes)?;
-        Ok(pixel_bytes)
+    #[cfg(feature = "ndarray")]
+    /// Read the whole file as a (P, T, C, Z, Y, X) array of components of
+    /// type `T`, e.g. `f32` for float files.
+    ///
+    /// Axes the file has no loop for have length 1, so indices are the same
+    /// as for [`Nd2File::read_frame_2d`]. Frames are read one time point at
+    /// a time; the array itself holds every plane.
+    pub fn to_array_as<T: Pixel>(&mut self) -> Result<ndarray::ArrayD<T>> {
+        let sizes = self.sizes()?;
+        let shape: Vec<usize> = [AXIS_P, AXIS_T, AXIS_C, AXIS_Z, AXIS_Y, AXIS_X]
+            .iter()
+            .map(|axis| sizes.get(*axis).copied().unwrap_or(1))
+            .collect();
+        let (n_pos, n_time, n_chan, n_z) = (shape[0], shape[1], shape[2], shape[3]);
+        let total = shape
+            .iter()
+            .try_fold(1usize, |acc, &n| acc.checked_mul(n))
+            .ok_or_else(|| Nd2Error::internal_overflow("array size"))?;
+        let mut out = Vec::with_capacity(total);
+        for p in 0..n_pos {
+            for t in 0..n_time {
+                let planes: Vec<[usize; 4]> = (0..n_chan)
+                    .flat_map(|c| (0..n_z).map(move |z| [p, t, c, z]))
+                    .collect();
+                for plane in self.read_planes_as::<T>(&planes)? {
+                    out.extend_from_slice(&plane);
+                }
+            }
+        }
+        ndarray::ArrayD::from_shape_vec(ndarray::IxDyn(&shape), out)
+            .map_err(|e| Nd2Error::file_invalid_format(format!("Array shape mismatch: {e}")))
     }
 
     /// Build axis order and coord shape for seq_index (chunk lookup).
     /// sequence_count = number of ImageDataSeq chunks. When channels are "in-pixel"
     /// (stored within each chunk), sequence_count = product(experiment loops) and we
     /// must NOT include C in axis_order for chunk indexing.
-    fn coord_axis_order(&mut self) -> Result<(Vec<&'static str>, Vec<usize>)> {
-        let attrs = self.attributes()?.clone();
-        let exp = self.experiment()?.clone();
+    fn coord_axis_order(attrs: &Attributes, exp: &[ExpLoop]) -> (Vec<&'static str>, Vec<usize>) {
         let n_chan = attrs.channel_count.unwrap_or(attrs.component_count) as usize;
         let seq_count = attrs.sequence_count as usize;
 
@@ -509,7 +2574,7 @@ impl Nd2File {
             axis_order.extend([AXIS_P, AXIS_T, AXIS_C, AXIS_Z]);
             coord_shape.extend([n_pos, n_time, n_chan, n_z]);
         } else {
-            for loop_ in &exp {
+            for loop_ in exp {
                 match loop_ {
                     crate::types::ExpLoop::TimeLoop(t) => {
                         axis_order.push(AXIS_T);
@@ -544,8 +2609,14 @@ impl Nd2File {
                 coord_shape.push(1);
             }
             // Only add C (and ensure Z) when sequence_count indicates chunks span channel
-            let exp_product: usize = coord_shape.iter().product();
-            if exp_product > 0 && exp_product * n_chan <= seq_count {
+            let exp_product = coord_shape
+                .iter()
+                .try_fold(1usize, |acc, &n| acc.checked_mul(n))
+                .unwrap_or(usize::MAX);
+            let spans_channels = exp_product
+                .checked_mul(n_chan)
+                .is_some_and(|total| total <= seq_count);
+            if exp_product > 0 && spans_channels {
                 axis_order.push(AXIS_C);
                 coord_shape.push(n_chan);
             }
@@ -555,86 +2626,253 @@ impl Nd2File {
             }
         }
 
-        Ok((axis_order, coord_shape))
+        (axis_order, coord_shape)
     }
 
     /// Compute sequence index from (p,t,c,z) using experiment loop order (matching nd2-py).
-    fn seq_index_from_coords(&mut self, p: usize, t: usize, c: usize, z: usize) -> Result<usize> {
-        let (axis_order, coord_shape) = self.coord_axis_order()?;
-        let coords: Vec<usize> = axis_order
-            .iter()
-            .map(|&ax| match ax {
-                AXIS_P => p,
-                AXIS_T => t,
-                AXIS_C => c,
-                AXIS_Z => z,
-                _ => 0,
-            })
-            .collect();
-
-        if coords.len() != coord_shape.len() {
-            return Err(Nd2Error::file_invalid_format(
-                "Coord/axis length mismatch".to_string(),
-            ));
-        }
+    pub(crate) fn seq_index_from_coords(
+        &mut self,
+        p: usize,
+        t: usize,
+        c: usize,
+        z: usize,
+    ) -> Result<usize> {
+        index_seq(self.frame_index()?, p, t, c, z)
+    }
 
-        for (idx, (&coord, &shape)) in coords.iter().zip(coord_shape.iter()).enumerate() {
-            if shape == 0 {
-                return Err(Nd2Error::file_invalid_format(format!(
-                    "Invalid axis length: {} has size 0",
-                    axis_order[idx]
-                )));
+    /// Timestamp (ms) stored at the start of a frame chunk's payload,
+    /// without decoding pixels. `None` when the chunk header is missing.
+    pub(crate) fn frame_timestamp(&mut self, index: usize) -> Result<Option<f64>> {
+        let derived = match self.geometry() {
+            Ok(geometry) => self.derived_span(index, &geometry),
+            Err(_) => None,
+        };
+        let payload_offset = match derived {
+            Some(FrameSpan::Compressed { data_offset, .. }) => Some(data_offset),
+            Some(FrameSpan::Raw {
+                timestamp_offset, ..
+            }) => timestamp_offset,
+            None => {
+                let name = format!("ImageDataSeq|{}!", index);
+                let (offset, _) = self
+                    .chunks
+                    .image(index)
+                    .ok_or_else(|| Nd2Error::file_chunk_not_found(&name))?;
+                let file_size = self.chunks.file_size();
+                crate::chunk::check_chunk_offset(name.as_bytes(), offset, file_size)?;
+                image_chunk_payload_offset(&mut self.reader, offset)?
             }
-            if coord >= shape {
-                return Err(Nd2Error::input_out_of_range(
-                    format!("axis {}", axis_order[idx]),
-                    coord,
-                    shape,
-                ));
+        };
+        match payload_offset {
+            Some(payload_offset) => {
+                self.reader.seek(SeekFrom::Start(payload_offset))?;
+                let mut bytes = [0u8; 8];
+                self.reader.read_exact(&mut bytes)?;
+                Ok(Some(f64::from_le_bytes(bytes)))
             }
+            None => Ok(None),
         }
+    }
+
+    /// Size and CRC-32C of the whole source, read from the start.
+    pub(crate) fn file_crc32c(&mut self) -> Result<(u64, u32)> {
+        let file_size = self.reader.seek(SeekFrom::End(0))?;
+        Ok((file_size, self.crc32c_range(0, file_size)?))
+    }
+
+    /// (offset, size) of the chunk named `name`.
+    pub(crate) fn chunk_location(&self, name: &[u8]) -> Option<(u64, u64)> {
+        self.chunks.get(name)
+    }
+
+    /// Every indexed chunk as (name, offset, size), in file order.
+    pub(crate) fn chunk_entries(&self) -> Vec<(Vec<u8>, u64, u64)> {
+        self.chunks.entries()
+    }
+
+    /// CRC-32C of the chunkmap sections indexed.
+    pub(crate) fn chunkmap_crc32c(&self) -> u32 {
+        self.chunks.sections_crc32c()
+    }
 
-        let mut seq = 0usize;
-        let mut stride = 1;
-        for i in (0..coords.len()).rev() {
-            let next = coords[i]
-                .checked_mul(stride)
-                .ok_or_else(|| Nd2Error::internal_overflow("sequence index multiply"))?;
-            seq = seq
-                .checked_add(next)
-                .ok_or_else(|| Nd2Error::internal_overflow("sequence index add"))?;
-            stride = stride
-                .checked_mul(coord_shape[i])
-                .ok_or_else(|| Nd2Error::internal_overflow("sequence stride multiply"))?;
+    /// CRC-32C of every frame chunk in the chunkmap, header included, by
+    /// sequence index: `None` for a chunk whose header is damaged or whose
+    /// data runs past the end of the file.
+    pub(crate) fn frame_crc32cs(&mut self) -> Result<BTreeMap<usize, Option<u32>>> {
+        let file_size = self.chunks.file_size();
+        let mut digests = BTreeMap::new();
+        for (index, (offset, _)) in self.chunks.frame_chunks() {
+            let end = if offset.saturating_add(ChunkHeader::SIZE as u64) <= file_size {
+                self.reader.seek(SeekFrom::Start(offset))?;
+                ChunkHeader::read(&mut self.reader)
+                    .ok()
+                    .filter(|header| header.magic == ND2_CHUNK_MAGIC)
+                    .and_then(|header| header.end(offset))
+                    .filter(|&end| end <= file_size)
+            } else {
+                None
+            };
+            let digest = match end {
+                Some(end) => Some(self.crc32c_range(offset, end - offset)?),
+                None => None,
+            };
+            digests.insert(index, digest);
         }
-        Ok(seq)
+        Ok(digests)
     }
 
-    fn read_image_chunk_payload_offset(&mut self, offset: u64) -> Result<Option<u64>> {
+    /// CRC-32C of `len` bytes of the source at `offset`, read in blocks.
+    fn crc32c_range(&mut self, offset: u64, len: u64) -> Result<u32> {
+        const BLOCK: u64 = 1 << 20;
+        let mut crc = Crc32c::new();
+        let mut buf = vec![0u8; len.min(BLOCK) as usize];
         self.reader.seek(SeekFrom::Start(offset))?;
+        let mut left = len;
+        while left > 0 {
+            let block = &mut buf[..left.min(BLOCK) as usize];
+            self.reader.read_exact(block)?;
+            crc.update(block);
+            left -= block.len() as u64;
+        }
+        Ok(crc.finish())
+    }
 
-        let header = match crate::chunk::ChunkHeader::read(&mut self.reader) {
-            Ok(header) => header,
-            Err(_) => return Ok(None),
-        };
+    /// Read 2D Y×X frame at (p,t,c,z). Returns the Y×X pixels for the requested channel.
+    pub fn read_frame_2d(&mut self, p: usize, t: usize, c: usize, z: usize) -> Result<Vec<u16>> {
+        self.read_planes_with(&[[p, t, c, z]], false)?
+            .swap_remove(0)
+            .ok_or_else(|| Nd2Error::internal_invariant("plane of an unskipped read missing"))
+    }
 
-        if header.magic != ND2_CHUNK_MAGIC {
-            return Ok(None);
+    /// Read the Y×X plane at `coord`, as [`Nd2File::read_frame_2d`] does.
+    pub fn read_frame_at(&mut self, coord: &FrameCoord) -> Result<Vec<u16>> {
+        self.read_frame_2d(coord.p, coord.t, coord.c, coord.z)
+    }
+
+    /// Sequence index of the frame holding the plane at `coord`.
+    ///
+    /// Each coordinate is checked against the axis lengths of
+    /// [`Nd2File::summary`]; an out-of-range one is an
+    /// [`InputError::OutOfRange`](crate::InputError::OutOfRange) naming the
+    /// axis.
+    pub fn seq_index_for(&mut self, coord: &FrameCoord) -> Result<usize> {
+        Ok(self.plane_location(coord.p, coord.t, coord.c, coord.z)?.0)
+    }
+
+    /// Read several Y×X planes given as `[p, t, c, z]`, in the given order.
+    ///
+    /// Each frame is decoded once however many of its channels are
+    /// requested, and compressed frames are decompressed concurrently.
+    /// Planes of frames skipped under [`Nd2Options::skip_bad_frames`] are
+    /// zero-filled.
+    pub(crate) fn read_planes(&mut self, planes: &[[usize; 4]]) -> Result<Vec<Vec<u16>>> {
+        self.read_planes_as::<u16>(planes)
+    }
+
+    /// Like [`Nd2File::read_planes`], as components of type `T`.
+    pub(crate) fn read_planes_as<T: Pixel>(
+        &mut self,
+        planes: &[[usize; 4]],
+    ) -> Result<Vec<Vec<T>>> {
+        let skip_bad = self.options.skip_bad_frames;
+        let read = self.read_planes_with::<T>(planes, skip_bad)?;
+        let (height, width) = self.shape()?;
+        Ok(read
+            .into_iter()
+            .map(|plane| plane.unwrap_or_else(|| vec![T::default(); height * width]))
+            .collect())
+    }
+
+    /// Like [`Nd2File::read_planes`], with `None` for the planes of frames
+    /// skipped under [`Nd2Options::skip_bad_frames`], for callers that leave
+    /// them out rather than zero-fill them.
+    #[cfg(feature = "ffmpeg")]
+    pub(crate) fn read_planes_or_skip(
+        &mut self,
+        planes: &[[usize; 4]],
+    ) -> Result<Vec<Option<Vec<u16>>>> {
+        let skip_bad = self.options.skip_bad_frames;
+        self.read_planes_with::<u16>(planes, skip_bad)
+    }
+
+    fn read_planes_with<T: Pixel>(
+        &mut self,
+        planes: &[[usize; 4]],
+        skip_bad: bool,
+    ) -> Result<Vec<Option<Vec<T>>>> {
+        let mut seq_indices = Vec::with_capacity(planes.len());
+        let mut len = 0;
+        for &[p, t, c, z] in planes {
+            let (seq_index, plane_len) = self.plane_location(p, t, c, z)?;
+            seq_indices.push(seq_index);
+            len = plane_len;
         }
+        let mut unique = seq_indices.clone();
+        unique.sort_unstable();
+        unique.dedup();
+        let frames = if skip_bad {
+            self.read_frames_or_skip::<T>(&unique)?
+        } else {
+            self.read_frames_decoded::<T>(&unique)?
+                .into_iter()
+                .map(|(pixels, _)| Some(pixels))
+                .collect()
+        };
 
-        let payload_offset = offset
-            .checked_add(16)
-            .and_then(|v| v.checked_add(header.name_length as u64))
-            .ok_or_else(|| {
-                Nd2Error::file_invalid_format("Frame payload offset overflow".to_string())
-            })?;
+        planes
+            .iter()
+            .zip(&seq_indices)
+            .map(|(&[_, _, c, _], seq_index)| {
+                let slot = unique
+                    .binary_search(seq_index)
+                    .map_err(|_| Nd2Error::internal_overflow("plane frame index"))?;
+                let Some(frame) = &frames[slot] else {
+                    return Ok(None);
+                };
+
+                // Frame is (C,Y,X) planar: channel c is at [c*len..(c+1)*len]
+                let start = c.checked_mul(len).ok_or_else(|| {
+                    Nd2Error::file_invalid_format("Frame slice start overflow".to_string())
+                })?;
+                let end = (c + 1).checked_mul(len).ok_or_else(|| {
+                    Nd2Error::file_invalid_format("Frame slice end overflow".to_string())
+                })?;
+                if end > frame.len() {
+                    return Err(Nd2Error::file_invalid_format(format!(
+                        "Frame data too short for requested channel: frame {} < {}",
+                        frame.len(),
+                        end
+                    )));
+                }
+                Ok(Some(frame[start..end].to_vec()))
+            })
+            .collect()
+    }
 
-        Ok(Some(payload_offset))
+    /// Decode `indices` as u16 frames, `None` for each frame that cannot be
+    /// read or decoded, skipped with [`Nd2File::skip_bad_frame`]. A failed
+    /// batch is read again frame by frame to tell the bad frames apart.
+    fn read_frames_or_skip<T: Pixel>(&mut self, indices: &[usize]) -> Result<Vec<Option<Vec<T>>>> {
+        // Errors not tied to a frame, such as an unsupported pixel type,
+        // fail the read as usual.
+        self.frame_geometry::<T>()?;
+        if let Ok(frames) = self.read_frames_decoded::<T>(indices) {
+            return Ok(frames.into_iter().map(|(pixels, _)| Some(pixels)).collect());
+        }
+        indices
+            .iter()
+            .map(|&index| match self.read_frames_decoded::<T>(&[index]) {
+                Ok(mut frame) => Ok(Some(frame.swap_remove(0).0)),
+                Err(err) => self.skip_bad_frame(index, err).map(|()| None),
+            })
+            .collect()
     }
 
-    /// Read 2D Y×X frame at (p,t,c,z). Returns the Y×X pixels for the requested channel.
-    pub fn read_frame_2d(&mut self, p: usize, t: usize, c: usize, z: usize) -> Result<Vec<u16>> {
-        let sizes = self.sizes()?;
+    /// Validate (p,t,c,z) and return its frame's sequence index and the
+    /// Y×X plane length.
+    fn plane_location(&mut self, p: usize, t: usize, c: usize, z: usize) -> Result<(usize, usize)> {
+        let layout = self.frame_layout()?;
+        let sizes = &layout.sizes;
         let height = *sizes.get(AXIS_Y).ok_or_else(|| {
             Nd2Error::file_invalid_format("Missing height (Y) dimension".to_string())
         })?;
@@ -667,28 +2905,32 @@ impl Nd2File {
             return Err(Nd2Error::input_out_of_range("z index", z, n_z));
         }
 
-        let seq_index = self.seq_index_from_coords(p, t, c, z)?;
+        let seq_index = index_seq(&layout.index, p, t, c, z)?;
 
-        let frame = self.read_frame(seq_index)?;
         let len = height.checked_mul(width).ok_or_else(|| {
             Nd2Error::file_invalid_format("Frame dimensions overflow".to_string())
         })?;
+        Ok((seq_index, len))
+    }
 
-        // Frame is (C,Y,X) planar: channel c is at [c*len..(c+1)*len]
-        let start = c.checked_mul(len).ok_or_else(|| {
-            Nd2Error::file_invalid_format("Frame slice start overflow".to_string())
-        })?;
-        let end = (c + 1)
-            .checked_mul(len)
-            .ok_or_else(|| Nd2Error::file_invalid_format("Frame slice end overflow".to_string()))?;
-        if end > frame.len() {
-            return Err(Nd2Error::file_invalid_format(format!(
-                "Frame data too short for requested channel: frame {} < {}",
-                frame.len(),
-                end
-            )));
-        }
-        Ok(frame[start..end].to_vec())
+    #[cfg(feature = "image")]
+    /// Read the 2D plane at (p,t,c,z) as a 16-bit grayscale [`image::ImageBuffer`].
+    pub fn read_frame_image(
+        &mut self,
+        p: usize,
+        t: usize,
+        c: usize,
+        z: usize,
+    ) -> Result<image::ImageBuffer<image::Luma<u16>, Vec<u16>>> {
+        let (height, width) = self.shape()?;
+        let pixels = self.read_frame_2d(p, t, c, z)?;
+        let width = u32::try_from(width)
+            .map_err(|_| Nd2Error::file_invalid_format("Frame width exceeds u32".to_string()))?;
+        let height = u32::try_from(height)
+            .map_err(|_| Nd2Error::file_invalid_format("Frame height exceeds u32".to_string()))?;
+        image::ImageBuffer::from_raw(width, height, pixels).ok_or_else(|| {
+            Nd2Error::file_invalid_format("Frame buffer does not match plane shape".to_string())
+        })
     }
 
     fn read_version<R: Read + Seek>(reader: &mut R) -> Result<(u32, u32)> {
@@ -742,8 +2984,130 @@ impl Nd2File {
     }
 }
 
+/// Format version of a modern chunk layer found in a legacy container,
+/// from the names of its attribute chunk; `None` when it has neither.
+fn modern_layer_version(chunks: &ChunkIndex) -> Option<(u32, u32)> {
+    if chunks.contains(b"ImageAttributesLV!") {
+        Some((3, 0))
+    } else if chunks.contains(b"ImageAttributes!") {
+        Some((2, 0))
+    } else {
+        None
+    }
+}
+
+/// Bytes of a `CustomData|` array of one 8-byte value per frame, for
+/// `sequence_count` frames.
+fn custom_data_len(chunk_name: &str, sequence_count: usize) -> Result<usize> {
+    sequence_count.checked_mul(8).ok_or_else(|| {
+        Nd2Error::file_invalid_format(format!(
+            "{} for {} frames overflows the address space",
+            chunk_name, sequence_count
+        ))
+    })
+}
+
+/// Sequence index of plane (p, t, c, z) in `index`; axes it is not
+/// indexed by are ignored.
+fn index_seq(index: &FrameIndex, p: usize, t: usize, c: usize, z: usize) -> Result<usize> {
+    let coords: Vec<usize> = index
+        .axes
+        .iter()
+        .map(|axis| match axis.as_str() {
+            AXIS_P => p,
+            AXIS_T => t,
+            AXIS_C => c,
+            AXIS_Z => z,
+            _ => 0,
+        })
+        .collect();
+    index.seq_index(&coords)
+}
+
+/// Whether chunk `name` holds CLX metadata, for
+/// [`Nd2File::unstructured_metadata`]: per-frame picture metadata only for
+/// the first frame.
+fn is_metadata_chunk(name: &[u8]) -> bool {
+    if name.starts_with(b"ImageMetadataSeq") {
+        return name.ends_with(b"|0!");
+    }
+    is_clx_chunk(name)
+}
+
+/// Whether chunk `name` is stored as CLX. Frames, the numeric
+/// `CustomData|` arrays and binary layers are not.
+pub(crate) fn is_clx_chunk(name: &[u8]) -> bool {
+    if name.starts_with(b"ImageDataSeq") {
+        return false;
+    }
+    name.starts_with(b"Image")
+        || name.starts_with(b"CustomDataVar|")
+        || name == b"CustomData|RoiMetadata_v1!"
+        || EVENTS_CHUNKS.contains(&name)
+}
+
+/// Bytes between the data of one frame chunk and the next (the next chunk's
+/// header and name, and any alignment padding) that a coalesced read may
+/// read through.
+const MAX_COALESCE_GAP: u64 = 64 * 1024;
+
+impl TryFrom<&Path> for Nd2File {
+    type Error = Nd2Error;
+
+    fn try_from(path: &Path) -> Result<Self> {
+        Self::open(path)
+    }
+}
+
+impl TryFrom<File> for Nd2File {
+    type Error = Nd2Error;
+
+    fn try_from(file: File) -> Result<Self> {
+        Self::open_file(file)
+    }
+}
+
+/// Summarises the file instead of dumping reader and chunk map internals.
+///
+/// Formatting never touches the file, so metadata that has not been loaded
+/// (or is not cached) is omitted.
+impl fmt::Debug for Nd2File {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut out = f.debug_struct("Nd2File");
+        out.field("version", &self.version);
+        if let Some(attrs) = &self.attributes {
+            let sizes = match (&self.frame_layout, &self.experiment) {
+                (Some(layout), _) => Some(layout.sizes.clone()),
+                (None, Some(exp)) => Some(Self::sizes_from(attrs, exp)),
+                (None, None) => None,
+            };
+            if let Some(sizes) = sizes {
+                let dims: Vec<String> = [AXIS_P, AXIS_T, AXIS_C, AXIS_Z, AXIS_Y, AXIS_X]
+                    .iter()
+                    .map(|axis| format!("{}={}", axis, sizes.get(*axis).copied().unwrap_or(1)))
+                    .collect();
+                out.field("dims", &format_args!("{}", dims.join(" ")));
+            } else {
+                out.field("frames", &attrs.sequence_count);
+                out.field("height", &attrs.height_px);
+            }
+            out.field(
+                "channels",
+                &attrs.channel_count.unwrap_or(attrs.component_count),
+            );
+            out.field("compression", &attrs.compression_type);
+        }
+        out.field("chunks", &self.chunks.len());
+        if self.attributes.is_none() {
+            out.finish_non_exhaustive()
+        } else {
+            out.finish()
+        }
+    }
+}
+
 impl Drop for Nd2File {
     fn drop(&mut self) {
-        // File is automatically closed when BufReader<File> is dropped
+        // The file is closed when the reader (and any shared handle) is dropped
     }
 }
@@ -0,0 +1,98 @@
+//! Repaired copies of damaged files.
+
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{BufWriter, Seek, SeekFrom};
+use std::path::Path;
+
+use crate::chunk::{image_seq_index, ChunkHeader, ChunkWriter};
+use crate::constants::ND2_FILE_SIGNATURE;
+use crate::error::{Nd2Error, Result};
+use crate::io::same_file;
+use crate::options::Nd2Options;
+use crate::reader::Nd2File;
+use crate::types::{DiagnosticKind, RepairReport};
+
+/// Copy the chunks of the file at `src` that can still be read to a new
+/// file at `dst`, with a chunkmap listing them, and report what was kept.
+///
+/// `src` is opened with [`Nd2Options::allow_recovery`], so a file whose
+/// chunkmap is missing or corrupt, as after a crashed acquisition, has its
+/// chunks found by scanning for chunk headers. Chunks are copied byte for
+/// byte, leaving out those cut off by the end of the file; anything else
+/// in the source, such as a truncated tail, is not copied. `dst` opens
+/// without recovery.
+pub fn repair_to<P: AsRef<Path>, Q: AsRef<Path>>(src: P, dst: Q) -> Result<RepairReport> {
+    if same_file(src.as_ref(), dst.as_ref()) {
+        return Err(Nd2Error::input_argument(
+            "dst",
+            "repaired copy would overwrite its source",
+        ));
+    }
+    let nd2 = Nd2File::open_with(src.as_ref(), Nd2Options::new().allow_recovery(true))?;
+    if nd2.is_legacy() {
+        let (major, minor) = nd2.version();
+        return Err(Nd2Error::unsupported_version(major, minor));
+    }
+    let chunkmap_rebuilt = nd2
+        .diagnostics()
+        .iter()
+        .any(|diagnostic| diagnostic.kind == DiagnosticKind::ChunkmapRecovered);
+    let entries = nd2.chunk_entries();
+    drop(nd2);
+
+    let mut source = File::open(src.as_ref())?;
+    let file_size = source.seek(SeekFrom::End(0))?;
+    let mut writer = ChunkWriter::new(BufWriter::new(File::create(dst.as_ref())?));
+    writer.copy_chunk(&mut source, ND2_FILE_SIGNATURE, 0)?;
+    let mut report = RepairReport {
+        chunkmap_rebuilt,
+        chunks_copied: 0,
+        frames_copied: 0,
+        chunks_dropped: Vec::new(),
+        missing_frames: Vec::new(),
+        bytes_discarded: 0,
+    };
+    let mut frames = HashSet::new();
+    for (name, offset, _) in entries {
+        if !is_complete(&mut source, offset, file_size) {
+            report
+                .chunks_dropped
+                .push(String::from_utf8_lossy(&name).into_owned());
+            continue;
+        }
+        writer.copy_chunk(&mut source, &name, offset)?;
+        report.chunks_copied += 1;
+        if let Some(index) = image_seq_index(&name) {
+            frames.insert(index);
+        }
+    }
+    report.frames_copied = frames.len();
+    report.bytes_discarded = file_size.saturating_sub(writer.position());
+    writer
+        .finish()?
+        .into_inner()
+        .map_err(|err| err.into_error())?;
+
+    let counts = Nd2File::open(dst.as_ref())?.frame_counts()?;
+    report.missing_frames = (0..counts.declared.max(counts.planned))
+        .filter(|index| !frames.contains(index))
+        .collect();
+    Ok(report)
+}
+
+/// Whether a chunk with a valid header starts at `offset` and ends inside
+/// the file.
+fn is_complete(source: &mut File, offset: u64, file_size: u64) -> bool {
+    let header = source
+        .seek(SeekFrom::Start(offset))
+        .map_err(Nd2Error::from)
+        .and_then(|_| ChunkHeader::read(source));
+    match header {
+        Ok(header) => {
+            header.validate_magic().is_ok()
+                && header.end(offset).is_some_and(|end| end <= file_size)
+        }
+        Err(_) => false,
+    }
+}
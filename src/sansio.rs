@@ -0,0 +1,19 @@
+//! I/O-free parsing core.
+//!
+//! Functions here operate on byte slices only, so they can be driven from
+//! async runtimes, WASM hosts or fuzzers without a `Read + Seek` source.
+//! [`crate::Nd2File`] is a thin I/O shell around the same functions.
+//!
+//! A typical flow: fetch the last [`CHUNKMAP_TRAILER_LEN`] bytes and pass
+//! them to [`parse_chunkmap_trailer`], fetch the section at the returned
+//! offset through to the end of the file and parse it with
+//! [`parse_chunkmap_section`], then fetch chunks by the recorded offsets and
+//! decode metadata with [`ClxLiteParser`], either into owned [`ClxValue`]
+//! trees or, without copying strings, into borrowed [`ClxValueRef`] trees.
+
+pub use crate::chunk::{
+    chunk_data, parse_chunkmap, parse_chunkmap_section, parse_chunkmap_trailer, ChunkHeader,
+    ChunkMap, CHUNKMAP_TRAILER_LEN,
+};
+pub use crate::options::Limits;
+pub use crate::parse::{ClxLiteParser, ClxObject, ClxValue, ClxValueRef, ClxVisitor, Utf16Str};
@@ -0,0 +1,550 @@
+//! Copies of files holding a selection of their frames.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::ops::Range;
+use std::path::Path;
+
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+
+use crate::chunk::{frame_chunk_index, image_seq_index, ChunkWriter};
+use crate::constants::ND2_FILE_SIGNATURE;
+use crate::decode::{FrameGeometry, FramePayload};
+use crate::error::{Nd2Error, Result};
+use crate::io::same_file;
+use crate::options::SubsetSelection;
+use crate::parse::{ClxEdit, ClxLiteParser, ClxValue, ClxValueRef};
+use crate::reader::{Nd2File, AXIS_C, AXIS_P, AXIS_T};
+
+/// Copy the frames of the file at `src` picked by `selection` to a new file
+/// at `dst`, such as one well of a multi-well acquisition.
+///
+/// Kept frames are renumbered in their original order, along with their
+/// other per-frame chunks (picture metadata and `CustomDataSeq|` data such
+/// as binary masks); those of dropped frames are left out. The sequence
+/// count, the counts of the time and XY position loops and the list of XY
+/// points are rewritten to match, so `dst` opens with the narrowed shape.
+/// Dropping channels also rewrites the pixel rows of every frame
+/// (recompressing lossless ones; lossy ones cannot be rewritten), the image
+/// attributes and the plane list of every picture metadata chunk; otherwise
+/// frames are copied byte for byte. Other metadata is copied as it is,
+/// except `CustomData|` arrays of 4 or 8 byte values, one per frame, which
+/// keep the values of the kept frames.
+pub fn subset_to<P: AsRef<Path>, Q: AsRef<Path>>(
+    src: P,
+    dst: Q,
+    selection: &SubsetSelection,
+) -> Result<()> {
+    if same_file(src.as_ref(), dst.as_ref()) {
+        return Err(Nd2Error::input_argument(
+            "dst",
+            "subset would overwrite its source",
+        ));
+    }
+    let mut nd2 = Nd2File::open(src.as_ref())?;
+    if nd2.is_legacy() {
+        let (major, minor) = nd2.version();
+        return Err(Nd2Error::unsupported_version(major, minor));
+    }
+    let plan = SubsetPlan::new(&mut nd2, selection)?;
+    let geometry = nd2.geometry()?;
+    let attributes_name = nd2.attributes_chunk_name();
+    let experiment_name = nd2.experiment_chunk_name();
+    let planes_name = nd2.picture_metadata_chunk_name();
+    let parser = ClxLiteParser::new(false).limits(nd2.options().limits);
+    let mut source = File::open(src.as_ref())?;
+    let mut writer = ChunkWriter::new(BufWriter::new(File::create(dst.as_ref())?));
+    writer.copy_chunk(&mut source, ND2_FILE_SIGNATURE, 0)?;
+
+    let (planes_prefix, _) = frame_chunk_index(planes_name).ok_or_else(|| {
+        Nd2Error::internal_invariant("picture metadata chunk name has no sequence index")
+    })?;
+    let entries = nd2.chunk_entries();
+    // The picture metadata of frame 0 also describes the whole file, so it
+    // stays when the first kept frame has none of its own.
+    let first_planes = plan
+        .kept
+        .first()
+        .map(|&seq| frame_chunk_name(planes_prefix, seq));
+    let keep_planes = !entries
+        .iter()
+        .any(|(name, _, _)| Some(name) == first_planes.as_ref());
+
+    for (name, offset, _) in entries {
+        if let Some((prefix, seq)) = frame_chunk_index(&name) {
+            let new_seq = match plan.renumber.get(&seq) {
+                Some(&new_seq) => new_seq,
+                None if name == planes_name && keep_planes => 0,
+                None => continue,
+            };
+            let new_name = frame_chunk_name(prefix, new_seq);
+            if image_seq_index(&name).is_some() {
+                let mut data = nd2.read_chunk(&name)?;
+                if let Some(channels) = &plan.channels {
+                    data = select_channels(&geometry, seq, &data, channels)?;
+                }
+                writer.write_chunk(&new_name, &data)?;
+            } else if prefix == planes_prefix && plan.channels.is_some() {
+                let data = nd2.read_chunk(&name)?;
+                let mut edit = PlanesEdit {
+                    channels: plan.channels.as_deref().unwrap_or_default(),
+                };
+                writer.write_chunk(&new_name, &parser.rewrite(&data, &mut edit)?)?;
+            } else if new_name == name {
+                writer.copy_chunk(&mut source, &name, offset)?;
+            } else {
+                writer.write_chunk(&new_name, &nd2.read_chunk(&name)?)?;
+            }
+        } else if name == attributes_name {
+            let data = nd2.read_chunk(&name)?;
+            let mut edit = AttributesEdit {
+                sequence_count: plan.kept.len() as u64,
+                channels: plan.channels.as_ref().map(|channels| ChannelAttributes {
+                    components: plan.components_per_channel * channels.len() as u64,
+                    channels: channels.len() as u64,
+                    width_bytes: geometry.channel_row_bytes(channels.len()) as u64,
+                }),
+            };
+            writer.write_chunk(&name, &parser.rewrite(&data, &mut edit)?)?;
+        } else if name == experiment_name && (plan.positions.is_some() || plan.time.is_some()) {
+            let data = nd2.read_chunk(&name)?;
+            let mut survey = LoopSurvey::default();
+            parser.rewrite(&data, &mut survey)?;
+            let mut edit = LoopEdit::new(survey, &plan);
+            writer.write_chunk(&name, &parser.rewrite(&data, &mut edit)?)?;
+        } else if name.starts_with(b"CustomData|") {
+            let data = nd2.read_chunk(&name)?;
+            match per_frame_values(&data, geometry.sequence_count, &plan.kept) {
+                Some(data) => writer.write_chunk(&name, &data)?,
+                None => writer.copy_chunk(&mut source, &name, offset)?,
+            }
+        } else {
+            writer.copy_chunk(&mut source, &name, offset)?;
+        }
+    }
+    writer
+        .finish()?
+        .into_inner()
+        .map_err(|err| err.into_error())?;
+    Ok(())
+}
+
+/// Name of the chunk of frame `seq` in the per-frame family `prefix`.
+fn frame_chunk_name(prefix: &[u8], seq: usize) -> Vec<u8> {
+    [prefix, seq.to_string().as_bytes(), b"!"].concat()
+}
+
+/// The frames of a subset and the narrowed axes.
+struct SubsetPlan {
+    /// Sequence indices of the kept frames, in order.
+    kept: Vec<usize>,
+    /// New sequence index of each kept frame.
+    renumber: HashMap<usize, usize>,
+    /// Kept positions, when narrowed.
+    positions: Option<Vec<usize>>,
+    /// Kept time points, when narrowed.
+    time: Option<Range<usize>>,
+    /// Kept in-pixel channels, when narrowed.
+    channels: Option<Vec<usize>>,
+    components_per_channel: u64,
+}
+
+impl SubsetPlan {
+    fn new(nd2: &mut Nd2File, selection: &SubsetSelection) -> Result<Self> {
+        let positions = narrowed("positions", &selection.positions, nd2.n_positions()?)?;
+        let channels = narrowed("channels", &selection.channels, nd2.n_channels()?)?;
+        let n_time = nd2.n_timepoints()?;
+        let time = match &selection.time {
+            Some(range) if range.start >= range.end => {
+                return Err(Nd2Error::input_argument("time", "time range is empty"));
+            }
+            Some(range) if range.end > n_time => {
+                return Err(Nd2Error::input_out_of_range("time", range.end - 1, n_time));
+            }
+            Some(range) if range.len() < n_time => Some(range.clone()),
+            _ => None,
+        };
+        let attrs = nd2.attributes()?;
+        let components_per_channel = match attrs.channel_count {
+            Some(n) if n > 0 => u64::from(attrs.component_count / n),
+            _ => 1,
+        };
+
+        let index = nd2.frame_index()?;
+        let axis = |name: &str| index.axes.iter().position(|axis| axis == name);
+        if channels.is_some() && axis(AXIS_C).is_some() {
+            return Err(Nd2Error::input_argument(
+                "channels",
+                "channels stored as separate frames cannot be selected",
+            ));
+        }
+        let (p_axis, t_axis) = (axis(AXIS_P), axis(AXIS_T));
+        let kept: Vec<usize> = (0..index.len())
+            .filter(|&seq| {
+                let coords = &index.coords[seq];
+                let position = match (p_axis, &positions) {
+                    (Some(axis), Some(positions)) => positions.binary_search(&coords[axis]).is_ok(),
+                    _ => true,
+                };
+                let time_point = match (t_axis, &time) {
+                    (Some(axis), Some(time)) => time.contains(&coords[axis]),
+                    _ => true,
+                };
+                position && time_point
+            })
+            .collect();
+        let renumber = kept.iter().enumerate().map(|(i, &seq)| (seq, i)).collect();
+        Ok(Self {
+            kept,
+            renumber,
+            positions,
+            time,
+            channels,
+            components_per_channel,
+        })
+    }
+}
+
+/// Check the indices of `field` against the axis length `len`, returning
+/// them when they leave out part of the axis.
+fn narrowed(field: &str, indices: &Option<Vec<usize>>, len: usize) -> Result<Option<Vec<usize>>> {
+    let Some(indices) = indices else {
+        return Ok(None);
+    };
+    match indices.last() {
+        None => Err(Nd2Error::input_argument(field, "selects nothing")),
+        Some(&last) if last >= len => Err(Nd2Error::input_out_of_range(field, last, len)),
+        _ => Ok((indices.len() < len).then(|| indices.clone())),
+    }
+}
+
+/// Frame chunk data keeping only `channels`: the timestamp, then the kept
+/// pixel rows, deflated again when the frames are compressed.
+fn select_channels(
+    geometry: &FrameGeometry,
+    index: usize,
+    data: &[u8],
+    channels: &[usize],
+) -> Result<Vec<u8>> {
+    let timestamp = data.get(..8).ok_or_else(|| {
+        Nd2Error::file_invalid_format(format!("Frame {} chunk has no timestamp", index))
+    })?;
+    if geometry.lossy {
+        return Err(Nd2Error::unsupported_compression(
+            "channels cannot be dropped from lossy (JPEG 2000) frames",
+        ));
+    }
+    let mut out = timestamp.to_vec();
+    if !geometry.compressed {
+        out.extend(geometry.select_channels(index, &data[8..], channels)?);
+        return Ok(out);
+    }
+    let FramePayload::Raw { bytes, .. } = geometry.inflate(index, data)? else {
+        return Err(Nd2Error::internal_invariant(
+            "compressed frame inflated to a compressed payload",
+        ));
+    };
+    let rows = geometry.select_channels(index, &bytes, channels)?;
+    let mut encoder = ZlibEncoder::new(out, Compression::default());
+    encoder.write_all(&rows)?;
+    Ok(encoder.finish()?)
+}
+
+/// `data` as an array of 4 or 8 byte values, one per frame of a file of
+/// `sequence_count` frames, narrowed to the `kept` frames.
+fn per_frame_values(data: &[u8], sequence_count: usize, kept: &[usize]) -> Option<Vec<u8>> {
+    let width = data.len().checked_div(sequence_count)?;
+    if !matches!(width, 4 | 8) || width * sequence_count != data.len() {
+        return None;
+    }
+    Some(
+        kept.iter()
+            .flat_map(|&seq| &data[seq * width..(seq + 1) * width])
+            .copied()
+            .collect(),
+    )
+}
+
+fn as_u64(value: &ClxValueRef<'_>) -> Option<u64> {
+    match *value {
+        ClxValueRef::UInt(v) => Some(v),
+        ClxValueRef::Int(v) => u64::try_from(v).ok(),
+        ClxValueRef::Float(v) if v >= 0.0 => Some(v as u64),
+        ClxValueRef::Bool(v) => Some(u64::from(v)),
+        _ => None,
+    }
+}
+
+/// Image attributes of the subset.
+struct AttributesEdit {
+    sequence_count: u64,
+    channels: Option<ChannelAttributes>,
+}
+
+struct ChannelAttributes {
+    components: u64,
+    channels: u64,
+    width_bytes: u64,
+}
+
+impl ClxEdit for AttributesEdit {
+    fn replace(&mut self, path: &[String], _: &ClxValueRef<'_>) -> Result<Option<ClxValue>> {
+        let name = path.last().map_or("", String::as_str);
+        let value = match (name, &self.channels) {
+            ("uiSequenceCount", _) => self.sequence_count,
+            ("uiComp", Some(channels)) => channels.components,
+            ("uiChannelCount", Some(channels)) => channels.channels,
+            ("uiWidthBytes", Some(channels)) => channels.width_bytes,
+            _ => return Ok(None),
+        };
+        Ok(Some(ClxValue::UInt(value)))
+    }
+}
+
+/// What an experiment entry is to the loop holding it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LoopRole {
+    /// `eType` or `uiLoopType`.
+    Type,
+    /// The loop's `uiCount`.
+    Count,
+    /// An XY point or NE time period.
+    Item,
+    /// The `uiCount` of an NE time period.
+    PeriodCount,
+    /// A `pItemValid` or `pPeriodValid` flag.
+    Valid,
+    /// `pItemValid` stored as a byte array.
+    ValidBytes,
+}
+
+/// The role of the experiment entry at `path`, with the length of the path
+/// of the loop level it belongs to. Loop parameters are found directly in
+/// the loop or in its `uLoopPars`, as [`parse_experiment`] looks for them.
+///
+/// [`parse_experiment`]: crate::meta_parse::parse_experiment
+fn loop_role(path: &[String]) -> Option<(usize, LoopRole)> {
+    let names: Vec<&str> = path.iter().map(String::as_str).collect();
+    let n = names.len();
+    let (len, role) = match names.as_slice() {
+        [.., "eType" | "uiLoopType"] => return Some((n - 1, LoopRole::Type)),
+        [.., "Points" | "pPeriod", _, "uiCount"] => (n - 3, LoopRole::PeriodCount),
+        [.., "uiCount"] => (n - 1, LoopRole::Count),
+        [.., "pItemValid" | "pPeriodValid", _] => (n - 2, LoopRole::Valid),
+        [.., "pItemValid"] => (n - 1, LoopRole::ValidBytes),
+        [.., "Points" | "pPeriod", _] => (n - 2, LoopRole::Item),
+        _ => return None,
+    };
+    let len = match &names[..len] {
+        [.., "uLoopPars", "i0000000000"] => len - 2,
+        [.., "uLoopPars"] => len - 1,
+        _ => len,
+    };
+    Some((len, role))
+}
+
+/// What one experiment loop holds.
+#[derive(Debug, Default)]
+struct LoopInfo {
+    loop_type: Option<u64>,
+    /// Validity flags of the points or periods.
+    valid: Vec<bool>,
+    /// Number of points or periods.
+    items: usize,
+    /// Counts of the NE time periods.
+    periods: Vec<u64>,
+}
+
+impl LoopInfo {
+    fn is_valid(&self, item: usize) -> bool {
+        self.valid
+            .get(item)
+            .copied()
+            .unwrap_or(self.valid.is_empty())
+    }
+}
+
+/// First pass over the experiment: what each loop holds, by loop path.
+#[derive(Default)]
+struct LoopSurvey {
+    loops: HashMap<Vec<String>, LoopInfo>,
+}
+
+impl ClxEdit for LoopSurvey {
+    fn replace(&mut self, path: &[String], value: &ClxValueRef<'_>) -> Result<Option<ClxValue>> {
+        let Some((len, role)) = loop_role(path) else {
+            return Ok(None);
+        };
+        let info = self.loops.entry(path[..len].to_vec()).or_default();
+        match (role, value) {
+            (LoopRole::Type, _) => info.loop_type = as_u64(value),
+            (LoopRole::Valid, _) => info.valid.push(as_u64(value) != Some(0)),
+            (LoopRole::ValidBytes, ClxValueRef::ByteArray(bytes)) => {
+                info.valid = bytes.iter().map(|&b| b != 0).collect();
+            }
+            (LoopRole::PeriodCount, _) => info.periods.push(as_u64(value).unwrap_or(0)),
+            _ => {}
+        }
+        Ok(None)
+    }
+
+    fn remove(&mut self, path: &[String], index: usize) -> bool {
+        if let Some((len, LoopRole::Item)) = loop_role(path) {
+            let info = self.loops.entry(path[..len].to_vec()).or_default();
+            info.items = info.items.max(index + 1);
+        }
+        false
+    }
+}
+
+/// Loop type codes, as in [`parse_experiment`](crate::meta_parse::parse_experiment).
+const TIME_LOOP: u64 = 1;
+const XY_POS_LOOP: u64 = 2;
+const NE_TIME_LOOP: u64 = 8;
+
+/// Second pass over the experiment: narrows the time and XY position loops.
+struct LoopEdit {
+    loops: HashMap<Vec<String>, LoopInfo>,
+    /// Kept points of each narrowed XY loop, by point index.
+    keep_points: HashMap<Vec<String>, Vec<bool>>,
+    /// New count of each period of each NE time loop, in stored order.
+    period_counts: HashMap<Vec<String>, Vec<u64>>,
+    /// Periods of each NE time loop counted so far.
+    periods_seen: HashMap<Vec<String>, usize>,
+    positions: Option<usize>,
+    time: Option<Range<usize>>,
+}
+
+impl LoopEdit {
+    fn new(survey: LoopSurvey, plan: &SubsetPlan) -> Self {
+        let mut keep_points = HashMap::new();
+        let mut period_counts = HashMap::new();
+        for (path, info) in &survey.loops {
+            match (info.loop_type, &plan.positions, &plan.time) {
+                (Some(XY_POS_LOOP), Some(positions), _) => {
+                    let mut position = 0;
+                    let keep = (0..info.items.max(info.valid.len()))
+                        .map(|item| {
+                            if !info.is_valid(item) {
+                                return false;
+                            }
+                            position += 1;
+                            positions.binary_search(&(position - 1)).is_ok()
+                        })
+                        .collect();
+                    keep_points.insert(path.clone(), keep);
+                }
+                (Some(NE_TIME_LOOP), _, Some(time)) => {
+                    let mut start = 0;
+                    let counts = info
+                        .periods
+                        .iter()
+                        .enumerate()
+                        .map(|(item, &count)| {
+                            if !info.is_valid(item) || count == 0 {
+                                return count;
+                            }
+                            let span = start..start + count as usize;
+                            start = span.end;
+                            let kept = span
+                                .end
+                                .min(time.end)
+                                .saturating_sub(span.start.max(time.start));
+                            kept as u64
+                        })
+                        .collect();
+                    period_counts.insert(path.clone(), counts);
+                }
+                _ => {}
+            }
+        }
+        Self {
+            loops: survey.loops,
+            keep_points,
+            period_counts,
+            periods_seen: HashMap::new(),
+            positions: plan.positions.as_ref().map(Vec::len),
+            time: plan.time.clone(),
+        }
+    }
+
+    fn loop_type(&self, loop_path: &[String]) -> Option<u64> {
+        self.loops.get(loop_path).and_then(|info| info.loop_type)
+    }
+}
+
+impl ClxEdit for LoopEdit {
+    fn replace(&mut self, path: &[String], value: &ClxValueRef<'_>) -> Result<Option<ClxValue>> {
+        let Some((len, role)) = loop_role(path) else {
+            return Ok(None);
+        };
+        let loop_path = &path[..len];
+        let new = match (role, self.loop_type(loop_path)) {
+            (LoopRole::Count, Some(TIME_LOOP | NE_TIME_LOOP)) => {
+                self.time.as_ref().map(|time| time.len() as u64)
+            }
+            (LoopRole::Count, Some(XY_POS_LOOP)) => self.positions.map(|n| n as u64),
+            (LoopRole::PeriodCount, Some(NE_TIME_LOOP)) => {
+                let seen = self.periods_seen.entry(loop_path.to_vec()).or_default();
+                *seen += 1;
+                self.period_counts
+                    .get(loop_path)
+                    .and_then(|counts| counts.get(*seen - 1))
+                    .copied()
+            }
+            (LoopRole::ValidBytes, Some(XY_POS_LOOP)) => {
+                let (Some(keep), ClxValueRef::ByteArray(bytes)) =
+                    (self.keep_points.get(loop_path), value)
+                else {
+                    return Ok(None);
+                };
+                let bytes = bytes
+                    .iter()
+                    .zip(keep)
+                    .filter(|(_, &keep)| keep)
+                    .map(|(&b, _)| b)
+                    .collect();
+                return Ok(Some(ClxValue::ByteArray(bytes)));
+            }
+            _ => None,
+        };
+        Ok(new.map(ClxValue::UInt))
+    }
+
+    fn remove(&mut self, path: &[String], index: usize) -> bool {
+        match loop_role(path) {
+            Some((len, LoopRole::Item | LoopRole::Valid)) => self
+                .keep_points
+                .get(&path[..len])
+                .is_some_and(|keep| !keep.get(index).copied().unwrap_or(false)),
+            _ => false,
+        }
+    }
+}
+
+/// Narrows the plane list of the picture metadata to the kept channels.
+struct PlanesEdit<'a> {
+    channels: &'a [usize],
+}
+
+impl ClxEdit for PlanesEdit<'_> {
+    fn replace(&mut self, path: &[String], _: &ClxValueRef<'_>) -> Result<Option<ClxValue>> {
+        let names: Vec<&str> = path.iter().map(String::as_str).collect();
+        match names.as_slice() {
+            [.., "sPicturePlanes", "uiCount"] => {
+                Ok(Some(ClxValue::UInt(self.channels.len() as u64)))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    fn remove(&mut self, path: &[String], index: usize) -> bool {
+        let names: Vec<&str> = path.iter().map(String::as_str).collect();
+        matches!(
+            names.as_slice(),
+            [.., "sPicturePlanes", "sPlaneNew" | "sPlane", _]
+        ) && self.channels.binary_search(&index).is_err()
+    }
+}
@@ -0,0 +1,206 @@
+//! Rows of per-frame, per-position and per-event values, shared by the
+//! companion sidecars and the `polars` data frames.
+
+use serde::Serialize;
+
+use crate::error::Result;
+use crate::reader::Nd2File;
+
+/// A table value: a number, a string, or missing.
+pub(crate) enum Cell {
+    Number(f64),
+    Text(String),
+    Missing,
+}
+
+impl From<Option<f64>> for Cell {
+    fn from(value: Option<f64>) -> Self {
+        value
+            .filter(|v| v.is_finite())
+            .map_or(Cell::Missing, Cell::Number)
+    }
+}
+
+impl From<Option<usize>> for Cell {
+    fn from(value: Option<usize>) -> Self {
+        value.map_or(Cell::Missing, |v| Cell::Number(v as f64))
+    }
+}
+
+/// A row of named columns, serialized as an object of the same fields.
+pub(crate) trait TableRow: Serialize {
+    /// Column names, in the order of the serialized fields.
+    const COLUMNS: &'static [&'static str];
+
+    /// The row's values, one per column.
+    fn cells(&self) -> Vec<Cell>;
+}
+
+/// One frame: its loop coordinates, acquisition time and recorded stage
+/// position.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub(crate) struct FrameRow {
+    pub(crate) index: usize,
+    #[serde(rename = "P")]
+    pub(crate) p: Option<usize>,
+    #[serde(rename = "T")]
+    pub(crate) t: Option<usize>,
+    #[serde(rename = "C")]
+    pub(crate) c: Option<usize>,
+    #[serde(rename = "Z")]
+    pub(crate) z: Option<usize>,
+    /// From [`Nd2File::frame_times`]; `None` when unknown.
+    pub(crate) time_ms: Option<f64>,
+    /// From [`Nd2File::frame_positions`]; `None` when not recorded.
+    pub(crate) x_um: Option<f64>,
+    pub(crate) y_um: Option<f64>,
+    pub(crate) z_um: Option<f64>,
+}
+
+#[cfg(feature = "polars")]
+impl FrameRow {
+    /// Loop coordinate along `axis` (`"P"`, `"T"`, `"C"` or `"Z"`).
+    pub(crate) fn coord(&self, axis: &str) -> Option<usize> {
+        match axis {
+            "P" => self.p,
+            "T" => self.t,
+            "C" => self.c,
+            "Z" => self.z,
+            _ => None,
+        }
+    }
+}
+
+impl TableRow for FrameRow {
+    const COLUMNS: &'static [&'static str] = &[
+        "index", "P", "T", "C", "Z", "time_ms", "x_um", "y_um", "z_um",
+    ];
+
+    fn cells(&self) -> Vec<Cell> {
+        vec![
+            Cell::from(Some(self.index)),
+            Cell::from(self.p),
+            Cell::from(self.t),
+            Cell::from(self.c),
+            Cell::from(self.z),
+            Cell::from(self.time_ms),
+            Cell::from(self.x_um),
+            Cell::from(self.y_um),
+            Cell::from(self.z_um),
+        ]
+    }
+}
+
+/// Loop axes of [`FrameRow`], outermost first.
+#[cfg(feature = "polars")]
+pub(crate) const FRAME_AXES: [&str; 4] = ["P", "T", "C", "Z"];
+
+/// One row per frame, by sequence index.
+pub(crate) fn frame_rows(nd2: &mut Nd2File) -> Result<Vec<FrameRow>> {
+    let frames = nd2.frames()?;
+    let times = nd2.frame_times()?;
+    let positions = nd2.frame_positions()?;
+    Ok(frames
+        .iter()
+        .map(|frame| {
+            let index = frame.index();
+            let stage = positions.get(index);
+            FrameRow {
+                index,
+                p: frame.coord("P"),
+                t: frame.coord("T"),
+                c: frame.coord("C"),
+                z: frame.coord("Z"),
+                time_ms: times.get(index).copied().filter(|t| t.is_finite()),
+                x_um: stage.map(|s| s.x),
+                y_um: stage.map(|s| s.y),
+                z_um: stage.map(|s| s.z),
+            }
+        })
+        .collect())
+}
+
+/// One planned XY point of the experiment.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub(crate) struct PositionRow {
+    pub(crate) index: usize,
+    pub(crate) name: Option<String>,
+    pub(crate) x_um: f64,
+    pub(crate) y_um: f64,
+    pub(crate) z_um: f64,
+    pub(crate) pfs_offset: Option<f64>,
+}
+
+impl TableRow for PositionRow {
+    const COLUMNS: &'static [&'static str] =
+        &["index", "name", "x_um", "y_um", "z_um", "pfs_offset"];
+
+    fn cells(&self) -> Vec<Cell> {
+        vec![
+            Cell::from(Some(self.index)),
+            self.name.clone().map_or(Cell::Missing, Cell::Text),
+            Cell::from(Some(self.x_um)),
+            Cell::from(Some(self.y_um)),
+            Cell::from(Some(self.z_um)),
+            Cell::from(self.pfs_offset),
+        ]
+    }
+}
+
+/// One row per XY point; empty without an XY loop.
+pub(crate) fn position_rows(nd2: &mut Nd2File) -> Result<Vec<PositionRow>> {
+    let points = nd2.xy_positions()?.map(|xy| xy.points).unwrap_or_default();
+    Ok(points
+        .into_iter()
+        .enumerate()
+        .map(|(index, point)| PositionRow {
+            index,
+            name: point.name,
+            x_um: point.stage_position_um.x,
+            y_um: point.stage_position_um.y,
+            z_um: point.stage_position_um.z,
+            pfs_offset: point.pfs_offset,
+        })
+        .collect())
+}
+
+/// One event of [`Nd2File::events`].
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub(crate) struct EventRow {
+    pub(crate) id: u32,
+    pub(crate) time_ms: f64,
+    /// The [`EventKind`](crate::EventKind) variant, e.g. `Stimulation` or
+    /// `Other(7)`.
+    pub(crate) kind: String,
+    pub(crate) description: String,
+    pub(crate) seq_index: Option<usize>,
+}
+
+impl TableRow for EventRow {
+    const COLUMNS: &'static [&'static str] = &["id", "time_ms", "kind", "description", "seq_index"];
+
+    fn cells(&self) -> Vec<Cell> {
+        vec![
+            Cell::Number(f64::from(self.id)),
+            Cell::from(Some(self.time_ms)),
+            Cell::Text(self.kind.clone()),
+            Cell::Text(self.description.clone()),
+            Cell::from(self.seq_index),
+        ]
+    }
+}
+
+/// One row per experiment event, by time.
+pub(crate) fn event_rows(nd2: &mut Nd2File) -> Result<Vec<EventRow>> {
+    Ok(nd2
+        .events()?
+        .into_iter()
+        .map(|event| EventRow {
+            id: event.id,
+            time_ms: event.time_ms,
+            kind: format!("{:?}", event.kind),
+            description: event.description,
+            seq_index: event.seq_index,
+        })
+        .collect())
+}
@@ -0,0 +1,190 @@
+//! Copies of files with their frames stored in another compression mode.
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+
+use crate::chunk::{image_seq_index, ChunkWriter};
+use crate::constants::ND2_FILE_SIGNATURE;
+use crate::decode::{FrameGeometry, FramePayload};
+use crate::error::{Nd2Error, Result};
+use crate::io::same_file;
+use crate::parse::{ClxEdit, ClxLiteParser, ClxValue, ClxValueRef};
+use crate::reader::Nd2File;
+use crate::types::CompressionType;
+
+/// Copy the file at `src` to `dst` with its frames stored as `compression`:
+/// [`CompressionType::None`] inflates lossless frames,
+/// [`CompressionType::Lossless`] deflates uncompressed ones.
+///
+/// Only the frames and the compression entry of the image attributes are
+/// rewritten; every other chunk, frame timestamps included, is copied byte
+/// for byte. When the frames already use `compression`, `dst` is a plain
+/// copy of the chunks `src` lists. Lossy (JPEG 2000) frames cannot be
+/// written; with the `jpeg2000` feature they can be decoded into either of
+/// the other modes.
+pub fn transcode_to<P: AsRef<Path>, Q: AsRef<Path>>(
+    src: P,
+    dst: Q,
+    compression: CompressionType,
+) -> Result<()> {
+    if same_file(src.as_ref(), dst.as_ref()) {
+        return Err(Nd2Error::input_argument(
+            "dst",
+            "transcoded copy would overwrite its source",
+        ));
+    }
+    if compression == CompressionType::Lossy {
+        return Err(Nd2Error::unsupported_compression(
+            "lossy (JPEG 2000) frames cannot be written",
+        ));
+    }
+    let mut nd2 = Nd2File::open(src.as_ref())?;
+    if nd2.is_legacy() {
+        let (major, minor) = nd2.version();
+        return Err(Nd2Error::unsupported_version(major, minor));
+    }
+    let stored = nd2
+        .attributes()?
+        .compression_type
+        .unwrap_or(CompressionType::None);
+    if stored == CompressionType::Lossy && !cfg!(feature = "jpeg2000") {
+        return Err(Nd2Error::unsupported_compression(
+            "lossy (JPEG 2000) frames need the `jpeg2000` feature",
+        ));
+    }
+    let geometry = nd2.geometry()?;
+    let attributes_name = nd2.attributes_chunk_name();
+    let parser = ClxLiteParser::new(false).limits(nd2.options().limits);
+    let mut source = File::open(src.as_ref())?;
+    let mut writer = ChunkWriter::new(BufWriter::new(File::create(dst.as_ref())?));
+    writer.copy_chunk(&mut source, ND2_FILE_SIGNATURE, 0)?;
+
+    for (name, offset, _) in nd2.chunk_entries() {
+        if stored == compression {
+            writer.copy_chunk(&mut source, &name, offset)?;
+        } else if let Some(seq) = image_seq_index(&name) {
+            let data = nd2.read_chunk(&name)?;
+            let frame = transcode_frame(&geometry, seq, &data, compression)?;
+            writer.write_chunk(&name, &frame)?;
+        } else if name == attributes_name {
+            let data = nd2.read_chunk(&name)?;
+            let mut survey = CompressionSurvey::default();
+            parser.rewrite(&data, &mut survey)?;
+            let mut edit = CompressionEdit {
+                compression,
+                found: false,
+                retype: survey.not_string,
+            };
+            writer.write_chunk(&name, &parser.rewrite(&data, &mut edit)?)?;
+        } else {
+            writer.copy_chunk(&mut source, &name, offset)?;
+        }
+    }
+    writer
+        .finish()?
+        .into_inner()
+        .map_err(|err| err.into_error())?;
+    Ok(())
+}
+
+/// Frame `index`'s chunk `data` stored as `compression` (uncompressed or
+/// lossless), keeping its timestamp.
+fn transcode_frame(
+    geometry: &FrameGeometry,
+    index: usize,
+    data: &[u8],
+    compression: CompressionType,
+) -> Result<Vec<u8>> {
+    let timestamp = data.get(..8).ok_or_else(|| {
+        Nd2Error::file_invalid_format(format!("Frame {} chunk has no timestamp", index))
+    })?;
+    let inflated;
+    let rows = if geometry.compressed {
+        let FramePayload::Raw { bytes, .. } = geometry.inflate(index, data)? else {
+            return Err(Nd2Error::internal_invariant(
+                "compressed frame inflated to a compressed payload",
+            ));
+        };
+        inflated = bytes;
+        &inflated[..]
+    } else {
+        &data[8..]
+    };
+    let mut out = timestamp.to_vec();
+    if compression == CompressionType::Lossless {
+        let mut encoder = ZlibEncoder::new(out, Compression::default());
+        encoder.write_all(rows)?;
+        return Ok(encoder.finish()?);
+    }
+    out.extend_from_slice(rows);
+    Ok(out)
+}
+
+/// Sets `eCompression` in the image attributes, adding it when missing.
+struct CompressionEdit {
+    compression: CompressionType,
+    found: bool,
+    /// The stored `eCompression` is not a string: it is left out and a
+    /// string entry appended in its place, since replaced values keep their
+    /// type.
+    retype: bool,
+}
+
+impl CompressionEdit {
+    fn value(&self) -> ClxValue {
+        let name = match self.compression {
+            CompressionType::Lossless => "lossless",
+            CompressionType::Lossy => "lossy",
+            CompressionType::None => "none",
+        };
+        ClxValue::String(name.to_string())
+    }
+}
+
+fn is_compression(path: &[String]) -> bool {
+    path.len() == 2 && path[1] == "eCompression"
+}
+
+impl ClxEdit for CompressionEdit {
+    fn replace(&mut self, path: &[String], value: &ClxValueRef<'_>) -> Result<Option<ClxValue>> {
+        if !is_compression(path) || !matches!(value, ClxValueRef::String(_)) {
+            return Ok(None);
+        }
+        self.found = true;
+        Ok(Some(self.value()))
+    }
+
+    fn remove(&mut self, path: &[String], _: usize) -> bool {
+        self.retype && is_compression(path)
+    }
+
+    fn append(&mut self, path: &[String]) -> Vec<(String, ClxValue)> {
+        if path.len() != 1
+            || self.found
+            || (self.compression == CompressionType::None && !self.retype)
+        {
+            return Vec::new();
+        }
+        vec![("eCompression".to_string(), self.value())]
+    }
+}
+
+/// First pass over the image attributes: whether `eCompression` is stored
+/// as something other than a string.
+#[derive(Default)]
+struct CompressionSurvey {
+    not_string: bool,
+}
+
+impl ClxEdit for CompressionSurvey {
+    fn replace(&mut self, path: &[String], value: &ClxValueRef<'_>) -> Result<Option<ClxValue>> {
+        if is_compression(path) && !matches!(value, ClxValueRef::String(_)) {
+            self.not_string = true;
+        }
+        Ok(None)
+    }
+}
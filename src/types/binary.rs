@@ -0,0 +1,18 @@
+use serde::{Deserialize, Serialize};
+
+use super::Color;
+
+/// A binary (segmentation) layer, from `CustomDataVar|BinaryMetadata_v1!`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BinaryLayer {
+    pub id: u32,
+    pub name: String,
+    /// Channel the layer was made from.
+    pub comp_name: String,
+    pub comp_order: u32,
+    pub color: Color,
+    pub color_mode: u32,
+    pub state: u32,
+    /// Names the layer's mask chunks, `CustomDataSeq|{file_tag}|{index}!`.
+    pub file_tag: String,
+}
@@ -0,0 +1,54 @@
+use std::fmt::Write as _;
+
+use serde::{Deserialize, Serialize};
+
+use super::summary::schema_v1;
+
+/// One chunkmap entry of a [`ChunkListing`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChunkEntry {
+    /// Chunk name as [`crate::Nd2File::chunk_names`] lists it.
+    pub name: String,
+    /// File offset of the chunk header.
+    pub offset: u64,
+    /// Size of the chunk as recorded in the chunkmap.
+    pub size: u64,
+}
+
+/// The chunkmap of a file, produced by [`crate::Nd2File::chunk_listing`].
+///
+/// Versioned like [`super::DatasetSummary`], via
+/// [`ChunkListing::SCHEMA_VERSION`]; [`ChunkListing::to_csv`] writes the
+/// version as its first column.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChunkListing {
+    /// Schema of this record; data written before versioning reads as 1.
+    #[serde(default = "schema_v1")]
+    pub schema_version: u32,
+    /// Entries in the order of [`crate::Nd2File::chunk_names`].
+    pub chunks: Vec<ChunkEntry>,
+}
+
+impl ChunkListing {
+    /// Current serialized schema version.
+    pub const SCHEMA_VERSION: u32 = 1;
+
+    /// The listing as CSV: a `schema_version,name,offset,size` header and
+    /// one row per chunk.
+    pub fn to_csv(&self) -> String {
+        let mut out = String::from("schema_version,name,offset,size\n");
+        for chunk in &self.chunks {
+            let name = if chunk.name.contains([',', '"', '\n', '\r']) {
+                format!("\"{}\"", chunk.name.replace('"', "\"\""))
+            } else {
+                chunk.name.clone()
+            };
+            let _ = writeln!(
+                out,
+                "{},{},{},{}",
+                self.schema_version, name, chunk.offset, chunk.size
+            );
+        }
+        out
+    }
+}
@@ -0,0 +1,66 @@
+use serde::{Deserialize, Serialize};
+
+/// Category of a non-fatal anomaly found while reading a file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum DiagnosticKind {
+    /// An experiment loop with an unrecognized type code was skipped.
+    UnknownLoopType,
+    /// A chunkmap entry points outside the file or has an implausible size.
+    SuspiciousChunkSize,
+    /// A metadata chunk could not be parsed and a fallback was used instead.
+    MetadataFallback,
+    /// The chunkmap could not be read and was rebuilt by scanning the file
+    /// for chunk headers.
+    ChunkmapRecovered,
+    /// An inflated frame could not be written to the on-disk frame cache;
+    /// reads go on without it.
+    FrameCacheWrite,
+    /// Several chunkmap entries share a name; the one listed last is used.
+    DuplicateChunk,
+    /// An older chunkmap section linked from a newer one could not be read,
+    /// or the chain of sections was too long; chunks listed only there are
+    /// missing.
+    ChunkmapSectionIgnored,
+    /// A metadata entry of an unknown CLX type was skipped, with the entries
+    /// after it in the same level.
+    UnknownClxType,
+    /// A metadata name or string held invalid UTF-16 (such as an unpaired
+    /// surrogate) and was decoded with replacement characters.
+    InvalidUtf16,
+    /// The file has a format version newer than this crate supports and was
+    /// read as 3.0, as allowed by
+    /// [`Nd2Options::allow_unknown_version`](crate::Nd2Options::allow_unknown_version).
+    UnknownVersion,
+    /// The frame count in the attributes, the frame chunks in the chunkmap
+    /// and the experiment loops disagree, as for acquisitions that stopped
+    /// early.
+    FrameCountMismatch,
+    /// A frame could not be read or decoded and was skipped, as allowed by
+    /// [`Nd2Options::skip_bad_frames`](crate::Nd2Options::skip_bad_frames).
+    BadFrame,
+    /// Frame chunks with no data, placeholders left by an aborted
+    /// acquisition, were treated as missing frames.
+    PlaceholderChunk,
+    /// The file starts with a legacy JPEG2000 container. A modern chunk
+    /// layer it also holds was read instead, or else its codestreams and
+    /// XML boxes were read as frames and metadata.
+    LegacyContainer,
+}
+
+/// A non-fatal parse warning.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Diagnostic {
+    pub kind: DiagnosticKind,
+    pub message: String,
+}
+
+impl Diagnostic {
+    pub fn new(kind: DiagnosticKind, message: impl Into<String>) -> Self {
+        Self {
+            kind,
+            message: message.into(),
+        }
+    }
+}
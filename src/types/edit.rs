@@ -0,0 +1,58 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use super::TextInfo;
+
+/// Where an edit put a rewritten metadata chunk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EditMode {
+    /// Over the old chunk, which had room for it.
+    InPlace,
+    /// At the end of the file, followed by a new chunkmap listing it
+    /// instead of the old chunk, which stays in the file unreferenced.
+    Appended,
+}
+
+/// Metadata overrides for [`apply_metadata_patch`](crate::apply_metadata_patch),
+/// usually read from a JSON document such as
+///
+/// ```json
+/// {
+///     "text_info": {"author": "J. Doe"},
+///     "channel_names": {"1": "mCherry"},
+///     "calibration_um": 0.325,
+///     "objective_name": "Plan Apo λ 20x",
+///     "objective_magnification": 20.0,
+///     "objective_numerical_aperture": 0.75
+/// }
+/// ```
+///
+/// Every field is optional; unset fields keep the file's values. Unknown
+/// fields are rejected, so a misspelt override is not silently ignored.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MetadataPatch {
+    /// Text info fields to set, as by [`edit_text_info`](crate::edit_text_info).
+    pub text_info: TextInfo,
+    /// New channel names, by channel index.
+    pub channel_names: BTreeMap<usize, String>,
+    /// Pixel size in µm.
+    pub calibration_um: Option<f64>,
+    pub objective_name: Option<String>,
+    pub objective_magnification: Option<f64>,
+    pub objective_numerical_aperture: Option<f64>,
+}
+
+impl MetadataPatch {
+    /// Whether the patch changes the picture metadata (channels,
+    /// calibration or objective).
+    pub(crate) fn touches_picture(&self) -> bool {
+        !self.channel_names.is_empty()
+            || self.calibration_um.is_some()
+            || self.objective_name.is_some()
+            || self.objective_magnification.is_some()
+            || self.objective_numerical_aperture.is_some()
+    }
+}
@@ -0,0 +1,46 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+/// A moment recorded during acquisition, from
+/// `CustomData|ExperimentEventsV1_0!`: a stimulation, a note typed by the
+/// user, an autofocus run, a change of incubation settings.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExperimentEvent {
+    pub id: u32,
+    /// Milliseconds from the start of acquisition.
+    pub time_ms: f64,
+    pub kind: EventKind,
+    pub description: String,
+    /// Sequence index of the last frame acquired at or before the event;
+    /// `None` when the event precedes every frame.
+    pub seq_index: Option<usize>,
+    /// Loop coordinates of that frame (e.g. `"T"`, `"P"`, `"Z"`); empty
+    /// when `seq_index` is `None`.
+    pub loop_indices: BTreeMap<String, usize>,
+}
+
+/// What an event records, as stored (0 to 4).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EventKind {
+    Unspecified,
+    Stimulation,
+    UserNote,
+    Autofocus,
+    IncubationChange,
+    /// A value this version does not know.
+    Other(u32),
+}
+
+impl EventKind {
+    pub fn from_u32(value: u32) -> Self {
+        match value {
+            0 => Self::Unspecified,
+            1 => Self::Stimulation,
+            2 => Self::UserNote,
+            3 => Self::Autofocus,
+            4 => Self::IncubationChange,
+            other => Self::Other(other),
+        }
+    }
+}
@@ -0,0 +1,148 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Nd2Error, Result};
+
+/// Integrity record of a file, from [`crate::Nd2File::manifest`], kept next
+/// to it so later audits can tell bit rot from an intact archive with
+/// [`crate::Nd2File::verify_against`].
+///
+/// All digests are CRC-32C. Stored as a small line-based text file (see
+/// [`Manifest::save`]); its `Display` and `FromStr` impls read and write
+/// the same text.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Manifest {
+    /// Size of the whole file in bytes.
+    pub file_size: u64,
+    /// Digest of the whole file.
+    pub file_crc32c: u32,
+    /// Digest of the chunkmap sections, as indexed when the file was opened.
+    pub chunkmap_crc32c: u32,
+    /// Digest of each frame chunk, header included, by sequence index;
+    /// `None` for a chunk that could not be read when the manifest was made.
+    pub frames: BTreeMap<usize, Option<u32>>,
+}
+
+impl Manifest {
+    /// Version of the text format written by [`Manifest::save`].
+    pub const FORMAT_VERSION: u32 = 1;
+
+    /// Where the manifest of the file at `path` is kept: the file name with
+    /// `.manifest` appended.
+    pub fn sidecar_path<P: AsRef<Path>>(path: P) -> PathBuf {
+        let mut sidecar = path.as_ref().as_os_str().to_owned();
+        sidecar.push(".manifest");
+        PathBuf::from(sidecar)
+    }
+
+    /// Write the manifest to `path`.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        std::fs::write(path, self.to_string())?;
+        Ok(())
+    }
+
+    /// Read a manifest written by [`Manifest::save`].
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        std::fs::read_to_string(path)?.parse()
+    }
+}
+
+impl fmt::Display for Manifest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "nd2-rs manifest {}", Self::FORMAT_VERSION)?;
+        writeln!(f, "file_size {}", self.file_size)?;
+        writeln!(f, "file_crc32c {:08x}", self.file_crc32c)?;
+        writeln!(f, "chunkmap_crc32c {:08x}", self.chunkmap_crc32c)?;
+        for (index, digest) in &self.frames {
+            match digest {
+                Some(digest) => writeln!(f, "frame {} {:08x}", index, digest)?,
+                None => writeln!(f, "frame {} unreadable", index)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Manifest {
+    type Err = Nd2Error;
+
+    fn from_str(text: &str) -> Result<Self> {
+        let mut lines = text.lines().enumerate().map(|(i, line)| (i + 1, line));
+        let invalid = |line: usize, detail: &str| {
+            Nd2Error::input_argument("manifest", format!("line {}: {}", line, detail))
+        };
+        let digest = |line: usize, value: &str| {
+            u32::from_str_radix(value, 16).map_err(|_| invalid(line, "invalid digest"))
+        };
+        let version = format!("nd2-rs manifest {}", Self::FORMAT_VERSION);
+        match lines.next() {
+            Some((_, header)) if header.trim_end() == version => {}
+            _ => return Err(invalid(1, "not an nd2-rs manifest of a known version")),
+        }
+
+        let (mut file_size, mut file_crc32c, mut chunkmap_crc32c) = (None, None, None);
+        let mut frames = BTreeMap::new();
+        for (line, text) in lines {
+            let fields: Vec<&str> = text.split_whitespace().collect();
+            match fields[..] {
+                [] => {}
+                ["file_size", size] => {
+                    file_size = Some(size.parse().map_err(|_| invalid(line, "invalid size"))?)
+                }
+                ["file_crc32c", value] => file_crc32c = Some(digest(line, value)?),
+                ["chunkmap_crc32c", value] => chunkmap_crc32c = Some(digest(line, value)?),
+                ["frame", index, value] => {
+                    let index = index
+                        .parse()
+                        .map_err(|_| invalid(line, "invalid frame index"))?;
+                    let value = match value {
+                        "unreadable" => None,
+                        value => Some(digest(line, value)?),
+                    };
+                    if frames.insert(index, value).is_some() {
+                        return Err(invalid(line, "frame listed twice"));
+                    }
+                }
+                _ => return Err(invalid(line, "unrecognized entry")),
+            }
+        }
+        let missing =
+            |field: &str| Nd2Error::input_argument("manifest", format!("missing {}", field));
+        Ok(Self {
+            file_size: file_size.ok_or_else(|| missing("file_size"))?,
+            file_crc32c: file_crc32c.ok_or_else(|| missing("file_crc32c"))?,
+            chunkmap_crc32c: chunkmap_crc32c.ok_or_else(|| missing("chunkmap_crc32c"))?,
+            frames,
+        })
+    }
+}
+
+/// Result of [`crate::Nd2File::verify_against`].
+///
+/// When the whole file still matches its digest nothing else is compared;
+/// otherwise the chunkmap and every frame chunk are, to find what changed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManifestReport {
+    /// The file has the recorded size and digest.
+    pub file_matches: bool,
+    /// The chunkmap has the recorded digest.
+    pub chunkmap_matches: bool,
+    /// Frames whose chunk no longer has the recorded digest, or can no
+    /// longer be read.
+    pub changed_frames: Vec<usize>,
+    /// Frames recorded in the manifest without a chunk in the file.
+    pub missing_frames: Vec<usize>,
+    /// Frames with a chunk in the file that the manifest doesn't list.
+    pub extra_frames: Vec<usize>,
+}
+
+impl ManifestReport {
+    /// The file is byte for byte as recorded.
+    pub fn is_ok(&self) -> bool {
+        self.file_matches
+    }
+}
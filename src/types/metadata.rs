@@ -1,6 +1,6 @@
 use serde::{Deserialize, Serialize};
 
-use super::PixelDataType;
+use super::{Affine2, PixelDataType};
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Metadata {
@@ -81,13 +81,22 @@ pub struct Volume {
     pub axes_interpretation: (AxisInterpretation, AxisInterpretation, AxisInterpretation),
     pub bits_per_component_in_memory: u32,
     pub bits_per_component_significant: u32,
-    pub camera_transformation_matrix: (f64, f64, f64, f64),
+    pub camera_transformation_matrix: Affine2,
     pub component_count: u32,
     pub component_data_type: PixelDataType,
     pub voxel_count: (u32, u32, u32),
     pub component_maxima: Option<Vec<f64>>,
     pub component_minima: Option<Vec<f64>>,
-    pub pixel_to_stage_transformation_matrix: Option<(f64, f64, f64, f64, f64, f64)>,
+    pub pixel_to_stage_transformation_matrix: Option<Affine2>,
+}
+
+/// Size of one voxel in µm, as returned by
+/// [`Nd2File::voxel_size`](crate::Nd2File::voxel_size).
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct VoxelSize {
+    pub x_um: f64,
+    pub y_um: f64,
+    pub z_um: f64,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
@@ -1,7 +1,37 @@
 pub mod attributes;
+pub mod binary;
+pub mod chunk_listing;
+pub mod diagnostic;
+pub mod edit;
+pub mod event;
 pub mod experiment;
+pub mod manifest;
+pub mod metadata;
+pub mod napari;
+pub mod recorded;
+pub mod repair;
+pub mod roi;
+pub mod snapshot;
 pub mod summary;
+pub mod text_info;
+pub mod transform;
+pub mod validation;
 
 pub use attributes::*;
+pub use binary::*;
+pub use chunk_listing::*;
+pub use diagnostic::*;
+pub use edit::*;
+pub use event::*;
 pub use experiment::*;
+pub use manifest::*;
+pub use metadata::*;
+pub use napari::*;
+pub use recorded::*;
+pub use repair::*;
+pub use roi::*;
+pub use snapshot::*;
 pub use summary::*;
+pub use text_info::*;
+pub use transform::*;
+pub use validation::*;
@@ -0,0 +1,27 @@
+use serde::{Deserialize, Serialize};
+
+/// Keyword arguments for napari's `Viewer.add_image`, one per channel.
+///
+/// Axes are `(T, Z, Y, X)`, matching one channel of a
+/// [`crate::StackOrder::Tczyx`] stack: `viewer.add_image(stack[:, c], **layer)`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NapariLayer {
+    pub name: String,
+    /// World units per pixel: ms for T, µm for Z, Y and X.
+    pub scale: [f64; 4],
+    /// Stage position of the field in µm; T is always 0.
+    pub translate: [f64; 4],
+    pub colormap: NapariColormap,
+    pub contrast_limits: [f64; 2],
+    /// `"additive"` for multi-channel files so channels overlay as in NIS.
+    pub blending: String,
+}
+
+/// A napari colormap argument: a built-in name, or `(name, [black, color])`
+/// ramping to the channel's NIS display color.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum NapariColormap {
+    Named(String),
+    Custom(String, [String; 2]),
+}
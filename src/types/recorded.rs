@@ -0,0 +1,30 @@
+use serde::{Deserialize, Serialize};
+
+/// One frame's value in a column of [`Nd2File::recorded_data`].
+///
+/// [`Nd2File::recorded_data`]: crate::Nd2File::recorded_data
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum RecordedValue {
+    Float(f64),
+    Int(i64),
+    Text(String),
+}
+
+impl RecordedValue {
+    /// The value as a number, `None` for text.
+    pub fn as_f64(&self) -> Option<f64> {
+        match *self {
+            Self::Float(value) => Some(value),
+            Self::Int(value) => Some(value as f64),
+            Self::Text(_) => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Self::Text(text) => Some(text),
+            _ => None,
+        }
+    }
+}
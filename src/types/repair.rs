@@ -0,0 +1,22 @@
+use serde::{Deserialize, Serialize};
+
+/// What [`repair_to`](crate::repair_to) made of a damaged file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RepairReport {
+    /// Whether the chunkmap could not be read and was rebuilt by scanning
+    /// the file for chunk headers.
+    pub chunkmap_rebuilt: bool,
+    /// Chunks copied to the repaired file, frames included.
+    pub chunks_copied: usize,
+    /// Frame chunks copied.
+    pub frames_copied: usize,
+    /// Names of listed chunks left out because they run past the end of the
+    /// file or have no valid header.
+    pub chunks_dropped: Vec<String>,
+    /// Sequence indices of frames declared by the attributes or planned by
+    /// the experiment loops that the repaired file has no chunk for.
+    pub missing_frames: Vec<usize>,
+    /// Bytes of the source not copied: chunkmaps, damaged chunks, chunks no
+    /// longer listed and trailing garbage.
+    pub bytes_discarded: u64,
+}
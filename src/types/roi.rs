@@ -0,0 +1,137 @@
+use serde::{Deserialize, Serialize};
+
+/// A region of interest drawn in NIS Elements, from
+/// `CustomData|RoiMetadata_v1!`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Roi {
+    pub id: u32,
+    pub info: RoiInfo,
+    pub guid: String,
+    /// Keyframes of the shape, at least one; more when it moves or changes
+    /// over time.
+    pub anim_params: Vec<AnimParam>,
+}
+
+impl Roi {
+    /// Whether the region marks background, for subtraction.
+    pub fn is_background(&self) -> bool {
+        self.info.interp_type == RoiInterpType::Background
+    }
+
+    /// Whether the region was stimulated (photoactivation, FRAP, ...).
+    pub fn is_stimulation(&self) -> bool {
+        self.info.interp_type == RoiInterpType::Stimulation
+    }
+
+    /// Whether the region is a reference for normalization.
+    pub fn is_reference(&self) -> bool {
+        self.info.interp_type == RoiInterpType::Reference
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RoiInfo {
+    pub shape_type: RoiShapeType,
+    pub interp_type: RoiInterpType,
+    pub cookie: u32,
+    pub color: u32,
+    pub label: String,
+    pub stimulation_group: u32,
+    pub scope: u32,
+    pub app_data: u32,
+    pub multi_frame: bool,
+    pub locked: bool,
+    pub comp_count: u32,
+    pub bpc: u32,
+    pub autodetected: bool,
+    pub gradient_stimulation: bool,
+    pub gradient_stimulation_bit_depth: u32,
+    pub gradient_stimulation_lo: f64,
+    pub gradient_stimulation_hi: f64,
+}
+
+/// Shape of a region, as stored (1 to 10).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RoiShapeType {
+    Raster,
+    Unknown,
+    Point,
+    Line,
+    PolyLine,
+    Rectangle,
+    Circle,
+    Ellipse,
+    Polygon,
+    Bezier,
+    /// A value this version does not know.
+    Other(u32),
+}
+
+impl RoiShapeType {
+    pub fn from_u32(value: u32) -> Self {
+        match value {
+            1 => Self::Raster,
+            2 => Self::Unknown,
+            3 => Self::Point,
+            4 => Self::Line,
+            5 => Self::PolyLine,
+            6 => Self::Rectangle,
+            7 => Self::Circle,
+            8 => Self::Ellipse,
+            9 => Self::Polygon,
+            10 => Self::Bezier,
+            other => Self::Other(other),
+        }
+    }
+}
+
+/// What a region is used for, as stored (1 to 4).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RoiInterpType {
+    Standard,
+    Background,
+    Reference,
+    Stimulation,
+    /// A value this version does not know.
+    Other(u32),
+}
+
+impl RoiInterpType {
+    pub fn from_u32(value: u32) -> Self {
+        match value {
+            1 => Self::Standard,
+            2 => Self::Background,
+            3 => Self::Reference,
+            4 => Self::Stimulation,
+            other => Self::Other(other),
+        }
+    }
+}
+
+/// Position and size of a region from `time_ms` on, in the units NIS
+/// Elements stores them in.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AnimParam {
+    pub time_ms: f64,
+    pub enabled: bool,
+    pub center_x: f64,
+    pub center_y: f64,
+    pub center_z: f64,
+    pub rotation_z: f64,
+    pub box_shape: BoxShape,
+    pub extruded_shape: ExtrudedShape,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct BoxShape {
+    pub size_x: f64,
+    pub size_y: f64,
+    pub size_z: f64,
+}
+
+/// Outline of a region as (x, y) base points, extruded `size_z` in Z.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ExtrudedShape {
+    pub size_z: f64,
+    pub base_points: Vec<(f64, f64)>,
+}
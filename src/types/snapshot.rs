@@ -0,0 +1,30 @@
+use serde::{Deserialize, Serialize};
+
+use super::summary::schema_v1;
+use super::{Attributes, DatasetSummary, Diagnostic, ExpLoop};
+use crate::frame::Frame;
+
+/// Owned copy of a file's metadata and frame table.
+///
+/// Produced by [`crate::Nd2File::snapshot`]; holds no reference to the file,
+/// so it can be serialized and stored by catalog services. Versioned like
+/// [`DatasetSummary`], via [`Nd2Snapshot::SCHEMA_VERSION`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Nd2Snapshot {
+    /// Schema of this record; data written before versioning reads as 1.
+    #[serde(default = "schema_v1")]
+    pub schema_version: u32,
+    pub version_major: u32,
+    pub version_minor: u32,
+    pub attributes: Attributes,
+    pub experiment: Vec<ExpLoop>,
+    pub summary: DatasetSummary,
+    /// One entry per `ImageDataSeq` chunk, in sequence order.
+    pub frames: Vec<Frame>,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl Nd2Snapshot {
+    /// Current serialized schema version.
+    pub const SCHEMA_VERSION: u32 = 1;
+}
@@ -18,8 +18,16 @@ pub struct SummaryScaling {
     pub unit: Option<String>,
 }
 
+/// Dimensions, channels and pixel type of a dataset.
+///
+/// Serialized with a `schema_version`. New fields are added with
+/// `#[serde(default)]` and keep the version; renaming, removing or changing
+/// the meaning of a field bumps [`DatasetSummary::SCHEMA_VERSION`].
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct DatasetSummary {
+    /// Schema of this record; data written before versioning reads as 1.
+    #[serde(default = "schema_v1")]
+    pub schema_version: u32,
     pub version_major: u32,
     pub version_minor: u32,
     pub sizes: BTreeMap<String, usize>,
@@ -28,3 +36,12 @@ pub struct DatasetSummary {
     pub pixel_type: Option<String>,
     pub scaling: Option<SummaryScaling>,
 }
+
+impl DatasetSummary {
+    /// Current serialized schema version.
+    pub const SCHEMA_VERSION: u32 = 1;
+}
+
+pub(crate) fn schema_v1() -> u32 {
+    1
+}
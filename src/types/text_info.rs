@@ -1,5 +1,7 @@
 use serde::{Deserialize, Serialize};
 
+/// Free-text fields of a file, from [`crate::Nd2File::text_info`]. Unset
+/// fields are `None`.
 #[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
 pub struct TextInfo {
     pub image_id: Option<String>,
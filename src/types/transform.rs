@@ -0,0 +1,120 @@
+use serde::{Deserialize, Serialize};
+
+/// 2D affine transform `p' = matrix · p + translation`.
+///
+/// Used for the camera transformation (pure 2 × 2, no translation) and the
+/// pixel-to-stage transformation (2 × 3, translation in µm). `matrix` is
+/// row-major: `matrix[row][col]`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Affine2 {
+    pub matrix: [[f64; 2]; 2],
+    pub translation: [f64; 2],
+}
+
+impl Affine2 {
+    pub const IDENTITY: Self = Self {
+        matrix: [[1.0, 0.0], [0.0, 1.0]],
+        translation: [0.0, 0.0],
+    };
+
+    pub fn new(matrix: [[f64; 2]; 2], translation: [f64; 2]) -> Self {
+        Self {
+            matrix,
+            translation,
+        }
+    }
+
+    /// Linear transform with no translation.
+    pub fn linear(matrix: [[f64; 2]; 2]) -> Self {
+        Self::new(matrix, [0.0, 0.0])
+    }
+
+    /// Map the point `[x, y]`.
+    pub fn apply(&self, point: [f64; 2]) -> [f64; 2] {
+        let [[a, b], [c, d]] = self.matrix;
+        let [x, y] = point;
+        [
+            a * x + b * y + self.translation[0],
+            c * x + d * y + self.translation[1],
+        ]
+    }
+
+    /// Map the vector `[x, y]`, ignoring the translation.
+    pub fn apply_vector(&self, vector: [f64; 2]) -> [f64; 2] {
+        let [[a, b], [c, d]] = self.matrix;
+        let [x, y] = vector;
+        [a * x + b * y, c * x + d * y]
+    }
+
+    /// `self ∘ other`: the transform applying `other` first, then `self`.
+    pub fn compose(&self, other: &Self) -> Self {
+        let [[a, b], [c, d]] = self.matrix;
+        let [[e, f], [g, h]] = other.matrix;
+        Self {
+            matrix: [
+                [a * e + b * g, a * f + b * h],
+                [c * e + d * g, c * f + d * h],
+            ],
+            translation: self.apply(other.translation),
+        }
+    }
+
+    pub fn determinant(&self) -> f64 {
+        let [[a, b], [c, d]] = self.matrix;
+        a * d - b * c
+    }
+
+    /// Inverse transform, or `None` if the matrix is singular.
+    pub fn invert(&self) -> Option<Self> {
+        let det = self.determinant();
+        if det == 0.0 || !det.is_finite() {
+            return None;
+        }
+        let [[a, b], [c, d]] = self.matrix;
+        let linear = Self::linear([[d / det, -b / det], [-c / det, a / det]]);
+        let [tx, ty] = linear.apply_vector(self.translation);
+        Some(Self::new(linear.matrix, [-tx, -ty]))
+    }
+}
+
+impl Default for Affine2 {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+/// Camera matrix as stored by NIS: `(m00, m01, m10, m11)`.
+impl From<(f64, f64, f64, f64)> for Affine2 {
+    fn from((a, b, c, d): (f64, f64, f64, f64)) -> Self {
+        Self::linear([[a, b], [c, d]])
+    }
+}
+
+/// Pixel-to-stage matrix as stored by NIS: `(m00, m01, tx, m10, m11, ty)`.
+impl From<(f64, f64, f64, f64, f64, f64)> for Affine2 {
+    fn from((a, b, tx, c, d, ty): (f64, f64, f64, f64, f64, f64)) -> Self {
+        Self::new([[a, b], [c, d]], [tx, ty])
+    }
+}
+
+#[cfg(feature = "nalgebra")]
+impl From<Affine2> for nalgebra::Affine2<f64> {
+    fn from(t: Affine2) -> Self {
+        let [[a, b], [c, d]] = t.matrix;
+        let [tx, ty] = t.translation;
+        nalgebra::Affine2::from_matrix_unchecked(nalgebra::Matrix3::new(
+            a, b, tx, c, d, ty, 0.0, 0.0, 1.0,
+        ))
+    }
+}
+
+#[cfg(feature = "nalgebra")]
+impl From<nalgebra::Affine2<f64>> for Affine2 {
+    fn from(t: nalgebra::Affine2<f64>) -> Self {
+        let m = t.matrix();
+        Self::new(
+            [[m[(0, 0)], m[(0, 1)]], [m[(1, 0)], m[(1, 1)]]],
+            [m[(0, 2)], m[(1, 2)]],
+        )
+    }
+}
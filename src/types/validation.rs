@@ -0,0 +1,77 @@
+use serde::{Deserialize, Serialize};
+
+use super::Diagnostic;
+
+/// How much of a file [`crate::Nd2File::validate`] reads.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ValidationLevel {
+    /// Chunkmap, metadata and the frame table; no frame chunk is read.
+    Metadata,
+    /// Also the header of every frame chunk, and whether the chunk holds a
+    /// whole frame.
+    Chunks,
+    /// Also inflate every compressed frame; reads the whole file.
+    Pixels,
+}
+
+/// Frame counts a file states or implies, from
+/// [`crate::Nd2File::frame_counts`]. All three agree in an intact file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct FrameCounts {
+    /// `uiSequenceCount` from the image attributes.
+    pub declared: usize,
+    /// Frame chunks in the chunkmap that lie inside the file.
+    pub stored: usize,
+    /// Frames the experiment loops were set up for (the product of their
+    /// sizes); `declared` when the file has no experiment loops.
+    pub planned: usize,
+}
+
+impl FrameCounts {
+    /// Whether the attributes, the chunkmap and the loops agree.
+    pub fn is_consistent(&self) -> bool {
+        self.declared == self.stored && self.stored == self.planned
+    }
+}
+
+/// Result of [`crate::Nd2File::validate`], for QC tools to consume.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ValidationReport {
+    pub level: ValidationLevel,
+    pub version_major: u32,
+    pub version_minor: u32,
+    /// The chunkmap was read as stored: not recovered by scanning, and no
+    /// entry points outside the file.
+    pub chunkmap_ok: bool,
+    /// Metadata that could not be parsed at all, one message per item.
+    pub metadata_errors: Vec<String>,
+    /// Frame counts from the attributes, chunkmap and loops; `None` when
+    /// the metadata could not be read.
+    pub frame_counts: Option<FrameCounts>,
+    /// Sequence indices of frames without a chunk in the file.
+    pub missing_frames: Vec<usize>,
+    /// Frames whose chunk is too small to hold a whole frame.
+    pub undersized_frames: Vec<usize>,
+    /// Frames whose chunk could not be read (or inflated), with the error.
+    pub corrupt_frames: Vec<(usize, String)>,
+    /// Every non-fatal warning raised while opening and validating, such as
+    /// metadata parsed with a fallback.
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl ValidationReport {
+    /// No errors found: the chunkmap is intact, metadata parses, the frame
+    /// counts agree and every frame checked at this level is present and
+    /// whole. Warnings in [`ValidationReport::diagnostics`] don't count.
+    pub fn is_ok(&self) -> bool {
+        self.chunkmap_ok
+            && self.metadata_errors.is_empty()
+            && self
+                .frame_counts
+                .map_or(true, |counts| counts.is_consistent())
+            && self.missing_frames.is_empty()
+            && self.undersized_frames.is_empty()
+            && self.corrupt_frames.is_empty()
+    }
+}
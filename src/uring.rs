@@ -0,0 +1,118 @@
+//! Batched positional reads through io_uring (Linux, `io-uring` feature).
+
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io;
+use std::os::unix::io::AsRawFd;
+
+use io_uring::{opcode, types, IoUring};
+
+/// Reads kept in flight at once.
+const QUEUE_DEPTH: u32 = 64;
+
+/// Largest single read submitted; longer buffers are read in several parts.
+const MAX_READ: usize = 1 << 30;
+
+const EINTR: i32 = 4;
+const EAGAIN: i32 = 11;
+const EBUSY: i32 = 16;
+
+/// An io_uring instance for reading many file regions with one submission.
+pub(crate) struct UringReader {
+    ring: IoUring,
+}
+
+impl UringReader {
+    /// Set up a ring. Fails where io_uring is unavailable (old kernels,
+    /// sandboxes that filter the syscalls).
+    pub(crate) fn new() -> io::Result<Self> {
+        Ok(Self {
+            ring: IoUring::new(QUEUE_DEPTH)?,
+        })
+    }
+
+    /// Fill every `(offset, buffer)` from `file`, keeping up to
+    /// [`QUEUE_DEPTH`] reads in flight. Short reads are resubmitted for the
+    /// remainder; reaching the end of the file is an `UnexpectedEof` error.
+    pub(crate) fn read_exact_at(
+        &mut self,
+        file: &File,
+        reads: &mut [(u64, &mut [u8])],
+    ) -> io::Result<()> {
+        let fd = types::Fd(file.as_raw_fd());
+        let mut filled = vec![0usize; reads.len()];
+        let mut pending: VecDeque<usize> = (0..reads.len())
+            .filter(|&i| !reads[i].1.is_empty())
+            .collect();
+        let mut in_flight = 0usize;
+        let mut error = None;
+
+        // Every submitted read is waited for, even after an error, so no
+        // buffer is released while the kernel may still write to it.
+        while in_flight > 0 || (error.is_none() && !pending.is_empty()) {
+            while error.is_none() && in_flight < QUEUE_DEPTH as usize {
+                let Some(i) = pending.pop_front() else {
+                    break;
+                };
+                let (offset, buf) = &mut reads[i];
+                let rest = &mut buf[filled[i]..];
+                let len = rest.len().min(MAX_READ) as u32;
+                let entry = opcode::Read::new(fd, rest.as_mut_ptr(), len)
+                    .offset(*offset + filled[i] as u64)
+                    .build()
+                    .user_data(i as u64);
+                // SAFETY: `rest` stays borrowed until this function returns,
+                // which only happens once the read has completed.
+                if unsafe { self.ring.submission().push(&entry) }.is_err() {
+                    pending.push_front(i);
+                    break;
+                }
+                in_flight += 1;
+            }
+
+            if let Err(err) = self.ring.submit_and_wait(1) {
+                match err.raw_os_error() {
+                    Some(EINTR | EAGAIN | EBUSY) => {}
+                    // Nothing was submitted, so nothing can still complete.
+                    _ if in_flight == 0 => return Err(err),
+                    _ => {
+                        error.get_or_insert(err);
+                    }
+                }
+                continue;
+            }
+            let completed: Vec<(usize, i32)> = self
+                .ring
+                .completion()
+                .map(|cqe| (cqe.user_data() as usize, cqe.result()))
+                .collect();
+            for (i, result) in completed {
+                in_flight -= 1;
+                match result {
+                    n if n > 0 => {
+                        filled[i] += n as usize;
+                        if filled[i] < reads[i].1.len() {
+                            pending.push_back(i);
+                        }
+                    }
+                    0 => {
+                        error.get_or_insert_with(|| {
+                            io::Error::new(
+                                io::ErrorKind::UnexpectedEof,
+                                format!("unexpected end of file at offset {}", reads[i].0),
+                            )
+                        });
+                    }
+                    n if n == -EINTR || n == -EAGAIN => pending.push_front(i),
+                    n => {
+                        error.get_or_insert_with(|| io::Error::from_raw_os_error(-n));
+                    }
+                }
+            }
+        }
+        match error {
+            Some(err) => Err(err),
+            None => Ok(()),
+        }
+    }
+}
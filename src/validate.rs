@@ -0,0 +1,67 @@
+//! Structured file checks for QC tooling.
+
+use crate::error::Result;
+use crate::reader::Nd2File;
+use crate::types::{DiagnosticKind, ValidationLevel, ValidationReport};
+
+impl Nd2File {
+    /// Check the file at `level` and report every problem found, rather
+    /// than failing on the first one.
+    ///
+    /// Damaged metadata and frames are listed in the report; an `Err` means
+    /// the file could not be read at all.
+    pub fn validate(&mut self, level: ValidationLevel) -> Result<ValidationReport> {
+        let (version_major, version_minor) = self.version();
+        let mut report = ValidationReport {
+            level,
+            version_major,
+            version_minor,
+            chunkmap_ok: true,
+            metadata_errors: Vec::new(),
+            frame_counts: None,
+            missing_frames: Vec::new(),
+            undersized_frames: Vec::new(),
+            corrupt_frames: Vec::new(),
+            diagnostics: Vec::new(),
+        };
+
+        let geometry = match self.geometry() {
+            Ok(geometry) => Some(geometry),
+            Err(err) => {
+                report.metadata_errors.push(format!("attributes: {}", err));
+                None
+            }
+        };
+        if let Err(err) = self.experiment() {
+            report.metadata_errors.push(format!("experiment: {}", err));
+        }
+        report.frame_counts = self.frame_counts().ok();
+        match self.frame_index() {
+            Ok(frame_index) => report.missing_frames = frame_index.missing(),
+            Err(err) => report.metadata_errors.push(format!("frame index: {}", err)),
+        }
+
+        if let (Some(geometry), true) = (geometry, level >= ValidationLevel::Chunks) {
+            let inflate = level >= ValidationLevel::Pixels;
+            for index in 0..geometry.sequence_count {
+                if report.missing_frames.binary_search(&index).is_ok() {
+                    continue;
+                }
+                match self.check_frame_chunk(index, &geometry, inflate) {
+                    Ok(true) => {}
+                    Ok(false) => report.undersized_frames.push(index),
+                    Err(err) => report.corrupt_frames.push((index, err.to_string())),
+                }
+            }
+        }
+
+        report.diagnostics = self.diagnostics().to_vec();
+        report.chunkmap_ok = !report.diagnostics.iter().any(|d| {
+            matches!(
+                d.kind,
+                DiagnosticKind::ChunkmapRecovered | DiagnosticKind::SuspiciousChunkSize
+            )
+        });
+        Ok(report)
+    }
+}
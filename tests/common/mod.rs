@@ -0,0 +1,483 @@
+//! Synthetic ND2 fixtures for tests that do not need a real acquisition.
+
+#![allow(dead_code)]
+
+const CHUNK_MAGIC: u32 = 0x0ABE_CEDA;
+pub const FILE_SIGNATURE: &[u8; 32] = b"ND2 FILE SIGNATURE CHUNK NAME01!";
+const FILEMAP_SIGNATURE: &[u8; 32] = b"ND2 FILEMAP SIGNATURE NAME 0001!";
+const CHUNKMAP_SIGNATURE: &[u8; 32] = b"ND2 CHUNK MAP SIGNATURE 0000001!";
+
+/// Minimal CLX Lite value tree used to encode metadata chunks.
+#[derive(Debug, Clone)]
+pub enum Clx {
+    Bool(&'static str, bool),
+    U32(&'static str, u32),
+    I32(&'static str, i32),
+    U64(&'static str, u64),
+    F64(&'static str, f64),
+    Str(&'static str, String),
+    Bytes(&'static str, Vec<u8>),
+    Level(&'static str, Vec<Clx>),
+    /// Encoded entry bytes, written as they are.
+    Raw(Vec<u8>),
+}
+
+fn put_name(out: &mut Vec<u8>, type_code: u8, name: &str) {
+    let units: Vec<u16> = name.encode_utf16().chain(std::iter::once(0)).collect();
+    out.push(type_code);
+    out.push(units.len() as u8);
+    for u in units {
+        out.extend_from_slice(&u.to_le_bytes());
+    }
+}
+
+impl Clx {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        match self {
+            Clx::Bool(n, v) => {
+                put_name(&mut out, 1, n);
+                out.push(*v as u8);
+            }
+            Clx::I32(n, v) => {
+                put_name(&mut out, 2, n);
+                out.extend_from_slice(&v.to_le_bytes());
+            }
+            Clx::U32(n, v) => {
+                put_name(&mut out, 3, n);
+                out.extend_from_slice(&v.to_le_bytes());
+            }
+            Clx::U64(n, v) => {
+                put_name(&mut out, 5, n);
+                out.extend_from_slice(&v.to_le_bytes());
+            }
+            Clx::F64(n, v) => {
+                put_name(&mut out, 6, n);
+                out.extend_from_slice(&v.to_le_bytes());
+            }
+            Clx::Str(n, v) => {
+                put_name(&mut out, 8, n);
+                for u in v.encode_utf16().chain(std::iter::once(0)) {
+                    out.extend_from_slice(&u.to_le_bytes());
+                }
+            }
+            Clx::Bytes(n, v) => {
+                put_name(&mut out, 9, n);
+                out.extend_from_slice(&(v.len() as u64).to_le_bytes());
+                out.extend_from_slice(v);
+            }
+            Clx::Level(n, items) => {
+                put_name(&mut out, 11, n);
+                let body: Vec<u8> = items.iter().flat_map(|i| i.encode()).collect();
+                // The length counts from the entry start to the end of the
+                // items, leaving out the offset table.
+                let length = out.len() + 12 + body.len();
+                out.extend_from_slice(&(items.len() as u32).to_le_bytes());
+                out.extend_from_slice(&(length as u64).to_le_bytes());
+                out.extend_from_slice(&body);
+                out.resize(out.len() + items.len() * 8, 0);
+            }
+            Clx::Raw(bytes) => out.extend_from_slice(bytes),
+        }
+        out
+    }
+}
+
+/// Builder for a small modern (v3.0) ND2 file held in memory.
+#[derive(Debug, Clone)]
+pub struct Nd2Builder {
+    pub width: u32,
+    pub height: u32,
+    pub channels: u32,
+    pub components_per_channel: u32,
+    pub frames: Vec<Vec<u16>>,
+    pub lossless: bool,
+    pub experiment: Option<Clx>,
+    pub extra_chunks: Vec<(Vec<u8>, Vec<u8>)>,
+    pub version: &'static str,
+}
+
+impl Nd2Builder {
+    /// `n_frames` frames of `height`×`width` with `channels` interleaved components.
+    /// Pixel values encode (frame, position) so reads can be checked exactly.
+    pub fn new(width: u32, height: u32, channels: u32, n_frames: usize) -> Self {
+        let per_frame = (width * height * channels) as usize;
+        let frames = (0..n_frames)
+            .map(|f| {
+                (0..per_frame)
+                    .map(|i| (f * 1000 + i) as u16)
+                    .collect::<Vec<u16>>()
+            })
+            .collect();
+        Self {
+            width,
+            height,
+            channels,
+            components_per_channel: 1,
+            frames,
+            lossless: false,
+            experiment: None,
+            extra_chunks: Vec::new(),
+            version: "Ver3.0",
+        }
+    }
+
+    pub fn attributes_clx(&self) -> Clx {
+        let comps = self.channels * self.components_per_channel;
+        let mut items = vec![
+            Clx::U32("uiWidth", self.width),
+            Clx::U32("uiWidthBytes", self.width * comps * 2),
+            Clx::U32("uiHeight", self.height),
+            Clx::U32("uiComp", comps),
+            Clx::U32("uiBpcInMemory", 16),
+            Clx::U32("uiBpcSignificant", 12),
+            Clx::U32("uiSequenceCount", self.frames.len() as u32),
+            Clx::U32("uiChannelCount", self.channels),
+        ];
+        if self.lossless {
+            items.push(Clx::Str("eCompression", "lossless".to_string()));
+        }
+        Clx::Level("SLxImageAttributes", items)
+    }
+
+    /// Named chunks in file order (metadata first, then frames, then extras).
+    pub fn chunks(&self) -> Vec<(Vec<u8>, Vec<u8>)> {
+        let mut chunks = vec![(
+            b"ImageAttributesLV!".to_vec(),
+            self.attributes_clx().encode(),
+        )];
+        if let Some(exp) = &self.experiment {
+            chunks.push((b"ImageMetadataLV!".to_vec(), exp.encode()));
+        }
+        for (i, frame) in self.frames.iter().enumerate() {
+            let mut payload = (i as f64 * 100.0).to_le_bytes().to_vec();
+            let raw: Vec<u8> = frame.iter().flat_map(|p| p.to_le_bytes()).collect();
+            if self.lossless {
+                payload.extend_from_slice(&zlib_stored(&raw));
+            } else {
+                payload.extend_from_slice(&raw);
+            }
+            chunks.push((format!("ImageDataSeq|{i}!").into_bytes(), payload));
+        }
+        chunks.extend(self.extra_chunks.iter().cloned());
+        chunks
+    }
+
+    pub fn build(&self) -> Vec<u8> {
+        build_file(self.version, &self.chunks())
+    }
+}
+
+/// Assemble header, chunks, chunkmap and footer into a complete file.
+pub fn build_file(version: &str, chunks: &[(Vec<u8>, Vec<u8>)]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut version_data = version.as_bytes().to_vec();
+    version_data.resize(64, 0);
+    write_chunk(&mut out, FILE_SIGNATURE, &version_data);
+
+    let mut entries = Vec::new();
+    for (name, data) in chunks {
+        let offset = out.len() as u64;
+        write_chunk(&mut out, name, data);
+        entries.push((name.clone(), offset, data.len() as u64));
+    }
+
+    append_chunkmap(&mut out, &entries);
+    out
+}
+
+/// Append a chunkmap section listing `entries` (name, offset, size) and its
+/// trailer, returning the section's offset.
+pub fn append_chunkmap(out: &mut Vec<u8>, entries: &[(Vec<u8>, u64, u64)]) -> u64 {
+    append_chunkmap_at(out, 0, entries)
+}
+
+/// Like [`append_chunkmap`], for bytes that go into the file at `base`.
+pub fn append_chunkmap_at(out: &mut Vec<u8>, base: u64, entries: &[(Vec<u8>, u64, u64)]) -> u64 {
+    let map_offset = base + out.len() as u64;
+    let mut map = Vec::new();
+    for (name, offset, size) in entries {
+        map.extend_from_slice(name);
+        map.extend_from_slice(&offset.to_le_bytes());
+        map.extend_from_slice(&size.to_le_bytes());
+    }
+    map.extend_from_slice(CHUNKMAP_SIGNATURE);
+    map.extend_from_slice(&map_offset.to_le_bytes());
+    write_chunk(out, FILEMAP_SIGNATURE, &map);
+    map_offset
+}
+
+pub fn write_chunk(out: &mut Vec<u8>, name: &[u8], data: &[u8]) {
+    out.extend_from_slice(&CHUNK_MAGIC.to_le_bytes());
+    out.extend_from_slice(&(name.len() as u32).to_le_bytes());
+    out.extend_from_slice(&(data.len() as u64).to_le_bytes());
+    out.extend_from_slice(name);
+    out.extend_from_slice(data);
+}
+
+/// Zlib stream using stored (uncompressed) deflate blocks.
+pub fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let mut out = vec![0x78, 0x01];
+    let blocks: Vec<&[u8]> = if data.is_empty() {
+        vec![&[]]
+    } else {
+        data.chunks(65535).collect()
+    };
+    let n = blocks.len();
+    for (i, block) in blocks.into_iter().enumerate() {
+        out.push(u8::from(i + 1 == n));
+        let len = block.len() as u16;
+        out.extend_from_slice(&len.to_le_bytes());
+        out.extend_from_slice(&(!len).to_le_bytes());
+        out.extend_from_slice(block);
+    }
+    let (mut a, mut b) = (1u32, 0u32);
+    for &x in data {
+        a = (a + x as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    out.extend_from_slice(&((b << 16) | a).to_be_bytes());
+    out
+}
+
+/// Open a builder's output as an in-memory `Nd2File`.
+pub fn open(builder: &Nd2Builder) -> nd2_rs::Nd2File {
+    nd2_rs::Nd2File::open_reader(std::io::Cursor::new(builder.build())).unwrap()
+}
+
+/// Fresh path under the system temp dir, unique per test name and process.
+pub fn temp_path(name: &str) -> std::path::PathBuf {
+    let path = std::env::temp_dir().join(format!("nd2_rs_{}_{}", std::process::id(), name));
+    let _ = std::fs::remove_dir_all(&path);
+    let _ = std::fs::remove_file(&path);
+    path
+}
+
+/// Validate OME-XML against the schema at `$OME_XSD` with `xmllint`, when set.
+///
+/// CI downloads the pinned `ome.xsd`; locally the check is skipped unless
+/// the variable points at a copy of the schema.
+pub fn validate_ome_xml(xml: &str) {
+    let Ok(schema) = std::env::var("OME_XSD") else {
+        return;
+    };
+    let path = temp_path("ome.xml");
+    std::fs::write(&path, xml).unwrap();
+    let output = std::process::Command::new("xmllint")
+        .arg("--noout")
+        .arg("--schema")
+        .arg(&schema)
+        .arg(&path)
+        .output()
+        .expect("xmllint must be installed when OME_XSD is set");
+    let _ = std::fs::remove_file(&path);
+    assert!(
+        output.status.success(),
+        "OME-XML failed schema validation:\n{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+/// One page of a TIFF read back by [`read_tiff`].
+pub struct TiffPage {
+    /// Integer tag values, by tag.
+    pub tags: std::collections::HashMap<u16, Vec<u64>>,
+    pub description: Option<String>,
+    /// Decompressed 16-bit pixels, row by row.
+    pub pixels: Vec<u16>,
+}
+
+/// Read every page in the main IFD chain of a little-endian TIFF or BigTIFF.
+pub fn read_tiff(bytes: &[u8]) -> Vec<TiffPage> {
+    assert_eq!(&bytes[..2], b"II");
+    let big = u16::from_le_bytes([bytes[2], bytes[3]]) == 43;
+    let mut next = if big {
+        read_u64(bytes, 8)
+    } else {
+        read_u32(bytes, 4)
+    };
+    let mut pages = Vec::new();
+    while next != 0 {
+        let (page, after) = read_tiff_ifd(bytes, next, big);
+        pages.push(page);
+        next = after;
+    }
+    pages
+}
+
+/// Read the IFD at `offset`, returning its page and the next IFD offset.
+pub fn read_tiff_ifd(bytes: &[u8], offset: u64, big: bool) -> (TiffPage, u64) {
+    let at = offset as usize;
+    let (count, mut entry, entry_size) = if big {
+        (read_u64(bytes, at) as usize, at + 8, 20)
+    } else {
+        (
+            u16::from_le_bytes([bytes[at], bytes[at + 1]]) as usize,
+            at + 2,
+            12,
+        )
+    };
+    let mut tags = std::collections::HashMap::new();
+    let mut description = None;
+    for _ in 0..count {
+        let tag = u16::from_le_bytes([bytes[entry], bytes[entry + 1]]);
+        let kind = u16::from_le_bytes([bytes[entry + 2], bytes[entry + 3]]);
+        let (n, value_at) = if big {
+            (read_u64(bytes, entry + 4) as usize, entry + 12)
+        } else {
+            (read_u32(bytes, entry + 4) as usize, entry + 8)
+        };
+        let size = match kind {
+            2 => 1,
+            3 => 2,
+            4 | 13 => 4,
+            16 | 18 => 8,
+            _ => panic!("unexpected TIFF type {kind}"),
+        };
+        let inline = if big { 8 } else { 4 };
+        let data = if n * size <= inline {
+            value_at
+        } else if big {
+            read_u64(bytes, value_at) as usize
+        } else {
+            read_u32(bytes, value_at) as usize
+        };
+        if kind == 2 {
+            let text = &bytes[data..data + n];
+            let text = text.strip_suffix(&[0]).unwrap_or(text);
+            description = Some(String::from_utf8(text.to_vec()).unwrap());
+        } else {
+            let values: Vec<u64> = (0..n)
+                .map(|i| match size {
+                    2 => u16::from_le_bytes([bytes[data + 2 * i], bytes[data + 2 * i + 1]]) as u64,
+                    4 => read_u32(bytes, data + 4 * i),
+                    _ => read_u64(bytes, data + 8 * i),
+                })
+                .collect();
+            tags.insert(tag, values);
+        }
+        entry += entry_size;
+    }
+    let next = if big {
+        read_u64(bytes, entry)
+    } else {
+        read_u32(bytes, entry)
+    };
+
+    let get = |tag: u16| tags[&tag][0] as usize;
+    let (width, height) = (get(256), get(257));
+    let compression = tags[&259][0];
+    let decode = |offset: u64, len: u64| {
+        let data = &bytes[offset as usize..(offset + len) as usize];
+        match compression {
+            1 => data.to_vec(),
+            5 => lzw_decode(data),
+            8 => {
+                use std::io::Read;
+                let mut out = Vec::new();
+                flate2::read::ZlibDecoder::new(data)
+                    .read_to_end(&mut out)
+                    .unwrap();
+                out
+            }
+            #[cfg(feature = "tiff-zstd")]
+            50000 => zstd::decode_all(data).unwrap(),
+            other => panic!("unexpected TIFF compression {other}"),
+        }
+    };
+    let mut pixels = vec![0u16; width * height];
+    if let Some(offsets) = tags.get(&324) {
+        let (tile_w, tile_h) = (get(322), get(323));
+        let across = (width + tile_w - 1) / tile_w;
+        for (i, (&offset, &len)) in offsets.iter().zip(&tags[&325]).enumerate() {
+            let tile = decode(offset, len);
+            assert_eq!(tile.len(), tile_w * tile_h * 2);
+            let (top, left) = (i / across * tile_h, i % across * tile_w);
+            for y in top..(top + tile_h).min(height) {
+                for x in left..(left + tile_w).min(width) {
+                    let j = ((y - top) * tile_w + x - left) * 2;
+                    pixels[y * width + x] = u16::from_le_bytes([tile[j], tile[j + 1]]);
+                }
+            }
+        }
+    } else {
+        let mut data = Vec::new();
+        for (&offset, &len) in tags[&273].iter().zip(&tags[&279]) {
+            data.extend(decode(offset, len));
+        }
+        assert_eq!(data.len(), width * height * 2);
+        for (pixel, bytes) in pixels.iter_mut().zip(data.chunks_exact(2)) {
+            *pixel = u16::from_le_bytes([bytes[0], bytes[1]]);
+        }
+    }
+    let page = TiffPage {
+        tags,
+        description,
+        pixels,
+    };
+    (page, next)
+}
+
+fn read_u32(bytes: &[u8], at: usize) -> u64 {
+    u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap()) as u64
+}
+
+fn read_u64(bytes: &[u8], at: usize) -> u64 {
+    u64::from_le_bytes(bytes[at..at + 8].try_into().unwrap())
+}
+
+/// Decode TIFF LZW (MSB-first codes with early change).
+pub fn lzw_decode(data: &[u8]) -> Vec<u8> {
+    let mut table: Vec<Vec<u8>> = Vec::new();
+    let reset = |table: &mut Vec<Vec<u8>>| {
+        *table = (0..=255u8).map(|b| vec![b]).collect();
+        table.push(Vec::new());
+        table.push(Vec::new());
+    };
+    reset(&mut table);
+    let (mut acc, mut bits, mut at) = (0u32, 0u32, 0usize);
+    let mut width = 9;
+    let mut prev: Option<Vec<u8>> = None;
+    let mut out = Vec::new();
+    loop {
+        while bits < width {
+            acc = (acc << 8) | *data.get(at).unwrap_or(&0) as u32;
+            at += 1;
+            bits += 8;
+        }
+        bits -= width;
+        let code = ((acc >> bits) & ((1 << width) - 1)) as usize;
+        match code {
+            256 => {
+                reset(&mut table);
+                width = 9;
+                prev = None;
+                continue;
+            }
+            257 => return out,
+            _ => {}
+        }
+        let entry = match (&prev, table.get(code)) {
+            (_, Some(entry)) => entry.clone(),
+            (Some(prev), None) => {
+                assert_eq!(code, table.len(), "LZW code out of sequence");
+                let mut entry = prev.clone();
+                entry.push(prev[0]);
+                entry
+            }
+            (None, None) => panic!("LZW code {code} without a prefix"),
+        };
+        out.extend_from_slice(&entry);
+        if let Some(mut new) = prev.take() {
+            new.push(entry[0]);
+            table.push(new);
+        }
+        prev = Some(entry);
+        width = match table.len() + 1 {
+            n if n >= 2048 => 12,
+            n if n >= 1024 => 11,
+            n if n >= 512 => 10,
+            _ => 9,
+        };
+    }
+}
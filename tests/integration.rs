@@ -122,3 +122,23 @@ fn test_read_frame_2d_out_of_range() -> Result<()> {
     assert!(nd2.read_frame_2d(0, 0, 0, z).is_err());
     Ok(())
 }
+
+#[test]
+fn test_shape_helpers() -> Result<()> {
+    let mut nd2 = match require_fixture() {
+        Some(x) => x,
+        None => return Ok(()),
+    };
+
+    let summary = nd2.summary()?;
+    assert_eq!(nd2.shape()?, (summary.sizes["Y"], summary.sizes["X"]));
+    assert!(nd2.n_frames()? > 0);
+    assert_eq!(nd2.n_timepoints()?, summary.sizes["T"]);
+    assert_eq!(nd2.n_channels()?, summary.sizes["C"]);
+    assert_eq!(nd2.n_positions()?, summary.sizes["P"]);
+    assert_eq!(nd2.n_z()?, summary.sizes["Z"]);
+    assert!(!nd2.is_legacy());
+    let _ = nd2.is_rgb()?;
+    let _ = nd2.is_compressed()?;
+    Ok(())
+}
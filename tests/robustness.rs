@@ -0,0 +1,92 @@
+//! Malformed input must produce errors, never panics.
+
+mod common;
+
+use common::{Clx, Nd2Builder};
+use nd2_rs::Nd2File;
+
+/// Exercise every read path; errors are fine, panics are not.
+fn exercise(bytes: Vec<u8>) {
+    let Ok(mut nd2) = Nd2File::open_reader(std::io::Cursor::new(bytes)) else {
+        return;
+    };
+    let _ = nd2.summary();
+    let _ = nd2.shape();
+    let _ = nd2.is_rgb();
+    if let Ok(frames) = nd2.frames() {
+        for frame in frames.iter().take(4) {
+            let _ = frame.pixels(&mut nd2);
+        }
+    }
+    for index in 0..4 {
+        let _ = nd2.read_frame(index);
+    }
+    let _ = nd2.read_frame_2d(0, 0, 0, 0);
+    let _ = nd2.diagnostics();
+}
+
+fn fixture() -> Vec<u8> {
+    let mut builder = Nd2Builder::new(5, 4, 2, 3);
+    builder.experiment = Some(Clx::Level(
+        "SLxExperiment",
+        vec![
+            Clx::U32("eType", 1),
+            Clx::Level("uLoopPars", vec![Clx::U32("uiCount", 3)]),
+        ],
+    ));
+    builder.build()
+}
+
+/// Small deterministic xorshift generator so failures are reproducible.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+}
+
+#[test]
+fn test_truncated_files_do_not_panic() {
+    let bytes = fixture();
+    for len in (0..bytes.len()).step_by(7) {
+        exercise(bytes[..len].to_vec());
+    }
+}
+
+#[test]
+fn test_corrupted_bytes_do_not_panic() {
+    let bytes = fixture();
+    let mut rng = Rng(0x9E37_79B9_7F4A_7C15);
+    for _ in 0..500 {
+        let mut mutated = bytes.clone();
+        for _ in 0..(1 + rng.next() % 8) {
+            let pos = (rng.next() % mutated.len() as u64) as usize;
+            mutated[pos] = rng.next() as u8;
+        }
+        exercise(mutated);
+    }
+}
+
+#[test]
+fn test_hostile_attribute_values_do_not_panic() {
+    for (width, comps) in [(0, 0), (u32::MAX, 1), (3, u32::MAX)] {
+        let attrs = Clx::Level(
+            "SLxImageAttributes",
+            vec![
+                Clx::U32("uiWidth", width),
+                Clx::U32("uiWidthBytes", u32::MAX),
+                Clx::U32("uiHeight", u32::MAX),
+                Clx::U32("uiComp", comps),
+                Clx::U32("uiBpcInMemory", 0),
+                Clx::U32("uiBpcSignificant", 0),
+                Clx::U32("uiSequenceCount", u32::MAX),
+            ],
+        );
+        let chunks = vec![(b"ImageAttributesLV!".to_vec(), attrs.encode())];
+        exercise(common::build_file("Ver3.0", &chunks));
+    }
+}
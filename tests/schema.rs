@@ -0,0 +1,142 @@
+//! Compatibility tests for the serialized output schemas.
+//!
+//! The JSON fixtures below are what schema version 1 looks like on disk.
+//! They must keep deserializing; if one stops parsing, the change needs a
+//! `SCHEMA_VERSION` bump instead.
+
+mod common;
+
+use common::Nd2Builder;
+use nd2_rs::{ChunkListing, DatasetSummary, MetadataPatch, Nd2Snapshot, Result};
+use serde_json::Value;
+
+const SUMMARY_V1: &str = r##"{
+    "schema_version": 1,
+    "version_major": 3,
+    "version_minor": 0,
+    "sizes": {"C": 2, "T": 1, "X": 4, "Y": 3},
+    "logical_frame_count": 1,
+    "channels": [
+        {"index": 0, "name": null, "color": null, "pixel_type": "Unsigned16"},
+        {"index": 1, "name": "GFP", "color": "#00ff00", "pixel_type": "Unsigned16"}
+    ],
+    "pixel_type": "Unsigned16",
+    "scaling": {"x": 0.65, "y": 0.65, "z": null, "unit": "um"}
+}"##;
+
+const CHUNK_LISTING_V1: &str = r##"{
+    "schema_version": 1,
+    "chunks": [
+        {"name": "ImageAttributesLV!", "offset": 4096, "size": 512},
+        {"name": "ImageDataSeq|0!", "offset": 8192, "size": 32}
+    ]
+}"##;
+
+fn keys(value: &Value) -> Vec<&str> {
+    let mut keys: Vec<&str> = value
+        .as_object()
+        .expect("object")
+        .keys()
+        .map(String::as_str)
+        .collect();
+    keys.sort_unstable();
+    keys
+}
+
+#[test]
+fn test_summary_v1_fixture_still_parses() {
+    let summary: DatasetSummary = serde_json::from_str(SUMMARY_V1).unwrap();
+    assert_eq!(summary.schema_version, 1);
+    assert_eq!(summary.sizes["C"], 2);
+    assert_eq!(summary.channels[1].name.as_deref(), Some("GFP"));
+}
+
+#[test]
+fn test_summary_ignores_unknown_fields() {
+    // Readers built against v1 must accept records from newer writers.
+    let mut value: Value = serde_json::from_str(SUMMARY_V1).unwrap();
+    value["added_in_a_later_release"] = Value::from(42);
+    let summary: DatasetSummary = serde_json::from_value(value).unwrap();
+    assert_eq!(summary.logical_frame_count, 1);
+}
+
+#[test]
+fn test_unversioned_summary_reads_as_v1() {
+    let mut value: Value = serde_json::from_str(SUMMARY_V1).unwrap();
+    value.as_object_mut().unwrap().remove("schema_version");
+    let summary: DatasetSummary = serde_json::from_value(value).unwrap();
+    assert_eq!(summary.schema_version, 1);
+}
+
+#[test]
+fn test_summary_output_matches_v1_fields() -> Result<()> {
+    let mut nd2 = common::open(&Nd2Builder::new(4, 3, 2, 3));
+    let summary = nd2.summary()?;
+    assert_eq!(summary.schema_version, DatasetSummary::SCHEMA_VERSION);
+
+    let written = serde_json::to_value(&summary).unwrap();
+    let fixture: Value = serde_json::from_str(SUMMARY_V1).unwrap();
+    assert_eq!(keys(&written), keys(&fixture));
+    assert_eq!(keys(&written["channels"][0]), keys(&fixture["channels"][0]));
+    Ok(())
+}
+
+#[test]
+fn test_snapshot_round_trip() -> Result<()> {
+    let mut nd2 = common::open(&Nd2Builder::new(4, 3, 2, 3));
+    let snapshot = nd2.snapshot()?;
+    assert_eq!(snapshot.schema_version, Nd2Snapshot::SCHEMA_VERSION);
+
+    let json = serde_json::to_string(&snapshot).unwrap();
+    let value: Value = serde_json::from_str(&json).unwrap();
+    assert_eq!(
+        keys(&value),
+        [
+            "attributes",
+            "diagnostics",
+            "experiment",
+            "frames",
+            "schema_version",
+            "summary",
+            "version_major",
+            "version_minor",
+        ]
+    );
+    let back: Nd2Snapshot = serde_json::from_str(&json).unwrap();
+    assert_eq!(back, snapshot);
+    Ok(())
+}
+
+#[test]
+fn test_metadata_patch_from_json() {
+    let patch: MetadataPatch = serde_json::from_str(
+        r#"{
+            "text_info": {"author": "J. Doe"},
+            "channel_names": {"1": "mCherry"},
+            "objective_magnification": 20.0
+        }"#,
+    )
+    .unwrap();
+    assert_eq!(patch.text_info.author.as_deref(), Some("J. Doe"));
+    assert_eq!(patch.channel_names[&1], "mCherry");
+    assert_eq!(patch.objective_magnification, Some(20.0));
+    assert_eq!(patch.calibration_um, None);
+
+    // A misspelt override must not be dropped silently.
+    assert!(serde_json::from_str::<MetadataPatch>(r#"{"calibration": 0.5}"#).is_err());
+}
+
+#[test]
+fn test_chunk_listing_v1_fixture_still_parses() -> Result<()> {
+    let listing: ChunkListing = serde_json::from_str(CHUNK_LISTING_V1).unwrap();
+    assert_eq!(listing.schema_version, 1);
+    assert_eq!(listing.chunks[1].name, "ImageDataSeq|0!");
+
+    let written =
+        serde_json::to_value(common::open(&Nd2Builder::new(4, 3, 1, 2)).chunk_listing()).unwrap();
+    let fixture: Value = serde_json::from_str(CHUNK_LISTING_V1).unwrap();
+    assert_eq!(written["schema_version"], ChunkListing::SCHEMA_VERSION);
+    assert_eq!(keys(&written), keys(&fixture));
+    assert_eq!(keys(&written["chunks"][0]), keys(&fixture["chunks"][0]));
+    Ok(())
+}
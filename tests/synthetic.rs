@@ -0,0 +1,4159 @@
+//! Tests against small synthetic ND2 files built in memory.
+
+mod common;
+
+use std::io::Cursor;
+
+use common::{Clx, Nd2Builder};
+use nd2_rs::sansio::ClxLiteParser;
+use nd2_rs::{
+    AnonymizePolicy, ChunkListing, CompanionExporter, CompressionType, DiagnosticKind, EditMode,
+    EventKind, FileError, FrameCoord, FrameCounts, FrameOrder, Limits, Manifest, MetaImageExporter,
+    MetadataPatch, MultipointExporter, N5Exporter, Nd2Error, Nd2File, Nd2Options, NiftiExporter,
+    OmeTiffExporter, OmeZarrExporter, PixelBuffer, PngExporter, ReadStrategy, RecordedValue,
+    Result, RoiShapeType, ShareMode, SidecarFormat, StackOrder, SubsetSelection, TextInfo,
+    TiffCompression, TiffExporter, TiffLayout, ToneMapping, ToneRange, ValidationLevel, VoxelSize,
+    ZarrExporter,
+};
+
+#[test]
+fn test_synthetic_read_frame() -> Result<()> {
+    let builder = Nd2Builder::new(4, 3, 2, 5);
+    let mut nd2 = common::open(&builder);
+
+    assert_eq!(nd2.version(), (3, 0));
+    assert_eq!(nd2.shape()?, (3, 4));
+    // Without experiment loops the five chunks are laid out as T=2 x C=2,
+    // which is what the frame index covers.
+    assert_eq!(nd2.n_frames()?, 4);
+    assert_eq!(nd2.n_frames()?, nd2.frames()?.len());
+
+    // Interleaved (Y, X, C) on disk becomes planar (C, Y, X).
+    let frame = nd2.read_frame(2)?;
+    assert_eq!(frame.len(), 2 * 3 * 4);
+    assert_eq!(frame[0], 2000);
+    assert_eq!(frame[1], 2002);
+    assert_eq!(frame[12], 2001);
+    Ok(())
+}
+
+#[test]
+fn test_synthetic_lossless_frame() -> Result<()> {
+    let mut builder = Nd2Builder::new(4, 3, 1, 2);
+    builder.lossless = true;
+    let mut nd2 = common::open(&builder);
+
+    assert!(nd2.is_compressed()?);
+    assert_eq!(nd2.read_frame(1)?, builder.frames[1]);
+    Ok(())
+}
+
+#[test]
+fn test_synthetic_parallel_lossless_reads() -> Result<()> {
+    let mut builder = Nd2Builder::new(4, 3, 2, 7);
+    builder.lossless = true;
+    let mut stacks = Vec::new();
+    for threads in [1, 3] {
+        let options = Nd2Options::new().decode_threads(threads);
+        let mut nd2 = Nd2File::open_reader_with(Cursor::new(builder.build()), options)?;
+
+        // More frames than one batch, so later batches reuse the workers.
+        let indices: Vec<usize> = (0..7).rev().cycle().take(28).collect();
+        let frames = nd2.read_frames(&indices)?;
+        for (&i, frame) in indices.iter().zip(&frames) {
+            assert_eq!(frame, &nd2.read_frame(i)?);
+        }
+        assert!(nd2.read_frames(&[0, 7]).is_err());
+
+        let stack = nd2.read_stack(0, StackOrder::Tczyx)?;
+        assert_eq!(&stack[12..24], &nd2.read_frame_2d(0, 0, 1, 0)?[..]);
+        stacks.push(stack);
+    }
+    assert_eq!(stacks[0], stacks[1]);
+    Ok(())
+}
+
+#[test]
+fn test_synthetic_read_strategies() -> Result<()> {
+    #[allow(unused_mut)]
+    let mut strategies = vec![
+        ReadStrategy::Auto,
+        ReadStrategy::Buffered,
+        ReadStrategy::Direct,
+    ];
+    #[cfg(feature = "io-uring")]
+    strategies.push(ReadStrategy::IoUring);
+
+    for lossless in [false, true] {
+        let mut builder = Nd2Builder::new(4, 3, 2, 5);
+        builder.lossless = lossless;
+        let path =
+            std::env::temp_dir().join(format!("nd2_rs_test_read_strategies_{}.nd2", lossless));
+        std::fs::write(&path, builder.build())?;
+        for &strategy in &strategies {
+            let options = Nd2Options::new().read_strategy(strategy).decode_threads(2);
+            let mut from_path = Nd2File::open_with(&path, options.clone())?;
+            let mut from_bytes = Nd2File::open_reader_with(Cursor::new(builder.build()), options)?;
+
+            let indices = [4, 0, 3, 1, 2, 0];
+            let frames = from_path.read_frames(&indices)?;
+            assert_eq!(frames, from_bytes.read_frames(&indices)?);
+            for (&i, frame) in indices.iter().zip(&frames) {
+                assert_eq!(frame, &from_path.read_frame(i)?);
+            }
+            assert!(from_path.read_frames(&[1, 5]).is_err());
+            assert_eq!(from_path.summary()?, from_bytes.summary()?);
+        }
+        std::fs::remove_file(&path)?;
+    }
+    Ok(())
+}
+
+#[test]
+fn test_synthetic_reads_after_failed_read() -> Result<()> {
+    for lossless in [false, true] {
+        let mut builder = Nd2Builder::new(4, 3, 2, 3);
+        builder.lossless = lossless;
+        let mut nd2 = common::open(&builder);
+
+        let first = nd2.read_frame(2)?;
+        assert!(nd2.read_frame(3).is_err());
+        assert_eq!(nd2.read_frame(0)?, nd2.read_frames(&[0])?[0]);
+        assert_eq!(nd2.read_frame(2)?, first);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_synthetic_frame_chunk_headers_checked_once() -> Result<()> {
+    for lossless in [false, true] {
+        let mut builder = Nd2Builder::new(4, 3, 2, 12);
+        builder.lossless = lossless;
+        let bytes = builder.build();
+        let path = common::temp_path(&format!("headers_checked_once_{}.nd2", lossless));
+        std::fs::write(&path, &bytes)?;
+        let options = Nd2Options::new().decode_threads(2);
+        let mut nd2 = Nd2File::open_with(&path, options)?;
+        // Frame names of one and two digits have headers of their own length.
+        let all: Vec<usize> = (0..12).collect();
+        let frames = common::open(&builder).read_frames(&all)?;
+        assert_eq!(nd2.read_frames(&[0, 10, 11])?[1..], frames[10..]);
+
+        // Clobber frame 1's chunk magic; frame 0 already confirmed the
+        // header layout, so frame 1 is found from the chunkmap alone.
+        let name = b"ImageDataSeq|1!";
+        let name_at = bytes
+            .windows(name.len())
+            .position(|window| window == name)
+            .unwrap();
+        let mut corrupted = bytes.clone();
+        corrupted[name_at - 16..name_at - 12].fill(0);
+        std::fs::write(&path, &corrupted)?;
+
+        assert_eq!(nd2.read_frame(1)?, frames[1]);
+        assert_eq!(
+            nd2.read_frames(&[2, 1])?,
+            vec![frames[2].clone(), frames[1].clone()]
+        );
+        if lossless {
+            nd2.clear_caches();
+            assert!(nd2.read_frame(1).is_err());
+        }
+        std::fs::remove_file(&path)?;
+    }
+    Ok(())
+}
+
+/// Counts the reads that reach the wrapped source.
+struct CountingReader {
+    inner: Cursor<Vec<u8>>,
+    reads: std::rc::Rc<std::cell::Cell<usize>>,
+}
+
+impl std::io::Read for CountingReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.reads.set(self.reads.get() + 1);
+        self.inner.read(buf)
+    }
+}
+
+impl std::io::Seek for CountingReader {
+    fn seek(&mut self, pos: std::io::SeekFrom) -> std::io::Result<u64> {
+        self.inner.seek(pos)
+    }
+}
+
+#[test]
+fn test_synthetic_coalesced_frame_reads() -> Result<()> {
+    for lossless in [false, true] {
+        let mut builder = Nd2Builder::new(4, 3, 2, 6);
+        builder.lossless = lossless;
+
+        let mut reads_by_limit = Vec::new();
+        for max_bytes in [0, 64, 1 << 20] {
+            let reads = std::rc::Rc::new(std::cell::Cell::new(0));
+            let reader = CountingReader {
+                inner: Cursor::new(builder.build()),
+                reads: reads.clone(),
+            };
+            let options = Nd2Options::new()
+                .read_strategy(ReadStrategy::Direct)
+                .coalesce_reads(max_bytes);
+            let mut nd2 = Nd2File::open_reader_with(reader, options)?;
+            let mut single = common::open(&builder);
+
+            reads.set(0);
+            let frames = nd2.read_frames(&[0, 1, 2, 3, 4, 5])?;
+            reads_by_limit.push(reads.get());
+            for (i, frame) in frames.iter().enumerate() {
+                assert_eq!(frame, &single.read_frame(i)?);
+            }
+            assert_eq!(
+                nd2.read_frames(&[5, 2, 3, 0])?,
+                single.read_frames(&[5, 2, 3, 0])?
+            );
+            assert!(nd2.read_frames(&[4, 5, 6]).is_err());
+        }
+        // Disabled and too small to hold two chunks read the same way; one
+        // coalesced read replaces a read per chunk.
+        assert_eq!(reads_by_limit[0], reads_by_limit[1]);
+        assert!(reads_by_limit[2] < reads_by_limit[0]);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_synthetic_typed_frame_reads() -> Result<()> {
+    let builder = Nd2Builder::new(4, 3, 1, 2);
+    let mut nd2 = common::open(&builder);
+
+    let expected = &builder.frames[1];
+    let wide: Vec<u32> = nd2.read_frame_as(1)?;
+    assert_eq!(
+        wide,
+        expected.iter().map(|&v| u32::from(v)).collect::<Vec<_>>()
+    );
+    let float: Vec<f32> = nd2.read_frame_as(1)?;
+    assert_eq!(
+        float,
+        expected.iter().map(|&v| f32::from(v)).collect::<Vec<_>>()
+    );
+
+    // 16-bit data cannot be narrowed to u8.
+    let err = nd2.read_frame_as::<u8>(1).unwrap_err();
+    assert!(err.is_input());
+    assert_eq!(
+        nd2.read_frame_native(1)?,
+        PixelBuffer::U16(expected.clone())
+    );
+
+    // 8-bit files read natively as u8.
+    let attributes = Clx::Level(
+        "SLxImageAttributes",
+        vec![
+            Clx::U32("uiWidth", 4),
+            Clx::U32("uiWidthBytes", 4),
+            Clx::U32("uiHeight", 3),
+            Clx::U32("uiComp", 1),
+            Clx::U32("uiBpcInMemory", 8),
+            Clx::U32("uiBpcSignificant", 8),
+            Clx::U32("uiSequenceCount", 1),
+        ],
+    );
+    let mut frame = 0f64.to_le_bytes().to_vec();
+    frame.extend(0u8..12);
+    let file = common::build_file(
+        "Ver3.0",
+        &[
+            (b"ImageAttributesLV!".to_vec(), attributes.encode()),
+            (b"ImageDataSeq|0!".to_vec(), frame),
+        ],
+    );
+    let buffer = Nd2File::open_reader(Cursor::new(file))?.read_frame_native(0)?;
+    assert_eq!(buffer.type_name(), "u8");
+    assert_eq!(buffer, PixelBuffer::U8((0..12).collect()));
+    Ok(())
+}
+
+#[test]
+fn test_synthetic_frame_coord() -> Result<()> {
+    let mut builder = Nd2Builder::new(4, 3, 1, 6);
+    builder.experiment = Some(Clx::Level(
+        "SLxExperiment",
+        vec![
+            Clx::U32("eType", 1),
+            Clx::Level("uLoopPars", vec![Clx::U32("uiCount", 3)]),
+            Clx::Level(
+                "ppNextLevelEx",
+                vec![Clx::Level(
+                    "i0000000000",
+                    vec![
+                        Clx::U32("eType", 4),
+                        Clx::Level(
+                            "uLoopPars",
+                            vec![Clx::U32("uiCount", 2), Clx::F64("dZStep", 1.0)],
+                        ),
+                    ],
+                )],
+            ),
+        ],
+    ));
+    let mut nd2 = common::open(&builder);
+
+    let coord = FrameCoord {
+        t: 2,
+        z: 1,
+        ..FrameCoord::default()
+    };
+    assert_eq!(nd2.seq_index_for(&coord)?, 5);
+    assert_eq!(nd2.read_frame_at(&coord)?, builder.frames[5]);
+    assert_eq!(
+        nd2.read_frame_at(&FrameCoord::new(0, 1, 0, 0))?,
+        builder.frames[2]
+    );
+
+    let err = nd2.seq_index_for(&FrameCoord::new(0, 3, 0, 0)).unwrap_err();
+    assert!(format!("{err:?}").contains("time index"));
+    assert!(nd2
+        .read_frame_at(&FrameCoord::new(0, 0, 1, 0))
+        .unwrap_err()
+        .is_input());
+    Ok(())
+}
+
+#[test]
+fn test_synthetic_split_channels() -> Result<()> {
+    let mut builder = Nd2Builder::new(4, 3, 3, 2);
+    builder.experiment = Some(Clx::Level(
+        "SLxExperiment",
+        vec![
+            Clx::U32("eType", 1),
+            Clx::Level("uLoopPars", vec![Clx::U32("uiCount", 2)]),
+        ],
+    ));
+    let mut nd2 = common::open(&builder);
+
+    let planes = nd2.frame(1)?.split_channels(&mut nd2)?;
+    assert_eq!(planes.len(), 3);
+    assert_eq!(planes.concat(), nd2.read_frame(1)?);
+    assert_eq!(nd2.read_channel(1, 2)?, planes[2]);
+    // The on-disk interleaving, strided by hand.
+    let yxc: Vec<u16> = nd2.read_frame_ordered(1, FrameOrder::Yxc)?;
+    let strided: Vec<u16> = yxc.iter().skip(1).step_by(3).copied().collect();
+    assert_eq!(nd2.read_channel(1, 1)?, strided);
+
+    let err = nd2.read_channel(1, 3).unwrap_err();
+    assert!(err.is_input());
+    Ok(())
+}
+
+#[test]
+fn test_synthetic_axis_order() -> Result<()> {
+    let mut builder = Nd2Builder::new(4, 3, 2, 3);
+    let mut nd2 = common::open(&builder);
+
+    // YXC is the on-disk interleaving.
+    let yxc: Vec<u16> = nd2.read_frame_ordered(1, FrameOrder::Yxc)?;
+    assert_eq!(yxc, builder.frames[1]);
+    let cyx: Vec<u16> = nd2.read_frame_ordered(1, FrameOrder::Cyx)?;
+    assert_eq!(cyx, nd2.read_frame(1)?);
+
+    // Z loop of 3 with two in-pixel channels: (T, C, Z) = (1, 2, 3).
+    builder.experiment = Some(Clx::Level(
+        "SLxExperiment",
+        vec![
+            Clx::U32("eType", 4),
+            Clx::Level("uLoopPars", vec![Clx::U32("uiCount", 3)]),
+        ],
+    ));
+    let mut nd2 = common::open(&builder);
+    assert_eq!(
+        (
+            nd2.n_positions()?,
+            nd2.n_timepoints()?,
+            nd2.n_channels()?,
+            nd2.n_z()?
+        ),
+        (1, 1, 2, 3)
+    );
+    let plane = 12;
+    let tczyx = nd2.read_stack(0, StackOrder::Tczyx)?;
+    let tzcyx = nd2.read_stack(0, StackOrder::Tzcyx)?;
+    assert_eq!(tczyx.len(), 2 * 3 * plane);
+    let (c, z) = (1, 2);
+    let expected = nd2.read_frame_2d(0, 0, c, z)?;
+    assert_eq!(&tczyx[(c * 3 + z) * plane..][..plane], &expected[..]);
+    assert_eq!(&tzcyx[(z * 2 + c) * plane..][..plane], &expected[..]);
+    assert!(nd2.read_stack(1, StackOrder::Tczyx).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_synthetic_cache_policy() -> Result<()> {
+    let builder = Nd2Builder::new(4, 3, 1, 2);
+    let mut cached = Nd2File::from_bytes(builder.build())?;
+    let summary = cached.summary()?;
+    cached.clear_caches();
+    assert_eq!(cached.summary()?, summary);
+
+    for options in [
+        Nd2Options::new().cache_metadata(false),
+        Nd2Options::new().max_cached_metadata_bytes(0),
+    ] {
+        let mut nd2 = Nd2File::open_reader_with(Cursor::new(builder.build()), options)?;
+        assert_eq!(nd2.summary()?, summary);
+        assert_eq!(nd2.read_frame(1)?, builder.frames[1]);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_synthetic_debug_summary() -> Result<()> {
+    let builder = Nd2Builder::new(4, 3, 2, 3);
+    let mut nd2 = common::open(&builder);
+    let before = format!("{nd2:?}");
+    assert!(before.starts_with("Nd2File { version: (3, 0)"));
+    assert!(before.ends_with(".. }"));
+
+    nd2.summary()?;
+    let after = format!("{nd2:?}");
+    assert!(after.contains("dims: P=1 T=1 C=2 Z=1 Y=3 X=4"));
+    assert!(after.contains("channels: 2"));
+    assert!(!after.contains("reader"));
+    Ok(())
+}
+
+#[test]
+fn test_synthetic_snapshot() -> Result<()> {
+    let builder = Nd2Builder::new(4, 3, 1, 3);
+    let mut nd2 = common::open(&builder);
+
+    let snapshot = nd2.snapshot()?;
+    assert_eq!((snapshot.version_major, snapshot.version_minor), (3, 0));
+    assert_eq!(snapshot.attributes.sequence_count, 3);
+    assert_eq!(snapshot.summary, nd2.summary()?);
+    assert_eq!(snapshot.frames, nd2.frames()?);
+    drop(nd2);
+
+    // The snapshot outlives the file handle.
+    assert_eq!(snapshot.frames[2].index(), 2);
+    Ok(())
+}
+
+#[test]
+fn test_synthetic_sansio_core() -> Result<()> {
+    use nd2_rs::sansio;
+
+    let builder = Nd2Builder::new(4, 3, 1, 2);
+    let bytes = builder.build();
+    let mut diagnostics = Vec::new();
+    let chunkmap = sansio::parse_chunkmap(&bytes, &mut diagnostics)?;
+    assert!(diagnostics.is_empty());
+
+    // Same result when fed only the trailer and then the section.
+    let trailer = &bytes[bytes.len() - sansio::CHUNKMAP_TRAILER_LEN..];
+    let offset = sansio::parse_chunkmap_trailer(trailer)?;
+    let section = &bytes[offset as usize..];
+    let from_section =
+        sansio::parse_chunkmap_section(section, offset, bytes.len() as u64, &mut diagnostics)?;
+    assert_eq!(from_section, chunkmap);
+
+    let attributes = sansio::chunk_data(&bytes, &chunkmap, b"ImageAttributesLV!")?;
+    let clx = sansio::ClxLiteParser::new(false).parse(attributes)?;
+    assert!(clx.as_object().is_some());
+    assert!(sansio::chunk_data(&bytes, &chunkmap, b"Missing!").is_err());
+    assert!(sansio::parse_chunkmap(&bytes[..20], &mut diagnostics).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_synthetic_borrowed_clx_matches_owned() -> Result<()> {
+    use nd2_rs::sansio::{ClxLiteParser, ClxValueRef};
+
+    let clx = Clx::Level(
+        "SLxExperiment",
+        vec![
+            Clx::Str("sDescription", "Z-stack µm".to_string()),
+            Clx::U32("uiCount", 3),
+            Clx::Bytes("pData", vec![1, 2, 3]),
+            Clx::Level(
+                "Points",
+                vec![
+                    Clx::Level("", vec![Clx::F64("dPosX", 1.5)]),
+                    Clx::Level("", vec![Clx::F64("dPosX", -2.0)]),
+                ],
+            ),
+        ],
+    )
+    .encode();
+
+    for strip_prefix in [false, true] {
+        let parser = ClxLiteParser::new(strip_prefix);
+        let borrowed = parser.parse_borrowed(&clx)?;
+        assert_eq!(borrowed.to_owned_value()?, parser.parse(&clx)?);
+    }
+
+    let borrowed = ClxLiteParser::new(false).parse_borrowed(&clx)?;
+    let exp = borrowed.get("SLxExperiment").expect("experiment level");
+    let description = exp.get("sDescription").and_then(ClxValueRef::as_str);
+    assert!(description.is_some_and(|s| s.eq_str("Z-stack µm")));
+    assert_eq!(
+        description.map(|s| s.to_string()),
+        Some("Z-stack µm".into())
+    );
+    assert_eq!(
+        exp.get("pData").and_then(ClxValueRef::as_bytes),
+        Some(&[1u8, 2, 3][..])
+    );
+    let points = exp
+        .get("Points")
+        .and_then(ClxValueRef::as_array)
+        .expect("points");
+    assert_eq!(
+        points[1].get("dPosX").and_then(ClxValueRef::as_f64),
+        Some(-2.0)
+    );
+    Ok(())
+}
+
+#[test]
+fn test_synthetic_clx_strings() -> Result<()> {
+    use nd2_rs::sansio::{ClxLiteParser, ClxValue};
+
+    let strings = [
+        ("sEmpty", ""),
+        ("sUnit", "Z-stack µm"),
+        ("sName", "DAPI 🔬 405"),
+    ];
+    let clx = Clx::Level(
+        "Strings",
+        strings
+            .iter()
+            .map(|&(name, s)| Clx::Str(name, s.to_string()))
+            .collect(),
+    )
+    .encode();
+    let parsed = ClxLiteParser::new(false).parse(&clx)?;
+    let level = parsed
+        .as_object()
+        .and_then(|o| o.get("Strings"))
+        .and_then(ClxValue::as_object)
+        .expect("strings level");
+    for (name, s) in strings {
+        assert_eq!(level.get(name), Some(&ClxValue::String(s.to_string())));
+    }
+
+    // A string running into the end of the data has no terminator.
+    let unterminated = Clx::Str("sValue", "abc".to_string()).encode();
+    let unterminated = &unterminated[..unterminated.len() - 2];
+    assert!(ClxLiteParser::new(false).parse(unterminated).is_err());
+
+    // Unpaired surrogates, in a name and in a string value.
+    let utf16 = |units: &[u16]| -> Vec<u8> { units.iter().flat_map(|u| u.to_le_bytes()).collect() };
+    let mut entry = vec![8, 3];
+    entry.extend(utf16(&[0x73, 0xDC00, 0]));
+    entry.extend(utf16(&[0x61, 0xD800, 0x62, 0]));
+    let clx = Clx::Level("Strings", vec![Clx::Raw(entry)]).encode();
+    assert!(ClxLiteParser::new(false).parse(&clx).is_err());
+    let mut diagnostics = Vec::new();
+    let parsed = ClxLiteParser::new(false)
+        .lenient(true)
+        .parse_with_diagnostics(&clx, &mut diagnostics)?;
+    let level = parsed
+        .as_object()
+        .and_then(|o| o.get("Strings"))
+        .and_then(ClxValue::as_object)
+        .expect("strings level");
+    assert_eq!(
+        level.get("s\u{FFFD}"),
+        Some(&ClxValue::String("a\u{FFFD}b".to_string()))
+    );
+    let kinds: Vec<_> = diagnostics.iter().map(|d| d.kind).collect();
+    assert_eq!(kinds, [DiagnosticKind::InvalidUtf16; 2]);
+    Ok(())
+}
+
+#[test]
+fn test_synthetic_clx_unknown_types() -> Result<()> {
+    use nd2_rs::sansio::{ClxLiteParser, ClxValue, ClxValueRef, ClxVisitor, Utf16Str};
+
+    // Type 42 with a one-character name and a value of unknown length.
+    let unknown = Clx::Raw(vec![42, 2, b'x', 0, 0, 0, 1, 2, 3, 4, 5]);
+    let clx = Clx::Level(
+        "Root",
+        vec![
+            Clx::U32("uiBefore", 1),
+            Clx::Level(
+                "Newer",
+                vec![Clx::U32("uiKept", 2), unknown, Clx::U32("uiLost", 3)],
+            ),
+            Clx::U32("uiAfter", 4),
+        ],
+    )
+    .encode();
+
+    let strict = ClxLiteParser::new(false);
+    assert!(strict.parse(&clx).is_err());
+    assert!(strict.parse_borrowed(&clx).is_err());
+
+    let lenient = ClxLiteParser::new(false).lenient(true);
+    let mut diagnostics = Vec::new();
+    let parsed = lenient.parse_with_diagnostics(&clx, &mut diagnostics)?;
+    let root = parsed
+        .as_object()
+        .and_then(|o| o.get("Root"))
+        .and_then(ClxValue::as_object)
+        .expect("root level");
+    assert_eq!(root.get("uiBefore"), Some(&ClxValue::UInt(1)));
+    assert_eq!(root.get("uiAfter"), Some(&ClxValue::UInt(4)));
+    let newer = root["Newer"].as_object().expect("newer level");
+    assert_eq!(newer.get("uiKept"), Some(&ClxValue::UInt(2)));
+    assert!(!newer.contains_key("uiLost"));
+    let kinds: Vec<_> = diagnostics.iter().map(|d| d.kind).collect();
+    assert_eq!(kinds, [DiagnosticKind::UnknownClxType]);
+
+    let ClxValueRef::Object(root) = lenient.parse_borrowed(&clx)? else {
+        panic!("expected an object");
+    };
+    let ClxValueRef::Object(root) = &root[0].1 else {
+        panic!("expected the root level");
+    };
+    let names: Vec<_> = root.iter().map(|(name, _)| name.to_string()).collect();
+    assert_eq!(names, ["uiBefore", "Newer", "uiAfter"]);
+
+    struct Skipped(Vec<(String, u8)>);
+    impl ClxVisitor for Skipped {
+        fn value(&mut self, _: Utf16Str<'_>, _: ClxValueRef<'_>) -> Result<()> {
+            Ok(())
+        }
+        fn skipped(&mut self, name: Utf16Str<'_>, data_type: u8) -> Result<()> {
+            self.0.push((name.to_string(), data_type));
+            Ok(())
+        }
+    }
+    let mut skipped = Skipped(Vec::new());
+    lenient.visit(&clx, &mut skipped)?;
+    assert_eq!(skipped.0, [("x".to_string(), 42)]);
+
+    // Files with newer entry types still open, with a diagnostic.
+    let builder = Nd2Builder::new(4, 3, 2, 2);
+    let mut chunks = builder.chunks();
+    let Clx::Level(name, mut items) = builder.attributes_clx() else {
+        unreachable!();
+    };
+    items.push(Clx::Level("Newer", vec![Clx::Raw(vec![42, 1, 0, 0, 9])]));
+    chunks[0].1 = Clx::Level(name, items).encode();
+    let file = common::build_file(builder.version, &chunks);
+    let mut nd2 = Nd2File::open_reader(Cursor::new(file))?;
+    assert_eq!(nd2.read_frame(1)?, common::open(&builder).read_frame(1)?);
+    let kinds: Vec<_> = nd2.diagnostics().iter().map(|d| d.kind).collect();
+    assert_eq!(kinds, [DiagnosticKind::UnknownClxType]);
+    Ok(())
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn test_synthetic_clx_to_json() -> Result<()> {
+    use nd2_rs::{ClxObject, ClxValue};
+
+    let level: ClxObject = [
+        ("bFlag".into(), ClxValue::Bool(true)),
+        ("iOffset".into(), ClxValue::Int(-3)),
+        ("dGain".into(), ClxValue::Float(1.5)),
+        ("dUnset".into(), ClxValue::Float(f64::NAN)),
+        (
+            "".into(),
+            ClxValue::Array(vec![ClxValue::UInt(7), ClxValue::String("a".into())]),
+        ),
+    ]
+    .into_iter()
+    .collect();
+    let clx = ClxValue::Object(
+        [
+            ("Level".into(), ClxValue::Object(level)),
+            ("One".into(), ClxValue::ByteArray(b"M".to_vec())),
+            ("Two".into(), ClxValue::ByteArray(b"Ma".to_vec())),
+            ("Three".into(), ClxValue::ByteArray(b"Man?".to_vec())),
+        ]
+        .into_iter()
+        .collect(),
+    );
+
+    let json = clx.to_json();
+    assert_eq!(json["Level"]["bFlag"].as_bool(), Some(true));
+    assert_eq!(json["Level"]["iOffset"].as_i64(), Some(-3));
+    assert_eq!(json["Level"]["dGain"].as_f64(), Some(1.5));
+    assert!(json["Level"]["dUnset"].is_null());
+    assert_eq!(json["Level"][""][0].as_u64(), Some(7));
+    assert_eq!(json["Level"][""][1].as_str(), Some("a"));
+    assert_eq!(json["One"].as_str(), Some("TQ=="));
+    assert_eq!(json["Two"].as_str(), Some("TWE="));
+    assert_eq!(json["Three"].as_str(), Some("TWFuPw=="));
+    assert_eq!(serde_json::Value::from(clx), json);
+    Ok(())
+}
+
+#[test]
+fn test_synthetic_clx_nesting_limits() -> Result<()> {
+    use nd2_rs::sansio::{ClxLiteParser, ClxValueRef, ClxVisitor, Utf16Str};
+
+    struct Count(usize);
+    impl ClxVisitor for Count {
+        fn value(&mut self, _: Utf16Str<'_>, _: ClxValueRef<'_>) -> Result<()> {
+            self.0 += 1;
+            Ok(())
+        }
+    }
+    let is_limit = |err: Nd2Error| {
+        matches!(
+            err,
+            Nd2Error::File {
+                source: FileError::LimitExceeded { .. }
+            }
+        )
+    };
+    let parser = ClxLiteParser::new(false);
+    let nest = |depth: usize, wrap: fn(Clx) -> Clx| {
+        (0..depth)
+            .fold(Clx::U32("uiLeaf", 1), |inner, _| wrap(inner))
+            .encode()
+    };
+    let levels = |inner| Clx::Level("Level", vec![inner]);
+    let arrays = |inner: Clx| Clx::Bytes("Nested", inner.encode());
+
+    for wrap in [levels as fn(Clx) -> Clx, arrays] {
+        let shallow = nest(100, wrap);
+        assert!(parser.parse(&shallow).is_ok());
+        assert!(parser.parse_borrowed(&shallow).is_ok());
+        let mut count = Count(0);
+        parser.visit(&shallow, &mut count)?;
+        assert_eq!(count.0, 1);
+
+        // Crafted data nested far deeper than any real metadata.
+        let deep = nest(300, wrap);
+        assert!(is_limit(parser.parse(&deep).unwrap_err()));
+        assert!(is_limit(parser.parse_borrowed(&deep).unwrap_err()));
+        assert!(is_limit(parser.visit(&deep, &mut Count(0)).unwrap_err()));
+    }
+    Ok(())
+}
+
+#[test]
+fn test_synthetic_resource_limits() -> Result<()> {
+    use nd2_rs::sansio::ClxLiteParser;
+
+    let is_limit = |err: Nd2Error| {
+        matches!(
+            err,
+            Nd2Error::File {
+                source: FileError::LimitExceeded { .. }
+            }
+        )
+    };
+    let open = |builder: &Nd2Builder, limits: Limits| {
+        let options = Nd2Options::new().limits(limits);
+        Nd2File::open_reader_with(Cursor::new(builder.build()), options)
+    };
+    // 8 KiB frames; the chunkmap and attributes are far smaller.
+    let mut builder = Nd2Builder::new(64, 64, 1, 2);
+    assert!(is_limit(
+        open(&builder, Limits::new().max_chunk_bytes(16)).unwrap_err()
+    ));
+    for lossless in [false, true] {
+        builder.lossless = lossless;
+        let loose = Limits::new()
+            .max_chunk_bytes(16 * 1024)
+            .max_decompressed_bytes(16 * 1024);
+        open(&builder, loose)?.read_frame(1)?;
+        let tight = if lossless {
+            loose.max_decompressed_bytes(4096)
+        } else {
+            loose.max_chunk_bytes(4096)
+        };
+        let mut nd2 = open(&builder, tight)?;
+        assert_eq!(nd2.shape()?, (64, 64));
+        assert!(is_limit(nd2.read_frame(1).unwrap_err()));
+    }
+    for limits in [Limits::new().max_clx_nodes(3), Limits::new().max_depth(0)] {
+        assert!(is_limit(open(&builder, limits)?.shape().unwrap_err()));
+    }
+
+    let clx = builder.attributes_clx().encode();
+    let parser = |nodes| ClxLiteParser::new(false).limits(Limits::new().max_clx_nodes(nodes));
+    assert!(parser(1000).parse(&clx).is_ok());
+    assert!(is_limit(parser(3).parse(&clx).unwrap_err()));
+    assert!(is_limit(parser(3).parse_borrowed(&clx).unwrap_err()));
+    Ok(())
+}
+
+#[test]
+fn test_synthetic_clx_keys_are_shared() -> Result<()> {
+    use nd2_rs::sansio::{ClxLiteParser, ClxValue};
+
+    let point = |x: f64| Clx::Level("", vec![Clx::F64("dPosX", x)]);
+    let clx = Clx::Level("Points", vec![point(1.5), point(-2.0), point(3.0)]).encode();
+    let parsed = ClxLiteParser::new(false).parse(&clx)?;
+    let Some(ClxValue::Array(points)) = parsed.as_object().and_then(|o| o.get("Points")) else {
+        panic!("expected an array of points");
+    };
+    let keys: Vec<_> = points
+        .iter()
+        .map(|point| point.as_object().unwrap().keys().next().unwrap().clone())
+        .collect();
+    assert_eq!(&*keys[0], "dPosX");
+    assert!(keys
+        .windows(2)
+        .all(|w| std::sync::Arc::ptr_eq(&w[0], &w[1])));
+    Ok(())
+}
+
+#[test]
+fn test_synthetic_clx_visitor() -> Result<()> {
+    use nd2_rs::sansio::{ClxLiteParser, ClxValueRef, ClxVisitor, Utf16Str};
+
+    /// Records entries as `name=value`, `name{` and `}`, skipping "pSkip".
+    struct Events(Vec<String>);
+
+    impl ClxVisitor for Events {
+        fn enter(&mut self, name: Utf16Str<'_>) -> Result<bool> {
+            if name.eq_str("pSkip") {
+                return Ok(false);
+            }
+            self.0.push(format!("{name}{{"));
+            Ok(true)
+        }
+
+        fn leave(&mut self) -> Result<()> {
+            self.0.push("}".to_string());
+            Ok(())
+        }
+
+        fn value(&mut self, name: Utf16Str<'_>, value: ClxValueRef<'_>) -> Result<()> {
+            let value = match value {
+                ClxValueRef::String(s) => s.to_string(),
+                ClxValueRef::ByteArray(bytes) => format!("{bytes:?}"),
+                other => format!("{:?}", other.as_f64().or(other.as_u64().map(|u| u as f64))),
+            };
+            self.0.push(format!("{name}={value}"));
+            Ok(())
+        }
+    }
+
+    let nested = Clx::Level("pInner", vec![Clx::U32("uiInner", 7)]).encode();
+    let clx = Clx::Level(
+        "SLxExperiment",
+        vec![
+            Clx::Str("sDescription", "Z-stack".to_string()),
+            Clx::Level("pSkip", vec![Clx::U32("uiHidden", 1)]),
+            Clx::Bytes("pData", vec![1, 2, 3]),
+            Clx::Bytes("pNested", nested),
+            Clx::Level("Points", vec![Clx::Level("", vec![Clx::F64("dPosX", 1.5)])]),
+        ],
+    )
+    .encode();
+
+    let mut events = Events(Vec::new());
+    ClxLiteParser::new(false).visit(&clx, &mut events)?;
+    assert_eq!(
+        events.0,
+        [
+            "SLxExperiment{",
+            "sDescription=Z-stack",
+            "pData=[1, 2, 3]",
+            "pNested{",
+            "pInner{",
+            "uiInner=Some(7.0)",
+            "}",
+            "}",
+            "Points{",
+            "{",
+            "dPosX=Some(1.5)",
+            "}",
+            "}",
+            "}",
+        ]
+    );
+
+    let mut stripped = Events(Vec::new());
+    ClxLiteParser::new(true).visit(&clx, &mut stripped)?;
+    assert_eq!(stripped.0[1], "Description=Z-stack");
+    Ok(())
+}
+
+#[test]
+fn test_synthetic_open_share_modes() -> Result<()> {
+    let builder = Nd2Builder::new(4, 3, 1, 2);
+    let path = common::temp_path("share.nd2");
+    std::fs::write(&path, builder.build())?;
+
+    for mode in [ShareMode::Shared, ShareMode::DenyWrite] {
+        let mut nd2 = Nd2File::open_with(&path, Nd2Options::new().share_mode(mode))?;
+        assert_eq!(nd2.read_frame(1)?, builder.frames[1]);
+    }
+    let _ = std::fs::remove_file(&path);
+    Ok(())
+}
+
+#[test]
+fn test_synthetic_frame_with_meta() -> Result<()> {
+    for lossless in [false, true] {
+        let mut builder = Nd2Builder::new(4, 3, 1, 3);
+        builder.lossless = lossless;
+        let mut nd2 = common::open(&builder);
+
+        let (pixels, meta) = nd2.read_frame_with_meta(2)?;
+        assert_eq!(pixels, builder.frames[2]);
+        assert_eq!(meta.index, 2);
+        assert_eq!(meta.timestamp_ms, Some(200.0));
+        assert_eq!(meta.coords.get("T"), Some(&2));
+        assert_eq!(meta.stage_position_um, None);
+        assert!(nd2.read_frame_with_meta(3).is_err());
+    }
+    Ok(())
+}
+
+#[test]
+fn test_synthetic_frame_times() -> Result<()> {
+    let mut builder = Nd2Builder::new(4, 3, 1, 3);
+    // Chunk timestamps are 0, 100 and 200 ms.
+    assert_eq!(common::open(&builder).frame_times()?, [0.0, 100.0, 200.0]);
+
+    // The cache wins; room allocated past the last frame is dropped.
+    let cache: Vec<u8> = [5.0f64, 105.5, 210.25, 0.0]
+        .iter()
+        .flat_map(|v| v.to_le_bytes())
+        .collect();
+    builder.extra_chunks = vec![(b"CustomData|AcqTimesCache!".to_vec(), cache)];
+    assert_eq!(common::open(&builder).frame_times()?, [5.0, 105.5, 210.25]);
+    Ok(())
+}
+
+#[test]
+fn test_synthetic_frame_positions() -> Result<()> {
+    let floats =
+        |values: &[f64]| -> Vec<u8> { values.iter().flat_map(|v| v.to_le_bytes()).collect() };
+    let point = |key: &'static str, name: &str| {
+        Clx::Level(
+            key,
+            vec![
+                Clx::F64("dPosX", 0.0),
+                Clx::F64("dPosY", 0.0),
+                Clx::F64("dPFSOffset", 12.0),
+                Clx::Str("dPosName", name.to_string()),
+            ],
+        )
+    };
+    let mut builder = Nd2Builder::new(4, 3, 1, 2);
+    builder.experiment = Some(Clx::Level(
+        "SLxExperiment",
+        vec![
+            Clx::U32("eType", 2),
+            Clx::Level(
+                "uLoopPars",
+                vec![
+                    Clx::U32("uiCount", 2),
+                    Clx::Level(
+                        "Points",
+                        vec![point("i0000000000", "A1"), point("i0000000001", "A2")],
+                    ),
+                ],
+            ),
+        ],
+    ));
+    assert!(common::open(&builder).frame_positions()?.is_empty());
+    assert!(common::open(&builder).frame_position_details()?.is_empty());
+
+    builder.extra_chunks = vec![
+        (b"CustomData|X!".to_vec(), floats(&[100.25, 5100.0])),
+        (b"CustomData|Y!".to_vec(), floats(&[-20.0, -20.5])),
+        (b"CustomData|Z!".to_vec(), floats(&[1500.0, 1502.0])),
+    ];
+    let mut nd2 = common::open(&builder);
+    let stage = nd2.frame_positions()?;
+    assert_eq!(stage.len(), 2);
+    assert_eq!(
+        (stage[1].x, stage[1].y, stage[1].z),
+        (5100.0, -20.5, 1502.0)
+    );
+    let positions = nd2.frame_position_details()?;
+    assert_eq!(positions[1].stage_position_um, stage[1]);
+    assert_eq!(positions[1].name.as_deref(), Some("A2"));
+    assert_eq!(positions[1].pfs_offset, Some(12.0));
+
+    builder
+        .extra_chunks
+        .push((b"CustomData|PFS_OFFSET!".to_vec(), floats(&[11.5, 12.5])));
+    let positions = common::open(&builder).frame_position_details()?;
+    assert_eq!(positions[0].pfs_offset, Some(11.5));
+    assert_eq!(positions[0].name.as_deref(), Some("A1"));
+    Ok(())
+}
+
+#[test]
+fn test_synthetic_frame_metadata() -> Result<()> {
+    let floats =
+        |values: &[f64]| -> Vec<u8> { values.iter().flat_map(|v| v.to_le_bytes()).collect() };
+    let picture = |name: &str| {
+        Clx::Level(
+            "SLxPictureMetadata",
+            vec![Clx::Level(
+                "sPicturePlanes",
+                vec![Clx::Level(
+                    "sPlaneNew",
+                    vec![Clx::Level(
+                        "a0",
+                        vec![Clx::Str("sDescription", name.to_string())],
+                    )],
+                )],
+            )],
+        )
+        .encode()
+    };
+    let mut builder = Nd2Builder::new(4, 3, 1, 3);
+    builder.extra_chunks = vec![
+        (b"CustomData|X!".to_vec(), floats(&[1.0, 2.0, 3.0])),
+        (b"CustomData|Y!".to_vec(), floats(&[-1.0, -2.0, -3.0])),
+        (b"CustomData|Z!".to_vec(), floats(&[10.0, 10.5, 11.0])),
+        (
+            b"CustomData|Camera_ExposureTime1!".to_vec(),
+            floats(&[50.0, 50.0, 75.0]),
+        ),
+        // Not one float per frame: ignored.
+        (b"CustomData|PFS_OFFSET!".to_vec(), floats(&[100.0])),
+        (b"ImageMetadataSeqLV|0!".to_vec(), picture("GFP")),
+        (b"ImageMetadataSeqLV|2!".to_vec(), picture("GFP (bleached)")),
+    ];
+    let mut nd2 = common::open(&builder);
+
+    let meta = nd2.frame_metadata(2)?;
+    assert_eq!(meta.index, 2);
+    assert_eq!(meta.timestamp_ms, Some(200.0));
+    let stage = meta.stage_position_um.unwrap();
+    assert_eq!((stage.x, stage.y, stage.z), (3.0, -3.0, 11.0));
+    assert_eq!(meta.exposure_ms, Some(75.0));
+    assert_eq!(meta.pfs_offset, None);
+    assert_eq!(meta.channels.unwrap()[0].channel.name, "GFP (bleached)");
+
+    let meta = nd2.frame_metadata(1)?;
+    assert_eq!(meta.channels.unwrap()[0].channel.name, "GFP");
+    assert_eq!(nd2.read_frame_with_meta(1)?.1, nd2.frame_metadata(1)?);
+    assert!(nd2.frame_metadata(3).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_synthetic_frame_handles() -> Result<()> {
+    let builder = Nd2Builder::new(4, 3, 1, 3);
+    let mut nd2 = common::open(&builder);
+
+    let frames = nd2.frames()?;
+    assert_eq!(frames.len(), 3);
+    assert!(frames.windows(2).all(|w| w[0].offset() < w[1].offset()));
+
+    let selected: Vec<_> = frames.iter().filter(|f| f.coord("T") == Some(1)).collect();
+    assert_eq!(selected.len(), 1);
+    assert_eq!(selected[0].pixels(&mut nd2)?, builder.frames[1]);
+    assert_eq!(nd2.frame(2)?.index(), 2);
+    assert!(nd2.frame(3).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_synthetic_frame_index() -> Result<()> {
+    let builder = Nd2Builder::new(4, 3, 1, 3);
+    let mut nd2 = common::open(&builder);
+
+    let index = nd2.frame_index()?.clone();
+    assert_eq!(index.len(), 3);
+    assert_eq!(index.axes.len(), index.shape.len());
+    let t = index.axes.iter().position(|axis| axis == "T").unwrap();
+    for (seq, coords) in index.coords.iter().enumerate() {
+        assert_eq!(coords[t], seq);
+        assert_eq!(index.seq_index(coords)?, seq);
+        assert_eq!(index.frame(seq)?, nd2.frame(seq)?);
+    }
+    assert!(index.frame(3).is_err());
+
+    nd2.clear_caches();
+    assert_eq!(nd2.frame_index()?, &index);
+    let mut uncached = Nd2File::open_reader_with(
+        Cursor::new(builder.build()),
+        Nd2Options::new().cache_metadata(false),
+    )?;
+    assert_eq!(uncached.frame_index()?, &index);
+    Ok(())
+}
+
+#[test]
+fn test_synthetic_frame_chunks_out_of_order() -> Result<()> {
+    let builder = Nd2Builder::new(4, 3, 1, 3);
+    let mut chunks = builder.chunks();
+    chunks[1..].reverse();
+    chunks.push((b"ImageDataSeq|4294967295!".to_vec(), vec![0; 8]));
+    let mut nd2 = Nd2File::open_reader(Cursor::new(common::build_file(builder.version, &chunks)))?;
+
+    for (i, expected) in builder.frames.iter().enumerate() {
+        assert_eq!(&nd2.read_frame(i)?, expected);
+    }
+    assert!(nd2
+        .frames()?
+        .windows(2)
+        .all(|w| w[0].offset() > w[1].offset()));
+    Ok(())
+}
+
+#[test]
+fn test_synthetic_tiff_export() -> Result<()> {
+    let builder = Nd2Builder::new(4, 3, 2, 3);
+    let mut nd2 = common::open(&builder);
+    let path = common::temp_path("export.tif");
+
+    TiffExporter::new(&path).export(&mut nd2)?;
+    let bytes = std::fs::read(&path)?;
+    assert_eq!(&bytes[..4], b"II*\0");
+    // 3 timepoints x 2 channels, 12 pixels of u16 each, plus IFDs.
+    assert!(bytes.len() > 6 * 24);
+
+    let one_channel = common::temp_path("export_c1.tif");
+    TiffExporter::new(&one_channel)
+        .channel(1)
+        .export(&mut nd2)?;
+    assert!(std::fs::metadata(&one_channel)?.len() < bytes.len() as u64);
+    assert!(TiffExporter::new(&one_channel)
+        .channel(2)
+        .export(&mut nd2)
+        .is_err());
+
+    let _ = std::fs::remove_file(&path);
+    let _ = std::fs::remove_file(&one_channel);
+    Ok(())
+}
+
+#[test]
+fn test_synthetic_ome_tiff_export() -> Result<()> {
+    // Time outermost, then two positions.
+    let mut builder = Nd2Builder::new(300, 200, 1, 4);
+    builder.experiment = Some(Clx::Level(
+        "SLxExperiment",
+        vec![
+            Clx::U32("eType", 1),
+            Clx::Level("uLoopPars", vec![Clx::U32("uiCount", 2)]),
+            Clx::Level(
+                "ppNextLevelEx",
+                vec![Clx::Level(
+                    "i0000000000",
+                    vec![
+                        Clx::U32("eType", 2),
+                        Clx::Level("uLoopPars", vec![Clx::U32("uiCount", 2)]),
+                    ],
+                )],
+            ),
+        ],
+    ));
+    let mut nd2 = common::open(&builder);
+    let path = common::temp_path("export.ome.tif");
+
+    let cases = [
+        (TiffLayout::default(), TiffCompression::None, 1, false),
+        // One strip per plane, long enough to fill the LZW table.
+        (
+            TiffLayout::Strips { rows: 200 },
+            TiffCompression::Lzw,
+            5,
+            false,
+        ),
+        (
+            TiffLayout::Tiles {
+                width: 64,
+                height: 48,
+            },
+            TiffCompression::Lzw,
+            5,
+            false,
+        ),
+        (
+            TiffLayout::Strips { rows: 7 },
+            TiffCompression::Deflate(6),
+            8,
+            true,
+        ),
+        #[cfg(feature = "tiff-zstd")]
+        (
+            TiffLayout::Tiles {
+                width: 64,
+                height: 48,
+            },
+            TiffCompression::Zstd(3),
+            50000,
+            true,
+        ),
+    ];
+    for (layout, compression, code, big) in cases {
+        OmeTiffExporter::new(&path)
+            .layout(layout)
+            .compression(compression)
+            .big_tiff(big)
+            .export(&mut nd2)?;
+        let bytes = std::fs::read(&path)?;
+        assert_eq!(bytes[2], if big { 43 } else { 42 });
+        let pages = common::read_tiff(&bytes);
+        // Position by position, so pages hold frames 0, 2 (P0) and 1, 3 (P1).
+        assert_eq!(pages.len(), 4);
+        for (page, seq) in pages.iter().zip([0, 2, 1, 3]) {
+            assert_eq!(page.pixels, builder.frames[seq]);
+            assert_eq!(page.tags[&256], [300]);
+            assert_eq!(page.tags[&259], [code]);
+        }
+        match layout {
+            TiffLayout::Tiles { .. } => assert_eq!(pages[0].tags[&324].len(), 5 * 5),
+            TiffLayout::Strips { rows } => {
+                assert_eq!(pages[0].tags[&278], [rows as u64]);
+                assert_eq!(pages[0].tags[&273].len(), (200 + rows - 1) / rows);
+            }
+        }
+        let xml = pages[0].description.as_deref().unwrap();
+        assert!(xml.contains("Type=\"uint16\""));
+        assert!(xml.contains("<TiffData IFD=\"2\" PlaneCount=\"2\"/>"));
+        assert!(!xml.contains("<MetadataOnly/>"));
+        assert!(pages[1..].iter().all(|page| page.description.is_none()));
+        common::validate_ome_xml(xml);
+    }
+
+    let tiles = TiffLayout::Tiles {
+        width: 20,
+        height: 16,
+    };
+    let err = OmeTiffExporter::new(&path)
+        .layout(tiles)
+        .export(&mut nd2)
+        .unwrap_err();
+    assert!(err.is_input(), "{err:?}");
+
+    let _ = std::fs::remove_file(&path);
+    Ok(())
+}
+
+#[test]
+fn test_synthetic_ome_tiff_pyramid() -> Result<()> {
+    let builder = Nd2Builder::new(300, 200, 1, 2);
+    let mut nd2 = common::open(&builder);
+    let path = common::temp_path("pyramid.ome.tif");
+    let tiles = TiffLayout::Tiles {
+        width: 64,
+        height: 64,
+    };
+
+    OmeTiffExporter::new(&path)
+        .layout(tiles)
+        .compression(TiffCompression::Lzw)
+        .pyramid(2)
+        .export(&mut nd2)?;
+    let bytes = std::fs::read(&path)?;
+    let pages = common::read_tiff(&bytes);
+    // Reduced levels hang off their page instead of joining the chain.
+    assert_eq!(pages.len(), 2);
+    let half = |pixels: &[u16], width: usize, height: usize| {
+        let mut out = Vec::new();
+        for y in (0..height).step_by(2) {
+            for x in (0..width).step_by(2) {
+                let sum: u32 = [(0, 0), (1, 0), (0, 1), (1, 1)]
+                    .iter()
+                    .map(|(dx, dy)| pixels[(y + dy) * width + x + dx] as u32)
+                    .sum();
+                out.push(((sum + 2) / 4) as u16);
+            }
+        }
+        out
+    };
+    for (page, frame) in pages.iter().zip(&builder.frames) {
+        assert_eq!(&page.pixels, frame);
+        assert!(!page.tags.contains_key(&254));
+        let sub_ifds = &page.tags[&330];
+        assert_eq!(sub_ifds.len(), 2);
+        let (level1, next) = common::read_tiff_ifd(&bytes, sub_ifds[0], false);
+        assert_eq!(next, 0);
+        assert_eq!(level1.tags[&254], [1]);
+        assert_eq!((level1.tags[&256][0], level1.tags[&257][0]), (150, 100));
+        assert_eq!(level1.pixels, half(frame, 300, 200));
+        let (level2, _) = common::read_tiff_ifd(&bytes, sub_ifds[1], false);
+        assert_eq!((level2.tags[&256][0], level2.tags[&257][0]), (75, 50));
+        assert_eq!(level2.pixels, half(&level1.pixels, 150, 100));
+        // 75 columns need a second, mostly padded, tile.
+        assert_eq!(level2.tags[&324].len(), 2);
+    }
+
+    let err = OmeTiffExporter::new(&path)
+        .pyramid(1)
+        .export(&mut nd2)
+        .unwrap_err();
+    assert!(err.is_input(), "{err:?}");
+
+    let _ = std::fs::remove_file(&path);
+    Ok(())
+}
+
+#[test]
+fn test_synthetic_skip_bad_frames() -> Result<()> {
+    let mut builder = Nd2Builder::new(4, 3, 2, 4);
+    builder.lossless = true;
+    let expected = common::open(&builder).read_stack(0, StackOrder::Tczyx)?;
+    let mut chunks = builder.chunks();
+    let chunk = chunks
+        .iter_mut()
+        .find(|(name, _)| name == b"ImageDataSeq|1!")
+        .unwrap();
+    chunk.1.truncate(8);
+    chunk.1.extend_from_slice(b"not zlib");
+    let file = common::build_file(builder.version, &chunks);
+    let path = common::temp_path("skip_bad_frames.tif");
+
+    let mut nd2 = Nd2File::open_reader(Cursor::new(file.clone()))?;
+    assert!(nd2.read_stack(0, StackOrder::Tczyx).is_err());
+    assert!(TiffExporter::new(&path).export(&mut nd2).is_err());
+    assert!(nd2.bad_frames().is_empty());
+
+    let options = Nd2Options::new().skip_bad_frames(true);
+    let mut nd2 = Nd2File::open_reader_with(Cursor::new(file), options)?;
+    let stack = nd2.read_stack(0, StackOrder::Tczyx)?;
+    assert_eq!(stack.len(), expected.len());
+    assert_ne!(stack, expected);
+    // The planes of frame 1 are zeros, the others read as usual.
+    assert!(stack
+        .iter()
+        .zip(&expected)
+        .all(|(&read, &want)| read == want || read == 0));
+    TiffExporter::new(&path).export(&mut nd2)?;
+    assert_eq!(nd2.bad_frames(), [1]);
+    let bad: Vec<_> = nd2
+        .diagnostics()
+        .iter()
+        .filter(|d| d.kind == DiagnosticKind::BadFrame)
+        .collect();
+    assert_eq!(bad.len(), 1);
+    assert!(bad[0].message.contains("Frame 1 "));
+    // Reads of single frames still fail.
+    assert!(nd2.read_frame(1).is_err());
+    assert!(nd2.read_frame_2d(0, 0, 0, 0).is_ok());
+
+    let _ = std::fs::remove_file(&path);
+    Ok(())
+}
+
+#[test]
+fn test_synthetic_tone_lut_and_png_previews() -> Result<()> {
+    let tone = ToneMapping {
+        range: ToneRange::Fixed { min: 100, max: 300 },
+        gamma: 2.2,
+    };
+    let lut = tone.lut((100, 300));
+    assert_eq!(lut.apply(&[0, 100, 300, 5000]), [0, 0, 255, 255]);
+    assert_eq!(lut.map(200), (0.5f64.powf(1.0 / 2.2) * 255.0).round() as u8);
+    // Inverted limits threshold at the lower one.
+    let ramp: Vec<u16> = (0..=u16::MAX).collect();
+    let mapped = tone.apply(&ramp, (300, 100));
+    assert!(mapped[..=300].iter().all(|&v| v == 0));
+    assert!(mapped[301..].iter().all(|&v| v == 255));
+
+    let builder = Nd2Builder::new(4, 3, 1, 2);
+    let mut nd2 = common::open(&builder);
+    let png_dir = common::temp_path("export_png_preview");
+    PngExporter::new(&png_dir)
+        .tone_mapping(ToneMapping::default())
+        .export(&mut nd2)?;
+    let png = std::fs::read(png_dir.join("t1_c0_z0.png"))?;
+    assert_eq!(&png[12..16], b"IHDR");
+    // Bit depth follows width and height in IHDR.
+    assert_eq!(png[24], 8);
+    let _ = std::fs::remove_dir_all(&png_dir);
+    Ok(())
+}
+
+#[test]
+fn test_synthetic_png_and_zarr_export() -> Result<()> {
+    let builder = Nd2Builder::new(4, 3, 1, 2);
+    let mut nd2 = common::open(&builder);
+
+    let png_dir = common::temp_path("export_png");
+    PngExporter::new(&png_dir).export(&mut nd2)?;
+    let png = std::fs::read(png_dir.join("t1_c0_z0.png"))?;
+    assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+    assert_eq!(&png[12..16], b"IHDR");
+
+    let zarr_dir = common::temp_path("export.zarr");
+    ZarrExporter::new(&zarr_dir).export(&mut nd2)?;
+    let zarray = std::fs::read_to_string(zarr_dir.join(".zarray"))?;
+    assert!(zarray.contains("\"shape\": [2, 1, 1, 3, 4]"));
+    let chunk = std::fs::read(zarr_dir.join("1/0/0/0/0"))?;
+    assert_eq!(chunk.len(), 3 * 4 * 2);
+    assert_eq!(
+        u16::from_le_bytes([chunk[0], chunk[1]]),
+        builder.frames[1][0]
+    );
+
+    let _ = std::fs::remove_dir_all(&png_dir);
+    let _ = std::fs::remove_dir_all(&zarr_dir);
+    Ok(())
+}
+
+#[test]
+fn test_synthetic_ome_zarr_export() -> Result<()> {
+    let mut builder = Nd2Builder::new(4, 3, 2, 3);
+    builder.experiment = Some(Clx::Level(
+        "SLxExperiment",
+        vec![
+            Clx::U32("eType", 4),
+            Clx::Level(
+                "uLoopPars",
+                vec![Clx::U32("uiCount", 3), Clx::F64("dZStep", 0.5)],
+            ),
+        ],
+    ));
+    let mut nd2 = common::open(&builder);
+
+    let plain = common::temp_path("export_ome.zarr");
+    OmeZarrExporter::new(&plain).export(&mut nd2)?;
+    let group = std::fs::read_to_string(plain.join("zarr.json"))?;
+    assert!(group.contains("\"version\": \"0.5\""));
+    assert!(group.contains("\"scale\": [1.0, 1.0, 0.5, 1.0, 1.0]"));
+    let array = std::fs::read_to_string(plain.join("0/zarr.json"))?;
+    assert!(array.contains("\"shape\": [1, 2, 3, 3, 4]"));
+    let chunk = std::fs::read(plain.join("0/c/0/1/2/0/0"))?;
+    let expected: Vec<u8> = nd2
+        .read_frame_2d(0, 0, 1, 2)?
+        .iter()
+        .flat_map(|p| p.to_le_bytes())
+        .collect();
+    assert_eq!(chunk, expected);
+
+    let sharded = common::temp_path("export_ome_sharded.zarr");
+    OmeZarrExporter::new(&sharded)
+        .sharded(true)
+        .export(&mut nd2)?;
+    let shard = std::fs::read(sharded.join("0/c/0/1/0/0/0"))?;
+    // Three 24-byte planes, a 3 x (offset, size) index and its CRC-32C.
+    assert_eq!(shard.len(), 3 * 24 + 3 * 16 + 4);
+    assert_eq!(&shard[48..72], &expected[..]);
+    let index = &shard[72..];
+    assert_eq!(&index[32..40], &48u64.to_le_bytes());
+    assert_eq!(&index[40..48], &24u64.to_le_bytes());
+
+    let _ = std::fs::remove_dir_all(&plain);
+    let _ = std::fs::remove_dir_all(&sharded);
+    Ok(())
+}
+
+#[test]
+fn test_synthetic_n5_bdv_export() -> Result<()> {
+    let mut builder = Nd2Builder::new(4, 3, 2, 3);
+    builder.experiment = Some(Clx::Level(
+        "SLxExperiment",
+        vec![
+            Clx::U32("eType", 4),
+            Clx::Level(
+                "uLoopPars",
+                vec![Clx::U32("uiCount", 3), Clx::F64("dZStep", 0.5)],
+            ),
+        ],
+    ));
+    let mut nd2 = common::open(&builder);
+
+    let path = common::temp_path("export_bdv.n5");
+    let exporter = N5Exporter::new(&path)
+        .block_size([3, 2, 2])
+        .downsampling(&[[2, 2, 1]]);
+    exporter.export(&mut nd2)?;
+
+    let setup = std::fs::read_to_string(path.join("setup1/attributes.json"))?;
+    assert!(setup.contains("\"downsamplingFactors\": [[1, 1, 1], [2, 2, 1]]"));
+    let s0 = std::fs::read_to_string(path.join("setup1/timepoint0/s0/attributes.json"))?;
+    assert!(s0.contains("\"blockSize\": [3, 2, 2], \"dimensions\": [4, 3, 3]"));
+    let s1 = std::fs::read_to_string(path.join("setup1/timepoint0/s1/attributes.json"))?;
+    assert!(s1.contains("\"dimensions\": [2, 2, 3]"));
+
+    // Edge block (1, 1, 1): x 3..4, y 2..3, z 2..3 of channel 1.
+    let block = std::fs::read(path.join("setup1/timepoint0/s0/1/1/1"))?;
+    let mut expected = vec![0, 0, 0, 3, 0, 0, 0, 1, 0, 0, 0, 1, 0, 0, 0, 1];
+    let plane = nd2.read_frame_2d(0, 0, 1, 2)?;
+    expected.extend_from_slice(&plane[2 * 4 + 3].to_be_bytes());
+    assert_eq!(block, expected);
+
+    let xml = std::fs::read_to_string(exporter.xml_path())?;
+    assert!(xml.contains("export_bdv.n5</n5>"));
+    assert_eq!(xml.matches("<ViewSetup>").count(), 2);
+    assert_eq!(xml.matches("<ViewRegistration ").count(), 2);
+    assert!(xml.contains("<size>1 1 0.5</size>"));
+
+    let _ = std::fs::remove_dir_all(&path);
+    let _ = std::fs::remove_file(exporter.xml_path());
+    Ok(())
+}
+
+#[test]
+fn test_synthetic_multipoint_export() -> Result<()> {
+    let point = |key: &'static str, x: f64, name: &str| {
+        Clx::Level(
+            key,
+            vec![
+                Clx::F64("dPosX", x),
+                Clx::F64("dPosY", -20.0),
+                Clx::F64("dPosZ", 1.5),
+                Clx::F64("dPFSOffset", -1.0),
+                Clx::Str("dPosName", name.to_string()),
+            ],
+        )
+    };
+    let mut builder = Nd2Builder::new(4, 3, 1, 2);
+    builder.experiment = Some(Clx::Level(
+        "SLxExperiment",
+        vec![
+            Clx::U32("eType", 2),
+            Clx::Level(
+                "uLoopPars",
+                vec![
+                    Clx::U32("uiCount", 2),
+                    Clx::Bool("bUseZ", true),
+                    Clx::Level(
+                        "Points",
+                        vec![
+                            point("i0000000000", 100.0, "A1"),
+                            point("i0000000001", 250.5, "B&2"),
+                        ],
+                    ),
+                ],
+            ),
+        ],
+    ));
+    let mut nd2 = common::open(&builder);
+
+    let path = common::temp_path("multipoint.xml");
+    MultipointExporter::new(&path).export(&mut nd2)?;
+    let bytes = std::fs::read(&path)?;
+    assert_eq!(&bytes[..2], &[0xFF, 0xFE]);
+    let units: Vec<u16> = bytes[2..]
+        .chunks_exact(2)
+        .map(|b| u16::from_le_bytes([b[0], b[1]]))
+        .collect();
+    let xml = String::from_utf16(&units).unwrap();
+
+    assert!(xml.contains("<bIncludeZ runtype=\"bool\" value=\"true\"/>"));
+    assert!(xml.contains("<Point00001 runtype=\"NDSetupMultipointListItem\">"));
+    assert!(xml.contains("<strName runtype=\"CLxStringW\" value=\"B&amp;2\"/>"));
+    assert!(xml.contains("<dXPosition runtype=\"double\" value=\"250.5\"/>"));
+    assert!(xml.contains("<dZPosition runtype=\"double\" value=\"1.5\"/>"));
+    assert_eq!(xml.matches("value=\"-1\"/>").count(), 2);
+
+    // Files without an XY loop have nothing to export.
+    let mut plain = common::open(&Nd2Builder::new(4, 3, 1, 2));
+    assert!(MultipointExporter::new(&path).export(&mut plain).is_err());
+
+    let _ = std::fs::remove_file(&path);
+    Ok(())
+}
+
+#[test]
+fn test_synthetic_multipoint_export_matches_parsed_experiment() -> Result<()> {
+    let point = |x: f64| Clx::Level("", vec![Clx::F64("dPosX", x), Clx::F64("dPosY", 2.0 * x)]);
+    let mut builder = Nd2Builder::new(4, 3, 1, 2);
+    builder.experiment = Some(Clx::Level(
+        "SLxExperiment",
+        vec![
+            Clx::U32("eType", 1),
+            Clx::Level("uLoopPars", vec![Clx::U32("uiCount", 1)]),
+            Clx::Level(
+                "ppNextLevelEx",
+                vec![Clx::Level(
+                    "i0000000000",
+                    vec![
+                        Clx::U32("eType", 2),
+                        Clx::Bytes("pItemValid", vec![1, 0, 1]),
+                        Clx::Level(
+                            "uLoopPars",
+                            vec![Clx::Level(
+                                "i0000000000",
+                                vec![
+                                    Clx::U32("uiCount", 3),
+                                    Clx::Bool("bRelativeXY", true),
+                                    Clx::F64("dReferenceX", 1000.0),
+                                    Clx::Level("Points", vec![point(1.0), point(2.0), point(3.0)]),
+                                ],
+                            )],
+                        ),
+                    ],
+                )],
+            ),
+        ],
+    ));
+
+    let export = |nd2: &mut Nd2File| -> Result<Vec<u8>> {
+        let path = common::temp_path("multipoint_parity.xml");
+        MultipointExporter::new(&path).export(nd2)?;
+        let bytes = std::fs::read(&path)?;
+        std::fs::remove_file(&path)?;
+        Ok(bytes)
+    };
+    // Walked from the chunk, then from the parsed (and cached) experiment.
+    let mut nd2 = common::open(&builder);
+    let streamed = export(&mut nd2)?;
+    assert_eq!(nd2.n_positions()?, 2);
+    assert_eq!(streamed, export(&mut nd2)?);
+
+    let units: Vec<u16> = streamed[2..]
+        .chunks_exact(2)
+        .map(|b| u16::from_le_bytes([b[0], b[1]]))
+        .collect();
+    let xml = String::from_utf16(&units).unwrap();
+    assert!(xml.contains("<dXPosition runtype=\"double\" value=\"1001\"/>"));
+    assert!(xml.contains("<dXPosition runtype=\"double\" value=\"1003\"/>"));
+    assert!(!xml.contains("value=\"1002\""));
+    Ok(())
+}
+
+#[test]
+fn test_synthetic_metaimage_export() -> Result<()> {
+    let mut builder = Nd2Builder::new(4, 3, 2, 3);
+    builder.experiment = Some(Clx::Level(
+        "SLxExperiment",
+        vec![
+            Clx::U32("eType", 4),
+            Clx::Level(
+                "uLoopPars",
+                vec![Clx::U32("uiCount", 3), Clx::F64("dZStep", 0.5)],
+            ),
+        ],
+    ));
+    let mut nd2 = common::open(&builder);
+    let mut expected = Vec::new();
+    for z in 0..3 {
+        expected.extend(
+            nd2.read_frame_2d(0, 0, 1, z)?
+                .iter()
+                .flat_map(|p| p.to_le_bytes()),
+        );
+    }
+
+    let mha = common::temp_path("stack.mha");
+    MetaImageExporter::new(&mha).channel(1).export(&mut nd2)?;
+    let bytes = std::fs::read(&mha)?;
+    let header = String::from_utf8_lossy(&bytes[..bytes.len() - expected.len()]).into_owned();
+    assert!(header.contains("DimSize = 4 3 3\n"));
+    assert!(header.contains("ElementSpacing = 1 1 0.5\n"));
+    assert!(header.ends_with("ElementDataFile = LOCAL\n"));
+    assert_eq!(&bytes[bytes.len() - expected.len()..], &expected[..]);
+
+    let mhd = common::temp_path("stack.mhd");
+    MetaImageExporter::new(&mhd).channel(1).export(&mut nd2)?;
+    let header = std::fs::read_to_string(&mhd)?;
+    let raw_name = mhd.with_extension("raw");
+    assert!(header.contains(&format!(
+        "ElementDataFile = {}",
+        raw_name.file_name().unwrap().to_string_lossy()
+    )));
+    assert_eq!(std::fs::read(&raw_name)?, expected);
+
+    assert!(MetaImageExporter::new(&mhd)
+        .timepoint(1)
+        .export(&mut nd2)
+        .is_err());
+
+    let _ = std::fs::remove_file(&mha);
+    let _ = std::fs::remove_file(&mhd);
+    let _ = std::fs::remove_file(&raw_name);
+    Ok(())
+}
+
+#[test]
+fn test_synthetic_nifti_export() -> Result<()> {
+    let mut builder = Nd2Builder::new(4, 3, 2, 3);
+    builder.experiment = Some(Clx::Level(
+        "SLxExperiment",
+        vec![
+            Clx::U32("eType", 4),
+            Clx::Level(
+                "uLoopPars",
+                vec![Clx::U32("uiCount", 3), Clx::F64("dZStep", 0.5)],
+            ),
+        ],
+    ));
+    let mut nd2 = common::open(&builder);
+
+    let path = common::temp_path("stack.nii");
+    NiftiExporter::new(&path).channel(1).export(&mut nd2)?;
+    let bytes = std::fs::read(&path)?;
+    let i16_at = |at: usize| i16::from_le_bytes([bytes[at], bytes[at + 1]]);
+    let f32_at = |at: usize| f32::from_le_bytes(bytes[at..at + 4].try_into().unwrap());
+    assert_eq!(i32::from_le_bytes(bytes[0..4].try_into().unwrap()), 348);
+    assert_eq!(
+        (0..4).map(|i| i16_at(40 + 2 * i)).collect::<Vec<_>>(),
+        [3, 4, 3, 3]
+    );
+    assert_eq!(i16_at(70), 512);
+    assert_eq!((f32_at(80), f32_at(84), f32_at(88)), (1.0, 1.0, 0.5));
+    assert_eq!(f32_at(108), 352.0);
+    assert_eq!(&bytes[344..348], b"n+1\0");
+    assert_eq!(bytes.len(), 352 + 3 * 24);
+    let expected: Vec<u8> = nd2
+        .read_frame_2d(0, 0, 1, 2)?
+        .iter()
+        .flat_map(|p| p.to_le_bytes())
+        .collect();
+    assert_eq!(&bytes[352 + 48..], &expected[..]);
+
+    let _ = std::fs::remove_file(&path);
+    Ok(())
+}
+
+#[test]
+fn test_synthetic_napari_layers() -> Result<()> {
+    let mut builder = Nd2Builder::new(4, 3, 2, 3);
+    builder.experiment = Some(Clx::Level(
+        "SLxExperiment",
+        vec![
+            Clx::U32("eType", 4),
+            Clx::Level(
+                "uLoopPars",
+                vec![Clx::U32("uiCount", 3), Clx::F64("dZStep", 0.5)],
+            ),
+        ],
+    ));
+    let mut nd2 = common::open(&builder);
+    let layers = nd2.napari_layers(0)?;
+
+    assert_eq!(layers.len(), 2);
+    assert_eq!(layers[0].scale, [1.0, 0.5, 1.0, 1.0]);
+    assert_eq!(layers[0].translate, [0.0; 4]);
+    assert_eq!(layers[0].contrast_limits, [0.0, 4095.0]);
+    assert_eq!(layers[0].blending, "additive");
+    assert_eq!(
+        layers[1].colormap,
+        nd2_rs::NapariColormap::Named("green".into())
+    );
+    assert!(nd2.napari_layers(1).is_err());
+    Ok(())
+}
+
+#[cfg(feature = "ffmpeg")]
+#[test]
+fn test_synthetic_video_export() -> Result<()> {
+    use nd2_rs::VideoExporter;
+
+    let fixed = ToneMapping {
+        range: ToneRange::Fixed { min: 100, max: 300 },
+        gamma: 1.0,
+    };
+    assert_eq!(
+        fixed.apply(&[0, 100, 200, 300, 5000], (100, 300)),
+        [0, 0, 128, 255, 255]
+    );
+    let mut histogram = vec![0u64; 1 << 16];
+    histogram[10] = 1;
+    histogram[20..30].iter_mut().for_each(|n| *n = 100);
+    histogram[60000] = 1;
+    let auto = ToneMapping {
+        range: ToneRange::Auto { saturated: 0.01 },
+        gamma: 1.0,
+    };
+    assert_eq!(auto.limits(&histogram), (20, 29));
+
+    let builder = Nd2Builder::new(4, 3, 1, 3);
+    let mut nd2 = common::open(&builder);
+    let path = common::temp_path("video.mp4");
+    assert!(VideoExporter::new(&path)
+        .ffmpeg("nd2-rs-missing-ffmpeg")
+        .export(&mut nd2)
+        .is_err());
+    assert!(VideoExporter::new(&path)
+        .timepoints(2..4)
+        .export(&mut nd2)
+        .is_err());
+
+    let has_ffmpeg = std::process::Command::new("ffmpeg")
+        .arg("-version")
+        .output()
+        .is_ok_and(|out| out.status.success());
+    if has_ffmpeg {
+        VideoExporter::new(&path).fps(5.0).export(&mut nd2)?;
+        assert!(std::fs::metadata(&path)?.len() > 0);
+        let _ = std::fs::remove_file(&path);
+    }
+    Ok(())
+}
+
+#[cfg(feature = "mmap")]
+#[test]
+fn test_synthetic_mmap_footer() -> Result<()> {
+    for lossless in [false, true] {
+        let mut builder = Nd2Builder::new(4, 3, 2, 3);
+        builder.lossless = lossless;
+        // Attributes after the frames, inside the mapped region.
+        let mut chunks = builder.chunks();
+        let attributes = chunks.remove(0);
+        chunks.push(attributes);
+        let path = common::temp_path(&format!("mmap_footer_{}.nd2", lossless));
+        std::fs::write(&path, common::build_file(builder.version, &chunks))?;
+
+        let mut expected = common::open(&builder);
+        // SAFETY: nothing else writes the temporary file while it is mapped.
+        let mapped = unsafe {
+            [
+                Nd2File::open_mmap(&path)?,
+                Nd2File::open_mmap_footer(&path)?,
+            ]
+        };
+        for mut nd2 in mapped {
+            assert_eq!(nd2.summary()?, expected.summary()?);
+            assert_eq!(
+                nd2.read_frames(&[2, 0, 1])?,
+                expected.read_frames(&[2, 0, 1])?
+            );
+            assert_eq!(nd2.read_frame(1)?, expected.read_frame(1)?);
+        }
+        std::fs::remove_file(&path)?;
+    }
+    Ok(())
+}
+
+#[cfg(feature = "mmap")]
+#[test]
+fn test_synthetic_mapped_slices() -> Result<()> {
+    let builder = Nd2Builder::new(4, 3, 2, 3);
+    let path = common::temp_path("mapped_slices.nd2");
+    std::fs::write(&path, builder.build())?;
+
+    let mut expected = common::open(&builder);
+    // SAFETY: nothing else writes the temporary files while they are mapped.
+    let mut nd2 = unsafe { Nd2File::open_mmap(&path)? };
+    let names = nd2.chunk_names();
+    for name in &names {
+        assert_eq!(nd2.mapped_chunk(name)?, &expected.read_chunk(name)?[..]);
+    }
+    let stored: Vec<u16> = nd2
+        .mapped_frame(1)?
+        .chunks_exact(2)
+        .map(|b| u16::from_le_bytes([b[0], b[1]]))
+        .collect();
+    assert_eq!(
+        stored,
+        expected.read_frame_ordered::<u16>(1, FrameOrder::Yxc)?
+    );
+    assert!(nd2.mapped_frame(3).unwrap_err().is_input());
+
+    // Only whole-file maps lend slices, and only of uncompressed frames.
+    let footer = unsafe { Nd2File::open_mmap_footer(&path)? };
+    assert!(footer.mapped_chunk(&names[0]).unwrap_err().is_input());
+    let mut lossless = builder.clone();
+    lossless.lossless = true;
+    let lossless_path = common::temp_path("mapped_slices_lossless.nd2");
+    std::fs::write(&lossless_path, lossless.build())?;
+    let mut compressed = unsafe { Nd2File::open_mmap(&lossless_path)? };
+    assert!(compressed.mapped_frame(0).unwrap_err().is_unsupported());
+    drop((nd2, footer, compressed));
+    std::fs::remove_file(&path)?;
+    std::fs::remove_file(&lossless_path)?;
+    Ok(())
+}
+
+#[test]
+fn test_synthetic_chunkmap_recovery() -> Result<()> {
+    for lossless in [false, true] {
+        let mut builder = Nd2Builder::new(4, 3, 2, 4);
+        builder.lossless = lossless;
+        let mut chunks = builder.chunks();
+        // A chunk header inside another chunk's data is not a chunk.
+        let mut decoy = Vec::new();
+        common::write_chunk(&mut decoy, b"ImageDataSeq|0!", &[0; 8]);
+        chunks.push((b"CustomData|Decoy!".to_vec(), decoy));
+        // Start frame 2's header 2 bytes before the first 8 MiB scan block
+        // ends, after the 112-byte file signature chunk.
+        let frame2 = chunks
+            .iter()
+            .position(|(name, _)| name == b"ImageDataSeq|2!")
+            .unwrap();
+        let before: usize = 112
+            + chunks[..frame2]
+                .iter()
+                .map(|(name, data)| 16 + name.len() + data.len())
+                .sum::<usize>();
+        let padding = b"CustomData|Padding!".to_vec();
+        let padding_len = (8 << 20) - 2 - before - 16 - padding.len();
+        chunks.insert(frame2, (padding, vec![0; padding_len]));
+        let mut file = common::build_file(builder.version, &chunks);
+        // Cut off the chunkmap trailer.
+        file.truncate(file.len() - 40);
+        let path = common::temp_path(&format!("recovery_{}.nd2", lossless));
+        std::fs::write(&path, &file)?;
+        assert!(Nd2File::open(&path).is_err());
+
+        let mut expected = common::open(&builder);
+        let indices = [0, 1, 2, 3];
+        for threads in [1, 4] {
+            let options = Nd2Options::new()
+                .allow_recovery(true)
+                .decode_threads(threads);
+            for mut nd2 in [
+                Nd2File::open_with(&path, options.clone())?,
+                Nd2File::open_reader_with(Cursor::new(file.clone()), options)?,
+            ] {
+                let kinds: Vec<_> = nd2.diagnostics().iter().map(|d| d.kind).collect();
+                assert_eq!(kinds, [DiagnosticKind::ChunkmapRecovered]);
+                assert_eq!(nd2.summary()?, expected.summary()?);
+                assert_eq!(nd2.read_frames(&indices)?, expected.read_frames(&indices)?);
+            }
+        }
+        std::fs::remove_file(&path)?;
+    }
+
+    // Nothing to recover from a file without chunks.
+    let options = Nd2Options::new().allow_recovery(true);
+    let file = common::build_file("Ver3.0", &[]);
+    assert!(
+        Nd2File::open_reader_with(Cursor::new(file[..file.len() - 40].to_vec()), options).is_err()
+    );
+    Ok(())
+}
+
+#[test]
+fn test_synthetic_duplicate_and_appended_chunkmaps() -> Result<()> {
+    let builder = Nd2Builder::new(4, 3, 2, 4);
+    let mut expected = common::open(&builder);
+    let chunks = builder.chunks();
+    let frame = |index: usize| {
+        let name = format!("ImageDataSeq|{}!", index).into_bytes();
+        chunks.iter().find(|(n, _)| *n == name).unwrap().1.clone()
+    };
+
+    // Frame 1 written twice: the later entry, holding frame 0's data, wins.
+    let mut duplicated = chunks.clone();
+    duplicated.push((b"ImageDataSeq|1!".to_vec(), frame(0)));
+    let file = common::build_file(builder.version, &duplicated);
+    let mut nd2 = Nd2File::open_reader(Cursor::new(file))?;
+    let kinds: Vec<_> = nd2.diagnostics().iter().map(|d| d.kind).collect();
+    assert_eq!(kinds, [DiagnosticKind::DuplicateChunk]);
+    assert_eq!(nd2.read_frame(1)?, expected.read_frame(0)?);
+    assert_eq!(nd2.read_frame(2)?, expected.read_frame(2)?);
+
+    // Frame 3 appended with a second chunkmap linking to the first.
+    let first: Vec<_> = chunks
+        .iter()
+        .filter(|(name, _)| name != b"ImageDataSeq|3!")
+        .cloned()
+        .collect();
+    let mut file = common::build_file(builder.version, &first);
+    let first_map = u64::from_le_bytes(file[file.len() - 8..].try_into().unwrap());
+    let frame3 = file.len() as u64;
+    let data = frame(3);
+    common::write_chunk(&mut file, b"ImageDataSeq|3!", &data);
+    let entries = [
+        (b"ImageDataSeq|3!".to_vec(), frame3, data.len() as u64),
+        (b"ND2 FILEMAP SIGNATURE NAME 0001!".to_vec(), first_map, 0),
+    ];
+    common::append_chunkmap(&mut file, &entries);
+    let mut nd2 = Nd2File::open_reader(Cursor::new(file.clone()))?;
+    assert!(nd2.diagnostics().is_empty());
+    let indices = [0, 1, 2, 3];
+    assert_eq!(nd2.read_frames(&indices)?, expected.read_frames(&indices)?);
+
+    // A link to something that is not a chunkmap is reported and skipped.
+    let mut broken = file;
+    let link = broken
+        .windows(8)
+        .rposition(|w| w == first_map.to_le_bytes())
+        .unwrap();
+    broken[link..link + 8].copy_from_slice(&frame3.to_le_bytes());
+    let nd2 = Nd2File::open_reader(Cursor::new(broken))?;
+    let kinds: Vec<_> = nd2.diagnostics().iter().map(|d| d.kind).collect();
+    assert_eq!(kinds, [DiagnosticKind::ChunkmapSectionIgnored]);
+    Ok(())
+}
+
+#[test]
+fn test_synthetic_implausible_sizes() -> Result<()> {
+    let attributes = |height: u32, sequence_count: u32| {
+        Clx::Level(
+            "SLxImageAttributes",
+            vec![
+                Clx::U32("uiWidth", 4),
+                Clx::U32("uiWidthBytes", 16),
+                Clx::U32("uiHeight", height),
+                Clx::U32("uiComp", 2),
+                Clx::U32("uiBpcInMemory", 16),
+                Clx::U32("uiBpcSignificant", 12),
+                Clx::U32("uiSequenceCount", sequence_count),
+                Clx::U32("uiChannelCount", 2),
+                Clx::Str("eCompression", "lossless".to_string()),
+            ],
+        )
+        .encode()
+    };
+    let mut builder = Nd2Builder::new(4, 3, 2, 2);
+    builder.lossless = true;
+    let open = |attributes: Vec<u8>| {
+        let mut chunks = builder.chunks();
+        chunks[0].1 = attributes;
+        let file = common::build_file(builder.version, &chunks);
+        Nd2File::open_reader(Cursor::new(file))
+    };
+
+    // A compressed frame claiming 16 GiB is an error, not an allocation.
+    let mut nd2 = open(attributes(1 << 29, 2))?;
+    assert!(nd2.read_frame(0).is_err());
+
+    // So is a frame count no file of this size can hold.
+    let mut nd2 = open(attributes(3, u32::MAX))?;
+    assert!(nd2.read_frame(0).is_err());
+    let report = nd2.validate(ValidationLevel::Chunks)?;
+    assert!(!report.metadata_errors.is_empty());
+    assert!(report.corrupt_frames.is_empty());
+    Ok(())
+}
+
+#[test]
+fn test_synthetic_chunk_offsets_past_eof() -> Result<()> {
+    let mut builder = Nd2Builder::new(4, 3, 2, 4);
+    for lossless in [false, true] {
+        builder.lossless = lossless;
+        let expected = common::open(&builder).read_frame(2)?;
+        // A newer chunkmap section moving frame 1 past the end of the file.
+        let mut file = builder.build();
+        let first_map = u64::from_le_bytes(file[file.len() - 8..].try_into().unwrap());
+        let past_eof = file.len() as u64 + 4096;
+        let entries = [
+            (b"ImageDataSeq|1!".to_vec(), past_eof, 64),
+            (b"ND2 FILEMAP SIGNATURE NAME 0001!".to_vec(), first_map, 0),
+        ];
+        common::append_chunkmap(&mut file, &entries);
+        let mut nd2 = Nd2File::open_reader(Cursor::new(file))?;
+        let err = nd2.read_frame(1).unwrap_err();
+        assert!(err.is_file());
+        let message = format!("{:?}", err);
+        assert!(
+            message.contains(&format!("points past EOF at offset {}", past_eof)),
+            "{}",
+            message
+        );
+        assert_eq!(nd2.read_frame(2)?, expected);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_synthetic_chunk_names_sorted() -> Result<()> {
+    let builder = Nd2Builder::new(4, 3, 1, 12);
+    let names = common::open(&builder).chunk_names();
+    assert_eq!(names, common::open(&builder).chunk_names());
+    let frames: Vec<&str> = names
+        .iter()
+        .map(String::as_str)
+        .filter(|name| name.starts_with("ImageDataSeq|"))
+        .collect();
+    let expected: Vec<String> = (0..12).map(|i| format!("ImageDataSeq|{}!", i)).collect();
+    assert_eq!(frames, expected);
+    let mut sorted = names.clone();
+    sorted.sort();
+    sorted.dedup();
+    assert_eq!(sorted.len(), names.len());
+    Ok(())
+}
+
+#[test]
+fn test_synthetic_unusual_chunk_names() -> Result<()> {
+    let builder = Nd2Builder::new(4, 3, 1, 2);
+    let mut chunks = builder.chunks();
+    let latin1 = b"Custom|Caf\xe9 Data!".to_vec();
+    let other = b"Custom|Caf\xe8 Data!".to_vec();
+    chunks.push((latin1.clone(), b"first".to_vec()));
+    chunks.push((b"Stage|X|Y!".to_vec(), b"second".to_vec()));
+    let file = common::build_file(builder.version, &chunks);
+    let options = Nd2Options::new().allow_recovery(true);
+    let recovered = file[..file.len() - 40].to_vec();
+    for mut nd2 in [
+        Nd2File::open_reader(Cursor::new(file.clone()))?,
+        Nd2File::open_reader_with(Cursor::new(recovered), options)?,
+    ] {
+        let names = nd2.chunk_names();
+        assert!(names.iter().any(|name| name == "Custom|Caf\u{fffd} Data!"));
+        assert!(names.iter().any(|name| name == "Stage|X|Y!"));
+        assert!(nd2.chunk_names_raw().contains(&latin1));
+        assert_eq!(nd2.read_chunk(&latin1)?, b"first");
+        assert_eq!(nd2.read_chunk("Custom|Caf\u{fffd} Data!")?, b"first");
+        assert_eq!(nd2.read_chunk("Stage|X|Y!")?, b"second");
+        assert!(nd2.read_chunk("Missing!").unwrap_err().is_file());
+    }
+
+    // Two names shown alike are only reachable as stored.
+    chunks.push((other.clone(), b"third".to_vec()));
+    let mut nd2 = Nd2File::open_reader(Cursor::new(common::build_file(builder.version, &chunks)))?;
+    assert!(nd2.read_chunk("Custom|Caf\u{fffd} Data!").is_err());
+    assert_eq!(nd2.read_chunk(&latin1)?, b"first");
+    assert_eq!(nd2.read_chunk(&other)?, b"third");
+    Ok(())
+}
+
+#[test]
+fn test_synthetic_manifest_verification() -> Result<()> {
+    let builder = Nd2Builder::new(4, 3, 1, 3);
+    let file = builder.build();
+    let mut nd2 = Nd2File::open_reader(Cursor::new(file.clone()))?;
+    let manifest = nd2.manifest()?;
+    assert_eq!(manifest.file_size, file.len() as u64);
+    assert_eq!(manifest.frames.len(), 3);
+    assert!(manifest.frames.values().all(Option::is_some));
+    assert!(nd2.verify_against(&manifest)?.is_ok());
+
+    let path = common::temp_path("manifest.nd2");
+    let sidecar = Manifest::sidecar_path(&path);
+    assert!(sidecar.to_str().unwrap().ends_with("manifest.nd2.manifest"));
+    manifest.save(&sidecar)?;
+    assert_eq!(Manifest::load(&sidecar)?, manifest);
+    std::fs::remove_file(&sidecar)?;
+    assert!("nd2-rs manifest 1\nfile_size x\n"
+        .parse::<Manifest>()
+        .unwrap_err()
+        .is_input());
+    assert!("not a manifest".parse::<Manifest>().is_err());
+
+    // One flipped bit in the pixels of frame 1.
+    let name = b"ImageDataSeq|1!";
+    let frame1 = file
+        .windows(name.len())
+        .position(|w| w == name)
+        .expect("frame 1 chunk");
+    let mut rotted = file.clone();
+    rotted[frame1 + 40] ^= 0x10;
+    let mut nd2 = Nd2File::open_reader(Cursor::new(rotted))?;
+    let report = nd2.verify_against(&manifest)?;
+    assert!(!report.is_ok());
+    assert!(report.chunkmap_matches);
+    assert_eq!(report.changed_frames, [1]);
+    assert!(report.missing_frames.is_empty() && report.extra_frames.is_empty());
+
+    let mut other = manifest.clone();
+    other.frames.remove(&2);
+    other.frames.insert(7, Some(0));
+    let report = nd2.verify_against(&other)?;
+    assert_eq!(report.changed_frames, [1]);
+    assert_eq!(report.missing_frames, [7]);
+    assert_eq!(report.extra_frames, [2]);
+    Ok(())
+}
+
+#[test]
+fn test_synthetic_legacy_container_with_chunks() -> Result<()> {
+    let builder = Nd2Builder::new(4, 3, 2, 3);
+    let mut expected = common::open(&builder);
+    // JP2 signature box in place of the 112-byte file signature chunk.
+    let jp2 = [
+        0, 0, 0, 0x0C, b'j', b'P', b' ', b' ', 0x0D, 0x0A, 0x87, 0x0A,
+    ];
+    let mut file = builder.build();
+    file[..112].fill(0);
+    file[..jp2.len()].copy_from_slice(&jp2);
+
+    let mut nd2 = Nd2File::open_reader(Cursor::new(file))?;
+    assert_eq!(nd2.version(), (3, 0));
+    assert!(!nd2.is_legacy());
+    assert!(nd2
+        .diagnostics()
+        .iter()
+        .any(|d| d.kind == DiagnosticKind::LegacyContainer));
+    assert_eq!(nd2.summary()?, expected.summary()?);
+    assert_eq!(
+        nd2.read_frames(&[0, 1, 2])?,
+        expected.read_frames(&[0, 1, 2])?
+    );
+
+    // Without a chunk layer or codestreams, there is nothing to read.
+    let mut legacy = jp2.to_vec();
+    legacy.resize(4096, 0);
+    let err = Nd2File::open_reader(Cursor::new(legacy)).unwrap_err();
+    assert!(err.is_unsupported());
+    Ok(())
+}
+
+/// A JP2 box of type `kind` holding `payload`.
+fn jp2_box(kind: &[u8; 4], payload: &[u8]) -> Vec<u8> {
+    let mut b = ((payload.len() + 8) as u32).to_be_bytes().to_vec();
+    b.extend_from_slice(kind);
+    b.extend_from_slice(payload);
+    b
+}
+
+/// Start of a codestream of `width`x`height` pixels with one 12-bit
+/// component, followed by `tag`.
+fn codestream(width: u32, height: u32, tag: u8) -> Vec<u8> {
+    let mut c = vec![0xFF, 0x4F, 0xFF, 0x51, 0, 41, 0, 0];
+    for v in [width, height, 0, 0, width, height, 0, 0] {
+        c.extend_from_slice(&v.to_be_bytes());
+    }
+    c.extend_from_slice(&[0, 1, 11, 1, 1, 0xFF, 0xD9, tag]);
+    c
+}
+
+#[test]
+fn test_synthetic_legacy_file() -> Result<()> {
+    let mut file = jp2_box(b"jP  ", &[0x0D, 0x0A, 0x87, 0x0A]);
+    file.extend(jp2_box(b"ftyp", b"jp2 \0\0\0\0jp2 "));
+    file.extend(jp2_box(
+        b"xml ",
+        br#"<?xml version="1.0"?><variant version="1.0"><no_name runtype="CLxListVariant">
+            <dCalibration runtype="double" value="0.25"/>
+            <bCalibrated runtype="bool" value="true"/>
+            <sPicturePlanes runtype="CLxListVariant"><sPlaneNew runtype="CLxListVariant">
+            <a0 runtype="CLxListVariant"><sDescription runtype="CLxStringW">DAPI</sDescription></a0>
+            </sPlaneNew></sPicturePlanes>
+            </no_name></variant>"#,
+    ));
+    file.extend(jp2_box(
+        b"xml ",
+        br#"<variant><no_name runtype="CLxListVariant">
+            <TextInfoItem_5 runtype="CLxStringW" value="Old &amp; trusted"/>
+            </no_name></variant>"#,
+    ));
+    let codestreams: Vec<Vec<u8>> = (0..3).map(|i| codestream(5, 4, i)).collect();
+    for c in &codestreams {
+        file.extend(jp2_box(b"jp2c", c));
+    }
+
+    let mut nd2 = Nd2File::open_reader(Cursor::new(file))?;
+    assert_eq!(nd2.version(), (1, 0));
+    assert!(nd2.is_legacy());
+    assert!(nd2
+        .diagnostics()
+        .iter()
+        .any(|d| d.kind == DiagnosticKind::LegacyContainer));
+    let summary = nd2.summary()?;
+    assert_eq!(summary.sizes["T"], 3);
+    assert_eq!(summary.sizes["X"], 5);
+    assert_eq!(summary.sizes["Y"], 4);
+    assert_eq!(summary.pixel_type.as_deref(), Some("Unsigned16"));
+    assert!(nd2.is_compressed()?);
+    let channel = &nd2.metadata()?.channels.as_ref().unwrap()[0];
+    assert_eq!(channel.channel.name, "DAPI");
+    assert_eq!(channel.volume.axes_calibration.0, 0.25);
+    assert_eq!(
+        nd2.text_info()?.description.as_deref(),
+        Some("Old & trusted")
+    );
+
+    // Frame chunks hold an unknown timestamp, then the codestream.
+    let mut chunk = 0f64.to_le_bytes().to_vec();
+    chunk.extend_from_slice(&codestreams[1]);
+    assert_eq!(nd2.read_chunk("ImageDataSeq|1!")?, chunk);
+    #[cfg(not(feature = "jpeg2000"))]
+    assert!(nd2.read_frame(0).unwrap_err().is_unsupported());
+    Ok(())
+}
+
+#[test]
+fn test_synthetic_placeholder_chunks() -> Result<()> {
+    let builder = Nd2Builder::new(4, 3, 2, 4);
+    let expected = common::open(&builder).read_frames(&[0, 1, 2])?;
+
+    // Listed in the chunkmap with no data.
+    let mut chunks = builder.chunks();
+    let chunk = chunks
+        .iter_mut()
+        .find(|(name, _)| name == b"ImageDataSeq|3!")
+        .unwrap();
+    chunk.1.clear();
+    let file = common::build_file(builder.version, &chunks);
+    let mut nd2 = Nd2File::open_reader(Cursor::new(file))?;
+    assert_eq!(nd2.frame_counts()?.stored, 3);
+    assert_eq!(nd2.missing_frames()?, [3]);
+    assert_eq!(nd2.read_frames(&[0, 1, 2])?, expected);
+    assert!(nd2.read_frame(3).unwrap_err().is_input());
+    let kinds: Vec<_> = nd2.diagnostics().iter().map(|d| d.kind).collect();
+    assert!(kinds.contains(&DiagnosticKind::PlaceholderChunk));
+
+    // Across chunkmap sections the entry listed last wins, placeholder or
+    // not, and a frame listed twice is reported either way.
+    let real = builder.build();
+    let real_map = u64::from_le_bytes(real[real.len() - 8..].try_into().unwrap());
+    let placeholder = common::build_file(builder.version, &chunks);
+    let placeholder_map =
+        u64::from_le_bytes(placeholder[placeholder.len() - 8..].try_into().unwrap());
+    let frame3 = builder.chunks().pop().unwrap().1;
+    for (mut file, older_map, newer_size, missing) in [
+        (real, real_map, 0, vec![3]),
+        (placeholder, placeholder_map, frame3.len(), vec![]),
+    ] {
+        let offset = file.len() as u64;
+        common::write_chunk(&mut file, b"ImageDataSeq|3!", &frame3[..newer_size]);
+        let entries = [
+            (b"ImageDataSeq|3!".to_vec(), offset, newer_size as u64),
+            (b"ND2 FILEMAP SIGNATURE NAME 0001!".to_vec(), older_map, 0),
+        ];
+        common::append_chunkmap(&mut file, &entries);
+        let mut nd2 = Nd2File::open_reader(Cursor::new(file))?;
+        let kinds: Vec<_> = nd2.diagnostics().iter().map(|d| d.kind).collect();
+        assert!(kinds.contains(&DiagnosticKind::DuplicateChunk));
+        assert_eq!(
+            kinds.contains(&DiagnosticKind::PlaceholderChunk),
+            !missing.is_empty()
+        );
+        assert_eq!(nd2.missing_frames()?, missing);
+        assert_eq!(nd2.read_frames(&[0, 1, 2])?, expected);
+        if missing.is_empty() {
+            assert_eq!(nd2.read_frame(3)?, common::open(&builder).read_frame(3)?);
+        } else {
+            assert!(nd2.read_frame(3).is_err());
+        }
+    }
+
+    // An empty chunk header behind a chunkmap entry that claims data.
+    let mut file = builder.build();
+    let name_at = file
+        .windows(15)
+        .position(|w| w == b"ImageDataSeq|3!")
+        .unwrap();
+    file[name_at - 8..name_at].fill(0);
+    let mut nd2 = Nd2File::open_reader(Cursor::new(file))?;
+    assert!(nd2.read_frame(3).unwrap_err().is_file());
+    assert_eq!(nd2.read_frames(&[0, 1, 2])?, expected);
+    Ok(())
+}
+
+#[test]
+fn test_synthetic_refresh_growing_file() -> Result<()> {
+    let builder = Nd2Builder::new(4, 3, 2, 4);
+    let mut expected = common::open(&builder);
+    let chunks = builder.chunks();
+    let path = common::temp_path("growing.nd2");
+
+    // Two of four frames written so far.
+    let partial: Vec<_> = chunks
+        .iter()
+        .filter(|(name, _)| name != b"ImageDataSeq|2!" && name != b"ImageDataSeq|3!")
+        .cloned()
+        .collect();
+    std::fs::write(&path, common::build_file(builder.version, &partial))?;
+    let mut nd2 = Nd2File::open(&path)?;
+    assert_eq!(nd2.frame_counts()?.stored, 2);
+    assert_eq!(nd2.n_timepoints()?, 1);
+    assert!(nd2.read_frame(3).is_err());
+    assert!(!nd2.refresh()?);
+
+    std::fs::write(&path, builder.build())?;
+    assert!(nd2.refresh()?);
+    assert!(nd2.frame_counts()?.is_consistent());
+    assert_eq!(nd2.n_timepoints()?, 2);
+    assert!(nd2.missing_frames()?.is_empty());
+    assert_eq!(nd2.read_frame(3)?, expected.read_frame(3)?);
+    assert!(!nd2.refresh()?);
+
+    // A chunkmap being rewritten is an error; the last good state stays.
+    let mut file = builder.build();
+    file.truncate(file.len() - 40);
+    std::fs::write(&path, &file)?;
+    assert!(nd2.refresh().is_err());
+    assert_eq!(nd2.read_frame(2)?, expected.read_frame(2)?);
+    std::fs::remove_file(&path)?;
+    Ok(())
+}
+
+#[test]
+fn test_synthetic_unknown_version() -> Result<()> {
+    let mut builder = Nd2Builder::new(4, 3, 2, 2);
+    let mut expected = common::open(&builder);
+    builder.version = "Ver4.1";
+    let file = builder.build();
+    assert!(matches!(
+        Nd2File::open_reader(Cursor::new(file.clone())),
+        Err(nd2_rs::Nd2Error::Unsupported { .. })
+    ));
+
+    let options = Nd2Options::new().allow_unknown_version(true);
+    let mut nd2 = Nd2File::open_reader_with(Cursor::new(file), options)?;
+    assert_eq!(nd2.version(), (4, 1));
+    let kinds: Vec<_> = nd2.diagnostics().iter().map(|d| d.kind).collect();
+    assert_eq!(kinds, [DiagnosticKind::UnknownVersion]);
+    assert_eq!(nd2.summary()?.sizes, expected.summary()?.sizes);
+    assert_eq!(nd2.read_frames(&[0, 1])?, expected.read_frames(&[0, 1])?);
+    Ok(())
+}
+
+#[test]
+fn test_synthetic_truncated_file() -> Result<()> {
+    let builder = Nd2Builder::new(4, 3, 2, 4);
+    let mut expected = common::open(&builder);
+    let file = builder.build();
+
+    // Chunkmap intact, frame 3's entry past the end of the file.
+    let mut cut_map = file.clone();
+    let name = b"ImageDataSeq|3!";
+    let entry = cut_map
+        .windows(name.len())
+        .rposition(|w| w == name)
+        .expect("frame 3 chunkmap entry")
+        + name.len();
+    cut_map[entry..entry + 8].copy_from_slice(&(file.len() as u64 * 2).to_le_bytes());
+    let mut nd2 = Nd2File::open_reader(Cursor::new(cut_map))?;
+    assert_eq!(nd2.n_frames()?, 4);
+    assert_eq!(nd2.missing_frames()?, [3]);
+    let present: Vec<_> = nd2.frames()?.iter().map(|f| f.index()).collect();
+    assert_eq!(present, [0, 1, 2]);
+    assert_eq!(nd2.read_frame(2)?, expected.read_frame(2)?);
+    assert!(nd2.read_frame(3).is_err());
+
+    // Acquisition stopped inside frame 2: no chunkmap at all.
+    let frame2 = file
+        .windows(name.len())
+        .position(|w| w == b"ImageDataSeq|2!")
+        .expect("frame 2 chunk");
+    let truncated = file[..frame2 + 20].to_vec();
+    assert!(Nd2File::open_reader(Cursor::new(truncated.clone())).is_err());
+    let options = Nd2Options::new().allow_recovery(true);
+    let mut nd2 = Nd2File::open_reader_with(Cursor::new(truncated), options)?;
+    // Only the first of two time points was reached.
+    assert_eq!(
+        nd2.frame_counts()?,
+        FrameCounts {
+            declared: 4,
+            stored: 2,
+            planned: 4
+        }
+    );
+    assert_eq!(nd2.n_timepoints()?, 1);
+    assert_eq!(nd2.summary()?.channels, expected.summary()?.channels);
+    assert!(nd2.missing_frames()?.is_empty());
+    assert_eq!(nd2.frames()?.len(), 2);
+    let kinds: Vec<_> = nd2.diagnostics().iter().map(|d| d.kind).collect();
+    assert!(kinds.contains(&DiagnosticKind::FrameCountMismatch));
+    assert!(!nd2.validate(ValidationLevel::Metadata)?.is_ok());
+    assert_eq!(nd2.read_frames(&[0, 1])?, expected.read_frames(&[0, 1])?);
+    assert!(nd2.read_frame(2).is_err());
+    assert_eq!(nd2.n_frames()?, 2);
+    assert!(format!("{nd2:?}").contains("dims: P=1 T=1 C=2 Z=1"));
+    assert_eq!(nd2.frame_times()?, expected.frame_times()?[..2]);
+    assert_eq!(nd2.recorded_data()?["Time [s]"].len(), 2);
+    Ok(())
+}
+
+#[test]
+fn test_synthetic_validate() -> Result<()> {
+    let levels = [
+        ValidationLevel::Metadata,
+        ValidationLevel::Chunks,
+        ValidationLevel::Pixels,
+    ];
+    let mut builder = Nd2Builder::new(4, 3, 2, 4);
+    for lossless in [false, true] {
+        builder.lossless = lossless;
+        let mut nd2 = common::open(&builder);
+        for level in levels {
+            let report = nd2.validate(level)?;
+            assert!(report.is_ok(), "{:?}", report);
+            assert_eq!(report.level, level);
+        }
+    }
+
+    // A raw frame chunk cut short, and a lossless one that does not inflate.
+    let frame_chunk = |index: usize, lossless: bool| {
+        let mut builder = Nd2Builder::new(4, 3, 2, 4);
+        builder.lossless = lossless;
+        let mut chunks = builder.chunks();
+        let name = format!("ImageDataSeq|{}!", index).into_bytes();
+        let chunk = chunks.iter_mut().find(|(n, _)| *n == name).unwrap();
+        if lossless {
+            chunk.1.truncate(8);
+            chunk.1.extend_from_slice(b"not zlib");
+        } else {
+            chunk.1.truncate(20);
+        }
+        let file = common::build_file(builder.version, &chunks);
+        Nd2File::open_reader(Cursor::new(file)).unwrap()
+    };
+    let report = frame_chunk(1, false).validate(ValidationLevel::Chunks)?;
+    assert_eq!(report.undersized_frames, [1]);
+    assert!(report.corrupt_frames.is_empty() && !report.is_ok());
+    assert!(frame_chunk(1, false)
+        .validate(ValidationLevel::Metadata)?
+        .is_ok());
+    let mut nd2 = frame_chunk(2, true);
+    assert!(nd2.validate(ValidationLevel::Chunks)?.is_ok());
+    let report = nd2.validate(ValidationLevel::Pixels)?;
+    let corrupt: Vec<_> = report.corrupt_frames.iter().map(|(i, _)| *i).collect();
+    assert_eq!(corrupt, [2]);
+
+    // Truncated and recovered: missing frames, chunkmap not intact.
+    let file = builder.build();
+    let options = Nd2Options::new().allow_recovery(true);
+    let mut nd2 =
+        Nd2File::open_reader_with(Cursor::new(file[..file.len() - 40].to_vec()), options)?;
+    let report = nd2.validate(ValidationLevel::Chunks)?;
+    assert!(!report.chunkmap_ok);
+    assert!(report.missing_frames.is_empty() && report.undersized_frames.is_empty());
+    Ok(())
+}
+
+#[test]
+fn test_synthetic_concurrent_frame_reads() -> Result<()> {
+    for lossless in [false, true] {
+        let mut builder = Nd2Builder::new(4, 3, 2, 6);
+        builder.lossless = lossless;
+        let path = common::temp_path(&format!("concurrent_{}.nd2", lossless));
+        std::fs::write(&path, builder.build())?;
+
+        let mut nd2 = Nd2File::open(&path)?;
+        let expected = nd2.read_frames(&[0, 1, 2, 3, 4, 5])?;
+        let reader = nd2.frame_reader()?;
+        assert_eq!(reader.n_frames(), 6);
+        std::thread::scope(|scope| {
+            for offset in 0..4 {
+                let (reader, expected) = (&reader, &expected);
+                scope.spawn(move || {
+                    for i in 0..12 {
+                        let index = (i + offset) % 6;
+                        assert_eq!(reader.read_frame(index).unwrap(), expected[index]);
+                    }
+                });
+            }
+        });
+        assert_eq!(
+            reader.read_frame_as::<f32>(2)?,
+            nd2.read_frame_as::<f32>(2)?
+        );
+        assert!(reader.read_frame_as::<u8>(2).unwrap_err().is_input());
+        assert!(reader.read_frame(6).unwrap_err().is_input());
+        std::fs::remove_file(&path)?;
+    }
+
+    // In-memory sources have a single cursor.
+    let mut nd2 = common::open(&Nd2Builder::new(4, 3, 1, 2));
+    assert!(nd2.frame_reader().unwrap_err().is_input());
+    Ok(())
+}
+
+#[cfg(feature = "ndarray")]
+#[test]
+fn test_synthetic_to_array() -> Result<()> {
+    let mut builder = Nd2Builder::new(4, 3, 2, 3);
+    builder.experiment = Some(Clx::Level(
+        "SLxExperiment",
+        vec![
+            Clx::U32("eType", 1),
+            Clx::Level("uLoopPars", vec![Clx::U32("uiCount", 3)]),
+        ],
+    ));
+    let mut nd2 = common::open(&builder);
+    let array = nd2.to_array()?;
+    assert_eq!(array.shape(), &[1, 3, 2, 1, 3, 4]);
+    let mut expected = Vec::new();
+    for t in 0..3 {
+        for c in 0..2 {
+            expected.extend(nd2.read_frame_2d(0, t, c, 0)?);
+        }
+    }
+    assert_eq!(array.iter().copied().collect::<Vec<u16>>(), expected);
+
+    let floats = nd2.to_array_as::<f32>()?;
+    assert_eq!(floats.shape(), array.shape());
+    assert!(floats
+        .iter()
+        .zip(array.iter())
+        .all(|(&f, &u)| f == f32::from(u)));
+    assert!(nd2.to_array_as::<u8>().is_err());
+    Ok(())
+}
+
+#[cfg(feature = "frame-cache")]
+#[test]
+fn test_synthetic_frame_cache() -> Result<()> {
+    let mut builder = Nd2Builder::new(4, 3, 2, 5);
+    builder.lossless = true;
+    let root = common::temp_path("frame_cache");
+    let file = common::temp_path("frame_cache.nd2");
+    std::fs::write(&file, builder.build())?;
+    let expected = common::open(&builder).read_frames(&[0, 1, 2, 3, 4])?;
+    for threads in [1, 3, 1] {
+        let options = Nd2Options::new().frame_cache(&root).decode_threads(threads);
+        let mut nd2 = Nd2File::open_with(&file, options)?;
+        assert_eq!(nd2.read_frame(3)?, expected[3]);
+        assert_eq!(nd2.read_frames(&[0, 1, 2, 3, 4])?, expected);
+        assert_eq!(nd2.read_frame_with_meta(2)?.0, expected[2]);
+        assert!(nd2.diagnostics().is_empty());
+    }
+    let dirs: Vec<_> = std::fs::read_dir(&root)?.collect::<std::io::Result<_>>()?;
+    assert_eq!(dirs.len(), 1);
+    let entry = dirs[0].path().join("2.zst");
+    assert_eq!(std::fs::read_dir(dirs[0].path())?.count(), 5);
+
+    // Unreadable entries are misses.
+    std::fs::write(&entry, b"garbage")?;
+    let options = Nd2Options::new().frame_cache(&root);
+    let mut nd2 = Nd2File::open_with(&file, options)?;
+    assert_eq!(nd2.read_frame(2)?, expected[2]);
+    assert_ne!(std::fs::read(&entry)?, b"garbage");
+
+    // Another file with the same chunk layout but other pixels gets its
+    // own entries.
+    let mut other = builder.clone();
+    for frame in &mut other.frames {
+        frame.iter_mut().for_each(|p| *p = p.wrapping_add(7));
+    }
+    let other_file = common::temp_path("frame_cache_other.nd2");
+    std::fs::write(&other_file, other.build())?;
+    let options = Nd2Options::new().frame_cache(&root);
+    let mut nd2 = Nd2File::open_with(&other_file, options)?;
+    assert_eq!(nd2.read_frame(2)?, common::open(&other).read_frame(2)?);
+    assert_eq!(std::fs::read_dir(&root)?.count(), 2);
+
+    // Readers cannot be identified and are read without the cache.
+    let options = Nd2Options::new().frame_cache(&root);
+    let mut nd2 = Nd2File::open_reader_with(Cursor::new(other.build()), options)?;
+    assert_eq!(nd2.read_frame(1)?, common::open(&other).read_frame(1)?);
+    assert_eq!(std::fs::read_dir(&root)?.count(), 2);
+    std::fs::remove_dir_all(&root)?;
+    std::fs::remove_file(&other_file)?;
+
+    // A cache that cannot be written is reported, not an error.
+    std::fs::write(&root, b"")?;
+    let options = Nd2Options::new().frame_cache(&root);
+    let mut nd2 = Nd2File::open_with(&file, options)?;
+    assert_eq!(nd2.read_frames(&[0, 1, 2, 3, 4])?, expected);
+    let kinds: Vec<_> = nd2.diagnostics().iter().map(|d| d.kind).collect();
+    assert_eq!(kinds, [DiagnosticKind::FrameCacheWrite]);
+    std::fs::remove_file(&root)?;
+    std::fs::remove_file(&file)?;
+    Ok(())
+}
+
+#[cfg(feature = "rerun")]
+#[test]
+fn test_synthetic_rerun_log() -> Result<()> {
+    let builder = Nd2Builder::new(4, 3, 2, 3);
+    let mut nd2 = common::open(&builder);
+    let (rec, storage) = rerun::RecordingStreamBuilder::new("nd2-rs-test")
+        .memory()
+        .unwrap();
+    nd2_rs::RerunLogger::new().channel(1).log(&mut nd2, &rec)?;
+    assert!(!storage.take().is_empty());
+    Ok(())
+}
+
+#[cfg(feature = "polars")]
+#[test]
+fn test_synthetic_polars_tables() -> Result<()> {
+    let mut builder = Nd2Builder::new(4, 3, 1, 3);
+    builder.experiment = Some(Clx::Level(
+        "SLxExperiment",
+        vec![
+            Clx::U32("eType", 4),
+            Clx::Level(
+                "uLoopPars",
+                vec![Clx::U32("uiCount", 3), Clx::F64("dZStep", 0.5)],
+            ),
+        ],
+    ));
+    let mut nd2 = common::open(&builder);
+
+    let frames = nd2.frames_dataframe()?;
+    assert_eq!(frames.shape(), (3, 9));
+    assert_eq!(
+        frames.get_column_names(),
+        ["index", "P", "T", "C", "Z", "time_ms", "x_um", "y_um", "z_um"]
+    );
+    assert_eq!(frames.column("x_um").unwrap().null_count(), 3);
+    let times: Vec<Option<f64>> = frames
+        .column("time_ms")
+        .unwrap()
+        .f64()
+        .unwrap()
+        .into_iter()
+        .collect();
+    assert_eq!(times, [Some(0.0), Some(100.0), Some(200.0)]);
+
+    let recorded = nd2.recorded_data_dataframe()?;
+    assert_eq!(recorded.get_column_names(), ["Frame", "Time [s]"]);
+    assert_eq!(recorded.shape(), (3, 2));
+    Ok(())
+}
+
+#[test]
+fn test_synthetic_ome_xml() -> Result<()> {
+    let builder = Nd2Builder::new(4, 3, 1, 3);
+    let mut nd2 = common::open(&builder);
+    let xml = nd2.ome_xml()?;
+
+    assert!(xml.contains(&format!(
+        "xmlns=\"http://www.openmicroscopy.org/Schemas/OME/{}\"",
+        nd2_rs::OME_SCHEMA_VERSION
+    )));
+    assert!(xml.contains("SizeX=\"4\" SizeY=\"3\" SizeZ=\"1\" SizeC=\"1\" SizeT=\"3\""));
+    assert_eq!(xml.matches("<Channel ").count(), 1);
+    assert_eq!(xml.matches("<Plane ").count(), 3);
+    // Per-plane DeltaT comes from the frame chunk timestamps.
+    assert!(
+        xml.contains("<Plane TheZ=\"0\" TheT=\"2\" TheC=\"0\" DeltaT=\"200\" DeltaTUnit=\"ms\"/>")
+    );
+    // MetadataOnly must precede the planes for the schema's sequence.
+    assert!(xml.find("<MetadataOnly/>") < xml.find("<Plane "));
+
+    common::validate_ome_xml(&xml);
+    Ok(())
+}
+
+#[test]
+fn test_synthetic_unknown_loop_diagnostic() -> Result<()> {
+    let mut builder = Nd2Builder::new(4, 3, 1, 3);
+    builder.experiment = Some(Clx::Level(
+        "SLxExperiment",
+        vec![
+            Clx::U32("eType", 5),
+            Clx::Level("uLoopPars", vec![Clx::U32("uiCount", 3)]),
+        ],
+    ));
+    let mut nd2 = common::open(&builder);
+    assert!(nd2.diagnostics().is_empty());
+
+    // The skipped loop leaves no experiment, so T falls back to the sequence count.
+    let summary = nd2.summary()?;
+    assert_eq!(summary.sizes["T"], 3);
+    assert!(nd2
+        .diagnostics()
+        .iter()
+        .any(|d| d.kind == DiagnosticKind::UnknownLoopType));
+    Ok(())
+}
+
+#[test]
+fn test_synthetic_huge_offsets_and_indices() -> Result<()> {
+    // Frame 1 and the chunkmap past 4 GiB, in a sparse file.
+    let builder = Nd2Builder::new(4, 3, 1, 2);
+    let mut chunks = builder.chunks();
+    let frame1 = chunks
+        .iter()
+        .position(|(name, _)| name == b"ImageDataSeq|1!");
+    let (frame1_name, frame1_data) = chunks.remove(frame1.unwrap());
+    let mut head = Vec::new();
+    let mut version = builder.version.as_bytes().to_vec();
+    version.resize(64, 0);
+    common::write_chunk(&mut head, common::FILE_SIGNATURE, &version);
+    let mut entries = Vec::new();
+    for (name, data) in &chunks {
+        entries.push((name.clone(), head.len() as u64, data.len() as u64));
+        common::write_chunk(&mut head, name, data);
+    }
+    let base = (5u64 << 30) + 3;
+    let mut tail = Vec::new();
+    common::write_chunk(&mut tail, &frame1_name, &frame1_data);
+    entries.push((frame1_name, base, frame1_data.len() as u64));
+    common::append_chunkmap_at(&mut tail, base, &entries);
+
+    let path = common::temp_path("huge_offsets.nd2");
+    {
+        use std::io::{Seek, SeekFrom, Write};
+        let mut file = std::fs::File::create(&path)?;
+        file.write_all(&head)?;
+        file.seek(SeekFrom::Start(base))?;
+        file.write_all(&tail)?;
+    }
+    let expected = common::open(&builder).read_frame(1)?;
+    let mut nd2 = Nd2File::open(&path)?;
+    assert_eq!(nd2.read_frame(1)?, expected);
+    assert_eq!(nd2.frame_reader()?.read_frame(1)?, expected);
+    assert_eq!(nd2.read_chunk("ImageDataSeq|1!")?, frame1_data);
+    drop(nd2);
+    std::fs::remove_file(&path)?;
+
+    // Sequence indices past 2^31 and at the top of the index range.
+    let builder = Nd2Builder::new(4, 3, 1, 2);
+    let mut chunks = builder.chunks();
+    let huge = format!("ImageDataSeq|{}!", 3_000_000_000u64);
+    let top = format!("ImageDataSeq|{}!", usize::MAX);
+    chunks.push((huge.clone().into_bytes(), b"huge".to_vec()));
+    chunks.push((top.clone().into_bytes(), b"top".to_vec()));
+    let mut nd2 = Nd2File::open_reader(Cursor::new(common::build_file(builder.version, &chunks)))?;
+    assert_eq!(nd2.n_frames()?, 2);
+    assert!(nd2.chunk_names().ends_with(&[huge.clone(), top.clone()]));
+    assert_eq!(nd2.read_chunk(&huge)?, b"huge");
+    assert_eq!(nd2.read_chunk(&top)?, b"top");
+    assert!(nd2.read_frame(3_000_000_001).unwrap_err().is_input());
+    assert!(nd2.read_frame(usize::MAX - 1).unwrap_err().is_input());
+    Ok(())
+}
+
+#[test]
+fn test_synthetic_edit_text_info() -> Result<()> {
+    let mut builder = Nd2Builder::new(4, 3, 1, 2);
+    let text_info = Clx::Level(
+        "SLxImageTextInfo",
+        vec![
+            Clx::Str("TextInfoItem_4", "A. Curator".to_string()),
+            Clx::Str("TextInfoItem_5", "Wrong objective noted here".to_string()),
+            Clx::Str("TextInfoItem_9", "2026-10-15".to_string()),
+        ],
+    );
+    builder
+        .extra_chunks
+        .push((b"ImageTextInfoLV!".to_vec(), text_info.encode()));
+    let path = common::temp_path("edit_text_info.nd2");
+    std::fs::write(&path, builder.build())?;
+    let info = Nd2File::open(&path)?.text_info()?;
+    assert_eq!(info.author.as_deref(), Some("A. Curator"));
+    assert_eq!(info.date.as_deref(), Some("2026-10-15"));
+
+    // A shorter description fits over the old chunk.
+    let changes = TextInfo {
+        description: Some("60x oil".to_string()),
+        ..TextInfo::default()
+    };
+    assert_eq!(nd2_rs::edit_text_info(&path, &changes)?, EditMode::InPlace);
+    let len = std::fs::metadata(&path)?.len();
+    let mut nd2 = Nd2File::open(&path)?;
+    let info = nd2.text_info()?;
+    assert_eq!(info.description.as_deref(), Some("60x oil"));
+    assert_eq!(info.author.as_deref(), Some("A. Curator"));
+
+    // A new field makes the chunk grow, so it moves to the end.
+    let changes = TextInfo {
+        sample_id: Some("Well A1, 96-well plate".to_string()),
+        author: Some(String::new()),
+        ..TextInfo::default()
+    };
+    assert_eq!(nd2_rs::edit_text_info(&path, &changes)?, EditMode::Appended);
+    assert!(std::fs::metadata(&path)?.len() > len);
+    let mut nd2 = Nd2File::open(&path)?;
+    let info = nd2.text_info()?;
+    assert_eq!(info.sample_id.as_deref(), Some("Well A1, 96-well plate"));
+    assert_eq!(info.description.as_deref(), Some("60x oil"));
+    assert_eq!(info.author, None);
+    assert_eq!(info.date.as_deref(), Some("2026-10-15"));
+    assert!(nd2.diagnostics().is_empty());
+    for (index, frame) in builder.frames.iter().enumerate() {
+        assert_eq!(&nd2.read_frame(index)?, frame);
+    }
+    drop(nd2);
+
+    // A file without text info gets a new chunk.
+    std::fs::write(&path, Nd2Builder::new(4, 3, 1, 2).build())?;
+    assert_eq!(nd2_rs::edit_text_info(&path, &changes)?, EditMode::Appended);
+    let info = Nd2File::open(&path)?.text_info()?;
+    assert_eq!(info.sample_id.as_deref(), Some("Well A1, 96-well plate"));
+    assert_eq!(info.author, None);
+    std::fs::remove_file(&path)?;
+    Ok(())
+}
+
+#[test]
+fn test_synthetic_anonymize_to() -> Result<()> {
+    let mut builder = Nd2Builder::new(4, 3, 1, 2);
+    let guid = "{0A1B2C3D-4E5F-6071-8293-A4B5C6D7E8F9}";
+    let text_info = Clx::Level(
+        "SLxImageTextInfo",
+        vec![
+            Clx::Str("TextInfoItem_4", "Alice Example".to_string()),
+            Clx::Str("TextInfoItem_5", "HeLa, 60x".to_string()),
+        ],
+    );
+    let metadata = Clx::Level(
+        "SLxPictureMetadata",
+        vec![
+            Clx::F64("dTimeAbsolute", 2_460_000.5),
+            Clx::Str("wsFileName", "C:\\Users\\alice\\plate1.nd2".to_string()),
+            Clx::Str("wsCameraName", "Camera 1".to_string()),
+            Clx::Str("sGuid", guid.to_string()),
+            Clx::Level(
+                "sRef",
+                vec![Clx::Str(
+                    "sGuid",
+                    guid.to_lowercase().replace(['{', '}'], ""),
+                )],
+            ),
+        ],
+    );
+    let events = Clx::Level(
+        "ExperimentEventsV1_0",
+        vec![Clx::Level(
+            "pEvents",
+            vec![Clx::Level(
+                "i0000000000",
+                vec![
+                    Clx::F64("dTime", 150.0),
+                    Clx::U32("uiMeaning", 3),
+                    Clx::Str(
+                        "wsDescription",
+                        "/home/alice/protocols/stim.txt".to_string(),
+                    ),
+                ],
+            )],
+        )],
+    );
+    let positions: Vec<u8> = [1.5f64, 2.5, 3.5, 4.5]
+        .iter()
+        .flat_map(|v| v.to_le_bytes())
+        .collect();
+    builder.extra_chunks = vec![
+        (b"ImageTextInfoLV!".to_vec(), text_info.encode()),
+        (b"ImageMetadataSeqLV|0!".to_vec(), metadata.encode()),
+        (
+            b"CustomData|ExperimentEventsV1_0!".to_vec(),
+            events.encode(),
+        ),
+        (b"CustomData|X!".to_vec(), positions.clone()),
+    ];
+    let src = common::temp_path("anonymize_src.nd2");
+    let dst = common::temp_path("anonymize_dst.nd2");
+    std::fs::write(&src, builder.build())?;
+    let policy = AnonymizePolicy::new().time_shift_days(-100.0);
+    assert!(nd2_rs::anonymize_to(&src, &src, &policy)
+        .unwrap_err()
+        .is_input());
+    nd2_rs::anonymize_to(&src, &dst, &policy)?;
+
+    let mut nd2 = Nd2File::open(&dst)?;
+    let info = nd2.text_info()?;
+    assert_eq!(info.author, None);
+    assert_eq!(info.description.as_deref(), Some("HeLa, 60x"));
+    let clx = ClxLiteParser::new(false).parse(&nd2.read_chunk("ImageMetadataSeqLV|0!")?)?;
+    let picture = clx.as_object().unwrap()["SLxPictureMetadata"]
+        .as_object()
+        .unwrap();
+    assert_eq!(picture["dTimeAbsolute"].as_f64(), Some(2_459_900.5));
+    assert_eq!(picture["wsFileName"].as_str(), Some(""));
+    assert_eq!(picture["wsCameraName"].as_str(), Some("Camera 1"));
+    let placeholder = "00000000-0000-0000-0000-000000000001";
+    assert_eq!(
+        picture["sGuid"].as_str(),
+        Some(format!("{{{}}}", placeholder).as_str())
+    );
+    assert_eq!(
+        picture["sRef"].as_object().unwrap()["sGuid"].as_str(),
+        Some(placeholder)
+    );
+    for (index, frame) in builder.frames.iter().enumerate() {
+        assert_eq!(&nd2.read_frame(index)?, frame);
+    }
+    assert_eq!(nd2.events()?[0].description, "");
+    assert_eq!(nd2.read_chunk("CustomData|X!")?, positions);
+    assert!(nd2.diagnostics().is_empty());
+    let bytes = std::fs::read(&dst)?;
+    let alice: Vec<u8> = "alice".encode_utf16().flat_map(u16::to_le_bytes).collect();
+    assert!(!bytes.windows(alice.len()).any(|w| w == alice));
+
+    // Text info the reader skips an unknown entry of cannot be scrubbed,
+    // so the copy fails instead of keeping it.
+    let Clx::Level(name, mut items) = text_info else {
+        unreachable!();
+    };
+    items.push(Clx::Raw(vec![42, 1, 0, 0, 9]));
+    builder.extra_chunks[0].1 = Clx::Level(name, items).encode();
+    std::fs::write(&src, builder.build())?;
+    assert_eq!(
+        Nd2File::open(&src)?.text_info()?.author.as_deref(),
+        Some("Alice Example")
+    );
+    assert!(nd2_rs::anonymize_to(&src, &dst, &policy).is_err());
+    std::fs::remove_file(&src)?;
+    std::fs::remove_file(&dst)?;
+    Ok(())
+}
+
+#[test]
+fn test_synthetic_subset_to() -> Result<()> {
+    let point = |key: &'static str, name: &str| {
+        Clx::Level(key, vec![Clx::Str("dPosName", name.to_string())])
+    };
+    let experiment = Clx::Level(
+        "SLxExperiment",
+        vec![
+            Clx::U32("eType", 1),
+            Clx::Level("uLoopPars", vec![Clx::U32("uiCount", 3)]),
+            Clx::Level(
+                "ppNextLevelEx",
+                vec![Clx::Level(
+                    "i0000000000",
+                    vec![
+                        Clx::U32("eType", 2),
+                        Clx::Level(
+                            "uLoopPars",
+                            vec![
+                                Clx::U32("uiCount", 3),
+                                Clx::Level(
+                                    "Points",
+                                    vec![
+                                        point("i0000000000", "A1"),
+                                        point("i0000000001", "A2"),
+                                        point("i0000000002", "A3"),
+                                    ],
+                                ),
+                            ],
+                        ),
+                        Clx::Level(
+                            "pItemValid",
+                            vec![
+                                Clx::Bool("i0000000000", true),
+                                Clx::Bool("i0000000001", false),
+                                Clx::Bool("i0000000002", true),
+                            ],
+                        ),
+                    ],
+                )],
+            ),
+        ],
+    );
+    let plane = |key: &'static str, name: &str| {
+        Clx::Level(key, vec![Clx::Str("sDescription", name.to_string())])
+    };
+    let picture = Clx::Level(
+        "SLxPictureMetadata",
+        vec![Clx::Level(
+            "sPicturePlanes",
+            vec![
+                Clx::U32("uiCount", 2),
+                Clx::Level("sPlaneNew", vec![plane("a0", "DAPI"), plane("a1", "GFP")]),
+            ],
+        )],
+    );
+    let times: Vec<u8> = (0..6)
+        .flat_map(|i| (i as f64 * 1.5).to_le_bytes())
+        .collect();
+
+    for lossless in [false, true] {
+        // Time outermost, then the two valid positions A1 and A3.
+        let mut builder = Nd2Builder::new(4, 3, 2, 6);
+        builder.lossless = lossless;
+        builder.experiment = Some(experiment.clone());
+        builder.extra_chunks = vec![
+            (b"ImageMetadataSeqLV|0!".to_vec(), picture.encode()),
+            (b"CustomData|AcqTimesCache!".to_vec(), times.clone()),
+        ];
+        let src = common::temp_path("subset_src.nd2");
+        let dst = common::temp_path("subset_dst.nd2");
+        std::fs::write(&src, builder.build())?;
+        let selection = SubsetSelection::new()
+            .positions([1])
+            .channels([1])
+            .time(1..3);
+        assert!(nd2_rs::subset_to(&src, &src, &selection)
+            .unwrap_err()
+            .is_input());
+        assert!(
+            nd2_rs::subset_to(&src, &dst, &SubsetSelection::new().positions([2]))
+                .unwrap_err()
+                .is_input()
+        );
+        nd2_rs::subset_to(&src, &dst, &selection)?;
+
+        let mut source = Nd2File::open(&src)?;
+        let mut nd2 = Nd2File::open(&dst)?;
+        assert_eq!(nd2.n_frames()?, 2);
+        assert_eq!(nd2.n_timepoints()?, 2);
+        assert_eq!(nd2.n_positions()?, 1);
+        assert_eq!(nd2.n_channels()?, 1);
+        assert_eq!(nd2.is_compressed()?, lossless);
+        for t in 0..2 {
+            assert_eq!(
+                nd2.read_frame_2d(0, t, 0, 0)?,
+                source.read_frame_2d(1, t + 1, 1, 0)?
+            );
+        }
+        assert!(nd2.diagnostics().is_empty());
+
+        let clx = ClxLiteParser::new(false).parse(&nd2.read_chunk("ImageMetadataLV!")?)?;
+        let xy = &clx.as_object().unwrap()["SLxExperiment"]
+            .as_object()
+            .unwrap()["ppNextLevelEx"]
+            .as_object()
+            .unwrap()["i0000000000"];
+        let points = xy.as_object().unwrap()["uLoopPars"].as_object().unwrap()["Points"]
+            .as_object()
+            .unwrap();
+        assert_eq!(points.len(), 1);
+        assert_eq!(
+            points["i0000000002"].as_object().unwrap()["dPosName"].as_str(),
+            Some("A3")
+        );
+        assert_eq!(
+            xy.as_object().unwrap()["pItemValid"]
+                .as_object()
+                .unwrap()
+                .len(),
+            1
+        );
+
+        let clx = ClxLiteParser::new(false).parse(&nd2.read_chunk("ImageMetadataSeqLV|0!")?)?;
+        let planes = clx.as_object().unwrap()["SLxPictureMetadata"]
+            .as_object()
+            .unwrap()["sPicturePlanes"]
+            .as_object()
+            .unwrap();
+        assert_eq!(planes["uiCount"].as_u64(), Some(1));
+        let kept = planes["sPlaneNew"].as_object().unwrap();
+        assert_eq!(kept.len(), 1);
+        assert_eq!(
+            kept["a1"].as_object().unwrap()["sDescription"].as_str(),
+            Some("GFP")
+        );
+        // Frames (t1, A3) and (t2, A3) were frames 3 and 5.
+        let times: Vec<u8> = [4.5f64, 7.5].iter().flat_map(|t| t.to_le_bytes()).collect();
+        assert_eq!(nd2.read_chunk("CustomData|AcqTimesCache!")?, times);
+        std::fs::remove_file(&src)?;
+        std::fs::remove_file(&dst)?;
+    }
+    Ok(())
+}
+
+#[test]
+fn test_synthetic_subset_per_frame_chunks() -> Result<()> {
+    let experiment = Clx::Level(
+        "SLxExperiment",
+        vec![
+            Clx::U32("eType", 1),
+            Clx::Level("uLoopPars", vec![Clx::U32("uiCount", 6)]),
+        ],
+    );
+    let picture = |seq: usize| {
+        let plane = |key: &'static str, name: &str| {
+            Clx::Level(key, vec![Clx::Str("sDescription", format!("{name}-{seq}"))])
+        };
+        Clx::Level(
+            "SLxPictureMetadata",
+            vec![Clx::Level(
+                "sPicturePlanes",
+                vec![
+                    Clx::U32("uiCount", 2),
+                    Clx::Level("sPlaneNew", vec![plane("a0", "DAPI"), plane("a1", "GFP")]),
+                ],
+            )],
+        )
+        .encode()
+    };
+    let planes = |nd2: &mut Nd2File, seq: usize| -> Result<Vec<String>> {
+        let name = format!("ImageMetadataSeqLV|{seq}!");
+        let clx = ClxLiteParser::new(false).parse(&nd2.read_chunk(&name)?)?;
+        let planes = clx.as_object().unwrap()["SLxPictureMetadata"]
+            .as_object()
+            .unwrap()["sPicturePlanes"]
+            .as_object()
+            .unwrap();
+        let names: Vec<String> = planes["sPlaneNew"]
+            .as_object()
+            .unwrap()
+            .values()
+            .map(|plane| {
+                plane.as_object().unwrap()["sDescription"]
+                    .as_str()
+                    .unwrap()
+                    .to_string()
+            })
+            .collect();
+        assert_eq!(planes["uiCount"].as_u64(), Some(names.len() as u64));
+        Ok(names)
+    };
+    let names = |nd2: &Nd2File, prefix: &str| -> Vec<String> {
+        nd2.chunk_listing()
+            .chunks
+            .into_iter()
+            .map(|chunk| chunk.name)
+            .filter(|name| name.starts_with(prefix))
+            .collect()
+    };
+
+    let mut builder = Nd2Builder::new(4, 3, 2, 6);
+    builder.experiment = Some(experiment);
+    for seq in 0..6 {
+        builder.extra_chunks.push((
+            format!("ImageMetadataSeqLV|{seq}!").into_bytes(),
+            picture(seq),
+        ));
+    }
+    for seq in [1, 3, 4] {
+        builder.extra_chunks.push((
+            format!("CustomDataSeq|Mask|{seq}!").into_bytes(),
+            vec![seq as u8; 6],
+        ));
+    }
+    let src = common::temp_path("subset_seq_src.nd2");
+    let dst = common::temp_path("subset_seq_dst.nd2");
+    std::fs::write(&src, builder.build())?;
+    let selection = SubsetSelection::new().time(2..5).channels([1]);
+    nd2_rs::subset_to(&src, &dst, &selection)?;
+
+    // Frames 2, 3 and 4 became 0, 1 and 2, with their metadata and masks.
+    let mut nd2 = Nd2File::open(&dst)?;
+    assert_eq!(nd2.n_frames()?, 3);
+    assert_eq!(
+        names(&nd2, "ImageMetadataSeqLV|"),
+        [
+            "ImageMetadataSeqLV|0!",
+            "ImageMetadataSeqLV|1!",
+            "ImageMetadataSeqLV|2!"
+        ]
+    );
+    for seq in 0..3 {
+        assert_eq!(planes(&mut nd2, seq)?, [format!("GFP-{}", seq + 2)]);
+    }
+    assert_eq!(
+        names(&nd2, "CustomDataSeq|"),
+        ["CustomDataSeq|Mask|1!", "CustomDataSeq|Mask|2!"]
+    );
+    assert_eq!(nd2.read_chunk("CustomDataSeq|Mask|1!")?, [3; 6]);
+    assert_eq!(nd2.read_chunk("CustomDataSeq|Mask|2!")?, [4; 6]);
+    assert!(nd2.diagnostics().is_empty());
+
+    // Picture metadata of frame 0 only stays, as the file's.
+    builder.extra_chunks = vec![(b"ImageMetadataSeqLV|0!".to_vec(), picture(0))];
+    std::fs::write(&src, builder.build())?;
+    nd2_rs::subset_to(&src, &dst, &selection)?;
+    let mut nd2 = Nd2File::open(&dst)?;
+    assert_eq!(
+        names(&nd2, "ImageMetadataSeqLV|"),
+        ["ImageMetadataSeqLV|0!"]
+    );
+    assert_eq!(planes(&mut nd2, 0)?, ["GFP-0"]);
+    std::fs::remove_file(&src)?;
+    std::fs::remove_file(&dst)?;
+    Ok(())
+}
+
+#[test]
+fn test_synthetic_repair_to() -> Result<()> {
+    let builder = Nd2Builder::new(4, 3, 1, 4);
+    let file = builder.build();
+    let name = b"ImageDataSeq|3!";
+    let last = file.windows(name.len()).position(|w| w == name).unwrap() - 16;
+    // Cut off inside the last frame, chunkmap and all, then garbage.
+    let mut damaged = file[..last + 16 + name.len() + 10].to_vec();
+    damaged.extend_from_slice(b"\0\0garbage");
+    let src = common::temp_path("repair_src.nd2");
+    let dst = common::temp_path("repair_dst.nd2");
+    std::fs::write(&src, &damaged)?;
+    assert!(Nd2File::open(&src).is_err());
+    assert!(nd2_rs::repair_to(&src, &src).unwrap_err().is_input());
+
+    let report = nd2_rs::repair_to(&src, &dst)?;
+    assert!(report.chunkmap_rebuilt);
+    assert_eq!(report.frames_copied, 3);
+    assert_eq!(report.chunks_copied, 4);
+    assert_eq!(report.missing_frames, [3]);
+    assert_eq!(report.bytes_discarded, (damaged.len() - last) as u64);
+    let mut nd2 = Nd2File::open(&dst)?;
+    assert!(nd2.diagnostics().is_empty());
+    for index in 0..3 {
+        assert_eq!(&nd2.read_frame(index)?, &builder.frames[index]);
+    }
+
+    // An intact file only loses its chunkmap, which is written anew.
+    std::fs::write(&src, &file)?;
+    let report = nd2_rs::repair_to(&src, &dst)?;
+    assert!(!report.chunkmap_rebuilt);
+    assert_eq!(report.frames_copied, 4);
+    assert!(report.chunks_dropped.is_empty());
+    assert!(report.missing_frames.is_empty());
+    assert_eq!(std::fs::read(&dst)?, file);
+    std::fs::remove_file(&src)?;
+    std::fs::remove_file(&dst)?;
+    Ok(())
+}
+
+#[cfg(not(feature = "jpeg2000"))]
+#[test]
+fn test_synthetic_lossy_needs_feature() -> Result<()> {
+    let builder = Nd2Builder::new(4, 3, 1, 2);
+    let mut attributes = builder.attributes_clx();
+    if let Clx::Level(_, items) = &mut attributes {
+        items.push(Clx::Str("eCompression", "lossy".to_string()));
+    }
+    let mut chunks = builder.chunks();
+    chunks[0].1 = attributes.encode();
+    let file = common::build_file(builder.version, &chunks);
+    let mut nd2 = Nd2File::open_reader(Cursor::new(file.clone()))?;
+    assert!(nd2.is_compressed()?);
+    // An error naming the codec, not misread pixel rows.
+    let err = nd2.read_frame(0).unwrap_err();
+    assert!(err.is_unsupported());
+    assert!(format!("{err:?}").contains("jpeg2000"));
+
+    let src = common::temp_path("lossy_src.nd2");
+    let dst = common::temp_path("lossy_dst.nd2");
+    std::fs::write(&src, &file)?;
+    assert!(nd2_rs::transcode_to(&src, &dst, CompressionType::None)
+        .unwrap_err()
+        .is_unsupported());
+    let _ = std::fs::remove_file(&src);
+    let _ = std::fs::remove_file(&dst);
+    Ok(())
+}
+
+/// A complete JPEG 2000 codestream of `width`x`height` pixels with one
+/// 12-bit component, no wavelet levels and one empty packet, so every
+/// pixel decodes to the DC level shift, 2048.
+#[cfg(feature = "jpeg2000")]
+fn flat_codestream(width: u32, height: u32) -> Vec<u8> {
+    let mut c = codestream(width, height, 0);
+    // Replace the EOC marker and tag with COD, QCD and one tile-part.
+    c.truncate(c.len() - 3);
+    c.extend_from_slice(&[0xFF, 0x52, 0, 12, 0, 0, 0, 1, 0, 0, 4, 4, 0, 1]);
+    c.extend_from_slice(&[0xFF, 0x5C, 0, 4, 0x40, 12 << 3]);
+    c.extend_from_slice(&[0xFF, 0x90, 0, 10, 0, 0, 0, 0, 0, 15, 0, 1]);
+    c.extend_from_slice(&[0xFF, 0x93, 0, 0xFF, 0xD9]);
+    c
+}
+
+#[cfg(feature = "jpeg2000")]
+#[test]
+fn test_synthetic_lossy_frames() -> Result<()> {
+    let builder = Nd2Builder::new(4, 3, 1, 2);
+    let mut attributes = builder.attributes_clx();
+    if let Clx::Level(_, items) = &mut attributes {
+        items.push(Clx::Str("eCompression", "lossy".to_string()));
+    }
+    let mut chunks = builder.chunks();
+    chunks[0].1 = attributes.encode();
+    for (name, data) in chunks.iter_mut() {
+        if name.starts_with(b"ImageDataSeq") {
+            data.truncate(8);
+            data.extend(flat_codestream(4, 3));
+        }
+    }
+    let file = common::build_file(builder.version, &chunks);
+    let mut nd2 = Nd2File::open_reader(Cursor::new(file.clone()))?;
+    assert_eq!(nd2.read_frame(0)?, vec![2048; 12]);
+    assert_eq!(nd2.read_frames(&[1, 0])?, vec![vec![2048; 12]; 2]);
+    assert_eq!(nd2.frame_times()?, [0.0, 100.0]);
+
+    // Decoded frames can be written losslessly.
+    let src = common::temp_path("lossy_frames_src.nd2");
+    let dst = common::temp_path("lossy_frames_dst.nd2");
+    std::fs::write(&src, &file)?;
+    nd2_rs::transcode_to(&src, &dst, CompressionType::Lossless)?;
+    let mut transcoded = Nd2File::open(&dst)?;
+    assert_eq!(transcoded.read_frame(1)?, vec![2048; 12]);
+    drop(transcoded);
+    std::fs::remove_file(&src)?;
+    std::fs::remove_file(&dst)?;
+
+    // A codestream of the wrong size is rejected, not cropped.
+    for (name, data) in chunks.iter_mut() {
+        if name.starts_with(b"ImageDataSeq") {
+            data.truncate(8);
+            data.extend(flat_codestream(5, 3));
+        }
+    }
+    let file = common::build_file(builder.version, &chunks);
+    let mut nd2 = Nd2File::open_reader(Cursor::new(file))?;
+    assert!(nd2.read_frame(0).unwrap_err().is_file());
+    Ok(())
+}
+
+#[test]
+fn test_synthetic_transcode_to() -> Result<()> {
+    let mut builder = Nd2Builder::new(4, 3, 1, 3);
+    builder.extra_chunks = vec![(b"CustomDataVar|Note!".to_vec(), b"kept as is".to_vec())];
+    let file = builder.build();
+    let src = common::temp_path("transcode_src.nd2");
+    let lossless = common::temp_path("transcode_lossless.nd2");
+    let raw = common::temp_path("transcode_raw.nd2");
+    std::fs::write(&src, &file)?;
+    assert!(nd2_rs::transcode_to(&src, &src, CompressionType::Lossless)
+        .unwrap_err()
+        .is_input());
+    assert!(
+        nd2_rs::transcode_to(&src, &lossless, CompressionType::Lossy)
+            .unwrap_err()
+            .is_unsupported()
+    );
+
+    // Deflating adds the missing `eCompression` entry.
+    nd2_rs::transcode_to(&src, &lossless, CompressionType::Lossless)?;
+    // Inflating again replaces it.
+    nd2_rs::transcode_to(&lossless, &raw, CompressionType::None)?;
+    for (path, compressed) in [(&lossless, true), (&raw, false)] {
+        let mut nd2 = Nd2File::open(path)?;
+        assert_eq!(nd2.is_compressed()?, compressed);
+        for (index, frame) in builder.frames.iter().enumerate() {
+            let (pixels, meta) = nd2.read_frame_with_meta(index)?;
+            assert_eq!(&pixels, frame);
+            assert_eq!(meta.timestamp_ms, Some(index as f64 * 100.0));
+        }
+        assert_eq!(nd2.read_chunk(b"CustomDataVar|Note!")?, b"kept as is");
+    }
+
+    // Frames already stored as asked for are copied as they are.
+    nd2_rs::transcode_to(&src, &raw, CompressionType::None)?;
+    assert_eq!(std::fs::read(&raw)?, file);
+
+    // An `eCompression` entry that is not a string is replaced as well.
+    let mut chunks = builder.chunks();
+    let Clx::Level(key, mut items) = builder.attributes_clx() else {
+        unreachable!()
+    };
+    items.push(Clx::U32("eCompression", 0));
+    chunks[0].1 = Clx::Level(key, items).encode();
+    std::fs::write(&src, common::build_file(builder.version, &chunks))?;
+    nd2_rs::transcode_to(&src, &lossless, CompressionType::Lossless)?;
+    let mut nd2 = Nd2File::open(&lossless)?;
+    assert!(nd2.is_compressed()?);
+    assert_eq!(nd2.read_frame(1)?, builder.frames[1]);
+
+    for path in [&src, &lossless, &raw] {
+        std::fs::remove_file(path)?;
+    }
+    Ok(())
+}
+
+#[test]
+fn test_synthetic_apply_metadata_patch() -> Result<()> {
+    let plane = |key: &'static str, name: &str| {
+        Clx::Level(key, vec![Clx::Str("sDescription", name.to_string())])
+    };
+    let picture = Clx::Level(
+        "SLxPictureMetadata",
+        vec![
+            Clx::F64("dCalibration", 1.0),
+            Clx::Bool("bCalibrated", false),
+            Clx::Level(
+                "sObjective",
+                vec![
+                    Clx::Str("wsObjectiveName", "Plan Fluor 10x".to_string()),
+                    Clx::F64("dObjectiveMag", 10.0),
+                    Clx::F64("dObjectiveNA", 0.3),
+                ],
+            ),
+            Clx::Level(
+                "sPicturePlanes",
+                vec![
+                    Clx::U32("uiCount", 2),
+                    Clx::Level("sPlaneNew", vec![plane("a0", "DAPI"), plane("a1", "GFP")]),
+                ],
+            ),
+        ],
+    );
+    let mut builder = Nd2Builder::new(4, 3, 2, 2);
+    builder.extra_chunks = vec![(b"ImageMetadataSeqLV|0!".to_vec(), picture.encode())];
+    let src = common::temp_path("patch_src.nd2");
+    let dst = common::temp_path("patch_dst.nd2");
+    std::fs::write(&src, builder.build())?;
+
+    let patch = MetadataPatch {
+        text_info: TextInfo {
+            author: Some("J. Doe".to_string()),
+            ..TextInfo::default()
+        },
+        channel_names: [(1, "mCherry".to_string())].into(),
+        calibration_um: Some(0.325),
+        objective_name: Some("Plan Apo 20x".to_string()),
+        objective_magnification: Some(20.0),
+        objective_numerical_aperture: Some(0.75),
+    };
+    assert!(nd2_rs::apply_metadata_patch(&src, &src, &patch)
+        .unwrap_err()
+        .is_input());
+    nd2_rs::apply_metadata_patch(&src, &dst, &patch)?;
+
+    let mut nd2 = Nd2File::open(&dst)?;
+    // The file had no text info chunk; one is added.
+    assert_eq!(nd2.text_info()?.author.as_deref(), Some("J. Doe"));
+    assert_eq!(nd2.read_frame(1)?, Nd2File::open(&src)?.read_frame(1)?);
+    let clx = ClxLiteParser::new(false).parse(&nd2.read_chunk("ImageMetadataSeqLV|0!")?)?;
+    let picture = clx.as_object().unwrap()["SLxPictureMetadata"]
+        .as_object()
+        .unwrap();
+    assert_eq!(picture["dCalibration"].as_f64(), Some(0.325));
+    assert_eq!(picture["bCalibrated"], nd2_rs::sansio::ClxValue::Bool(true));
+    let objective = picture["sObjective"].as_object().unwrap();
+    assert_eq!(objective["wsObjectiveName"].as_str(), Some("Plan Apo 20x"));
+    assert_eq!(objective["dObjectiveMag"].as_f64(), Some(20.0));
+    assert_eq!(objective["dObjectiveNA"].as_f64(), Some(0.75));
+    let planes = picture["sPicturePlanes"].as_object().unwrap()["sPlaneNew"]
+        .as_object()
+        .unwrap();
+    let name = |key: &str| planes[key].as_object().unwrap()["sDescription"].clone();
+    assert_eq!(name("a0").as_str(), Some("DAPI"));
+    assert_eq!(name("a1").as_str(), Some("mCherry"));
+
+    let out_of_range = MetadataPatch {
+        channel_names: [(2, "Cy5".to_string())].into(),
+        ..MetadataPatch::default()
+    };
+    let err = nd2_rs::apply_metadata_patch(&src, &dst, &out_of_range).unwrap_err();
+    assert!(err.is_input(), "{err:?}");
+    // Overrides without an entry to replace are reported.
+    std::fs::write(&src, Nd2Builder::new(4, 3, 1, 1).build())?;
+    let calibration = MetadataPatch {
+        calibration_um: Some(0.5),
+        ..MetadataPatch::default()
+    };
+    let err = nd2_rs::apply_metadata_patch(&src, &dst, &calibration).unwrap_err();
+    assert!(err.is_file(), "{err:?}");
+
+    std::fs::remove_file(&src)?;
+    std::fs::remove_file(&dst)?;
+    Ok(())
+}
+
+#[test]
+fn test_synthetic_companion_export() -> Result<()> {
+    let point = |key: &'static str, name: &str, x: f64| {
+        Clx::Level(
+            key,
+            vec![
+                Clx::Str("dPosName", name.to_string()),
+                Clx::F64("dPosX", x),
+                Clx::F64("dPosY", -x),
+            ],
+        )
+    };
+    // Time outermost, then two positions.
+    let mut builder = Nd2Builder::new(4, 3, 1, 4);
+    builder.experiment = Some(Clx::Level(
+        "SLxExperiment",
+        vec![
+            Clx::U32("eType", 1),
+            Clx::Level("uLoopPars", vec![Clx::U32("uiCount", 2)]),
+            Clx::Level(
+                "ppNextLevelEx",
+                vec![Clx::Level(
+                    "i0000000000",
+                    vec![
+                        Clx::U32("eType", 2),
+                        Clx::Level(
+                            "uLoopPars",
+                            vec![
+                                Clx::U32("uiCount", 2),
+                                Clx::Level(
+                                    "Points",
+                                    vec![
+                                        point("i0000000000", "A1", 100.0),
+                                        point("i0000000001", "B1, \"edge\"", 250.5),
+                                    ],
+                                ),
+                            ],
+                        ),
+                    ],
+                )],
+            ),
+        ],
+    ));
+    let floats =
+        |values: &[f64]| -> Vec<u8> { values.iter().flat_map(|v| v.to_le_bytes()).collect() };
+    let event = Clx::Level(
+        "ExperimentEventsV1_0",
+        vec![Clx::Level(
+            "pEvents",
+            vec![Clx::Level(
+                "i0000000000",
+                vec![
+                    Clx::F64("dTime", 150.0),
+                    Clx::U32("uiMeaning", 1),
+                    Clx::Str("wsDescription", "Stimulation, ROI 1".to_string()),
+                ],
+            )],
+        )],
+    );
+    builder.extra_chunks = vec![
+        (
+            b"CustomData|X!".to_vec(),
+            floats(&[100.5, 250.5, 101.0, 251.0]),
+        ),
+        (
+            b"CustomData|Y!".to_vec(),
+            floats(&[-100.0, -250.0, -100.0, -250.0]),
+        ),
+        (b"CustomData|Z!".to_vec(), floats(&[10.0, 20.0, 10.5, 20.5])),
+        (
+            b"CustomDataVar|ExperimentEventsV1_0!".to_vec(),
+            event.encode(),
+        ),
+    ];
+    let mut nd2 = common::open(&builder);
+    let dir = common::temp_path("companion");
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join("plate.companion.ome");
+
+    let exporter = CompanionExporter::new(&path).sidecars(SidecarFormat::Csv);
+    exporter.export(&mut nd2)?;
+    let xml = std::fs::read_to_string(&path)?;
+    assert!(xml.contains("<M K=\"SourceFile\">plate.nd2</M>"));
+    assert_eq!(
+        xml.matches("<AnnotationRef ID=\"Annotation:SourceFile\"/>")
+            .count(),
+        2
+    );
+    assert!(xml.contains("<MetadataOnly/>"));
+    common::validate_ome_xml(&xml);
+
+    let positions = std::fs::read_to_string(dir.join("plate.positions.csv"))?;
+    assert_eq!(
+        positions,
+        "schema_version,index,name,x_um,y_um,z_um,pfs_offset\n\
+         1,0,A1,100,-100,0,\n\
+         1,1,\"B1, \"\"edge\"\"\",250.5,-250.5,0,\n"
+    );
+    // Frames carry their recorded stage positions, not the planned points.
+    let frames = std::fs::read_to_string(exporter.sidecar_path("frames", SidecarFormat::Csv)?)?;
+    let lines: Vec<&str> = frames.lines().collect();
+    assert_eq!(
+        lines[0],
+        "schema_version,index,P,T,C,Z,time_ms,x_um,y_um,z_um"
+    );
+    assert_eq!(lines.len(), 5);
+    assert_eq!(lines[4], "1,3,1,1,0,0,300,251,-250,20.5");
+    let events = std::fs::read_to_string(dir.join("plate.events.csv"))?;
+    assert_eq!(
+        events,
+        "schema_version,id,time_ms,kind,description,seq_index\n\
+         1,0,150,Stimulation,\"Stimulation, ROI 1\",1\n"
+    );
+
+    #[cfg(feature = "json")]
+    {
+        CompanionExporter::new(&path)
+            .source("renamed.nd2")
+            .sidecars(SidecarFormat::Json)
+            .export(&mut nd2)?;
+        assert!(std::fs::read_to_string(&path)?.contains(">renamed.nd2</M>"));
+        let positions = std::fs::read_to_string(dir.join("plate.positions.json"))?;
+        assert!(positions.starts_with(
+            "{\n  \"schema_version\": 1,\n  \"rows\": [\n    {\n      \"index\": 0,\n      \
+             \"name\": \"A1\",\n      \"x_um\": 100.0,\n      \"y_um\": -100.0,\n      \
+             \"z_um\": 0.0,\n      \"pfs_offset\": null\n    },"
+        ));
+        assert!(positions.contains("\"name\": \"B1, \\\"edge\\\"\""));
+        let frames = std::fs::read_to_string(dir.join("plate.frames.json"))?;
+        assert!(frames.contains("\"P\": 1,\n      \"T\": 1,"));
+        assert!(frames.contains("\"time_ms\": 300.0,\n      \"x_um\": 251.0,"));
+        let events = std::fs::read_to_string(dir.join("plate.events.json"))?;
+        assert!(events.contains(
+            "\"kind\": \"Stimulation\",\n      \"description\": \"Stimulation, ROI 1\","
+        ));
+    }
+
+    std::fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+#[test]
+fn test_synthetic_chunk_listing() -> Result<()> {
+    let mut builder = Nd2Builder::new(4, 3, 1, 2);
+    builder.extra_chunks = vec![(b"CustomData|a,b!".to_vec(), vec![0; 8])];
+    let mut nd2 = common::open(&builder);
+
+    let listing = nd2.chunk_listing();
+    assert_eq!(listing.schema_version, ChunkListing::SCHEMA_VERSION);
+    let names: Vec<&str> = listing.chunks.iter().map(|c| c.name.as_str()).collect();
+    assert_eq!(names, nd2.chunk_names());
+    let frame = listing
+        .chunks
+        .iter()
+        .find(|c| c.name == "ImageDataSeq|1!")
+        .expect("frame chunk");
+    assert_eq!(frame.offset, nd2.frames()?[1].offset());
+
+    let csv = listing.to_csv();
+    let mut lines = csv.lines();
+    assert_eq!(lines.next(), Some("schema_version,name,offset,size"));
+    assert!(csv.contains("\n1,\"CustomData|a,b!\","));
+    assert_eq!(lines.count(), listing.chunks.len());
+    Ok(())
+}
+
+#[test]
+fn test_synthetic_rois() -> Result<()> {
+    let keyframe = |key: &'static str, time: f64, x: f64| {
+        Clx::Level(
+            key,
+            vec![
+                Clx::F64("m_dTimeMs", time),
+                Clx::Bool("m_bEnabled", true),
+                Clx::F64("m_dCenterX", x),
+                Clx::F64("m_dCenterY", -0.25),
+                Clx::Level(
+                    "m_sBoxShape",
+                    vec![Clx::F64("m_dSizeX", 0.1), Clx::F64("m_dSizeY", 0.2)],
+                ),
+            ],
+        )
+    };
+    let rois = Clx::Level(
+        "RoiMetadata_v1",
+        vec![
+            Clx::U32("Global_Size", 2),
+            Clx::Level(
+                "0",
+                vec![
+                    Clx::U32("Id", 2),
+                    Clx::Str("GUID", "{b}".to_string()),
+                    Clx::Level(
+                        "Info",
+                        vec![
+                            Clx::U32("ShapeType", 9),
+                            Clx::U32("InterpType", 2),
+                            Clx::Str("Label", "bg".to_string()),
+                        ],
+                    ),
+                    Clx::U32("AnimParams_Size", 1),
+                    Clx::Level(
+                        "AnimParams_0",
+                        vec![Clx::Level(
+                            "ExtrudedShape",
+                            vec![
+                                Clx::F64("SizeZ", 1.5),
+                                Clx::U32("BasePoints_Size", 2),
+                                Clx::Level(
+                                    "BasePoints_0",
+                                    vec![Clx::F64("", 0.0), Clx::F64("", 0.5)],
+                                ),
+                                Clx::Level(
+                                    "BasePoints_1",
+                                    vec![Clx::F64("", 1.0), Clx::F64("", -0.5)],
+                                ),
+                            ],
+                        )],
+                    ),
+                ],
+            ),
+            Clx::Level(
+                "1",
+                vec![
+                    Clx::U32("Id", 1),
+                    Clx::Level(
+                        "Info",
+                        vec![
+                            Clx::U32("ShapeType", 6),
+                            Clx::U32("InterpType", 4),
+                            Clx::U32("StimulationGroup", 3),
+                            Clx::Bool("MultiFrame", true),
+                        ],
+                    ),
+                    Clx::U32("AnimParams_Size", 2),
+                    keyframe("AnimParams_0", 0.0, 0.1),
+                    keyframe("AnimParams_1", 500.0, 0.3),
+                ],
+            ),
+        ],
+    );
+    let mut builder = Nd2Builder::new(4, 3, 1, 2);
+    builder.extra_chunks = vec![(b"CustomData|RoiMetadata_v1!".to_vec(), rois.encode())];
+    let mut nd2 = common::open(&builder);
+
+    let rois = nd2.rois()?;
+    assert_eq!(rois.iter().map(|r| r.id).collect::<Vec<_>>(), [1, 2]);
+    let stim = &rois[0];
+    assert!(stim.is_stimulation() && !stim.is_background());
+    assert_eq!(stim.info.shape_type, RoiShapeType::Rectangle);
+    assert_eq!(stim.info.stimulation_group, 3);
+    assert!(stim.info.multi_frame);
+    let times: Vec<f64> = stim.anim_params.iter().map(|p| p.time_ms).collect();
+    assert_eq!(times, [0.0, 500.0]);
+    assert_eq!(stim.anim_params[1].center_x, 0.3);
+    assert_eq!(stim.anim_params[1].box_shape.size_y, 0.2);
+    let background = &rois[1];
+    assert!(background.is_background());
+    assert_eq!(background.info.shape_type, RoiShapeType::Polygon);
+    assert_eq!(background.info.label, "bg");
+    assert_eq!(background.guid, "{b}");
+    let shape = &background.anim_params[0].extruded_shape;
+    assert_eq!(shape.size_z, 1.5);
+    assert_eq!(shape.base_points, [(0.0, 0.5), (1.0, -0.5)]);
+
+    assert!(common::open(&Nd2Builder::new(4, 3, 1, 2))
+        .rois()?
+        .is_empty());
+    Ok(())
+}
+
+#[test]
+fn test_synthetic_binary_layers() -> Result<()> {
+    let layer = |key: &'static str, id: u32, name: &str, tag: &str| {
+        Clx::Level(
+            key,
+            vec![
+                Clx::U32("BinLayerID", id),
+                Clx::Str("Name", name.to_string()),
+                Clx::Str("CompName", "DAPI".to_string()),
+                Clx::U32("Color", 0x0000_00FF),
+                Clx::Str("FileTag", tag.to_string()),
+            ],
+        )
+    };
+    let layers = Clx::Level(
+        "BinaryMetadata_v1",
+        vec![
+            layer("i0000000000", 1, "nuclei", "RleZipBinarySequence_1_v1"),
+            layer("i0000000001", 2, "cells", "RleZipBinarySequence_2_v1"),
+        ],
+    );
+    let mask = |words: &[u32]| {
+        let words: Vec<u8> = words.iter().flat_map(|w| w.to_le_bytes()).collect();
+        let mut data = vec![0; 4];
+        data.extend(common::zlib_stored(&words));
+        data
+    };
+    let mut builder = Nd2Builder::new(4, 3, 1, 3);
+    builder.extra_chunks = vec![
+        (
+            b"CustomDataVar|BinaryMetadata_v1!".to_vec(),
+            layers.encode(),
+        ),
+        (
+            b"CustomDataSeq|RleZipBinarySequence_1_v1|0!".to_vec(),
+            mask(&[4, 3, 2, 3, 1, 4]),
+        ),
+        (
+            b"CustomDataSeq|RleZipBinarySequence_1_v1|2!".to_vec(),
+            vec![0; 4],
+        ),
+        (
+            b"CustomDataSeq|RleZipBinarySequence_2_v1|0!".to_vec(),
+            mask(&[3, 4, 1]),
+        ),
+    ];
+    let mut nd2 = common::open(&builder);
+
+    let layers = nd2.binary_layers()?;
+    let names: Vec<&str> = layers.iter().map(|l| l.name.as_str()).collect();
+    assert_eq!(names, ["nuclei", "cells"]);
+    assert_eq!(layers[0].color.as_hex(), "#ff0000");
+    assert_eq!(layers[0].comp_name, "DAPI");
+    let set: Vec<usize> = nd2
+        .read_binary_mask(0, 0)?
+        .unwrap()
+        .iter()
+        .enumerate()
+        .filter_map(|(i, &set)| set.then_some(i))
+        .collect();
+    assert_eq!(set, [2, 3, 4, 6, 7, 8, 9]);
+    assert_eq!(nd2.read_binary_mask(0, 1)?, None);
+    assert_eq!(nd2.read_binary_mask(0, 2)?, None);
+    // A mask the size of another frame.
+    assert!(nd2.read_binary_mask(1, 0).unwrap_err().is_file());
+    assert!(nd2.read_binary_mask(2, 0).unwrap_err().is_input());
+    assert!(nd2.read_binary_mask(0, 3).unwrap_err().is_input());
+
+    assert!(common::open(&Nd2Builder::new(4, 3, 1, 1))
+        .binary_layers()?
+        .is_empty());
+    Ok(())
+}
+
+#[test]
+fn test_synthetic_events() -> Result<()> {
+    let event = |key: &'static str, time: f64, meaning: u32, text: &str| {
+        Clx::Level(
+            key,
+            vec![
+                Clx::F64("dTime", time),
+                Clx::U32("uiMeaning", meaning),
+                Clx::Str("wsDescription", text.to_string()),
+            ],
+        )
+    };
+    let events = Clx::Level(
+        "ExperimentEventsV1_0",
+        vec![Clx::Level(
+            "pEvents",
+            vec![
+                event("i0000000000", 150.0, 1, "Stimulation ROI 1"),
+                event("i0000000001", -20.0, 2, "drug added"),
+                event("i0000000002", 250.0, 9, ""),
+            ],
+        )],
+    );
+    // Frames are acquired at 0, 100 and 200 ms.
+    let mut builder = Nd2Builder::new(4, 3, 1, 3);
+    builder.extra_chunks = vec![(
+        b"CustomDataVar|ExperimentEventsV1_0!".to_vec(),
+        events.encode(),
+    )];
+    let mut nd2 = common::open(&builder);
+
+    let events = nd2.events()?;
+    let times: Vec<f64> = events.iter().map(|e| e.time_ms).collect();
+    assert_eq!(times, [-20.0, 150.0, 250.0]);
+    assert_eq!(events[0].kind, EventKind::UserNote);
+    assert_eq!(events[0].description, "drug added");
+    assert_eq!(events[0].seq_index, None);
+    assert!(events[0].loop_indices.is_empty());
+    let stimulation = &events[1];
+    assert_eq!(
+        (stimulation.id, stimulation.kind),
+        (0, EventKind::Stimulation)
+    );
+    assert_eq!(stimulation.seq_index, Some(1));
+    assert_eq!(stimulation.loop_indices.get("T"), Some(&1));
+    assert_eq!(events[2].kind, EventKind::Other(9));
+    assert_eq!(events[2].seq_index, Some(2));
+
+    assert!(common::open(&Nd2Builder::new(4, 3, 1, 2))
+        .events()?
+        .is_empty());
+    Ok(())
+}
+
+#[test]
+fn test_synthetic_recorded_data() -> Result<()> {
+    let tag = |key: &'static str, id: &str, kind: u32, size: u32, desc: &str, unit: &str| {
+        Clx::Level(
+            key,
+            vec![
+                Clx::Str("ID", id.to_string()),
+                Clx::U32("Type", kind),
+                Clx::U32("Size", size),
+                Clx::Str("Desc", desc.to_string()),
+                Clx::Str("Unit", unit.to_string()),
+            ],
+        )
+    };
+    let tags = Clx::Level(
+        "CustomTagDescription_v1.0",
+        vec![
+            tag("Tag0", "Temp", 3, 3, "Temperature", "°C"),
+            tag("Tag1", "PFS_STATUS", 2, 3, "PFS Status", ""),
+            tag("Tag2", "Note", 1, 2, "Note", ""),
+            tag("Tag3", "Short", 3, 3, "Truncated", ""),
+            tag("Tag4", "Absent", 3, 3, "Absent", ""),
+        ],
+    );
+    let floats =
+        |values: &[f64]| -> Vec<u8> { values.iter().flat_map(|v| v.to_le_bytes()).collect() };
+    // Two UTF-16 units per note, NUL-padded.
+    let notes: Vec<u8> = "okg\0on"
+        .encode_utf16()
+        .flat_map(|unit| unit.to_le_bytes())
+        .collect();
+    let mut builder = Nd2Builder::new(4, 3, 1, 3);
+    builder.extra_chunks = vec![
+        (b"CustomDataVar|CustomDataV2_0!".to_vec(), tags.encode()),
+        (
+            b"CustomData|Temp!".to_vec(),
+            floats(&[36.5, 36.75, 37.0, 0.0]),
+        ),
+        (
+            b"CustomData|PFS_STATUS!".to_vec(),
+            [5i32, 5, -1].iter().flat_map(|v| v.to_le_bytes()).collect(),
+        ),
+        (b"CustomData|Note!".to_vec(), notes),
+        (b"CustomData|Short!".to_vec(), floats(&[1.0])),
+    ];
+    let mut nd2 = common::open(&builder);
+
+    let data = nd2.recorded_data()?;
+    let mut names: Vec<&str> = data.keys().map(String::as_str).collect();
+    names.sort_unstable();
+    assert_eq!(
+        names,
+        ["Note", "PFS Status", "Temperature [°C]", "Time [s]"]
+    );
+    let column =
+        |name: &str| -> Vec<Option<f64>> { data[name].iter().map(RecordedValue::as_f64).collect() };
+    assert_eq!(column("Time [s]"), [Some(0.0), Some(0.1), Some(0.2)]);
+    assert_eq!(
+        column("Temperature [°C]"),
+        [Some(36.5), Some(36.75), Some(37.0)]
+    );
+    assert_eq!(
+        data["PFS Status"],
+        [
+            RecordedValue::Int(5),
+            RecordedValue::Int(5),
+            RecordedValue::Int(-1)
+        ]
+    );
+
+    let notes: Vec<_> = data["Note"].iter().map(|v| v.as_str().unwrap()).collect();
+    assert_eq!(notes, ["ok", "g", "on"]);
+
+    // The data frame holds the same values, after the sequence index.
+    #[cfg(feature = "polars")]
+    {
+        let frame = nd2.recorded_data_dataframe()?;
+        assert_eq!(
+            frame.get_column_names(),
+            [
+                "Frame",
+                "Time [s]",
+                "Note",
+                "PFS Status",
+                "Temperature [°C]"
+            ]
+        );
+        for (name, values) in &data {
+            let column = frame.column(name).unwrap();
+            match values[0] {
+                RecordedValue::Int(_) => assert!(column.i64().unwrap().into_iter().eq(values
+                    .iter()
+                    .map(|v| match *v {
+                        RecordedValue::Int(i) => Some(i),
+                        _ => None,
+                    }))),
+                RecordedValue::Text(_) => assert!(column
+                    .str()
+                    .unwrap()
+                    .into_iter()
+                    .eq(values.iter().map(RecordedValue::as_str))),
+                RecordedValue::Float(_) => assert!(column
+                    .f64()
+                    .unwrap()
+                    .into_iter()
+                    .eq(values.iter().map(RecordedValue::as_f64))),
+            }
+        }
+    }
+
+    let data = common::open(&Nd2Builder::new(4, 3, 1, 2)).recorded_data()?;
+    assert_eq!(data.len(), 1);
+    Ok(())
+}
+
+#[test]
+fn test_synthetic_unstructured_metadata() -> Result<()> {
+    use nd2_rs::sansio::ClxValue;
+
+    let app_info = Clx::Level(
+        "AppInfo_V1_0",
+        vec![Clx::Str("SWNameString", "NIS-Elements AR".to_string())],
+    );
+    let broken = Clx::Str("sValue", "abc".to_string()).encode();
+    let mut builder = Nd2Builder::new(4, 3, 1, 2);
+    builder.extra_chunks = vec![
+        (b"CustomDataVar|AppInfo_V1_0!".to_vec(), app_info.encode()),
+        (
+            b"CustomDataVar|Broken!".to_vec(),
+            broken[..broken.len() - 2].to_vec(),
+        ),
+        (b"CustomData|AcqTimesCache!".to_vec(), vec![0; 16]),
+    ];
+    let mut nd2 = common::open(&builder);
+
+    let metadata = nd2.unstructured_metadata()?;
+    let mut names: Vec<&str> = metadata.keys().map(String::as_str).collect();
+    names.sort_unstable();
+    assert_eq!(names, ["CustomDataVar|AppInfo_V1_0", "ImageAttributesLV"]);
+    let name = metadata["CustomDataVar|AppInfo_V1_0"]
+        .as_object()
+        .and_then(|o| o.get("AppInfo_V1_0"))
+        .and_then(ClxValue::as_object)
+        .and_then(|o| o.get("SWNameString"))
+        .and_then(ClxValue::as_str);
+    assert_eq!(name, Some("NIS-Elements AR"));
+    let attributes = metadata["ImageAttributesLV"]
+        .as_object()
+        .and_then(|o| o.get("SLxImageAttributes"))
+        .and_then(ClxValue::as_object)
+        .expect("attributes level");
+    assert_eq!(attributes.get("uiWidth"), Some(&ClxValue::UInt(4)));
+    assert!(nd2
+        .diagnostics()
+        .iter()
+        .any(|d| d.kind == DiagnosticKind::MetadataFallback
+            && d.message.contains("CustomDataVar|Broken")));
+    Ok(())
+}
+
+#[test]
+fn test_synthetic_picture_metadata() -> Result<()> {
+    let point = |key: &'static str, nm: f64, t: f64| {
+        Clx::Level(
+            key,
+            vec![Clx::F64("dWavelength", nm), Clx::F64("dTValue", t)],
+        )
+    };
+    let plane = |key: &'static str, name: &str, color: u32, peak: f64| {
+        Clx::Level(
+            key,
+            vec![
+                Clx::Str("sDescription", name.to_string()),
+                Clx::U32("uiColor", color),
+                Clx::U32("uiSampleIndex", 0),
+                Clx::Level(
+                    "pEmissionSpectrum",
+                    vec![Clx::Level(
+                        "pPoint",
+                        vec![
+                            point("Point0", peak - 20.0, 0.2),
+                            point("Point1", peak, 1.0),
+                            point("Point2", peak + 20.0, 0.5),
+                        ],
+                    )],
+                ),
+            ],
+        )
+    };
+    let picture = Clx::Level(
+        "SLxPictureMetadata",
+        vec![
+            Clx::F64("dCalibration", 0.325),
+            Clx::Bool("bCalibrated", true),
+            Clx::Level(
+                "sSampleSetting",
+                vec![Clx::Level(
+                    "a0",
+                    vec![Clx::Level(
+                        "pObjectiveSetting",
+                        vec![
+                            Clx::Str("wsObjectiveName", "Plan Apo 20x".to_string()),
+                            Clx::F64("dObjectiveMag", 20.0),
+                            Clx::F64("dObjectiveNA", 0.75),
+                            Clx::F64("dRefractIndex1", 1.0),
+                        ],
+                    )],
+                )],
+            ),
+            Clx::Level(
+                "sPicturePlanes",
+                vec![
+                    Clx::U32("uiCount", 2),
+                    Clx::Level(
+                        "sPlaneNew",
+                        vec![
+                            plane("a1", "GFP", 0x0000_FF00, 510.0),
+                            plane("a0", "DAPI", 0x00FF_0000, 460.0),
+                        ],
+                    ),
+                ],
+            ),
+        ],
+    );
+    let mut builder = Nd2Builder::new(4, 3, 2, 3);
+    builder.experiment = Some(Clx::Level(
+        "SLxExperiment",
+        vec![
+            Clx::U32("eType", 4),
+            Clx::Level(
+                "uLoopPars",
+                vec![Clx::U32("uiCount", 3), Clx::F64("dZStep", 0.5)],
+            ),
+        ],
+    ));
+    builder.extra_chunks = vec![(b"ImageMetadataSeqLV|0!".to_vec(), picture.encode())];
+    let mut nd2 = common::open(&builder);
+
+    let metadata = nd2.metadata()?.clone();
+    assert_eq!(metadata.contents.as_ref().unwrap().channel_count, 2);
+    let channels = metadata.channels.unwrap();
+    let names: Vec<&str> = channels.iter().map(|c| c.channel.name.as_str()).collect();
+    assert_eq!(names, ["DAPI", "GFP"]);
+    assert_eq!(channels[0].channel.color.as_hex(), "#0000ff");
+    assert_eq!(channels[1].channel.color.as_hex(), "#00ff00");
+    assert_eq!(channels[1].channel.emission_lambda_nm, Some(510.0));
+    assert_eq!(channels[1].channel.excitation_lambda_nm, None);
+    let microscope = &channels[0].microscope;
+    assert_eq!(microscope.objective_name.as_deref(), Some("Plan Apo 20x"));
+    assert_eq!(microscope.objective_magnification, Some(20.0));
+    assert_eq!(microscope.objective_numerical_aperture, Some(0.75));
+    assert_eq!(microscope.immersion_refractive_index, Some(1.0));
+    assert_eq!(microscope.zoom_magnification, None);
+    let volume = &channels[0].volume;
+    assert_eq!(volume.axes_calibration, (0.325, 0.325, 0.5));
+    assert_eq!(volume.axes_calibrated, (true, true, true));
+    assert_eq!(volume.voxel_count, (4, 3, 3));
+    assert_eq!(channels[0].loops.as_ref().unwrap().z_stack_loop, Some(0));
+    assert_eq!(
+        nd2.voxel_size()?,
+        VoxelSize {
+            x_um: 0.325,
+            y_um: 0.325,
+            z_um: 0.5
+        }
+    );
+
+    // Without the chunk, only the contents are known.
+    let mut bare = common::open(&Nd2Builder::new(4, 3, 2, 1));
+    let metadata = bare.metadata()?;
+    assert!(metadata.channels.is_none());
+    assert_eq!(metadata.contents.as_ref().unwrap().channel_count, 2);
+    let unit = bare.voxel_size()?;
+    assert_eq!((unit.x_um, unit.y_um, unit.z_um), (1.0, 1.0, 1.0));
+    Ok(())
+}
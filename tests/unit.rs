@@ -1,6 +1,6 @@
 //! Unit tests that do not require an ND2 file.
 
-use nd2_rs::Nd2File;
+use nd2_rs::{Affine2, Nd2File};
 use std::io::Write;
 
 #[test]
@@ -25,3 +25,55 @@ fn test_open_invalid_file_fails() {
     );
     let _ = std::fs::remove_file(&tmp);
 }
+
+#[test]
+fn test_open_with_options_nonexistent_fails() {
+    let options = nd2_rs::Nd2Options::new().buffer_capacity(64 * 1024);
+    assert!(Nd2File::open_with("nonexistent_file_xyz.nd2", options).is_err());
+    assert!(Nd2File::try_from(std::path::Path::new("nonexistent_file_xyz.nd2")).is_err());
+}
+
+#[test]
+fn test_open_file_handle_invalid_fails() {
+    let tmp = std::env::temp_dir().join("nd2_rs_test_garbage_handle.nd2");
+    let mut f = std::fs::File::create(&tmp).unwrap();
+    f.write_all(&[0u8; 200]).unwrap();
+    drop(f);
+    let file = std::fs::File::open(&tmp).unwrap();
+    let err = match Nd2File::try_from(file) {
+        Ok(_) => unreachable!("garbage file should not open successfully"),
+        Err(err) => err,
+    };
+    assert!(err.is_file());
+    let _ = std::fs::remove_file(&tmp);
+}
+
+#[test]
+fn test_affine_compose_invert_apply() {
+    // NIS tuple layouts: camera (m00, m01, m10, m11), pixel-to-stage with
+    // the translation after each row.
+    let camera = Affine2::from((0.0, -1.0, 1.0, 0.0));
+    let to_stage = Affine2::from((0.5, 0.0, 100.0, 0.0, 0.5, -50.0));
+    assert_eq!(camera.apply([2.0, 3.0]), [-3.0, 2.0]);
+    assert_eq!(to_stage.apply([10.0, 20.0]), [105.0, -40.0]);
+
+    let combined = to_stage.compose(&camera);
+    assert_eq!(
+        combined.apply([2.0, 3.0]),
+        to_stage.apply(camera.apply([2.0, 3.0]))
+    );
+
+    let inverse = combined.invert().unwrap();
+    assert_eq!(inverse.apply(combined.apply([7.0, -4.0])), [7.0, -4.0]);
+    assert_eq!(inverse.compose(&combined), Affine2::IDENTITY);
+    assert!(Affine2::linear([[1.0, 2.0], [2.0, 4.0]]).invert().is_none());
+}
+
+#[cfg(feature = "nalgebra")]
+#[test]
+fn test_affine_nalgebra_round_trip() {
+    let t = Affine2::new([[0.5, 0.1], [-0.1, 0.5]], [12.0, -3.0]);
+    let na: nalgebra::Affine2<f64> = t.into();
+    assert_eq!(na.matrix()[(0, 2)], 12.0);
+    assert_eq!(Affine2::from(na), t);
+}